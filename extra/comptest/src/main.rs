@@ -1,17 +1,330 @@
 //! Compression test program
 //!
 //! This application is for testing purposes only and is not intended for practical use
+//!
+//! `comptest deflate|inflate|gzip|stk1 [-l LEVEL] [-d] [IN [OUT]]` compresses or decompresses a file,
+//! where `-` or a missing path means the standard input or output.
+//! `comptest inspect [IN]` describes the blocks of a deflate or zlib stream.
 
 use compress::{
-    deflate::{CompressionLevel, deflate, inflate},
+    bench::{self, Algorithm},
+    deflate::{self, CompressionLevel, InflateStatus, InflateStream, deflate, inflate},
     lz::lzss,
+    stk1,
+    zip::crc32::update as crc32,
+};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    process,
 };
-use std::{env, path::Path, process};
+
+/// Size of the chunks read from the input of the streaming decoders
+const CHUNK_SIZE: usize = 0x10000;
 
 fn main() {
     let mut args = env::args();
     let _ = args.next().unwrap();
 
+    let result = match args.next().as_deref() {
+        Some("deflate") => run_codec(Format::Deflate, false, args),
+        Some("inflate") => run_codec(Format::Deflate, true, args),
+        Some("gzip") => run_codec(Format::Gzip, false, args),
+        Some("stk1") => run_codec(Format::Stk1, false, args),
+        Some("inspect") => run_inspect(args),
+        Some("bench") => {
+            run_bench(args);
+            Ok(())
+        }
+        Some("synthetic") => {
+            run_synthetic();
+            Ok(())
+        }
+        _ => usage(),
+    };
+    if let Err(error) = result {
+        eprintln!("{error}");
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Raw deflate, or zlib when decompressing
+    Deflate,
+    Gzip,
+    /// The container of `stk1::compress`
+    Stk1,
+}
+
+/// Compresses or decompresses a file in the format.
+fn run_codec(format: Format, mut decompress: bool, mut args: env::Args) -> io::Result<()> {
+    let mut level = CompressionLevel::Default;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" => {
+                level = CompressionLevel::from_u8(parse_number(args.next()) as u8)
+                    .unwrap_or_else(|| usage())
+            }
+            "-d" => decompress = true,
+            _ if paths.len() < 2 && (arg == "-" || !arg.starts_with('-')) => paths.push(arg),
+            _ => usage(),
+        }
+    }
+    let input_path = paths.first().map_or("-", |v| v.as_str());
+    let output_path = paths.get(1).map_or("-", |v| v.as_str());
+
+    let mut reader: Box<dyn Read> = if input_path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(input_path)?))
+    };
+    let mut writer: Box<dyn Write> = if output_path == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(BufWriter::new(File::create(output_path)?))
+    };
+
+    if format == Format::Deflate && decompress {
+        // The only format that can be decoded without reading the whole input
+        inflate_stream(&mut reader, &mut writer, |_| {})?;
+        return writer.flush();
+    }
+
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let output = match (format, decompress) {
+        (Format::Deflate, _) => deflate(&input, level, None)?,
+        (Format::Gzip, false) => gzip_compress(&input, level)?,
+        (Format::Gzip, true) => {
+            let mut output = Vec::new();
+            gzip_decompress(&input, &mut output)?;
+            output
+        }
+        (Format::Stk1, false) => stk1::compress(&input, stk1::Configuration::DEFAULT)?,
+        (Format::Stk1, true) => stk1::decompress(&input)?,
+    };
+    writer.write_all(&output)?;
+    writer.flush()
+}
+
+/// Prints the blocks of a deflate or zlib stream and the bit sizes of their parts.
+fn run_inspect(mut args: env::Args) -> io::Result<()> {
+    let path = args.next().unwrap_or_else(|| String::from("-"));
+    if args.next().is_some() {
+        usage();
+    }
+    let mut input = Vec::new();
+    if path == "-" {
+        io::stdin().lock().read_to_end(&mut input)?;
+    } else {
+        File::open(path)?.read_to_end(&mut input)?;
+    }
+
+    let info = deflate::inspect(&input)?;
+    println!(
+        "{} stream: {} bytes -> {} bytes, {} blocks",
+        if info.is_zlib { "zlib" } else { "deflate" },
+        info.stream_len,
+        info.output_len,
+        info.blocks.len()
+    );
+    println!(
+        "{:>5} {:>10} {:>8} {:>9} {:>8} {:>8} {:>9} {:>10} {:>9}",
+        "block",
+        "bit offset",
+        "type",
+        "output",
+        "literals",
+        "matches",
+        "header",
+        "symbols",
+        "extra"
+    );
+    for (index, block) in info.blocks.iter().enumerate() {
+        let bits = &block.bits;
+        println!(
+            "{:>5} {:>10} {:>8} {:>9} {:>8} {:>8} {:>9} {:>10} {:>9}{}",
+            index,
+            block.bit_offset,
+            format!("{:?}", block.block_type),
+            block.output_len(),
+            block.literals,
+            block.matches,
+            bits.header + bits.tables,
+            bits.literals + bits.lengths + bits.distances + bits.end_of_block + bits.stored,
+            bits.length_extra + bits.distance_extra,
+            if block.is_final { " final" } else { "" },
+        );
+    }
+
+    let bits = info.total_bits();
+    let total = bits.total().max(1) as f64;
+    println!("bits:");
+    for (name, value) in [
+        ("header", bits.header),
+        ("tables", bits.tables),
+        ("literals", bits.literals),
+        ("lengths", bits.lengths),
+        ("length extra", bits.length_extra),
+        ("distances", bits.distances),
+        ("distance extra", bits.distance_extra),
+        ("end of block", bits.end_of_block),
+        ("stored", bits.stored),
+    ] {
+        println!(
+            "  {name:<14} {value:>12} {:>6.2}%",
+            value as f64 / total * 100.0
+        );
+    }
+    Ok(())
+}
+
+/// Decompresses a deflate or zlib stream from the reader, passing each chunk of the output to `f` as well.
+///
+/// Returns the number of bytes of the stream.
+fn inflate_stream<F>(reader: &mut dyn Read, writer: &mut dyn Write, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(&[u8]),
+{
+    let mut stream = InflateStream::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        match stream.poll()? {
+            InflateStatus::Output(chunk) => {
+                f(chunk);
+                writer.write_all(chunk)?;
+            }
+            InflateStatus::Done => return Ok(stream.total_in()),
+            InflateStatus::NeedsMoreInput => match reader.read(&mut buf)? {
+                0 => stream.finish_input(),
+                len => stream.push_input(&buf[..len]),
+            },
+        }
+    }
+}
+
+/// Compresses the input into a gzip member (RFC 1952) without the file name and the time.
+fn gzip_compress(input: &[u8], level: CompressionLevel) -> io::Result<Vec<u8>> {
+    let xfl = match level {
+        CompressionLevel::Best => 2,
+        CompressionLevel::Fastest => 4,
+        _ => 0,
+    };
+    // ID1, ID2, CM = deflate, FLG, MTIME, XFL, OS = unknown
+    let mut output = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, xfl, 0xff];
+    output.extend_from_slice(&deflate(input, level, None)?);
+    output.extend_from_slice(&crc32(0, input).to_le_bytes());
+    output.extend_from_slice(&(input.len() as u32).to_le_bytes());
+    Ok(output)
+}
+
+/// Decompresses concatenated gzip members, verifying the CRC-32 and the size of each member.
+fn gzip_decompress(mut input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let invalid_data = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+    while !input.is_empty() {
+        if input.len() < 10 || input[..3] != [0x1f, 0x8b, 8] {
+            return Err(invalid_data("not a gzip member"));
+        }
+        let flags = input[3];
+        let mut rest = &input[10..];
+        if flags & 0x04 != 0 {
+            // FEXTRA
+            let len = rest
+                .get(..2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]) as usize + 2)
+                .ok_or_else(|| invalid_data("truncated header"))?;
+            rest = rest
+                .get(len..)
+                .ok_or_else(|| invalid_data("truncated header"))?;
+        }
+        for flag in [0x08, 0x10] {
+            // FNAME and FCOMMENT end with a zero byte
+            if flags & flag != 0 {
+                let len = rest
+                    .iter()
+                    .position(|&v| v == 0)
+                    .ok_or_else(|| invalid_data("truncated header"))?;
+                rest = &rest[len + 1..];
+            }
+        }
+        if flags & 0x02 != 0 {
+            // FHCRC
+            rest = rest
+                .get(2..)
+                .ok_or_else(|| invalid_data("truncated header"))?;
+        }
+
+        let start = output.len();
+        let mut crc = 0;
+        let len = inflate_stream(&mut { rest }, output, |chunk| crc = crc32(crc, chunk))?;
+        let size = output.len() - start;
+        let trailer = rest
+            .get(len..len + 8)
+            .ok_or_else(|| invalid_data("truncated trailer"))?;
+        if trailer[..4] != crc.to_le_bytes() || trailer[4..] != (size as u32).to_le_bytes() {
+            return Err(invalid_data("checksum mismatch"));
+        }
+        input = &rest[len + 8..];
+    }
+    Ok(())
+}
+
+/// Runs the corpus benchmark and prints the report in JSON.
+fn run_bench(mut args: env::Args) {
+    let mut names = Vec::new();
+    let mut level = None;
+    let mut iterations = 3;
+    let mut dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" => names.push(args.next().unwrap_or_else(|| usage())),
+            "-l" => level = Some(parse_number(args.next()) as u8),
+            "-n" => iterations = parse_number(args.next()),
+            _ if dir.is_none() && !arg.starts_with('-') => dir = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(dir) = dir else { usage() };
+
+    let algorithms = if names.is_empty() {
+        match level {
+            Some(level) => Algorithm::all()
+                .into_iter()
+                .filter(|v| v.level().is_none_or(|v| v == level))
+                .collect(),
+            None => Algorithm::all(),
+        }
+    } else {
+        names
+            .iter()
+            .flat_map(|name| {
+                Algorithm::from_name(name, level).unwrap_or_else(|| {
+                    eprintln!("unknown algorithm: {name}");
+                    process::exit(1);
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match bench::run_corpus(Path::new(&dir), &algorithms, iterations) {
+        Ok(report) => println!("{}", report.to_json()),
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_number(arg: Option<String>) -> usize {
+    arg.and_then(|v| v.parse().ok()).unwrap_or_else(|| usage())
+}
+
+/// Runs the synthetic benchmark of deflate.
+fn run_synthetic() {
     const SRC_SIZE: usize = 0x10_0000;
     let times = 10;
 
@@ -108,13 +421,18 @@ fn main() {
     }
 }
 
-#[allow(unused)]
-fn usage() {
+fn usage() -> ! {
     let mut args = env::args_os();
     let arg = args.next().unwrap();
     let path = Path::new(&arg);
     let lpc = path.file_name().unwrap();
-    eprintln!("{} [OPTIONS] INFILE OUTFILE", lpc.to_str().unwrap());
+    let lpc = lpc.to_str().unwrap();
+    eprintln!("{lpc} deflate|inflate|gzip|stk1 [-l LEVEL] [-d] [IN [OUT]]");
+    eprintln!("{lpc} inspect [IN]");
+    eprintln!("{lpc} synthetic");
+    eprintln!("{lpc} bench [-a ALGORITHM]... [-l LEVEL] [-n ITERATIONS] DIR");
+    eprintln!();
+    eprintln!("ALGORITHM: deflate, stk1, lz4, snappy, bzip2, ppm (default: all)");
     process::exit(1);
 }
 
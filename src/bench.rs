@@ -0,0 +1,453 @@
+//! Benchmark of the compressors on a corpus
+//!
+//! Each file of a directory, such as the Calgary or Silesia corpus, is compressed and decompressed
+//! with each [`Algorithm`], and the ratio and the speed are reported in JSON,
+//! so that regressions of the ratio can be tracked between releases.
+//!
+//! ```no_run
+//! use compress::bench::{self, Algorithm};
+//!
+//! let report = bench::run_corpus("corpus/silesia".as_ref(), &Algorithm::all(), 3).unwrap();
+//! println!("{}", report.to_json());
+//! ```
+//!
+//! Every run verifies the round trip with [`testing::compare`].
+
+use crate::testing::{self, RoundtripFailure};
+use crate::*;
+use alloc::format;
+use alloc::string::String;
+use core::fmt::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Compressor and its level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Raw deflate
+    Deflate(deflate::CompressionLevel),
+    /// The container of [`stk1::compress`]
+    Stk1,
+    /// LZ4 frame
+    Lz4,
+    /// Snappy framing format
+    Snappy,
+    /// bzip2 with the block size level `1..=9`
+    Bzip2(u8),
+    /// Experimental PPM
+    Ppm,
+}
+
+impl Algorithm {
+    /// Returns every level of every algorithm.
+    pub fn all() -> Vec<Self> {
+        let mut algorithms = (0..=9)
+            .filter_map(deflate::CompressionLevel::from_u8)
+            .map(Self::Deflate)
+            .collect::<Vec<_>>();
+        algorithms.push(Self::Deflate(deflate::CompressionLevel::Auto));
+        algorithms.extend([Self::Stk1, Self::Lz4, Self::Snappy]);
+        algorithms.extend((1..=9).map(Self::Bzip2));
+        algorithms.push(Self::Ppm);
+        algorithms
+    }
+
+    /// Returns the algorithms of the name, such as `deflate`, or `None` if the name is unknown.
+    ///
+    /// If `level` is `None`, every level of the algorithm is returned.
+    /// The level is ignored for the algorithms without levels.
+    pub fn from_name(name: &str, level: Option<u8>) -> Option<Vec<Self>> {
+        let algorithms = Self::all()
+            .into_iter()
+            .filter(|v| v.name() == name)
+            .filter(|v| level.is_none() || v.level().is_none() || v.level() == level)
+            .collect::<Vec<_>>();
+        (!algorithms.is_empty()).then_some(algorithms)
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Deflate(_) => "deflate",
+            Self::Stk1 => "stk1",
+            Self::Lz4 => "lz4",
+            Self::Snappy => "snappy",
+            Self::Bzip2(_) => "bzip2",
+            Self::Ppm => "ppm",
+        }
+    }
+
+    /// Returns the level, or `None` if the algorithm has no levels.
+    ///
+    /// `CompressionLevel::Auto` of deflate is reported as `10`.
+    #[inline]
+    pub const fn level(&self) -> Option<u8> {
+        match self {
+            Self::Deflate(level) => Some(*level as u8),
+            Self::Bzip2(level) => Some(*level),
+            Self::Stk1 | Self::Lz4 | Self::Snappy | Self::Ppm => None,
+        }
+    }
+
+    pub fn compress(&self, input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        match *self {
+            Self::Deflate(level) => deflate::deflate(input, level, None),
+            Self::Stk1 => stk1::compress(input, stk1::Configuration::DEFAULT),
+            Self::Lz4 => lz4::compress(input, lz4::FrameOptions::new()),
+            Self::Snappy => snappy::compress(input),
+            Self::Bzip2(level) => bzip2::compress_with_level(input, level),
+            Self::Ppm => ppm::compress(input),
+        }
+    }
+
+    /// Decompresses the output of [`Self::compress`] of the input of `size` bytes.
+    pub fn decompress(&self, input: &[u8], size: usize) -> Result<Vec<u8>, DecodeError> {
+        match self {
+            Self::Deflate(_) => deflate::inflate(input, size),
+            Self::Stk1 => stk1::decompress_with_limit(input, size),
+            Self::Lz4 => lz4::decompress_with_limit(input, size),
+            Self::Snappy => snappy::decompress_with_limit(input, size),
+            Self::Bzip2(_) => bzip2::decompress_with_limit(input, size),
+            Self::Ppm => ppm::decompress_with_limit(input, size),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level() {
+            Some(level) => write!(f, "{}-{}", self.name(), level),
+            None => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Failure of a benchmark
+#[derive(Debug)]
+pub enum BenchError {
+    /// The corpus could not be read
+    Io(std::io::Error),
+    /// The round trip of the file with the algorithm failed
+    Roundtrip {
+        file: String,
+        algorithm: Algorithm,
+        failure: RoundtripFailure,
+    },
+}
+
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Roundtrip {
+                file,
+                algorithm,
+                failure,
+            } => write!(f, "{file} ({algorithm}): {failure}"),
+        }
+    }
+}
+
+impl std::error::Error for BenchError {}
+
+impl From<std::io::Error> for BenchError {
+    #[inline]
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Result of a file with an algorithm
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub file: String,
+    pub algorithm: Algorithm,
+    pub input_size: usize,
+    pub compressed_size: usize,
+    /// Shortest time of the iterations
+    pub compress_time: Duration,
+    /// Shortest time of the iterations
+    pub decompress_time: Duration,
+}
+
+impl BenchResult {
+    /// Returns the compressed size divided by the input size.
+    #[inline]
+    pub fn ratio(&self) -> f64 {
+        _ratio(self.compressed_size, self.input_size)
+    }
+
+    /// Returns the compression speed in MB/s of the input.
+    #[inline]
+    pub fn compress_speed(&self) -> f64 {
+        _speed(self.input_size, self.compress_time)
+    }
+
+    /// Returns the decompression speed in MB/s of the output.
+    #[inline]
+    pub fn decompress_speed(&self) -> f64 {
+        _speed(self.input_size, self.decompress_time)
+    }
+}
+
+/// Results of the corpus
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub results: Vec<BenchResult>,
+}
+
+impl Report {
+    /// Returns the sum of the results of each algorithm over the files, in the order of their first appearance.
+    pub fn totals(&self) -> Vec<BenchResult> {
+        let mut totals: Vec<BenchResult> = Vec::new();
+        for result in self.results.iter() {
+            match totals.iter_mut().find(|v| v.algorithm == result.algorithm) {
+                Some(total) => {
+                    total.input_size += result.input_size;
+                    total.compressed_size += result.compressed_size;
+                    total.compress_time += result.compress_time;
+                    total.decompress_time += result.decompress_time;
+                }
+                None => totals.push(BenchResult {
+                    file: String::from("*"),
+                    ..result.clone()
+                }),
+            }
+        }
+        totals
+    }
+
+    /// Returns the results and the totals in JSON.
+    ///
+    /// ```json
+    /// {"results":[{"file":"...","algorithm":"deflate","level":6,"input_size":0,"compressed_size":0,
+    /// "ratio":0.0,"compress_mb_s":0.0,"decompress_mb_s":0.0},...],"totals":[...]}
+    /// ```
+    ///
+    /// The level is `null` for the algorithms without levels, and the file of the totals is `*`.
+    pub fn to_json(&self) -> String {
+        let mut output = String::new();
+        output.push_str("{\"results\":[");
+        _write_json_list(&mut output, &self.results);
+        output.push_str("],\"totals\":[");
+        _write_json_list(&mut output, &self.totals());
+        output.push_str("]}");
+        output
+    }
+}
+
+/// Compresses and decompresses the input `iterations` times, and returns the shortest times.
+pub fn run(
+    file: &str,
+    input: &[u8],
+    algorithm: Algorithm,
+    iterations: usize,
+) -> Result<BenchResult, BenchError> {
+    let roundtrip_error = |failure: RoundtripFailure| BenchError::Roundtrip {
+        file: String::from(file),
+        algorithm,
+        failure,
+    };
+    let mut compress_time = Duration::MAX;
+    let mut decompress_time = Duration::MAX;
+    let mut compressed = Vec::new();
+    for _ in 0..iterations.max(1) {
+        let time0 = Instant::now();
+        compressed = algorithm
+            .compress(input)
+            .map_err(|error| roundtrip_error(error.into()))?;
+        compress_time = compress_time.min(time0.elapsed());
+
+        let time0 = Instant::now();
+        let decoded = algorithm
+            .decompress(&compressed, input.len())
+            .map_err(|error| roundtrip_error(error.into()))?;
+        decompress_time = decompress_time.min(time0.elapsed());
+        testing::compare(input, &decoded, None).map_err(roundtrip_error)?;
+    }
+    Ok(BenchResult {
+        file: String::from(file),
+        algorithm,
+        input_size: input.len(),
+        compressed_size: compressed.len(),
+        compress_time,
+        decompress_time,
+    })
+}
+
+/// Runs the algorithms on each file of the directory in the order of their names.
+///
+/// Subdirectories are not searched.
+pub fn run_corpus(
+    dir: &Path,
+    algorithms: &[Algorithm],
+    iterations: usize,
+) -> Result<Report, BenchError> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|v| v.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|v| v.is_file());
+    paths.sort();
+
+    let mut report = Report::default();
+    for path in paths {
+        let input = std::fs::read(&path)?;
+        let file = path
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        for &algorithm in algorithms {
+            report
+                .results
+                .push(run(&file, &input, algorithm, iterations)?);
+        }
+    }
+    Ok(report)
+}
+
+#[inline]
+fn _ratio(compressed_size: usize, input_size: usize) -> f64 {
+    if input_size == 0 {
+        0.0
+    } else {
+        compressed_size as f64 / input_size as f64
+    }
+}
+
+#[inline]
+fn _speed(size: usize, time: Duration) -> f64 {
+    let secs = time.as_secs_f64();
+    if secs == 0.0 {
+        0.0
+    } else {
+        size as f64 / secs / 1_000_000.0
+    }
+}
+
+fn _write_json_list(output: &mut String, results: &[BenchResult]) {
+    for (index, result) in results.iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+        output.push_str("{\"file\":");
+        _write_json_string(output, &result.file);
+        let level = result
+            .algorithm
+            .level()
+            .map_or(String::from("null"), |v| format!("{v}"));
+        let _ = write!(
+            output,
+            ",\"algorithm\":\"{}\",\"level\":{},\"input_size\":{},\"compressed_size\":{},\"ratio\":{:.6},\"compress_mb_s\":{:.3},\"decompress_mb_s\":{:.3}}}",
+            result.algorithm.name(),
+            level,
+            result.input_size,
+            result.compressed_size,
+            result.ratio(),
+            result.compress_speed(),
+            result.decompress_speed(),
+        );
+    }
+}
+
+fn _write_json_string(output: &mut String, value: &str) {
+    output.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", ch as u32);
+            }
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_run() {
+        let input = fib_str(b'a', b'b', 0x4000);
+        for algorithm in Algorithm::all() {
+            let result = run("fib", &input, algorithm, 1).unwrap();
+            assert_eq!(result.input_size, input.len());
+            assert_eq!(
+                result.compressed_size,
+                algorithm.compress(&input).unwrap().len()
+            );
+            assert!(result.ratio() > 0.0);
+        }
+        assert_eq!(
+            Algorithm::from_name("deflate", Some(6)),
+            Some(vec![Algorithm::Deflate(deflate::CompressionLevel::Default)])
+        );
+        assert_eq!(
+            Algorithm::from_name("bzip2", None).map(|v| v.len()),
+            Some(9)
+        );
+        assert_eq!(Algorithm::from_name("deflate", Some(11)), None);
+        assert_eq!(Algorithm::from_name("zstd", None), None);
+        assert_eq!(
+            Algorithm::from_name("lz4", Some(3)),
+            Some(vec![Algorithm::Lz4])
+        );
+        assert_eq!(Algorithm::Bzip2(9).to_string(), "bzip2-9");
+        assert_eq!(Algorithm::Lz4.to_string(), "lz4");
+    }
+
+    #[test]
+    fn bench_corpus() {
+        let dir = std::env::temp_dir().join(format!("compress-bench-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("b\"quoted"), fib_str(b'a', b'b', 0x1000)).unwrap();
+        std::fs::write(dir.join("a.txt"), b"abracadabra abracadabra").unwrap();
+        std::fs::write(dir.join("nested").join("c"), b"skipped").unwrap();
+
+        let algorithms = [
+            Algorithm::Deflate(deflate::CompressionLevel::Best),
+            Algorithm::Lz4,
+        ];
+        let report = run_corpus(&dir, &algorithms, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let report = report.unwrap();
+
+        let files = report
+            .results
+            .iter()
+            .map(|v| (v.file.as_str(), v.algorithm))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [
+                ("a.txt", algorithms[0]),
+                ("a.txt", algorithms[1]),
+                ("b\"quoted", algorithms[0]),
+                ("b\"quoted", algorithms[1]),
+            ]
+        );
+        let totals = report.totals();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].input_size, 23 + 0x1000);
+        assert_eq!(
+            totals[1].compressed_size,
+            report.results[1].compressed_size + report.results[3].compressed_size
+        );
+
+        let json = report.to_json();
+        assert!(json.starts_with("{\"results\":[{\"file\":\"a.txt\",\"algorithm\":\"deflate\",\"level\":9,\"input_size\":23,"));
+        assert!(json.contains("{\"file\":\"b\\\"quoted\",\"algorithm\":\"lz4\",\"level\":null,"));
+        assert!(json.contains("],\"totals\":[{\"file\":\"*\","));
+        assert!(json.ends_with("}]}"));
+
+        assert!(matches!(
+            run_corpus(&dir, &algorithms, 1),
+            Err(BenchError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,535 @@
+//! Brotli decoder
+
+use super::*;
+use crate::bwt::mtf;
+use crate::entropy::prefix::CanonicalPrefixDecoder;
+use crate::num::bits::{BitSize, BitStreamReader};
+use alloc::vec;
+
+/// Decompresses a Brotli stream.
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses a Brotli stream.
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut reader = BitStreamReader::new(input);
+    let window_bits = _read_window_bits(&mut reader)?;
+    let mut decoder = Decoder {
+        output: Vec::new(),
+        max_size,
+        window_size: (1 << window_bits) - 16,
+        distances: INITIAL_DISTANCES,
+    };
+    while !decoder.meta_block(&mut reader)? {}
+
+    reader.skip_to_next_byte_boundary();
+    if !reader.is_empty() {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok(decoder.output)
+}
+
+/// Reads `bits` bits, which may be zero.
+#[inline]
+fn _read_bits(reader: &mut BitStreamReader, bits: u8) -> Result<u32, DecodeError> {
+    if bits == 0 {
+        return Ok(0);
+    }
+    BitSize::new(bits)
+        .and_then(|bits| reader.read_bits(bits))
+        .ok_or(DecodeError::UnexpectedEof)
+}
+
+#[inline]
+fn _read_bool(reader: &mut BitStreamReader) -> Result<bool, DecodeError> {
+    reader.read_bool().ok_or(DecodeError::UnexpectedEof)
+}
+
+/// Reads the bits up to the next byte boundary, which must be zero.
+#[inline]
+fn _read_padding(reader: &mut BitStreamReader) -> Result<(), DecodeError> {
+    if _read_bits(reader, (reader.remaining_bits() & 7) as u8)? != 0 {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok(())
+}
+
+fn _read_window_bits(reader: &mut BitStreamReader) -> Result<u8, DecodeError> {
+    if !_read_bool(reader)? {
+        return Ok(16);
+    }
+    let n = _read_bits(reader, 3)? as u8;
+    if n != 0 {
+        return Ok(17 + n);
+    }
+    match _read_bits(reader, 3)? as u8 {
+        0 => Ok(17),
+        // large window
+        1 => Err(DecodeError::UnsupportedFormat),
+        m => Ok(8 + m),
+    }
+}
+
+/// Reads a value in `0..=255` with a variable length.
+fn _read_var_len_u8(reader: &mut BitStreamReader) -> Result<usize, DecodeError> {
+    if !_read_bool(reader)? {
+        return Ok(0);
+    }
+    let n = _read_bits(reader, 3)? as u8;
+    if n == 0 {
+        return Ok(1);
+    }
+    Ok((1 << n) + _read_bits(reader, n)? as usize)
+}
+
+struct Decoder {
+    output: Vec<u8>,
+    max_size: usize,
+    window_size: usize,
+    /// the last four distances, the last one first
+    distances: [usize; 4],
+}
+
+impl Decoder {
+    /// Decodes a meta-block, and returns `true` if it is the last one.
+    fn meta_block(&mut self, reader: &mut BitStreamReader) -> Result<bool, DecodeError> {
+        let is_last = _read_bool(reader)?;
+        if is_last && _read_bool(reader)? {
+            return Ok(true);
+        }
+
+        let n_nibbles = match _read_bits(reader, 2)? {
+            3 => 0,
+            v => v as u8 + 4,
+        };
+        if n_nibbles == 0 {
+            // metadata
+            if _read_bool(reader)? {
+                return Err(DecodeError::InvalidData);
+            }
+            let n_bytes = _read_bits(reader, 2)? as u8;
+            let mut len = 0;
+            for i in 0..n_bytes {
+                let byte = _read_bits(reader, 8)? as usize;
+                if i > 0 && i == n_bytes - 1 && byte == 0 {
+                    return Err(DecodeError::InvalidData);
+                }
+                len |= byte << (i * 8);
+            }
+            if n_bytes > 0 {
+                len += 1;
+            }
+            _read_padding(reader)?;
+            reader
+                .read_next_bytes_slice(len)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            return Ok(is_last);
+        }
+
+        let mut len = 0;
+        for i in 0..n_nibbles {
+            let nibble = _read_bits(reader, 4)? as usize;
+            if i > 3 && i == n_nibbles - 1 && nibble == 0 {
+                return Err(DecodeError::InvalidData);
+            }
+            len |= nibble << (i * 4);
+        }
+        let len = len + 1;
+        if len > self.max_size - self.output.len() {
+            return Err(DecodeError::LimitExceeded);
+        }
+
+        if !is_last && _read_bool(reader)? {
+            // uncompressed
+            _read_padding(reader)?;
+            let data = reader
+                .read_next_bytes_slice(len)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            self.output.extend_from_slice(data);
+            return Ok(false);
+        }
+
+        self.compressed_meta_block(reader, len)?;
+        Ok(is_last)
+    }
+
+    fn compressed_meta_block(
+        &mut self,
+        reader: &mut BitStreamReader,
+        len: usize,
+    ) -> Result<(), DecodeError> {
+        let mut literal_blocks = BlockSwitch::read(reader)?;
+        let mut command_blocks = BlockSwitch::read(reader)?;
+        let mut distance_blocks = BlockSwitch::read(reader)?;
+
+        let n_postfix = _read_bits(reader, 2)? as u8;
+        let n_direct = (_read_bits(reader, 4)? as usize) << n_postfix;
+        let postfix_mask = (1 << n_postfix) - 1;
+        let n_distance_codes = NUM_DISTANCE_SHORT_CODES + n_direct + (48 << n_postfix);
+
+        let mut context_modes = Vec::with_capacity(literal_blocks.n_types);
+        for _ in 0..literal_blocks.n_types {
+            context_modes.push(ContextMode::from_code(_read_bits(reader, 2)?));
+        }
+        let (n_literal_trees, literal_context_map) =
+            _read_context_map(reader, literal_blocks.n_types << LITERAL_CONTEXT_BITS)?;
+        let (n_distance_trees, distance_context_map) =
+            _read_context_map(reader, distance_blocks.n_types << DISTANCE_CONTEXT_BITS)?;
+
+        let literal_codes = (0..n_literal_trees)
+            .map(|_| PrefixCode::read(reader, NUM_LITERAL_CODES))
+            .collect::<Result<Vec<_>, _>>()?;
+        let command_codes = (0..command_blocks.n_types)
+            .map(|_| PrefixCode::read(reader, NUM_COMMAND_CODES))
+            .collect::<Result<Vec<_>, _>>()?;
+        let distance_codes = (0..n_distance_trees)
+            .map(|_| PrefixCode::read(reader, n_distance_codes))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let end = self.output.len() + len;
+        while self.output.len() < end {
+            let command_type = command_blocks.next(reader)?;
+            let command = command_codes[command_type].decode(reader)? as usize;
+            let (insert_base, copy_base) = COMMAND_CELLS[command >> 6];
+            let (insert_len, insert_extra) =
+                INSERT_LENGTH_CODES[insert_base + ((command >> 3) & 7)];
+            let (copy_len, copy_extra) = COPY_LENGTH_CODES[copy_base + (command & 7)];
+            let insert_len = (insert_len + _read_bits(reader, insert_extra)?) as usize;
+            let copy_len = (copy_len + _read_bits(reader, copy_extra)?) as usize;
+
+            if insert_len > end - self.output.len() {
+                return Err(DecodeError::InvalidData);
+            }
+            for _ in 0..insert_len {
+                let literal_type = literal_blocks.next(reader)?;
+                let p1 = self.output.last().copied().unwrap_or_default();
+                let p2 = self
+                    .output
+                    .len()
+                    .checked_sub(2)
+                    .map(|v| self.output[v])
+                    .unwrap_or_default();
+                let context = context_modes[literal_type].context(p1, p2);
+                let tree = literal_context_map[(literal_type << LITERAL_CONTEXT_BITS) + context];
+                let literal = literal_codes[tree as usize].decode(reader)?;
+                self.output.push(literal as u8);
+            }
+            if self.output.len() == end {
+                // the copy length is ignored at the end of the meta-block
+                break;
+            }
+
+            let distance_code = if command >> 6 < IMPLICIT_DISTANCE_CELLS {
+                0
+            } else {
+                let distance_type = distance_blocks.next(reader)?;
+                let context = copy_len.min(5) - 2;
+                let tree = distance_context_map[(distance_type << DISTANCE_CONTEXT_BITS) + context];
+                distance_codes[tree as usize].decode(reader)? as usize
+            };
+            let distance = if distance_code < NUM_DISTANCE_SHORT_CODES {
+                let (index, delta) = DISTANCE_SHORT_CODES[distance_code];
+                self.distances[index]
+                    .checked_add_signed(delta)
+                    .filter(|&v| v > 0)
+                    .ok_or(DecodeError::InvalidData)?
+            } else if distance_code < NUM_DISTANCE_SHORT_CODES + n_direct {
+                distance_code - NUM_DISTANCE_SHORT_CODES + 1
+            } else {
+                let code = distance_code - NUM_DISTANCE_SHORT_CODES - n_direct;
+                let extra_bits = 1 + (code >> (n_postfix + 1)) as u8;
+                let high = (code >> n_postfix) & 1;
+                let offset = ((2 + high) << extra_bits) - 4;
+                let extra = _read_bits(reader, extra_bits)? as usize;
+                ((offset + extra) << n_postfix) + (code & postfix_mask) + n_direct + 1
+            };
+
+            let max_distance = self.window_size.min(self.output.len());
+            if distance > max_distance {
+                // static dictionary reference
+                let word_id = distance - max_distance - 1;
+                dictionary::append_word(copy_len, word_id, &mut self.output)?;
+                if self.output.len() > end {
+                    return Err(DecodeError::InvalidData);
+                }
+                continue;
+            }
+            if distance_code != 0 {
+                self.distances.copy_within(..3, 1);
+                self.distances[0] = distance;
+            }
+
+            if copy_len > end - self.output.len() {
+                return Err(DecodeError::InvalidData);
+            }
+            let start = self.output.len() - distance;
+            if distance >= copy_len {
+                self.output.extend_from_within(start..start + copy_len);
+            } else {
+                self.output.reserve(copy_len);
+                for i in start..start + copy_len {
+                    self.output.push(self.output[i]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Block types and counts of a category
+struct BlockSwitch {
+    n_types: usize,
+    /// prefix codes of the block types and the block counts
+    codes: Option<(PrefixCode, PrefixCode)>,
+    current: usize,
+    previous: usize,
+    remaining: usize,
+}
+
+impl BlockSwitch {
+    fn read(reader: &mut BitStreamReader) -> Result<Self, DecodeError> {
+        let n_types = _read_var_len_u8(reader)? + 1;
+        if n_types < 2 {
+            return Ok(Self {
+                n_types,
+                codes: None,
+                current: 0,
+                previous: 1,
+                remaining: usize::MAX,
+            });
+        }
+        let type_code = PrefixCode::read(reader, n_types + 2)?;
+        let count_code = PrefixCode::read(reader, NUM_BLOCK_COUNT_CODES)?;
+        let remaining = _read_block_count(reader, &count_code)?;
+        Ok(Self {
+            n_types,
+            codes: Some((type_code, count_code)),
+            current: 0,
+            previous: 1,
+            remaining,
+        })
+    }
+
+    /// Returns the block type of the next symbol.
+    #[inline]
+    fn next(&mut self, reader: &mut BitStreamReader) -> Result<usize, DecodeError> {
+        if self.remaining == 0 {
+            let Some((type_code, count_code)) = self.codes.as_ref() else {
+                return Err(DecodeError::InvalidData);
+            };
+            let next = match type_code.decode(reader)? as usize {
+                0 => self.previous,
+                1 => (self.current + 1) % self.n_types,
+                v => v - 2,
+            };
+            self.previous = self.current;
+            self.current = next;
+            self.remaining = _read_block_count(reader, count_code)?;
+        }
+        self.remaining -= 1;
+        Ok(self.current)
+    }
+}
+
+fn _read_block_count(
+    reader: &mut BitStreamReader,
+    count_code: &PrefixCode,
+) -> Result<usize, DecodeError> {
+    let (base, extra_bits) = BLOCK_COUNT_CODES[count_code.decode(reader)? as usize];
+    Ok((base + _read_bits(reader, extra_bits)?) as usize)
+}
+
+/// Reads a context map of `size` entries, and returns the number of trees and the map.
+fn _read_context_map(
+    reader: &mut BitStreamReader,
+    size: usize,
+) -> Result<(usize, Vec<u8>), DecodeError> {
+    let n_trees = _read_var_len_u8(reader)? + 1;
+    if n_trees < 2 {
+        return Ok((n_trees, vec![0; size]));
+    }
+
+    let max_run_len_prefix = if _read_bool(reader)? {
+        _read_bits(reader, 4)? as usize + 1
+    } else {
+        0
+    };
+    let code = PrefixCode::read(reader, n_trees + max_run_len_prefix)?;
+    let mut context_map = Vec::with_capacity(size);
+    while context_map.len() < size {
+        let symbol = code.decode(reader)? as usize;
+        if symbol == 0 {
+            context_map.push(0);
+        } else if symbol <= max_run_len_prefix {
+            let run_len = (1 << symbol) + _read_bits(reader, symbol as u8)? as usize;
+            if run_len > size - context_map.len() {
+                return Err(DecodeError::InvalidData);
+            }
+            context_map.resize(context_map.len() + run_len, 0);
+        } else {
+            context_map.push((symbol - max_run_len_prefix) as u8);
+        }
+    }
+    if _read_bool(reader)? {
+        context_map = mtf::decode(&context_map);
+    }
+    Ok((n_trees, context_map))
+}
+
+/// Prefix code, which may consist of a single symbol with a zero-length code
+enum PrefixCode {
+    Single(u16),
+    Canonical(CanonicalPrefixDecoder),
+}
+
+impl PrefixCode {
+    fn read(reader: &mut BitStreamReader, alphabet_size: usize) -> Result<Self, DecodeError> {
+        let h_skip = _read_bits(reader, 2)? as usize;
+        if h_skip == 1 {
+            return Self::_read_simple(reader, alphabet_size);
+        }
+
+        // code lengths of the code length code
+        let mut code_length_lengths = [0u8; CODE_LENGTH_CODES];
+        let mut space = 32i32;
+        let mut n_codes = 0;
+        for &symbol in CODE_LENGTH_ORDER[h_skip..].iter() {
+            let len = _read_code_length_length(reader)?;
+            code_length_lengths[symbol] = len;
+            if len != 0 {
+                space -= 32 >> len;
+                n_codes += 1;
+                if space <= 0 {
+                    break;
+                }
+            }
+        }
+        if n_codes != 1 && space != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        let code_length_code = Self::_from_lengths(&code_length_lengths)?;
+
+        // code lengths of the symbols
+        let mut lengths = vec![0u8; alphabet_size];
+        let mut symbol = 0;
+        let mut prev_len = INITIAL_REPEATED_CODE_LENGTH;
+        let mut repeat = 0;
+        let mut repeat_len = 0;
+        let mut space = 1i32 << MAX_CODE_LEN;
+        while symbol < alphabet_size && space > 0 {
+            let code = code_length_code.decode(reader)? as u8;
+            if code <= MAX_CODE_LEN {
+                repeat = 0;
+                lengths[symbol] = code;
+                symbol += 1;
+                if code != 0 {
+                    prev_len = code;
+                    space -= (1 << MAX_CODE_LEN) >> code;
+                }
+                continue;
+            }
+
+            let (extra_bits, new_len) = if code == REPEAT_PREVIOUS_CODE_LENGTH {
+                (2, prev_len)
+            } else {
+                debug_assert_eq!(code, REPEAT_ZERO_CODE_LENGTH);
+                (3, 0)
+            };
+            if repeat_len != new_len {
+                repeat = 0;
+                repeat_len = new_len;
+            }
+            let old_repeat = repeat;
+            if repeat > 0 {
+                repeat = (repeat - 2) << extra_bits;
+            }
+            repeat += _read_bits(reader, extra_bits)? as usize + 3;
+            let delta = repeat - old_repeat;
+            if delta > alphabet_size - symbol {
+                return Err(DecodeError::InvalidData);
+            }
+            lengths[symbol..symbol + delta].fill(new_len);
+            symbol += delta;
+            if new_len != 0 {
+                space -= ((delta << MAX_CODE_LEN) >> new_len) as i32;
+            }
+        }
+        if space != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        Self::_from_lengths(&lengths)
+    }
+
+    fn _read_simple(
+        reader: &mut BitStreamReader,
+        alphabet_size: usize,
+    ) -> Result<Self, DecodeError> {
+        let alphabet_bits = (usize::BITS - (alphabet_size - 1).leading_zeros()) as u8;
+        let n_symbols = _read_bits(reader, 2)? as usize + 1;
+        let mut symbols = [0; 4];
+        for i in 0..n_symbols {
+            let symbol = _read_bits(reader, alphabet_bits)? as usize;
+            if symbol >= alphabet_size || symbols[..i].contains(&symbol) {
+                return Err(DecodeError::InvalidData);
+            }
+            symbols[i] = symbol;
+        }
+        let code_lengths: &[u8] = match n_symbols {
+            1 => return Ok(Self::Single(symbols[0] as u16)),
+            2 => &[1, 1],
+            3 => &[1, 2, 2],
+            _ => {
+                if _read_bool(reader)? {
+                    &[1, 2, 3, 3]
+                } else {
+                    &[2, 2, 2, 2]
+                }
+            }
+        };
+        let mut lengths = vec![0u8; alphabet_size];
+        for (&symbol, &len) in symbols.iter().zip(code_lengths) {
+            lengths[symbol] = len;
+        }
+        Self::_from_lengths(&lengths)
+    }
+
+    fn _from_lengths(lengths: &[u8]) -> Result<Self, DecodeError> {
+        let mut symbols = lengths.iter().enumerate().filter(|v| *v.1 != 0);
+        match (symbols.next(), symbols.next()) {
+            (None, _) => Err(DecodeError::InvalidData),
+            (Some((symbol, _)), None) => Ok(Self::Single(symbol as u16)),
+            _ => CanonicalPrefixDecoder::with_lengths(lengths, false).map(Self::Canonical),
+        }
+    }
+
+    #[inline]
+    fn decode(&self, reader: &mut BitStreamReader) -> Result<u32, DecodeError> {
+        match self {
+            Self::Single(symbol) => Ok(*symbol as u32),
+            Self::Canonical(decoder) => decoder.decode(reader),
+        }
+    }
+}
+
+/// Reads a code length of the code length code with its static prefix code.
+fn _read_code_length_length(reader: &mut BitStreamReader) -> Result<u8, DecodeError> {
+    match _read_bits(reader, 2)? {
+        0 => Ok(0),
+        1 => Ok(4),
+        2 => Ok(3),
+        _ => {
+            if !_read_bool(reader)? {
+                Ok(2)
+            } else if !_read_bool(reader)? {
+                Ok(1)
+            } else {
+                Ok(5)
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Static dictionary and word transforms
+//!
+//! The dictionary data is only included with the `brotli-dictionary` feature.
+//! Without it, streams that refer to the dictionary fail with `DecodeError::UnsupportedFormat`.
+
+use super::*;
+
+/// Contents of the static dictionary, see Appendix A of RFC 7932
+#[cfg(feature = "brotli-dictionary")]
+static DICTIONARY: &[u8; 122_784] = include_bytes!("dictionary.bin");
+
+/// Minimum length of the dictionary words
+pub const MIN_WORD_LEN: usize = 4;
+
+/// Maximum length of the dictionary words
+pub const MAX_WORD_LEN: usize = 24;
+
+/// Number of bits of the word index for each length
+const SIZE_BITS_BY_LENGTH: [u8; MAX_WORD_LEN + 1] = [
+    0, 0, 0, 0, 10, 10, 11, 11, 10, 10, 10, 10, 10, 9, 9, 8, 7, 7, 8, 7, 7, 6, 6, 5, 5,
+];
+
+/// Offset of the words of each length
+#[cfg(feature = "brotli-dictionary")]
+const OFFSETS_BY_LENGTH: [u32; MAX_WORD_LEN + 1] = [
+    0, 0, 0, 0, 0, 4096, 9216, 21504, 35840, 44032, 53248, 63488, 74752, 87040, 93696, 100864,
+    104704, 106752, 108928, 113536, 115968, 118528, 119872, 121280, 122016,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transform {
+    Identity,
+    OmitLast(u8),
+    UppercaseFirst,
+    UppercaseAll,
+    OmitFirst(u8),
+}
+
+use Transform::*;
+
+/// Prefix, transform and suffix of each transform id, see Appendix B of RFC 7932
+static TRANSFORMS: [(&[u8], Transform, &[u8]); 121] = [
+    (b"", Identity, b""),
+    (b"", Identity, b" "),
+    (b" ", Identity, b" "),
+    (b"", OmitFirst(1), b""),
+    (b"", UppercaseFirst, b" "),
+    (b"", Identity, b" the "),
+    (b" ", Identity, b""),
+    (b"s ", Identity, b" "),
+    (b"", Identity, b" of "),
+    (b"", UppercaseFirst, b""),
+    (b"", Identity, b" and "),
+    (b"", OmitFirst(2), b""),
+    (b"", OmitLast(1), b""),
+    (b", ", Identity, b" "),
+    (b"", Identity, b", "),
+    (b" ", UppercaseFirst, b" "),
+    (b"", Identity, b" in "),
+    (b"", Identity, b" to "),
+    (b"e ", Identity, b" "),
+    (b"", Identity, b"\""),
+    (b"", Identity, b"."),
+    (b"", Identity, b"\">"),
+    (b"", Identity, b"\n"),
+    (b"", OmitLast(3), b""),
+    (b"", Identity, b"]"),
+    (b"", Identity, b" for "),
+    (b"", OmitFirst(3), b""),
+    (b"", OmitLast(2), b""),
+    (b"", Identity, b" a "),
+    (b"", Identity, b" that "),
+    (b" ", UppercaseFirst, b""),
+    (b"", Identity, b". "),
+    (b".", Identity, b""),
+    (b" ", Identity, b", "),
+    (b"", OmitFirst(4), b""),
+    (b"", Identity, b" with "),
+    (b"", Identity, b"'"),
+    (b"", Identity, b" from "),
+    (b"", Identity, b" by "),
+    (b"", OmitFirst(5), b""),
+    (b"", OmitFirst(6), b""),
+    (b" the ", Identity, b""),
+    (b"", OmitLast(4), b""),
+    (b"", Identity, b". The "),
+    (b"", UppercaseAll, b""),
+    (b"", Identity, b" on "),
+    (b"", Identity, b" as "),
+    (b"", Identity, b" is "),
+    (b"", OmitLast(7), b""),
+    (b"", OmitLast(1), b"ing "),
+    (b"", Identity, b"\n\t"),
+    (b"", Identity, b":"),
+    (b" ", Identity, b". "),
+    (b"", Identity, b"ed "),
+    (b"", OmitFirst(9), b""),
+    (b"", OmitFirst(7), b""),
+    (b"", OmitLast(6), b""),
+    (b"", Identity, b"("),
+    (b"", UppercaseFirst, b", "),
+    (b"", OmitLast(8), b""),
+    (b"", Identity, b" at "),
+    (b"", Identity, b"ly "),
+    (b" the ", Identity, b" of "),
+    (b"", OmitLast(5), b""),
+    (b"", OmitLast(9), b""),
+    (b" ", UppercaseFirst, b", "),
+    (b"", UppercaseFirst, b"\""),
+    (b".", Identity, b"("),
+    (b"", UppercaseAll, b" "),
+    (b"", UppercaseFirst, b"\">"),
+    (b"", Identity, b"=\""),
+    (b" ", Identity, b"."),
+    (b".com/", Identity, b""),
+    (b" the ", Identity, b" of the "),
+    (b"", UppercaseFirst, b"'"),
+    (b"", Identity, b". This "),
+    (b"", Identity, b","),
+    (b".", Identity, b" "),
+    (b"", UppercaseFirst, b"("),
+    (b"", UppercaseFirst, b"."),
+    (b"", Identity, b" not "),
+    (b" ", Identity, b"=\""),
+    (b"", Identity, b"er "),
+    (b" ", UppercaseAll, b" "),
+    (b"", Identity, b"al "),
+    (b" ", UppercaseAll, b""),
+    (b"", Identity, b"='"),
+    (b"", UppercaseAll, b"\""),
+    (b"", UppercaseFirst, b". "),
+    (b" ", Identity, b"("),
+    (b"", Identity, b"ful "),
+    (b" ", UppercaseFirst, b". "),
+    (b"", Identity, b"ive "),
+    (b"", Identity, b"less "),
+    (b"", UppercaseAll, b"'"),
+    (b"", Identity, b"est "),
+    (b" ", UppercaseFirst, b"."),
+    (b"", UppercaseAll, b"\">"),
+    (b" ", Identity, b"='"),
+    (b"", UppercaseFirst, b","),
+    (b"", Identity, b"ize "),
+    (b"", UppercaseAll, b"."),
+    (b"\xc2\xa0", Identity, b""),
+    (b" ", Identity, b","),
+    (b"", UppercaseFirst, b"=\""),
+    (b"", UppercaseAll, b"=\""),
+    (b"", Identity, b"ous "),
+    (b"", UppercaseAll, b", "),
+    (b"", UppercaseFirst, b"='"),
+    (b" ", UppercaseFirst, b","),
+    (b" ", UppercaseAll, b"=\""),
+    (b" ", UppercaseAll, b", "),
+    (b"", UppercaseAll, b","),
+    (b"", UppercaseAll, b"("),
+    (b"", UppercaseAll, b". "),
+    (b" ", UppercaseAll, b"."),
+    (b"", UppercaseAll, b"='"),
+    (b" ", UppercaseAll, b". "),
+    (b" ", UppercaseFirst, b"=\""),
+    (b" ", UppercaseAll, b"='"),
+    (b" ", UppercaseFirst, b"='"),
+];
+
+/// Appends the transformed dictionary word referred to by a copy of `len` bytes.
+pub(super) fn append_word(
+    len: usize,
+    word_id: usize,
+    output: &mut Vec<u8>,
+) -> Result<(), DecodeError> {
+    if !(MIN_WORD_LEN..=MAX_WORD_LEN).contains(&len) {
+        return Err(DecodeError::InvalidData);
+    }
+    let size_bits = SIZE_BITS_BY_LENGTH[len];
+    let index = word_id & ((1 << size_bits) - 1);
+    let (prefix, transform, suffix) = TRANSFORMS
+        .get(word_id >> size_bits)
+        .ok_or(DecodeError::InvalidData)?;
+    let word = _word(len, index)?;
+
+    output.extend_from_slice(prefix);
+    let word_start = output.len();
+    match *transform {
+        Identity | UppercaseFirst | UppercaseAll => output.extend_from_slice(word),
+        OmitLast(n) => output.extend_from_slice(&word[..len.saturating_sub(n as usize)]),
+        OmitFirst(n) => output.extend_from_slice(&word[(n as usize).min(len)..]),
+    }
+    match *transform {
+        UppercaseFirst => {
+            _to_upper_case(&mut output[word_start..]);
+        }
+        UppercaseAll => {
+            let mut position = word_start;
+            while position < output.len() {
+                position += _to_upper_case(&mut output[position..]);
+            }
+        }
+        _ => {}
+    }
+    output.extend_from_slice(suffix);
+    Ok(())
+}
+
+#[cfg(feature = "brotli-dictionary")]
+#[inline]
+fn _word(len: usize, index: usize) -> Result<&'static [u8], DecodeError> {
+    let offset = OFFSETS_BY_LENGTH[len] as usize + index * len;
+    Ok(&DICTIONARY[offset..offset + len])
+}
+
+#[cfg(not(feature = "brotli-dictionary"))]
+#[inline]
+fn _word(_len: usize, _index: usize) -> Result<&'static [u8], DecodeError> {
+    Err(DecodeError::UnsupportedFormat)
+}
+
+/// Converts the first UTF-8 character to upper case in the way of RFC 7932,
+/// and returns the length of the character.
+///
+/// Bytes beyond the end of `word` are not modified.
+fn _to_upper_case(word: &mut [u8]) -> usize {
+    if word[0] < 0xc0 {
+        word[0] = word[0].to_ascii_uppercase();
+        1
+    } else if word[0] < 0xe0 {
+        if let Some(p) = word.get_mut(1) {
+            *p ^= 32;
+        }
+        2
+    } else {
+        if let Some(p) = word.get_mut(2) {
+            *p ^= 5;
+        }
+        3
+    }
+}
@@ -0,0 +1,245 @@
+//! Brotli compressed data format (decoder only)
+//!
+//! The static dictionary is included with the `brotli-dictionary` feature, which is enabled by default.
+//! Large windows are not supported.
+//!
+//! See also: <https://www.rfc-editor.org/rfc/rfc7932>
+
+use crate::*;
+
+#[cfg(test)]
+mod tests;
+
+mod decode;
+mod dictionary;
+pub use decode::*;
+
+/// Number of symbols of the code length code
+const CODE_LENGTH_CODES: usize = 18;
+
+/// Order of the code length code lengths
+const CODE_LENGTH_ORDER: [usize; CODE_LENGTH_CODES] =
+    [1, 2, 3, 4, 0, 5, 17, 6, 16, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Code length that repeats the previous non-zero code length
+const REPEAT_PREVIOUS_CODE_LENGTH: u8 = 16;
+
+/// Code length that repeats zeros
+const REPEAT_ZERO_CODE_LENGTH: u8 = 17;
+
+/// Code length that is repeated by [`REPEAT_PREVIOUS_CODE_LENGTH`] if no non-zero code length precedes it
+const INITIAL_REPEATED_CODE_LENGTH: u8 = 8;
+
+/// Maximum length of the prefix codes
+const MAX_CODE_LEN: u8 = 15;
+
+const NUM_LITERAL_CODES: usize = 256;
+const NUM_COMMAND_CODES: usize = 704;
+const NUM_BLOCK_COUNT_CODES: usize = 26;
+
+/// Number of distance codes that refer to the last distances
+const NUM_DISTANCE_SHORT_CODES: usize = 16;
+
+/// Number of literal contexts for each block type
+const LITERAL_CONTEXT_BITS: usize = 6;
+
+/// Number of distance contexts for each block type
+const DISTANCE_CONTEXT_BITS: usize = 2;
+
+/// Initial values of the last four distances, the last one first
+const INITIAL_DISTANCES: [usize; 4] = [4, 11, 15, 16];
+
+/// Last distance index and delta of each short distance code
+const DISTANCE_SHORT_CODES: [(usize, isize); NUM_DISTANCE_SHORT_CODES] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (0, -1),
+    (0, 1),
+    (0, -2),
+    (0, 2),
+    (0, -3),
+    (0, 3),
+    (1, -1),
+    (1, 1),
+    (1, -2),
+    (1, 2),
+    (1, -3),
+    (1, 3),
+];
+
+/// Base value and number of extra bits of the block count codes
+const BLOCK_COUNT_CODES: [(u32, u8); NUM_BLOCK_COUNT_CODES] = [
+    (1, 2),
+    (5, 2),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 3),
+    (41, 3),
+    (49, 4),
+    (65, 4),
+    (81, 4),
+    (97, 4),
+    (113, 5),
+    (145, 5),
+    (177, 5),
+    (209, 5),
+    (241, 6),
+    (305, 6),
+    (369, 7),
+    (497, 8),
+    (753, 9),
+    (1265, 10),
+    (2289, 11),
+    (4337, 12),
+    (8433, 13),
+    (16625, 24),
+];
+
+/// Base value and number of extra bits of the insert length codes
+const INSERT_LENGTH_CODES: [(u32, u8); 24] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 1),
+    (8, 1),
+    (10, 2),
+    (14, 2),
+    (18, 3),
+    (26, 3),
+    (34, 4),
+    (50, 4),
+    (66, 5),
+    (98, 5),
+    (130, 6),
+    (194, 7),
+    (322, 8),
+    (578, 9),
+    (1090, 10),
+    (2114, 12),
+    (6210, 14),
+    (22594, 24),
+];
+
+/// Base value and number of extra bits of the copy length codes
+const COPY_LENGTH_CODES: [(u32, u8); 24] = [
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 1),
+    (12, 1),
+    (14, 2),
+    (18, 2),
+    (22, 3),
+    (30, 3),
+    (38, 4),
+    (54, 4),
+    (70, 5),
+    (102, 5),
+    (134, 6),
+    (198, 7),
+    (326, 8),
+    (582, 9),
+    (1094, 10),
+    (2118, 24),
+];
+
+/// First insert length code and first copy length code of each cell of 64 insert-and-copy length codes
+///
+/// The distance code is implicitly zero in the first two cells.
+const COMMAND_CELLS: [(usize, usize); 11] = [
+    (0, 0),
+    (0, 8),
+    (0, 0),
+    (0, 8),
+    (8, 0),
+    (8, 8),
+    (0, 16),
+    (16, 0),
+    (8, 16),
+    (16, 8),
+    (16, 16),
+];
+
+/// Number of cells in which the distance code is implicitly zero
+const IMPLICIT_DISTANCE_CELLS: usize = 2;
+
+/// Context modes of the literals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContextMode {
+    Lsb6,
+    Msb6,
+    Utf8,
+    Signed,
+}
+
+impl ContextMode {
+    #[inline]
+    const fn from_code(code: u32) -> Self {
+        match code & 3 {
+            0 => Self::Lsb6,
+            1 => Self::Msb6,
+            2 => Self::Utf8,
+            _ => Self::Signed,
+        }
+    }
+
+    /// Returns the literal context from the last two bytes.
+    #[inline]
+    const fn context(&self, p1: u8, p2: u8) -> usize {
+        match self {
+            Self::Lsb6 => (p1 & 0x3f) as usize,
+            Self::Msb6 => (p1 >> 2) as usize,
+            Self::Utf8 => (LUT0[p1 as usize] | LUT1[p2 as usize]) as usize,
+            Self::Signed => ((LUT2[p1 as usize] << 3) | LUT2[p2 as usize]) as usize,
+        }
+    }
+}
+
+/// Context of the last byte in the UTF-8 context mode
+static LUT0: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    8, 12, 16, 12, 12, 20, 12, 16, 24, 28, 12, 12, 32, 12, 36, 12, 44, 44, 44, 44, 44, 44, 44, 44,
+    44, 44, 32, 32, 24, 40, 28, 12, 12, 48, 52, 52, 52, 48, 52, 52, 52, 48, 52, 52, 52, 52, 52, 48,
+    52, 52, 52, 52, 52, 48, 52, 52, 52, 52, 52, 24, 12, 28, 12, 12, 12, 56, 60, 60, 60, 56, 60, 60,
+    60, 56, 60, 60, 60, 60, 60, 56, 60, 60, 60, 60, 60, 56, 60, 60, 60, 60, 60, 24, 12, 28, 12, 0,
+    0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1,
+    0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1,
+    2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3,
+    2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3,
+];
+
+/// Context of the second-to-last byte in the UTF-8 context mode
+static LUT1: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1,
+    1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1,
+    1, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 1, 1, 1, 1, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+/// Context of a byte in the signed context mode
+static LUT2: [u8; 256] = [
+    0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5,
+    5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 7,
+];
@@ -0,0 +1,123 @@
+use super::*;
+use base64::prelude::*;
+
+const QUICK: &[u8] =
+    b"The quick brown fox jumps over the lazy dog. THE QUICK BROWN FOX! the Quick brown Fox.\n";
+
+/// `QUICK` compressed by `brotli -q 11`, which refers to the static dictionary with transforms
+const QUICK_BR: &[u8] = &[
+    0x1b, 0x56, 0x00, 0x00, 0x2c, 0x0e, 0x78, 0xf3, 0x26, 0x62, 0xe5, 0xb8, 0x56, 0x78, 0xa6, 0xa3,
+    0xb2, 0x92, 0xea, 0x0c, 0xaf, 0x8d, 0xb0, 0xc8, 0xcc, 0x34, 0x88, 0x8b, 0xc2, 0xdc, 0x7e, 0x43,
+    0x42, 0x7b, 0x5a, 0x21, 0x45, 0xd0, 0x92, 0x03, 0xd6, 0xb6, 0xdb, 0x02, 0x09, 0xf2, 0x5e, 0xf0,
+    0xdd, 0xc6, 0xce, 0x52, 0x10, 0x1d, 0x8d, 0x46, 0x84, 0xf1, 0x8b, 0x43, 0xcc, 0xc5, 0x6d, 0x3e,
+    0x7a, 0x2a, 0x7e, 0x58, 0xf5, 0x4b, 0xd1, 0x7e, 0x52, 0xe9, 0x50, 0xf3, 0x98, 0x68, 0x6c, 0x2d,
+    0x48, 0x56, 0xc0, 0xdc, 0x00,
+];
+
+/// A metadata meta-block, an uncompressed meta-block with `Hello` and an empty last meta-block
+const HELLO_UNCOMPRESSED_BR: &[u8] = &[
+    0x2c, 0x01, 0x78, 0x79, 0x7a, 0x20, 0x00, 0x08, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x03,
+];
+
+const TEXT: &[u8] = br#"Brotli is a generic-purpose lossless compression algorithm that compresses data using a combination
+of a modern variant of the LZ77 algorithm, Huffman coding and 2nd order context modeling, with a
+compression ratio comparable to the best currently available general-purpose compression methods.
+It is similar in speed with deflate but offers more dense compression.
+
+The specification of the Brotli Compressed Data Format is defined in RFC 7932. Brotli is open-sourced
+under the MIT License. The format uses a pre-defined dictionary of common words and phrases found in
+text, HTML, JavaScript and other documents, which allows short inputs to be compressed efficiently.
+
+Each meta-block may use several block types for literals, insert-and-copy lengths and distances.
+The literal of a block type is coded with one of the prefix codes selected by the context map,
+from the context of the previous two bytes: <html><head><title>Example</title></head></html>
+function main() { return 0x1234 + 5678 * 90; } // numbers: 3.14159, 2.71828, 1.41421, 1.73205
+"#;
+
+/// The input of `mixed_input` compressed by `brotli -q 10 -w 16`,
+/// which has three literal block types, a context map with six prefix codes and dictionary references
+const MIXED_BR_BASE64: &[u8] = b"YgZqJMIsUFYAqFUCPR2i9Xd4lMf8qEBOGzpzMaBFprfjMf2x2Mvox+ICGrom+wHbZ9X2fua7u0y8PX1M5wS/JApGuPLTFdzWKWngV0S0VAiF6v1CZfZ2ZU4/oxo/lrdI8q445IF5htpH6OL9r7XX7/U7KAlKEK3h9o8gbu0kIfMRSyeNRNwQ4SIi1hKhEuvu4Cl3HuZs7MRftagWyB0I+nMsM8gGQHCNNZ6OK4F06zL85dlR6XCl1ilielzqa9q5AZbIiPL0scc/Te+3YpdtmlVYAzfNQitiMArHIg+j056sTiLpJHTUaHyDSFo2js1tVXIaikNANHPWuSUg3s/hRmnsBrzDBHNDNvuJWomjAk1j6VAGVdMriNYf6Ohsx0uGgXZRuzfSbIEDtOywO0C+LavhdxqR5mEgYYPTTau1Z9MJ4rjzkec10HgXkorOBJPhz1pPcNG2BZKHpjkp5hes23atdLqTOt1cRbsy6dPYUVodAk0k1ZjVYxltIQXWOnuPyXLzUyrLGq50xNxQZ4Wb+rFEE46YCXZ4xr7mwe21VOF9V3rxN3gL0A/5nabBtxS80A2NAYoaD9kA3IhVyyAPwg2TY9pgiO7YpQ5i66bYeIGMMIfqB41LA7SNOuxgVigMosEPU8cq0Ymn2j2hyj1bBF8ONqA9BG+m+tHh5QdhBIY4STNQ5F6JH1wXr6RXQCj9yI9zjkCmfhZkHL6M/CjwN65Kw8CLlZ1RCJnkdYEd6YTOKlZKoGULXhJ5vpr8kWbLKvrgyv7U8vhT00NnvLe/Bn0AbwO9Ap2JQPQB/CPoDPhr0AfwftA9+EQZ9AG++gAAChY+jUkbfg0MAMBnykCDAdGwQDRAEA0Vdqqq6YZp2Q6hjF9K3rgDzud/Qde8KB/r57bLEWTicqry9t662viYHuu2H+fPr++f379/sB8sakma5UVZ1U3b9cM4zcu6PREAIRhBMZwgKZphOV4QJVlRNd0wLdtxPT8IozhJs7woq7ppu34Yp3lZt/04nS/X2/3xfL0/398fghEUwwmSohmW4wVRkhVV0w3Tsh3X84MwipM0y4uyqpu264dxmpd124/zup/3+/3N4CAwLZqAOOAyta+5XAtt2zZzb+zOmBmmm+5YVIzDsexAGLhQzyXG89PJCuBaLReU9u2OyqODsbBh2GMtpaNCNhLrQCW4UwKbxHMxlDtfI030xL6zJExqdE0MMabTNIXJNrawB3dlGDd60HkQmxQQ/9HLY5IEiC898xmi4IOZ1LAfcWkYDMsa68Y8g26TgVj/kB1d9m1tkmGgXbCRBgiWfHmL+LI1TxIdHYxt8GgbrJdu68xMCcd/gFySWZ1bKwt60OrsgDAmXwpuo00dka13Uz7T9KDSQkI/vNiqBKIp+IinQDRf4uDP7Kq3iAX8Dl5tBoiONRzCYGGtM+L8je0MMzBX6By4TuJiO55hdV6t6HCkK85DdZMTmsTfPfwCRJ1nXKLSZbZxXi/3oB/Cqj/Or1JUvtANVgBFjQdxyEZWLVH/B46CmRM2cXBEyNil7ojjIpVp/UiSFf7uXBy7wXIO9nt4rNHbM/PGYpJo/MyMseZHu5ofvVQ5WxA2YKij6OhgOSbHOrUUB1GcIO8=";
+
+fn mixed_input() -> Vec<u8> {
+    let mut input = TEXT.to_vec();
+    input.extend((0..0x4000u32).map(|v| b"abcd"[(v.wrapping_mul(0x9E37_79B1) >> 30) as usize]));
+    for _ in 0..8 {
+        input.extend(0..=255);
+    }
+    input.extend_from_slice(&TEXT.to_ascii_uppercase());
+    input
+}
+
+#[test]
+fn brotli_decompress_reference() {
+    // empty
+    assert_eq!(decompress(&[0x06]).unwrap(), b"");
+
+    assert_eq!(decompress(HELLO_UNCOMPRESSED_BR).unwrap(), b"Hello");
+
+    let mixed = BASE64_STANDARD.decode(MIXED_BR_BASE64).unwrap();
+    assert_eq!(
+        decompress_with_limit(&mixed, mixed_input().len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    #[cfg(feature = "brotli-dictionary")]
+    {
+        assert_eq!(decompress(&mixed).unwrap(), mixed_input());
+        assert_eq!(decompress(QUICK_BR).unwrap(), QUICK);
+    }
+    #[cfg(not(feature = "brotli-dictionary"))]
+    {
+        assert_eq!(decompress(&mixed), Err(DecodeError::UnsupportedFormat));
+        assert_eq!(decompress(QUICK_BR), Err(DecodeError::UnsupportedFormat));
+    }
+}
+
+#[test]
+fn brotli_invalid() {
+    let mixed = BASE64_STANDARD.decode(MIXED_BR_BASE64).unwrap();
+    for len in [0, 1, mixed.len() / 2, mixed.len() - 1] {
+        assert!(decompress(&mixed[..len]).is_err());
+    }
+
+    // trailing data
+    assert_eq!(decompress(&[0x06, 0x00]), Err(DecodeError::InvalidData));
+
+    // non-zero padding of the uncompressed meta-block
+    let mut input = HELLO_UNCOMPRESSED_BR.to_vec();
+    input[7] |= 0x80;
+    assert_eq!(decompress(&input), Err(DecodeError::InvalidData));
+
+    // large window
+    assert_eq!(
+        decompress(&[0x11, 0x03]),
+        Err(DecodeError::UnsupportedFormat)
+    );
+}
+
+#[test]
+fn brotli_dictionary_words() {
+    let mut output = Vec::new();
+    assert_eq!(
+        dictionary::append_word(3, 0, &mut output),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        dictionary::append_word(4, 121 << 10, &mut output),
+        Err(DecodeError::InvalidData)
+    );
+
+    #[cfg(feature = "brotli-dictionary")]
+    for (len, word_id, expected) in [
+        (4, 0, &b"time"[..]),
+        (4, 44 << 10, b"TIME"),
+        (4, 15 << 10, b" Time "),
+        // omits more bytes than the word has
+        (4, 54 << 10, b""),
+        (5, (9 << 10) | 1, b"Video"),
+    ] {
+        let mut output = b"prefix".to_vec();
+        dictionary::append_word(len, word_id, &mut output).unwrap();
+        assert_eq!(&output[6..], expected);
+    }
+}
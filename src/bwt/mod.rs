@@ -0,0 +1,98 @@
+//! Burrows–Wheeler transform
+//!
+//! The transform is computed from the suffix array with an implicit end-of-string marker,
+//! so it does not need the rotations used by bzip2.
+//! [`compress`] chains it with [`mtf`], the zero run-length coding of [`rle`] and a canonical prefix code.
+//!
+//! See also: <https://en.wikipedia.org/wiki/Burrows%E2%80%93Wheeler_transform>
+
+use crate::lz::suffix::SuffixArray;
+use crate::*;
+use alloc::vec;
+
+#[cfg(test)]
+mod tests;
+
+pub mod mtf;
+pub mod rle;
+
+mod pipeline;
+pub use pipeline::*;
+
+/// Applies the Burrows–Wheeler transform.
+///
+/// Returns the last column without the end-of-string marker, and the row where the marker was.
+///
+/// # Panics
+///
+/// Panics if the input length is greater than `i32::MAX`.
+pub fn transform(input: &[u8]) -> (Vec<u8>, usize) {
+    if input.is_empty() {
+        return (Vec::new(), 0);
+    }
+    let sa = SuffixArray::new(input);
+    let mut output = Vec::with_capacity(input.len());
+    // The first row is the suffix consisting only of the marker.
+    output.push(input[input.len() - 1]);
+    let mut primary_index = 0;
+    for (row, &suffix) in sa.as_slice().iter().enumerate() {
+        if suffix == 0 {
+            primary_index = row + 1;
+        } else {
+            output.push(input[suffix as usize - 1]);
+        }
+    }
+    (output, primary_index)
+}
+
+/// Reverts the Burrows–Wheeler transform.
+pub fn inverse(input: &[u8], primary_index: usize) -> Result<Vec<u8>, DecodeError> {
+    if input.is_empty() {
+        return if primary_index == 0 {
+            Ok(Vec::new())
+        } else {
+            Err(DecodeError::InvalidData)
+        };
+    }
+    if primary_index == 0 || primary_index > input.len() {
+        return Err(DecodeError::InvalidData);
+    }
+    let row_symbol = |row: usize| {
+        if row < primary_index {
+            Some(input[row])
+        } else if row > primary_index {
+            Some(input[row - 1])
+        } else {
+            None
+        }
+    };
+
+    // The marker sorts before all bytes.
+    let mut buckets = [0u32; 256];
+    let mut acc = 1;
+    for (bucket, count) in buckets.iter_mut().zip(stats::histogram_of_bytes(input)) {
+        *bucket = acc;
+        acc += count as u32;
+    }
+
+    // next row in the first column for each row in the last column
+    let mut lf = Vec::with_capacity(input.len() + 1);
+    for row in 0..=input.len() {
+        match row_symbol(row) {
+            Some(byte) => {
+                let bucket = &mut buckets[byte as usize];
+                lf.push(*bucket);
+                *bucket += 1;
+            }
+            None => lf.push(0),
+        }
+    }
+
+    let mut output = vec![0; input.len()];
+    let mut row = 0;
+    for p in output.iter_mut().rev() {
+        *p = row_symbol(row).ok_or(DecodeError::InvalidData)?;
+        row = lf[row] as usize;
+    }
+    Ok(output)
+}
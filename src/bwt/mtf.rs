@@ -0,0 +1,41 @@
+//! Move-to-front transform
+
+use crate::*;
+
+/// Replaces each byte with its index in the recently used list, and moves it to the front.
+pub fn encode(input: &[u8]) -> Vec<u8> {
+    let mut table = _identity();
+    input
+        .iter()
+        .map(|&byte| {
+            let index = table.iter().position(|&v| v == byte).unwrap();
+            table.copy_within(..index, 1);
+            table[0] = byte;
+            index as u8
+        })
+        .collect()
+}
+
+/// Reverts the move-to-front transform.
+pub fn decode(input: &[u8]) -> Vec<u8> {
+    let mut table = _identity();
+    input
+        .iter()
+        .map(|&index| {
+            let index = index as usize;
+            let byte = table[index];
+            table.copy_within(..index, 1);
+            table[0] = byte;
+            byte
+        })
+        .collect()
+}
+
+#[inline]
+fn _identity() -> [u8; 256] {
+    let mut table = [0; 256];
+    for (index, p) in table.iter_mut().enumerate() {
+        *p = index as u8;
+    }
+    table
+}
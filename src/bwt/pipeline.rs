@@ -0,0 +1,137 @@
+//! bzip2-like compression with BWT, MTF, RLE and prefix coding
+
+use super::*;
+use crate::entropy::prefix::{CanonicalPrefixCoder, CanonicalPrefixDecoder, PermutationFlavor};
+use crate::num::VarLenInteger;
+use crate::num::bits::{BitSize, BitStreamReader, BitStreamWriter, Write};
+use crate::stats::Histogram;
+
+/// Default size of a block, same as `bzip2 -9`
+pub const DEFAULT_BLOCK_SIZE: usize = 900_000;
+
+/// Maximum size of a block, limited by the 24-bit header fields
+pub const MAX_BLOCK_SIZE: usize = 0xff_ffff;
+
+/// End of block symbol following the symbols of [`rle`]
+const END_OF_BLOCK: u16 = rle::ALPHABET_SIZE as u16;
+
+const NUM_SYMBOLS: usize = rle::ALPHABET_SIZE + 1;
+
+/// Compresses the input with the default block size.
+#[inline]
+pub fn compress(input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+    compress_with_block_size(input, DEFAULT_BLOCK_SIZE)
+}
+
+/// Compresses the input, splitting it into blocks of `block_size` bytes.
+///
+/// Each block has a final flag, the block size, the primary index and its own prefix code.
+pub fn compress_with_block_size(input: &[u8], block_size: usize) -> Result<Vec<u8>, EncodeError> {
+    if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+        return Err(EncodeError::InvalidInput);
+    }
+    let mut writer = BitStreamWriter::new();
+    if input.is_empty() {
+        writer.write(true);
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit24, 0));
+        return Ok(writer.into_bytes());
+    }
+    let n_blocks = input.len().div_ceil(block_size);
+    for (index, block) in input.chunks(block_size).enumerate() {
+        writer.write(index + 1 == n_blocks);
+        writer.push(VarLenInteger::new_checked(BitSize::Bit24, block.len() as u32).unwrap());
+        _compress_block(block, &mut writer)?;
+    }
+    Ok(writer.into_bytes())
+}
+
+fn _compress_block(input: &[u8], writer: &mut BitStreamWriter) -> Result<(), EncodeError> {
+    let (bwt, primary_index) = transform(input);
+    writer.push(VarLenInteger::new_checked(BitSize::Bit24, primary_index as u32).unwrap());
+
+    let mut symbols = rle::encode(&mtf::encode(&bwt));
+    symbols.push(END_OF_BLOCK);
+
+    let mut histogram = Histogram::<NUM_SYMBOLS>::new();
+    for &symbol in symbols.iter() {
+        histogram.add(symbol as usize);
+    }
+    let prefix_table =
+        CanonicalPrefixCoder::make_prefix_table(&histogram, BitSize::Bit15, NUM_SYMBOLS);
+    let prefix_tables =
+        CanonicalPrefixCoder::encode_single_prefix_table(&prefix_table, PermutationFlavor::Deflate)
+            .map_err(|_| EncodeError::EntropyError)?;
+    writer.write(prefix_tables.hclen);
+    writer.write(prefix_tables.prefix_table.as_slice());
+    writer.write(prefix_tables.content.as_slice());
+
+    for &symbol in symbols.iter() {
+        let code = prefix_table[symbol as usize].ok_or(EncodeError::InternalInconsistency)?;
+        writer.write(code.reversed());
+    }
+    Ok(())
+}
+
+/// Decompresses data written by [`compress`].
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses data written by [`compress`].
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut reader = BitStreamReader::new(input);
+    let mut output = Vec::new();
+    loop {
+        let is_final = reader.read_bool().ok_or(DecodeError::UnexpectedEof)?;
+        let block_size = reader
+            .read_bits(BitSize::Bit24)
+            .ok_or(DecodeError::UnexpectedEof)? as usize;
+        if block_size > max_size - output.len() {
+            return Err(DecodeError::LimitExceeded);
+        }
+        if block_size > 0 {
+            let block = _decompress_block(&mut reader, block_size)?;
+            output.extend_from_slice(&block);
+        } else if !is_final {
+            return Err(DecodeError::InvalidData);
+        }
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+fn _decompress_block(
+    reader: &mut BitStreamReader,
+    block_size: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let primary_index = reader
+        .read_bits(BitSize::Bit24)
+        .ok_or(DecodeError::UnexpectedEof)? as usize;
+
+    let mut lengths = Vec::new();
+    CanonicalPrefixDecoder::decode_length_table_deflate(reader, &mut lengths, NUM_SYMBOLS)?;
+    let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, false)?;
+
+    let mut symbols = Vec::new();
+    loop {
+        let symbol = decoder.decode(reader)? as u16;
+        if symbol == END_OF_BLOCK {
+            break;
+        }
+        // a run digit cannot represent more than one byte each
+        if symbols.len() > block_size {
+            return Err(DecodeError::InvalidData);
+        }
+        symbols.push(symbol);
+    }
+
+    let mtf = rle::decode(&symbols, block_size).map_err(|_| DecodeError::InvalidData)?;
+    if mtf.len() != block_size {
+        return Err(DecodeError::InvalidData);
+    }
+    inverse(&mtf::decode(&mtf), primary_index)
+}
@@ -0,0 +1,82 @@
+//! Zero run-length coding of the move-to-front output
+//!
+//! Runs of zeros are written in bijective base 2 with [`RUN_A`] and [`RUN_B`],
+//! other values are shifted by one, as in bzip2.
+
+use crate::*;
+
+/// Digit 1 of a run length
+pub const RUN_A: u16 = 0;
+
+/// Digit 2 of a run length
+pub const RUN_B: u16 = 1;
+
+/// Number of symbols produced by [`encode`]
+pub const ALPHABET_SIZE: usize = 257;
+
+/// Encodes runs of zeros.
+pub fn encode(input: &[u8]) -> Vec<u16> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut run = 0usize;
+    for &value in input {
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        _push_run(&mut output, run);
+        run = 0;
+        output.push(value as u16 + 1);
+    }
+    _push_run(&mut output, run);
+    output
+}
+
+/// Decodes runs of zeros.
+///
+/// Returns `DecodeError::InvalidData` if a symbol is out of the alphabet,
+/// or `DecodeError::LimitExceeded` if the output exceeds `max_size`.
+pub fn decode(input: &[u16], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut run = 0usize;
+    let mut weight = 1usize;
+    for &symbol in input {
+        if symbol <= RUN_B {
+            run = weight
+                .checked_mul(symbol as usize + 1)
+                .and_then(|v| v.checked_add(run))
+                .ok_or(DecodeError::LimitExceeded)?;
+            weight = weight.checked_mul(2).ok_or(DecodeError::LimitExceeded)?;
+            continue;
+        }
+        _flush_run(&mut output, &mut run, max_size)?;
+        weight = 1;
+        if symbol as usize >= ALPHABET_SIZE {
+            return Err(DecodeError::InvalidData);
+        }
+        if output.len() >= max_size {
+            return Err(DecodeError::LimitExceeded);
+        }
+        output.push((symbol - 1) as u8);
+    }
+    _flush_run(&mut output, &mut run, max_size)?;
+    Ok(output)
+}
+
+#[inline]
+fn _push_run(output: &mut Vec<u16>, mut run: usize) {
+    while run > 0 {
+        run -= 1;
+        output.push(if run & 1 == 0 { RUN_A } else { RUN_B });
+        run >>= 1;
+    }
+}
+
+#[inline]
+fn _flush_run(output: &mut Vec<u8>, run: &mut usize, max_size: usize) -> Result<(), DecodeError> {
+    if *run > max_size - output.len() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    output.resize(output.len() + *run, 0);
+    *run = 0;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use super::*;
+
+const BANANA: &[u8] = b"banana";
+
+#[test]
+fn bwt_transform() {
+    // rows: $, a$, ana$, anana$, banana$, na$, nana$
+    let (bwt, primary_index) = transform(BANANA);
+    assert_eq!(bwt, b"annbaa");
+    assert_eq!(primary_index, 4);
+    assert_eq!(inverse(&bwt, primary_index).unwrap(), BANANA);
+
+    for input in [
+        b"a".to_vec(),
+        b"aaaaaaaa".to_vec(),
+        b"abracadabra".to_vec(),
+        fib_str(b'a', b'b', 0x1000),
+        random_alphabet(0, 255, 0x1000),
+    ] {
+        let (bwt, primary_index) = transform(&input);
+        assert_eq!(inverse(&bwt, primary_index).unwrap(), input);
+    }
+
+    assert_eq!(transform(b""), (Vec::new(), 0));
+    assert_eq!(inverse(b"", 0).unwrap(), b"");
+    assert!(inverse(b"annbaa", 0).is_err());
+    assert!(inverse(b"annbaa", 7).is_err());
+}
+
+#[test]
+fn bwt_mtf_rle() {
+    assert_eq!(mtf::encode(b"bbbaac"), [98, 0, 0, 98, 0, 99]);
+    assert_eq!(mtf::decode(&[98, 0, 0, 98, 0, 99]), b"bbbaac");
+
+    // runs of 1 to 6 zeros are A, B, AA, BA, AB, BB
+    let input = [
+        0, 5, 0, 0, 5, 0, 0, 0, 5, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+    ];
+    let encoded = rle::encode(&input);
+    assert_eq!(encoded, [0, 6, 1, 6, 0, 0, 6, 1, 0, 6, 0, 1, 6, 1, 1]);
+    assert_eq!(rle::decode(&encoded, input.len()).unwrap(), input);
+    assert_eq!(
+        rle::decode(&encoded, input.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(rle::decode(&[257], 10), Err(DecodeError::InvalidData));
+}
+
+#[test]
+fn bwt_roundtrip() {
+    let mut input = Vec::new();
+    input.extend_from_slice(&random_alphabet(0, 255, 0x1000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x10000));
+    input.extend_from_slice(&[0; 0x1000]);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x10000));
+
+    for block_size in [0x1000, 0x8000, DEFAULT_BLOCK_SIZE] {
+        let encoded = compress_with_block_size(&input, block_size).unwrap();
+        assert!(encoded.len() < input.len() / 2);
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+
+    for len in [0, 1, 2, 100] {
+        let input = &input[..len];
+        let encoded = compress(input).unwrap();
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+
+    let encoded = compress(&input).unwrap();
+    assert_eq!(
+        decompress_with_limit(&encoded, input.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert!(decompress(&encoded[..encoded.len() / 2]).is_err());
+    assert_eq!(
+        compress_with_block_size(&input, 0),
+        Err(EncodeError::InvalidInput)
+    );
+}
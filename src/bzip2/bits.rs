@@ -0,0 +1,65 @@
+//! MSB-first bit I/O used by bzip2
+//!
+//! Thin wrappers around [`MsbBitStreamReader`] and [`MsbBitStreamWriter`] for the fields of up to 32 bits.
+
+use crate::num::bits::{MsbBitStreamReader, MsbBitStreamWriter};
+use crate::*;
+
+pub(super) struct MsbBitReader<'a>(MsbBitStreamReader<'a>);
+
+impl<'a> MsbBitReader<'a> {
+    #[inline]
+    pub fn new(input: &'a [u8]) -> Self {
+        Self(MsbBitStreamReader::new(input))
+    }
+
+    /// Reads up to 32 bits.
+    #[inline]
+    pub fn read_bits(&mut self, bits: u32) -> Result<u32, DecodeError> {
+        self.0
+            .read_bits64(bits)
+            .map(|v| v as u32)
+            .ok_or(DecodeError::UnexpectedEof)
+    }
+
+    #[inline]
+    pub fn read_bool(&mut self) -> Result<bool, DecodeError> {
+        self.0.read_bool().ok_or(DecodeError::UnexpectedEof)
+    }
+
+    #[inline]
+    pub fn skip_to_next_byte_boundary(&mut self) {
+        self.0.skip_to_next_byte_boundary();
+    }
+
+    /// Returns `true` if nothing but the padding of the current byte remains.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.0.remaining_bits() < 8
+    }
+}
+
+pub(super) struct MsbBitWriter(MsbBitStreamWriter);
+
+impl MsbBitWriter {
+    #[inline]
+    pub const fn new() -> Self {
+        Self(MsbBitStreamWriter::new())
+    }
+
+    /// Writes the lower `bits` bits of the value, up to 32 bits.
+    #[inline]
+    pub fn write_bits(&mut self, value: u32, bits: u32) {
+        self.0.push_bits64(value as u64, bits);
+    }
+
+    #[inline]
+    pub fn write_bool(&mut self, value: bool) {
+        self.0.push_bool(value);
+    }
+
+    /// Pads with zeros up to the next byte boundary and returns the bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0.into_bytes()
+    }
+}
@@ -0,0 +1,20 @@
+//! CRC-32 used by bzip2
+//!
+//! Unlike the CRC-32 of zlib, the bits are not reflected.
+
+use crate::hash::crc32::MsbFirst;
+
+type Crc = MsbFirst<0x04C1_1DB7>;
+
+/// Updates the CRC with the data.
+///
+/// Starts with [`checksum`] of an empty slice, which is `0`.
+pub fn update(crc: u32, data: &[u8]) -> u32 {
+    Crc::update(crc, data)
+}
+
+/// bzip2 CRC-32 implementation
+#[inline]
+pub fn checksum(data: &[u8]) -> u32 {
+    Crc::checksum(data)
+}
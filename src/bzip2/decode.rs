@@ -0,0 +1,275 @@
+//! bzip2 decoder
+
+use super::bits::MsbBitReader;
+use super::*;
+use crate::bwt::{mtf, rle};
+use alloc::vec;
+
+/// Decompresses concatenated bzip2 streams.
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses concatenated bzip2 streams.
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut reader = MsbBitReader::new(input);
+    let mut output = Vec::new();
+    loop {
+        _decompress_stream(&mut reader, &mut output, max_size)?;
+        if reader.is_empty() {
+            return Ok(output);
+        }
+    }
+}
+
+fn _decompress_stream(
+    reader: &mut MsbBitReader,
+    output: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), DecodeError> {
+    for magic in STREAM_MAGIC {
+        if reader.read_bits(8)? != magic as u32 {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+    }
+    let level = reader.read_bits(8)?;
+    if !(b'1' as u32..=b'9' as u32).contains(&level) {
+        return Err(DecodeError::InvalidData);
+    }
+    let max_block_size = (level - b'0' as u32) as usize * BLOCK_SIZE_UNIT;
+
+    let mut combined_crc = 0u32;
+    loop {
+        let magic = ((reader.read_bits(24)? as u64) << 24) | reader.read_bits(24)? as u64;
+        let crc = reader.read_bits(32)?;
+        match magic {
+            BLOCK_MAGIC => {
+                let block_start = output.len();
+                _decompress_block(reader, output, max_block_size, max_size)?;
+                if crc32::checksum(&output[block_start..]) != crc {
+                    return Err(DecodeError::ChecksumMismatch);
+                }
+                combined_crc = combined_crc.rotate_left(1) ^ crc;
+            }
+            END_OF_STREAM_MAGIC => {
+                if crc != combined_crc {
+                    return Err(DecodeError::ChecksumMismatch);
+                }
+                reader.skip_to_next_byte_boundary();
+                return Ok(());
+            }
+            _ => return Err(DecodeError::InvalidData),
+        }
+    }
+}
+
+fn _decompress_block(
+    reader: &mut MsbBitReader,
+    output: &mut Vec<u8>,
+    max_block_size: usize,
+    max_size: usize,
+) -> Result<(), DecodeError> {
+    let is_randomized = reader.read_bool()?;
+    let orig_ptr = reader.read_bits(24)? as usize;
+
+    // symbols in use
+    let mut seq_to_unseq = Vec::new();
+    let used_map = reader.read_bits(16)?;
+    for i in 0..16 {
+        if used_map & (0x8000 >> i) != 0 {
+            let used = reader.read_bits(16)?;
+            for j in 0..16 {
+                if used & (0x8000 >> j) != 0 {
+                    seq_to_unseq.push((i * 16 + j) as u8);
+                }
+            }
+        }
+    }
+    if seq_to_unseq.is_empty() {
+        return Err(DecodeError::InvalidData);
+    }
+    let alpha_size = seq_to_unseq.len() + 2;
+
+    let n_groups = reader.read_bits(3)? as usize;
+    if !(MIN_GROUPS..=MAX_GROUPS).contains(&n_groups) {
+        return Err(DecodeError::InvalidData);
+    }
+    let n_selectors = reader.read_bits(15)? as usize;
+    if n_selectors == 0 {
+        return Err(DecodeError::InvalidData);
+    }
+    let mut group_mtf = [0, 1, 2, 3, 4, 5];
+    let mut selectors = Vec::with_capacity(n_selectors);
+    for _ in 0..n_selectors {
+        let mut index = 0;
+        while reader.read_bool()? {
+            index += 1;
+            if index >= n_groups {
+                return Err(DecodeError::InvalidData);
+            }
+        }
+        let group = group_mtf[index];
+        group_mtf.copy_within(..index, 1);
+        group_mtf[0] = group;
+        selectors.push(group);
+    }
+
+    let mut tables = Vec::with_capacity(n_groups);
+    let mut lengths = Vec::with_capacity(alpha_size);
+    for _ in 0..n_groups {
+        lengths.clear();
+        let mut len = reader.read_bits(5)? as u8;
+        for _ in 0..alpha_size {
+            loop {
+                if !(1..=MAX_CODE_LEN).contains(&len) {
+                    return Err(DecodeError::InvalidData);
+                }
+                if !reader.read_bool()? {
+                    break;
+                }
+                if reader.read_bool()? {
+                    len -= 1;
+                } else {
+                    len += 1;
+                }
+            }
+            lengths.push(len);
+        }
+        tables.push(HuffmanTable::new(&lengths));
+    }
+
+    // Huffman coded symbols
+    let end_of_block = alpha_size as u16 - 1;
+    let max_symbols = max_block_size + 1;
+    let mut symbols = Vec::new();
+    let mut selectors = selectors.into_iter();
+    'outer: loop {
+        let table = &tables[selectors.next().ok_or(DecodeError::InvalidData)?];
+        for _ in 0..GROUP_SIZE {
+            let symbol = table.decode(reader)?;
+            if symbol == end_of_block {
+                break 'outer;
+            }
+            if symbols.len() >= max_symbols {
+                return Err(DecodeError::InvalidData);
+            }
+            symbols.push(symbol);
+        }
+    }
+
+    let indices = rle::decode(&symbols, max_block_size).map_err(|_| DecodeError::InvalidData)?;
+    let bwt = mtf::decode(&indices)
+        .into_iter()
+        .map(|v| seq_to_unseq[v as usize])
+        .collect::<Vec<_>>();
+    if orig_ptr >= bwt.len() {
+        return Err(DecodeError::InvalidData);
+    }
+
+    // inverse BWT, the rows are rotations
+    let mut buckets = [0u32; 256];
+    let mut acc = 0;
+    for (bucket, count) in buckets.iter_mut().zip(stats::histogram_of_bytes(&bwt)) {
+        *bucket = acc;
+        acc += count as u32;
+    }
+    let mut next = vec![0u32; bwt.len()];
+    for (row, &byte) in bwt.iter().enumerate() {
+        let bucket = &mut buckets[byte as usize];
+        next[*bucket as usize] = row as u32;
+        *bucket += 1;
+    }
+
+    let mut randomizer = Randomizer::new();
+    let mut row = next[orig_ptr] as usize;
+    let mut prev = None;
+    let mut run_len = 0;
+    for _ in 0..bwt.len() {
+        let mut byte = bwt[row];
+        row = next[row] as usize;
+        if is_randomized {
+            byte ^= randomizer.next_mask();
+        }
+
+        // initial run-length coding
+        if run_len == RUN_LEN_THRESHOLD {
+            if byte as usize > max_size - output.len() {
+                return Err(DecodeError::LimitExceeded);
+            }
+            output.resize(output.len() + byte as usize, prev.unwrap_or_default());
+            prev = None;
+            run_len = 0;
+            continue;
+        }
+        if prev == Some(byte) {
+            run_len += 1;
+        } else {
+            prev = Some(byte);
+            run_len = 1;
+        }
+        if output.len() >= max_size {
+            return Err(DecodeError::LimitExceeded);
+        }
+        output.push(byte);
+    }
+
+    Ok(())
+}
+
+/// Canonical Huffman table with MSB-first codes
+struct HuffmanTable {
+    /// last code of each length, or -1 if there is none
+    limit: [i32; MAX_CODE_LEN as usize + 1],
+    /// the first code of each length subtracted from the index in `perm`
+    base: [i32; MAX_CODE_LEN as usize + 1],
+    /// symbols sorted by code length
+    perm: Vec<u16>,
+    max_len: u8,
+}
+
+impl HuffmanTable {
+    fn new(lengths: &[u8]) -> Self {
+        let mut perm = (0..lengths.len() as u16).collect::<Vec<_>>();
+        perm.sort_by_key(|&v| lengths[v as usize]);
+        let max_len = lengths.iter().copied().max().unwrap_or_default();
+
+        let mut limit = [-1; MAX_CODE_LEN as usize + 1];
+        let mut base = [0; MAX_CODE_LEN as usize + 1];
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for len in 1..=max_len {
+            let count = lengths.iter().filter(|&&v| v == len).count() as i32;
+            base[len as usize] = index - code;
+            code += count;
+            index += count;
+            limit[len as usize] = code - 1;
+            code <<= 1;
+        }
+
+        Self {
+            limit,
+            base,
+            perm,
+            max_len,
+        }
+    }
+
+    #[inline]
+    fn decode(&self, reader: &mut MsbBitReader) -> Result<u16, DecodeError> {
+        let mut code = 0i32;
+        for len in 1..=self.max_len as usize {
+            code = (code << 1) | reader.read_bool()? as i32;
+            if code <= self.limit[len] {
+                return self
+                    .perm
+                    .get((self.base[len] + code) as usize)
+                    .copied()
+                    .ok_or(DecodeError::InvalidData);
+            }
+        }
+        Err(DecodeError::InvalidData)
+    }
+}
@@ -0,0 +1,285 @@
+//! bzip2 encoder
+
+use super::bits::MsbBitWriter;
+use super::*;
+use crate::bwt::{mtf, rle};
+use crate::entropy::prefix::{CanonicalPrefixCoder, CanonicalPrefixDecoder};
+use crate::lz::suffix::SuffixArray;
+use crate::num::bits::BitSize;
+use alloc::vec;
+
+/// Maximum code length written by the encoder, same as `bzip2`
+const MAX_ENCODE_CODE_LEN: BitSize = BitSize::Bit17;
+
+/// Number of refinements of the Huffman tables
+const N_ITERATIONS: usize = 4;
+
+/// Compresses the input with level 9, which uses 900k blocks.
+#[inline]
+pub fn compress(input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+    compress_with_level(input, 9)
+}
+
+/// Compresses the input with blocks of `level * 100k` bytes.
+///
+/// Returns `EncodeError::InvalidInput` if the level is not in `1..=9`.
+pub fn compress_with_level(input: &[u8], level: u8) -> Result<Vec<u8>, EncodeError> {
+    if !(1..=9).contains(&level) {
+        return Err(EncodeError::InvalidInput);
+    }
+    // same margin as bzip2
+    let max_block_size = level as usize * BLOCK_SIZE_UNIT - 19;
+
+    let mut writer = MsbBitWriter::new();
+    for magic in STREAM_MAGIC {
+        writer.write_bits(magic as u32, 8);
+    }
+    writer.write_bits((b'0' + level) as u32, 8);
+
+    let mut combined_crc = 0u32;
+    let mut cursor = 0;
+    let mut block = Vec::with_capacity(max_block_size);
+    while cursor < input.len() {
+        let start = cursor;
+        block.clear();
+        cursor = _run_length_encode(input, cursor, max_block_size, &mut block);
+        let crc = crc32::checksum(&input[start..cursor]);
+        combined_crc = combined_crc.rotate_left(1) ^ crc;
+        _write_magic(&mut writer, BLOCK_MAGIC);
+        writer.write_bits(crc, 32);
+        _compress_block(&block, &mut writer)?;
+    }
+
+    _write_magic(&mut writer, END_OF_STREAM_MAGIC);
+    writer.write_bits(combined_crc, 32);
+    Ok(writer.into_bytes())
+}
+
+#[inline]
+fn _write_magic(writer: &mut MsbBitWriter, magic: u64) {
+    writer.write_bits((magic >> 24) as u32, 24);
+    writer.write_bits(magic as u32, 24);
+}
+
+/// Appends the initial run-length coding of the input to `block` until it is full,
+/// and returns the position of the next byte.
+fn _run_length_encode(
+    input: &[u8],
+    mut cursor: usize,
+    max_block_size: usize,
+    block: &mut Vec<u8>,
+) -> usize {
+    while cursor < input.len() {
+        let byte = input[cursor];
+        let run_len = input[cursor..]
+            .iter()
+            .take(RUN_LEN_THRESHOLD + 255)
+            .take_while(|&&v| v == byte)
+            .count();
+        if run_len >= RUN_LEN_THRESHOLD {
+            if block.len() + RUN_LEN_THRESHOLD + 1 > max_block_size {
+                break;
+            }
+            block.resize(block.len() + RUN_LEN_THRESHOLD, byte);
+            block.push((run_len - RUN_LEN_THRESHOLD) as u8);
+        } else {
+            if block.len() + run_len > max_block_size {
+                break;
+            }
+            block.resize(block.len() + run_len, byte);
+        }
+        cursor += run_len;
+    }
+    cursor
+}
+
+fn _compress_block(block: &[u8], writer: &mut MsbBitWriter) -> Result<(), EncodeError> {
+    // BWT of the rotations, the suffixes of the doubled block starting in the first half are sorted as the rotations
+    let n = block.len();
+    let sa = SuffixArray::new(&[block, block].concat());
+    let mut bwt = Vec::with_capacity(n);
+    let mut orig_ptr = 0;
+    for &suffix in sa.as_slice().iter().filter(|&&v| (v as usize) < n) {
+        let suffix = suffix as usize;
+        if suffix == 0 {
+            orig_ptr = bwt.len();
+        }
+        bwt.push(block[(suffix + n - 1) % n]);
+    }
+
+    // symbols in use
+    let mut in_use = [false; 256];
+    for &byte in block {
+        in_use[byte as usize] = true;
+    }
+    let mut unseq_to_seq = [0u8; 256];
+    let mut n_in_use = 0;
+    for (byte, _) in in_use.iter().enumerate().filter(|v| *v.1) {
+        unseq_to_seq[byte] = n_in_use as u8;
+        n_in_use += 1;
+    }
+    let alpha_size = n_in_use + 2;
+    let end_of_block = alpha_size as u16 - 1;
+
+    let bwt = bwt
+        .iter()
+        .map(|&v| unseq_to_seq[v as usize])
+        .collect::<Vec<_>>();
+    let mut symbols = rle::encode(&mtf::encode(&bwt));
+    symbols.push(end_of_block);
+
+    let (selectors, tables) = _make_tables(&symbols, alpha_size);
+
+    writer.write_bool(false); // randomized
+    writer.write_bits(orig_ptr as u32, 24);
+
+    let mut used_map = 0;
+    for i in 0..16 {
+        if in_use[i * 16..i * 16 + 16].iter().any(|&v| v) {
+            used_map |= 0x8000 >> i;
+        }
+    }
+    writer.write_bits(used_map, 16);
+    for i in 0..16 {
+        if used_map & (0x8000 >> i) != 0 {
+            let mut used = 0;
+            for j in 0..16 {
+                if in_use[i * 16 + j] {
+                    used |= 0x8000 >> j;
+                }
+            }
+            writer.write_bits(used, 16);
+        }
+    }
+
+    writer.write_bits(tables.len() as u32, 3);
+    writer.write_bits(selectors.len() as u32, 15);
+    let mut group_mtf = [0, 1, 2, 3, 4, 5];
+    for &selector in selectors.iter() {
+        let index = group_mtf.iter().position(|&v| v == selector).unwrap();
+        group_mtf.copy_within(..index, 1);
+        group_mtf[0] = selector;
+        for _ in 0..index {
+            writer.write_bool(true);
+        }
+        writer.write_bool(false);
+    }
+
+    let mut codes = Vec::with_capacity(tables.len());
+    for lengths in tables.iter() {
+        let mut len = lengths[0];
+        writer.write_bits(len as u32, 5);
+        for &target in lengths.iter() {
+            while len < target {
+                writer.write_bits(0b10, 2);
+                len += 1;
+            }
+            while len > target {
+                writer.write_bits(0b11, 2);
+                len -= 1;
+            }
+            writer.write_bool(false);
+        }
+
+        let mut table = vec![(0, 0); alpha_size];
+        for (symbol, code) in CanonicalPrefixDecoder::make_prefix_table(
+            lengths.iter().enumerate().map(|(i, &v)| (i, v)),
+            false,
+        )
+        .map_err(|_| EncodeError::InternalInconsistency)?
+        {
+            table[symbol] = (code.value(), code.size().as_u32());
+        }
+        codes.push(table);
+    }
+
+    for (chunk, &selector) in symbols.chunks(GROUP_SIZE).zip(selectors.iter()) {
+        let table = &codes[selector as usize];
+        for &symbol in chunk {
+            let (code, len) = table[symbol as usize];
+            writer.write_bits(code, len);
+        }
+    }
+
+    Ok(())
+}
+
+/// Selects a Huffman table for each group of symbols, and returns the selectors and the code lengths of the tables.
+fn _make_tables(symbols: &[u16], alpha_size: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let n_groups = match symbols.len() {
+        0..200 => 2,
+        200..600 => 3,
+        600..1200 => 4,
+        1200..2400 => 5,
+        _ => MAX_GROUPS,
+    };
+
+    let mut freq_table = vec![0usize; alpha_size];
+    for &symbol in symbols {
+        freq_table[symbol as usize] += 1;
+    }
+
+    // Initially, each table covers a range of symbols with similar total frequencies.
+    let mut tables = Vec::with_capacity(n_groups);
+    let mut remaining = symbols.len();
+    let mut start = 0;
+    for group in 0..n_groups {
+        let target = remaining / (n_groups - group);
+        let mut end = start;
+        let mut acc = 0;
+        while end < alpha_size && (acc < target || end == start) {
+            acc += freq_table[end];
+            end += 1;
+        }
+        if group == n_groups - 1 {
+            end = alpha_size;
+        }
+        let lengths = (0..alpha_size)
+            .map(|v| if (start..end).contains(&v) { 1 } else { 15 })
+            .collect::<Vec<u8>>();
+        tables.push(lengths);
+        remaining = remaining.saturating_sub(acc);
+        start = end.min(alpha_size - 1);
+    }
+
+    let mut selectors = Vec::with_capacity(symbols.len().div_ceil(GROUP_SIZE));
+    for _ in 0..N_ITERATIONS {
+        selectors.clear();
+        let mut freq_tables = vec![vec![0usize; alpha_size]; n_groups];
+        for chunk in symbols.chunks(GROUP_SIZE) {
+            let (selector, _) = tables
+                .iter()
+                .enumerate()
+                .map(|(index, lengths)| {
+                    let cost = chunk
+                        .iter()
+                        .map(|&v| lengths[v as usize] as usize)
+                        .sum::<usize>();
+                    (index, cost)
+                })
+                .min_by_key(|v| v.1)
+                .unwrap();
+            selectors.push(selector as u8);
+            for &symbol in chunk {
+                freq_tables[selector][symbol as usize] += 1;
+            }
+        }
+
+        for (lengths, freq_table) in tables.iter_mut().zip(freq_tables.iter_mut()) {
+            // every symbol needs a code
+            for freq in freq_table.iter_mut() {
+                *freq = *freq * 2 + 1;
+            }
+            let prefix_table = CanonicalPrefixCoder::make_prefix_table(
+                freq_table,
+                MAX_ENCODE_CODE_LEN,
+                alpha_size,
+            );
+            for (length, code) in lengths.iter_mut().zip(prefix_table.iter()) {
+                *length = code.map(|v| v.size().as_u8()).unwrap_or(MAX_CODE_LEN);
+            }
+        }
+    }
+
+    (selectors, tables)
+}
@@ -0,0 +1,100 @@
+use super::*;
+
+const HELLO: &[u8] = b"Hello, bzip2! Hello, bzip2! Hello, bzip2!\naaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbb\n";
+
+/// `bzip2 -9` of `HELLO`
+const HELLO_BZ2: &[u8] = &[
+    0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x1a, 0x85, 0x5b, 0xd1, 0x00, 0x00,
+    0x0a, 0x5d, 0x80, 0x40, 0x10, 0x60, 0x04, 0x10, 0x00, 0x00, 0x40, 0x32, 0x24, 0xc0, 0x10, 0x20,
+    0x00, 0x31, 0x4c, 0x00, 0x00, 0xda, 0x90, 0x1a, 0x69, 0xa6, 0x8e, 0xd8, 0x2d, 0x93, 0x44, 0x9e,
+    0x1d, 0x3a, 0x83, 0x6c, 0xca, 0xe9, 0x41, 0x44, 0x94, 0x8a, 0x34, 0x7c, 0x5d, 0xc9, 0x14, 0xe1,
+    0x42, 0x40, 0x6a, 0x15, 0x6f, 0x44,
+];
+
+/// `HELLO` repeated 40 times in a randomized block, as written by old versions of bzip2
+const HELLO_RANDOMIZED_BZ2: &[u8] = &[
+    0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x66, 0xa0, 0x71, 0x43, 0x80, 0x01,
+    0x9a, 0x5d, 0x80, 0x40, 0x18, 0x60, 0x04, 0x10, 0x00, 0x00, 0x40, 0x36, 0x64, 0xc0, 0x10, 0x20,
+    0x00, 0x80, 0x14, 0xa5, 0x49, 0x8d, 0x3d, 0x53, 0xd4, 0x7a, 0x3d, 0x42, 0x00, 0x04, 0x01, 0xa1,
+    0x30, 0x4f, 0x02, 0xae, 0xc2, 0x79, 0xa9, 0x5c, 0x0a, 0xb3, 0xd8, 0xab, 0xe0, 0xab, 0x8d, 0x44,
+    0xcc, 0xa8, 0xc8, 0x8d, 0x61, 0xc8, 0xab, 0xd0, 0x98, 0x26, 0x04, 0xef, 0x10, 0xd6, 0x29, 0xc8,
+    0xab, 0x62, 0xae, 0xaa, 0x2e, 0xb0, 0x36, 0x15, 0x6e, 0x06, 0xf1, 0x4b, 0x42, 0xaf, 0xa2, 0xee,
+    0x48, 0xa7, 0x0a, 0x12, 0x0c, 0xd4, 0x0e, 0x28, 0x60,
+];
+
+#[test]
+fn bzip2_crc32() {
+    assert_eq!(crc32::checksum(b""), 0);
+    assert_eq!(crc32::checksum(b"123456789"), 0xFC89_1918);
+    assert_eq!(
+        crc32::update(crc32::checksum(b"1234"), b"56789"),
+        0xFC89_1918
+    );
+}
+
+#[test]
+fn bzip2_decompress_reference() {
+    assert_eq!(decompress(HELLO_BZ2).unwrap(), HELLO);
+    assert_eq!(decompress(HELLO_RANDOMIZED_BZ2).unwrap(), HELLO.repeat(40));
+
+    // concatenated streams
+    assert_eq!(
+        decompress(&[HELLO_BZ2, HELLO_RANDOMIZED_BZ2].concat()).unwrap(),
+        [HELLO, &HELLO.repeat(40)].concat()
+    );
+
+    // the randomization flag is the first bit after the block CRC
+    let mut input = HELLO_RANDOMIZED_BZ2.to_vec();
+    input[14] ^= 0x80;
+    assert_eq!(decompress(&input), Err(DecodeError::ChecksumMismatch));
+
+    // corrupted stream CRC
+    let mut input = HELLO_BZ2.to_vec();
+    *input.last_mut().unwrap() ^= 0x80;
+    assert_eq!(decompress(&input), Err(DecodeError::ChecksumMismatch));
+
+    assert_eq!(
+        decompress(&HELLO_BZ2[..HELLO_BZ2.len() - 8]),
+        Err(DecodeError::UnexpectedEof)
+    );
+    assert_eq!(
+        decompress_with_limit(HELLO_BZ2, HELLO.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(decompress(b"BZh0"), Err(DecodeError::InvalidData));
+    assert_eq!(
+        decompress(b"PK\x03\x04"),
+        Err(DecodeError::UnsupportedFormat)
+    );
+}
+
+#[test]
+fn bzip2_roundtrip() {
+    let mut input = Vec::new();
+    input.extend_from_slice(&random_alphabet(0, 255, 0x3000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x20000));
+    input.extend_from_slice(&[0; 0x1000]);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x20000));
+
+    for level in [1, 9] {
+        let encoded = compress_with_level(&input, level).unwrap();
+        assert!(encoded.len() < input.len() / 2);
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+
+    for len in [0, 1, 4, 5, 100] {
+        let input = &HELLO[HELLO.len() - 120..][..len];
+        let encoded = compress(input).unwrap();
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+    assert_eq!(compress(b"").unwrap().len(), 14);
+
+    // long runs are split into runs of at most 259 bytes
+    let input = [[7u8; 1000], [8; 1000]].concat();
+    assert_eq!(decompress(&compress(&input).unwrap()).unwrap(), input);
+
+    assert_eq!(
+        compress_with_level(&input, 0),
+        Err(EncodeError::InvalidInput)
+    );
+}
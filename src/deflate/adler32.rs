@@ -6,15 +6,221 @@
 //! * <https://en.wikipedia.org/wiki/Adler-32>
 //!
 
+const MOD_ADLER: u32 = 65521;
+
+/// Largest number of bytes that can be summed before `s2` may overflow `u32`
+const NMAX: usize = 5552;
+
 /// Adler-32 checksum implementation
+#[inline]
 pub fn checksum(data: &[u8]) -> u32 {
-    let mut s1 = 1u32;
-    let mut s2 = 0u32;
+    let mut hasher = Adler32::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Incremental Adler-32 checksum
+///
+/// ```
+/// use compress::deflate::adler32::{self, Adler32};
+///
+/// let mut hasher = Adler32::new();
+/// hasher.update(b"Wiki");
+/// hasher.update(b"pedia");
+/// assert_eq!(hasher.finalize(), adler32::checksum(b"Wikipedia"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adler32 {
+    s1: u32,
+    s2: u32,
+}
+
+impl Adler32 {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { s1: 1, s2: 0 }
+    }
+
+    /// Resumes the computation from a checksum of the preceding data.
+    #[inline]
+    pub const fn from_checksum(checksum: u32) -> Self {
+        Self {
+            s1: checksum & 0xffff,
+            s2: checksum >> 16,
+        }
+    }
+
+    /// Adds the data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut s1 = self.s1;
+        let mut s2 = self.s2;
+
+        for chunk in data.chunks(NMAX) {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            let chunk = {
+                let (v1, v2, rest) = sse2::sum_blocks(s1, s2, chunk);
+                s1 = v1;
+                s2 = v2;
+                rest
+            };
+            for &byte in chunk {
+                s1 += byte as u32;
+                s2 += s1;
+            }
+            s1 %= MOD_ADLER;
+            s2 %= MOD_ADLER;
+        }
+
+        self.s1 = s1;
+        self.s2 = s2;
+    }
+
+    /// Returns the checksum of the data added so far.
+    #[inline]
+    pub const fn finalize(&self) -> u32 {
+        (self.s2 << 16) | self.s1
+    }
+
+    /// Returns the checksum of the concatenation of two pieces of data,
+    /// from their checksums `a` and `b` and the length of the second piece.
+    ///
+    /// This allows the pieces to be checksummed in parallel.
+    pub const fn combine(a: u32, b: u32, len_b: usize) -> u32 {
+        let rem = (len_b % MOD_ADLER as usize) as u32;
+        let a1 = a & 0xffff;
+        let a2 = a >> 16;
+        let b1 = b & 0xffff;
+        let b2 = b >> 16;
+
+        // Every byte of the second piece adds `a1 - 1` to `s2`, since it is counted from `s1 = 1`.
+        let s1 = (a1 + b1 + MOD_ADLER - 1) % MOD_ADLER;
+        let s2 = (a2 + b2 + (rem * a1) % MOD_ADLER + MOD_ADLER - rem) % MOD_ADLER;
+        (s2 << 16) | s1
+    }
+}
+
+impl Default for Adler32 {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    /// Adds the 16-byte blocks of a chunk of at most `NMAX` bytes to the sums,
+    /// and returns the new sums and the remaining bytes.
+    ///
+    /// The sums are not reduced.
+    #[inline]
+    pub fn sum_blocks(s1: u32, s2: u32, chunk: &[u8]) -> (u32, u32, &[u8]) {
+        let blocks = chunk.chunks_exact(16);
+        let rest = blocks.remainder();
+        let n_blocks = blocks.len() as u32;
+        unsafe {
+            // Safety: SSE2 is always available on x86_64, and the loads are unaligned.
+            let zero = _mm_setzero_si128();
+            let weights_first = _mm_setr_epi16(16, 15, 14, 13, 12, 11, 10, 9);
+            let weights_second = _mm_setr_epi16(8, 7, 6, 5, 4, 3, 2, 1);
+            // sum of the bytes
+            let mut v_s1 = zero;
+            // sum of `v_s1` before each block
+            let mut v_ps = zero;
+            // sum of the bytes weighted by the distance from the end of the block
+            let mut v_s2 = zero;
+            for block in blocks {
+                let bytes = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+                v_ps = _mm_add_epi32(v_ps, v_s1);
+                v_s1 = _mm_add_epi32(v_s1, _mm_sad_epu8(bytes, zero));
+                let first = _mm_madd_epi16(_mm_unpacklo_epi8(bytes, zero), weights_first);
+                let second = _mm_madd_epi16(_mm_unpackhi_epi8(bytes, zero), weights_second);
+                v_s2 = _mm_add_epi32(v_s2, _mm_add_epi32(first, second));
+            }
+            let sum_s1 = _hsum(v_s1);
+            let sum_s2 = 16 * n_blocks * s1 + 16 * _hsum(v_ps) + _hsum(v_s2);
+            (s1 + sum_s1, s2 + sum_s2, rest)
+        }
+    }
+
+    #[inline]
+    unsafe fn _hsum(v: __m128i) -> u32 {
+        unsafe {
+            let v = _mm_add_epi32(v, _mm_shuffle_epi32(v, 0b01_00_11_10));
+            let v = _mm_add_epi32(v, _mm_shuffle_epi32(v, 0b10_11_00_01));
+            _mm_cvtsi128_si32(v) as u32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _checksum_bytewise(data: &[u8]) -> u32 {
+        let mut s1 = 1u32;
+        let mut s2 = 0u32;
+        for &byte in data {
+            s1 = (s1 + byte as u32) % MOD_ADLER;
+            s2 = (s2 + s1) % MOD_ADLER;
+        }
+        (s2 << 16) | s1
+    }
+
+    #[test]
+    fn adler32() {
+        assert_eq!(checksum(b""), 1);
+        assert_eq!(checksum(b"Wikipedia"), 0x11e6_0398);
 
-    for &byte in data {
-        s1 = (s1 + byte as u32) % 65521;
-        s2 = (s2 + s1) % 65521;
+        let mut input = vec![0xff; NMAX * 3 + 17];
+        input.extend((0..0x12345u32).map(|v| (v.wrapping_mul(0x9e37_79b1) >> 24) as u8));
+        for len in [
+            1,
+            15,
+            16,
+            17,
+            31,
+            32,
+            NMAX - 1,
+            NMAX,
+            NMAX + 1,
+            NMAX * 3 + 17,
+            input.len(),
+        ] {
+            assert_eq!(checksum(&input[..len]), _checksum_bytewise(&input[..len]));
+            assert_eq!(
+                checksum(&input[input.len() - len..]),
+                _checksum_bytewise(&input[input.len() - len..])
+            );
+        }
     }
 
-    (s2 << 16) | s1
+    #[test]
+    fn adler32_incremental() {
+        let input = (0..100_000u32)
+            .map(|v| (v.wrapping_mul(0x9e37_79b1) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let expected = checksum(&input);
+
+        let mut hasher = Adler32::default();
+        for chunk in input.chunks(777) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), expected);
+
+        for split in [0, 1, 15, 16, 777, NMAX + 3, 65521, 65522, input.len()] {
+            let (a, b) = input.split_at(split);
+            assert_eq!(
+                Adler32::combine(checksum(a), checksum(b), b.len()),
+                expected,
+                "split {split}"
+            );
+
+            let mut hasher = Adler32::from_checksum(checksum(a));
+            hasher.update(b);
+            assert_eq!(hasher.finalize(), expected);
+        }
+        assert_eq!(Adler32::combine(1, 1, 0), 1);
+    }
 }
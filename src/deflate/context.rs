@@ -0,0 +1,80 @@
+//! Reusable buffers of the compressor and the decompressor
+
+use super::*;
+use deflate::{_deflate, PrefixTables};
+use inflate::_inflate_into;
+use lz::cache::OffsetCacheTables;
+
+/// Buffers reused by repeated calls of the compressor and the decompressor
+///
+/// Compressing many small messages with [`deflate`] allocates the buffers of the LZ parse, the offset cache,
+/// the prefix tables and the output for each call.
+/// A context keeps them between the calls, and returns the output borrowed from itself.
+/// The suffix arrays of the optimal parse and the tables of the threads are still allocated for each call.
+///
+/// ```
+/// use compress::deflate::{CompressionLevel, DeflateContext};
+///
+/// let mut context = DeflateContext::new();
+/// for message in [b"Hello, world!".as_slice(), b"Hello, deflate!"] {
+///     let compressed = context.deflate(message, CompressionLevel::Default, None).unwrap().to_vec();
+///     assert_eq!(context.inflate(&compressed, message.len()).unwrap(), message);
+/// }
+/// ```
+#[derive(Default)]
+pub struct DeflateContext {
+    /// Intermediate representation of the LZ parse
+    pub(super) ir: Vec<DeflateLZIR>,
+    /// Compressed output
+    pub(super) output: Vec<u8>,
+    /// Blocks encoded to compare their sizes
+    pub(super) trial: Vec<u8>,
+    /// Decompressed output
+    pub(super) decoded: Vec<u8>,
+    /// Tables of the offset cache of the LZ parse
+    pub(super) offsets: OffsetCacheTables,
+    /// Prefix tables of the blocks
+    pub(super) prefix_tables: PrefixTables,
+}
+
+impl DeflateContext {
+    /// Creates a context with empty buffers, which grow on the first calls.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            ir: Vec::new(),
+            output: Vec::new(),
+            trial: Vec::new(),
+            decoded: Vec::new(),
+            offsets: OffsetCacheTables::new(),
+            prefix_tables: PrefixTables::new(),
+        }
+    }
+
+    /// Compresses the input like [`deflate`], and returns the output kept until the next call.
+    pub fn deflate(
+        &mut self,
+        input: &[u8],
+        level: CompressionLevel,
+        options: Option<OptionConfig>,
+    ) -> Result<&[u8], EncodeError> {
+        _deflate(self, input, &[], level, options, None, None)?;
+        Ok(&self.output)
+    }
+
+    /// Decompresses a deflate stream like [`inflate`], and returns the output kept until the next call.
+    pub fn inflate(&mut self, input: &[u8], decode_size: usize) -> Result<&[u8], DecodeError> {
+        self.decoded.clear();
+        self.decoded
+            .try_reserve(decode_size)
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        self.decoded.resize(decode_size, 0);
+        _inflate_into(
+            input,
+            &mut self.decoded,
+            &InflateLimits::NONE,
+            InflateOptions::new(),
+        )?;
+        Ok(&self.decoded)
+    }
+}
@@ -2,16 +2,54 @@
 
 use super::*;
 use core::f64::{self, INFINITY};
-use entropy::entropy_of;
-use entropy::prefix::{CanonicalPrefixCoder, CanonicalPrefixDecoder, PermutationFlavor};
+use core::num::NonZero;
+use core::ops::{ControlFlow, Range};
+use entropy::prefix::{
+    CanonicalPrefixCoder, CanonicalPrefixDecoder, EncodedPrefixTable, PermutationFlavor,
+};
 use lz::Match;
+use lz::cache::OffsetCacheTables;
 use lz::lzss::{self, LZSS};
 use num::bits::{BitStreamWriter, Write};
 use num::math;
+use stats::Histogram;
 
 /// Minimum block size in literals
 const MIN_BLOCK_SIZE: usize = 16 * 1024;
 
+/// Block size in literals for fast levels
+const FAST_BLOCK_SIZE: usize = 32 * 1024;
+
+/// Number of elements of the smallest blocks merged by the block splitter of `CompressionLevel::Best`
+const SPLIT_UNIT_SIZE: usize = 1024;
+
+/// Segment size in bytes for which `CompressionLevel::Auto` selects a level
+const AUTO_SEGMENT_SIZE: usize = 128 * 1024;
+
+/// Window size in bytes for detecting changes in the distribution of the input
+const SPLIT_WINDOW_SIZE: usize = 8 * 1024;
+
+/// Step in bytes between candidate block boundaries
+const SPLIT_STEP: usize = 1024;
+
+/// Minimum estimated gain in bits to start a new block, about the cost of a dynamic block header
+const SPLIT_MIN_GAIN: f64 = 1024.0;
+
+/// Minimum size in bytes of the segments encoded by each thread
+const PARALLEL_MIN_SEGMENT_SIZE: usize = 256 * 1024;
+
+/// Window size in bytes of the rolling sum that finds the boundaries of the rsyncable mode
+const RSYNC_WINDOW_SIZE: usize = 4096;
+
+/// Minimum distance in bytes between the boundaries of the rsyncable mode
+const RSYNC_MIN_SEGMENT_SIZE: usize = 4096;
+
+/// Placeholder of the zlib trailer written by [`OptionConfig::skip_checksum`]
+pub const SKIPPED_CHECKSUM: u32 = 0;
+
+/// Maximum length of a stored block
+const MAX_STORED_BLOCK_SIZE: usize = 0xffff;
+
 /// Threshold for static vs dynamic encoding
 const THRESHOLD_STATIC: usize = 4096;
 
@@ -24,74 +62,568 @@ pub fn deflate_zlib(
     deflate(input, level, options.unwrap_or_default().zlib().into())
 }
 
+#[inline]
 pub fn deflate(
     input: &[u8],
     level: CompressionLevel,
     options: Option<OptionConfig>,
 ) -> Result<Vec<u8>, EncodeError> {
+    _deflate_to_vec(input, &[], level, options, None, None)
+}
+
+/// Compresses the input like [`deflate`], reporting the progress to the hook.
+///
+/// `Progress::consumed` reaches the size of the input when the LZ parse is finished,
+/// and `Progress::emitted` grows as the blocks are written after that.
+/// Returning `ControlFlow::Break` from the hook stops the encoder with `EncodeError::Cancelled`.
+#[inline]
+pub fn deflate_with_progress(
+    input: &[u8],
+    level: CompressionLevel,
+    options: Option<OptionConfig>,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> Result<Vec<u8>, EncodeError> {
+    _deflate_to_vec(input, &[], level, options, Some(progress), None)
+}
+
+/// Compresses the input like [`deflate`], and returns the statistics of each block with the output.
+///
+/// The statistics tell the decisions of the encoder without parsing the output again (see also [`inspect`]).
+#[inline]
+pub fn deflate_with_stats(
+    input: &[u8],
+    level: CompressionLevel,
+    options: Option<OptionConfig>,
+) -> Result<(Vec<u8>, Vec<BlockStats>), EncodeError> {
+    let mut stats = Vec::new();
+    let output = _deflate_to_vec(input, &[], level, options, None, Some(&mut stats))?;
+    Ok((output, stats))
+}
+
+/// Compresses the input with a preset dictionary, like `deflateSetDictionary` of zlib.
+///
+/// The matches may refer to the last window of the dictionary, which is not emitted to the output,
+/// so the decoder needs the same dictionary (see [`inflate_with_dictionary`]).
+/// The zlib format records the Adler-32 checksum of the dictionary in the header.
+/// Returns `EncodeError::InvalidInput` if the input is empty and the level compresses it, as [`deflate`] does.
+#[inline]
+pub fn deflate_with_dictionary(
+    input: &[u8],
+    dictionary: &[u8],
+    level: CompressionLevel,
+    options: Option<OptionConfig>,
+) -> Result<Vec<u8>, EncodeError> {
+    _deflate_to_vec(input, dictionary, level, options, None, None)
+}
+
+/// Estimates the size of the raw deflate output of the input without encoding it.
+///
+/// Only the LZ parse of `CompressionLevel::Fastest` runs, and the size of each block is estimated
+/// from the entropy of its symbols and the extra bits of lengths and distances,
+/// ignoring the block headers and the Huffman tables.
+/// The estimate does not exceed the size of the input stored in stored blocks,
+/// so it tells whether compressing the input is worth it.
+pub fn estimate_compressed_size(input: &[u8]) -> usize {
+    let stored_size = input.len() + input.len().div_ceil(MAX_STORED_BLOCK_SIZE).max(1) * 5;
+    if input.is_empty() {
+        return stored_size;
+    }
+
+    let config = Configuration {
+        level: CompressionLevel::Fastest,
+        window_size: WindowSize::preferred_for(input.len()),
+    };
+    let mut buff = Vec::new();
+    let result = LZSS::encode_fast(input, config.lzss_config(), |lzss| {
+        buff.push(DeflateLZIR::from_lzss(lzss));
+        Ok(())
+    });
+    if result.is_err() {
+        return stored_size;
+    }
+
+    let estimated_size = buff
+        .chunks(config.block_size())
+        .map(|chunk| {
+            let extra_bits = chunk
+                .iter()
+                .map(|v| {
+                    v.length_extra_bit_size().map_or(0, |v| v.as_usize())
+                        + v.distance_extra_bit_size().map_or(0, |v| v.as_usize())
+                })
+                .sum::<usize>();
+            DeflateIrBlock::new(chunk).estimated_size() + extra_bits.div_ceil(8)
+        })
+        .sum::<usize>();
+    estimated_size.min(stored_size)
+}
+
+fn _deflate_to_vec(
+    input: &[u8],
+    dictionary: &[u8],
+    level: CompressionLevel,
+    options: Option<OptionConfig>,
+    progress: Option<&mut dyn FnMut(Progress) -> ControlFlow<()>>,
+    stats: Option<&mut Vec<BlockStats>>,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut context = DeflateContext::new();
+    _deflate(
+        &mut context,
+        input,
+        dictionary,
+        level,
+        options,
+        progress,
+        stats,
+    )?;
+    Ok(context.output)
+}
+
+/// Compresses the input into `context.output`, reusing the buffers of the context.
+pub(super) fn _deflate(
+    context: &mut DeflateContext,
+    input: &[u8],
+    dictionary: &[u8],
+    level: CompressionLevel,
+    options: Option<OptionConfig>,
+    progress: Option<&mut dyn FnMut(Progress) -> ControlFlow<()>>,
+    mut stats: Option<&mut Vec<BlockStats>>,
+) -> Result<(), EncodeError> {
+    let mut progress = ProgressReporter::new(progress, input.len());
     let mut config = Configuration::DEFAULT;
     config.level = level;
-    config.window_size = WindowSize::preferred_for(input.len());
     let options = options.unwrap_or_default();
+    config.window_size = options.select_window_size(input);
+    if options.window_size.is_none() && !dictionary.is_empty() {
+        // The matches may also refer to the dictionary.
+        config.window_size = config
+            .window_size
+            .max(WindowSize::preferred_for(dictionary.len() + input.len()));
+    }
+
+    let DeflateContext {
+        ir: buff,
+        output: output_buf,
+        trial,
+        offsets,
+        prefix_tables,
+        ..
+    } = context;
+    output_buf.clear();
+    let mut output = BitStreamWriter::with_sink(core::mem::take(output_buf));
+    if options.is_zlib {
+        let mut header = zlib::Header::new(config.window_size, config.level);
+        if !dictionary.is_empty() {
+            header = header.with_dictionary(dictionary);
+        }
+        output.write(&header.to_bytes()[..header.size()]);
+    }
+
+    // The output buffer goes back to the context even if the encoding fails, so that the next call reuses it.
+    let encode = |output: &mut BitStreamWriter| -> Result<(), EncodeError> {
+        if config.level.is_store_only()
+            || (options.store_compressed
+                && stats::classify(input) == stats::ContentClass::AlreadyCompressed)
+        {
+            let bit_start = output.bit_count();
+            encode_stored(output, input, true);
+            if let Some(stats) = stats {
+                stats.push(BlockStats::stored(
+                    input.len(),
+                    output.bit_count() - bit_start,
+                ));
+            }
+            return Ok(());
+        }
+
+        // The input follows the last window of the dictionary, and starts at `start` in `data`.
+        let dictionary = &dictionary[dictionary.len().saturating_sub(config.window_size.value())..];
+        let primed;
+        let (data, start) = if dictionary.is_empty() {
+            (input, 0)
+        } else {
+            if input.is_empty() {
+                // The parse of the dictionary alone emits nothing, like the empty input without a dictionary.
+                return Err(EncodeError::InvalidInput);
+            }
+            primed = [dictionary, input].concat();
+            (primed.as_slice(), dictionary.len())
+        };
 
-    let mut buff = Vec::with_capacity(config.window_size.value());
+        buff.clear();
+        buff.reserve(config.window_size.value());
 
-    if options.use_experimental_encoder && matches!(config.level, CompressionLevel::Best) {
-        LZSS::encode_sa_lcp(input, config.lzss_config(), |lzss| {
+        // Input positions where the distribution changes, and the corresponding indices in `buff`
+        let splits = if options.rsyncable {
+            stats::content_defined_boundaries(input, RSYNC_WINDOW_SIZE, RSYNC_MIN_SEGMENT_SIZE)
+        } else if config.level.is_fast_method() || config.level.is_best_method() {
+            // The best level splits the blocks by itself.
+            Vec::new()
+        } else {
+            stats::suggest_splits(input, SPLIT_WINDOW_SIZE, SPLIT_STEP, SPLIT_MIN_GAIN)
+        };
+        let mut next_split = splits.iter().copied().peekable();
+        let mut cuts = Vec::with_capacity(splits.len());
+        let mut position = 0;
+        let mut emit = |lzss: LZSS| {
+            if next_split.next_if(|&split| position >= split).is_some() {
+                cuts.push(buff.len());
+            }
+            position += match lzss {
+                LZSS::Literal(_) => 1,
+                LZSS::Match(matches) => matches.len.get(),
+            };
             buff.push(DeflateLZIR::from_lzss(lzss));
-            Ok(())
-        })?;
+            progress.advance(position, 0)
+        };
+
+        if options.rsyncable {
+            // Each segment is encoded without referring to the preceding segments,
+            // except that the first segment refers to the dictionary.
+            let mut segment_start = 0;
+            for end in splits.iter().copied().chain(Some(input.len())) {
+                if segment_start == 0 {
+                    _encode_segment(
+                        data,
+                        start..start + end,
+                        &config,
+                        &options,
+                        offsets,
+                        &mut emit,
+                    )?;
+                } else {
+                    let segment = &input[segment_start..end];
+                    _encode_segment(
+                        segment,
+                        0..segment.len(),
+                        &config,
+                        &options,
+                        offsets,
+                        &mut emit,
+                    )?;
+                }
+                segment_start = end;
+            }
+        } else if config.level.is_auto() {
+            if start == data.len() {
+                // The other levels reject the empty input in the LZ parse.
+                return Err(EncodeError::InvalidInput);
+            }
+            // Select the level for each segment, referring to the preceding data as a dictionary.
+            let window_size = config.window_size.value();
+            for segment_start in (start..data.len()).step_by(AUTO_SEGMENT_SIZE) {
+                let end = (segment_start + AUTO_SEGMENT_SIZE).min(data.len());
+                let dict_start = segment_start.saturating_sub(window_size);
+                let segment_config = Configuration {
+                    level: CompressionLevel::auto_select(&data[segment_start..end]),
+                    window_size: config.window_size,
+                };
+                // The segments do not estimate the symbol costs, so `CompressionLevel::Best` keeps the lazy parse here.
+                let lzss_config = segment_config.lzss_config();
+                let parser = match lzss_config.parser {
+                    lzss::Parser::Optimal => lzss::Parser::Lazy,
+                    parser => parser,
+                };
+                LZSS::encode_with_tables(
+                    &data[dict_start..end],
+                    lzss_config
+                        .parser(parser)
+                        .dictionary_len(segment_start - dict_start),
+                    offsets,
+                    &mut emit,
+                )?;
+            }
+        } else if options.effective_threads() > 1
+            && cfg!(feature = "threads")
+            && input.len() >= PARALLEL_MIN_SEGMENT_SIZE * 2
+        {
+            #[cfg(feature = "threads")]
+            _encode_parallel(data, start..data.len(), &config, &options, &mut emit)?;
+        } else {
+            _encode_segment(
+                data,
+                start..data.len(),
+                &config,
+                &options,
+                offsets,
+                &mut emit,
+            )?;
+        }
+
+        let mut blocks = Vec::new();
+        // Numbers of blocks after which the output is aligned to a byte boundary
+        let mut sync_points = Vec::new();
+        let mut block_start = 0;
+        for cut in cuts.into_iter().chain(Some(buff.len())) {
+            if config.level.is_best_method() {
+                blocks.extend(DeflateIrBlock::split(&buff[block_start..cut]));
+            } else {
+                for chunk in buff[block_start..cut].chunks(config.block_size()) {
+                    blocks.push(DeflateIrBlock::new(chunk));
+                }
+            }
+            block_start = cut;
+            if options.rsyncable {
+                sync_points.push(blocks.len());
+            }
+        }
+        let Some(last) = blocks.last_mut() else {
+            panic!("Internal error: no blocks generated");
+            // return Err(EncodeError::InternalInconsistency);
+        };
+        last.is_final = true;
+
+        let mut input_pos = 0;
+        for (index, block) in blocks.into_iter().enumerate() {
+            let input_len = block.input_len();
+            let data = &input[input_pos..input_pos + input_len];
+            input_pos += input_len;
+
+            // Returns the size in bits of the block encoded into the trial buffer.
+            let mut trial_bit_count = |use_static: bool| {
+                trial.clear();
+                let mut writer = BitStreamWriter::with_sink(core::mem::take(trial));
+                block._encode_reusing(&mut writer, use_static, prefix_tables);
+                let bit_count = writer.bit_count();
+                *trial = writer.into_sink();
+                bit_count
+            };
+            let (use_static, bit_count) =
+                if !config.level.is_fast_method() && block.estimated_size() < THRESHOLD_STATIC {
+                    let static_bit_count = trial_bit_count(true);
+                    let dynamic_bit_count = trial_bit_count(false);
+
+                    // choose the smaller one
+                    if static_bit_count < dynamic_bit_count {
+                        (true, Some(static_bit_count))
+                    } else {
+                        (false, Some(dynamic_bit_count))
+                    }
+                } else if block.estimated_size() < THRESHOLD_STATIC
+                    || block.estimated_size() * 8 >= input_len * 7
+                {
+                    // The data is small or hardly compressible, so a stored block may be smaller.
+                    (false, Some(trial_bit_count(false)))
+                } else {
+                    (false, None)
+                };
+
+            let bit_start = output.bit_count();
+            let block_type = if let Some(bit_count) = bit_count
+                && stored_bit_count(output.bit_count(), input_len) < bit_count
+            {
+                encode_stored(output, data, block.is_final());
+                BlockType::Stored
+            } else {
+                block._encode_reusing(output, use_static, prefix_tables);
+                if use_static {
+                    BlockType::Fixed
+                } else {
+                    BlockType::Dynamic
+                }
+            };
+            if let Some(stats) = stats.as_mut() {
+                stats.push(BlockStats::new(
+                    &block,
+                    block_type,
+                    output.bit_count() - bit_start,
+                ));
+            }
+
+            if !block.is_final() && sync_points.binary_search(&(index + 1)).is_ok() {
+                // An empty stored block, as a sync flush of zlib does
+                encode_stored(output, &[], false);
+            }
+            progress.report(input.len(), output.bit_count() / 8)?;
+        }
+
+        Ok(())
+    };
+    let result = encode(&mut output);
+    *output_buf = match result {
+        Ok(()) => _finish(output, input, &options),
+        Err(_) => output.into_sink(),
+    };
+    result?;
+    progress.report(input.len(), output_buf.len())?;
+    Ok(())
+}
+
+/// Encodes `input[range]` with the LZSS encoder selected by the level,
+/// referring to the preceding window as a dictionary.
+///
+/// The greedy and lazy parsers reuse the tables of the offset cache.
+fn _encode_segment<F>(
+    input: &[u8],
+    range: Range<usize>,
+    config: &Configuration,
+    options: &OptionConfig,
+    tables: &mut OffsetCacheTables,
+    mut f: F,
+) -> Result<(), EncodeError>
+where
+    F: FnMut(LZSS) -> Result<(), EncodeError>,
+{
+    let dict_start = range.start.saturating_sub(config.window_size.value());
+    let input = &input[dict_start..range.end];
+    let lzss_config = || {
+        config
+            .lzss_config()
+            .dictionary_len(range.start - dict_start)
+    };
+
+    if options.use_experimental_encoder
+        && matches!(
+            config.level,
+            CompressionLevel::Level7 | CompressionLevel::Level8 | CompressionLevel::Best
+        )
+    {
+        LZSS::encode_sa_lcp(input, lzss_config(), f)
+    } else if matches!(config.level, CompressionLevel::Best) && options.iterations > 1 {
+        // Each iteration re-estimates the symbol costs from the previous parse,
+        // and the parse with the lowest estimated cost is emitted.
+        let mut tokens = Vec::new();
+        LZSS::encode_with_tables(
+            input,
+            lzss_config().parser(lzss::Parser::Lazy),
+            tables,
+            |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            },
+        )?;
+        let mut best: Option<(u64, Vec<LZSS>)> = None;
+        for _ in 0..options.iterations {
+            let cost_model = DeflateCostModel::from_lzss(&tokens);
+            let mut parse = Vec::with_capacity(tokens.len());
+            LZSS::encode_optimal(input, lzss_config(), &cost_model, |lzss| {
+                parse.push(lzss);
+                Ok(())
+            })?;
+            let cost = DeflateCostModel::from_lzss(&parse).total_cost(&parse);
+            if best.as_ref().is_none_or(|v| cost < v.0) {
+                best = Some((cost, parse.clone()));
+            }
+            tokens = parse;
+        }
+        for lzss in best.map(|v| v.1).unwrap_or_default() {
+            f(lzss)?;
+        }
+        Ok(())
+    } else if matches!(config.level, CompressionLevel::Best) {
+        // The first pass estimates the symbol costs for the optimal parse.
+        let mut first_pass = Vec::new();
+        LZSS::encode_with_tables(
+            input,
+            lzss_config().parser(lzss::Parser::Lazy),
+            tables,
+            |lzss| {
+                first_pass.push(DeflateLZIR::from_lzss(lzss));
+                Ok(())
+            },
+        )?;
+        let cost_model = DeflateCostModel::new(&first_pass);
+        drop(first_pass);
+        LZSS::encode_optimal(input, lzss_config(), &cost_model, f)
+    } else if matches!(config.level, CompressionLevel::Fastest) {
+        LZSS::encode_fast_with_tables(input, lzss_config(), tables, f)
     } else {
-        LZSS::encode(input, config.lzss_config(), |lzss| {
-            buff.push(DeflateLZIR::from_lzss(lzss));
-            Ok(())
-        })?;
+        LZSS::encode_with_tables(input, lzss_config(), tables, f)
     }
+}
 
-    let mut blocks = buff
-        .chunks(MIN_BLOCK_SIZE)
-        .map(|chunk| DeflateIrBlock::new(chunk))
-        .collect::<Vec<_>>();
-    let Some(last) = blocks.last_mut() else {
-        panic!("Internal error: no blocks generated");
-        // return Err(EncodeError::InternalInconsistency);
-    };
-    last.is_final = true;
+/// Splits `input[range]` into segments and encodes them concurrently.
+///
+/// Each segment is primed with the preceding window, so the elements are emitted as a single stream in order.
+#[cfg(feature = "threads")]
+fn _encode_parallel<F>(
+    input: &[u8],
+    range: Range<usize>,
+    config: &Configuration,
+    options: &OptionConfig,
+    mut f: F,
+) -> Result<(), EncodeError>
+where
+    F: FnMut(LZSS) -> Result<(), EncodeError>,
+{
+    let segment_size = range
+        .len()
+        .div_ceil(options.effective_threads())
+        .max(PARALLEL_MIN_SEGMENT_SIZE);
 
-    let mut output = BitStreamWriter::new();
-    if options.is_zlib {
-        let cmf = ((config.window_size as u8) << 4) | 0x08;
-        let mut flg = config.level.zlib_flevel() << 6;
-        let fcheck = 31 - (cmf as u16 * 256 + flg as u16) % 31;
-        flg |= fcheck as u8;
-        output.push_byte(cmf);
-        output.push_byte(flg);
-    }
-
-    for block in blocks {
-        if !config.level.is_fast_method() && block.estimated_size() < THRESHOLD_STATIC {
-            let mut ref_static = BitStreamWriter::new();
-            block.encode(&mut ref_static, true);
-            let mut ref_dynamic = BitStreamWriter::new();
-            block.encode(&mut ref_dynamic, false);
-
-            // choose the smaller one
-            block.encode(
-                &mut output,
-                ref_static.bit_count() < ref_dynamic.bit_count(),
-            );
-        } else {
-            block.encode(&mut output, false);
+    let results = std::thread::scope(|scope| {
+        let handles = range
+            .clone()
+            .step_by(segment_size)
+            .map(|start| {
+                let end = (start + segment_size).min(range.end);
+                scope.spawn(move || {
+                    // Each thread has its own tables, which are not kept by the context.
+                    let mut tables = OffsetCacheTables::new();
+                    let mut elements = Vec::new();
+                    _encode_segment(input, start..end, config, options, &mut tables, |lzss| {
+                        elements.push(lzss);
+                        Ok(())
+                    })
+                    .map(|_| elements)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| EncodeError::InternalInconsistency)?
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for elements in results {
+        for lzss in elements? {
+            f(lzss)?;
         }
     }
+    Ok(())
+}
 
+/// Writes the zlib trailer if needed and returns the output.
+fn _finish(mut output: BitStreamWriter, input: &[u8], options: &OptionConfig) -> Vec<u8> {
     if options.is_zlib {
         output.skip_to_next_byte_boundary();
-        let adler32 = adler32::checksum(input);
+        let adler32 = if options.skip_checksum {
+            SKIPPED_CHECKSUM
+        } else {
+            adler32::checksum(input)
+        };
         output.write(&adler32.to_be_bytes() as &[u8]);
     }
 
-    Ok(output.into_bytes())
+    output.into_bytes()
+}
+
+/// Returns the size in bits of stored blocks of `len` bytes written at `bit_position`.
+pub(super) fn stored_bit_count(bit_position: usize, len: usize) -> usize {
+    let n_blocks = len.div_ceil(MAX_STORED_BLOCK_SIZE).max(1);
+    // header and padding of the first block
+    let first = (bit_position + 3).next_multiple_of(8) - bit_position;
+    first + (n_blocks - 1) * 8 + n_blocks * 32 + len * 8
+}
+
+/// Writes the data as stored (uncompressed) blocks, splitting it at 65535 bytes.
+pub(super) fn encode_stored(output: &mut BitStreamWriter, data: &[u8], is_final: bool) {
+    let n_blocks = data.len().div_ceil(MAX_STORED_BLOCK_SIZE).max(1);
+    for index in 0..n_blocks {
+        let chunk = &data[(index * MAX_STORED_BLOCK_SIZE).min(data.len())
+            ..((index + 1) * MAX_STORED_BLOCK_SIZE).min(data.len())];
+        let len = chunk.len() as u16;
+        output.write(is_final && index + 1 == n_blocks); // bfinal
+        output.write(VarLenInteger::new_truncated(BitSize::Bit2, 0b00)); // btype
+        output.skip_to_next_byte_boundary();
+        output.write(&len.to_le_bytes() as &[u8]);
+        output.write(&(!len).to_le_bytes() as &[u8]);
+        output.write(chunk);
+    }
 }
 
 /// Intermediate Representation of deflate data
@@ -151,6 +683,19 @@ impl DeflateLZIR {
         self.0 >> 19
     }
 
+    /// Returns the number of input bytes represented by this element.
+    #[inline]
+    pub fn input_len(&self) -> usize {
+        match self.literal_value() {
+            0..=255 => 1,
+            lit_len @ 257..=285 => {
+                VARIABLE_LENGTH_BASE_TABLE[(lit_len - 257) as usize].1 as usize
+                    + self.length_extra_bits_raw() as usize
+            }
+            _ => 0,
+        }
+    }
+
     #[inline]
     pub fn length_extra_bit_size(&self) -> Option<BitSize> {
         match self.literal_value() {
@@ -162,7 +707,7 @@ impl DeflateLZIR {
     #[inline]
     pub fn length_extra_bits(&self) -> Option<VarLenInteger> {
         self.length_extra_bit_size()
-            .map(|size| VarLenInteger::new(size, self.length_extra_bits_raw()))
+            .map(|size| VarLenInteger::new_checked(size, self.length_extra_bits_raw()).unwrap())
     }
 
     #[inline]
@@ -176,7 +721,86 @@ impl DeflateLZIR {
     #[inline]
     pub fn distance_extra_bits(&self) -> Option<VarLenInteger> {
         self.distance_extra_bit_size()
-            .map(|size| VarLenInteger::new(size, self.distance_extra_bits_raw()))
+            .map(|size| VarLenInteger::new_checked(size, self.distance_extra_bits_raw()).unwrap())
+    }
+}
+
+/// Statistics of a block written by the encoder, returned by [`deflate_with_stats`]
+///
+/// The counts are those of the LZ parse, even if the block is written as a stored block.
+/// A stored block longer than 65535 bytes is written as several blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockStats {
+    pub block_type: BlockType,
+    pub is_final: bool,
+    /// Number of input bytes covered by the block
+    pub input_len: usize,
+    pub literals: usize,
+    pub matches: usize,
+    /// Number of matches of each length, indexed by the length `3..=258`
+    pub length_histogram: Box<Histogram<259>>,
+    /// Number of matches of each distance code
+    pub distance_histogram: Histogram<30>,
+    /// Size of the block in bits, including the header
+    pub bit_count: usize,
+}
+
+impl BlockStats {
+    fn new(block: &DeflateIrBlock, block_type: BlockType, bit_count: usize) -> Self {
+        let mut stats = Self::stored(0, bit_count);
+        stats.block_type = block_type;
+        stats.is_final = block.is_final();
+        for element in block.block.iter() {
+            stats.input_len += element.input_len();
+            if element.literal_value() < 256 {
+                stats.literals += 1;
+            } else {
+                stats.matches += 1;
+                stats.length_histogram.add(element.input_len());
+                stats
+                    .distance_histogram
+                    .add(element.distance_value() as usize);
+            }
+        }
+        stats
+    }
+
+    /// Statistics of the stored blocks of the whole input
+    fn stored(input_len: usize, bit_count: usize) -> Self {
+        Self {
+            block_type: BlockType::Stored,
+            is_final: true,
+            input_len,
+            literals: input_len,
+            matches: 0,
+            length_histogram: Box::new(Histogram::new()),
+            distance_histogram: Histogram::new(),
+            bit_count,
+        }
+    }
+}
+
+/// Prefix tables of the literal/length and distance codes of a block,
+/// whose allocations are reused by the following blocks
+///
+/// The tables of the fixed codes are made once, and kept for the static blocks.
+#[derive(Default)]
+pub(super) struct PrefixTables {
+    pub(super) lit: Vec<Option<VarLenInteger>>,
+    pub(super) dist: Vec<Option<VarLenInteger>>,
+    pub(super) fixed_lit: Vec<Option<VarLenInteger>>,
+    pub(super) fixed_dist: Vec<Option<VarLenInteger>>,
+}
+
+impl PrefixTables {
+    #[inline]
+    pub(super) const fn new() -> Self {
+        Self {
+            lit: Vec::new(),
+            dist: Vec::new(),
+            fixed_lit: Vec::new(),
+            fixed_dist: Vec::new(),
+        }
     }
 }
 
@@ -184,8 +808,8 @@ impl DeflateLZIR {
 pub struct DeflateIrBlock<'a> {
     block: &'a [DeflateLZIR],
     estimated_size: usize,
-    freq_count_lit: Box<[usize; 288]>,
-    freq_count_dist: Box<[usize; 30]>,
+    freq_count_lit: Box<Histogram<288>>,
+    freq_count_dist: Box<Histogram<30>>,
     entropy_lit: f64,
     entropy_dist: f64,
 
@@ -196,24 +820,36 @@ pub struct DeflateIrBlock<'a> {
 #[allow(unused)]
 impl<'a> DeflateIrBlock<'a> {
     pub fn new(block: &'a [DeflateLZIR]) -> Self {
-        let mut freq_count_lit = Box::new([0usize; 288]);
-        let mut freq_count_dist = Box::new([0usize; 30]);
+        let mut freq_count_lit = Box::new(Histogram::new());
+        let mut freq_count_dist = Box::new(Histogram::new());
 
-        for &item in block.iter() {
-            let lit = item.literal_value() as usize;
-            let dist = item.distance_value() as usize;
-            freq_count_lit[lit] += 1;
-            freq_count_dist[dist] += 1;
+        // Count with two partial tables to avoid stalls on runs of the same symbol
+        let mut freq_count_lit2 = Histogram::new();
+        let mut freq_count_dist2 = Histogram::new();
+        let mut iter = block.chunks_exact(2);
+        for pair in iter.by_ref() {
+            freq_count_lit.add(pair[0].literal_value() as usize);
+            freq_count_dist.add(pair[0].distance_value() as usize);
+            freq_count_lit2.add(pair[1].literal_value() as usize);
+            freq_count_dist2.add(pair[1].distance_value() as usize);
         }
+        for item in iter.remainder() {
+            freq_count_lit.add(item.literal_value() as usize);
+            freq_count_dist.add(item.distance_value() as usize);
+        }
+        freq_count_lit.merge(&freq_count_lit2);
+        freq_count_dist.merge(&freq_count_dist2);
+        // Literals have no distance, but are counted as the distance code 0 above.
+        freq_count_dist[0] -= freq_count_lit.as_slice()[..256].iter().sum::<usize>();
         freq_count_lit[256] = 1; // end of block
 
-        let entropy_lit = entropy_of(freq_count_lit.as_ref());
-        let entropy_dist = entropy_of(freq_count_dist.as_ref());
+        let entropy_lit = freq_count_lit.entropy();
+        let entropy_dist = freq_count_dist.entropy();
 
         let estimated_size = Self::_estimated_size(
-            freq_count_lit.iter().sum::<usize>(),
+            freq_count_lit.total(),
             entropy_lit,
-            freq_count_dist.iter().sum::<usize>(),
+            freq_count_dist.total(),
             entropy_dist,
         );
 
@@ -228,6 +864,53 @@ impl<'a> DeflateIrBlock<'a> {
         }
     }
 
+    /// Splits the elements into blocks so as to minimize the total size, as zopfli does.
+    ///
+    /// Starting from blocks of `SPLIT_UNIT_SIZE` elements, the adjacent pair whose merge saves the most bits
+    /// is merged repeatedly, until no merge saves any bits.
+    pub fn split(elements: &'a [DeflateLZIR]) -> Vec<Self> {
+        let mut blocks = elements
+            .chunks(SPLIT_UNIT_SIZE)
+            .map(|chunk| {
+                let block = Self::new(chunk);
+                let bit_count = block.dynamic_bit_count();
+                (block, bit_count)
+            })
+            .collect::<Vec<_>>();
+
+        // the merged block of each block and the next one, its size and the bits saved
+        type SizedBlock<'a> = (DeflateIrBlock<'a>, usize);
+        fn merge<'a>(a: &SizedBlock<'a>, b: &SizedBlock<'a>) -> (SizedBlock<'a>, isize) {
+            let merged = a.0.merged(&b.0);
+            let bit_count = merged.dynamic_bit_count();
+            let gain = (a.1 + b.1) as isize - bit_count as isize;
+            ((merged, bit_count), gain)
+        }
+        let mut merges = blocks
+            .windows(2)
+            .map(|pair| merge(&pair[0], &pair[1]))
+            .collect::<Vec<_>>();
+
+        while let Some((index, _)) = merges
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.1 > 0)
+            .max_by_key(|(_, v)| v.1)
+        {
+            let (merged, _) = merges.remove(index);
+            blocks[index] = merged;
+            blocks.remove(index + 1);
+            if index > 0 {
+                merges[index - 1] = merge(&blocks[index - 1], &blocks[index]);
+            }
+            if index + 1 < blocks.len() {
+                merges[index] = merge(&blocks[index], &blocks[index + 1]);
+            }
+        }
+
+        blocks.into_iter().map(|v| v.0).collect()
+    }
+
     /// Merge two contiguous blocks.
     ///
     /// # Panics
@@ -243,21 +926,17 @@ impl<'a> DeflateIrBlock<'a> {
 
         let mut freq_count_lit = self.freq_count_lit.clone();
         let mut freq_count_dist = self.freq_count_dist.clone();
-        for (p, q) in freq_count_lit.iter_mut().zip(next.freq_count_lit.iter()) {
-            *p += *q;
-        }
-        for (p, q) in freq_count_dist.iter_mut().zip(next.freq_count_dist.iter()) {
-            *p += *q;
-        }
+        freq_count_lit.merge(&next.freq_count_lit);
+        freq_count_dist.merge(&next.freq_count_dist);
         freq_count_lit[256] = 1; // fix end of block
 
-        let entropy_lit = entropy_of(freq_count_lit.as_ref());
-        let entropy_dist = entropy_of(freq_count_dist.as_ref());
+        let entropy_lit = freq_count_lit.entropy();
+        let entropy_dist = freq_count_dist.entropy();
 
         let estimated_size = Self::_estimated_size(
-            freq_count_lit.iter().sum::<usize>(),
+            freq_count_lit.total(),
             entropy_lit,
-            freq_count_dist.iter().sum::<usize>(),
+            freq_count_dist.total(),
             entropy_dist,
         );
 
@@ -295,12 +974,24 @@ impl<'a> DeflateIrBlock<'a> {
 
     #[inline]
     pub fn freq_count_lit(&self) -> &[usize] {
-        self.freq_count_lit.as_ref()
+        self.freq_count_lit.as_slice()
     }
 
     #[inline]
     pub fn freq_count_dist(&self) -> &[usize] {
-        self.freq_count_dist.as_ref()
+        self.freq_count_dist.as_slice()
+    }
+
+    /// Returns the histogram of the literal/length symbols, including the end of block.
+    #[inline]
+    pub fn lit_histogram(&self) -> &Histogram<288> {
+        &self.freq_count_lit
+    }
+
+    /// Returns the histogram of the distance symbols.
+    #[inline]
+    pub fn dist_histogram(&self) -> &Histogram<30> {
+        &self.freq_count_dist
     }
 
     #[inline]
@@ -308,6 +999,12 @@ impl<'a> DeflateIrBlock<'a> {
         self.entropy_lit + self.entropy_dist
     }
 
+    /// Returns the number of input bytes covered by the block.
+    #[inline]
+    pub fn input_len(&self) -> usize {
+        self.block.iter().map(|v| v.input_len()).sum()
+    }
+
     /// Returns the number of elements in the block.
     #[inline]
     pub const fn n_elements(&self) -> usize {
@@ -326,79 +1023,211 @@ impl<'a> DeflateIrBlock<'a> {
         self.estimated_size
     }
 
-    /// Encode the block to the output stream.
-    pub fn encode(&self, output: &mut BitStreamWriter, use_static: bool) {
-        let (prefix_table_lit, prefix_table_dist) = if use_static {
-            let mut lengths_lit = [0u8; 288];
-            for i in 0..288 {
-                lengths_lit[i] = if i < 144 {
-                    8
-                } else if i < 256 {
-                    9
-                } else if i < 280 {
-                    7
-                } else {
-                    8
-                };
-            }
-            let mut prefix_table_lit = Vec::with_capacity(288);
-            prefix_table_lit.resize(288, None);
-            for (index, value) in CanonicalPrefixDecoder::make_prefix_table(
-                lengths_lit.into_iter().enumerate(),
-                false,
-            )
+    /// Makes the prefix tables of the literal/length and distance codes of a dynamic block.
+    fn _dynamic_prefix_tables(&self, tables: &mut PrefixTables) {
+        CanonicalPrefixCoder::make_prefix_table_into(
+            &mut tables.lit,
+            self.lit_histogram(),
+            BitSize::Bit15,
+            257,
+        );
+        let prefix_table_dist = &mut tables.dist;
+        CanonicalPrefixCoder::make_prefix_table_into(
+            prefix_table_dist,
+            self.dist_histogram(),
+            BitSize::Bit15,
+            1,
+        );
+
+        // fix prefix table for dist
+        let prefix_table_dist_count = prefix_table_dist.iter().filter(|v| v.is_some()).count();
+        if prefix_table_dist_count == 0 {
+            prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
+            prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
+        } else if prefix_table_dist_count < 2 {
+            prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
+        }
+    }
+
+    /// Makes the prefix tables of the fixed codes of a static block, unless they are already made.
+    fn _fixed_prefix_tables(tables: &mut PrefixTables) {
+        if !tables.fixed_lit.is_empty() {
+            return;
+        }
+        let mut lengths_lit = [0u8; 288];
+        for i in 0..288 {
+            lengths_lit[i] = if i < 144 {
+                8
+            } else if i < 256 {
+                9
+            } else if i < 280 {
+                7
+            } else {
+                8
+            };
+        }
+        Self::_prefix_table_from_lengths_into(&mut tables.fixed_lit, &lengths_lit).unwrap();
+
+        tables
+            .fixed_dist
+            .extend((0..30).map(|v| VarLenInteger::new_checked(BitSize::Bit5, v as u32)));
+    }
+
+    fn _encode_prefix_tables(
+        prefix_table_lit: &[Option<VarLenInteger>],
+        prefix_table_dist: &[Option<VarLenInteger>],
+    ) -> EncodedPrefixTable {
+        let prefix_tables = prefix_table_lit
+            .iter()
+            .chain(prefix_table_dist.iter())
+            .map(|v| v.map(|v| v.size().as_u8()).unwrap_or_default())
+            .collect::<Vec<_>>();
+        CanonicalPrefixCoder::encode_prefix_tables(&[&prefix_tables], PermutationFlavor::Deflate)
             .unwrap()
-            {
-                prefix_table_lit[index] = Some(value);
-            }
+    }
 
-            let prefix_table_dist = (0..30)
-                .map(|v| Some(VarLenInteger::new(BitSize::Bit5, v as u32)))
-                .collect::<Vec<_>>();
+    /// Returns the size in bits of the block encoded as a dynamic block, excluding the extra bits of lengths and distances.
+    ///
+    /// Since the extra bits do not depend on how the elements are split into blocks,
+    /// this is enough to compare different splits.
+    pub fn dynamic_bit_count(&self) -> usize {
+        let mut tables = PrefixTables::new();
+        self._dynamic_prefix_tables(&mut tables);
+        let (prefix_table_lit, prefix_table_dist) = (tables.lit, tables.dist);
+        let prefix_tables = Self::_encode_prefix_tables(&prefix_table_lit, &prefix_table_dist);
+        let header = 3
+            + 5
+            + 5
+            + 4
+            + prefix_tables
+                .prefix_table
+                .iter()
+                .chain(prefix_tables.content.iter())
+                .map(|v| v.size().as_usize())
+                .sum::<usize>();
+        let symbols = |freq_count: &[usize], prefix_table: &[Option<VarLenInteger>]| {
+            freq_count
+                .iter()
+                .zip(prefix_table)
+                .map(|(&freq, code)| freq * code.map(|v| v.size().as_usize()).unwrap_or_default())
+                .sum::<usize>()
+        };
+        header
+            + symbols(self.freq_count_lit(), &prefix_table_lit)
+            + symbols(self.freq_count_dist(), &prefix_table_dist)
+    }
 
-            (prefix_table_lit, prefix_table_dist)
+    /// Encode the block to the output stream.
+    pub fn encode(&self, output: &mut BitStreamWriter, use_static: bool) {
+        self._encode_reusing(output, use_static, &mut PrefixTables::new());
+    }
+
+    /// Encodes the block like [`Self::encode`], making the prefix tables in the storage of the previous blocks.
+    pub(super) fn _encode_reusing(
+        &self,
+        output: &mut BitStreamWriter,
+        use_static: bool,
+        tables: &mut PrefixTables,
+    ) {
+        if use_static {
+            Self::_fixed_prefix_tables(tables);
+            self._encode_with(output, &tables.fixed_lit, &tables.fixed_dist, true);
         } else {
-            let prefix_table_lit =
-                CanonicalPrefixCoder::make_prefix_table(self.freq_count_lit(), BitSize::Bit15, 257);
-            let mut prefix_table_dist =
-                CanonicalPrefixCoder::make_prefix_table(self.freq_count_dist(), BitSize::Bit15, 1);
-
-            // fix prefix table for dist
-            let prefix_table_dist_count = prefix_table_dist.iter().filter(|v| v.is_some()).count();
-            if prefix_table_dist_count == 0 {
-                prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
-                prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
-            } else if prefix_table_dist_count < 2 {
-                prefix_table_dist.push(Some(VarLenInteger::with_bool(true)));
-            }
+            self._dynamic_prefix_tables(tables);
+            self._encode_with(output, &tables.lit, &tables.dist, false);
+        }
+    }
 
-            (prefix_table_lit, prefix_table_dist)
+    /// Encodes the block to the output stream as a dynamic block with the given code lengths,
+    /// instead of computing new ones.
+    ///
+    /// This is intended for interoperability testing, such as forcing long codes.
+    /// `lit_lengths` must have 257 to 286 entries and `dist_lengths` 1 to 30 entries,
+    /// which are stored in the block header as they are.
+    ///
+    /// Returns `EncodeError::InvalidInput` without writing anything if a length exceeds 15,
+    /// the lengths violate the Kraft inequality,
+    /// or a symbol of the block, including the end of block, has no code.
+    pub fn encode_with_tables(
+        &self,
+        lit_lengths: &[u8],
+        dist_lengths: &[u8],
+        output: &mut BitStreamWriter,
+    ) -> Result<(), EncodeError> {
+        if !(257..=286).contains(&lit_lengths.len()) || !(1..=30).contains(&dist_lengths.len()) {
+            return Err(EncodeError::InvalidInput);
+        }
+        let prefix_table_lit = Self::_prefix_table_from_lengths(lit_lengths)?;
+        let prefix_table_dist = Self::_prefix_table_from_lengths(dist_lengths)?;
+        let has_code = |prefix_table: &[Option<VarLenInteger>], freq_count: &[usize]| {
+            freq_count.iter().enumerate().all(|(symbol, &freq)| {
+                freq == 0 || matches!(prefix_table.get(symbol), Some(Some(_)))
+            })
         };
+        if !has_code(&prefix_table_lit, self.freq_count_lit())
+            || !has_code(&prefix_table_dist, self.freq_count_dist())
+        {
+            return Err(EncodeError::InvalidInput);
+        }
+        self._encode_with(output, &prefix_table_lit, &prefix_table_dist, false);
+        Ok(())
+    }
+
+    /// Returns the prefix table of the code lengths, which must satisfy the Kraft inequality.
+    fn _prefix_table_from_lengths(
+        lengths: &[u8],
+    ) -> Result<Vec<Option<VarLenInteger>>, EncodeError> {
+        let mut prefix_table = Vec::new();
+        Self::_prefix_table_from_lengths_into(&mut prefix_table, lengths)?;
+        Ok(prefix_table)
+    }
 
+    /// Makes the prefix table of the code lengths into `prefix_table`, keeping its allocation.
+    fn _prefix_table_from_lengths_into(
+        prefix_table: &mut Vec<Option<VarLenInteger>>,
+        lengths: &[u8],
+    ) -> Result<(), EncodeError> {
+        let kraft_sum = lengths
+            .iter()
+            .filter(|&&v| v > 0)
+            .try_fold(0usize, |acc, &v| (v <= 15).then(|| acc + (1 << (15 - v))))
+            .ok_or(EncodeError::InvalidInput)?;
+        if kraft_sum > 1 << 15 {
+            return Err(EncodeError::InvalidInput);
+        }
+        let codes =
+            CanonicalPrefixDecoder::make_prefix_table(lengths.iter().copied().enumerate(), false)
+                .map_err(|_| EncodeError::InvalidInput)?;
+        prefix_table.clear();
+        prefix_table.resize(lengths.len(), None);
+        for (index, value) in codes {
+            prefix_table[index] = Some(value);
+        }
+        Ok(())
+    }
+
+    fn _encode_with(
+        &self,
+        output: &mut BitStreamWriter,
+        prefix_table_lit: &[Option<VarLenInteger>],
+        prefix_table_dist: &[Option<VarLenInteger>],
+        use_static: bool,
+    ) {
         output.write(self.is_final()); // bfinal
         if use_static {
-            output.write(VarLenInteger::new(BitSize::Bit2, 0b01)); // btype
+            output.write(VarLenInteger::new_truncated(BitSize::Bit2, 0b01)); // btype
         } else {
-            let prefix_tables = prefix_table_lit
-                .iter()
-                .chain(prefix_table_dist.iter())
-                .map(|v| v.map(|v| v.size().as_u8()).unwrap_or_default())
-                .collect::<Vec<_>>();
-            let prefix_tables = CanonicalPrefixCoder::encode_prefix_tables(
-                &[&prefix_tables],
-                PermutationFlavor::Deflate,
-            )
-            .unwrap();
-
-            output.write(VarLenInteger::new(BitSize::Bit2, 0b10)); // btype
-            output.write(VarLenInteger::new(
-                BitSize::Bit5,
-                prefix_table_lit.len() as u32 - 257,
-            )); // hlit
-            output.write(VarLenInteger::new(
-                BitSize::Bit5,
-                prefix_table_dist.len() as u32 - 1,
-            )); // hdist
+            let prefix_tables = Self::_encode_prefix_tables(prefix_table_lit, prefix_table_dist);
+
+            output.write(VarLenInteger::new_truncated(BitSize::Bit2, 0b10)); // btype
+            output.write(
+                VarLenInteger::new_checked(BitSize::Bit5, prefix_table_lit.len() as u32 - 257)
+                    .unwrap(),
+            ); // hlit
+            output.write(
+                VarLenInteger::new_checked(BitSize::Bit5, prefix_table_dist.len() as u32 - 1)
+                    .unwrap(),
+            ); // hdist
             output.write(prefix_tables.hclen); // hclen
             output.write(prefix_tables.prefix_table.as_slice());
             output.write(prefix_tables.content.as_slice());
@@ -422,6 +1251,141 @@ impl<'a> DeflateIrBlock<'a> {
     }
 }
 
+/// Bit costs of deflate symbols estimated from the result of a previous parse
+///
+/// Code lengths are taken from the Huffman tables that the previous parse would produce.
+/// Symbols that did not appear are assumed to have the longest code length.
+pub struct DeflateCostModel {
+    literal: [u32; 256],
+    /// indexed by match length, including the extra bits
+    length: [u32; 259],
+    /// indexed by distance code, including the extra bits
+    distance: [u32; 30],
+}
+
+impl DeflateCostModel {
+    const UNSEEN_COST: u32 = 15;
+
+    pub fn new(block: &[DeflateLZIR]) -> Self {
+        let mut freq_count_lit = [0usize; 288];
+        let mut freq_count_dist = [0usize; 30];
+        for lzir in block {
+            let lit_len = lzir.literal_value();
+            freq_count_lit[lit_len as usize] += 1;
+            if lit_len > 256 {
+                freq_count_dist[lzir.distance_value() as usize] += 1;
+            }
+        }
+        freq_count_lit[256] = 1; // end of block
+
+        let code_lengths = |freq_count: &[usize], min_len: usize| {
+            CanonicalPrefixCoder::make_prefix_table(freq_count, BitSize::Bit15, min_len)
+                .iter()
+                .map(|v| {
+                    v.map(|v| v.size().as_u8() as u32)
+                        .unwrap_or(Self::UNSEEN_COST)
+                })
+                .chain(core::iter::repeat(Self::UNSEEN_COST))
+                .take(freq_count.len())
+                .collect::<Vec<_>>()
+        };
+        let lengths_lit = code_lengths(&freq_count_lit, 257);
+        let lengths_dist = code_lengths(&freq_count_dist, 1);
+
+        let mut literal = [0; 256];
+        literal.copy_from_slice(&lengths_lit[..256]);
+
+        let mut length = [0; 259];
+        for (len, cost) in length.iter_mut().enumerate().skip(LenType::MIN as usize) {
+            let len_type = LenType::new(len as u32).unwrap();
+            *cost = lengths_lit[257 + len_type.leading() as usize]
+                + len_type
+                    .trailing()
+                    .map(|v| v.size().as_u8() as u32)
+                    .unwrap_or_default();
+        }
+
+        let mut distance = [0; 30];
+        for (code, cost) in distance.iter_mut().enumerate() {
+            *cost = lengths_dist[code]
+                + DistanceType::trailing_bits_for(code as u8)
+                    .map(|v| v.as_u8() as u32)
+                    .unwrap_or_default();
+        }
+
+        Self {
+            literal,
+            length,
+            distance,
+        }
+    }
+
+    /// Creates the cost model from LZSS elements.
+    pub fn from_lzss(tokens: &[LZSS]) -> Self {
+        Self::new(
+            &tokens
+                .iter()
+                .map(|&v| DeflateLZIR::from_lzss(v))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Returns the total cost of the elements in bits.
+    pub fn total_cost(&self, tokens: &[LZSS]) -> u64 {
+        use lzss::CostModel;
+        tokens
+            .iter()
+            .map(|token| match *token {
+                LZSS::Literal(literal) => self.literal_cost(literal) as u64,
+                LZSS::Match(matches) => {
+                    self.match_cost(matches.len.get(), matches.distance.get()) as u64
+                }
+            })
+            .sum()
+    }
+
+    /// Returns the size in bits of a match with the fixed prefix codes, including the extra bits.
+    ///
+    /// This can be passed to [`lzss::Configuration::match_cost`] where the dynamic codes are not known yet.
+    pub fn fixed_match_cost(len: usize, distance: usize) -> u32 {
+        let lzir = DeflateLZIR::with_match(Match::new(
+            NonZero::new(len.clamp(3, 258)).unwrap(),
+            NonZero::new(distance.clamp(1, 32768)).unwrap(),
+        ));
+        let lit_len = if lzir.literal_value() < 280 { 7 } else { 8 };
+        let extra_bits = [lzir.length_extra_bit_size(), lzir.distance_extra_bit_size()]
+            .into_iter()
+            .flatten()
+            .map(|v| v.as_u8() as u32)
+            .sum::<u32>();
+        lit_len + 5 + extra_bits
+    }
+
+    /// Returns the distance code of a distance in the range `1..=32768`.
+    #[inline]
+    fn _distance_code(distance: usize) -> usize {
+        let d = distance - 1;
+        if d < 4 {
+            d
+        } else {
+            let bits = d.ilog2() as usize;
+            (bits * 2 + ((d >> (bits - 1)) & 1)).min(29)
+        }
+    }
+}
+
+impl lzss::CostModel for DeflateCostModel {
+    #[inline]
+    fn literal_cost(&self, literal: u8) -> u32 {
+        self.literal[literal as usize]
+    }
+
+    #[inline]
+    fn match_cost(&self, len: usize, distance: usize) -> u32 {
+        self.length[len.min(258)] + self.distance[Self::_distance_code(distance)]
+    }
+}
+
 #[allow(unused)]
 pub struct Configuration {
     pub level: CompressionLevel,
@@ -434,17 +1398,75 @@ impl Configuration {
         window_size: WindowSize::Size32768,
     };
 
+    /// Returns the configuration of the LZSS encoder for the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is `CompressionLevel::Level0`, which emits only stored blocks without an LZ parse.
     pub fn lzss_config(&self) -> lzss::Configuration {
-        let window_size = self.window_size.value();
-        let max_len = window_size.min(258);
-        let default_config = lzss::Configuration::new(window_size, max_len).skip_first_literal(1);
+        let max_len = self.window_size.value().min(258);
+        let default_config = lzss::Configuration::new(LZSS::MAX_DISTANCE, max_len)
+            .window_size(self.window_size.into())
+            .skip_first_literal(1);
 
+        // With only one or two attempts, 4-byte keys skip the candidates that cannot extend beyond 3 bytes,
+        // so these levels emit no 3-byte matches either.
+        // The levels that probe more candidates lose more by missing the 3-byte matches than they gain.
+        // Preferring the matches with the cheaper distances under the fixed codes gains on text and binaries
+        // at levels 3, 5 and 6, but was measured to be neutral or worse at the other levels.
         match self.level {
-            CompressionLevel::Fastest => default_config.number_of_attempts(1).threshold_len(3),
-            CompressionLevel::Fast | CompressionLevel::Default => default_config,
+            CompressionLevel::Fastest => default_config
+                .number_of_attempts(1)
+                .threshold_len(3)
+                .acceleration(lzss::Configuration::FAST_ACCELERATION)
+                .key_len(4)
+                .min_len(4),
+            CompressionLevel::Fast => default_config
+                .number_of_attempts(2)
+                .threshold_len(6)
+                .key_len(4)
+                .min_len(4),
+            CompressionLevel::Level3 => default_config
+                .number_of_attempts(4)
+                .threshold_len(8)
+                .match_cost(DeflateCostModel::fixed_match_cost),
+            CompressionLevel::Level4 => default_config
+                .number_of_attempts(6)
+                .threshold_len(12)
+                .parser(lzss::Parser::Lazy),
+            CompressionLevel::Level5 => default_config
+                .number_of_attempts(8)
+                .threshold_len(16)
+                .parser(lzss::Parser::Lazy)
+                .match_cost(DeflateCostModel::fixed_match_cost),
+            CompressionLevel::Level0 => unreachable!("Level0 does not parse the input"),
+            CompressionLevel::Default | CompressionLevel::Auto => default_config
+                .parser(lzss::Parser::Lazy)
+                .match_cost(DeflateCostModel::fixed_match_cost),
+            CompressionLevel::Level7 => default_config
+                .number_of_attempts(32)
+                .threshold_len(32)
+                .parser(lzss::Parser::Lazy)
+                .sa_search_depth(32),
+            CompressionLevel::Level8 => default_config
+                .number_of_attempts(64)
+                .threshold_len(lzss::Configuration::LONG_THRESHOLD_LEN)
+                .parser(lzss::Parser::Lazy)
+                .sa_search_depth(64),
             CompressionLevel::Best => default_config
                 .number_of_attempts(lzss::Configuration::LONG_ATTEMPTS)
-                .threshold_len(max_len),
+                .threshold_len(max_len)
+                .parser(lzss::Parser::Optimal),
+        }
+    }
+
+    /// Number of LZ elements per deflate block.
+    #[inline]
+    pub const fn block_size(&self) -> usize {
+        if self.level.is_fast_method() {
+            FAST_BLOCK_SIZE
+        } else {
+            MIN_BLOCK_SIZE
         }
     }
 }
@@ -453,6 +1475,14 @@ impl Configuration {
 pub struct OptionConfig {
     is_zlib: bool,
     use_experimental_encoder: bool,
+    window_size: Option<lz::WindowSize>,
+    content_aware_window: bool,
+    threads: usize,
+    rsyncable: bool,
+    iterations: usize,
+    store_compressed: bool,
+    deterministic: bool,
+    skip_checksum: bool,
 }
 
 impl OptionConfig {
@@ -461,15 +1491,130 @@ impl OptionConfig {
         Self {
             is_zlib: false,
             use_experimental_encoder: false,
+            window_size: None,
+            content_aware_window: false,
+            threads: 1,
+            rsyncable: false,
+            iterations: 1,
+            store_compressed: false,
+            deterministic: false,
+            skip_checksum: false,
         }
     }
 
+    /// Uses the specified window size instead of selecting it from the input.
+    ///
+    /// The size is written to the zlib header and limits the match distances,
+    /// so a small window bounds the memory required by the decoder.
+    /// Sizes larger than 32KB are clamped. This takes precedence over `content_aware_window`.
+    #[inline]
+    pub const fn window_size(mut self, window_size: lz::WindowSize) -> Self {
+        self.window_size = Some(window_size);
+        self
+    }
+
+    /// Selects the window size from the match distances observed in the input, not only from its length.
+    ///
+    /// A smaller window reduces the memory required by the decoder, at the cost of an extra sampling pass.
+    #[inline]
+    pub const fn content_aware_window(mut self) -> Self {
+        self.content_aware_window = true;
+        self
+    }
+
+    /// Returns the window size to be used for the input.
+    ///
+    /// An explicit `window_size` has the highest priority, then `content_aware_window`, then the input length.
+    pub fn select_window_size(&self, input: &[u8]) -> WindowSize {
+        if let Some(window_size) = self.window_size {
+            WindowSize::clamped_from_lz(window_size)
+        } else if self.content_aware_window {
+            WindowSize::clamped_from_lz(lz::WindowSize::preferred_for_content(
+                input,
+                lz::WindowSize::SIZE_32K,
+            ))
+        } else {
+            WindowSize::preferred_for(input.len())
+        }
+    }
+
+    /// Encodes segments of the input concurrently on up to `threads` threads.
+    ///
+    /// Each segment refers to the preceding window as a dictionary, so the output is still a single deflate stream,
+    /// but it differs slightly from the single-threaded output. Requires the `threads` feature, otherwise it has no effect.
+    /// `CompressionLevel::Auto` ignores it, and encodes its segments on the calling thread.
+    #[inline]
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Repeats the optimal parse of `CompressionLevel::Best` up to `iterations` times, as zopfli does.
+    ///
+    /// Each iteration estimates the symbol costs from the code lengths of the previous parse,
+    /// which usually makes the output smaller at the cost of encoding time. Values less than 2 perform a single parse.
+    #[inline]
+    pub const fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Resets the sliding window and aligns the output to a byte boundary at content-defined boundaries of the input,
+    /// as `gzip --rsyncable` does.
+    ///
+    /// A small edit of the input changes only the output around it, which helps delta synchronization such as rsync,
+    /// at the cost of a slightly lower compression ratio. This takes precedence over `threads` and the segments of `CompressionLevel::Auto`.
+    #[inline]
+    pub const fn rsyncable(mut self) -> Self {
+        self.rsyncable = true;
+        self
+    }
+
+    /// Emits stored blocks without the LZ parse if [`stats::classify`] detects the input as already compressed.
+    ///
+    /// This saves the encoding time of data that would not shrink, such as compressed archives and images.
+    #[inline]
+    pub const fn store_compressed(mut self) -> Self {
+        self.store_compressed = true;
+        self
+    }
+
+    /// Guarantees that the output depends only on the input, the level and the other options.
+    ///
+    /// The output is then bit-exact across runs, targets and crate features, so that its hash can be stored,
+    /// as content-addressed storage systems do. This ignores `threads`, whose output depends on the number of threads
+    /// and on the `threads` feature. The other parts of the encoder do not depend on the target:
+    /// the hashes of the match finders are computed in 32 bits, the Huffman builder breaks ties by the symbol,
+    /// and the floating-point estimates use the software implementation of `libm`.
+    #[inline]
+    pub const fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Returns the number of threads to be used for encoding.
+    #[inline]
+    pub const fn effective_threads(&self) -> usize {
+        if self.deterministic { 1 } else { self.threads }
+    }
+
     #[inline]
     pub const fn zlib(mut self) -> Self {
         self.is_zlib = true;
         self
     }
 
+    /// Writes [`SKIPPED_CHECKSUM`] to the zlib trailer instead of the Adler-32 of the input.
+    ///
+    /// This saves a pass over the input when the consumer does not verify the checksum,
+    /// but the output is no longer a valid zlib stream for decoders that do.
+    #[inline]
+    pub const fn skip_checksum(mut self) -> Self {
+        self.skip_checksum = true;
+        self
+    }
+
+    /// Uses [`LZSS::encode_sa_lcp`] at levels 7, 8 and `Best`, whose suffix arrays are searched deeper at the higher levels.
     #[inline]
     pub const fn use_experimental(mut self) -> Self {
         self.use_experimental_encoder = true;
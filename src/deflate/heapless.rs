@@ -0,0 +1,277 @@
+//! Deflate compressor without heap allocation
+
+use super::*;
+use core::ops::Range;
+
+/// Number of bits of the hash table of [`deflate_to_slice`]
+const HASH_BITS: usize = 10;
+
+/// Maximum number of input bytes in a block, so that a stored block can replace it
+const BLOCK_SIZE: usize = 0xffff;
+
+/// Returns the maximum size of the output of [`deflate_to_slice`] for an input of `len` bytes.
+#[inline]
+pub const fn deflate_to_slice_bound(len: usize, is_zlib: bool) -> usize {
+    let n_blocks = if len > 0 { len.div_ceil(BLOCK_SIZE) } else { 1 };
+    let zlib_size = if is_zlib { 2 + 4 } else { 0 };
+    zlib_size + len + n_blocks * 5
+}
+
+/// Compresses the input into the output buffer without heap allocation,
+/// and returns the size of the compressed data.
+///
+/// This is intended for microcontrollers without an allocator.
+/// Only stored and fixed Huffman blocks are emitted, and `CompressionLevel::Level0` emits only stored blocks.
+/// The matches are found with a small hash table on the stack.
+///
+/// Returns `EncodeError::OutOfMemory` if the output buffer is too small,
+/// which does not happen if it has [`deflate_to_slice_bound`] bytes.
+pub fn deflate_to_slice(
+    input: &[u8],
+    output: &mut [u8],
+    level: CompressionLevel,
+    is_zlib: bool,
+) -> Result<usize, EncodeError> {
+    let window_size = WindowSize::preferred_for(input.len());
+    let mut writer = SliceBitWriter::new(output);
+    if is_zlib {
+        let header = zlib::Header::new(window_size, level);
+        for &byte in &header.to_bytes()[..header.size()] {
+            writer.write(byte as u32, 8);
+        }
+    }
+
+    let mut matcher = HashMatcher::new(window_size.value());
+    let n_blocks = input.len().div_ceil(BLOCK_SIZE).max(1);
+    for index in 0..n_blocks {
+        let start = index * BLOCK_SIZE;
+        let end = (start + BLOCK_SIZE).min(input.len());
+        let is_final = index + 1 == n_blocks;
+
+        let checkpoint = writer.checkpoint();
+        if !level.is_store_only() {
+            _encode_fixed_block(&mut writer, &mut matcher, input, start..end, is_final);
+            if !writer.is_overflowed()
+                && writer.bit_count() - checkpoint.bit_count()
+                    <= stored_bit_count(checkpoint.bit_count(), end - start)
+            {
+                continue;
+            }
+            writer.restore(checkpoint);
+        }
+        _encode_stored_block(&mut writer, &input[start..end], is_final);
+    }
+
+    if is_zlib {
+        writer.skip_to_next_byte_boundary();
+        for byte in adler32::checksum(input).to_be_bytes() {
+            writer.write(byte as u32, 8);
+        }
+    }
+    writer.finish()
+}
+
+fn _encode_stored_block(writer: &mut SliceBitWriter, data: &[u8], is_final: bool) {
+    let len = data.len() as u16;
+    writer.write(is_final as u32, 1); // bfinal
+    writer.write(0b00, 2); // btype
+    writer.skip_to_next_byte_boundary();
+    writer.write(len as u32, 16);
+    writer.write(!len as u32, 16);
+    for &byte in data {
+        writer.write(byte as u32, 8);
+    }
+}
+
+fn _encode_fixed_block(
+    writer: &mut SliceBitWriter,
+    matcher: &mut HashMatcher,
+    input: &[u8],
+    range: Range<usize>,
+    is_final: bool,
+) {
+    writer.write(is_final as u32, 1); // bfinal
+    writer.write(0b01, 2); // btype
+
+    let mut cursor = range.start;
+    while cursor < range.end {
+        match matcher.find(input, cursor, range.end) {
+            Some((len, distance)) => {
+                let len_type = LenType::new(len as u32).unwrap();
+                _write_fixed_symbol(writer, 257 + len_type.leading as u32);
+                if let Some(extra) = len_type.trailing {
+                    writer.write(extra.value(), extra.size().as_u32());
+                }
+                let dist_type = DistanceType::new(distance as u32).unwrap();
+                writer.write((dist_type.leading as u32).reverse_bits() >> (32 - 5), 5);
+                if let Some(extra) = dist_type.trailing {
+                    writer.write(extra.value(), extra.size().as_u32());
+                }
+                for position in cursor + 1..cursor + len {
+                    matcher.insert(input, position);
+                }
+                cursor += len;
+            }
+            None => {
+                _write_fixed_symbol(writer, input[cursor] as u32);
+                cursor += 1;
+            }
+        }
+    }
+    _write_fixed_symbol(writer, 256); // end of block
+}
+
+/// Writes a literal/length symbol with the fixed Huffman code.
+#[inline]
+fn _write_fixed_symbol(writer: &mut SliceBitWriter, symbol: u32) {
+    let (code, bits) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.write(code.reverse_bits() >> (32 - bits), bits);
+}
+
+/// Match finder that remembers the latest position of each hash of 3 bytes
+struct HashMatcher {
+    heads: [u32; 1 << HASH_BITS],
+    max_distance: usize,
+}
+
+impl HashMatcher {
+    const EMPTY: u32 = u32::MAX;
+
+    #[inline]
+    fn new(max_distance: usize) -> Self {
+        Self {
+            heads: [Self::EMPTY; 1 << HASH_BITS],
+            max_distance,
+        }
+    }
+
+    #[inline]
+    fn _hash(input: &[u8], position: usize) -> Option<usize> {
+        let key = input.get(position..position + 3)?;
+        let key = u32::from_le_bytes([key[0], key[1], key[2], 0]);
+        Some((key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize)
+    }
+
+    #[inline]
+    fn insert(&mut self, input: &[u8], position: usize) {
+        if let Some(hash) = Self::_hash(input, position) {
+            self.heads[hash] = position as u32;
+        }
+    }
+
+    /// Returns the length and the distance of a match at `position` that ends before `limit`,
+    /// and inserts the position.
+    #[inline]
+    fn find(&mut self, input: &[u8], position: usize, limit: usize) -> Option<(usize, usize)> {
+        let hash = Self::_hash(input, position)?;
+        let candidate = core::mem::replace(&mut self.heads[hash], position as u32);
+        if candidate == Self::EMPTY || position > u32::MAX as usize {
+            return None;
+        }
+        let distance = position - candidate as usize;
+        if distance > self.max_distance {
+            return None;
+        }
+        let max_len = (limit - position).min(LenType::MAX as usize);
+        let len = input[position..position + max_len]
+            .iter()
+            .zip(input[candidate as usize..].iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        (len >= LenType::MIN as usize).then_some((len, distance))
+    }
+}
+
+/// Bit writer into a fixed buffer
+///
+/// Writing beyond the buffer is recorded instead of failing, so that the writer can be restored to a checkpoint.
+struct SliceBitWriter<'a> {
+    buffer: &'a mut [u8],
+    state: SliceBitWriterState,
+}
+
+#[derive(Clone, Copy)]
+struct SliceBitWriterState {
+    position: usize,
+    acc: u32,
+    bits: u32,
+}
+
+impl SliceBitWriterState {
+    #[inline]
+    const fn bit_count(&self) -> usize {
+        self.position * 8 + self.bits as usize
+    }
+}
+
+impl<'a> SliceBitWriter<'a> {
+    #[inline]
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            state: SliceBitWriterState {
+                position: 0,
+                acc: 0,
+                bits: 0,
+            },
+        }
+    }
+
+    /// Writes the lower `bits` bits of the value, where `bits` is at most 16.
+    #[inline]
+    fn write(&mut self, value: u32, bits: u32) {
+        let state = &mut self.state;
+        state.acc |= value << state.bits;
+        state.bits += bits;
+        while state.bits >= 8 {
+            if let Some(p) = self.buffer.get_mut(state.position) {
+                *p = state.acc as u8;
+            }
+            state.position += 1;
+            state.acc >>= 8;
+            state.bits -= 8;
+        }
+    }
+
+    #[inline]
+    fn skip_to_next_byte_boundary(&mut self) {
+        let bits = self.state.bits;
+        if bits > 0 {
+            self.write(0, 8 - bits);
+        }
+    }
+
+    #[inline]
+    const fn bit_count(&self) -> usize {
+        self.state.bit_count()
+    }
+
+    #[inline]
+    fn is_overflowed(&self) -> bool {
+        self.state.position > self.buffer.len()
+    }
+
+    #[inline]
+    const fn checkpoint(&self) -> SliceBitWriterState {
+        self.state
+    }
+
+    #[inline]
+    fn restore(&mut self, checkpoint: SliceBitWriterState) {
+        self.state = checkpoint;
+    }
+
+    /// Flushes the remaining bits and returns the size of the output.
+    fn finish(mut self) -> Result<usize, EncodeError> {
+        self.skip_to_next_byte_boundary();
+        if self.is_overflowed() {
+            return Err(EncodeError::OutOfMemory);
+        }
+        Ok(self.state.position)
+    }
+}
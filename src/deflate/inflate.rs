@@ -2,170 +2,1361 @@
 
 use super::*;
 use crate::entropy::prefix::{CanonicalPrefixDecoder, LitLen2};
-use crate::lz::LzOutputBuffer;
+use crate::lz::{LzOutputBuffer, LzOutputVec, LzOutputWindow, LzSink};
 use crate::num::bits::{BitSize, BitStreamReader};
+use adler32::Adler32;
+use core::ops::Range;
 
 /// Decompresses a deflate stream into a new vector.
 pub fn inflate(input: &[u8], decode_size: usize) -> Result<Vec<u8>, DecodeError> {
+    inflate_with_limits(input, decode_size, InflateLimits::NONE)
+}
+
+/// Decompresses a deflate stream into a new vector, within the limits.
+///
+/// Returns `DecodeError::LimitExceeded` if `decode_size` or the decoding tables exceed the limits,
+/// before allocating them.
+pub fn inflate_with_limits(
+    input: &[u8],
+    decode_size: usize,
+    limits: InflateLimits,
+) -> Result<Vec<u8>, DecodeError> {
+    if decode_size > limits.max_output {
+        return Err(DecodeError::LimitExceeded);
+    }
     let mut output = Vec::new();
+    output
+        .try_reserve_exact(decode_size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
     output.resize(decode_size, 0);
-    inflate_in_place(input, &mut output)?;
+    _inflate_into(input, &mut output, &limits, InflateOptions::new())?;
     Ok(output)
 }
 
-/// Decompresses a deflate stream in place into the provided output buffer.
-pub fn inflate_in_place(input: &[u8], output: &mut [u8]) -> Result<(), DecodeError> {
-    let mut output = LzOutputBuffer::new(output);
+/// Decompresses a deflate stream into a new vector with the options.
+pub fn inflate_with_options(
+    input: &[u8],
+    decode_size: usize,
+    options: InflateOptions,
+) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(decode_size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+    output.resize(decode_size, 0);
+    _inflate_into(input, &mut output, &InflateLimits::NONE, options)?;
+    Ok(output)
+}
+
+/// Options of the decompressor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InflateOptions {
+    skip_checksum: bool,
+    raw: bool,
+}
+
+impl InflateOptions {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            skip_checksum: false,
+            raw: false,
+        }
+    }
+
+    /// Does not verify the Adler-32 trailer of zlib streams,
+    /// such as those written with [`OptionConfig::skip_checksum`].
+    ///
+    /// This saves a pass over the output when the integrity is ensured otherwise.
+    #[inline]
+    pub const fn skip_checksum(mut self) -> Self {
+        self.skip_checksum = true;
+        self
+    }
+
+    /// Decodes the input as a raw deflate stream, without looking for a zlib header.
+    ///
+    /// The format is otherwise guessed from the first byte, which can be wrong for raw streams
+    /// (see [`zlib::Header::is_zlib`]), so containers of raw deflate streams such as zip should set this.
+    #[inline]
+    pub const fn raw(mut self) -> Self {
+        self.raw = true;
+        self
+    }
+}
+
+/// Default limit of the decompressed size for [`inflate_to_vec`] (1GB)
+pub const DEFAULT_MAX_OUTPUT_SIZE: usize = 0x4000_0000;
+
+/// Limits of the resources used by the decompressor
+///
+/// Services that decompress untrusted data can bound the blowup of decompression bombs with this,
+/// and the decompressor returns `DecodeError::LimitExceeded` instead of allocating without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InflateLimits {
+    /// Maximum size of the decompressed data in bytes
+    pub max_output: usize,
+    /// Maximum total number of entries of the decoding tables of a block
+    pub max_code_table_entries: usize,
+}
+
+impl InflateLimits {
+    /// Limits the decompressed size to [`DEFAULT_MAX_OUTPUT_SIZE`]
+    pub const DEFAULT: Self = Self {
+        max_output: DEFAULT_MAX_OUTPUT_SIZE,
+        max_code_table_entries: usize::MAX,
+    };
+
+    /// No limits
+    pub const NONE: Self = Self {
+        max_output: usize::MAX,
+        max_code_table_entries: usize::MAX,
+    };
+
+    #[inline]
+    pub const fn max_output(mut self, max_output: usize) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    #[inline]
+    pub const fn max_code_table_entries(mut self, max_code_table_entries: usize) -> Self {
+        self.max_code_table_entries = max_code_table_entries;
+        self
+    }
+
+    /// Returns `DecodeError::LimitExceeded` if the decoding tables of a block exceed the limit.
+    #[inline]
+    fn check_tables(&self, table_entries: usize) -> Result<(), DecodeError> {
+        if table_entries > self.max_code_table_entries {
+            Err(DecodeError::LimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for InflateLimits {
+    #[inline]
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Decompresses a deflate stream whose decompressed size is unknown.
+///
+/// The output grows until the final block, up to [`DEFAULT_MAX_OUTPUT_SIZE`] bytes.
+#[inline]
+pub fn inflate_to_vec(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    inflate_to_vec_with_limit(input, DEFAULT_MAX_OUTPUT_SIZE)
+}
+
+/// Decompresses a deflate stream whose decompressed size is unknown.
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`,
+/// which protects against decompression bombs.
+#[inline]
+pub fn inflate_to_vec_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    inflate_to_vec_with_limits(input, InflateLimits::DEFAULT.max_output(max_size))
+}
+
+/// Decompresses a deflate stream whose decompressed size is unknown, within the limits.
+pub fn inflate_to_vec_with_limits(
+    input: &[u8],
+    limits: InflateLimits,
+) -> Result<Vec<u8>, DecodeError> {
+    inflate_to_vec_with_options(input, limits, InflateOptions::new())
+}
+
+/// Decompresses a deflate stream whose decompressed size is unknown, within the limits and with the options.
+pub fn inflate_to_vec_with_options(
+    input: &[u8],
+    limits: InflateLimits,
+    options: InflateOptions,
+) -> Result<Vec<u8>, DecodeError> {
+    let mut stream = InflateStream::with_options(limits, options);
+    stream.push_input(input);
+    stream.finish_input();
 
-    // In zlib, the first byte is always 08, 78, etc., but a pure deflate stream will never have such a value.
-    let leading = *input.get(0).ok_or(DecodeError::UnexpectedEof)?;
-    let (skip, _window_size) = if leading & 0x0f == 0x08 {
-        // zlib header
-        let cmf = leading;
-        let flg = *input.get(1).ok_or(DecodeError::UnexpectedEof)?;
-        let cmf_flg = cmf as u16 * 256 + flg as u16;
-        if (flg & 0x20) != 0 {
-            return Err(DecodeError::UnsupportedFormat);
+    let mut output = Vec::new();
+    loop {
+        match stream.poll()? {
+            InflateStatus::Output(chunk) => {
+                output.extend_from_slice(chunk);
+            }
+            InflateStatus::Done => return Ok(output),
+            InflateStatus::NeedsMoreInput => return Err(DecodeError::UnexpectedEof),
         }
-        if (cmf_flg % 31) != 0 {
-            return Err(DecodeError::InvalidData);
+    }
+}
+
+/// Boundaries of a member of concatenated deflate or zlib streams
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InflateMember {
+    /// Range of the compressed member in the input, including the zlib header and trailer
+    pub input: Range<usize>,
+    /// Range of the decompressed data of the member in the output
+    pub output: Range<usize>,
+}
+
+/// Decompresses concatenated deflate or zlib streams until the input is exhausted,
+/// and returns the output of all members with their boundaries.
+///
+/// Each member starts at the byte following the end of the previous one,
+/// and the zlib header is detected for each member in the same way as [`inflate`].
+/// The output is limited to [`DEFAULT_MAX_OUTPUT_SIZE`] bytes in total.
+///
+/// ```
+/// use compress::deflate::*;
+///
+/// let mut input = deflate(b"Hello, ", CompressionLevel::Default, None).unwrap();
+/// input.extend_from_slice(&deflate_zlib(b"world!", CompressionLevel::Default, None).unwrap());
+///
+/// let (output, members) = inflate_members(&input).unwrap();
+/// assert_eq!(output, b"Hello, world!");
+/// assert_eq!(members.len(), 2);
+/// assert_eq!(members[1].output, 7..13);
+/// assert_eq!(members[1].input.end, input.len());
+/// ```
+#[inline]
+pub fn inflate_members(input: &[u8]) -> Result<(Vec<u8>, Vec<InflateMember>), DecodeError> {
+    inflate_members_with_limits(input, InflateLimits::DEFAULT)
+}
+
+/// Decompresses concatenated deflate or zlib streams like [`inflate_members`], within the limits.
+///
+/// The limit of the output applies to the total of all members.
+pub fn inflate_members_with_limits(
+    input: &[u8],
+    limits: InflateLimits,
+) -> Result<(Vec<u8>, Vec<InflateMember>), DecodeError> {
+    let mut output = Vec::new();
+    let mut members = Vec::new();
+    let mut cursor = 0;
+    while cursor < input.len() {
+        let output_start = output.len();
+        let mut stream =
+            InflateStream::with_limits(limits.max_output(limits.max_output - output_start));
+        stream.push_input(&input[cursor..]);
+        stream.finish_input();
+        loop {
+            match stream.poll()? {
+                InflateStatus::Output(chunk) => {
+                    output.extend_from_slice(chunk);
+                }
+                InflateStatus::Done => break,
+                InflateStatus::NeedsMoreInput => return Err(DecodeError::UnexpectedEof),
+            }
+        }
+        let member_len = stream.total_in();
+        members.push(InflateMember {
+            input: cursor..cursor + member_len,
+            output: output_start..output.len(),
+        });
+        cursor += member_len;
+    }
+    Ok((output, members))
+}
+
+/// Decompresses a deflate stream in place into the provided output buffer,
+/// and returns the number of bytes of the input consumed.
+///
+/// The count includes the zlib header and trailer, so the data following the stream starts there.
+/// If the output buffer is filled before the final block, the count is up to the last byte read.
+///
+/// The Adler-32 trailer of a zlib stream is verified if the whole stream has been decoded,
+/// and `DecodeError::ChecksumMismatch` is returned if it does not match.
+#[inline]
+pub fn inflate_in_place(input: &[u8], output: &mut [u8]) -> Result<usize, DecodeError> {
+    inflate_in_place_with_options(input, output, InflateOptions::new())
+}
+
+/// Decompresses a deflate stream in place like [`inflate_in_place`] with the options.
+#[inline]
+pub fn inflate_in_place_with_options(
+    input: &[u8],
+    output: &mut [u8],
+    options: InflateOptions,
+) -> Result<usize, DecodeError> {
+    let consumed = _inflate_into(input, output, &InflateLimits::NONE, options)?;
+    Ok(consumed)
+}
+
+/// Decompresses a deflate stream of any length with constant memory,
+/// passes the output to `output` in chunks, and returns the size of the decompressed data.
+///
+/// Only the last 32 KB of the output is kept for the matches, in a buffer of twice [`LZ_OUTPUT_WINDOW_SIZE`](crate::lz::LZ_OUTPUT_WINDOW_SIZE) bytes,
+/// which suits microcontrollers decompressing data larger than their memory.
+/// If an error occurs, the output decoded before it may have already been passed,
+/// which includes `DecodeError::ChecksumMismatch` of the Adler-32 trailer of a zlib stream.
+///
+/// ```
+/// use compress::deflate::{CompressionLevel, deflate, inflate_with_callback};
+///
+/// let input = b"Nobody inspects the spammish repetition".repeat(0x1000);
+/// let encoded = deflate(&input, CompressionLevel::Default, None).unwrap();
+/// let mut decoded = Vec::new();
+/// let len = inflate_with_callback(&encoded, |chunk| decoded.extend_from_slice(chunk)).unwrap();
+/// assert_eq!(len, input.len());
+/// assert_eq!(decoded, input);
+/// ```
+pub fn inflate_with_callback<F>(input: &[u8], output: F) -> Result<usize, DecodeError>
+where
+    F: FnMut(&[u8]),
+{
+    // the output is not kept, so the checksum of a zlib stream is computed as it is passed
+    let is_zlib = input.first().is_some_and(|&v| zlib::Header::is_zlib(v));
+    let mut hasher = Adler32::new();
+    let mut output = output;
+    let mut window = LzOutputWindow::new(|chunk: &[u8]| {
+        if is_zlib {
+            hasher.update(chunk);
         }
-        let window_size = 256usize << ((cmf_flg >> 4) & 0x0f);
-        (Some(BitSize::Bit16), window_size)
+        output(chunk);
+    });
+    let options = InflateOptions::new();
+    let end = _inflate(input, &mut window, None, &InflateLimits::NONE, &options)?;
+    let len = window.total_len();
+    window.finish();
+    end.verify(|| hasher.finalize(), &options)?;
+    Ok(len)
+}
+
+/// Decompresses a deflate stream at the start of the input, which may be followed by other data,
+/// and returns the output with the number of bytes of the input consumed.
+///
+/// This is useful for containers where other fields follow the compressed data.
+/// See [`inflate_in_place`] for the count.
+pub fn inflate_prefix(input: &[u8], decode_size: usize) -> Result<(Vec<u8>, usize), DecodeError> {
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(decode_size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+    output.resize(decode_size, 0);
+    let consumed = _inflate_into(
+        input,
+        &mut output,
+        &InflateLimits::NONE,
+        InflateOptions::new(),
+    )?;
+    Ok((output, consumed))
+}
+
+/// Decompresses a deflate stream compressed with a preset dictionary by [`deflate_with_dictionary`].
+///
+/// For the zlib format, returns `DecodeError::InvalidData` if the checksum of the dictionary does not match the header.
+pub fn inflate_with_dictionary(
+    input: &[u8],
+    dictionary: &[u8],
+    decode_size: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(dictionary.len() + decode_size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+    output.extend_from_slice(dictionary);
+    output.resize(dictionary.len() + decode_size, 0);
+    let mut sink = LzOutputBuffer::with_dictionary(&mut output, dictionary.len());
+    let options = InflateOptions::new();
+    let end = _inflate(
+        input,
+        &mut sink,
+        Some(dictionary),
+        &InflateLimits::NONE,
+        &options,
+    )?;
+    let len = sink.position();
+    end.verify(
+        || adler32::checksum(&output[dictionary.len()..len]),
+        &InflateOptions::new(),
+    )?;
+    output.drain(..dictionary.len());
+    Ok(output)
+}
+
+/// Decompresses a deflate stream like [`inflate`], but reports where decoding failed.
+///
+/// The error locates the structure that failed, which helps to debug interoperability issues with other encoders.
+pub fn inflate_verbose(input: &[u8], decode_size: usize) -> Result<Vec<u8>, InflateError> {
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(decode_size)
+        .map_err(|_| InflateError {
+            error: DecodeError::OutOfMemory,
+            site: InflateErrorSite::Output,
+            bit_offset: 0,
+            block_index: 0,
+        })?;
+    output.resize(decode_size, 0);
+    _inflate_into(
+        input,
+        &mut output,
+        &InflateLimits::NONE,
+        InflateOptions::new(),
+    )?;
+    Ok(output)
+}
+
+/// Structure of the deflate stream where decoding failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateErrorSite {
+    /// The zlib header
+    ZlibHeader,
+    /// The type of a block, or the length of a stored block
+    BlockHeader,
+    /// The data of a stored block
+    StoredData,
+    /// The prefix codes of a Huffman block
+    PrefixCodes,
+    /// A literal/length or distance symbol
+    Symbol,
+    /// A match whose distance refers to a position before the start of the output
+    Distance(usize),
+    /// The Adler-32 trailer of a zlib stream
+    Trailer,
+    /// The output buffer, which could not be allocated
+    Output,
+}
+
+/// Error of [`inflate_verbose`] with the location where decoding failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InflateError {
+    pub error: DecodeError,
+    pub site: InflateErrorSite,
+    /// Offset in bits of the failed structure from the start of the input, including the zlib header
+    pub bit_offset: usize,
+    /// Index of the block where decoding failed
+    pub block_index: usize,
+}
+
+impl From<InflateError> for DecodeError {
+    #[inline]
+    fn from(value: InflateError) -> Self {
+        value.error
+    }
+}
+
+impl core::fmt::Display for InflateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.site {
+            InflateErrorSite::ZlibHeader => write!(f, "invalid zlib header")?,
+            InflateErrorSite::BlockHeader => write!(f, "invalid block header")?,
+            InflateErrorSite::StoredData => write!(f, "invalid stored block")?,
+            InflateErrorSite::PrefixCodes => write!(f, "invalid prefix codes")?,
+            InflateErrorSite::Symbol => write!(f, "invalid symbol")?,
+            InflateErrorSite::Distance(distance) => write!(f, "invalid distance {distance}")?,
+            InflateErrorSite::Trailer => write!(f, "invalid zlib trailer")?,
+            InflateErrorSite::Output => write!(f, "cannot allocate the output")?,
+        }
+        write!(
+            f,
+            " at bit {} in block {}: {}",
+            self.bit_offset, self.block_index, self.error
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InflateError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Position of the decoder, which locates errors
+struct InflateCursor {
+    /// Size of the input in bits
+    total_bits: usize,
+    block_index: usize,
+}
+
+impl InflateCursor {
+    /// Returns a function that locates an error at the structure starting with `remaining_bits` left.
+    #[inline]
+    fn error_at(
+        &self,
+        remaining_bits: usize,
+        site: InflateErrorSite,
+    ) -> impl FnOnce(DecodeError) -> InflateError + Copy {
+        let bit_offset = self.total_bits - remaining_bits;
+        let block_index = self.block_index;
+        move |error| InflateError {
+            error,
+            site,
+            bit_offset,
+            block_index,
+        }
+    }
+}
+
+fn _inflate<S: LzSink>(
+    input: &[u8],
+    output: &mut S,
+    dictionary: Option<&[u8]>,
+    limits: &InflateLimits,
+    options: &InflateOptions,
+) -> Result<InflateEnd, InflateError> {
+    let mut cursor = InflateCursor {
+        total_bits: input.len() * 8,
+        block_index: 0,
+    };
+    let header_error = cursor.error_at(cursor.total_bits, InflateErrorSite::ZlibHeader);
+
+    // In zlib, the first byte is always 08, 78, etc., which a raw deflate stream rarely starts with.
+    let leading = *input
+        .first()
+        .ok_or(DecodeError::UnexpectedEof)
+        .map_err(header_error)?;
+    let skip = if !options.raw && zlib::Header::is_zlib(leading) {
+        let header = || -> Result<usize, DecodeError> {
+            let header = zlib::Header::parse(input)?;
+            if header.has_dict {
+                let dictionary = dictionary.ok_or(DecodeError::UnsupportedFormat)?;
+                if header.dict_id != adler32::checksum(dictionary) {
+                    return Err(DecodeError::InvalidData);
+                }
+            }
+            Ok(header.size())
+        };
+        header().map_err(header_error)?
     } else {
-        (None, 0x8000)
+        0
     };
 
-    let mut reader = BitStreamReader::new(input);
-    if let Some(skip) = skip {
-        reader.advance(skip);
-    }
+    let mut reader = BitStreamReader::new(&input[skip..]);
+    let mut is_final = false;
+    let mut fixed_decoders = None;
 
     while !output.is_eof() {
-        let bfinal = reader.read_bool().ok_or(DecodeError::UnexpectedEof)?;
+        let block_start = reader.remaining_bits();
+        let header_error = cursor.error_at(block_start, InflateErrorSite::BlockHeader);
+        let bfinal = reader
+            .read_bool()
+            .ok_or(DecodeError::UnexpectedEof)
+            .map_err(header_error)?;
         let btype = reader
             .read_bits(BitSize::Bit2)
-            .ok_or(DecodeError::UnexpectedEof)?;
-        match btype {
+            .ok_or(DecodeError::UnexpectedEof)
+            .map_err(header_error)?;
+        let block_ended = match btype {
             0b00 => {
                 // uncompressed block
-                let len =
-                    u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
-                let nlen =
-                    u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
+                let len = u16::from_le_bytes(
+                    reader
+                        .read_next_bytes()
+                        .ok_or(DecodeError::UnexpectedEof)
+                        .map_err(header_error)?,
+                );
+                let nlen = u16::from_le_bytes(
+                    reader
+                        .read_next_bytes()
+                        .ok_or(DecodeError::UnexpectedEof)
+                        .map_err(header_error)?,
+                );
                 if len != !nlen {
-                    return Err(DecodeError::InvalidData);
+                    return Err(header_error(DecodeError::InvalidData));
                 }
+                let data_error =
+                    cursor.error_at(reader.remaining_bits(), InflateErrorSite::StoredData);
                 output
                     .extend_from_slice(
                         reader
                             .read_next_bytes_slice(len as usize)
-                            .ok_or(DecodeError::UnexpectedEof)?,
+                            .ok_or(DecodeError::UnexpectedEof)
+                            .map_err(data_error)?,
                     )
-                    .ok_or(DecodeError::InvalidData)?;
+                    .ok_or(DecodeError::InvalidData)
+                    .map_err(data_error)?;
+                true
             }
             0b01 => {
                 // fixed Huffman block
-                let mut lengths_lit = [0; 288];
-                for i in 0..288 {
-                    lengths_lit[i] = if i < 144 {
-                        8
-                    } else if i < 256 {
-                        9
-                    } else if i < 280 {
-                        7
-                    } else {
-                        8
-                    };
-                }
-                let lengths_dist = [5; 32];
-
-                _decode_block(&mut reader, &mut output, &lengths_lit, &lengths_dist)?;
+                _decode_huffman_block(
+                    &mut reader,
+                    output,
+                    HuffmanDecoders::fixed(&mut fixed_decoders),
+                    limits,
+                    &cursor,
+                )?
             }
             0b10 => {
                 // dynamic Huffman block
+                let codes_error =
+                    cursor.error_at(reader.remaining_bits(), InflateErrorSite::PrefixCodes);
                 let hlit = 257
                     + reader
                         .read_bits(BitSize::Bit5)
-                        .ok_or(DecodeError::UnexpectedEof)? as usize;
+                        .ok_or(DecodeError::UnexpectedEof)
+                        .map_err(codes_error)? as usize;
                 let hdist = 1 + reader
                     .read_bits(BitSize::Bit5)
-                    .ok_or(DecodeError::UnexpectedEof)? as usize;
+                    .ok_or(DecodeError::UnexpectedEof)
+                    .map_err(codes_error)? as usize;
                 let mut prefix_table = Vec::new();
                 CanonicalPrefixDecoder::decode_length_table_deflate(
                     &mut reader,
                     &mut prefix_table,
                     hlit + hdist,
-                )?;
+                )
+                .map_err(codes_error)?;
                 let (lengths_lit, lengths_dist) = prefix_table.split_at(hlit);
 
-                _decode_block(&mut reader, &mut output, lengths_lit, lengths_dist)?;
+                _decode_block(
+                    &mut reader,
+                    output,
+                    lengths_lit,
+                    lengths_dist,
+                    limits,
+                    &cursor,
+                )?
             }
             _ => {
                 // reserved (error)
-                return Err(DecodeError::InvalidData);
+                return Err(header_error(DecodeError::InvalidData));
             }
-        }
+        };
         if bfinal {
+            is_final = block_ended;
             break;
         }
+        cursor.block_index += 1;
+    }
+
+    // the unread bits of a partially read byte are padding
+    let mut consumed = skip + reader.bit_position().div_ceil(8);
+    let mut checksum = None;
+    let trailer_bit_offset = consumed * 8;
+    if is_final && skip > 0 {
+        // Adler-32 checksum
+        checksum = zlib::parse_trailer(&input[consumed..]).ok();
+        consumed = (consumed + zlib::TRAILER_SIZE).min(input.len());
     }
+    Ok(InflateEnd {
+        consumed,
+        checksum,
+        trailer_bit_offset,
+        block_index: cursor.block_index,
+    })
+}
 
-    Ok(())
+/// Decodes the stream into the buffer, and verifies the checksum of the decoded data.
+pub(super) fn _inflate_into(
+    input: &[u8],
+    output: &mut [u8],
+    limits: &InflateLimits,
+    options: InflateOptions,
+) -> Result<usize, InflateError> {
+    let mut sink = LzOutputBuffer::new(output);
+    let end = _inflate(input, &mut sink, None, limits, &options)?;
+    let len = sink.position();
+    end.verify(|| adler32::checksum(&output[..len]), &options)
 }
 
-fn _decode_block(
+/// End of the stream decoded by `_inflate`
+struct InflateEnd {
+    /// Number of bytes of the input consumed
+    consumed: usize,
+    /// Adler-32 in the trailer, if the final block and the trailer of a zlib stream have been read
+    checksum: Option<u32>,
+    trailer_bit_offset: usize,
+    block_index: usize,
+}
+
+impl InflateEnd {
+    /// Verifies the checksum of the decoded data computed by `checksum` if necessary,
+    /// and returns the number of bytes consumed.
+    #[inline]
+    fn verify(
+        &self,
+        checksum: impl FnOnce() -> u32,
+        options: &InflateOptions,
+    ) -> Result<usize, InflateError> {
+        match self.checksum {
+            Some(expected) if !options.skip_checksum && expected != checksum() => {
+                Err(InflateError {
+                    error: DecodeError::ChecksumMismatch,
+                    site: InflateErrorSite::Trailer,
+                    bit_offset: self.trailer_bit_offset,
+                    block_index: self.block_index,
+                })
+            }
+            _ => Ok(self.consumed),
+        }
+    }
+}
+
+fn _decode_block<S: LzSink>(
     reader: &mut BitStreamReader,
-    output: &mut LzOutputBuffer,
+    output: &mut S,
     lengths_lit: &[u8],
     lengths_dist: &[u8],
-) -> Result<(), DecodeError> {
+    limits: &InflateLimits,
+    cursor: &InflateCursor,
+) -> Result<bool, InflateError> {
+    let codes_error = cursor.error_at(reader.remaining_bits(), InflateErrorSite::PrefixCodes);
     if lengths_dist.len() >= 2 {
-        let decoder_lit = CanonicalPrefixDecoder::with_lengths(lengths_lit, true)?;
-        let decoder_dist = CanonicalPrefixDecoder::with_lengths(lengths_dist, false)?;
-
-        while !output.is_eof() {
-            match decoder_lit.decode_lit(reader)? {
-                LitLen2::Single(lit) => {
-                    // literal
-                    let _ = output.push_literal(lit);
-                }
-                LitLen2::Double(lit1, lit2) => {
-                    // two literals
-                    let _ = output.push_literal(lit1);
-                    let _ = output.push_literal(lit2);
-                }
-                LitLen2::Length(lit) => {
-                    // length/distance pair
-                    let len = LenType::decode_value(lit, reader).ok_or(DecodeError::InvalidData)?
-                        as usize;
-                    let distance =
-                        DistanceType::decode_value(decoder_dist.decode(reader)? as u8, reader)
-                            .ok_or(DecodeError::InvalidData)? as usize;
-
-                    output
-                        .copy_lz(distance, len)
-                        .ok_or(DecodeError::InvalidData)?;
-                }
-                LitLen2::EndOfBlock(_) => {
-                    // end of block
-                    break;
-                }
-            }
-        }
+        let decoders = HuffmanDecoders {
+            lit: CanonicalPrefixDecoder::with_lengths(lengths_lit, true).map_err(codes_error)?,
+            dist: DistanceDecoder::Prefix(
+                CanonicalPrefixDecoder::with_lengths(lengths_dist, false).map_err(codes_error)?,
+            ),
+        };
+        _decode_huffman_block(reader, output, &decoders, limits, cursor)
     } else {
-        let decoder_lit = CanonicalPrefixDecoder::with_lengths(lengths_lit, false)?;
+        let decoder_lit =
+            CanonicalPrefixDecoder::with_lengths(lengths_lit, false).map_err(codes_error)?;
+        limits
+            .check_tables(decoder_lit.table_entries())
+            .map_err(codes_error)?;
         while !output.is_eof() {
-            let lit = decoder_lit.decode(reader)?;
+            let symbol_error = cursor.error_at(reader.remaining_bits(), InflateErrorSite::Symbol);
+            let lit = decoder_lit.decode(reader).map_err(symbol_error)?;
             if lit < 256 {
                 // literal
                 let _ = output.push_literal(lit as u8);
             } else if lit == 256 {
                 // end of block
-                break;
+                return Ok(true);
             }
         }
+        // consume the end of block that immediately follows the output
+        let mut lookahead = reader.clone();
+        if decoder_lit.decode(&mut lookahead) == Ok(256) {
+            *reader = lookahead;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Decodes the symbols of a Huffman block until the end of the block or the end of the output.
+///
+/// Returns `true` if the end of the block has been read.
+fn _decode_huffman_block<S: LzSink>(
+    reader: &mut BitStreamReader,
+    output: &mut S,
+    decoders: &HuffmanDecoders,
+    limits: &InflateLimits,
+    cursor: &InflateCursor,
+) -> Result<bool, InflateError> {
+    limits
+        .check_tables(decoders.table_entries())
+        .map_err(cursor.error_at(reader.remaining_bits(), InflateErrorSite::PrefixCodes))?;
+
+    // whether the last symbol was cut short by the end of the output
+    let mut truncated = false;
+    while !output.is_eof() {
+        let symbol_start = reader.remaining_bits();
+        let symbol_error = cursor.error_at(symbol_start, InflateErrorSite::Symbol);
+        match decoders.lit.decode_lit(reader).map_err(symbol_error)? {
+            LitLen2::Single(lit) => {
+                // literal
+                let _ = output.push_literal(lit);
+            }
+            LitLen2::Double(lit1, lit2) => {
+                // two literals
+                truncated = output.remaining() < 2;
+                let _ = output.push_literal(lit1);
+                let _ = output.push_literal(lit2);
+            }
+            LitLen2::Length(lit) => {
+                // length/distance pair
+                let len = LenType::decode_value(lit, reader)
+                    .ok_or(DecodeError::InvalidData)
+                    .map_err(symbol_error)? as usize;
+                let distance = DistanceType::decode_value(
+                    decoders.dist.decode(reader).map_err(symbol_error)?,
+                    reader,
+                )
+                .ok_or(DecodeError::InvalidData)
+                .map_err(symbol_error)? as usize;
+
+                truncated = output.remaining() < len;
+                output
+                    .copy_lz(distance, len)
+                    .ok_or(DecodeError::InvalidData)
+                    .map_err(cursor.error_at(symbol_start, InflateErrorSite::Distance(distance)))?;
+            }
+            LitLen2::EndOfBlock(_) => {
+                // end of block
+                return Ok(true);
+            }
+        }
+    }
+
+    // consume the end of block that immediately follows the output
+    if truncated {
+        return Ok(false);
+    }
+    let mut lookahead = reader.clone();
+    if let Ok(LitLen2::EndOfBlock(_)) = decoders.lit.decode_lit(&mut lookahead) {
+        *reader = lookahead;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Size of the sliding window kept by [`InflateStream`]
+const STREAM_WINDOW_SIZE: usize = 0x8000;
+
+/// Amount of output that [`InflateStream`] accumulates before returning it
+const STREAM_OUTPUT_CHUNK_SIZE: usize = 0x8000;
+
+/// Upper bound of the size of a block header in bits, including dynamic Huffman tables
+const MAX_BLOCK_HEADER_BITS: usize = 7 + 3 + 5 + 5 + 4 + 19 * 3 + (288 + 32) * (7 + 7) + 32;
+
+/// Upper bound of the size of a literal or a length/distance pair in bits
+const MAX_SYMBOL_BITS: usize = 15 + 5 + 15 + 13;
+
+/// Result of [`InflateStream::poll`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum InflateStatus<'a> {
+    /// Decompressed data
+    Output(&'a [u8]),
+    /// All input supplied so far has been consumed
+    NeedsMoreInput,
+    /// The final block has been decoded
+    Done,
+}
+
+enum StreamState {
+    Header,
+    BlockHeader,
+    Stored {
+        remaining: usize,
+        is_final: bool,
+    },
+    Huffman {
+        tables: HuffmanTables,
+        is_final: bool,
+    },
+    Trailer,
+    Done,
+}
+
+/// Prefix decoders of a Huffman block
+pub(super) struct HuffmanDecoders {
+    pub(super) lit: CanonicalPrefixDecoder,
+    pub(super) dist: DistanceDecoder,
+}
+
+impl HuffmanDecoders {
+    pub(super) fn with_lengths(
+        lengths_lit: &[u8],
+        lengths_dist: &[u8],
+    ) -> Result<Self, DecodeError> {
+        Ok(Self {
+            lit: CanonicalPrefixDecoder::with_lengths(lengths_lit, true)?,
+            dist: DistanceDecoder::with_lengths(lengths_dist)?,
+        })
+    }
+
+    /// Returns the decoders of the fixed Huffman codes.
+    ///
+    /// They are built into `cache` by the first fixed block of a stream, and shared by the following ones.
+    #[inline]
+    pub(super) fn fixed(cache: &mut Option<Self>) -> &Self {
+        cache.get_or_insert_with(Self::_new_fixed)
+    }
+
+    /// Returns the code lengths of the fixed Huffman codes.
+    pub(super) fn fixed_lengths() -> ([u8; 288], [u8; 32]) {
+        let mut lengths_lit = [0; 288];
+        for (i, p) in lengths_lit.iter_mut().enumerate() {
+            *p = if i < 144 {
+                8
+            } else if i < 256 {
+                9
+            } else if i < 280 {
+                7
+            } else {
+                8
+            };
+        }
+        (lengths_lit, [5; 32])
+    }
+
+    #[cold]
+    fn _new_fixed() -> Self {
+        let (lengths_lit, lengths_dist) = Self::fixed_lengths();
+        Self::with_lengths(&lengths_lit, &lengths_dist).expect("the fixed Huffman codes are valid")
+    }
+
+    #[inline]
+    fn table_entries(&self) -> usize {
+        self.lit.table_entries() + self.dist.table_entries()
+    }
+}
+
+/// Prefix decoders of the current block of [`InflateStream`]
+enum HuffmanTables {
+    Fixed,
+    Dynamic(HuffmanDecoders),
+}
+
+impl HuffmanTables {
+    #[inline]
+    fn decoders<'a>(&'a self, fixed: &'a mut Option<HuffmanDecoders>) -> &'a HuffmanDecoders {
+        match self {
+            Self::Fixed => HuffmanDecoders::fixed(fixed),
+            Self::Dynamic(decoders) => decoders,
+        }
+    }
+}
+
+pub(super) enum DistanceDecoder {
+    Prefix(CanonicalPrefixDecoder),
+    /// A code with at most one symbol, which `CanonicalPrefixDecoder` cannot represent
+    Single(Option<(u8, BitSize)>),
+}
+
+impl DistanceDecoder {
+    fn with_lengths(lengths: &[u8]) -> Result<Self, DecodeError> {
+        let mut iter = lengths.iter().enumerate().filter(|(_, v)| **v > 0);
+        match (iter.next(), iter.next()) {
+            (None, _) => Ok(Self::Single(None)),
+            (Some((symbol, &len)), None) => Ok(Self::Single(Some((
+                symbol as u8,
+                BitSize::new(len).ok_or(DecodeError::InvalidData)?,
+            )))),
+            _ => CanonicalPrefixDecoder::with_lengths(lengths, false).map(Self::Prefix),
+        }
+    }
+
+    #[inline]
+    fn table_entries(&self) -> usize {
+        match self {
+            Self::Prefix(decoder) => decoder.table_entries(),
+            Self::Single(_) => 0,
+        }
+    }
+
+    #[inline]
+    pub(super) fn decode(&self, reader: &mut BitStreamReader) -> Result<u8, DecodeError> {
+        match self {
+            Self::Prefix(decoder) => decoder.decode(reader).map(|v| v as u8),
+            Self::Single(Some((symbol, bits))) => {
+                reader.read_bits(*bits).ok_or(DecodeError::UnexpectedEof)?;
+                Ok(*symbol)
+            }
+            Self::Single(None) => Err(DecodeError::InvalidData),
+        }
+    }
+}
+
+enum StepResult {
+    Continue,
+    NeedsMoreInput,
+    Done,
+}
+
+/// Streaming deflate decompressor
+///
+/// Unlike [`inflate`], the input can be supplied in chunks and the decompressed size does not need to be known.
+/// The zlib header is detected in the same way as [`inflate`].
+///
+/// ```
+/// use compress::deflate::*;
+///
+/// let input = b"Lorem ipsum dolor sit amet, Lorem ipsum dolor sit amet";
+/// let encoded = deflate(input, CompressionLevel::Default, None).unwrap();
+///
+/// let mut stream = InflateStream::new();
+/// let mut decoded = Vec::new();
+/// for chunk in encoded.chunks(7) {
+///     stream.push_input(chunk);
+///     while let InflateStatus::Output(output) = stream.poll().unwrap() {
+///         decoded.extend_from_slice(output);
+///     }
+/// }
+/// stream.finish_input();
+/// loop {
+///     match stream.poll().unwrap() {
+///         InflateStatus::Output(output) => decoded.extend_from_slice(output),
+///         InflateStatus::Done => break,
+///         InflateStatus::NeedsMoreInput => unreachable!(),
+///     }
+/// }
+/// assert_eq!(decoded, input);
+/// ```
+pub struct InflateStream {
+    input: Vec<u8>,
+    /// Number of bytes removed from the front of `input`
+    input_offset: usize,
+    /// Number of bits consumed in `input`
+    bit_pos: usize,
+    is_input_finished: bool,
+    is_zlib: bool,
+    window: Vec<u8>,
+    /// Number of bytes in `window` that have already been returned
+    emitted: usize,
+    total_out: usize,
+    state: StreamState,
+    /// Decoders of the fixed Huffman codes, built by the first fixed block
+    fixed_decoders: Option<HuffmanDecoders>,
+    limits: InflateLimits,
+    options: InflateOptions,
+}
+
+impl InflateStream {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_limits(InflateLimits::NONE)
+    }
+
+    /// Creates a stream that returns `DecodeError::LimitExceeded` when the limits are exceeded.
+    #[inline]
+    pub fn with_limits(limits: InflateLimits) -> Self {
+        Self::with_options(limits, InflateOptions::new())
+    }
+
+    /// Creates a stream with the limits and the options.
+    ///
+    /// The Adler-32 trailer of zlib streams is not verified yet, so [`InflateOptions::skip_checksum`] has no effect.
+    #[inline]
+    pub fn with_options(limits: InflateLimits, options: InflateOptions) -> Self {
+        Self {
+            input: Vec::new(),
+            input_offset: 0,
+            bit_pos: 0,
+            is_input_finished: false,
+            is_zlib: false,
+            window: Vec::new(),
+            emitted: 0,
+            total_out: 0,
+            state: StreamState::Header,
+            fixed_decoders: None,
+            limits,
+            options,
+        }
+    }
+
+    /// Supplies the next chunk of compressed data.
+    pub fn push_input(&mut self, input: &[u8]) {
+        let consumed = self.bit_pos / 8;
+        if consumed > 0 && consumed * 2 >= self.input.len() {
+            self.input.drain(..consumed);
+            self.input_offset += consumed;
+            self.bit_pos -= consumed * 8;
+        }
+        self.input.extend_from_slice(input);
+    }
+
+    /// Declares that no more input will be supplied.
+    ///
+    /// After this, truncated input is reported as an error instead of `NeedsMoreInput`.
+    #[inline]
+    pub fn finish_input(&mut self) {
+        self.is_input_finished = true;
+    }
+
+    /// Returns the total number of bytes decompressed so far.
+    #[inline]
+    pub const fn total_out(&self) -> usize {
+        self.total_out
+    }
+
+    /// Returns the total number of bytes of the input consumed so far.
+    ///
+    /// After `Done`, this is the size of the stream including the zlib header and trailer,
+    /// and the input beyond it has not been read.
+    #[inline]
+    pub const fn total_in(&self) -> usize {
+        self.input_offset + self.bit_pos.div_ceil(8)
+    }
+
+    /// Returns `true` if the final block has been decoded.
+    #[inline]
+    pub const fn is_done(&self) -> bool {
+        matches!(self.state, StreamState::Done)
     }
 
-    Ok(())
+    /// Decompresses as much of the supplied input as possible.
+    ///
+    /// Returns `Output` while decompressed data is available, then `NeedsMoreInput` or `Done`.
+    pub fn poll(&mut self) -> Result<InflateStatus<'_>, DecodeError> {
+        if self.emitted > STREAM_WINDOW_SIZE * 2 {
+            let drain = self.emitted - STREAM_WINDOW_SIZE;
+            self.window.drain(..drain);
+            self.emitted -= drain;
+        }
+
+        let result = loop {
+            if self.window.len() - self.emitted >= STREAM_OUTPUT_CHUNK_SIZE {
+                break StepResult::Continue;
+            }
+            let len = self.window.len();
+            let result = self._step();
+            self.total_out += self.window.len() - len;
+            if self.total_out > self.limits.max_output {
+                return Err(DecodeError::LimitExceeded);
+            }
+            match result? {
+                StepResult::Continue => {}
+                result => break result,
+            }
+        };
+
+        if self.window.len() > self.emitted {
+            let start = self.emitted;
+            self.emitted = self.window.len();
+            return Ok(InflateStatus::Output(&self.window[start..]));
+        }
+        match result {
+            StepResult::Done => Ok(InflateStatus::Done),
+            _ => Ok(InflateStatus::NeedsMoreInput),
+        }
+    }
+
+    /// Returns `NeedsMoreInput` if the error may be caused by the input being incomplete, otherwise the error.
+    ///
+    /// `available_bits` is the number of bits that were available at the start of the failed element,
+    /// and `max_bits` is the maximum size of the element.
+    #[inline]
+    fn _rollback(
+        is_input_finished: bool,
+        error: DecodeError,
+        available_bits: usize,
+        max_bits: usize,
+    ) -> Result<StepResult, DecodeError> {
+        if is_input_finished || available_bits >= max_bits {
+            Err(error)
+        } else {
+            Ok(StepResult::NeedsMoreInput)
+        }
+    }
+
+    fn _step(&mut self) -> Result<StepResult, DecodeError> {
+        let total_bits = self.input.len() * 8;
+        let available_bits = total_bits - self.bit_pos;
+        let mut reader = BitStreamReader::new(&self.input[self.bit_pos / 8..]);
+        if let Some(bits) = BitSize::new((self.bit_pos % 8) as u8) {
+            reader.advance(bits);
+        }
+
+        match &mut self.state {
+            StreamState::Header => {
+                let Some(leading) = reader.peek_bits(BitSize::Bit8) else {
+                    return Self::_rollback(
+                        self.is_input_finished,
+                        DecodeError::UnexpectedEof,
+                        available_bits,
+                        16,
+                    );
+                };
+                if !self.options.raw && zlib::Header::is_zlib(leading as u8) {
+                    let header = match zlib::Header::parse(&self.input[self.bit_pos / 8..]) {
+                        Ok(header) => header,
+                        Err(DecodeError::UnexpectedEof) => {
+                            return Self::_rollback(
+                                self.is_input_finished,
+                                DecodeError::UnexpectedEof,
+                                available_bits,
+                                zlib::Header::MAX_LEN * 8,
+                            );
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    if header.has_dict {
+                        return Err(DecodeError::UnsupportedFormat);
+                    }
+                    self.bit_pos += header.size() * 8;
+                    self.is_zlib = true;
+                }
+                self.state = StreamState::BlockHeader;
+                Ok(StepResult::Continue)
+            }
+            StreamState::BlockHeader => match _read_block_header(&mut reader) {
+                Ok(state) => {
+                    if let StreamState::Huffman { tables, .. } = &state {
+                        self.limits.check_tables(
+                            tables.decoders(&mut self.fixed_decoders).table_entries(),
+                        )?;
+                    }
+                    self.bit_pos = total_bits - reader.remaining_bits();
+                    self.state = state;
+                    Ok(StepResult::Continue)
+                }
+                Err(error) => Self::_rollback(
+                    self.is_input_finished,
+                    error,
+                    available_bits,
+                    MAX_BLOCK_HEADER_BITS,
+                ),
+            },
+            StreamState::Stored {
+                remaining,
+                is_final,
+            } => {
+                let start = self.bit_pos / 8;
+                let len = (*remaining).min(self.input.len() - start);
+                self.window
+                    .extend_from_slice(&self.input[start..start + len]);
+                self.bit_pos += len * 8;
+                *remaining -= len;
+                if *remaining > 0 {
+                    return Self::_rollback(
+                        self.is_input_finished,
+                        DecodeError::UnexpectedEof,
+                        0,
+                        1,
+                    );
+                }
+                self.state = if *is_final {
+                    StreamState::Trailer
+                } else {
+                    StreamState::BlockHeader
+                };
+                Ok(StepResult::Continue)
+            }
+            StreamState::Huffman { tables, is_final } => {
+                let decoders = tables.decoders(&mut self.fixed_decoders);
+                let window_limit = self.emitted + STREAM_OUTPUT_CHUNK_SIZE;
+                let mut checkpoint = reader.remaining_bits();
+                let mut output = LzOutputVec::new(&mut self.window, usize::MAX);
+                while output.len() < window_limit {
+                    match _decode_symbol(&mut reader, decoders, &mut output) {
+                        Ok(true) => {
+                            checkpoint = reader.remaining_bits();
+                        }
+                        Ok(false) => {
+                            self.bit_pos = total_bits - reader.remaining_bits();
+                            self.state = if *is_final {
+                                StreamState::Trailer
+                            } else {
+                                StreamState::BlockHeader
+                            };
+                            return Ok(StepResult::Continue);
+                        }
+                        Err(error) => {
+                            self.bit_pos = total_bits - checkpoint;
+                            return Self::_rollback(
+                                self.is_input_finished,
+                                error,
+                                checkpoint,
+                                MAX_SYMBOL_BITS,
+                            );
+                        }
+                    }
+                }
+                self.bit_pos = total_bits - checkpoint;
+                Ok(StepResult::Continue)
+            }
+            StreamState::Trailer => {
+                if self.is_zlib {
+                    // Adler-32 checksum
+                    let start = self.bit_pos.div_ceil(8);
+                    if self.input.len() < start + zlib::TRAILER_SIZE {
+                        return Self::_rollback(
+                            self.is_input_finished,
+                            DecodeError::UnexpectedEof,
+                            0,
+                            1,
+                        );
+                    }
+                    self.bit_pos = (start + zlib::TRAILER_SIZE) * 8;
+                }
+                self.state = StreamState::Done;
+                Ok(StepResult::Done)
+            }
+            StreamState::Done => Ok(StepResult::Done),
+        }
+    }
+}
+
+impl Default for InflateStream {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn _read_block_header(reader: &mut BitStreamReader) -> Result<StreamState, DecodeError> {
+    let is_final = reader.read_bool().ok_or(DecodeError::UnexpectedEof)?;
+    let btype = reader
+        .read_bits(BitSize::Bit2)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    match btype {
+        0b00 => {
+            // uncompressed block
+            let len =
+                u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
+            let nlen =
+                u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
+            if len != !nlen {
+                return Err(DecodeError::InvalidData);
+            }
+            Ok(StreamState::Stored {
+                remaining: len as usize,
+                is_final,
+            })
+        }
+        0b01 => {
+            // fixed Huffman block
+            Ok(StreamState::Huffman {
+                tables: HuffmanTables::Fixed,
+                is_final,
+            })
+        }
+        0b10 => {
+            // dynamic Huffman block
+            let hlit = 257
+                + reader
+                    .read_bits(BitSize::Bit5)
+                    .ok_or(DecodeError::UnexpectedEof)? as usize;
+            let hdist = 1 + reader
+                .read_bits(BitSize::Bit5)
+                .ok_or(DecodeError::UnexpectedEof)? as usize;
+            let mut prefix_table = Vec::new();
+            CanonicalPrefixDecoder::decode_length_table_deflate(
+                reader,
+                &mut prefix_table,
+                hlit + hdist,
+            )?;
+            let (lengths_lit, lengths_dist) = prefix_table.split_at(hlit);
+            Ok(StreamState::Huffman {
+                tables: HuffmanTables::Dynamic(HuffmanDecoders::with_lengths(
+                    lengths_lit,
+                    lengths_dist,
+                )?),
+                is_final,
+            })
+        }
+        _ => {
+            // reserved (error)
+            Err(DecodeError::InvalidData)
+        }
+    }
+}
+
+/// Decodes a literal or a length/distance pair into the window.
+///
+/// Returns `false` at the end of the block. Nothing is written if an error occurs.
+#[inline]
+fn _decode_symbol<S: LzSink>(
+    reader: &mut BitStreamReader,
+    decoders: &HuffmanDecoders,
+    output: &mut S,
+) -> Result<bool, DecodeError> {
+    match decoders.lit.decode_lit(reader)? {
+        LitLen2::Single(lit) => {
+            let _ = output.push_literal(lit);
+        }
+        LitLen2::Double(lit1, lit2) => {
+            let _ = output.push_literal(lit1);
+            let _ = output.push_literal(lit2);
+        }
+        LitLen2::Length(lit) => {
+            let len =
+                LenType::decode_value(lit, reader).ok_or(DecodeError::UnexpectedEof)? as usize;
+            let distance = DistanceType::decode_value(decoders.dist.decode(reader)?, reader)
+                .ok_or(DecodeError::UnexpectedEof)? as usize;
+            output
+                .copy_lz(distance, len)
+                .ok_or(DecodeError::InvalidData)?;
+        }
+        LitLen2::EndOfBlock(_) => return Ok(false),
+    }
+    Ok(true)
 }
@@ -0,0 +1,291 @@
+//! Structural description of deflate streams
+
+use super::inflate::HuffmanDecoders;
+use super::*;
+use crate::entropy::prefix::{CanonicalPrefixDecoder, LitLen2};
+
+/// Description of a deflate or zlib stream made by [`inspect`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// `true` if the stream has a zlib header
+    pub is_zlib: bool,
+    /// Adler-32 checksum of the preset dictionary recorded in the zlib header
+    pub dictionary_id: Option<u32>,
+    pub blocks: Vec<BlockInfo>,
+    /// Adler-32 checksum of the zlib trailer
+    pub checksum: Option<u32>,
+    /// Size of the stream in bytes, including the zlib header and trailer
+    pub stream_len: usize,
+    /// Size of the decompressed data in bytes
+    pub output_len: usize,
+}
+
+impl StreamInfo {
+    /// Returns the sum of the bit sizes of all blocks.
+    pub fn total_bits(&self) -> BitBreakdown {
+        self.blocks
+            .iter()
+            .fold(BitBreakdown::default(), |acc, block| {
+                acc.merged(&block.bits)
+            })
+    }
+}
+
+/// Type of a deflate block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockType {
+    Stored,
+    Fixed,
+    Dynamic,
+}
+
+/// Description of a deflate block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub block_type: BlockType,
+    pub is_final: bool,
+    /// Offset in bits of the block header from the start of the stream, including the zlib header
+    pub bit_offset: usize,
+    /// Code lengths of the literal/length alphabet, empty for a stored block
+    pub lit_lengths: Vec<u8>,
+    /// Code lengths of the distance alphabet, empty for a stored block
+    pub dist_lengths: Vec<u8>,
+    /// Number of literals, including the bytes of a stored block
+    pub literals: usize,
+    /// Number of length/distance pairs
+    pub matches: usize,
+    /// Sum of the lengths of the matches
+    pub match_bytes: usize,
+    pub bits: BitBreakdown,
+}
+
+impl BlockInfo {
+    /// Returns the number of decompressed bytes of the block.
+    #[inline]
+    pub fn output_len(&self) -> usize {
+        self.literals + self.match_bytes
+    }
+}
+
+/// Size in bits of each part of blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BitBreakdown {
+    /// Block headers, including the lengths and the padding of stored blocks
+    pub header: usize,
+    /// Code lengths of dynamic blocks
+    pub tables: usize,
+    /// Literal codes
+    pub literals: usize,
+    /// Length codes
+    pub lengths: usize,
+    /// Extra bits of lengths
+    pub length_extra: usize,
+    /// Distance codes
+    pub distances: usize,
+    /// Extra bits of distances
+    pub distance_extra: usize,
+    /// End-of-block codes
+    pub end_of_block: usize,
+    /// Data of stored blocks
+    pub stored: usize,
+}
+
+impl BitBreakdown {
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.header
+            + self.tables
+            + self.literals
+            + self.lengths
+            + self.length_extra
+            + self.distances
+            + self.distance_extra
+            + self.end_of_block
+            + self.stored
+    }
+
+    #[inline]
+    pub fn merged(&self, other: &Self) -> Self {
+        Self {
+            header: self.header + other.header,
+            tables: self.tables + other.tables,
+            literals: self.literals + other.literals,
+            lengths: self.lengths + other.lengths,
+            length_extra: self.length_extra + other.length_extra,
+            distances: self.distances + other.distances,
+            distance_extra: self.distance_extra + other.distance_extra,
+            end_of_block: self.end_of_block + other.end_of_block,
+            stored: self.stored + other.stored,
+        }
+    }
+}
+
+/// Parses a deflate or zlib stream into the description of its blocks without decompressing it.
+///
+/// The zlib header is detected in the same way as [`inflate`].
+/// The distances are checked against the decompressed size, assuming no preset dictionary,
+/// but the checksum of the zlib trailer is not verified.
+///
+/// ```
+/// use compress::deflate::{self, BlockType, CompressionLevel};
+///
+/// let encoded = deflate::deflate(b"abcabcabc", CompressionLevel::Default, None).unwrap();
+/// let info = deflate::inspect(&encoded).unwrap();
+/// assert_eq!(info.output_len, 9);
+/// assert_eq!(info.blocks[0].block_type, BlockType::Fixed);
+/// assert_eq!(info.blocks[0].matches, 1);
+/// assert_eq!(info.total_bits().total().div_ceil(8), encoded.len());
+/// ```
+pub fn inspect(input: &[u8]) -> Result<StreamInfo, DecodeError> {
+    let leading = *input.first().ok_or(DecodeError::UnexpectedEof)?;
+    let mut info = StreamInfo {
+        is_zlib: zlib::Header::is_zlib(leading),
+        dictionary_id: None,
+        blocks: Vec::new(),
+        checksum: None,
+        stream_len: 0,
+        output_len: 0,
+    };
+    let mut skip = 0;
+    if info.is_zlib {
+        let header = zlib::Header::parse(input)?;
+        info.dictionary_id = header.has_dict.then_some(header.dict_id);
+        skip = header.size();
+    }
+
+    let mut reader = BitStreamReader::new(&input[skip..]);
+    let mut fixed_decoders = None;
+    loop {
+        let block = _inspect_block(&mut reader, skip * 8, info.output_len, &mut fixed_decoders)?;
+        info.output_len += block.output_len();
+        let is_final = block.is_final;
+        info.blocks.push(block);
+        if is_final {
+            break;
+        }
+    }
+
+    info.stream_len = skip + reader.bit_position().div_ceil(8);
+    if info.is_zlib {
+        info.checksum = Some(zlib::parse_trailer(&input[info.stream_len..])?);
+        info.stream_len += zlib::TRAILER_SIZE;
+    }
+    Ok(info)
+}
+
+fn _inspect_block(
+    reader: &mut BitStreamReader,
+    bit_base: usize,
+    output_len: usize,
+    fixed_decoders: &mut Option<HuffmanDecoders>,
+) -> Result<BlockInfo, DecodeError> {
+    let start = reader.bit_position();
+    let mut block = BlockInfo {
+        block_type: BlockType::Stored,
+        is_final: reader.read_bool().ok_or(DecodeError::UnexpectedEof)?,
+        bit_offset: bit_base + start,
+        lit_lengths: Vec::new(),
+        dist_lengths: Vec::new(),
+        literals: 0,
+        matches: 0,
+        match_bytes: 0,
+        bits: BitBreakdown::default(),
+    };
+    let btype = reader
+        .read_bits(BitSize::Bit2)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    let owned_decoders;
+    let decoders = match btype {
+        0b00 => {
+            // uncompressed block
+            let len =
+                u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
+            let nlen =
+                u16::from_le_bytes(reader.read_next_bytes().ok_or(DecodeError::UnexpectedEof)?);
+            if len != !nlen {
+                return Err(DecodeError::InvalidData);
+            }
+            block.bits.header = reader.bit_position() - start;
+            reader
+                .read_next_bytes_slice(len as usize)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            block.literals = len as usize;
+            block.bits.stored = len as usize * 8;
+            return Ok(block);
+        }
+        0b01 => {
+            // fixed Huffman block
+            block.block_type = BlockType::Fixed;
+            let (lengths_lit, lengths_dist) = HuffmanDecoders::fixed_lengths();
+            block.lit_lengths = lengths_lit.to_vec();
+            block.dist_lengths = lengths_dist.to_vec();
+            block.bits.header = reader.bit_position() - start;
+            HuffmanDecoders::fixed(fixed_decoders)
+        }
+        0b10 => {
+            // dynamic Huffman block
+            block.block_type = BlockType::Dynamic;
+            block.bits.header = reader.bit_position() - start;
+            let tables_start = reader.bit_position();
+            let hlit = 257
+                + reader
+                    .read_bits(BitSize::Bit5)
+                    .ok_or(DecodeError::UnexpectedEof)? as usize;
+            let hdist = 1 + reader
+                .read_bits(BitSize::Bit5)
+                .ok_or(DecodeError::UnexpectedEof)? as usize;
+            let mut prefix_table = Vec::new();
+            CanonicalPrefixDecoder::decode_length_table_deflate(
+                reader,
+                &mut prefix_table,
+                hlit + hdist,
+            )?;
+            block.bits.tables = reader.bit_position() - tables_start;
+            let (lengths_lit, lengths_dist) = prefix_table.split_at(hlit);
+            owned_decoders = HuffmanDecoders::with_lengths(lengths_lit, lengths_dist)?;
+            block.lit_lengths = lengths_lit.to_vec();
+            block.dist_lengths = lengths_dist.to_vec();
+            &owned_decoders
+        }
+        _ => {
+            // reserved (error)
+            return Err(DecodeError::InvalidData);
+        }
+    };
+
+    loop {
+        let symbol_start = reader.bit_position();
+        match decoders.lit.decode_lit(reader)? {
+            LitLen2::Single(_) => {
+                block.literals += 1;
+                block.bits.literals += reader.bit_position() - symbol_start;
+            }
+            LitLen2::Double(_, _) => {
+                block.literals += 2;
+                block.bits.literals += reader.bit_position() - symbol_start;
+            }
+            LitLen2::Length(lit) => {
+                let code_end = reader.bit_position();
+                let len = LenType::decode_value(lit, reader).ok_or(DecodeError::InvalidData)?;
+                let extra_end = reader.bit_position();
+                let dist_code = decoders.dist.decode(reader)?;
+                let dist_code_end = reader.bit_position();
+                let distance = DistanceType::decode_value(dist_code, reader)
+                    .ok_or(DecodeError::InvalidData)? as usize;
+                if distance > output_len + block.output_len() {
+                    return Err(DecodeError::InvalidData);
+                }
+                block.matches += 1;
+                block.match_bytes += len as usize;
+                block.bits.lengths += code_end - symbol_start;
+                block.bits.length_extra += extra_end - code_end;
+                block.bits.distances += dist_code_end - extra_end;
+                block.bits.distance_extra += reader.bit_position() - dist_code_end;
+            }
+            LitLen2::EndOfBlock(_) => {
+                block.bits.end_of_block += reader.bit_position() - symbol_start;
+                return Ok(block);
+            }
+        }
+    }
+}
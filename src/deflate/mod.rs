@@ -2,6 +2,7 @@
 //!
 //! See also: <https://www.ietf.org/rfc/rfc1951.txt>
 
+use crate::lz::cache::{OffsetCache, OffsetCache3};
 use crate::num::{
     VarLenInteger,
     bits::{BitSize, BitStreamReader},
@@ -12,11 +13,21 @@ use crate::*;
 mod tests;
 
 pub mod adler32;
+pub mod zlib;
 
+/// Number of bytes analyzed by `CompressionLevel::auto_select`
+const AUTO_SAMPLE_SIZE: usize = 16 * 1024;
+
+mod context;
 mod deflate;
+mod heapless;
 mod inflate;
+mod inspect;
+pub use context::*;
 pub use deflate::*;
+pub use heapless::*;
 pub use inflate::*;
+pub use inspect::*;
 
 macro_rules! var_uint32 {
     ($class_name:ident, $base_table:ident, $min_value:expr, $max_value:expr) => {
@@ -238,39 +249,176 @@ impl WindowSize {
     pub const fn value(&self) -> usize {
         256 << *self as usize
     }
+
+    /// Converts a general LZ window size into a deflate window size.
+    ///
+    /// Returns `None` if the size exceeds the range allowed by deflate (256 bytes to 32KB).
+    #[inline]
+    pub const fn from_lz(window_size: lz::WindowSize) -> Option<Self> {
+        match window_size.bits() {
+            8 => Some(Self::Size256),
+            9 => Some(Self::Size512),
+            10 => Some(Self::Size1024),
+            11 => Some(Self::Size2048),
+            12 => Some(Self::Size4096),
+            13 => Some(Self::Size8192),
+            14 => Some(Self::Size16384),
+            15 => Some(Self::Size32768),
+            _ => None,
+        }
+    }
+
+    /// Converts a general LZ window size into a deflate window size, clamping it to 32KB.
+    #[inline]
+    pub const fn clamped_from_lz(window_size: lz::WindowSize) -> Self {
+        match Self::from_lz(window_size.min(lz::WindowSize::SIZE_32K)) {
+            Some(v) => v,
+            None => Self::Size32768,
+        }
+    }
+
+    /// Converts into a general LZ window size.
+    #[inline]
+    pub const fn into_lz(self) -> lz::WindowSize {
+        match lz::WindowSize::from_bits(8 + self as u8) {
+            Some(v) => v,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl From<WindowSize> for lz::WindowSize {
+    #[inline]
+    fn from(value: WindowSize) -> Self {
+        value.into_lz()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum CompressionLevel {
+    /// No compression, emits only stored blocks
+    Level0 = 0,
     /// Compress as fast as possible
-    Fastest = 0,
-    /// Currently, same as `Default`
-    Fast = 1,
+    Fastest = 1,
+    /// Fast compression, slightly better ratio than `Fastest`
+    Fast = 2,
+    Level3 = 3,
+    Level4 = 4,
+    Level5 = 5,
     /// Default compression level, balances speed and compression ratio
     #[default]
     Default = 6,
+    Level7 = 7,
+    Level8 = 8,
     /// Compress as much as possible
     Best = 9,
+    /// Selects `Fastest`, `Default` or `Best` for each segment by analyzing the input
+    ///
+    /// The segments are encoded in turn on the calling thread, and `Best` uses the lazy parse there,
+    /// so [`OptionConfig::threads`], `iterations` and `use_experimental` have no effect.
+    Auto = 10,
 }
 
 impl CompressionLevel {
+    /// Returns the compression level corresponding to the numeric level `0..=9`, or `Auto` for `10`.
+    ///
+    /// As with zlib, `0` means no compression and larger values trade speed for compression ratio.
+    #[inline]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Level0),
+            1 => Some(Self::Fastest),
+            2 => Some(Self::Fast),
+            3 => Some(Self::Level3),
+            4 => Some(Self::Level4),
+            5 => Some(Self::Level5),
+            6 => Some(Self::Default),
+            7 => Some(Self::Level7),
+            8 => Some(Self::Level8),
+            9 => Some(Self::Best),
+            10 => Some(Self::Auto),
+            _ => None,
+        }
+    }
+
+    /// Returns the compression level corresponding to the numeric level, as with zlib.
+    ///
+    /// `10` is `Auto`, and the values greater than `10` are treated as `9` (`Best`).
+    #[inline]
+    pub const fn precise(value: u8) -> Self {
+        match Self::from_u8(value) {
+            Some(v) => v,
+            None => Self::Best,
+        }
+    }
+
+    /// Returns the numeric level `0..=9`, or `10` for `Auto`, which [`from_u8`](Self::from_u8) accepts back.
+    #[inline]
+    pub const fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
     #[inline]
     pub const fn is_fast_method(&self) -> bool {
         matches!(self, Self::Fastest | Self::Fast)
     }
 
+    /// Returns `true` if the level does not compress at all.
+    #[inline]
+    pub const fn is_store_only(&self) -> bool {
+        matches!(self, Self::Level0)
+    }
+
     #[inline]
     pub const fn is_best_method(&self) -> bool {
         matches!(self, Self::Best)
     }
 
+    #[inline]
+    pub const fn is_auto(&self) -> bool {
+        matches!(self, Self::Auto)
+    }
+
+    /// Selects `Fastest`, `Default` or `Best` by analyzing a sample of the input.
+    ///
+    /// The decision is based on the order-0 entropy and the match density,
+    /// which is the ratio of positions that have a 3-byte match in the preceding data.
+    pub fn auto_select(input: &[u8]) -> Self {
+        let sample = &input[..input.len().min(AUTO_SAMPLE_SIZE)];
+        if sample.len() < 16 {
+            return Self::Default;
+        }
+
+        let entropy = entropy::entropy_of_bytes(sample);
+
+        let mut cache = OffsetCache3::new(sample, WindowSize::Size32768.value());
+        let mut hits = 0;
+        for _ in 0..sample.len() {
+            if cache.nearest().is_some() {
+                hits += 1;
+            }
+            cache.advance(1);
+        }
+        let density = hits as f64 / sample.len() as f64;
+
+        if density < 0.1 {
+            // There is little to gain from searching harder.
+            Self::Fastest
+        } else if entropy < 6.0 && density < 0.75 {
+            // Text-like data benefits from searching more candidates.
+            Self::Best
+        } else {
+            Self::Default
+        }
+    }
+
     #[inline]
     pub const fn zlib_flevel(&self) -> u8 {
         match self {
-            CompressionLevel::Fastest => 0,
-            CompressionLevel::Fast => 1,
-            CompressionLevel::Default => 2,
-            CompressionLevel::Best => 3,
+            Self::Level0 | Self::Fastest => 0,
+            Self::Fast | Self::Level3 | Self::Level4 | Self::Level5 => 1,
+            Self::Default | Self::Auto => 2,
+            Self::Level7 | Self::Level8 | Self::Best => 3,
         }
     }
 }
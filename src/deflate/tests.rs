@@ -91,6 +91,17 @@ fn var_length() {
     test_var_uint32!(LenType);
 }
 
+#[test]
+fn fixed_match_cost() {
+    assert_eq!(DeflateCostModel::fixed_match_cost(3, 1), 7 + 5);
+    assert_eq!(DeflateCostModel::fixed_match_cost(11, 5), 7 + 1 + 5 + 1);
+    assert_eq!(DeflateCostModel::fixed_match_cost(258, 1), 8 + 5);
+    assert_eq!(
+        DeflateCostModel::fixed_match_cost(257, 32768),
+        8 + 5 + 5 + 13
+    );
+}
+
 #[track_caller]
 fn assert_eq_array(lhs: &[u8], rhs: &[u8]) {
     for (i, (l, r)) in lhs.iter().zip(rhs.iter()).enumerate() {
@@ -151,6 +162,74 @@ fn deflate_lorem() {
     assert_eq!(encoded1.len() + 2 + 4, encoded2.len());
 }
 
+#[test]
+fn zlib_header() {
+    for (bytes, flevel) in [
+        ([0x78, 0x01], 0),
+        ([0x78, 0x5e], 1),
+        ([0x78, 0x9c], 2),
+        ([0x78, 0xda], 3),
+    ] {
+        let header = zlib::Header::parse(&bytes).unwrap();
+        assert_eq!(header.window_size, WindowSize::Size32768);
+        assert_eq!(header.flevel, flevel);
+        assert!(!header.has_dict);
+        assert_eq!(header.size(), 2);
+        let mut output = Vec::new();
+        header.write(&mut output);
+        assert_eq!(output, bytes);
+    }
+
+    for window_bits in 0..8 {
+        let window_size = WindowSize::preferred_for(256 << window_bits);
+        for level in [
+            CompressionLevel::Fastest,
+            CompressionLevel::Fast,
+            CompressionLevel::Best,
+        ] {
+            let header = zlib::Header::new(window_size, level).with_dictionary(b"dictionary");
+            let bytes = header.to_bytes();
+            assert_eq!(bytes[2..], adler32::checksum(b"dictionary").to_be_bytes());
+            assert_eq!(zlib::Header::parse(&bytes), Ok(header));
+            assert_eq!(
+                zlib::Header::parse(&bytes[..5]),
+                Err(DecodeError::UnexpectedEof)
+            );
+        }
+    }
+
+    let encoded = deflate(
+        LOREM_TXT,
+        CompressionLevel::Default,
+        OptionConfig::new().zlib().into(),
+    )
+    .unwrap();
+    let header = zlib::Header::parse(&encoded).unwrap();
+    assert_eq!(header.flevel, 2);
+    assert_eq!(
+        zlib::parse_trailer(&encoded[encoded.len() - zlib::TRAILER_SIZE..]),
+        Ok(adler32::checksum(LOREM_TXT))
+    );
+
+    // FCHECK, the method and the window size (CINFO > 7)
+    assert_eq!(
+        zlib::Header::parse(&[0x78, 0x9d]),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        zlib::Header::parse(&[0x79, 0x9c]),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        zlib::Header::parse(&[0x88, 0x98]),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        zlib::Header::parse(&[0x78]),
+        Err(DecodeError::UnexpectedEof)
+    );
+}
+
 #[test]
 fn inflate_zero_4m() {
     let size = 0x40_0000;
@@ -1457,12 +1536,9 @@ fn deflate_zero_16m_best() {
     input.resize(size, 0);
 
     let encoded1: Vec<u8> = deflate(&input, CompressionLevel::Best, None).unwrap();
-    assert_eq!(encoded1.len(), ZERO_16M_ZIP.len(),);
-    assert_eq_array(&encoded1[..16], &ZERO_16M_ZIP[..16]);
-    assert_eq_array(
-        &encoded1[ZERO_16M_ZIP.len() - 16..],
-        &ZERO_16M_ZIP[ZERO_16M_ZIP.len() - 16..],
-    );
+    // The block splitter merges the blocks of the fixed size of the reference into a single block.
+    assert!(encoded1.len() < ZERO_16M_ZIP.len());
+    assert_eq!(encoded1[0] & 1, 1);
     let decoded = inflate(&encoded1, input.len()).unwrap();
     assert_eq_array(&decoded, &input);
 }
@@ -1552,6 +1628,24 @@ fn deflate_random_alphabet() {
     assert_eq_array(&decoded, &input);
 }
 
+#[test]
+fn deflate_experimental() {
+    let mut input = _portable_text(0x10000);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x8000));
+    input.extend_from_slice(&input.clone()[..0x4000]);
+    let options = OptionConfig::new().use_experimental();
+    for level in [
+        CompressionLevel::Level7,
+        CompressionLevel::Level8,
+        CompressionLevel::Best,
+    ] {
+        let encoded = deflate(&input, level, options.into()).unwrap();
+        assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+        let plain = deflate(&input, level, None).unwrap();
+        assert!(encoded.len() < plain.len() * 11 / 10, "{level:?}");
+    }
+}
+
 #[test]
 fn huffman_test() {
     let data: &[u8] = &[
@@ -1563,3 +1657,1415 @@ fn huffman_test() {
     let decoded = inflate(data, expected.len()).unwrap();
     assert_eq!(decoded.as_slice(), expected);
 }
+
+#[test]
+fn compression_levels() {
+    for value in 0..=10 {
+        let level = CompressionLevel::from_u8(value).unwrap();
+        assert_eq!(level.as_u8(), value);
+        assert_eq!(CompressionLevel::precise(value), level);
+    }
+    assert_eq!(CompressionLevel::from_u8(10), Some(CompressionLevel::Auto));
+    assert!(CompressionLevel::from_u8(11).is_none());
+    assert_eq!(CompressionLevel::from_u8(0), Some(CompressionLevel::Level0));
+    assert_eq!(
+        CompressionLevel::from_u8(1),
+        Some(CompressionLevel::Fastest)
+    );
+    assert_eq!(
+        CompressionLevel::from_u8(6),
+        Some(CompressionLevel::Default)
+    );
+    assert_eq!(CompressionLevel::from_u8(9), Some(CompressionLevel::Best));
+    assert_eq!(CompressionLevel::precise(9), CompressionLevel::Best);
+    assert_eq!(CompressionLevel::precise(11), CompressionLevel::Best);
+    assert_eq!(CompressionLevel::precise(200), CompressionLevel::Best);
+
+    // level 0 emits only stored blocks
+    for input in [&b""[..], LOREM_TXT] {
+        let encoded = deflate_zlib(input, CompressionLevel::precise(0), None).unwrap();
+        assert_eq!(encoded.len(), 2 + 5 + input.len() + 4);
+        assert_eq!(encoded[2], 0x01);
+        assert_eq_array(&inflate_to_vec(&encoded).unwrap(), input);
+    }
+
+    let input = random_alphabet(b'A', b'Z', 0x10000);
+    for value in 0..=9 {
+        let level = CompressionLevel::from_u8(value).unwrap();
+        for input in [LOREM_TXT, &input] {
+            let encoded = deflate_zlib(input, level, None).unwrap();
+            let decoded = inflate(&encoded, input.len()).unwrap();
+            assert_eq_array(&decoded, input);
+        }
+    }
+}
+
+#[test]
+fn window_size_from_lz() {
+    use crate::lz;
+
+    for bits in lz::WindowSize::MIN_BITS..=lz::WindowSize::MAX_BITS {
+        let lz_size = lz::WindowSize::from_bits(bits).unwrap();
+        let clamped = WindowSize::clamped_from_lz(lz_size);
+        if lz_size <= lz::WindowSize::SIZE_32K {
+            let window_size = WindowSize::from_lz(lz_size).unwrap();
+            assert_eq!(window_size.value(), lz_size.value());
+            assert_eq!(window_size, clamped);
+            assert_eq!(window_size.into_lz(), lz_size);
+        } else {
+            assert!(WindowSize::from_lz(lz_size).is_none());
+            assert_eq!(clamped, WindowSize::Size32768);
+        }
+    }
+}
+
+#[test]
+fn deflate_auto() {
+    // the empty input is rejected like the other levels
+    for options in [None, Some(OptionConfig::new().zlib())] {
+        assert_eq!(
+            deflate(&[], CompressionLevel::Auto, options),
+            Err(EncodeError::InvalidInput)
+        );
+    }
+
+    let random = random_alphabet_seeded(0, 255, 0x8000, 2493);
+    assert_eq!(
+        CompressionLevel::auto_select(&random),
+        CompressionLevel::Fastest
+    );
+
+    let text = LOREM_TXT.repeat(64);
+    assert_ne!(
+        CompressionLevel::auto_select(&text),
+        CompressionLevel::Fastest
+    );
+
+    let mut input = Vec::new();
+    input.extend_from_slice(&text);
+    input.extend_from_slice(&random);
+    input.extend_from_slice(&fib_str(0x55, 0xaa, 0x30000));
+    input.extend_from_slice(&random);
+    input.extend_from_slice(&text);
+    let encoded = deflate_zlib(&input, CompressionLevel::Auto, None).unwrap();
+    let decoded = inflate(&encoded, input.len()).unwrap();
+    assert_eq_array(&decoded, &input);
+    assert!(encoded.len() < input.len());
+}
+
+#[test]
+fn window_size_selection() {
+    let mut input = Vec::new();
+    for _ in 0..0x100 {
+        let chunk = random_alphabet(0, 255, 0x30);
+        input.extend_from_slice(&chunk);
+        input.extend_from_slice(&chunk);
+    }
+
+    let options = OptionConfig::new();
+    assert_eq!(options.select_window_size(&input), WindowSize::Size32768);
+    let options = OptionConfig::new().content_aware_window();
+    assert!(options.select_window_size(&input) <= WindowSize::Size256);
+    let options = OptionConfig::new()
+        .content_aware_window()
+        .window_size(lz::WindowSize::new(0x1000).unwrap());
+    assert_eq!(options.select_window_size(&input), WindowSize::Size4096);
+    let options = OptionConfig::new().window_size(lz::WindowSize::MAX);
+    assert_eq!(options.select_window_size(&input), WindowSize::Size32768);
+
+    for options in [
+        OptionConfig::new().zlib().content_aware_window(),
+        OptionConfig::new().zlib().window_size(lz::WindowSize::MIN),
+    ] {
+        let window_size = options.select_window_size(&input);
+        let encoded = deflate(&input, CompressionLevel::Default, Some(options)).unwrap();
+        assert_eq!(encoded[0] >> 4, window_size as u8);
+        let decoded = inflate(&encoded, input.len()).unwrap();
+        assert_eq_array(&decoded, &input);
+    }
+}
+
+#[test]
+fn deflate_small_window() {
+    // repetitions beyond a 2KB window
+    let block = random_alphabet(0, 255, 3000);
+    let input = block.repeat(4);
+    let options = OptionConfig::new()
+        .zlib()
+        .window_size(lz::WindowSize::from_bits(11).unwrap());
+
+    for level in [
+        CompressionLevel::Fastest,
+        CompressionLevel::Fast,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+        CompressionLevel::Auto,
+    ] {
+        let encoded = deflate(&input, level, Some(options)).unwrap();
+        assert_eq!(encoded[0], 0x38, "{level:?}");
+        assert!(encoded.len() > input.len() * 9 / 10, "{level:?}");
+        let decoded = inflate(&encoded, input.len()).unwrap();
+        assert_eq_array(&decoded, &input);
+
+        let encoded = deflate_zlib(&input, level, None).unwrap();
+        assert!(encoded.len() < input.len() / 2, "{level:?}");
+    }
+}
+
+fn inflate_stream_chunks(input: &[u8], chunk_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut stream = InflateStream::new();
+    let mut output = Vec::new();
+    for chunk in input.chunks(chunk_size) {
+        stream.push_input(chunk);
+        loop {
+            match stream.poll()? {
+                InflateStatus::Output(chunk) => output.extend_from_slice(chunk),
+                InflateStatus::NeedsMoreInput => break,
+                InflateStatus::Done => return Ok(output),
+            }
+        }
+    }
+    stream.finish_input();
+    loop {
+        match stream.poll()? {
+            InflateStatus::Output(chunk) => output.extend_from_slice(chunk),
+            InflateStatus::NeedsMoreInput => unreachable!(),
+            InflateStatus::Done => break,
+        }
+    }
+    assert_eq!(stream.total_out(), output.len());
+    Ok(output)
+}
+
+#[test]
+fn inflate_stream() {
+    let mut input = LOREM_TXT.repeat(20);
+    input.extend_from_slice(&random_alphabet(0, 255, 0x12345));
+    input.extend_from_slice(&[0; 0x23456]);
+
+    for (level, options) in [
+        (CompressionLevel::Fastest, None),
+        (CompressionLevel::Default, Some(OptionConfig::new().zlib())),
+        (CompressionLevel::Best, None),
+    ] {
+        let encoded = deflate(&input, level, options).unwrap();
+        for chunk_size in [7, 0x1000, encoded.len()] {
+            let decoded = inflate_stream_chunks(&encoded, chunk_size).unwrap();
+            assert_eq_array(&decoded, &input);
+        }
+    }
+
+    // a byte at a time at every level, with a smaller input
+    let mut input = LOREM_TXT.repeat(2);
+    input.extend_from_slice(&random_alphabet_seeded(0, 255, 0x2345, 2502));
+    input.extend_from_slice(&[0; 0x3456]);
+    for value in 0..=10 {
+        let level = CompressionLevel::from_u8(value).unwrap();
+        for options in [None, Some(OptionConfig::new().zlib())] {
+            let encoded = deflate(&input, level, options).unwrap();
+            let decoded = inflate_stream_chunks(&encoded, 1).unwrap();
+            assert_eq_array(&decoded, &input);
+        }
+    }
+
+    // stored blocks
+    let stored = [
+        0x00, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c', 0x01, 0x02, 0x00, 0xfd, 0xff, b'd', b'e',
+    ];
+    for chunk_size in 1..=stored.len() {
+        assert_eq!(
+            inflate_stream_chunks(&stored, chunk_size).unwrap(),
+            b"abcde"
+        );
+    }
+
+    // truncated input
+    let encoded = deflate(LOREM_TXT, CompressionLevel::Default, None).unwrap();
+    assert!(inflate_stream_chunks(&encoded[..encoded.len() - 2], 0x100).is_err());
+}
+
+#[test]
+fn inflate_to_vec_limit() {
+    let input = LOREM_TXT.repeat(10);
+    for options in [None, Some(OptionConfig::new().zlib())] {
+        let encoded = deflate(&input, CompressionLevel::Default, options).unwrap();
+        assert_eq_array(&inflate_to_vec(&encoded).unwrap(), &input);
+        assert_eq_array(
+            &inflate_to_vec_with_limit(&encoded, input.len()).unwrap(),
+            &input,
+        );
+        assert_eq!(
+            inflate_to_vec_with_limit(&encoded, input.len() - 1),
+            Err(DecodeError::LimitExceeded)
+        );
+        assert!(inflate_to_vec(&encoded[..encoded.len() / 2]).is_err());
+    }
+
+    let zero = inflate_to_vec(ZERO_16M_ZIP).unwrap();
+    assert_eq!(zero.len(), 0x0100_0000);
+    assert!(zero.iter().all(|&v| v == 0));
+    assert_eq!(
+        inflate_to_vec_with_limit(ZERO_16M_ZIP, 0x10_0000),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn inflate_callback() {
+    let mut input = LOREM_TXT.repeat(10);
+    input.extend_from_slice(&random_alphabet(0, 255, 0x1_2345));
+    input.extend_from_slice(&input.clone()[0x1000..0x9000]);
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Best,
+    ] {
+        for options in [None, Some(OptionConfig::new().zlib())] {
+            let encoded = deflate(&input, level, options).unwrap();
+            let mut chunks = Vec::new();
+            let len = inflate_with_callback(&encoded, |chunk| chunks.push(chunk.len())).unwrap();
+            assert_eq!(len, input.len());
+            assert!(chunks.iter().all(|&len| len <= 0x1_0000));
+
+            let mut output = Vec::new();
+            inflate_with_callback(&encoded, |chunk| output.extend_from_slice(chunk)).unwrap();
+            assert_eq_array(&output, &input);
+
+            assert!(inflate_with_callback(&encoded[..encoded.len() / 2], |_| {}).is_err());
+        }
+    }
+
+    let mut len = 0;
+    assert_eq!(
+        inflate_with_callback(ZERO_16M_ZIP, |chunk| {
+            assert!(chunk.iter().all(|&v| v == 0));
+            len += chunk.len();
+        }),
+        Ok(0x0100_0000)
+    );
+    assert_eq!(len, 0x0100_0000);
+}
+
+#[test]
+fn inflate_limits() {
+    let input = LOREM_TXT.repeat(10);
+    let encoded = deflate(&input, CompressionLevel::Default, None).unwrap();
+    let limits = InflateLimits::DEFAULT.max_output(input.len());
+    assert_eq_array(
+        &inflate_with_limits(&encoded, input.len(), limits).unwrap(),
+        &input,
+    );
+    assert_eq_array(
+        &inflate_to_vec_with_limits(&encoded, limits).unwrap(),
+        &input,
+    );
+    assert_eq!(
+        inflate_with_limits(&encoded, input.len() + 1, limits),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    let mut stream = InflateStream::with_limits(InflateLimits::NONE.max_output(0x10_0000));
+    stream.push_input(ZERO_16M_ZIP);
+    stream.finish_input();
+    let result = loop {
+        match stream.poll() {
+            Ok(InflateStatus::Output(_)) => {}
+            result => break result.map(|_| ()),
+        }
+    };
+    assert_eq!(result, Err(DecodeError::LimitExceeded));
+    assert!(stream.total_out() <= 0x10_0000 + 0x8000);
+
+    // dynamic Huffman tables
+    let limits = InflateLimits::NONE.max_code_table_entries(100);
+    assert_eq!(
+        inflate_with_limits(&encoded, input.len(), limits),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(
+        inflate_to_vec_with_limits(&encoded, limits),
+        Err(DecodeError::LimitExceeded)
+    );
+    let limits = InflateLimits::NONE.max_code_table_entries(0x10000);
+    assert_eq_array(
+        &inflate_with_limits(&encoded, input.len(), limits).unwrap(),
+        &input,
+    );
+    assert_eq_array(
+        &inflate_to_vec_with_limits(&encoded, limits).unwrap(),
+        &input,
+    );
+
+    // stored blocks have no tables
+    let stored = deflate(&input, CompressionLevel::Level0, None).unwrap();
+    let limits = InflateLimits::NONE.max_code_table_entries(0);
+    assert_eq_array(
+        &inflate_to_vec_with_limits(&stored, limits).unwrap(),
+        &input,
+    );
+}
+
+#[test]
+fn deflate_stored() {
+    for len in [1, 100, 0xffff, 0x10000, 0x2_0001] {
+        let input = random_alphabet(0, 255, len);
+        for level in [
+            CompressionLevel::Fastest,
+            CompressionLevel::Default,
+            CompressionLevel::Best,
+        ] {
+            let encoded = deflate_zlib(&input, level, None).unwrap();
+            // 2 bytes header, 5 bytes per stored block (at least every 16K literals) and 4 bytes checksum
+            assert!(
+                encoded.len() <= 2 + input.len() + (input.len() / 0x4000 + 1) * 5 + 4,
+                "{:?} {} {}",
+                level,
+                input.len(),
+                encoded.len()
+            );
+            assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+            assert_eq_array(&inflate_to_vec(&encoded).unwrap(), &input);
+        }
+    }
+
+    let mut input = random_alphabet(0, 255, 0x8000);
+    input.extend_from_slice(&LOREM_TXT.repeat(4));
+    input.extend_from_slice(&random_alphabet(0, 255, 0x8000));
+    let encoded = deflate(&input, CompressionLevel::Default, None).unwrap();
+    assert!(encoded.len() < input.len());
+    assert_eq_array(&inflate_to_vec(&encoded).unwrap(), &input);
+}
+
+#[test]
+fn deflate_best_optimal() {
+    let mut input = LOREM_TXT.to_vec();
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x4000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+
+    let encoded_level8 = deflate(&input, CompressionLevel::Level8, None).unwrap();
+    let encoded_best = deflate(&input, CompressionLevel::Best, None).unwrap();
+    let decoded = inflate(&encoded_best, input.len()).unwrap();
+    assert_eq_array(&decoded, &input);
+    assert!(encoded_best.len() < encoded_level8.len());
+}
+
+#[test]
+#[cfg(feature = "threads")]
+fn deflate_threads() {
+    let mut input = Vec::new();
+    while input.len() < 0x80000 {
+        input.extend_from_slice(LOREM_TXT);
+        input.extend_from_slice(&random_alphabet(b'a', b'h', 0x1000));
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+    }
+
+    for level in [CompressionLevel::Fastest, CompressionLevel::Default] {
+        let encoded1 = deflate(&input, level, None).unwrap();
+        let encoded2 = deflate(&input, level, OptionConfig::new().threads(4).into()).unwrap();
+        let decoded = inflate(&encoded2, input.len()).unwrap();
+        assert_eq_array(&decoded, &input);
+        assert!(encoded2.len() <= encoded1.len() + encoded1.len() / 20);
+    }
+}
+
+#[test]
+fn deflate_rsyncable() {
+    let common_suffix = |a: &[u8], b: &[u8]| {
+        a.iter()
+            .rev()
+            .zip(b.iter().rev())
+            .take_while(|(p, q)| p == q)
+            .count()
+    };
+    let input = random_alphabet_seeded(b'a', b'p', 0x40000, 2531);
+    let mut edited = input.clone();
+    edited.splice(0x10000..0x10000, *b"small edit");
+
+    for level in [CompressionLevel::Fastest, CompressionLevel::Default] {
+        let options = OptionConfig::new().rsyncable();
+        let encoded1 = deflate(&input, level, options.into()).unwrap();
+        let encoded2 = deflate(&edited, level, options.into()).unwrap();
+        assert_eq_array(&inflate(&encoded1, input.len()).unwrap(), &input);
+        assert_eq_array(&inflate(&encoded2, edited.len()).unwrap(), &edited);
+        assert!(common_suffix(&encoded1, &encoded2) > encoded1.len() / 2);
+
+        let plain1 = deflate(&input, level, None).unwrap();
+        let plain2 = deflate(&edited, level, None).unwrap();
+        assert!(common_suffix(&plain1, &plain2) < plain1.len() / 4);
+        assert!(encoded1.len() <= plain1.len() + plain1.len() / 20);
+    }
+
+    let encoded = deflate_zlib(
+        &input,
+        CompressionLevel::Default,
+        OptionConfig::new().rsyncable().into(),
+    )
+    .unwrap();
+    assert_eq_array(
+        &inflate_to_vec_with_limit(&encoded, input.len()).unwrap(),
+        &input,
+    );
+}
+
+#[test]
+fn deflate_block_split() {
+    let mut input = random_alphabet(b'a', b'd', 0x2000);
+    input.extend_from_slice(&random_alphabet(0, 255, 0x2000));
+    input.extend_from_slice(&random_alphabet(b'0', b'9', 0x3000));
+    let elements = input
+        .iter()
+        .map(|&v| DeflateLZIR::with_literal(v))
+        .collect::<Vec<_>>();
+
+    let blocks = DeflateIrBlock::split(&elements);
+    assert_eq!(
+        blocks.iter().map(|v| v.n_elements()).sum::<usize>(),
+        elements.len()
+    );
+    let mut boundaries = blocks.iter().scan(0, |acc, v| {
+        *acc += v.n_elements();
+        Some(*acc)
+    });
+    assert!(boundaries.any(|v| v.abs_diff(0x2000) <= 0x400));
+    let split_size = blocks.iter().map(|v| v.dynamic_bit_count()).sum::<usize>();
+    let single_size = DeflateIrBlock::new(&elements).dynamic_bit_count();
+    let chunked_size = elements
+        .chunks(0x4000)
+        .map(|v| DeflateIrBlock::new(v).dynamic_bit_count())
+        .sum::<usize>();
+    assert!(split_size < single_size && split_size < chunked_size);
+
+    let encoded = deflate(&input, CompressionLevel::Best, None).unwrap();
+    assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+    let encoded_default = deflate(&input, CompressionLevel::Default, None).unwrap();
+    assert!(encoded.len() <= encoded_default.len());
+}
+
+#[test]
+fn deflate_iterations() {
+    // The iterations minimize the estimated cost, so the input is fixed to keep the sizes comparable.
+    let mut input = LOREM_TXT.to_vec();
+    input.extend_from_slice(&random_alphabet_seeded(b'a', b'h', 0x2000, 2536));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x2000));
+    input.extend_from_slice(LOREM_TXT);
+
+    let single = deflate(&input, CompressionLevel::Best, None).unwrap();
+    assert_eq!(
+        deflate(
+            &input,
+            CompressionLevel::Best,
+            OptionConfig::new().iterations(1).into()
+        )
+        .unwrap(),
+        single
+    );
+    for iterations in [2, 5] {
+        let encoded = deflate(
+            &input,
+            CompressionLevel::Best,
+            OptionConfig::new().iterations(iterations).into(),
+        )
+        .unwrap();
+        assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+        assert!(encoded.len() <= single.len());
+    }
+}
+
+#[test]
+fn deflate_dictionary() {
+    let dictionary = random_alphabet(0, 255, 0x1000);
+    let mut input = dictionary[0x800..0xc00].to_vec();
+    input.extend_from_slice(&random_alphabet(b'a', b'd', 0x400));
+    input.extend_from_slice(&dictionary[..0x400]);
+
+    for level in [
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+        CompressionLevel::Auto,
+    ] {
+        let plain = deflate(&input, level, None).unwrap();
+        let encoded = deflate_with_dictionary(&input, &dictionary, level, None).unwrap();
+        assert!(encoded.len() < plain.len() / 2, "{level:?}");
+        assert_eq_array(
+            &inflate_with_dictionary(&encoded, &dictionary, input.len()).unwrap(),
+            &input,
+        );
+        assert_eq!(
+            inflate(&encoded, input.len()),
+            Err(DecodeError::InvalidData)
+        );
+    }
+
+    // zlib records the checksum of the dictionary
+    let encoded = deflate_with_dictionary(
+        &input,
+        &dictionary,
+        CompressionLevel::Default,
+        OptionConfig::new().zlib().into(),
+    )
+    .unwrap();
+    assert_eq!(encoded[1] & 0x20, 0x20);
+    assert_eq!((encoded[0] as u16 * 256 + encoded[1] as u16) % 31, 0);
+    assert_eq!(encoded[2..6], adler32::checksum(&dictionary).to_be_bytes());
+    assert_eq_array(
+        &inflate_with_dictionary(&encoded, &dictionary, input.len()).unwrap(),
+        &input,
+    );
+    assert_eq!(
+        inflate(&encoded, input.len()),
+        Err(DecodeError::UnsupportedFormat)
+    );
+    assert_eq!(
+        inflate_with_dictionary(&encoded, &dictionary[1..], input.len()),
+        Err(DecodeError::InvalidData)
+    );
+
+    // the empty input is rejected with a dictionary too
+    for level in [
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+        CompressionLevel::Auto,
+    ] {
+        assert_eq!(
+            deflate_with_dictionary(&[], b"hello hello", level, None),
+            Err(EncodeError::InvalidInput)
+        );
+    }
+
+    // an empty dictionary is the same as no dictionary
+    assert_eq!(
+        deflate_with_dictionary(&input, &[], CompressionLevel::Default, None).unwrap(),
+        deflate(&input, CompressionLevel::Default, None).unwrap()
+    );
+}
+
+#[test]
+fn inflate_verbose_errors() {
+    let input = LOREM_TXT.repeat(4);
+    let encoded = deflate(&input, CompressionLevel::Default, None).unwrap();
+    assert_eq_array(&inflate_verbose(&encoded, input.len()).unwrap(), &input);
+
+    // a match into the missing dictionary
+    let dictionary = LOREM_TXT;
+    let encoded =
+        deflate_with_dictionary(&input, dictionary, CompressionLevel::Default, None).unwrap();
+    let error = inflate_verbose(&encoded, input.len()).unwrap_err();
+    assert_eq!(error.error, DecodeError::InvalidData);
+    assert_eq!(error.block_index, 0);
+    assert!(error.bit_offset >= 3 && error.bit_offset < encoded.len() * 8);
+    let InflateErrorSite::Distance(distance) = error.site else {
+        panic!("unexpected site {:?}", error.site);
+    };
+    assert!(distance > 0 && distance <= dictionary.len());
+    assert_eq!(
+        format!("{error}"),
+        format!(
+            "invalid distance {distance} at bit {} in block 0: invalid data",
+            error.bit_offset
+        )
+    );
+    assert_eq!(DecodeError::from(error), DecodeError::InvalidData);
+
+    // invalid zlib header
+    let error = inflate_verbose(&[0x78, 0x00, 0x03, 0x00], 0).unwrap_err();
+    assert_eq!(
+        (error.site, error.bit_offset, error.error),
+        (InflateErrorSite::ZlibHeader, 0, DecodeError::InvalidData)
+    );
+
+    // a reserved block type after a stored block
+    let stream = [0x00, 0x01, 0x00, 0xfe, 0xff, b'a', 0x07];
+    let error = inflate_verbose(&stream, 2).unwrap_err();
+    assert_eq!(
+        (error.site, error.bit_offset, error.block_index),
+        (InflateErrorSite::BlockHeader, 48, 1)
+    );
+
+    // truncated stored data
+    let error = inflate_verbose(&stream[..5], 1).unwrap_err();
+    assert_eq!(
+        (error.site, error.bit_offset, error.error),
+        (InflateErrorSite::StoredData, 40, DecodeError::UnexpectedEof)
+    );
+
+    // an output too large to allocate
+    let error = inflate_verbose(&stream, usize::MAX).unwrap_err();
+    assert_eq!(
+        (error.site, error.error),
+        (InflateErrorSite::Output, DecodeError::OutOfMemory)
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn decode_error_io() {
+    let error = inflate_to_vec(&[0x07]).unwrap_err();
+    let io_error = std::io::Error::from(error);
+    assert_eq!(io_error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(io_error.to_string(), "invalid data");
+    assert_eq!(
+        io_error
+            .into_inner()
+            .unwrap()
+            .downcast::<DecodeError>()
+            .ok(),
+        Some(Box::new(DecodeError::InvalidData))
+    );
+
+    let error: Box<dyn std::error::Error> = inflate_verbose(&[0x07], 1).unwrap_err().into();
+    assert!(error.source().is_some());
+}
+
+#[test]
+fn deflate_heapless() {
+    let mut inputs = vec![
+        Vec::new(),
+        b"a".to_vec(),
+        LOREM_TXT.to_vec(),
+        LOREM_TXT.repeat(100),
+        vec![0; 0x2_0000],
+        random_alphabet(0, 255, 0x1_8000),
+        fib_str(b'a', b'b', 0x1_0000),
+    ];
+    inputs.push(random_alphabet(b'a', b'd', 0x1_0001));
+
+    for input in inputs.iter() {
+        for is_zlib in [false, true] {
+            let bound = deflate_to_slice_bound(input.len(), is_zlib);
+            let mut output = vec![0; bound];
+            for level in [CompressionLevel::Level0, CompressionLevel::Default] {
+                let len = deflate_to_slice(input, &mut output, level, is_zlib).unwrap();
+                assert!(len <= bound);
+                if level.is_store_only() {
+                    assert_eq!(len, bound);
+                }
+                assert_eq_array(&inflate(&output[..len], input.len()).unwrap(), input);
+            }
+        }
+    }
+
+    let input = LOREM_TXT.repeat(100);
+    let mut output = vec![0; input.len()];
+    let len = deflate_to_slice(&input, &mut output, CompressionLevel::Default, true).unwrap();
+    assert!(len < input.len() / 10);
+    assert_eq_array(&inflate_to_vec(&output[..len]).unwrap(), &input);
+    assert_eq!(
+        deflate_to_slice(
+            &input,
+            &mut output[..len - 1],
+            CompressionLevel::Default,
+            true
+        ),
+        Err(EncodeError::OutOfMemory)
+    );
+    assert_eq!(
+        deflate_to_slice(b"", &mut [], CompressionLevel::Default, false),
+        Err(EncodeError::OutOfMemory)
+    );
+}
+
+#[test]
+fn deflate_estimate_size() {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    let mut rng = StdRng::seed_from_u64(2551);
+    let random = (0..0x20000).map(|_| rng.random::<u8>()).collect::<Vec<_>>();
+    let text = (0..0x20000)
+        .map(|_| rng.random_range(b'a'..=b'h'))
+        .collect::<Vec<_>>();
+    let repetitive = fib_str(b'a', b'b', 0x20000);
+
+    for input in [&random, &text, &repetitive] {
+        let estimated = estimate_compressed_size(input);
+        let actual = deflate(input, CompressionLevel::Fastest, None)
+            .unwrap()
+            .len();
+        assert!(
+            estimated * 10 >= actual * 8 && estimated * 10 <= actual * 12 + 100,
+            "estimated {estimated} actual {actual}"
+        );
+    }
+    let estimated = estimate_compressed_size(&random);
+    assert!(estimated <= random.len() + 2 * 5 && estimated * 100 >= random.len() * 99);
+    assert!(estimate_compressed_size(&text) < text.len() / 2);
+    assert!(estimate_compressed_size(&repetitive) < repetitive.len() / 10);
+    assert_eq!(estimate_compressed_size(b""), 5);
+    assert!(estimate_compressed_size(b"a") <= 6);
+}
+
+#[test]
+fn deflate_store_compressed() {
+    let text = random_alphabet(b'a', b'p', 0x10000);
+    let compressed = deflate(&text, CompressionLevel::Default, None).unwrap();
+    let options = OptionConfig::new().store_compressed();
+
+    // the output of deflate has high entropy
+    let stored = deflate(&compressed, CompressionLevel::Best, Some(options)).unwrap();
+    assert_eq!(
+        stored.len(),
+        compressed.len() + compressed.len().div_ceil(0xffff) * 5
+    );
+    assert_eq!(inflate(&stored, compressed.len()).unwrap(), compressed);
+
+    let encoded = deflate(&text, CompressionLevel::Default, Some(options)).unwrap();
+    assert_eq!(encoded, compressed);
+}
+
+#[test]
+fn deflate_skip_checksum() {
+    let input = LOREM_TXT.repeat(10);
+    for level in [CompressionLevel::Level0, CompressionLevel::Default] {
+        let options = OptionConfig::new().zlib();
+        let encoded = deflate(&input, level, Some(options)).unwrap();
+        let skipped = deflate(&input, level, Some(options.skip_checksum())).unwrap();
+        let (body, trailer) = skipped.split_at(skipped.len() - zlib::TRAILER_SIZE);
+        assert_eq!(body, &encoded[..body.len()]);
+        assert_eq!(zlib::parse_trailer(trailer), Ok(SKIPPED_CHECKSUM));
+        assert_eq_array(
+            &inflate_with_options(&skipped, input.len(), InflateOptions::new().skip_checksum())
+                .unwrap(),
+            &input,
+        );
+    }
+
+    // no trailer without zlib
+    let options = OptionConfig::new().skip_checksum();
+    assert_eq!(
+        deflate(&input, CompressionLevel::Default, Some(options)).unwrap(),
+        deflate(&input, CompressionLevel::Default, None).unwrap()
+    );
+}
+
+#[test]
+fn inflate_checksum() {
+    let input = LOREM_TXT.repeat(10);
+    let encoded = deflate_zlib(&input, CompressionLevel::Default, None).unwrap();
+    let mut corrupted = encoded.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    let trailer_offset = encoded.len() - zlib::TRAILER_SIZE;
+
+    let mut output = vec![0; input.len()];
+    assert_eq!(inflate_in_place(&encoded, &mut output), Ok(encoded.len()));
+    assert_eq!(
+        inflate_in_place(&corrupted, &mut output),
+        Err(DecodeError::ChecksumMismatch)
+    );
+    let options = InflateOptions::new().skip_checksum();
+    assert_eq!(
+        inflate_in_place_with_options(&corrupted, &mut output, options),
+        Ok(encoded.len())
+    );
+    assert_eq_array(&output, &input);
+
+    // a larger buffer, whose tail is not part of the output
+    assert_eq!(
+        inflate(&encoded, input.len() + 100).map(|v| v.len()),
+        Ok(input.len() + 100)
+    );
+    assert_eq!(
+        inflate(&corrupted, input.len() + 100),
+        Err(DecodeError::ChecksumMismatch)
+    );
+
+    // the trailer is not verified unless the whole stream has been decoded
+    assert!(inflate(&corrupted, input.len() - 1).is_ok());
+    assert!(inflate(&corrupted[..trailer_offset], input.len()).is_ok());
+
+    let error = inflate_verbose(&corrupted, input.len()).unwrap_err();
+    assert_eq!(
+        (error.site, error.bit_offset, error.error),
+        (
+            InflateErrorSite::Trailer,
+            trailer_offset * 8,
+            DecodeError::ChecksumMismatch
+        )
+    );
+    assert_eq!(
+        inflate_with_callback(&corrupted, |_| {}),
+        Err(DecodeError::ChecksumMismatch)
+    );
+
+    let dictionary = &input[..100];
+    let mut encoded = deflate_with_dictionary(
+        &input,
+        dictionary,
+        CompressionLevel::Default,
+        OptionConfig::new().zlib().into(),
+    )
+    .unwrap();
+    assert_eq!(
+        inflate_with_dictionary(&encoded, dictionary, input.len()),
+        Ok(input.clone())
+    );
+    *encoded.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        inflate_with_dictionary(&encoded, dictionary, input.len()),
+        Err(DecodeError::ChecksumMismatch)
+    );
+}
+
+#[test]
+fn inflate_fixed_blocks() {
+    use crate::num::bits::BitStreamWriter;
+
+    // many fixed blocks of a single literal each
+    let fixed_blocks = |input: &[u8]| {
+        let mut writer = BitStreamWriter::new();
+        for (index, &byte) in input.iter().enumerate() {
+            writer.push_bool(index + 1 == input.len()); // bfinal
+            writer.push_bits64(0b01, 2); // btype
+            writer.push_bits64(((0x30 + byte as u32).reverse_bits() >> 24) as u64, 8);
+            writer.push_bits64(0, 7); // end of block
+        }
+        writer.into_bytes()
+    };
+    let input = random_alphabet(b'a', b'z', 5000);
+    let encoded = fixed_blocks(&input);
+
+    assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+    assert_eq_array(&inflate_to_vec(&encoded).unwrap(), &input);
+    assert_eq_array(&inflate_stream_chunks(&encoded, 0x100).unwrap(), &input);
+    assert_eq!(inspect(&encoded).unwrap().blocks.len(), input.len());
+
+    // the decoders are built once for a stream, however many fixed blocks it has
+    let single = fixed_blocks(&input[..1]);
+    let (_, allocated_single) = allocated_bytes(|| inflate(&single, 1));
+    let (_, allocated) = allocated_bytes(|| inflate(&encoded, input.len()));
+    assert!(
+        allocated <= allocated_single + input.len(),
+        "{allocated} {allocated_single}"
+    );
+
+    // the shared tables are still subject to the limits
+    let limits = InflateLimits::NONE.max_code_table_entries(100);
+    assert_eq!(
+        inflate_with_limits(&encoded, input.len(), limits),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(
+        inflate_to_vec_with_limits(&encoded, limits),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn deflate_encode_with_tables() {
+    use crate::num::bits::BitStreamWriter;
+
+    let input = fib_str(b'a', b'b', 0x3000);
+    let tokens = lz::TokenStream::collect(&input, lz::lzss::Configuration::DEFAULT).unwrap();
+    let elements = tokens
+        .iter()
+        .map(|&v| DeflateLZIR::from_lzss(v))
+        .collect::<Vec<_>>();
+    let mut block = DeflateIrBlock::new(&elements);
+    block.is_final = true;
+
+    // a complete code with codes of up to 12 bits
+    let mut lit_lengths = vec![8; 254];
+    lit_lengths.resize(286, 12);
+    let mut dist_lengths = vec![4, 4];
+    dist_lengths.resize(30, 5);
+
+    let mut output = BitStreamWriter::new();
+    block
+        .encode_with_tables(&lit_lengths, &dist_lengths, &mut output)
+        .unwrap();
+    let encoded = output.into_bytes();
+    assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+    assert_eq_array(&inflate_to_vec(&encoded).unwrap(), &input);
+
+    let mut invalid = Vec::new();
+    // over-subscribed
+    invalid.push((vec![7; 286], dist_lengths.clone()));
+    // too long
+    let mut lengths = lit_lengths.clone();
+    lengths[285] = 16;
+    invalid.push((lengths, dist_lengths.clone()));
+    // a used literal without a code
+    let mut lengths = lit_lengths.clone();
+    lengths[b'a' as usize] = 0;
+    invalid.push((lengths, dist_lengths.clone()));
+    // the end of block without a code
+    let mut lengths = lit_lengths.clone();
+    lengths[256] = 0;
+    invalid.push((lengths, dist_lengths.clone()));
+    // too many or too few lengths
+    invalid.push((vec![9; 287], dist_lengths.clone()));
+    invalid.push((lit_lengths.clone(), Vec::new()));
+    for (lit_lengths, dist_lengths) in invalid {
+        let mut output = BitStreamWriter::new();
+        assert_eq!(
+            block.encode_with_tables(&lit_lengths, &dist_lengths, &mut output),
+            Err(EncodeError::InvalidInput)
+        );
+        assert_eq!(output.bit_count(), 0);
+    }
+}
+
+#[test]
+fn inflate_concatenated_members() {
+    let inputs = [
+        LOREM_TXT.to_vec(),
+        b"x".to_vec(),
+        fib_str(b'a', b'b', 0x12345),
+        random_alphabet(0, 255, 0x1234),
+    ];
+    let mut input = Vec::new();
+    let mut expected = Vec::new();
+    let mut boundaries = Vec::new();
+    for (index, data) in inputs.iter().enumerate() {
+        let start = input.len();
+        let encoded = match index {
+            0 => deflate(data, CompressionLevel::Default, None),
+            1 => deflate_zlib(data, CompressionLevel::Default, None),
+            2 => deflate_zlib(data, CompressionLevel::Best, None),
+            _ => deflate(data, CompressionLevel::Level0, None),
+        }
+        .unwrap();
+        input.extend_from_slice(&encoded);
+        boundaries.push(InflateMember {
+            input: start..input.len(),
+            output: expected.len()..expected.len() + data.len(),
+        });
+        expected.extend_from_slice(data);
+    }
+
+    let (output, members) = inflate_members(&input).unwrap();
+    assert_eq_array(&output, &expected);
+    assert_eq!(members, boundaries);
+
+    assert_eq!(inflate_members(&[]).unwrap(), (Vec::new(), Vec::new()));
+    let mut invalid = input.clone();
+    invalid.push(0xff);
+    assert!(inflate_members(&invalid).is_err());
+    assert_eq!(
+        inflate_members(&input[..input.len() - 1]),
+        Err(DecodeError::UnexpectedEof)
+    );
+    assert_eq!(
+        inflate_members_with_limits(
+            &input,
+            InflateLimits::DEFAULT.max_output(expected.len() - 1)
+        ),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn inflate_consumed_input() {
+    let input = fib_str(b'a', b'b', 0x4321);
+    let trailer = b"trailing fields";
+    for (encoded, header_size, is_stored) in [
+        (
+            deflate(&input, CompressionLevel::Default, None).unwrap(),
+            0,
+            false,
+        ),
+        (
+            deflate(&input, CompressionLevel::Level0, None).unwrap(),
+            0,
+            true,
+        ),
+        (
+            deflate_zlib(&input, CompressionLevel::Default, None).unwrap(),
+            2,
+            false,
+        ),
+    ] {
+        let mut data = encoded.clone();
+        data.extend_from_slice(trailer);
+
+        let (output, consumed) = inflate_prefix(&data, input.len()).unwrap();
+        assert_eq_array(&output, &input);
+        assert_eq!(consumed, encoded.len());
+
+        let mut output = vec![0; input.len()];
+        assert_eq!(inflate_in_place(&data, &mut output), Ok(encoded.len()));
+        assert_eq_array(&output, &input);
+
+        let mut stream = InflateStream::new();
+        for chunk in data.chunks(1000) {
+            stream.push_input(chunk);
+        }
+        stream.finish_input();
+        while !matches!(stream.poll().unwrap(), InflateStatus::Done) {}
+        assert_eq!(stream.total_in(), encoded.len());
+
+        // a short output stops in the middle of a Huffman block,
+        // while a stored block does not fit
+        match inflate_prefix(&data, 100) {
+            Ok((_, consumed)) => {
+                assert!(!is_stored);
+                assert!(consumed > header_size && consumed < encoded.len());
+            }
+            Err(error) => {
+                assert!(is_stored);
+                assert_eq!(error, DecodeError::InvalidData);
+            }
+        }
+    }
+}
+
+#[test]
+fn deflate_progress() {
+    use core::ops::ControlFlow;
+
+    let input = fib_str(b'a', b'b', 0x54321);
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        let mut reports = Vec::new();
+        let encoded = deflate_with_progress(&input, level, None, &mut |progress| {
+            reports.push(progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(encoded, deflate(&input, level, None).unwrap());
+        assert!(reports.is_sorted_by_key(|v| (v.consumed, v.emitted)));
+        assert!(reports.iter().all(|v| v.total == input.len()));
+        assert_eq!(
+            reports.last().map(|v| (v.consumed, v.emitted)),
+            Some((input.len(), encoded.len()))
+        );
+
+        // the stored level reports only once at the end
+        assert_eq!(reports.len() == 1, level.is_store_only());
+
+        let mut count = 0;
+        let result = deflate_with_progress(&input, level, None, &mut |_| {
+            count += 1;
+            ControlFlow::Break(())
+        });
+        assert_eq!(result, Err(EncodeError::Cancelled));
+        assert_eq!(count, 1);
+    }
+}
+
+/// Text that does not depend on the random number generator of the target
+fn _portable_text(len: usize) -> Vec<u8> {
+    let mut state = 2572u32;
+    let mut output = Vec::with_capacity(len);
+    while output.len() < len {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        match state >> 29 {
+            0 => output.extend_from_slice(&LOREM_TXT[..(state >> 8) as usize % LOREM_TXT.len()]),
+            1 => output.extend_from_slice(&fib_str(b'a', b'b', (state >> 16) as usize % 0x400)),
+            _ => output.push(b'a' + (state >> 24) as u8 % 26),
+        }
+    }
+    output.truncate(len);
+    output
+}
+
+#[test]
+fn deflate_deterministic() {
+    let input = _portable_text(0x20000);
+    let options = OptionConfig::new().deterministic(true);
+    // The checksums of the output are the same on every target.
+    for (level, expected) in [
+        (CompressionLevel::Level0, 0x1fbe5ead),
+        (CompressionLevel::Fastest, 0xbaa3a73e),
+        (CompressionLevel::Fast, 0x7a018a27),
+        (CompressionLevel::Level3, 0xbc5cfe6b),
+        (CompressionLevel::Level4, 0x4e53ef89),
+        (CompressionLevel::Level5, 0x5e68a906),
+        (CompressionLevel::Default, 0x4e397f9b),
+        (CompressionLevel::Level7, 0x7276bf74),
+        (CompressionLevel::Level8, 0x04f34b5f),
+        (CompressionLevel::Best, 0xe5c99027),
+        (CompressionLevel::Auto, 0x4e397f9b),
+    ] {
+        let encoded = deflate(&input, level, options.into()).unwrap();
+        assert_eq!(adler32::checksum(&encoded), expected, "{level:?}");
+        assert_eq!(deflate(&input, level, options.into()).unwrap(), encoded);
+        assert_eq_array(&inflate(&encoded, input.len()).unwrap(), &input);
+    }
+
+    // The number of threads does not change the output.
+    let input = _portable_text(0x90000);
+    let encoded = deflate(&input, CompressionLevel::Fastest, options.into()).unwrap();
+    assert_eq!(
+        deflate(&input, CompressionLevel::Fastest, options.threads(4).into()).unwrap(),
+        encoded
+    );
+    assert_eq!(
+        deflate(&input, CompressionLevel::Fastest, None).unwrap(),
+        encoded
+    );
+}
+
+#[test]
+fn deflate_inspect() {
+    let mut input = fib_str(b'a', b'b', 0x12345);
+    input.extend_from_slice(&random_alphabet(0, 255, 0x4000));
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        for options in [OptionConfig::new(), OptionConfig::new().zlib()] {
+            let encoded = deflate(&input, level, options.into()).unwrap();
+            let info = inspect(&encoded).unwrap();
+            assert_eq!(info.output_len, input.len());
+            assert_eq!(info.stream_len, encoded.len());
+            assert_eq!(
+                info.blocks.iter().map(|v| v.output_len()).sum::<usize>(),
+                input.len()
+            );
+            assert_eq!(
+                info.blocks.iter().position(|v| v.is_final),
+                Some(info.blocks.len() - 1)
+            );
+
+            let header_size = if info.is_zlib {
+                assert_eq!(info.checksum, Some(adler32::checksum(&input)));
+                2
+            } else {
+                assert_eq!(info.checksum, None);
+                0
+            };
+            let bits = info.total_bits();
+            assert_eq!(
+                header_size + bits.total().div_ceil(8),
+                info.stream_len - info.checksum.map_or(0, |_| 4)
+            );
+            for pair in info.blocks.windows(2) {
+                assert_eq!(
+                    pair[0].bit_offset + pair[0].bits.total(),
+                    pair[1].bit_offset
+                );
+            }
+
+            if level.is_store_only() {
+                assert!(
+                    info.blocks
+                        .iter()
+                        .all(|v| v.block_type == BlockType::Stored)
+                );
+                assert_eq!(bits.stored, input.len() * 8);
+            } else {
+                assert!(
+                    info.blocks
+                        .iter()
+                        .any(|v| v.block_type != BlockType::Stored)
+                );
+                assert!(info.blocks.iter().any(|v| v.matches > 0));
+                assert!(bits.lengths > 0 && bits.distances > 0);
+                for block in info
+                    .blocks
+                    .iter()
+                    .filter(|v| v.block_type == BlockType::Dynamic)
+                {
+                    assert!(block.lit_lengths.len() >= 257);
+                    assert!(block.lit_lengths[256] > 0);
+                    assert!(block.bits.tables > 0);
+                }
+            }
+        }
+    }
+
+    // a distance beyond the start of the output
+    use crate::num::bits::BitStreamWriter;
+    let mut writer = BitStreamWriter::new();
+    writer.push_bool(true); // bfinal
+    writer.push_bits64(0b01, 2); // btype
+    writer.push_bits64(0b1000000, 7); // length 3 (code 257 = 0000001)
+    writer.push_bits64(0, 5); // distance 1
+    writer.push_bits64(0, 7); // end of block
+    let encoded = writer.into_bytes();
+    assert_eq!(inspect(&encoded), Err(DecodeError::InvalidData));
+    assert_eq!(inspect(&[]), Err(DecodeError::UnexpectedEof));
+}
+
+#[test]
+fn deflate_stats() {
+    let mut input = fib_str(b'a', b'b', 0x12345);
+    input.extend_from_slice(&random_alphabet(0, 255, 0x8000));
+    input.extend_from_slice(LOREM_TXT);
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        let (encoded, stats) = deflate_with_stats(&input, level, None).unwrap();
+        assert_eq!(encoded, deflate(&input, level, None).unwrap());
+        assert_eq!(
+            stats.iter().map(|v| v.input_len).sum::<usize>(),
+            input.len()
+        );
+        assert_eq!(
+            stats.iter().map(|v| v.bit_count).sum::<usize>().div_ceil(8),
+            encoded.len()
+        );
+        assert_eq!(stats.last().map(|v| v.is_final), Some(true));
+        for block in stats.iter() {
+            assert_eq!(block.length_histogram.total(), block.matches);
+            assert_eq!(block.distance_histogram.total(), block.matches);
+            assert_eq!(
+                block.literals
+                    + (3..=258)
+                        .map(|len| block.length_histogram.as_slice()[len] * len)
+                        .sum::<usize>(),
+                block.input_len
+            );
+        }
+
+        // The blocks agree with the parsed stream, except that a long stored block is split.
+        let info = inspect(&encoded).unwrap();
+        if level.is_store_only() {
+            assert_eq!(stats.len(), 1);
+            assert_eq!(stats[0].block_type, BlockType::Stored);
+            continue;
+        }
+        assert!(stats.iter().any(|v| v.block_type == BlockType::Stored));
+        let compressed = |block_type| block_type != BlockType::Stored;
+        let expected = info
+            .blocks
+            .iter()
+            .filter(|v| compressed(v.block_type))
+            .map(|v| (v.block_type, v.literals, v.matches, v.bits.total()))
+            .collect::<Vec<_>>();
+        let actual = stats
+            .iter()
+            .filter(|v| compressed(v.block_type))
+            .map(|v| (v.block_type, v.literals, v.matches, v.bit_count))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn deflate_context() {
+    let mut context = DeflateContext::new();
+    let inputs = [
+        b"Hello, world!".to_vec(),
+        LOREM_TXT.to_vec(),
+        random_alphabet(0, 255, 1000),
+        random_ab(b'a', b'b', 0x4000),
+        LOREM_TXT.repeat(10),
+        b"a".to_vec(),
+    ];
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        for options in [None, Some(OptionConfig::new().zlib())] {
+            for input in &inputs {
+                let expected = deflate(input, level, options).unwrap();
+                let encoded = context.deflate(input, level, options).unwrap();
+                assert_eq_array(encoded, &expected);
+                assert_eq_array(context.inflate(&expected, input.len()).unwrap(), input);
+            }
+        }
+    }
+
+    // the context is still usable after errors, and keeps its output buffer
+    let capacity = context.output.capacity();
+    assert_eq!(
+        context.deflate(&[], CompressionLevel::Default, None),
+        Err(EncodeError::InvalidInput)
+    );
+    assert_eq!(context.output.capacity(), capacity);
+    assert!(context.inflate(&[0xff; 16], 100).is_err());
+    let encoded = context
+        .deflate(LOREM_TXT, CompressionLevel::Default, None)
+        .unwrap()
+        .to_vec();
+    assert_eq_array(
+        context.inflate(&encoded, LOREM_TXT.len()).unwrap(),
+        LOREM_TXT,
+    );
+}
+
+#[test]
+fn inflate_raw() {
+    // a non-final stored block with a padding bit set, whose first byte looks like a zlib header
+    let input = b"\x08\x05\x00\xfa\xffhello\x03\x00";
+    let options = InflateOptions::new().raw();
+    assert_eq!(inflate_with_options(input, 5, options).unwrap(), b"hello");
+    assert_eq!(
+        inflate_to_vec_with_options(input, InflateLimits::DEFAULT, options).unwrap(),
+        b"hello"
+    );
+
+    // zlib streams are not detected in the raw mode
+    let encoded = deflate_zlib(LOREM_TXT, CompressionLevel::Default, None).unwrap();
+    assert!(inflate_to_vec_with_options(&encoded, InflateLimits::DEFAULT, options).is_err());
+    assert_eq_array(
+        &inflate_with_options(&encoded[2..], LOREM_TXT.len(), options).unwrap(),
+        LOREM_TXT,
+    );
+}
+
+#[test]
+fn deflate_context_tables() {
+    fn bytes<T>(table: &Vec<T>) -> usize {
+        table.capacity() * size_of::<T>()
+    }
+
+    // Drops a group of tables, and returns their size in bytes.
+    type DropTables = fn(&mut DeflateContext) -> usize;
+    let drop_offsets = |context: &mut DeflateContext| {
+        let offsets = core::mem::take(&mut context.offsets);
+        bytes(&offsets.head) + bytes(&offsets.prev) + bytes(&offsets.keys)
+    };
+    let drop_dynamic = |context: &mut DeflateContext| {
+        let lit = core::mem::take(&mut context.prefix_tables.lit);
+        let dist = core::mem::take(&mut context.prefix_tables.dist);
+        bytes(&lit) + bytes(&dist)
+    };
+    let drop_fixed = |context: &mut DeflateContext| {
+        let lit = core::mem::take(&mut context.prefix_tables.fixed_lit);
+        let dist = core::mem::take(&mut context.prefix_tables.fixed_dist);
+        bytes(&lit) + bytes(&dist)
+    };
+
+    // The small blocks of the text are encoded twice, to choose a fixed or dynamic code,
+    // while the large blocks of the random data are encoded once with a dynamic code.
+    let inputs: [(Vec<u8>, &[DropTables]); 2] = [
+        (
+            LOREM_TXT.repeat(10),
+            &[drop_offsets, drop_dynamic, drop_fixed],
+        ),
+        (
+            random_alphabet_seeded(b'a', b'd', 0x10000, 2605),
+            &[drop_offsets, drop_dynamic],
+        ),
+    ];
+    for (input, groups) in &inputs {
+        for level in [CompressionLevel::Default, CompressionLevel::Level8] {
+            let mut context = DeflateContext::new();
+            let expected = context.deflate(input, level, None).unwrap().to_vec();
+            let deflate = |context: &mut DeflateContext| {
+                let (encoded, allocated) =
+                    allocated_bytes(|| context.deflate(input, level, None).map(<[u8]>::to_vec));
+                assert_eq_array(&encoded.unwrap(), &expected);
+                allocated
+            };
+
+            // the second call allocates no tables
+            let reused = deflate(&mut context);
+
+            // the same call allocates each group of tables once it is dropped
+            for drop_tables in groups.iter() {
+                let tables = drop_tables(&mut context);
+                assert!(tables > 0, "{level:?}");
+                let allocated = deflate(&mut context);
+                assert!(
+                    reused + tables <= allocated,
+                    "{level:?}: {reused} + {tables} > {allocated}"
+                );
+            }
+        }
+    }
+}
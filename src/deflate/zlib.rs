@@ -0,0 +1,150 @@
+//! zlib stream format
+//!
+//! A zlib stream is a deflate stream with a header and an Adler-32 trailer.
+//! [`Header`] reads and writes the header for containers that embed zlib streams, such as PNG and PDF.
+//!
+//! See also: <https://www.ietf.org/rfc/rfc1950.txt>
+//!
+//! ```
+//! use compress::deflate::{self, CompressionLevel, OptionConfig, WindowSize, zlib};
+//!
+//! let encoded = deflate::deflate(b"abcabc", CompressionLevel::Best, OptionConfig::new().zlib().into()).unwrap();
+//! let header = zlib::Header::parse(&encoded).unwrap();
+//! assert_eq!(header.window_size, WindowSize::Size256);
+//! assert_eq!(header.flevel, 3);
+//! assert!(!header.has_dict);
+//! assert_eq!(header.to_bytes()[..header.size()], encoded[..2]);
+//! ```
+
+use super::*;
+
+/// Compression method of deflate (CM)
+const CM_DEFLATE: u8 = 8;
+
+/// The header is followed by the Adler-32 of a preset dictionary (FDICT)
+const FLG_FDICT: u8 = 0x20;
+
+/// Size of the Adler-32 trailer
+pub const TRAILER_SIZE: usize = 4;
+
+/// Header of a zlib stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Size of the sliding window (CINFO)
+    pub window_size: WindowSize,
+    /// Compression level from 0 (fastest) to 3 (maximum compression) (FLEVEL)
+    pub flevel: u8,
+    /// The stream is compressed with a preset dictionary (FDICT)
+    pub has_dict: bool,
+    /// Adler-32 of the preset dictionary if `has_dict` is set, otherwise zero (DICTID)
+    pub dict_id: u32,
+}
+
+impl Header {
+    /// Size of the header without a preset dictionary
+    pub const MIN_LEN: usize = 2;
+
+    /// Size of the header with a preset dictionary
+    pub const MAX_LEN: usize = 6;
+
+    #[inline]
+    pub const fn new(window_size: WindowSize, level: CompressionLevel) -> Self {
+        Self {
+            window_size,
+            flevel: level.zlib_flevel(),
+            has_dict: false,
+            dict_id: 0,
+        }
+    }
+
+    /// Marks the stream as compressed with the preset dictionary.
+    #[inline]
+    pub fn with_dictionary(mut self, dictionary: &[u8]) -> Self {
+        self.has_dict = true;
+        self.dict_id = adler32::checksum(dictionary);
+        self
+    }
+
+    /// Returns `true` if the first byte can be that of a zlib header (CM is deflate).
+    ///
+    /// This is only a guess for input of unknown format, since a raw deflate stream can also start with such a byte,
+    /// such as a non-final stored block with padding bits set.
+    /// Decoders of formats known to be raw deflate should use [`InflateOptions::raw`](super::InflateOptions::raw).
+    #[inline]
+    pub const fn is_zlib(leading: u8) -> bool {
+        leading & 0x0f == CM_DEFLATE
+    }
+
+    /// Reads the header at the start of the input.
+    ///
+    /// Returns `DecodeError::InvalidData` if the method is not deflate, the window exceeds 32KB,
+    /// or the check bits (FCHECK) do not match.
+    pub fn parse(input: &[u8]) -> Result<Self, DecodeError> {
+        let cmf = *input.first().ok_or(DecodeError::UnexpectedEof)?;
+        let flg = *input.get(1).ok_or(DecodeError::UnexpectedEof)?;
+        if !Self::is_zlib(cmf) || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+            return Err(DecodeError::InvalidData);
+        }
+        let window_size = match cmf >> 4 {
+            0 => WindowSize::Size256,
+            1 => WindowSize::Size512,
+            2 => WindowSize::Size1024,
+            3 => WindowSize::Size2048,
+            4 => WindowSize::Size4096,
+            5 => WindowSize::Size8192,
+            6 => WindowSize::Size16384,
+            7 => WindowSize::Size32768,
+            _ => return Err(DecodeError::InvalidData),
+        };
+        let has_dict = (flg & FLG_FDICT) != 0;
+        let dict_id = if has_dict {
+            let dict_id = input.get(2..6).ok_or(DecodeError::UnexpectedEof)?;
+            u32::from_be_bytes(dict_id.try_into().unwrap())
+        } else {
+            0
+        };
+        Ok(Self {
+            window_size,
+            flevel: flg >> 6,
+            has_dict,
+            dict_id,
+        })
+    }
+
+    /// Returns the size of the header in bytes.
+    #[inline]
+    pub const fn size(&self) -> usize {
+        if self.has_dict {
+            Self::MAX_LEN
+        } else {
+            Self::MIN_LEN
+        }
+    }
+
+    /// Returns the header, which is the first [`Header::size`] bytes of the array.
+    pub const fn to_bytes(&self) -> [u8; Self::MAX_LEN] {
+        let cmf = ((self.window_size as u8) << 4) | CM_DEFLATE;
+        let mut flg = (self.flevel & 3) << 6;
+        if self.has_dict {
+            flg |= FLG_FDICT;
+        }
+        flg |= (31 - (cmf as u16 * 256 + flg as u16) % 31) as u8;
+        let dict_id = self.dict_id.to_be_bytes();
+        [cmf, flg, dict_id[0], dict_id[1], dict_id[2], dict_id[3]]
+    }
+
+    /// Appends the header to the output.
+    #[inline]
+    pub fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.to_bytes()[..self.size()]);
+    }
+}
+
+/// Reads the Adler-32 trailer at the start of the input.
+#[inline]
+pub fn parse_trailer(input: &[u8]) -> Result<u32, DecodeError> {
+    let trailer = input
+        .get(..TRAILER_SIZE)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    Ok(u32::from_be_bytes(trailer.try_into().unwrap()))
+}
@@ -1,8 +1,15 @@
 //! Finite State Entropy coder
 //!
+//! * [`FseEncoder`] and [`FseDecoder`] implement a static table-based ANS (tANS) coder,
+//!   with two interleaved states sharing one bitstream.
+//! * [`AdaptiveBitEncoder`] and [`AdaptiveBitDecoder`] implement a binary ANS coder with adaptive probabilities.
+//!
 //! See also: <https://en.wikipedia.org/wiki/Asymmetric_numeral_systems>
 
-use crate::{num::bits::BitSize, *};
+use crate::num::VarLenInteger;
+use crate::num::bits::{BitSize, BitStreamReader, BitStreamWriter};
+use crate::*;
+use alloc::vec;
 
 const INIT_PROB: u8 = 0x80;
 const INIT_STATE: u32 = 0x1000;
@@ -11,38 +18,355 @@ pub const CONTEXT_INITIAL: usize = 0;
 pub const CONTEXT_BYTE: usize = 1;
 pub const CONTEXT_BYTE_MAX: usize = CONTEXT_BYTE + 255;
 
+/// Minimum size of the state table in bits
+pub const MIN_TABLE_LOG: u8 = 5;
+
+/// Maximum size of the state table in bits
+pub const MAX_TABLE_LOG: u8 = 15;
+
+/// Size of the state table in bits used by [`FSE::encode_bytes`]
+pub const DEFAULT_TABLE_LOG: u8 = 11;
+
 /// Finite State Entropy coder
 pub struct FSE;
 
 impl FSE {
+    /// Encodes bytes with a table normalized from their frequencies, followed by the table itself.
     pub fn encode_bytes(input: &[u8]) -> Vec<u8> {
-        let mut encoder = FseEncoder::new(CONTEXT_BYTE_MAX);
-        for &byte in input {
-            encoder.encode_byte(byte);
+        if input.is_empty() {
+            return Vec::new();
         }
-        encoder.finish()
+        let freq_table = stats::histogram_of_bytes(input);
+        let table = NormalizedFrequencies::new(&freq_table, DEFAULT_TABLE_LOG).unwrap();
+        let mut writer = BitStreamWriter::new();
+        table.write(&mut writer);
+        FseEncoder::new(&table).encode(input, &mut writer).unwrap();
+        writer.into_bytes()
     }
 
     pub fn decode_bytes(input: &[u8], len: usize) -> Option<Vec<u8>> {
-        let mut iter = input.iter().copied();
-        let mut decoder = FseDecoder::new(&mut iter, CONTEXT_BYTE_MAX)?;
         let mut result = Vec::with_capacity(len);
-        while result.len() < len {
-            result.push(decoder.decode_byte()?);
+        if len == 0 {
+            return Some(result);
         }
+        let mut reader = BitStreamReader::new(input);
+        let table = NormalizedFrequencies::read(&mut reader, 256).ok()?;
+        FseDecoder::new(&table)
+            .decode(&mut reader, len, |symbol| result.push(symbol as u8))
+            .ok()?;
         Some(result)
     }
 }
 
+/// Symbol frequencies normalized so that they sum to `1 << table_log`
+///
+/// Every symbol that appears at least once gets a count of at least one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedFrequencies {
+    table_log: u8,
+    counts: Vec<u32>,
+}
+
+impl NormalizedFrequencies {
+    /// Normalizes a frequency table.
+    ///
+    /// Returns `EncodeError::EntropyError` if there are more symbols than states.
+    pub fn new(freq_table: &[usize], table_log: u8) -> Result<Self, EncodeError> {
+        if !(MIN_TABLE_LOG..=MAX_TABLE_LOG).contains(&table_log) {
+            return Err(EncodeError::InvalidInput);
+        }
+        let table_size = 1u64 << table_log;
+        let total = freq_table.iter().map(|&v| v as u64).sum::<u64>();
+        if total == 0 {
+            return Err(EncodeError::InvalidInput);
+        }
+        if freq_table.iter().filter(|&&v| v > 0).count() as u64 > table_size {
+            return Err(EncodeError::EntropyError);
+        }
+
+        let mut counts = freq_table
+            .iter()
+            .map(|&freq| {
+                if freq > 0 {
+                    ((freq as u64 * table_size / total) as u32).max(1)
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let sum = counts.iter().map(|&v| v as u64).sum::<u64>();
+        if sum < table_size {
+            // largest remainder first
+            let mut order = (0..counts.len())
+                .filter(|&i| freq_table[i] > 0)
+                .collect::<Vec<_>>();
+            order.sort_by_key(|&i| core::cmp::Reverse(freq_table[i] as u64 * table_size % total));
+            for &i in order.iter().cycle().take((table_size - sum) as usize) {
+                counts[i] += 1;
+            }
+        } else {
+            // take from the largest counts, which are the least affected relatively
+            let mut excess = sum - table_size;
+            while excess > 0 {
+                let (index, &max) = counts.iter().enumerate().max_by_key(|v| v.1).unwrap();
+                let delta = ((max / 8).max(1) as u64).min(excess);
+                counts[index] -= delta as u32;
+                excess -= delta;
+            }
+        }
+
+        Ok(Self { table_log, counts })
+    }
+
+    /// Creates a table from normalized counts, which must sum to `1 << table_log`.
+    pub fn from_counts(counts: &[u32], table_log: u8) -> Option<Self> {
+        if !(MIN_TABLE_LOG..=MAX_TABLE_LOG).contains(&table_log)
+            || counts.iter().map(|&v| v as u64).sum::<u64>() != 1 << table_log
+        {
+            return None;
+        }
+        Some(Self {
+            table_log,
+            counts: counts.to_vec(),
+        })
+    }
+
+    #[inline]
+    pub const fn table_log(&self) -> u8 {
+        self.table_log
+    }
+
+    #[inline]
+    pub const fn table_size(&self) -> usize {
+        1 << self.table_log
+    }
+
+    #[inline]
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Writes the table.
+    ///
+    /// Each count is written with just enough bits to represent the remaining total,
+    /// and the trailing symbols are omitted once the total is reached.
+    pub fn write(&self, writer: &mut BitStreamWriter) {
+        writer.push(
+            VarLenInteger::new_checked(BitSize::Bit4, (self.table_log - MIN_TABLE_LOG) as u32)
+                .unwrap(),
+        );
+        let mut remaining = self.table_size() as u32;
+        for &count in self.counts.iter() {
+            if remaining == 0 {
+                break;
+            }
+            writer.push(VarLenInteger::new_checked(Self::_count_bits(remaining), count).unwrap());
+            remaining -= count;
+        }
+    }
+
+    /// Reads a table written by [`NormalizedFrequencies::write`].
+    pub fn read(reader: &mut BitStreamReader, max_symbols: usize) -> Result<Self, DecodeError> {
+        let table_log = MIN_TABLE_LOG
+            + reader
+                .read_bits(BitSize::Bit4)
+                .ok_or(DecodeError::UnexpectedEof)? as u8;
+        if table_log > MAX_TABLE_LOG {
+            return Err(DecodeError::InvalidData);
+        }
+        let mut counts = Vec::new();
+        let mut remaining = 1u32 << table_log;
+        while remaining > 0 {
+            if counts.len() >= max_symbols {
+                return Err(DecodeError::InvalidData);
+            }
+            let count = reader
+                .read_bits(Self::_count_bits(remaining))
+                .ok_or(DecodeError::UnexpectedEof)?;
+            if count > remaining {
+                return Err(DecodeError::InvalidData);
+            }
+            counts.push(count);
+            remaining -= count;
+        }
+        Ok(Self { table_log, counts })
+    }
+
+    #[inline]
+    fn _count_bits(remaining: u32) -> BitSize {
+        BitSize::new(remaining.ilog2() as u8 + 1).unwrap()
+    }
+
+    /// Spreads the symbols over the states, so that each symbol is scattered across the table.
+    fn _spread(&self) -> Vec<u16> {
+        let table_size = self.table_size();
+        let mask = table_size - 1;
+        let step = (table_size >> 1) + (table_size >> 3) + 3;
+        let mut table = vec![0; table_size];
+        let mut position = 0;
+        for (symbol, &count) in self.counts.iter().enumerate() {
+            for _ in 0..count {
+                table[position] = symbol as u16;
+                position = (position + step) & mask;
+            }
+        }
+        table
+    }
+}
+
+/// Static tANS encoder
+///
+/// Symbols are encoded with two interleaved states, alternating between even and odd positions.
 pub struct FseEncoder {
+    table_log: u8,
+    /// count and index of the first state of each symbol in `states`
+    symbols: Vec<(u32, u32)>,
+    /// states of each symbol, in ascending order
+    states: Vec<u16>,
+}
+
+impl FseEncoder {
+    pub fn new(table: &NormalizedFrequencies) -> Self {
+        let spread = table._spread();
+        let mut symbols = Vec::with_capacity(table.counts().len());
+        let mut cumul = 0;
+        for &count in table.counts() {
+            symbols.push((count, cumul));
+            cumul += count;
+        }
+        let mut states = vec![0; table.table_size()];
+        let mut next = symbols.iter().map(|v| v.1).collect::<Vec<_>>();
+        for (state, &symbol) in spread.iter().enumerate() {
+            let next = &mut next[symbol as usize];
+            states[*next as usize] = state as u16;
+            *next += 1;
+        }
+        Self {
+            table_log: table.table_log(),
+            symbols,
+            states,
+        }
+    }
+
+    /// Encodes symbols and writes them to `writer`.
+    ///
+    /// Returns `EncodeError::EntropyError` if a symbol is not in the table.
+    pub fn encode<T>(&self, input: &[T], writer: &mut BitStreamWriter) -> Result<(), EncodeError>
+    where
+        T: Copy + Into<usize>,
+    {
+        let table_size = 1u32 << self.table_log;
+        let mut states = [table_size; 2];
+
+        // The decoder reads the bits in the reverse order of encoding.
+        let mut bits = Vec::with_capacity(input.len() + 2);
+        for (index, &symbol) in input.iter().enumerate().rev() {
+            let &(count, first) = self
+                .symbols
+                .get(symbol.into())
+                .filter(|v| v.0 > 0)
+                .ok_or(EncodeError::EntropyError)?;
+            let state = &mut states[index & 1];
+            let nb_bits_base = self.table_log as u32 - count.ilog2();
+            let nb_bits = if (*state >> nb_bits_base) >= count {
+                nb_bits_base
+            } else {
+                nb_bits_base - 1
+            };
+            bits.push((*state & ((1 << nb_bits) - 1), nb_bits as u8));
+            let x = *state >> nb_bits;
+            *state = table_size + self.states[(first + x - count) as usize] as u32;
+        }
+        bits.push((states[1] - table_size, self.table_log));
+        bits.push((states[0] - table_size, self.table_log));
+
+        for &(value, nb_bits) in bits.iter().rev() {
+            if let Some(size) = BitSize::new(nb_bits) {
+                writer.push(VarLenInteger::new_checked(size, value).unwrap());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Static tANS decoder
+pub struct FseDecoder {
+    table_log: u8,
+    entries: Vec<DecodeEntry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DecodeEntry {
+    symbol: u16,
+    nb_bits: u8,
+    base: u16,
+}
+
+impl FseDecoder {
+    pub fn new(table: &NormalizedFrequencies) -> Self {
+        let table_log = table.table_log();
+        let table_size = table.table_size() as u32;
+        let mut next = table.counts().to_vec();
+        let entries = table
+            ._spread()
+            .into_iter()
+            .map(|symbol| {
+                let next = &mut next[symbol as usize];
+                let x = *next;
+                *next += 1;
+                let nb_bits = table_log - x.ilog2() as u8;
+                DecodeEntry {
+                    symbol,
+                    nb_bits,
+                    base: ((x << nb_bits) - table_size) as u16,
+                }
+            })
+            .collect();
+        Self { table_log, entries }
+    }
+
+    /// Decodes `len` symbols from `reader`.
+    pub fn decode<F>(
+        &self,
+        reader: &mut BitStreamReader,
+        len: usize,
+        mut f: F,
+    ) -> Result<(), DecodeError>
+    where
+        F: FnMut(usize),
+    {
+        let table_log = BitSize::new(self.table_log).unwrap();
+        let mut states = [0u32; 2];
+        for state in states.iter_mut() {
+            *state = reader
+                .read_bits(table_log)
+                .ok_or(DecodeError::UnexpectedEof)?;
+        }
+        for index in 0..len {
+            let state = &mut states[index & 1];
+            let entry = self.entries[*state as usize];
+            f(entry.symbol as usize);
+            let bits = match BitSize::new(entry.nb_bits) {
+                Some(size) => reader.read_bits(size).ok_or(DecodeError::UnexpectedEof)?,
+                None => 0,
+            };
+            *state = entry.base as u32 + bits;
+        }
+        Ok(())
+    }
+}
+
+/// Binary ANS encoder with adaptive probabilities
+pub struct AdaptiveBitEncoder {
     bits: Vec<(bool, u8)>,
     contexts: ContextState,
 }
 
-impl FseEncoder {
+impl AdaptiveBitEncoder {
     #[inline]
     pub fn new(size: usize) -> Self {
-        FseEncoder {
+        AdaptiveBitEncoder {
             bits: Vec::new(),
             contexts: ContextState::new(size),
         }
@@ -152,16 +476,20 @@ impl Context<'_> {
     }
 }
 
-pub struct FseDecoder<'a> {
+/// Binary ANS decoder with adaptive probabilities
+pub struct AdaptiveBitDecoder<'a> {
     state: u32,
     contexts: ContextState,
     reader: &'a mut dyn Iterator<Item = u8>,
 }
 
-impl FseDecoder<'_> {
+impl AdaptiveBitDecoder<'_> {
     #[inline]
-    pub fn new<'a>(reader: &'a mut dyn Iterator<Item = u8>, size: usize) -> Option<FseDecoder<'a>> {
-        let mut fse = FseDecoder {
+    pub fn new<'a>(
+        reader: &'a mut dyn Iterator<Item = u8>,
+        size: usize,
+    ) -> Option<AdaptiveBitDecoder<'a>> {
+        let mut fse = AdaptiveBitDecoder {
             state: 0,
             contexts: ContextState::new(size),
             reader,
@@ -230,3 +558,127 @@ impl FseDecoder<'_> {
         Some(acc)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(input: &[u8], table_log: u8) -> usize {
+        let freq_table = stats::histogram_of_bytes(input);
+        let table = NormalizedFrequencies::new(&freq_table, table_log).unwrap();
+        assert_eq!(
+            table.counts().iter().sum::<u32>() as usize,
+            table.table_size()
+        );
+        for (&count, &freq) in table.counts().iter().zip(freq_table.iter()) {
+            assert_eq!(count > 0, freq > 0);
+        }
+
+        let mut writer = BitStreamWriter::new();
+        FseEncoder::new(&table).encode(input, &mut writer).unwrap();
+        let encoded = writer.into_bytes();
+
+        let mut decoded = Vec::new();
+        let mut reader = BitStreamReader::new(&encoded);
+        FseDecoder::new(&table)
+            .decode(&mut reader, input.len(), |v| decoded.push(v as u8))
+            .unwrap();
+        assert_eq!(decoded, input);
+
+        encoded.len()
+    }
+
+    #[test]
+    fn fse_roundtrip() {
+        // skewed
+        let mut input = Vec::new();
+        for i in 0..0x10000u32 {
+            let v = i.wrapping_mul(0x9E37_79B1) >> 24;
+            input.push(match v {
+                0..=191 => b'a',
+                192..=239 => b'b',
+                240..=253 => b'c',
+                254 => b'd',
+                _ => (v as u8) ^ (i as u8),
+            });
+        }
+        for table_log in [8, DEFAULT_TABLE_LOG, MAX_TABLE_LOG] {
+            roundtrip(&input, table_log);
+        }
+
+        // close to the entropy
+        let input = input
+            .iter()
+            .map(|&v| v.clamp(b'a', b'd'))
+            .collect::<Vec<_>>();
+        let entropy = stats::histogram_of_bytes(&input)
+            .iter()
+            .filter(|&&v| v > 0)
+            .map(|&v| {
+                let p = v as f64 / input.len() as f64;
+                -p * libm::log2(p)
+            })
+            .sum::<f64>();
+        let size = roundtrip(&input, DEFAULT_TABLE_LOG);
+        assert!((size as f64) < entropy * input.len() as f64 / 8.0 * 1.01 + 16.0);
+
+        // uniform
+        let input = (0..0x4000).map(|v| v as u8).collect::<Vec<_>>();
+        let size = roundtrip(&input, DEFAULT_TABLE_LOG);
+        assert!(size <= input.len() + 8);
+
+        let input = random_alphabet(0, 255, 0x1000);
+        roundtrip(&input, DEFAULT_TABLE_LOG);
+
+        // single symbol
+        let input = [b'x'; 1000];
+        assert!(roundtrip(&input, MIN_TABLE_LOG) <= 8);
+        roundtrip(&input[..1], MIN_TABLE_LOG);
+    }
+
+    #[test]
+    fn fse_table() {
+        let table = NormalizedFrequencies::new(&[100, 0, 1, 7, 0, 3000], 8).unwrap();
+        assert_eq!(table.counts().iter().sum::<u32>(), 256);
+        assert_eq!(table.counts()[1], 0);
+        assert_eq!(table.counts()[2], 1);
+
+        let mut writer = BitStreamWriter::new();
+        table.write(&mut writer);
+        let data = writer.into_bytes();
+        let mut reader = BitStreamReader::new(&data);
+        assert_eq!(
+            NormalizedFrequencies::read(&mut reader, 256),
+            Ok(table.clone())
+        );
+        let mut reader = BitStreamReader::new(&data);
+        assert_eq!(
+            NormalizedFrequencies::read(&mut reader, 3),
+            Err(DecodeError::InvalidData)
+        );
+
+        assert!(NormalizedFrequencies::from_counts(table.counts(), 8).is_some());
+        assert!(NormalizedFrequencies::from_counts(table.counts(), 9).is_none());
+
+        assert_eq!(
+            NormalizedFrequencies::new(&[1; 40], MIN_TABLE_LOG),
+            Err(EncodeError::EntropyError)
+        );
+        assert_eq!(
+            NormalizedFrequencies::new(&[0; 4], DEFAULT_TABLE_LOG),
+            Err(EncodeError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn fse_bytes() {
+        for input in [
+            Vec::new(),
+            fib_str(b'a', b'b', 0x1000),
+            random_alphabet(b'a', b'z', 0x1000),
+        ] {
+            let encoded = FSE::encode_bytes(&input);
+            assert_eq!(FSE::decode_bytes(&encoded, input.len()).unwrap(), input);
+        }
+    }
+}
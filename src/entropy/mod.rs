@@ -1,30 +1,29 @@
 //! Entropy coder
 
 use crate::num::math;
+use crate::stats;
 
 #[path = "prefix/prefix.rs"]
 pub mod prefix;
 
 pub mod fse;
 
+pub mod range;
+
+pub mod rans;
+
 /// Calculates the entropy of a sequence of blocks.
 pub fn entropy_of_blocks(blocks: &[&[u8]]) -> f64 {
     let mut freq_table = [0; 256];
     for bytes in blocks {
-        for &byte in bytes.iter() {
-            freq_table[byte as usize] += 1;
-        }
+        stats::count_bytes(&mut freq_table, bytes);
     }
     entropy_of(&freq_table)
 }
 
 /// Calculate the entropy of a byte slice.
 pub fn entropy_of_bytes(bytes: &[u8]) -> f64 {
-    let mut freq_table = [0; 256];
-    for &byte in bytes {
-        freq_table[byte as usize] += 1;
-    }
-    entropy_of(&freq_table)
+    entropy_of(&stats::histogram_of_bytes(bytes))
 }
 
 /// Calculate the entropy of a frequency table.
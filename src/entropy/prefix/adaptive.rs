@@ -0,0 +1,246 @@
+//! Adaptive Prefix Coding
+//!
+//! A semi-adaptive coder that encodes a stream in one pass without transmitting the code lengths.
+//! The encoder and the decoder count the symbols in the same way,
+//! and periodically rebuild the same canonical prefix code from the counts.
+
+use super::*;
+use crate::num::{
+    VarLenInteger,
+    bits::{BitSize, BitStreamReader, BitStreamWriter},
+};
+use crate::*;
+
+/// Symbol counts shared by [`AdaptivePrefixEncoder`] and [`AdaptivePrefixDecoder`]
+#[derive(Debug, Clone)]
+pub struct AdaptivePrefixModel {
+    counts: Vec<usize>,
+    total: usize,
+    max_len: BitSize,
+    interval: usize,
+    until_rebuild: usize,
+}
+
+impl AdaptivePrefixModel {
+    /// The code is first rebuilt after this number of symbols
+    pub const INITIAL_REBUILD_INTERVAL: usize = 32;
+
+    /// The interval doubles after each rebuild up to this number of symbols
+    pub const MAX_REBUILD_INTERVAL: usize = 4096;
+
+    /// The counts are halved when the total exceeds this value, so that the code follows recent statistics
+    pub const MAX_TOTAL: usize = 0x2000;
+
+    /// Creates a model in which all symbols are equally likely.
+    ///
+    /// Returns `None` if the alphabet has less than two symbols.
+    pub fn new(alphabet_size: usize, max_len: BitSize) -> Option<Self> {
+        if alphabet_size < 2 || alphabet_size > 1 << max_len.as_usize() {
+            return None;
+        }
+        let mut counts = Vec::new();
+        counts.resize(alphabet_size, 1);
+        Some(Self {
+            counts,
+            total: alphabet_size,
+            max_len,
+            interval: Self::INITIAL_REBUILD_INTERVAL,
+            until_rebuild: Self::INITIAL_REBUILD_INTERVAL,
+        })
+    }
+
+    #[inline]
+    pub fn alphabet_size(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns the prefix code of each symbol for the current counts.
+    pub fn prefix_table(&self) -> Vec<Option<VarLenInteger>> {
+        CanonicalPrefixCoder::make_prefix_table(&self.counts, self.max_len, self.counts.len())
+    }
+
+    /// Counts the symbol, and returns `true` if the code needs to be rebuilt.
+    fn update(&mut self, symbol: usize) -> bool {
+        self.counts[symbol] += 1;
+        self.total += 1;
+        if self.total > Self::MAX_TOTAL {
+            self.total = 0;
+            for count in self.counts.iter_mut() {
+                *count = count.div_ceil(2);
+                self.total += *count;
+            }
+        }
+        self.until_rebuild -= 1;
+        if self.until_rebuild == 0 {
+            self.interval = (self.interval * 2).min(Self::MAX_REBUILD_INTERVAL);
+            self.until_rebuild = self.interval;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Adaptive prefix encoder
+pub struct AdaptivePrefixEncoder {
+    model: AdaptivePrefixModel,
+    prefix_table: Vec<Option<VarLenInteger>>,
+}
+
+impl AdaptivePrefixEncoder {
+    #[inline]
+    pub fn new(model: AdaptivePrefixModel) -> Self {
+        let prefix_table = model.prefix_table();
+        Self {
+            model,
+            prefix_table,
+        }
+    }
+
+    /// Encodes a symbol.
+    ///
+    /// Returns `EncodeError::InvalidInput` if the symbol is out of the alphabet.
+    pub fn encode(
+        &mut self,
+        symbol: usize,
+        writer: &mut BitStreamWriter,
+    ) -> Result<(), EncodeError> {
+        let code = self
+            .prefix_table
+            .get(symbol)
+            .copied()
+            .flatten()
+            .ok_or(EncodeError::InvalidInput)?;
+        writer.push(code.reversed());
+        if self.model.update(symbol) {
+            self.prefix_table = self.model.prefix_table();
+        }
+        Ok(())
+    }
+
+    /// Encodes bytes with an alphabet of 256 symbols.
+    pub fn encode_bytes(input: &[u8]) -> Vec<u8> {
+        let model = AdaptivePrefixModel::new(256, BitSize::Bit15).unwrap();
+        let mut encoder = Self::new(model);
+        let mut writer = BitStreamWriter::new();
+        for &byte in input {
+            encoder.encode(byte as usize, &mut writer).unwrap();
+        }
+        writer.into_bytes()
+    }
+}
+
+/// Adaptive prefix decoder
+pub struct AdaptivePrefixDecoder {
+    model: AdaptivePrefixModel,
+    decoder: CanonicalPrefixDecoder,
+}
+
+impl AdaptivePrefixDecoder {
+    #[inline]
+    pub fn new(model: AdaptivePrefixModel) -> Result<Self, DecodeError> {
+        let decoder = Self::_make_decoder(&model)?;
+        Ok(Self { model, decoder })
+    }
+
+    /// Decodes a symbol.
+    pub fn decode(&mut self, reader: &mut BitStreamReader) -> Result<usize, DecodeError> {
+        let symbol = self.decoder.decode(reader)? as usize;
+        if symbol >= self.model.alphabet_size() {
+            return Err(DecodeError::InvalidData);
+        }
+        if self.model.update(symbol) {
+            self.decoder = Self::_make_decoder(&self.model)?;
+        }
+        Ok(symbol)
+    }
+
+    /// Decodes `len` bytes encoded by [`AdaptivePrefixEncoder::encode_bytes`].
+    pub fn decode_bytes(input: &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+        let model = AdaptivePrefixModel::new(256, BitSize::Bit15).unwrap();
+        let mut decoder = Self::new(model)?;
+        let mut reader = BitStreamReader::new(input);
+        let mut output = Vec::with_capacity(len);
+        for _ in 0..len {
+            output.push(decoder.decode(&mut reader)? as u8);
+        }
+        Ok(output)
+    }
+
+    fn _make_decoder(model: &AdaptivePrefixModel) -> Result<CanonicalPrefixDecoder, DecodeError> {
+        let lengths = model
+            .prefix_table()
+            .iter()
+            .map(|v| v.map(|v| v.size().as_u8()).unwrap_or_default())
+            .collect::<Vec<_>>();
+        CanonicalPrefixDecoder::with_lengths(&lengths, false)
+    }
+}
+
+#[test]
+fn adaptive_prefix() {
+    let mut skewed = Vec::new();
+    for i in 0..0x10000u32 {
+        skewed.push(match i.wrapping_mul(0x9E37_79B1) >> 24 {
+            0..=191 => b'a',
+            192..=239 => b'b',
+            240..=253 => b'c',
+            v => v as u8,
+        });
+    }
+    for input in [
+        Vec::new(),
+        b"a".to_vec(),
+        (0..=255).collect(),
+        random_alphabet(0, 255, 0x1000),
+        fib_str(b'a', b'b', 0x1000),
+        skewed.clone(),
+    ] {
+        let encoded = AdaptivePrefixEncoder::encode_bytes(&input);
+        assert_eq!(
+            AdaptivePrefixDecoder::decode_bytes(&encoded, input.len()).unwrap(),
+            input
+        );
+    }
+
+    // a static prefix code needs about 1.3 bits per symbol
+    let encoded = AdaptivePrefixEncoder::encode_bytes(&skewed);
+    assert!(encoded.len() < skewed.len() / 8 * 3 / 2);
+
+    // the statistics change in the middle of the stream
+    let mut input = random_alphabet(b'a', b'b', 0x4000);
+    input.extend_from_slice(&random_alphabet(b'x', b'z', 0x4000));
+    let encoded = AdaptivePrefixEncoder::encode_bytes(&input);
+    // without adaptation, the symbols of the second half would take 9 bits each
+    assert!(encoded.len() < input.len() / 8 * 3);
+    assert_eq!(
+        AdaptivePrefixDecoder::decode_bytes(&encoded, input.len()).unwrap(),
+        input
+    );
+
+    assert!(
+        AdaptivePrefixDecoder::decode_bytes(&encoded[..encoded.len() / 2], input.len()).is_err()
+    );
+
+    // small alphabets
+    let model = AdaptivePrefixModel::new(3, BitSize::Bit4).unwrap();
+    let mut encoder = AdaptivePrefixEncoder::new(model.clone());
+    let mut writer = BitStreamWriter::new();
+    let input = random_alphabet(0, 2, 0x100);
+    for &symbol in input.iter() {
+        encoder.encode(symbol as usize, &mut writer).unwrap();
+    }
+    assert_eq!(
+        encoder.encode(3, &mut writer),
+        Err(EncodeError::InvalidInput)
+    );
+    let encoded = writer.into_bytes();
+    let mut decoder = AdaptivePrefixDecoder::new(model).unwrap();
+    let mut reader = BitStreamReader::new(&encoded);
+    for &symbol in input.iter() {
+        assert_eq!(decoder.decode(&mut reader), Ok(symbol as usize));
+    }
+
+    assert!(AdaptivePrefixModel::new(1, BitSize::Bit4).is_none());
+    assert!(AdaptivePrefixModel::new(17, BitSize::Bit4).is_none());
+}
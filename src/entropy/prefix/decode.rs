@@ -12,13 +12,14 @@ use core::cmp;
 ///
 /// If this value is too small, faster lookup tables cannot be used, resulting in slower decoding.
 /// However, if this value is too large, memory usage will increase while speed may not improve.
+///
+/// Longer codes are decoded through a second level of sub-tables, like zlib and libdeflate.
 const MAX_LOOKUP_TABLE_BITS: usize = 12;
 
 #[allow(unused)]
 pub struct CanonicalPrefixDecoder {
     lookup_table: Vec<LookupTableEntry>,
     lookup_table2: Vec<LookupTableEntry2>,
-    decode_tree: Vec<u32>,
     peek_bits: BitSize,
     max_bits: BitSize,
     min_bits: BitSize,
@@ -29,7 +30,6 @@ impl CanonicalPrefixDecoder {
     #[inline]
     fn new(max_symbol: usize, peek_bits: BitSize, max_bits: BitSize, min_bits: BitSize) -> Self {
         Self {
-            decode_tree: [0].to_vec(),
             lookup_table: Vec::new(),
             lookup_table2: Vec::new(),
             max_symbol,
@@ -78,17 +78,24 @@ impl CanonicalPrefixDecoder {
             BitSize::new(min_bits as u8).unwrap(),
         );
 
-        decoder.decode_tree.reserve(prefix_table.len() * 2);
-        for (value, path) in prefix_table.iter().copied() {
-            decoder.insert_node(path, value as u16)?;
-        }
-
         if is_lit {
             // For LZSS literal and length codes
-            decoder
-                .lookup_table2
-                .resize(1 << peek_bits, LookupTableEntry2::EMPTY);
-            let max_peek_value = decoder.lookup_table2.len();
+            _fill_lookup_table(
+                &mut decoder.lookup_table2,
+                &prefix_table,
+                peek_bits,
+                LookupTableEntry2::EMPTY,
+                |sym, bits| {
+                    Some(LookupTableEntry2::new(
+                        LitLen2::from_lit_len(sym as u32),
+                        bits,
+                    ))
+                },
+                LookupTableEntry2::link,
+            )?;
+
+            // Pairs of literals that fit in the root table
+            let max_peek_value = 1 << peek_bits;
             let prefix_table2 = prefix_table
                 .iter()
                 .filter(|(sym, path)| {
@@ -96,17 +103,6 @@ impl CanonicalPrefixDecoder {
                 })
                 .collect::<Vec<_>>();
             for (sym1, path1) in prefix_table.iter().copied() {
-                if path1.size().as_usize() > peek_bits {
-                    continue;
-                }
-                let entry =
-                    LookupTableEntry2::new(LitLen2::from_lit_len(sym1 as u32), path1.size());
-                let mut path = path1.value() as usize;
-                let delta = path1.size().power_of_two() as usize;
-                while path < max_peek_value {
-                    decoder.lookup_table2[path] = entry;
-                    path += delta;
-                }
                 if sym1 > 255 || path1.size().as_usize() + min_bits > peek_bits {
                     continue;
                 }
@@ -131,127 +127,106 @@ impl CanonicalPrefixDecoder {
             }
         } else {
             // For other prefix codes
-            decoder
-                .lookup_table
-                .resize(1 << peek_bits, LookupTableEntry::EMPTY);
-            let max_peek_value = decoder.lookup_table.len();
-            for (sym1, path1) in prefix_table.iter().copied() {
-                if path1.size().as_usize() > peek_bits {
-                    continue;
-                }
-                if let Some(entry) = LookupTableEntry::new(sym1, path1.size()) {
-                    let mut path = path1.value() as usize;
-                    let delta = path1.size().power_of_two() as usize;
-                    while path < max_peek_value {
-                        decoder.lookup_table[path] = entry;
-                        path += delta;
-                    }
-                }
-            }
+            _fill_lookup_table(
+                &mut decoder.lookup_table,
+                &prefix_table,
+                peek_bits,
+                LookupTableEntry::EMPTY,
+                LookupTableEntry::new,
+                LookupTableEntry::link,
+            )?;
         }
 
         Ok(decoder)
     }
 
-    fn insert_node(&mut self, path: VarLenInteger, value: u16) -> Result<(), DecodeError> {
-        let mut index = 0;
-        let mut rpath = path.value();
-        for _ in 1..path.size().as_usize() {
-            let bit = rpath & 1;
-            let mut next = self.decode_tree[index];
-            if bit != 0 {
-                next >>= 16;
-            } else {
-                next &= 0xffff;
-            }
-            if (next & DecodeTreeNode::LITERAL_FLAG) != 0 {
-                // Perhaps the prefix table is invalid or decoding failed.
+    /// Creates a decoder of the code lengths returned by [`CanonicalPrefixCoder::code_lengths`].
+    ///
+    /// Returns `DecodeError::InvalidData` if fewer than two symbols are used or the lengths are over-subscribed.
+    #[inline]
+    pub fn from_code_lengths(lengths: &[u8]) -> Result<Self, DecodeError> {
+        Self::with_lengths(lengths, false)
+    }
+
+    /// Reads the code lengths written by [`CanonicalPrefixCoder::serialize_code_lengths`].
+    pub fn deserialize_code_lengths(reader: &mut BitStreamReader) -> Result<Vec<u8>, DecodeError> {
+        let len = reader
+            .read_bits(BitSize::Bit16)
+            .ok_or(DecodeError::UnexpectedEof)? as usize;
+        let mut lengths = Vec::new();
+        if len > 0 {
+            CodeLengthCodec::DEFLATE.decode(reader, &mut lengths, len)?;
+            if lengths.len() != len {
+                // A repeat code must not run past the end
                 return Err(DecodeError::InvalidData);
             }
-            if next == 0 {
-                let new_index = self.decode_tree.len();
-                if bit != 0 {
-                    self.decode_tree[index] |= (new_index as u32) << 16;
-                } else {
-                    self.decode_tree[index] |= new_index as u32;
-                }
-                self.decode_tree.push(0);
-                index = new_index;
-            } else {
-                index = next as usize;
-            }
-            rpath >>= 1;
-        }
-        let bit = rpath & 1;
-        if bit != 0 {
-            self.decode_tree[index] |= (DecodeTreeNode::LITERAL_FLAG | value as u32) << 16;
-        } else {
-            self.decode_tree[index] |= DecodeTreeNode::LITERAL_FLAG | value as u32;
         }
-        Ok(())
+        Ok(lengths)
     }
 
+    /// Returns the number of entries of the lookup tables, including the sub-tables.
     #[inline]
-    pub fn root_node<'a>(&'a self) -> DecodeTreeNode<'a> {
-        DecodeTreeNode::new(&self.decode_tree, 0)
+    pub fn table_entries(&self) -> usize {
+        self.lookup_table.len() + self.lookup_table2.len()
     }
 
     /// Decodes a symbol using the lookup table.
     ///
-    /// This function is fast but cannot process prefix code that is not in the lookup table,
-    /// so it falls back to the slow version.
+    /// Codes longer than the root table are resolved with a single lookup in its sub-table.
     #[inline]
     pub fn decode(&self, reader: &mut BitStreamReader) -> Result<u32, DecodeError> {
-        if let Some(key) = reader.peek_bits(self.peek_bits) {
-            let entry = self
+        let key = reader.peek_bits_padded(self.peek_bits) as usize;
+        let mut entry = *self.lookup_table.get(key).ok_or(DecodeError::InvalidData)?;
+        if let Some((offset, bits)) = entry.sub_table() {
+            let key = reader.peek_bits_padded(bits) as usize >> self.peek_bits.as_usize();
+            entry = *self
                 .lookup_table
-                .get(key as usize)
+                .get(offset + key)
                 .ok_or(DecodeError::InvalidData)?;
-            if let Some(bits) = entry.bit_len() {
-                reader.advance(bits);
-                return Ok(entry.symbol1());
-            }
         }
-        self.decode_slow(reader)
+        let Some(bits) = entry.bit_len() else {
+            return Err(self._invalid_code(reader));
+        };
+        reader.advance(bits).ok_or(DecodeError::UnexpectedEof)?;
+        Ok(entry.symbol1())
     }
 
     /// Decode up to 2 literals using the lookup table.
     ///
-    /// This function is fast but cannot process prefix code that is not in the lookup table,
-    /// so it falls back to the slow version.
+    /// Codes longer than the root table are resolved with a single lookup in its sub-table.
     #[inline]
     pub fn decode_lit(&self, reader: &mut BitStreamReader) -> Result<LitLen2, DecodeError> {
-        if let Some(key) = reader.peek_bits(self.peek_bits) {
-            let entry = self
+        let key = reader.peek_bits_padded(self.peek_bits) as usize;
+        let mut entry = *self
+            .lookup_table2
+            .get(key)
+            .ok_or(DecodeError::InvalidData)?;
+        if let Some((offset, bits)) = entry.sub_table() {
+            let key = reader.peek_bits_padded(bits) as usize >> self.peek_bits.as_usize();
+            entry = *self
                 .lookup_table2
-                .get(key as usize)
+                .get(offset + key)
                 .ok_or(DecodeError::InvalidData)?;
-            if let Some(bits) = entry.bit_len() {
-                reader.advance(bits);
-                return Ok(entry.into_lit_len());
-            }
         }
-        self.decode_lit_slow(reader)
+        let Some(bits) = entry.bit_len() else {
+            return Err(self._invalid_code(reader));
+        };
+        reader.advance(bits).ok_or(DecodeError::UnexpectedEof)?;
+        Ok(entry.into_lit_len())
     }
 
-    /// Decodes a symbol.
+    /// Returns the error for a code that is not assigned to any symbol.
     ///
-    /// This function is slower than the lookup version, but can process all prefix codes.
-    pub fn decode_slow(&self, reader: &mut BitStreamReader) -> Result<u32, DecodeError> {
-        let mut node = self.root_node();
-        loop {
-            let bit = reader.read_bool().ok_or(DecodeError::UnexpectedEof)?;
-            match node.next(bit) {
-                ChildNode::Leaf(value) => return Ok(value),
-                ChildNode::Node(child) => node = child,
-            }
+    /// The bits beyond the end of the input are read as zero, so a truncated code is reported as such.
+    #[cold]
+    fn _invalid_code(&self, reader: &BitStreamReader) -> DecodeError {
+        if reader.remaining_bits() < self.max_bits.as_usize() {
+            DecodeError::UnexpectedEof
+        } else {
+            DecodeError::InvalidData
         }
     }
 
-    pub fn decode_lit_slow(&self, reader: &mut BitStreamReader) -> Result<LitLen2, DecodeError> {
-        self.decode_slow(reader).map(|v| LitLen2::from_lit_len(v))
-    }
-
     /// Create a canonical prefix code table from a length table.
     ///
     /// This function takes an iterator of (key, length) pairs, where `key` is the symbol and `length` is the bit length.
@@ -299,199 +274,136 @@ impl CanonicalPrefixDecoder {
         Ok(prefix_table)
     }
 
+    #[inline]
     pub fn decode_length_table_deflate(
         reader: &mut BitStreamReader,
         output: &mut Vec<u8>,
         output_size: usize,
     ) -> Result<(), DecodeError> {
-        let num_prefixes = 4 + reader.read_nibble().ok_or(DecodeError::InvalidData)? as usize;
-        let mut lengths = [0; 19];
-        for &index in PermutationFlavor::Deflate
-            .permutation_order()
-            .iter()
-            .take(num_prefixes)
-        {
-            let prefix_bit = reader
-                .read_bits(BitSize::Bit3)
-                .ok_or(DecodeError::InvalidData)?;
-            lengths[index as usize] = prefix_bit as u8;
-        }
-
-        output.reserve(output_size);
-        let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, false)?;
-        let mut prev = 0; // not strictly defined
-        while output.len() < output_size {
-            let decoded = decoder.decode(reader)? as u8;
-            match decoded {
-                0..=15 => {
-                    output.push(decoded);
-                    prev = decoded;
-                }
-                REP3P2 => {
-                    let ext_bits = 3 + reader
-                        .read_bits(BitSize::Bit2)
-                        .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(prev);
-                    }
-                }
-                REP3Z3 => {
-                    let ext_bits = 3 + reader
-                        .read_bits(BitSize::Bit3)
-                        .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(0);
-                    }
-                    prev = 0;
-                }
-                REP11Z7 => {
-                    let ext_bits = 11
-                        + reader
-                            .read_bits(BitSize::Bit7)
-                            .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(0);
-                    }
-                    prev = 0;
-                }
-                _ => return Err(DecodeError::InvalidData),
-            }
-        }
-
-        Ok(())
+        CodeLengthCodec::DEFLATE.decode(reader, output, output_size)
     }
 
+    #[inline]
     pub fn decode_length_table_webp(
         reader: &mut BitStreamReader,
         output: &mut Vec<u8>,
         output_size: usize,
     ) -> Result<(), DecodeError> {
-        let num_prefixes = 4 + reader.read_nibble().ok_or(DecodeError::InvalidData)? as usize;
-        let mut lengths = [0; 19];
-        for &index in PermutationFlavor::WebP
-            .permutation_order()
-            .iter()
-            .take(num_prefixes)
-        {
-            let prefix_bit = reader
-                .read_bits(BitSize::Bit3)
-                .ok_or(DecodeError::InvalidData)?;
-            let p = lengths
-                .get_mut(index as usize)
-                .ok_or(DecodeError::InvalidData)?;
-            *p = prefix_bit as u8;
-        }
-
-        output.reserve(output_size);
-        let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, false)?;
-        let mut prev = 8;
-        while output.len() < output_size {
-            let decoded = decoder.decode(reader)? as u8;
-            match decoded {
-                0 => {
-                    output.push(decoded);
-                }
-                1..=15 => {
-                    output.push(decoded);
-                    prev = decoded;
-                }
-                REP3P2 => {
-                    let ext_bits = 3 + reader
-                        .read_bits(BitSize::Bit2)
-                        .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(prev);
-                    }
-                }
-                REP3Z3 => {
-                    let ext_bits = 3 + reader
-                        .read_bits(BitSize::Bit3)
-                        .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(0);
-                    }
-                }
-                REP11Z7 => {
-                    let ext_bits = 11
-                        + reader
-                            .read_bits(BitSize::Bit7)
-                            .ok_or(DecodeError::InvalidData)?;
-                    for _ in 0..ext_bits {
-                        output.push(0);
-                    }
-                }
-                _ => return Err(DecodeError::InvalidData),
-            }
-        }
-
-        Ok(())
+        CodeLengthCodec::WEBP.decode(reader, output, output_size)
     }
 }
 
-#[derive(Debug)]
-pub struct DecodeTreeNode<'a> {
-    tree: &'a [u32],
-    index: u16,
-}
-
-impl<'a> DecodeTreeNode<'a> {
-    const LITERAL_FLAG: u32 = 0x8000;
-    const LITERAL_MASK: u32 = 0x7fff;
-
-    #[inline]
-    fn new(tree: &'a [u32], index: u16) -> Self {
-        Self { tree, index }
-    }
-
-    pub fn next(&self, bit: bool) -> ChildNode<'a> {
-        let mut next = self.tree[self.index as usize];
-        if bit {
-            next >>= 16;
+/// Fills a lookup table with the codes of the prefix table.
+///
+/// The root table has `1 << peek_bits` entries.
+/// Each root entry shared by codes longer than `peek_bits` links to a sub-table appended after the root table,
+/// which is indexed by the remaining bits of the longest of those codes.
+fn _fill_lookup_table<T: Copy>(
+    table: &mut Vec<T>,
+    prefix_table: &[(usize, VarLenInteger)],
+    peek_bits: usize,
+    empty: T,
+    new_entry: impl Fn(usize, BitSize) -> Option<T>,
+    new_link: impl Fn(usize, BitSize) -> T,
+) -> Result<(), DecodeError> {
+    let root_size = 1 << peek_bits;
+    table.clear();
+    table.resize(root_size, empty);
+
+    let mut sub_tables = BTreeMap::new();
+    for (_sym, path) in prefix_table.iter() {
+        let bits = path.size().as_usize();
+        if bits > peek_bits {
+            let root = path.value() as usize & (root_size - 1);
+            let max_bits = sub_tables.entry(root).or_insert(bits);
+            *max_bits = (*max_bits).max(bits);
         }
-        if next & Self::LITERAL_FLAG == 0 {
-            ChildNode::Node(DecodeTreeNode::new(self.tree, next as u16))
+    }
+    let sub_tables = sub_tables
+        .into_iter()
+        .map(|(root, bits)| {
+            let offset = table.len();
+            table[root] = new_link(offset, BitSize::new(bits as u8).unwrap());
+            table.resize(offset + (1 << (bits - peek_bits)), empty);
+            (root, (offset, bits))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for (sym, path) in prefix_table.iter().copied() {
+        let entry = new_entry(sym, path.size()).ok_or(DecodeError::InvalidData)?;
+        let bits = path.size().as_usize();
+        let (base, size, mut index, delta) = if bits > peek_bits {
+            let (offset, max_bits) = sub_tables[&(path.value() as usize & (root_size - 1))];
+            (
+                offset,
+                1 << (max_bits - peek_bits),
+                path.value() as usize >> peek_bits,
+                1 << (bits - peek_bits),
+            )
         } else {
-            ChildNode::Leaf(next & Self::LITERAL_MASK)
+            (0, root_size, path.value() as usize, 1 << bits)
+        };
+        while index < size {
+            table[base + index] = entry;
+            index += delta;
         }
     }
-}
 
-#[derive(Debug)]
-pub enum ChildNode<'a> {
-    Leaf(u32),
-    Node(DecodeTreeNode<'a>),
+    Ok(())
 }
 
 /// A lookup table entry for the canonical prefix decoder.
 ///
 /// format:
-/// * bit 0-3: bit lengths to advance (1-15)
-/// * bit 4-6: reserved, mbz
-/// * bit 7-15: symbol1
+/// * bit 0-7: bit lengths to advance (1-15), or `LINK_FLAG` with the bit lengths to peek for the sub-table
+/// * bit 8-31: symbol1, or the offset of the sub-table
 ///
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy)]
-pub struct LookupTableEntry(u16);
+pub struct LookupTableEntry(u32);
 
 impl LookupTableEntry {
     pub const EMPTY: Self = Self(0);
 
+    /// Symbols greater than this value cannot be stored in the lookup table
+    pub const MAX_SYMBOL: usize = 0xff_ffff;
+
+    const LINK_FLAG: u32 = 0x80;
+
     #[inline]
     pub fn new(symbol1: usize, bits: BitSize) -> Option<Self> {
-        if bits > BitSize::Bit15 {
+        if bits > BitSize::Bit15 || symbol1 > Self::MAX_SYMBOL {
             return None;
         }
-        Some(Self((bits.as_usize() as u16) | (symbol1 as u16) << 7))
+        Some(Self(bits.as_u32() | (symbol1 as u32) << 8))
+    }
+
+    /// Creates an entry of the root table that links to the sub-table at `offset`,
+    /// which is indexed by the bits following the first `peek_bits` of `bits`.
+    #[inline]
+    fn link(offset: usize, bits: BitSize) -> Self {
+        Self(Self::LINK_FLAG | bits.as_u32() | (offset as u32) << 8)
     }
 
     #[inline]
     pub const fn symbol1(&self) -> u32 {
-        self.0 as u32 >> 7
+        self.0 >> 8
     }
 
     #[inline]
     pub const fn bit_len(&self) -> Option<BitSize> {
-        BitSize::new((self.0 & 15) as u8)
+        BitSize::new(self.0 as u8)
+    }
+
+    /// Returns the offset of the sub-table and the bit lengths to peek for it.
+    #[inline]
+    pub fn sub_table(&self) -> Option<(usize, BitSize)> {
+        if self.0 & Self::LINK_FLAG != 0 {
+            let bits = BitSize::new((self.0 as u8) & !(Self::LINK_FLAG as u8))?;
+            Some(((self.0 >> 8) as usize, bits))
+        } else {
+            None
+        }
     }
 }
 
@@ -503,6 +415,9 @@ impl LookupTableEntry {
 /// * bit 16-23: symbol2
 /// * bit 24-31: number of bits to advance
 ///
+/// An entry that links to a sub-table has `LINK_FLAG` with the bit lengths to peek in bit 24-31,
+/// and the offset of the sub-table in bit 0-23.
+///
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct LookupTableEntry2(u32);
@@ -523,6 +438,8 @@ pub enum LitLen2 {
 impl LookupTableEntry2 {
     pub const EMPTY: Self = Self(0);
 
+    const LINK_FLAG: u32 = 0x80;
+
     #[inline]
     pub fn new(lit: LitLen2, bit_len: BitSize) -> Self {
         let mut lit: [u8; 4] = unsafe {
@@ -533,11 +450,29 @@ impl LookupTableEntry2 {
         Self(u32::from_le_bytes(lit))
     }
 
+    /// Creates an entry of the root table that links to the sub-table at `offset`.
+    #[inline]
+    fn link(offset: usize, bits: BitSize) -> Self {
+        Self((Self::LINK_FLAG | bits.as_u32()) << 24 | offset as u32)
+    }
+
     #[inline]
     pub fn bit_len(&self) -> Option<BitSize> {
         BitSize::new((self.0 >> 24) as u8)
     }
 
+    /// Returns the offset of the sub-table and the bit lengths to peek for it.
+    #[inline]
+    pub fn sub_table(&self) -> Option<(usize, BitSize)> {
+        let tag = self.0 >> 24;
+        if tag & Self::LINK_FLAG != 0 {
+            let bits = BitSize::new((tag & !Self::LINK_FLAG) as u8)?;
+            Some(((self.0 & 0xff_ffff) as usize, bits))
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn into_lit_len(self) -> LitLen2 {
         let lit: [u8; 4] = self.0.to_le_bytes();
@@ -626,3 +561,80 @@ fn literal2_repr() {
         LitLen2::EndOfBlock([0x78, 0x9a, 0xbc])
     );
 }
+
+#[test]
+fn two_level_lookup() {
+    use crate::num::bits::BitStreamWriter;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    // a skewed but complete code with lengths from 1 to 15 bits
+    let mut skewed = (1..=15).collect::<Vec<u8>>();
+    skewed.push(15);
+    // many long codes under different roots of the table
+    let mut wide = vec![8u8; 240];
+    wide.extend_from_slice(&[13; 96]);
+    wide.extend_from_slice(&[15; 256]);
+
+    let mut rng = StdRng::seed_from_u64(2555);
+    for lengths in [skewed, wide] {
+        let prefix_table = CanonicalPrefixDecoder::make_prefix_table(
+            lengths.iter().enumerate().map(|(i, &v)| (i, v)),
+            true,
+        )
+        .unwrap();
+        let codes = prefix_table.iter().copied().collect::<BTreeMap<_, _>>();
+        let symbols = (0..0x1000)
+            .map(|_| rng.random_range(0..lengths.len()))
+            .collect::<Vec<_>>();
+        let mut writer = BitStreamWriter::new();
+        for symbol in symbols.iter() {
+            writer.push(codes[symbol]);
+        }
+        let encoded = writer.into_bytes();
+
+        let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, false).unwrap();
+        assert!(decoder.table_entries() < 1 << 13);
+        let mut reader = BitStreamReader::new(&encoded);
+        for &symbol in symbols.iter() {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), symbol as u32);
+        }
+        assert!(reader.expect_end().is_ok());
+
+        let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, true).unwrap();
+        let mut reader = BitStreamReader::new(&encoded);
+        let mut decoded = Vec::new();
+        while decoded.len() < symbols.len() {
+            match decoder.decode_lit(&mut reader).unwrap() {
+                LitLen2::Double(sym1, sym2) => {
+                    decoded.extend([LitLen2::Single(sym1), LitLen2::Single(sym2)])
+                }
+                lit_len => decoded.push(lit_len),
+            }
+        }
+        assert!(
+            decoded
+                .into_iter()
+                .eq(symbols.iter().map(|&v| LitLen2::from_lit_len(v as u32)))
+        );
+    }
+
+    // an incomplete code
+    let decoder = CanonicalPrefixDecoder::with_lengths(&[1, 3, 14], false).unwrap();
+    assert_eq!(
+        decoder.decode(&mut BitStreamReader::new(&[0xff, 0xff])),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        decoder.decode(&mut BitStreamReader::new(&[0xff])),
+        Err(DecodeError::UnexpectedEof)
+    );
+    // a long code cut off
+    assert_eq!(
+        decoder.decode(&mut BitStreamReader::new(&[0x05, 0x00])),
+        Ok(2)
+    );
+    assert_eq!(
+        decoder.decode(&mut BitStreamReader::new(&[0x05])),
+        Err(DecodeError::UnexpectedEof)
+    );
+}
@@ -0,0 +1,303 @@
+//! Run-length coded code length tables
+
+use super::*;
+use crate::num::{
+    VarLenInteger,
+    bits::{BitSize, BitStreamReader},
+};
+use crate::*;
+
+/// A repeat code of a code length table
+///
+/// The symbol is followed by `extra_bits` bits, and the run length is `base + readbits(extra_bits)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatCode {
+    pub symbol: u8,
+    pub base: u8,
+    pub extra_bits: BitSize,
+}
+
+impl RepeatCode {
+    #[inline]
+    pub const fn new(symbol: u8, base: u8, extra_bits: BitSize) -> Self {
+        Self {
+            symbol,
+            base,
+            extra_bits,
+        }
+    }
+
+    /// Returns the minimum run length that can be represented by this code.
+    #[inline]
+    pub const fn min_len(&self) -> usize {
+        self.base as usize
+    }
+
+    /// Returns the maximum run length that can be represented by this code.
+    #[inline]
+    pub const fn max_len(&self) -> usize {
+        self.base as usize + self.extra_bits.mask() as usize
+    }
+}
+
+/// Descriptor of a run-length coded code length table
+///
+/// Deflate and WebP lossless encode their Huffman code lengths with the same scheme,
+/// but they differ slightly in the order of the code length code and in the handling of the previous value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeLengthCodec {
+    /// Order in which the lengths of the code length code are stored
+    pub permutation_order: &'static [u8; 19],
+    /// Largest code length that is coded as a literal
+    pub max_length: u8,
+    /// Value repeated by `repeat_prev` before any non-zero length is seen
+    pub initial_prev: u8,
+    /// Whether a zero length (literal or run) becomes the value repeated by `repeat_prev`
+    pub zero_updates_prev: bool,
+    /// Repeat the previous value
+    pub repeat_prev: RepeatCode,
+    /// Repeat zero (short run)
+    pub repeat_zero_short: RepeatCode,
+    /// Repeat zero (long run)
+    pub repeat_zero_long: RepeatCode,
+}
+
+impl CodeLengthCodec {
+    /// Code length table of deflate (RFC 1951 3.2.7)
+    pub const DEFLATE: Self = Self {
+        permutation_order: PermutationFlavor::ORDER_DEFLATE,
+        max_length: 15,
+        initial_prev: 0,
+        zero_updates_prev: true,
+        repeat_prev: RepeatCode::new(REP3P2, 3, BitSize::Bit2),
+        repeat_zero_short: RepeatCode::new(REP3Z3, 3, BitSize::Bit3),
+        repeat_zero_long: RepeatCode::new(REP11Z7, 11, BitSize::Bit7),
+    };
+
+    /// Code length table of WebP lossless
+    pub const WEBP: Self = Self {
+        permutation_order: PermutationFlavor::ORDER_WEBP,
+        max_length: 15,
+        initial_prev: 8,
+        zero_updates_prev: false,
+        repeat_prev: RepeatCode::new(REP3P2, 3, BitSize::Bit2),
+        repeat_zero_short: RepeatCode::new(REP3Z3, 3, BitSize::Bit3),
+        repeat_zero_long: RepeatCode::new(REP11Z7, 11, BitSize::Bit7),
+    };
+
+    /// Reads the code length code and decodes `output_size` code lengths.
+    pub fn decode(
+        &self,
+        reader: &mut BitStreamReader,
+        output: &mut Vec<u8>,
+        output_size: usize,
+    ) -> Result<(), DecodeError> {
+        let num_prefixes = 4 + reader.read_nibble().ok_or(DecodeError::InvalidData)? as usize;
+        let mut lengths = [0; 19];
+        for &index in self.permutation_order.iter().take(num_prefixes) {
+            let prefix_bit = reader
+                .read_bits(BitSize::Bit3)
+                .ok_or(DecodeError::InvalidData)?;
+            let p = lengths
+                .get_mut(index as usize)
+                .ok_or(DecodeError::InvalidData)?;
+            *p = prefix_bit as u8;
+        }
+
+        output.reserve(output_size);
+        let decoder = CanonicalPrefixDecoder::with_lengths(&lengths, false)?;
+        let mut prev = self.initial_prev;
+        while output.len() < output_size {
+            let decoded = decoder.decode(reader)? as u8;
+            if decoded <= self.max_length {
+                output.push(decoded);
+                if decoded > 0 || self.zero_updates_prev {
+                    prev = decoded;
+                }
+                continue;
+            }
+            let (code, value) = if decoded == self.repeat_prev.symbol {
+                (self.repeat_prev, prev)
+            } else if decoded == self.repeat_zero_short.symbol {
+                (self.repeat_zero_short, 0)
+            } else if decoded == self.repeat_zero_long.symbol {
+                (self.repeat_zero_long, 0)
+            } else {
+                return Err(DecodeError::InvalidData);
+            };
+            let len = code.base as u32
+                + reader
+                    .read_bits(code.extra_bits)
+                    .ok_or(DecodeError::InvalidData)?;
+            for _ in 0..len {
+                output.push(value);
+            }
+            if value == 0 && self.zero_updates_prev {
+                prev = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run-length encodes a code length table.
+    ///
+    /// Symbols are returned as 8-bit values, each repeat code is followed by its extra bits.
+    pub fn rle_encode(&self, input: &[u8]) -> Vec<VarLenInteger> {
+        let mut output = Vec::new();
+        let mut cursor = 0;
+        let mut prev = self.initial_prev;
+        while let Some(&current) = input.get(cursor) {
+            if current > 0 {
+                let len = if current == prev {
+                    CanonicalPrefixCoder::rle_match_len(
+                        prev,
+                        input,
+                        cursor,
+                        self.repeat_prev.max_len(),
+                    )
+                } else {
+                    0
+                };
+                if len >= self.repeat_prev.min_len() {
+                    Self::_push_repeat(&mut output, self.repeat_prev, len);
+                    cursor += len;
+                } else {
+                    output.push(VarLenInteger::with_byte(current));
+                    prev = current;
+                    cursor += 1;
+                }
+            } else {
+                let len = CanonicalPrefixCoder::rle_match_len(
+                    0,
+                    input,
+                    cursor,
+                    self.repeat_zero_long.max_len(),
+                );
+                if self.zero_updates_prev {
+                    prev = 0;
+                }
+                if len >= self.repeat_zero_long.min_len() {
+                    Self::_push_repeat(&mut output, self.repeat_zero_long, len);
+                    cursor += len;
+                } else if len >= self.repeat_zero_short.min_len() {
+                    let len = len.min(self.repeat_zero_short.max_len());
+                    Self::_push_repeat(&mut output, self.repeat_zero_short, len);
+                    cursor += len;
+                } else {
+                    output.push(VarLenInteger::with_byte(current));
+                    cursor += 1;
+                }
+            }
+        }
+        output
+    }
+
+    #[inline]
+    fn _push_repeat(output: &mut Vec<VarLenInteger>, code: RepeatCode, len: usize) {
+        output.push(VarLenInteger::with_byte(code.symbol));
+        output.push(
+            VarLenInteger::new_checked(code.extra_bits, (len - code.min_len()) as u32).unwrap(),
+        );
+    }
+}
+
+impl From<PermutationFlavor> for CodeLengthCodec {
+    #[inline]
+    fn from(value: PermutationFlavor) -> Self {
+        match value {
+            PermutationFlavor::Deflate => Self::DEFLATE,
+            PermutationFlavor::WebP => Self::WEBP,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::bits::{BitStreamWriter, Write};
+
+    #[test]
+    fn code_length_table() {
+        let mut input = Vec::new();
+        input.extend_from_slice(&[8; 10]);
+        input.extend_from_slice(&[0; 2]);
+        input.extend_from_slice(&[0; 200]);
+        input.extend_from_slice(&[5, 5, 5, 5, 7, 0, 7, 7, 7, 7]);
+        input.extend_from_slice(&[0; 5]);
+        input.extend_from_slice(&[9; 3]);
+
+        for flavor in [PermutationFlavor::Deflate, PermutationFlavor::WebP] {
+            let codec = CodeLengthCodec::from(flavor);
+            let encoded = CanonicalPrefixCoder::encode_prefix_tables(&[&input], flavor).unwrap();
+
+            let mut writer = BitStreamWriter::new();
+            writer.write(encoded.hclen);
+            writer.write(encoded.prefix_table.as_slice());
+            writer.write(encoded.content.as_slice());
+            let data = writer.into_bytes();
+
+            let mut reader = BitStreamReader::new(&data);
+            let mut output = Vec::new();
+            codec.decode(&mut reader, &mut output, input.len()).unwrap();
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn code_length_serialization() {
+        let mut freq_table = vec![0; 300];
+        for (index, p) in freq_table.iter_mut().enumerate().skip(20) {
+            *p = index % 7 * index % 13;
+        }
+        let prefix_table = CanonicalPrefixCoder::make_prefix_table(&freq_table, BitSize::Bit15, 0);
+        let lengths = CanonicalPrefixCoder::code_lengths(&prefix_table);
+
+        for input in [
+            &lengths[..],
+            &[],
+            &[0; 10],
+            &[8; 300],
+            &[1, 1],
+            &[15; 0xffff],
+        ] {
+            let mut writer = BitStreamWriter::new();
+            CanonicalPrefixCoder::serialize_code_lengths(input, &mut writer).unwrap();
+            writer.push_byte(0xa5);
+            let data = writer.into_bytes();
+            let mut reader = BitStreamReader::new(&data);
+            assert_eq!(
+                CanonicalPrefixDecoder::deserialize_code_lengths(&mut reader).unwrap(),
+                input
+            );
+            assert_eq!(reader.read_byte(), Some(0xa5));
+        }
+
+        // the decoder built from the lengths decodes the codes of the encoder
+        let decoder = CanonicalPrefixDecoder::from_code_lengths(&lengths).unwrap();
+        let symbols = (0..300).filter(|&v| freq_table[v] > 0).collect::<Vec<_>>();
+        let mut writer = BitStreamWriter::new();
+        for &symbol in symbols.iter() {
+            writer.push(prefix_table[symbol].unwrap().reversed());
+        }
+        let data = writer.into_bytes();
+        let mut reader = BitStreamReader::new(&data);
+        for &symbol in symbols.iter() {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), symbol as u32);
+        }
+
+        let mut writer = BitStreamWriter::new();
+        assert_eq!(
+            CanonicalPrefixCoder::serialize_code_lengths(&[16], &mut writer),
+            Err(EncodeError::InvalidInput)
+        );
+        assert_eq!(
+            CanonicalPrefixCoder::serialize_code_lengths(&[1; 0x10000], &mut writer),
+            Err(EncodeError::InvalidInput)
+        );
+        assert_eq!(
+            CanonicalPrefixDecoder::deserialize_code_lengths(&mut BitStreamReader::new(&[3])),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+}
@@ -4,9 +4,12 @@
 
 mod decode;
 mod encode;
+mod length_table;
 pub use decode::*;
 pub use encode::*;
+pub use length_table::*;
 
+pub mod adaptive;
 pub mod simple;
 
 /// Repeat the previous value `3 + readbits(2)` times
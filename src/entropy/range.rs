@@ -0,0 +1,428 @@
+//! Binary range coder with adaptive bit probabilities
+//!
+//! The bitstream is compatible with the range coder used by LZMA.
+//! Symbols of a larger alphabet can be coded in the same stream with their cumulative frequencies,
+//! which makes it an arithmetic coder for models such as [`crate::ppm`].
+//!
+//! See also: <https://en.wikipedia.org/wiki/Range_coding>
+
+use crate::num::bits::BitSize;
+use crate::*;
+
+/// Number of bits of the probabilities
+pub const PROB_BITS: u32 = 11;
+
+/// Adaptation speed of [`BitModel`]
+pub const MOVE_BITS: u32 = 5;
+
+const PROB_ONE: u32 = 1 << PROB_BITS;
+
+/// Range is renormalized when it falls below this value
+const TOP_VALUE: u32 = 1 << 24;
+
+/// Maximum total of the frequencies of [`RangeEncoder::encode_freq`]
+pub const MAX_TOTAL_FREQ: u32 = 1 << 16;
+
+/// Adaptive probability of a bit being 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitModel(u16);
+
+impl BitModel {
+    /// Both bit values are equally likely
+    pub const INITIAL: Self = Self((PROB_ONE / 2) as u16);
+
+    #[inline]
+    pub const fn new() -> Self {
+        Self::INITIAL
+    }
+
+    /// Returns the probability of 0 in units of `1 / (1 << PROB_BITS)`.
+    #[inline]
+    pub const fn prob(&self) -> u32 {
+        self.0 as u32
+    }
+
+    #[inline]
+    pub const fn update(&mut self, bit: bool) {
+        if bit {
+            self.0 -= self.0 >> MOVE_BITS;
+        } else {
+            self.0 += ((PROB_ONE - self.0 as u32) >> MOVE_BITS) as u16;
+        }
+    }
+}
+
+impl Default for BitModel {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Range encoder
+pub struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: usize,
+    output: Vec<u8>,
+}
+
+impl RangeEncoder {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            output: Vec::new(),
+        }
+    }
+
+    /// Encodes a bit with the model, and updates the model.
+    #[inline]
+    pub fn encode_bit(&mut self, model: &mut BitModel, bit: bool) {
+        let bound = (self.range >> PROB_BITS) * model.prob();
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        model.update(bit);
+        while self.range < TOP_VALUE {
+            self.range <<= 8;
+            self._shift_low();
+        }
+    }
+
+    /// Encodes the lower `bits` bits of the value with fixed probabilities, from the highest bit.
+    pub fn encode_direct_bits(&mut self, value: u32, bits: BitSize) {
+        for i in (0..bits.as_u32()).rev() {
+            self.range >>= 1;
+            if (value >> i) & 1 != 0 {
+                self.low += self.range as u64;
+            }
+            while self.range < TOP_VALUE {
+                self.range <<= 8;
+                self._shift_low();
+            }
+        }
+    }
+
+    /// Encodes the lower `bits` bits of the value from the highest bit,
+    /// using a binary tree of models which must have `1 << bits` elements.
+    pub fn encode_tree(&mut self, models: &mut [BitModel], value: u32, bits: BitSize) {
+        let mut index = 1;
+        for i in (0..bits.as_u32()).rev() {
+            let bit = (value >> i) & 1 != 0;
+            self.encode_bit(&mut models[index], bit);
+            index = (index << 1) | bit as usize;
+        }
+    }
+
+    /// Same as [`RangeEncoder::encode_tree`], but from the lowest bit.
+    pub fn encode_reverse_tree(&mut self, models: &mut [BitModel], value: u32, bits: BitSize) {
+        let mut index = 1;
+        for i in 0..bits.as_u32() {
+            let bit = (value >> i) & 1 != 0;
+            self.encode_bit(&mut models[index], bit);
+            index = (index << 1) | bit as usize;
+        }
+    }
+
+    /// Encodes a symbol that occupies `cum_freq..cum_freq + freq` of the frequencies summing to `total`.
+    ///
+    /// `freq` must not be zero, and `total` must not exceed [`MAX_TOTAL_FREQ`].
+    pub fn encode_freq(&mut self, cum_freq: u32, freq: u32, total: u32) {
+        debug_assert!(freq > 0 && cum_freq + freq <= total && total <= MAX_TOTAL_FREQ);
+        let step = self.range / total;
+        self.low += (step * cum_freq) as u64;
+        self.range = step * freq;
+        while self.range < TOP_VALUE {
+            self.range <<= 8;
+            self._shift_low();
+        }
+    }
+
+    /// Flushes the pending state and returns the encoded bytes.
+    pub fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self._shift_low();
+        }
+        self.output
+    }
+
+    fn _shift_low(&mut self) {
+        if self.low < 0xff00_0000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            for _ in 0..self.cache_size {
+                self.output.push(byte.wrapping_add(carry));
+                byte = 0xff;
+            }
+            self.cache_size = 0;
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+}
+
+impl Default for RangeEncoder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Range decoder
+pub struct RangeDecoder<'a> {
+    input: &'a [u8],
+    cursor: usize,
+    range: u32,
+    code: u32,
+    /// Range of a unit of frequency, set by `decode_freq`
+    step: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    pub fn new(input: &'a [u8]) -> Result<Self, DecodeError> {
+        let header = input.get(..5).ok_or(DecodeError::UnexpectedEof)?;
+        if header[0] != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        let code = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+        Ok(Self {
+            input,
+            cursor: 5,
+            range: u32::MAX,
+            code,
+            step: 0,
+        })
+    }
+
+    /// Returns the number of bytes consumed so far.
+    #[inline]
+    pub const fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// Returns whether the stream ended exactly where the encoder finished.
+    #[inline]
+    pub const fn is_finished(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Decodes a bit with the model, and updates the model.
+    #[inline]
+    pub fn decode_bit(&mut self, model: &mut BitModel) -> Result<bool, DecodeError> {
+        let bound = (self.range >> PROB_BITS) * model.prob();
+        let bit = if self.code < bound {
+            self.range = bound;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            true
+        };
+        model.update(bit);
+        self._normalize()?;
+        Ok(bit)
+    }
+
+    /// Decodes `bits` bits encoded by [`RangeEncoder::encode_direct_bits`].
+    pub fn decode_direct_bits(&mut self, bits: BitSize) -> Result<u32, DecodeError> {
+        let mut acc = 0;
+        for _ in 0..bits.as_u32() {
+            self.range >>= 1;
+            let bit = self.code >= self.range;
+            if bit {
+                self.code -= self.range;
+            }
+            acc = (acc << 1) | bit as u32;
+            self._normalize()?;
+        }
+        Ok(acc)
+    }
+
+    /// Decodes `bits` bits encoded by [`RangeEncoder::encode_tree`].
+    pub fn decode_tree(
+        &mut self,
+        models: &mut [BitModel],
+        bits: BitSize,
+    ) -> Result<u32, DecodeError> {
+        let mut index = 1;
+        for _ in 0..bits.as_u32() {
+            let bit = self.decode_bit(&mut models[index])?;
+            index = (index << 1) | bit as usize;
+        }
+        Ok((index - (1 << bits.as_u32())) as u32)
+    }
+
+    /// Decodes `bits` bits encoded by [`RangeEncoder::encode_reverse_tree`].
+    pub fn decode_reverse_tree(
+        &mut self,
+        models: &mut [BitModel],
+        bits: BitSize,
+    ) -> Result<u32, DecodeError> {
+        let mut index = 1;
+        let mut acc = 0;
+        for i in 0..bits.as_u32() {
+            let bit = self.decode_bit(&mut models[index])?;
+            index = (index << 1) | bit as usize;
+            acc |= (bit as u32) << i;
+        }
+        Ok(acc)
+    }
+
+    /// Returns the cumulative frequency of the next symbol for the frequencies summing to `total`.
+    ///
+    /// The symbol whose range contains the value must be consumed with [`Self::consume_freq`].
+    #[inline]
+    pub fn decode_freq(&mut self, total: u32) -> u32 {
+        debug_assert!(total > 0 && total <= MAX_TOTAL_FREQ);
+        self.step = self.range / total;
+        (self.code / self.step).min(total - 1)
+    }
+
+    /// Consumes the symbol that occupies `cum_freq..cum_freq + freq` after [`Self::decode_freq`].
+    pub fn consume_freq(&mut self, cum_freq: u32, freq: u32) -> Result<(), DecodeError> {
+        self.code -= self.step * cum_freq;
+        self.range = self.step * freq;
+        if self.code >= self.range {
+            return Err(DecodeError::InvalidData);
+        }
+        self._normalize()
+    }
+
+    #[inline]
+    fn _normalize(&mut self) -> Result<(), DecodeError> {
+        while self.range < TOP_VALUE {
+            let byte = *self
+                .input
+                .get(self.cursor)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            self.cursor += 1;
+            self.range <<= 8;
+            self.code = (self.code << 8) | byte as u32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_coder_skewed() {
+        // one bit in 64 is set
+        let bits = (0..0x10000u32)
+            .map(|v| v.wrapping_mul(0x9E37_79B1) >> 26 == 0)
+            .collect::<Vec<_>>();
+
+        let mut encoder = RangeEncoder::new();
+        let mut model = BitModel::new();
+        for &bit in bits.iter() {
+            encoder.encode_bit(&mut model, bit);
+        }
+        let encoded = encoder.finish();
+        // the entropy is about 0.116 bits per bit, while a prefix code needs at least one bit
+        assert!(encoded.len() < bits.len() / 8 / 6);
+
+        let mut decoder = RangeDecoder::new(&encoded).unwrap();
+        let mut model = BitModel::new();
+        for &bit in bits.iter() {
+            assert_eq!(decoder.decode_bit(&mut model), Ok(bit));
+        }
+        assert!(decoder.is_finished());
+        assert_eq!(decoder.position(), encoded.len());
+    }
+
+    #[test]
+    fn range_coder_roundtrip() {
+        let input = random_alphabet(b'a', b'h', 0x1000);
+
+        let mut encoder = RangeEncoder::new();
+        let mut tree = [BitModel::new(); 0x100];
+        let mut reverse_tree = [BitModel::new(); 0x10];
+        for (index, &byte) in input.iter().enumerate() {
+            encoder.encode_tree(&mut tree, byte as u32, BitSize::OCTET);
+            encoder.encode_reverse_tree(&mut reverse_tree, index as u32, BitSize::Bit4);
+            encoder.encode_direct_bits(index as u32, BitSize::Bit12);
+        }
+        let encoded = encoder.finish();
+
+        let mut decoder = RangeDecoder::new(&encoded).unwrap();
+        let mut tree = [BitModel::new(); 0x100];
+        let mut reverse_tree = [BitModel::new(); 0x10];
+        for (index, &byte) in input.iter().enumerate() {
+            assert_eq!(
+                decoder.decode_tree(&mut tree, BitSize::OCTET),
+                Ok(byte as u32)
+            );
+            assert_eq!(
+                decoder.decode_reverse_tree(&mut reverse_tree, BitSize::Bit4),
+                Ok(index as u32 & 15)
+            );
+            assert_eq!(
+                decoder.decode_direct_bits(BitSize::Bit12),
+                Ok(index as u32 & 0xfff)
+            );
+        }
+        assert!(decoder.is_finished());
+
+        // truncated
+        let mut decoder = RangeDecoder::new(&encoded[..encoded.len() / 2]).unwrap();
+        let mut tree = [BitModel::new(); 0x100];
+        let mut reverse_tree = [BitModel::new(); 0x10];
+        let result = (0..input.len()).try_for_each(|_| {
+            decoder.decode_tree(&mut tree, BitSize::OCTET)?;
+            decoder.decode_reverse_tree(&mut reverse_tree, BitSize::Bit4)?;
+            decoder.decode_direct_bits(BitSize::Bit12).map(|_| ())
+        });
+        assert_eq!(result, Err(DecodeError::UnexpectedEof));
+
+        assert!(RangeDecoder::new(&[0, 0, 0]).is_err());
+        assert!(RangeDecoder::new(&[1, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn range_coder_freq() {
+        // cumulative frequencies of 4 symbols, with a model bit between them
+        let freqs = [1u32, 100, 1000, MAX_TOTAL_FREQ - 1101];
+        let cum_freqs = [0, 1, 101, 1101];
+        let symbols = (0..0x4000u32)
+            .map(|v| match v.wrapping_mul(0x9E37_79B1) >> 20 {
+                0 => 0,
+                1..=25 => 1,
+                26..=300 => 2,
+                _ => 3,
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = RangeEncoder::new();
+        let mut model = BitModel::new();
+        for &symbol in symbols.iter() {
+            encoder.encode_freq(cum_freqs[symbol], freqs[symbol], MAX_TOTAL_FREQ);
+            encoder.encode_bit(&mut model, symbol == 0);
+        }
+        let encoded = encoder.finish();
+
+        let mut decoder = RangeDecoder::new(&encoded).unwrap();
+        let mut model = BitModel::new();
+        for &symbol in symbols.iter() {
+            let value = decoder.decode_freq(MAX_TOTAL_FREQ);
+            let decoded = cum_freqs.iter().rposition(|&v| v <= value).unwrap();
+            assert_eq!(decoded, symbol);
+            decoder
+                .consume_freq(cum_freqs[decoded], freqs[decoded])
+                .unwrap();
+            assert_eq!(decoder.decode_bit(&mut model), Ok(symbol == 0));
+        }
+        assert!(decoder.is_finished());
+        assert_eq!(decoder.position(), encoded.len());
+    }
+}
@@ -0,0 +1,244 @@
+//! Static rANS coder with two interleaved states
+//!
+//! Frequencies are normalized to [`PROB_BITS`] bits with [`NormalizedFrequencies`],
+//! which also provides the serialization of the table.
+//!
+//! See also: <https://en.wikipedia.org/wiki/Asymmetric_numeral_systems>
+
+use super::fse::NormalizedFrequencies;
+use crate::num::bits::{BitStreamReader, BitStreamWriter};
+use crate::*;
+
+/// Number of bits of the normalized frequencies
+pub const PROB_BITS: u8 = 12;
+
+const PROB_MASK: u32 = (1 << PROB_BITS) - 1;
+
+/// Lower bound of the states, which are renormalized one byte at a time
+const RANS_L: u32 = 1 << 23;
+
+/// Static rANS coder
+pub struct Rans;
+
+impl Rans {
+    /// Normalizes a frequency table to [`PROB_BITS`] bits.
+    #[inline]
+    pub fn normalize(freq_table: &[usize]) -> Result<NormalizedFrequencies, EncodeError> {
+        NormalizedFrequencies::new(freq_table, PROB_BITS)
+    }
+
+    /// Encodes bytes, preceded by their normalized frequency table.
+    pub fn encode_bytes(input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        let table = Self::normalize(&stats::histogram_of_bytes(input))?;
+        let mut writer = BitStreamWriter::new();
+        table.write(&mut writer);
+        let mut output = writer.into_bytes();
+        output.extend_from_slice(&RansEncoder::new(&table)?.encode(input)?);
+        Ok(output)
+    }
+
+    /// Decodes `len` bytes encoded by [`Rans::encode_bytes`].
+    pub fn decode_bytes(input: &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::with_capacity(len);
+        if len == 0 {
+            return Ok(output);
+        }
+        let mut reader = BitStreamReader::new(input);
+        let table = NormalizedFrequencies::read(&mut reader, 256)?;
+        reader.skip_to_next_byte_boundary();
+        let payload = &input[input.len() - reader.remaining_bytes()..];
+        RansDecoder::new(&table)?.decode(payload, len, |symbol| output.push(symbol as u8))?;
+        Ok(output)
+    }
+}
+
+/// Static rANS encoder
+pub struct RansEncoder {
+    /// frequency and cumulative frequency of each symbol
+    symbols: Vec<(u32, u32)>,
+}
+
+impl RansEncoder {
+    /// Returns `EncodeError::InvalidInput` if the table is not normalized to [`PROB_BITS`] bits.
+    pub fn new(table: &NormalizedFrequencies) -> Result<Self, EncodeError> {
+        if table.table_log() != PROB_BITS {
+            return Err(EncodeError::InvalidInput);
+        }
+        let mut cumul = 0;
+        let symbols = table
+            .counts()
+            .iter()
+            .map(|&freq| {
+                let start = cumul;
+                cumul += freq;
+                (freq, start)
+            })
+            .collect();
+        Ok(Self { symbols })
+    }
+
+    /// Encodes symbols.
+    ///
+    /// Returns `EncodeError::EntropyError` if a symbol is not in the table.
+    pub fn encode<T>(&self, input: &[T]) -> Result<Vec<u8>, EncodeError>
+    where
+        T: Copy + Into<usize>,
+    {
+        // The states are encoded in reverse, so the output is built backwards.
+        let mut output = Vec::with_capacity(input.len() / 2 + 8);
+        let mut states = [RANS_L; 2];
+        for (index, &symbol) in input.iter().enumerate().rev() {
+            let &(freq, start) = self
+                .symbols
+                .get(symbol.into())
+                .filter(|v| v.0 > 0)
+                .ok_or(EncodeError::EntropyError)?;
+            let state = &mut states[index & 1];
+            let max_state = ((RANS_L >> PROB_BITS) << 8) * freq;
+            while *state >= max_state {
+                output.push(*state as u8);
+                *state >>= 8;
+            }
+            *state = ((*state / freq) << PROB_BITS) + (*state % freq) + start;
+        }
+        output.extend_from_slice(&states[1].to_le_bytes());
+        output.extend_from_slice(&states[0].to_le_bytes());
+        output.reverse();
+        Ok(output)
+    }
+}
+
+/// Static rANS decoder
+pub struct RansDecoder {
+    /// frequency and cumulative frequency of each symbol
+    symbols: Vec<(u32, u32)>,
+    /// symbol of each slot
+    slots: Vec<u16>,
+}
+
+impl RansDecoder {
+    /// Returns `DecodeError::InvalidData` if the table is not normalized to [`PROB_BITS`] bits.
+    pub fn new(table: &NormalizedFrequencies) -> Result<Self, DecodeError> {
+        if table.table_log() != PROB_BITS {
+            return Err(DecodeError::InvalidData);
+        }
+        let mut symbols = Vec::with_capacity(table.counts().len());
+        let mut slots = Vec::with_capacity(table.table_size());
+        for (symbol, &freq) in table.counts().iter().enumerate() {
+            symbols.push((freq, slots.len() as u32));
+            slots.resize(slots.len() + freq as usize, symbol as u16);
+        }
+        Ok(Self { symbols, slots })
+    }
+
+    /// Decodes `len` symbols.
+    ///
+    /// Returns `DecodeError::InvalidData` if the states do not end where the encoder started.
+    pub fn decode<F>(&self, input: &[u8], len: usize, mut f: F) -> Result<(), DecodeError>
+    where
+        F: FnMut(usize),
+    {
+        let mut cursor = 0;
+        let mut states = [0u32; 2];
+        for state in states.iter_mut() {
+            let bytes = input
+                .get(cursor..cursor + 4)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            *state = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            cursor += 4;
+        }
+        for index in 0..len {
+            let state = &mut states[index & 1];
+            let slot = *state & PROB_MASK;
+            let symbol = self.slots[slot as usize];
+            let (freq, start) = self.symbols[symbol as usize];
+            f(symbol as usize);
+            *state = freq * (*state >> PROB_BITS) + slot - start;
+            while *state < RANS_L {
+                let byte = *input.get(cursor).ok_or(DecodeError::UnexpectedEof)?;
+                cursor += 1;
+                *state = (*state << 8) | byte as u32;
+            }
+        }
+        if states != [RANS_L; 2] {
+            return Err(DecodeError::InvalidData);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rans_roundtrip() {
+        let mut skewed = Vec::new();
+        for i in 0..0x10000u32 {
+            skewed.push(match i.wrapping_mul(0x9E37_79B1) >> 24 {
+                0..=191 => b'a',
+                192..=239 => b'b',
+                240..=253 => b'c',
+                _ => b'd',
+            });
+        }
+
+        for input in [
+            Vec::new(),
+            b"x".to_vec(),
+            [b'x'; 1000].to_vec(),
+            (0..0x4000).map(|v| v as u8).collect(),
+            random_alphabet(0, 255, 0x1000),
+            fib_str(b'a', b'b', 0x1000),
+            skewed.clone(),
+        ] {
+            let encoded = Rans::encode_bytes(&input).unwrap();
+            assert_eq!(Rans::decode_bytes(&encoded, input.len()).unwrap(), input);
+        }
+
+        // close to the entropy
+        let table = Rans::normalize(&stats::histogram_of_bytes(&skewed)).unwrap();
+        let payload = RansEncoder::new(&table).unwrap().encode(&skewed).unwrap();
+        let entropy = entropy::entropy_of_bytes(&skewed) * skewed.len() as f64 / 8.0;
+        assert!((payload.len() as f64) < entropy * 1.01 + 8.0);
+
+        let encoded = Rans::encode_bytes(&skewed).unwrap();
+
+        // corrupted
+        let mut corrupted = encoded.clone();
+        let len = corrupted.len();
+        corrupted[len - 10] ^= 0x55;
+        assert!(Rans::decode_bytes(&corrupted, skewed.len()).is_err());
+        assert!(Rans::decode_bytes(&encoded[..len - 10], skewed.len()).is_err());
+    }
+
+    #[test]
+    fn rans_symbols() {
+        let input = (0..0x3000u32)
+            .map(|v| (v.wrapping_mul(0x9E37_79B1) >> 20) as usize % 300)
+            .collect::<Vec<_>>();
+        let mut freq_table = [0; 300];
+        for &symbol in input.iter() {
+            freq_table[symbol] += 1;
+        }
+        let table = Rans::normalize(&freq_table).unwrap();
+        let encoded = RansEncoder::new(&table).unwrap().encode(&input).unwrap();
+
+        let mut decoded = Vec::new();
+        RansDecoder::new(&table)
+            .unwrap()
+            .decode(&encoded, input.len(), |v| decoded.push(v))
+            .unwrap();
+        assert_eq!(decoded, input);
+
+        assert_eq!(
+            RansEncoder::new(&table).unwrap().encode(&[300usize]).err(),
+            Some(EncodeError::EntropyError)
+        );
+        let table = NormalizedFrequencies::new(&freq_table, 11).unwrap();
+        assert!(RansEncoder::new(&table).is_err());
+    }
+}
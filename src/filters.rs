@@ -0,0 +1,338 @@
+//! Reversible filters applied before compression
+//!
+//! Numeric data such as time series and images often changes little between neighboring elements,
+//! so the differences are smaller and more repetitive than the values themselves,
+//! and compress better with [`deflate`] or [`stk1`].
+//!
+//! The `stride` is the distance in elements to the neighbor, such as the number of channels of interleaved samples.
+//!
+//! [`byte_shuffle`] groups the bytes of the same significance of the elements together, as Blosc does.
+//! The upper bytes of arrays of integers and floating point values are often similar or zero,
+//! and become long runs after the shuffle.
+//!
+//! ```
+//! use compress::filters::{self, Filter};
+//!
+//! let mut data = [100u32, 102, 104, 107, 110];
+//! filters::encode(&mut data, Filter::Delta, 1);
+//! assert_eq!(data, [100, 2, 2, 3, 3]);
+//! filters::decode(&mut data, Filter::Delta, 1);
+//! assert_eq!(data, [100, 102, 104, 107, 110]);
+//! ```
+
+use crate::*;
+
+/// Filter of [`encode`] and [`decode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Difference from the neighbor, for data that changes gradually
+    Delta,
+    /// Difference of the differences, for data that changes at a steady rate such as timestamps
+    DeltaOfDelta,
+    /// Exclusive or with the neighbor, for data whose upper bits rarely change such as floating point values
+    Xor,
+}
+
+/// Element type of the filters
+pub trait Element: Copy + Default {
+    /// Size in bytes
+    const SIZE: usize;
+
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    fn xor(self, rhs: Self) -> Self;
+
+    /// Reads the value from the first `SIZE` bytes in little endian.
+    fn read_le(bytes: &[u8]) -> Self;
+
+    /// Writes the value into the first `SIZE` bytes in little endian.
+    fn write_le(self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_element {
+    ($($ty:ty),*) => {
+        $(
+            impl Element for $ty {
+                const SIZE: usize = size_of::<$ty>();
+
+                #[inline]
+                fn wrapping_add(self, rhs: Self) -> Self {
+                    <$ty>::wrapping_add(self, rhs)
+                }
+
+                #[inline]
+                fn wrapping_sub(self, rhs: Self) -> Self {
+                    <$ty>::wrapping_sub(self, rhs)
+                }
+
+                #[inline]
+                fn xor(self, rhs: Self) -> Self {
+                    self ^ rhs
+                }
+
+                #[inline]
+                fn read_le(bytes: &[u8]) -> Self {
+                    <$ty>::from_le_bytes(bytes[..Self::SIZE].try_into().unwrap())
+                }
+
+                #[inline]
+                fn write_le(self, bytes: &mut [u8]) {
+                    bytes[..Self::SIZE].copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_element!(u8, u16, u32, u64);
+
+/// Applies the filter to the data in place.
+///
+/// The first `stride` elements are left as they are.
+///
+/// # Panics
+///
+/// Panics if `stride` is zero.
+pub fn encode<T: Element>(data: &mut [T], filter: Filter, stride: usize) {
+    assert!(stride > 0, "stride must be greater than zero");
+    match filter {
+        Filter::Delta => _encode_with(data, stride, T::wrapping_sub),
+        Filter::DeltaOfDelta => {
+            _encode_with(data, stride, T::wrapping_sub);
+            _encode_with(data, stride, T::wrapping_sub);
+        }
+        Filter::Xor => _encode_with(data, stride, T::xor),
+    }
+}
+
+/// Reverts the filter applied by [`encode`] in place.
+///
+/// # Panics
+///
+/// Panics if `stride` is zero.
+pub fn decode<T: Element>(data: &mut [T], filter: Filter, stride: usize) {
+    assert!(stride > 0, "stride must be greater than zero");
+    match filter {
+        Filter::Delta => _decode_with(data, stride, T::wrapping_add),
+        Filter::DeltaOfDelta => {
+            _decode_with(data, stride, T::wrapping_add);
+            _decode_with(data, stride, T::wrapping_add);
+        }
+        Filter::Xor => _decode_with(data, stride, T::xor),
+    }
+}
+
+/// Applies the filter to the bytes of little endian elements of `T` in place.
+///
+/// Returns `EncodeError::InvalidInput` if the length is not a multiple of the element size or `stride` is zero.
+pub fn encode_bytes<T: Element>(
+    data: &mut [u8],
+    filter: Filter,
+    stride: usize,
+) -> Result<(), EncodeError> {
+    let mut elements = _read_elements::<T>(data, stride).ok_or(EncodeError::InvalidInput)?;
+    encode(&mut elements, filter, stride);
+    _write_elements(&elements, data);
+    Ok(())
+}
+
+/// Reverts the filter applied by [`encode_bytes`] in place.
+///
+/// Returns `DecodeError::InvalidInput` if the length is not a multiple of the element size or `stride` is zero.
+pub fn decode_bytes<T: Element>(
+    data: &mut [u8],
+    filter: Filter,
+    stride: usize,
+) -> Result<(), DecodeError> {
+    let mut elements = _read_elements::<T>(data, stride).ok_or(DecodeError::InvalidInput)?;
+    decode(&mut elements, filter, stride);
+    _write_elements(&elements, data);
+    Ok(())
+}
+
+/// Transposes the bytes of the elements of `element_size` bytes,
+/// so that the first bytes of all elements come first, followed by the second bytes, and so on.
+///
+/// The trailing bytes that do not make up a whole element are left as they are.
+///
+/// # Panics
+///
+/// Panics if `element_size` is zero.
+///
+/// ```
+/// use compress::filters;
+///
+/// let data = [0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0xff];
+/// let shuffled = filters::byte_shuffle(&data, 2);
+/// assert_eq!(shuffled, [0x01, 0x02, 0x03, 0x00, 0x00, 0x00, 0xff]);
+/// assert_eq!(filters::byte_unshuffle(&shuffled, 2), data);
+/// ```
+pub fn byte_shuffle(data: &[u8], element_size: usize) -> Vec<u8> {
+    assert!(element_size > 0, "element_size must be greater than zero");
+    let count = data.len() / element_size;
+    let mut output = Vec::with_capacity(data.len());
+    for byte_index in 0..element_size {
+        output.extend(
+            data[..count * element_size]
+                .iter()
+                .skip(byte_index)
+                .step_by(element_size),
+        );
+    }
+    output.extend_from_slice(&data[count * element_size..]);
+    output
+}
+
+/// Reverts [`byte_shuffle`].
+///
+/// # Panics
+///
+/// Panics if `element_size` is zero.
+pub fn byte_unshuffle(data: &[u8], element_size: usize) -> Vec<u8> {
+    assert!(element_size > 0, "element_size must be greater than zero");
+    let count = data.len() / element_size;
+    let mut output = data.to_vec();
+    for (byte_index, plane) in data[..count * element_size]
+        .chunks_exact(count.max(1))
+        .enumerate()
+    {
+        for (element, &byte) in plane.iter().enumerate() {
+            output[element * element_size + byte_index] = byte;
+        }
+    }
+    output
+}
+
+/// Replaces each element with `f(element, neighbor)`, from the end so that the neighbors are still the original values.
+#[inline]
+fn _encode_with<T: Copy>(data: &mut [T], stride: usize, f: impl Fn(T, T) -> T) {
+    for i in (stride..data.len()).rev() {
+        data[i] = f(data[i], data[i - stride]);
+    }
+}
+
+/// Replaces each element with `f(element, neighbor)`, from the start so that the neighbors are already restored.
+#[inline]
+fn _decode_with<T: Copy>(data: &mut [T], stride: usize, f: impl Fn(T, T) -> T) {
+    for i in stride..data.len() {
+        data[i] = f(data[i], data[i - stride]);
+    }
+}
+
+fn _read_elements<T: Element>(data: &[u8], stride: usize) -> Option<Vec<T>> {
+    (stride > 0 && data.len().is_multiple_of(T::SIZE))
+        .then(|| data.chunks_exact(T::SIZE).map(T::read_le).collect())
+}
+
+fn _write_elements<T: Element>(elements: &[T], data: &mut [u8]) {
+    for (&element, bytes) in elements.iter().zip(data.chunks_exact_mut(T::SIZE)) {
+        element.write_le(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _roundtrip<T: Element + PartialEq + core::fmt::Debug>(input: &[T]) {
+        for filter in [Filter::Delta, Filter::DeltaOfDelta, Filter::Xor] {
+            for stride in [1, 2, 3, 7, input.len().max(1), input.len() + 1] {
+                let mut data = input.to_vec();
+                encode(&mut data, filter, stride);
+                decode(&mut data, filter, stride);
+                assert_eq!(data, input, "{filter:?} {stride}");
+            }
+        }
+    }
+
+    #[test]
+    fn filters_roundtrip() {
+        _roundtrip::<u8>(&[]);
+        _roundtrip(&random_alphabet(0, 255, 1000));
+        _roundtrip(
+            &(0..1000u16)
+                .map(|v| v.wrapping_mul(40503))
+                .collect::<Vec<_>>(),
+        );
+        _roundtrip(
+            &(0..1000u32)
+                .map(|v| v.wrapping_mul(0x9e37_79b1))
+                .collect::<Vec<_>>(),
+        );
+        _roundtrip(&[u64::MAX, 0, 1, u64::MAX - 1, 0x8000_0000_0000_0000]);
+
+        let mut data = [10u8, 20, 11, 22, 12, 24];
+        encode(&mut data, Filter::Delta, 2);
+        assert_eq!(data, [10, 20, 1, 2, 1, 2]);
+
+        let mut data = [1000u64, 1010, 1020, 1030, 1041];
+        encode(&mut data, Filter::DeltaOfDelta, 1);
+        assert_eq!(data, [1000, 1010u64.wrapping_sub(2000), 0, 0, 1]);
+
+        let mut data = [0x1234u16, 0x1235, 0x1237];
+        encode(&mut data, Filter::Xor, 1);
+        assert_eq!(data, [0x1234, 0x0001, 0x0002]);
+    }
+
+    #[test]
+    fn filters_bytes() {
+        let samples = (0..0x4000u32)
+            .map(|v| 0x0100_0000 + v * 3 + (v * 7 % 5))
+            .collect::<Vec<_>>();
+        let input = samples
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut filtered = input.clone();
+        encode_bytes::<u32>(&mut filtered, Filter::Delta, 1).unwrap();
+        assert_eq!(filtered[4..8], (samples[1] - samples[0]).to_le_bytes());
+        let plain = deflate::deflate(&input, deflate::CompressionLevel::Default, None).unwrap();
+        let compressed =
+            deflate::deflate(&filtered, deflate::CompressionLevel::Default, None).unwrap();
+        assert!(compressed.len() * 4 < plain.len());
+
+        decode_bytes::<u32>(&mut filtered, Filter::Delta, 1).unwrap();
+        assert_eq!(filtered, input);
+
+        assert_eq!(
+            encode_bytes::<u32>(&mut [0; 6], Filter::Delta, 1),
+            Err(EncodeError::InvalidInput)
+        );
+        assert_eq!(
+            decode_bytes::<u16>(&mut [0; 6], Filter::Xor, 0),
+            Err(DecodeError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn filters_shuffle() {
+        let input = random_alphabet(0, 255, 1001);
+        for element_size in [1, 2, 3, 4, 8, 1000, 1001, 1002] {
+            let shuffled = byte_shuffle(&input, element_size);
+            assert_eq!(byte_unshuffle(&shuffled, element_size), input);
+        }
+        assert_eq!(byte_shuffle(&[], 4), []);
+        assert_eq!(byte_shuffle(&[1, 2, 3], 4), [1, 2, 3]);
+        assert_eq!(
+            byte_shuffle(&[1, 2, 3, 4, 5, 6, 7], 3),
+            [1, 4, 2, 5, 3, 6, 7]
+        );
+
+        // the upper bytes of small integers become runs of zeros
+        let input = (0..0x4000u32)
+            .map(|v| v.wrapping_mul(0x9e37_79b1) % 1000)
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let plain = deflate::deflate(&input, deflate::CompressionLevel::Default, None).unwrap();
+        let shuffled = deflate::deflate(
+            &byte_shuffle(&input, 4),
+            deflate::CompressionLevel::Default,
+            None,
+        )
+        .unwrap();
+        assert!(shuffled.len() < plain.len());
+    }
+}
@@ -0,0 +1,100 @@
+//! Table-driven CRC-32
+//!
+//! The bits are reflected for the CRC-32 of zip and gzip and the CRC-32C of Snappy,
+//! and are not reflected for the CRC-32 of bzip2.
+//! Each polynomial gets its own table, built at compile time.
+//!
+//! References:
+//!
+//! * <https://reveng.sourceforge.io/crc-catalogue/17plus.htm#crc.cat-bits.32>
+//!
+
+/// Reflected CRC-32, with the bit-reversed `POLYNOMIAL`
+pub struct Reflected<const POLYNOMIAL: u32>;
+
+impl<const POLYNOMIAL: u32> Reflected<POLYNOMIAL> {
+    const TABLE: &'static [u32; 256] = &_reflected_table(POLYNOMIAL);
+
+    /// Updates the CRC with the data.
+    ///
+    /// Starts with [`Self::checksum`] of an empty slice, which is `0`.
+    pub fn update(crc: u32, data: &[u8]) -> u32 {
+        let table = Self::TABLE;
+        let mut crc = !crc;
+        for &byte in data {
+            crc = (crc >> 8) ^ table[((crc ^ byte as u32) & 0xff) as usize];
+        }
+        !crc
+    }
+
+    /// CRC-32 implementation
+    #[inline]
+    pub fn checksum(data: &[u8]) -> u32 {
+        Self::update(0, data)
+    }
+}
+
+/// CRC-32 with the most significant bit first, with the `POLYNOMIAL` as is
+pub struct MsbFirst<const POLYNOMIAL: u32>;
+
+impl<const POLYNOMIAL: u32> MsbFirst<POLYNOMIAL> {
+    const TABLE: &'static [u32; 256] = &_msb_first_table(POLYNOMIAL);
+
+    /// Updates the CRC with the data.
+    ///
+    /// Starts with [`Self::checksum`] of an empty slice, which is `0`.
+    pub fn update(crc: u32, data: &[u8]) -> u32 {
+        let table = Self::TABLE;
+        let mut crc = !crc;
+        for &byte in data {
+            crc = (crc << 8) ^ table[((crc >> 24) ^ byte as u32) as usize];
+        }
+        !crc
+    }
+
+    /// CRC-32 implementation
+    #[inline]
+    pub fn checksum(data: &[u8]) -> u32 {
+        Self::update(0, data)
+    }
+}
+
+const fn _reflected_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+const fn _msb_first_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = (index as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ polynomial
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
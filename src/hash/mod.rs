@@ -0,0 +1,74 @@
+//! Non-cryptographic hash functions
+//!
+//! Both xxHash functions can be computed incrementally, and are used by the checksums of the LZ4 frame format.
+//! The CRC-32 tables are shared by the checksums of zip, gzip, Snappy and bzip2.
+
+pub mod crc32;
+pub mod xxhash32;
+pub mod xxhash64;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    const SPAM: &[u8] = b"Nobody inspects the spammish repetition";
+
+    #[test]
+    fn crc32() {
+        use crc32::{MsbFirst, Reflected};
+        // CRC-32/ISO-HDLC, CRC-32C and CRC-32/BZIP2
+        assert_eq!(
+            Reflected::<0xEDB8_8320>::checksum(b"123456789"),
+            0xCBF4_3926
+        );
+        assert_eq!(
+            Reflected::<0x82F6_3B78>::checksum(b"123456789"),
+            0xE306_9283
+        );
+        assert_eq!(MsbFirst::<0x04C1_1DB7>::checksum(b"123456789"), 0xFC89_1918);
+        assert_eq!(MsbFirst::<0x04C1_1DB7>::checksum(b""), 0);
+        assert_eq!(
+            Reflected::<0xEDB8_8320>::update(Reflected::<0xEDB8_8320>::checksum(SPAM), b"!"),
+            Reflected::<0xEDB8_8320>::checksum(b"Nobody inspects the spammish repetition!")
+        );
+    }
+
+    #[test]
+    fn xxhash64() {
+        assert_eq!(xxhash64::checksum(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxhash64::checksum(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxhash64::checksum(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(xxhash64::checksum(SPAM, 0), 0xFBCE_A83C_8A37_8BF1);
+        assert_eq!(xxhash64::checksum(b"", 0x9E37_79B1), 0xAC75_FDA2_929B_17EF);
+        assert_eq!(xxhash32::checksum(b"", 0x9E37_79B1), 0x36B7_8AE7);
+    }
+
+    #[test]
+    fn xxhash_incremental() {
+        let input = random_alphabet(0, 255, 1000);
+        for seed in [0, 1, 0x9E37_79B1] {
+            for split in [1, 3, 15, 16, 17, 31, 32, 33, 100] {
+                let mut hasher32 = xxhash32::XxHash32::new(seed as u32);
+                let mut hasher64 = xxhash64::XxHash64::new(seed);
+                for chunk in input.chunks(split) {
+                    hasher32.update(chunk);
+                    hasher64.update(chunk);
+                }
+                assert_eq!(hasher32.finalize(), xxhash32::checksum(&input, seed as u32));
+                assert_eq!(hasher64.finalize(), xxhash64::checksum(&input, seed));
+            }
+
+            // every prefix, which covers each length of the buffered tail
+            let mut hasher32 = xxhash32::XxHash32::new(seed as u32);
+            let mut hasher64 = xxhash64::XxHash64::new(seed);
+            for (index, &byte) in input[..80].iter().enumerate() {
+                hasher32.update(&[byte]);
+                hasher64.update(&[byte]);
+                let prefix = &input[..=index];
+                assert_eq!(hasher32.finalize(), xxhash32::checksum(prefix, seed as u32));
+                assert_eq!(hasher64.finalize(), xxhash64::checksum(prefix, seed));
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! xxHash32 hash function
+//!
+//! References:
+//!
+//! * <https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md>
+//!
+
+const PRIME32_1: u32 = 0x9E37_79B1;
+const PRIME32_2: u32 = 0x85EB_CA77;
+const PRIME32_3: u32 = 0xC2B2_AE3D;
+const PRIME32_4: u32 = 0x27D4_EB2F;
+const PRIME32_5: u32 = 0x1656_67B1;
+
+/// Size in bytes of a stripe processed by the four lanes
+const STRIPE_SIZE: usize = 16;
+
+/// xxHash32 implementation
+#[inline]
+pub fn checksum(data: &[u8], seed: u32) -> u32 {
+    let mut hasher = XxHash32::new(seed);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Incremental xxHash32
+///
+/// ```
+/// use compress::hash::xxhash32::{self, XxHash32};
+///
+/// let mut hasher = XxHash32::new(0);
+/// hasher.update(b"Nobody inspects ");
+/// hasher.update(b"the spammish repetition");
+/// assert_eq!(hasher.finalize(), xxhash32::checksum(b"Nobody inspects the spammish repetition", 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct XxHash32 {
+    seed: u32,
+    lanes: [u32; 4],
+    buffer: [u8; STRIPE_SIZE],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl XxHash32 {
+    #[inline]
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME32_1).wrapping_add(PRIME32_2),
+                seed.wrapping_add(PRIME32_2),
+                seed,
+                seed.wrapping_sub(PRIME32_1),
+            ],
+            buffer: [0; STRIPE_SIZE],
+            buffered: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Adds the data to the hash.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        if self.buffered > 0 {
+            let len = data.len().min(STRIPE_SIZE - self.buffered);
+            self.buffer[self.buffered..][..len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < STRIPE_SIZE {
+                return;
+            }
+            let buffer = self.buffer;
+            self._stripe(&buffer);
+            self.buffered = 0;
+        }
+
+        let mut stripes = data.chunks_exact(STRIPE_SIZE);
+        for stripe in stripes.by_ref() {
+            self._stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Returns the hash of the data added so far.
+    pub fn finalize(&self) -> u32 {
+        let mut acc = if self.total_len >= STRIPE_SIZE as u64 {
+            self.lanes[0]
+                .rotate_left(1)
+                .wrapping_add(self.lanes[1].rotate_left(7))
+                .wrapping_add(self.lanes[2].rotate_left(12))
+                .wrapping_add(self.lanes[3].rotate_left(18))
+        } else {
+            self.seed.wrapping_add(PRIME32_5)
+        };
+        acc = acc.wrapping_add(self.total_len as u32);
+
+        let mut words = self.buffer[..self.buffered].chunks_exact(4);
+        for word in words.by_ref() {
+            acc = acc
+                .wrapping_add(_read_u32(word).wrapping_mul(PRIME32_3))
+                .rotate_left(17)
+                .wrapping_mul(PRIME32_4);
+        }
+        for &byte in words.remainder() {
+            acc = acc
+                .wrapping_add((byte as u32).wrapping_mul(PRIME32_5))
+                .rotate_left(11)
+                .wrapping_mul(PRIME32_1);
+        }
+
+        acc ^= acc >> 15;
+        acc = acc.wrapping_mul(PRIME32_2);
+        acc ^= acc >> 13;
+        acc = acc.wrapping_mul(PRIME32_3);
+        acc ^= acc >> 16;
+        acc
+    }
+
+    #[inline]
+    fn _stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(4)) {
+            *lane = _round(*lane, _read_u32(word));
+        }
+    }
+}
+
+#[inline]
+fn _round(acc: u32, input: u32) -> u32 {
+    acc.wrapping_add(input.wrapping_mul(PRIME32_2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME32_1)
+}
+
+#[inline]
+fn _read_u32(word: &[u8]) -> u32 {
+    u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+}
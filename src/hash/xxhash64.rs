@@ -0,0 +1,157 @@
+//! xxHash64 hash function
+//!
+//! References:
+//!
+//! * <https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md>
+//!
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/// Size in bytes of a stripe processed by the four lanes
+const STRIPE_SIZE: usize = 32;
+
+/// xxHash64 implementation
+#[inline]
+pub fn checksum(data: &[u8], seed: u64) -> u64 {
+    let mut hasher = XxHash64::new(seed);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Incremental xxHash64
+///
+/// ```
+/// use compress::hash::xxhash64::{self, XxHash64};
+///
+/// let mut hasher = XxHash64::new(0);
+/// hasher.update(b"Nobody inspects ");
+/// hasher.update(b"the spammish repetition");
+/// assert_eq!(hasher.finalize(), xxhash64::checksum(b"Nobody inspects the spammish repetition", 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct XxHash64 {
+    seed: u64,
+    lanes: [u64; 4],
+    buffer: [u8; STRIPE_SIZE],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl XxHash64 {
+    #[inline]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            buffer: [0; STRIPE_SIZE],
+            buffered: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Adds the data to the hash.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        if self.buffered > 0 {
+            let len = data.len().min(STRIPE_SIZE - self.buffered);
+            self.buffer[self.buffered..][..len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < STRIPE_SIZE {
+                return;
+            }
+            let buffer = self.buffer;
+            self._stripe(&buffer);
+            self.buffered = 0;
+        }
+
+        let mut stripes = data.chunks_exact(STRIPE_SIZE);
+        for stripe in stripes.by_ref() {
+            self._stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Returns the hash of the data added so far.
+    pub fn finalize(&self) -> u64 {
+        let mut acc = if self.total_len >= STRIPE_SIZE as u64 {
+            let acc = self.lanes[0]
+                .rotate_left(1)
+                .wrapping_add(self.lanes[1].rotate_left(7))
+                .wrapping_add(self.lanes[2].rotate_left(12))
+                .wrapping_add(self.lanes[3].rotate_left(18));
+            self.lanes.iter().fold(acc, |acc, &lane| _merge(acc, lane))
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+        acc = acc.wrapping_add(self.total_len);
+
+        let mut words = self.buffer[..self.buffered].chunks_exact(8);
+        for word in words.by_ref() {
+            acc ^= _round(0, _read_u64(word));
+            acc = acc
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+        }
+        let mut rest = words.remainder();
+        if rest.len() >= 4 {
+            acc ^= (u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as u64)
+                .wrapping_mul(PRIME64_1);
+            acc = acc
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            acc ^= (byte as u64).wrapping_mul(PRIME64_5);
+            acc = acc.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        acc ^= acc >> 33;
+        acc = acc.wrapping_mul(PRIME64_2);
+        acc ^= acc >> 29;
+        acc = acc.wrapping_mul(PRIME64_3);
+        acc ^= acc >> 32;
+        acc
+    }
+
+    #[inline]
+    fn _stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = _round(*lane, _read_u64(word));
+        }
+    }
+}
+
+#[inline]
+fn _round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+#[inline]
+fn _merge(acc: u64, lane: u64) -> u64 {
+    (acc ^ _round(0, lane))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+#[inline]
+fn _read_u64(word: &[u8]) -> u64 {
+    u64::from_le_bytes(word[..8].try_into().unwrap())
+}
@@ -4,12 +4,17 @@
 #![cfg_attr(not(test), no_std)]
 
 extern crate alloc;
+#[cfg(all(feature = "std", not(test)))]
+extern crate std;
 
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
+use core::ops::ControlFlow;
 
 pub mod entropy;
+pub mod filters;
+pub mod hash;
 #[path = "lz/lz.rs"]
 pub mod lz;
 pub mod num;
@@ -20,6 +25,32 @@ pub mod stk1;
 
 pub mod deflate;
 
+pub mod lz4;
+
+pub mod bwt;
+
+pub mod bzip2;
+
+pub mod snappy;
+
+pub mod brotli;
+
+pub mod webp_lossless;
+
+pub mod png;
+
+pub mod ppm;
+
+pub mod rle;
+
+pub mod zip;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(any(test, feature = "bench"))]
+pub mod bench;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DecodeError {
     InvalidInput,
@@ -27,6 +58,10 @@ pub enum DecodeError {
     OutOfMemory,
     UnsupportedFormat,
     UnexpectedEof,
+    /// The decoded data exceeds the specified limit
+    LimitExceeded,
+    /// The checksum of the decoded data does not match the one recorded in the input
+    ChecksumMismatch,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,6 +71,137 @@ pub enum EncodeError {
     OutOfMemory,
     EntropyError,
     InternalInconsistency,
+    /// The progress hook requested to stop the encoder
+    Cancelled,
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::InvalidInput => "invalid input",
+            Self::InvalidData => "invalid data",
+            Self::OutOfMemory => "out of memory",
+            Self::UnsupportedFormat => "unsupported format",
+            Self::UnexpectedEof => "unexpected end of input",
+            Self::LimitExceeded => "decoded data exceeds the limit",
+            Self::ChecksumMismatch => "checksum mismatch",
+        })
+    }
+}
+
+impl core::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::InvalidInput => "invalid input",
+            Self::InvalidData => "invalid data",
+            Self::OutOfMemory => "out of memory",
+            Self::EntropyError => "entropy coding failed",
+            Self::InternalInconsistency => "internal inconsistency",
+            Self::Cancelled => "cancelled",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodeError {}
+
+#[cfg(feature = "std")]
+impl From<DecodeError> for std::io::Error {
+    fn from(value: DecodeError) -> Self {
+        use std::io::ErrorKind;
+        let kind = match value {
+            DecodeError::InvalidInput => ErrorKind::InvalidInput,
+            DecodeError::InvalidData
+            | DecodeError::LimitExceeded
+            | DecodeError::ChecksumMismatch => ErrorKind::InvalidData,
+            DecodeError::OutOfMemory => ErrorKind::OutOfMemory,
+            DecodeError::UnsupportedFormat => ErrorKind::Unsupported,
+            DecodeError::UnexpectedEof => ErrorKind::UnexpectedEof,
+        };
+        std::io::Error::new(kind, value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<EncodeError> for std::io::Error {
+    fn from(value: EncodeError) -> Self {
+        use std::io::ErrorKind;
+        let kind = match value {
+            EncodeError::InvalidInput => ErrorKind::InvalidInput,
+            EncodeError::OutOfMemory => ErrorKind::OutOfMemory,
+            EncodeError::Cancelled => ErrorKind::Interrupted,
+            EncodeError::InvalidData
+            | EncodeError::EntropyError
+            | EncodeError::InternalInconsistency => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, value)
+    }
+}
+
+/// Interval in input bytes between the reports of the progress hook
+pub const PROGRESS_INTERVAL: usize = 64 * 1024;
+
+/// Progress of an encoder reported to the progress hook
+///
+/// The hook is called about every [`PROGRESS_INTERVAL`] bytes of the input,
+/// and returning `ControlFlow::Break` stops the encoder with `EncodeError::Cancelled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of input bytes processed so far
+    pub consumed: usize,
+    /// Number of output bytes written so far
+    pub emitted: usize,
+    /// Size of the input
+    pub total: usize,
+}
+
+/// Calls the optional progress hook at intervals.
+pub(crate) struct ProgressReporter<'a> {
+    hook: Option<&'a mut dyn FnMut(Progress) -> ControlFlow<()>>,
+    total: usize,
+    next: usize,
+}
+
+impl<'a> ProgressReporter<'a> {
+    #[inline]
+    pub(crate) fn new(
+        hook: Option<&'a mut dyn FnMut(Progress) -> ControlFlow<()>>,
+        total: usize,
+    ) -> Self {
+        Self {
+            hook,
+            total,
+            next: PROGRESS_INTERVAL,
+        }
+    }
+
+    /// Reports the progress if the input has advanced by [`PROGRESS_INTERVAL`] since the last report.
+    #[inline]
+    pub(crate) fn advance(&mut self, consumed: usize, emitted: usize) -> Result<(), EncodeError> {
+        if consumed < self.next {
+            return Ok(());
+        }
+        self.report(consumed, emitted)
+    }
+
+    /// Reports the progress unconditionally.
+    pub(crate) fn report(&mut self, consumed: usize, emitted: usize) -> Result<(), EncodeError> {
+        self.next = consumed.saturating_add(PROGRESS_INTERVAL);
+        let Some(hook) = self.hook.as_mut() else {
+            return Ok(());
+        };
+        match hook(Progress {
+            consumed,
+            emitted,
+            total: self.total,
+        }) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(()) => Err(EncodeError::Cancelled),
+        }
+    }
 }
 
 /// A Fibonacci word generator for testing purposes.
@@ -78,12 +244,22 @@ pub(crate) fn random_ab(a: u8, b: u8, limit: usize) -> Vec<u8> {
 
 #[cfg(test)]
 pub(crate) fn random_alphabet(min: u8, max: u8, limit: usize) -> Vec<u8> {
-    use rand::RngCore;
+    _random_alphabet_with(&mut rand::rng(), min, max, limit)
+}
+
+/// Same as `random_alphabet`, but always the same input for the seed
+#[cfg(test)]
+pub(crate) fn random_alphabet_seeded(min: u8, max: u8, limit: usize, seed: u64) -> Vec<u8> {
+    use rand::{SeedableRng, rngs::StdRng};
+    _random_alphabet_with(&mut StdRng::seed_from_u64(seed), min, max, limit)
+}
+
+#[cfg(test)]
+fn _random_alphabet_with(rng: &mut impl rand::RngCore, min: u8, max: u8, limit: usize) -> Vec<u8> {
     assert!(min < max, "min must be less than max");
     let min = min as u32;
     let range_max = max as u32 - min;
     let mask = (range_max + 1).next_power_of_two() - 1;
-    let mut rng = rand::rng();
     let mut v = Vec::with_capacity(limit);
     while v.len() < limit {
         let rand = rng.next_u32() & mask;
@@ -93,3 +269,45 @@ pub(crate) fn random_alphabet(min: u8, max: u8, limit: usize) -> Vec<u8> {
     }
     v
 }
+
+#[cfg(test)]
+pub(crate) use test_allocator::allocated_bytes;
+
+/// Allocator of the tests, which counts the bytes allocated by the current thread
+#[cfg(test)]
+mod test_allocator {
+    use core::cell::Cell;
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    std::thread_local! {
+        static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|v| v.set(v.get() + layout.size()));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|v| v.set(v.get() + new_size));
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    /// Calls the closure, and returns its result with the bytes allocated and reallocated in it.
+    pub(crate) fn allocated_bytes<R>(f: impl FnOnce() -> R) -> (R, usize) {
+        let start = ALLOCATED.with(Cell::get);
+        let result = f();
+        (result, ALLOCATED.with(Cell::get) - start)
+    }
+}
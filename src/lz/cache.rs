@@ -1,7 +1,6 @@
 //! cache offsets of matching patterns
 
 use crate::*;
-use alloc::vec;
 use core::num::NonZero;
 
 macro_rules! def_key {
@@ -9,7 +8,7 @@ macro_rules! def_key {
         pub trait $trait_name
         where
             Self::ElementType: Copy,
-            Self::KeyType: Copy + Ord,
+            Self::KeyType: Copy + Ord + Into<u64>,
         {
             type ElementType;
             type KeyType;
@@ -75,49 +74,51 @@ macro_rules! def_cache {
         {
             source: &'a [KEY::ElementType],
             key: KEY,
-            cache: BTreeMap<KEY::KeyType, OffsetList>,
+            tables: OffsetCacheTables,
             cursor: usize,
             limit: usize,
             max_distance: usize,
-            purge_count: usize,
-            purge_limit: usize,
         }
 
         impl<'a, KEY: $key_name> $class_name<'a, KEY> {
             #[inline]
-            pub fn new(
+            pub fn new(source: &'a [KEY::ElementType], max_distance: usize) -> Self {
+                Self::with_tables(source, max_distance, OffsetCacheTables::new())
+            }
+
+            /// Creates a cache that reuses the tables of a previous cache, returned by [`Self::into_tables`].
+            pub fn with_tables(
                 source: &'a [KEY::ElementType],
                 max_distance: usize,
-                purge_limit: usize,
+                mut tables: OffsetCacheTables,
             ) -> Self {
+                tables.reset(source.len(), max_distance);
                 if source.len() < ($magic_number + 1) {
                     Self {
                         source,
                         key: KEY::null(),
-                        cache: BTreeMap::new(),
+                        tables,
                         cursor: 0,
                         limit: 0,
                         max_distance,
-                        purge_count: 0,
-                        purge_limit: 0,
                     }
                 } else {
                     Self {
                         source,
                         key: KEY::new(source[..$magic_number].try_into().unwrap()),
-                        cache: BTreeMap::new(),
+                        tables,
                         cursor: 0,
                         limit: source.len() - ($magic_number - 1),
                         max_distance,
-                        purge_count: 0,
-                        purge_limit: if purge_limit > 0 {
-                            purge_limit
-                        } else {
-                            max_distance * 2
-                        },
                     }
                 }
             }
+
+            /// Returns the tables to be reused by the next cache.
+            #[inline]
+            pub fn into_tables(self) -> OffsetCacheTables {
+                self.tables
+            }
         }
 
         impl<KEY: $key_name> OffsetCache for $class_name<'_, KEY> {
@@ -128,11 +129,7 @@ macro_rules! def_cache {
                     return;
                 }
                 for _ in 0..step {
-                    let value = cursor as u32;
-                    self.cache
-                        .entry(self.key.key_value())
-                        .and_modify(|list| list.push(value))
-                        .or_insert_with(|| OffsetList::new(value));
+                    self.tables.insert(cursor, self.key.key_value().into());
 
                     cursor += 1;
                     if cursor >= limit {
@@ -141,13 +138,22 @@ macro_rules! def_cache {
                     self.key.advance(self.source[cursor + ($magic_number - 1)]);
                 }
 
-                self.purge_count += step;
-                if self.purge_count >= self.purge_limit {
-                    let min_value = self.cursor.saturating_sub(self.max_distance) as u32;
-                    self.cache.retain(|_k, v| v.retain(min_value));
-                    self.purge_count = cursor % self.max_distance;
-                }
+                self.cursor = cursor;
+            }
 
+            fn skip(&mut self, step: usize) {
+                if step <= 1 || self.cursor >= self.limit {
+                    return self.advance(step);
+                }
+                self.advance(1);
+                let cursor = (self.cursor + step - 1).min(self.limit);
+                if cursor < self.limit {
+                    self.key = KEY::new(
+                        self.source[cursor..cursor + $magic_number]
+                            .try_into()
+                            .unwrap(),
+                    );
+                }
                 self.cursor = cursor;
             }
 
@@ -156,20 +162,12 @@ macro_rules! def_cache {
                     return None;
                 }
                 let min_value = self.cursor.saturating_sub(self.max_distance);
-                self.cache
-                    .get(&self.key.key_value())
-                    .map(|v| v.distances(self.cursor, min_value))
+                self.tables
+                    .distances(self.key.key_value().into(), self.cursor, min_value)
             }
 
             fn nearest(&self) -> Option<usize> {
-                if self.cursor >= self.limit {
-                    return None;
-                }
-                let min_value = self.cursor.saturating_sub(self.max_distance);
-                self.cache.get(&self.key.key_value()).and_then(|v| {
-                    let nearest = v.nearest().unwrap() as usize;
-                    (nearest >= min_value).then(|| self.cursor - nearest)
-                })
+                self.matches()?.next().map(|v| v.get())
             }
 
             fn guaranteed_min_len(&self) -> usize {
@@ -198,6 +196,12 @@ def_cache!(8, Matching8Cache, MatchingKey8, Matching8BKey, OffsetCache8);
 pub trait OffsetCache {
     fn advance(&mut self, step: usize);
 
+    /// Moves forward by `step`, registering only the current position.
+    #[inline]
+    fn skip(&mut self, step: usize) {
+        self.advance(step);
+    }
+
     fn matches<'a>(&'a self) -> Option<impl Iterator<Item = NonZero<usize>> + 'a>;
 
     fn nearest(&self) -> Option<usize>;
@@ -259,73 +263,129 @@ impl<T: Copy> LruVec3<T> {
     }
 }
 
-pub struct OffsetList {
-    inner: Vec<u32>,
+/// Position that terminates the chains
+const NIL: u32 = u32::MAX;
+
+/// Tables of the offset cache, which the next cache can reuse to avoid allocating them again
+///
+/// The positions of the same hash of the keys are chained from the newest one,
+/// and the positions whose keys differ are skipped while following the chain.
+/// The links and the keys are kept in a ring buffer, which covers the maximum distance.
+#[derive(Default)]
+pub struct OffsetCacheTables {
+    /// Newest position of each hash of the keys
+    pub(crate) head: Vec<u32>,
+    /// Previous position with the same hash, in the ring buffer
+    pub(crate) prev: Vec<u32>,
+    /// Key of each position, in the ring buffer
+    pub(crate) keys: Vec<u64>,
+    /// Number of bits of the hash
+    hash_bits: u32,
 }
 
-impl OffsetList {
+impl OffsetCacheTables {
+    /// Number of bits of the largest hash table
+    const MAX_HASH_BITS: u32 = 16;
+
+    /// Number of bits of the smallest hash table
+    const MIN_HASH_BITS: u32 = 8;
+
     #[inline]
-    pub fn new(value: u32) -> Self {
-        Self { inner: vec![value] }
+    pub const fn new() -> Self {
+        Self {
+            head: Vec::new(),
+            prev: Vec::new(),
+            keys: Vec::new(),
+            hash_bits: 0,
+        }
     }
 
-    #[inline]
-    pub fn push(&mut self, value: u32) {
-        self.inner.push(value);
+    /// Empties the tables, keeping their allocations.
+    fn reset(&mut self, len: usize, max_distance: usize) {
+        let ring_size = len.min(max_distance.saturating_add(1)).next_power_of_two();
+        self.hash_bits = ring_size
+            .trailing_zeros()
+            .clamp(Self::MIN_HASH_BITS, Self::MAX_HASH_BITS);
+        self.head.clear();
+        self.head.resize(1 << self.hash_bits, NIL);
+        // The positions are written before they are read, so the ring buffer needs no clearing.
+        self.prev.resize(ring_size, NIL);
+        self.keys.resize(ring_size, 0);
     }
 
     #[inline]
-    pub fn nearest(&self) -> Option<u32> {
-        self.inner.last().copied()
+    fn hash(&self, key: u64) -> usize {
+        (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - self.hash_bits)) as usize
     }
 
-    pub fn retain(&mut self, min_value: u32) -> bool {
-        match self.nearest() {
-            None => return false,
-            Some(v) if v < min_value => return false,
-            _ => {}
-        }
-        for (index, item) in self.inner.iter().enumerate().rev() {
-            if *item < min_value {
-                self.inner.drain(..=index);
-                self.inner.shrink_to_fit();
-                break;
-            }
-        }
-        true
+    #[inline]
+    fn insert(&mut self, position: usize, key: u64) {
+        let hash = self.hash(key);
+        let slot = position & (self.prev.len() - 1);
+        self.prev[slot] = self.head[hash];
+        self.keys[slot] = key;
+        self.head[hash] = position as u32;
     }
 
-    #[inline]
-    pub fn distances<'a>(
-        &'a self,
+    /// Returns the distances from `current` to the positions of the key not before `min_value`, from the nearest,
+    /// or `None` if there are none.
+    ///
+    /// Since the ring buffer covers the maximum distance,
+    /// the positions not before `min_value` have not been overwritten.
+    fn distances(
+        &self,
+        key: u64,
         current: usize,
         min_value: usize,
-    ) -> impl Iterator<Item = NonZero<usize>> + 'a {
-        Distances {
-            iter: self.inner.iter(),
+    ) -> Option<impl Iterator<Item = NonZero<usize>> + '_> {
+        let mut distances = Distances {
+            tables: self,
+            key,
+            next: self.head[self.hash(key)],
             current,
             min_value,
-        }
+        };
+        distances._find()?;
+        Some(distances)
     }
 }
 
 struct Distances<'a> {
-    iter: core::slice::Iter<'a, u32>,
+    tables: &'a OffsetCacheTables,
+    key: u64,
+    /// Next position in the chain, which may have another key
+    next: u32,
     current: usize,
     min_value: usize,
 }
 
+impl Distances<'_> {
+    /// Skips the positions of other keys, and returns the next position of the key.
+    #[inline]
+    fn _find(&mut self) -> Option<usize> {
+        let mask = self.tables.prev.len() - 1;
+        loop {
+            let position = self.next as usize;
+            if self.next == NIL || position < self.min_value {
+                self.next = NIL;
+                return None;
+            }
+            let slot = position & mask;
+            if self.tables.keys[slot] == self.key {
+                return Some(position);
+            }
+            self.next = self.tables.prev[slot];
+        }
+    }
+}
+
 impl Iterator for Distances<'_> {
     type Item = NonZero<usize>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(&value) = self.iter.next_back() {
-            let value = value as usize;
-            if value >= self.min_value {
-                return NonZero::new(self.current - value);
-            }
-        }
-        None
+        let position = self._find()?;
+        self.next = self.tables.prev[position & (self.tables.prev.len() - 1)];
+        NonZero::new(self.current - position)
     }
 }
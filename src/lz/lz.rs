@@ -8,65 +8,182 @@ pub mod lzss;
 #[path = "match_finder/match_finder.rs"]
 pub mod match_finder;
 
+#[path = "suffix/suffix.rs"]
+pub mod suffix;
+
+mod sink;
+pub use sink::*;
+
 mod slice_window;
 pub use slice_window::*;
 
+mod token_stream;
+pub use token_stream::*;
+
+mod window_size;
+pub use window_size::*;
+
 use core::num::NonZero;
 
+/// Returns the length of the match at `current` with the data `distance` elements before.
+///
+/// The elements are compared from `current` up to the end of `data`,
+/// so a match may overlap the current position as in LZ77.
+///
+/// # Panics
+///
+/// Panics if `current` is greater than the length of `data` or `distance` is greater than `current`.
+///
+/// ```
+/// use compress::lz;
+/// use core::num::NonZero;
+///
+/// let data = b"abcabcabd";
+/// assert_eq!(lz::matching_len(data, 3, NonZero::new(3).unwrap()), 5);
+/// assert_eq!(lz::matching_len(data, 3, NonZero::new(1).unwrap()), 0);
+/// ```
 #[inline]
 #[track_caller]
 pub fn matching_len<T>(data: &[T], current: usize, distance: NonZero<usize>) -> usize
 where
-    T: Sized + Copy + PartialEq,
+    T: MatchingElement,
 {
-    debug_assert!(
-        data.len() >= current && current >= distance.get(),
-        "INVALID MATCHES: LEN {} CURRENT {} DISTANCE {}",
-        data.len(),
-        current,
-        distance.get(),
-    );
-    unsafe {
-        // Safety: `data` is guaranteed to be valid, and `current` and `distance` are checked.
-        let distance = distance.get();
-        let max_len = data.len() - current;
-        let p = data.as_ptr().add(current);
-        let q = data.as_ptr().add(current - distance);
-
-        for len in 0..max_len {
-            if p.add(len).read_volatile() != q.add(len).read_volatile() {
-                return len;
+    let p = &data[current..];
+    let q = &data[current - distance.get()..][..p.len()];
+    T::common_prefix_len(p, q)
+}
+
+/// Element of the data compared by [`matching_len`]
+///
+/// The primitive integers narrower than a word are compared a word at a time.
+/// Other types can use the default element by element comparison with an empty `impl`.
+///
+/// ```
+/// use compress::lz::{self, MatchingElement};
+/// use core::num::NonZero;
+///
+/// #[derive(Clone, Copy, PartialEq)]
+/// struct Pixel(u8, u8, u8);
+///
+/// impl MatchingElement for Pixel {}
+///
+/// let data = [Pixel(1, 2, 3), Pixel(4, 5, 6), Pixel(1, 2, 3), Pixel(4, 5, 6)];
+/// assert_eq!(lz::matching_len(&data, 2, NonZero::new(2).unwrap()), 2);
+/// ```
+pub trait MatchingElement: Copy + PartialEq {
+    /// Returns the number of the leading elements that `p` and `q` have in common.
+    ///
+    /// `p` and `q` have the same length.
+    #[inline]
+    fn common_prefix_len(p: &[Self], q: &[Self]) -> usize {
+        p.iter().zip(q.iter()).take_while(|(a, b)| a == b).count()
+    }
+}
+
+impl MatchingElement for u8 {
+    #[inline]
+    fn common_prefix_len(p: &[Self], q: &[Self]) -> usize {
+        const WORD_SIZE: usize = size_of::<usize>();
+        let mut len = 0;
+        for (a, b) in p.chunks_exact(WORD_SIZE).zip(q.chunks_exact(WORD_SIZE)) {
+            let a = usize::from_le_bytes(a.try_into().unwrap());
+            let b = usize::from_le_bytes(b.try_into().unwrap());
+            let diff = a ^ b;
+            if diff != 0 {
+                return len + diff.trailing_zeros() as usize / 8;
             }
+            len += WORD_SIZE;
         }
-        max_len
+        len + p[len..]
+            .iter()
+            .zip(q[len..].iter())
+            .take_while(|(a, b)| a == b)
+            .count()
     }
 }
 
+macro_rules! matching_element_words {
+    ($($type:ty)*) => {
+        $(
+            impl MatchingElement for $type {
+                #[inline]
+                fn common_prefix_len(p: &[Self], q: &[Self]) -> usize {
+                    const LANES: usize = size_of::<u64>() / size_of::<$type>();
+                    // The elements are packed into a word from the least significant bits.
+                    fn pack(v: &[$type]) -> u64 {
+                        v.iter()
+                            .rev()
+                            .fold(0, |acc, &v| (acc << <$type>::BITS) | v as u64)
+                    }
+                    let mut len = 0;
+                    for (a, b) in p.chunks_exact(LANES).zip(q.chunks_exact(LANES)) {
+                        let diff = pack(a) ^ pack(b);
+                        if diff != 0 {
+                            return len + (diff.trailing_zeros() / <$type>::BITS) as usize;
+                        }
+                        len += LANES;
+                    }
+                    len + p[len..]
+                        .iter()
+                        .zip(q[len..].iter())
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                }
+            }
+        )*
+    };
+}
+
+matching_element_words!(u16 u32);
+
+// A word is compared at a time already.
+impl MatchingElement for u64 {}
+impl MatchingElement for usize {}
+
+impl MatchingElement for char {}
+
+/// Returns the longest match at `cursor` among the candidate distances.
+///
+/// * `threshold_min` - matches shorter than this are discarded
+/// * `threshold_max` - the search stops at the first match of at least this length
+/// * `guaranteed_min_len` - number of elements known to match at every candidate, such as the key length of a hash table,
+///   which are not compared again
+/// * `dist_iter` - candidate distances, none of which may be greater than `cursor`
+///
+/// The earlier candidate wins a tie, so the distances are usually given from the nearest.
+///
+/// ```
+/// use compress::lz;
+/// use core::num::NonZero;
+///
+/// let data = b"abcd_abce_abcde";
+/// let distances = [5, 10].into_iter().filter_map(NonZero::new);
+/// let matches = lz::find_distance_matches(data, 10, 3, 16, 0, distances).unwrap();
+/// assert_eq!((matches.len.get(), matches.distance.get()), (4, 10));
+/// ```
 #[inline]
-pub fn find_distance_matches<T: Sized + Copy + PartialEq>(
+pub fn find_distance_matches<T>(
     input: &[T],
     cursor: usize,
     threshold_min: usize,
     threshold_max: usize,
     guaranteed_min_len: usize,
     dist_iter: impl Iterator<Item = NonZero<usize>>,
-) -> Option<Match> {
-    let threshold_min_len = threshold_min.saturating_sub(guaranteed_min_len);
-    let threshold_max_len = threshold_max.saturating_sub(guaranteed_min_len);
-    let cursor = cursor + guaranteed_min_len;
+) -> Option<Match>
+where
+    T: MatchingElement,
+{
     let mut matches = MaybeMatch::default();
     for distance in dist_iter {
-        let len = matching_len(input, cursor, distance) + guaranteed_min_len;
+        let len = matching_len(input, cursor + guaranteed_min_len, distance) + guaranteed_min_len;
         if matches.len() < len {
             matches = Match::new(NonZero::new(len).unwrap(), distance).into();
-            if matches.len() >= threshold_max_len {
+            if len >= threshold_max {
                 break;
             }
         }
     }
-    (matches.len() >= threshold_min_len as usize)
-        .then(|| matches.get())
-        .flatten()
+    matches.get().filter(|v| v.len.get() >= threshold_min)
 }
 
 /// Matching distance and length
@@ -150,95 +267,93 @@ impl From<Match> for MaybeMatch {
     }
 }
 
-pub struct LzOutputBuffer<'a> {
-    buffer: &'a mut [u8],
-    position: usize,
-}
-
-impl<'a> LzOutputBuffer<'a> {
-    #[inline]
-    pub fn new(buffer: &'a mut [u8]) -> Self {
-        Self {
-            buffer,
-            position: 0,
-        }
-    }
-
-    #[inline]
-    pub fn is_eof(&self) -> bool {
-        self.position >= self.buffer.len()
-    }
-
-    #[inline]
-    pub fn push_literal(&mut self, literal: u8) -> LzOutputBufferResult {
-        if self.position < self.buffer.len() {
-            self.buffer[self.position] = literal;
-            self.position += 1;
-            LzOutputBufferResult::Success
-        } else {
-            LzOutputBufferResult::Failure
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
 
-    pub fn extend_from_slice(&mut self, data: &[u8]) -> LzOutputBufferResult {
-        if self.position + data.len() <= self.buffer.len() {
-            self.buffer[self.position..self.position + data.len()].copy_from_slice(data);
-            self.position += data.len();
-            LzOutputBufferResult::Success
-        } else {
-            LzOutputBufferResult::Failure
-        }
-    }
+    #[test]
+    fn matching_len_words() {
+        let bytes = fib_str(b'a', b'b', 0x400);
+        let words = bytes
+            .windows(2)
+            .map(|v| u32::from_le_bytes([v[0], v[1], 0, 0]))
+            .collect::<Vec<_>>();
+        let halves = words.iter().map(|&v| v as u16).collect::<Vec<_>>();
+        let chars = bytes.iter().map(|&v| v as char).collect::<Vec<_>>();
 
-    pub fn copy_lz(&mut self, distance: usize, copy_len: usize) -> LzOutputBufferResult {
-        if distance > self.position {
-            return LzOutputBufferResult::Failure;
+        fn _scalar<T: PartialEq>(data: &[T], current: usize, distance: usize) -> usize {
+            (current..data.len())
+                .take_while(|&i| data[i] == data[i - distance])
+                .count()
         }
-        let copy_len = copy_len.min(self.buffer.len() - self.position);
-        unsafe {
-            // Safety: distance is guaranteed to be valid, and copy_len is checked against the buffer size.
-            let dest = self.buffer.as_mut_ptr().add(self.position);
-            if distance == 1 {
-                core::slice::from_raw_parts_mut(dest, copy_len).fill(dest.sub(1).read_volatile());
-            } else {
-                _memcpy(dest, dest.sub(distance), copy_len);
+        for current in (1..bytes.len()).step_by(7) {
+            for distance in [1, 2, 3, 5, 8, 13, 21, 34, 55, 100, current] {
+                if distance > current {
+                    continue;
+                }
+                let nz_distance = NonZero::new(distance).unwrap();
+                assert_eq!(
+                    matching_len(&bytes, current, nz_distance),
+                    _scalar(&bytes, current, distance)
+                );
+                assert_eq!(
+                    matching_len(&words, current, nz_distance),
+                    _scalar(&words, current, distance)
+                );
+                assert_eq!(
+                    matching_len(&halves, current, nz_distance),
+                    _scalar(&halves, current, distance)
+                );
+                assert_eq!(
+                    matching_len(&chars, current, nz_distance),
+                    _scalar(&chars, current, distance)
+                );
             }
         }
-        self.position += copy_len;
 
-        LzOutputBufferResult::Success
+        let mut data = vec![0u8; 40];
+        assert_eq!(matching_len(&data, 40, NonZero::new(1).unwrap()), 0);
+        assert_eq!(matching_len(&data, 20, NonZero::new(20).unwrap()), 20);
+        data[33] = 1;
+        assert_eq!(matching_len(&data, 20, NonZero::new(20).unwrap()), 13);
+        data[10] = 1;
+        assert_eq!(matching_len(&data, 10, NonZero::new(3).unwrap()), 0);
     }
-}
 
-/// # Safety
-///
-/// Everything is the caller's responsibility.
-#[inline]
-unsafe fn _memcpy(dest: *mut u8, src: *const u8, count: usize) {
-    unsafe {
-        let mut dest = dest;
-        let mut src = src;
-        for _ in 0..count {
-            dest.write(src.read());
-            dest = dest.add(1);
-            src = src.add(1);
-        }
-    }
-}
+    #[test]
+    fn find_distance_matches_thresholds() {
+        let data = b"abcdef_abcdefgh_abcdefghij_abcdefghij";
+        let cursor = data.len() - 10;
+        let distances = || [11, 20, 27].into_iter().filter_map(NonZero::new);
+        let find = |min, max, guaranteed| {
+            find_distance_matches(data, cursor, min, max, guaranteed, distances())
+                .map(|v| (v.len.get(), v.distance.get()))
+        };
+        assert_eq!(find(3, 100, 0), Some((10, 11)));
+        // the first candidate of `threshold_max` is taken
+        assert_eq!(find(3, 8, 0), Some((10, 11)));
+        assert_eq!(find(11, 100, 0), None);
+        // the guaranteed elements are counted but not compared
+        assert_eq!(find(3, 100, 6), Some((10, 11)));
+        assert_eq!(find(10, 100, 6), Some((10, 11)));
 
-#[must_use]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum LzOutputBufferResult {
-    Success,
-    Failure,
-}
+        // the thresholds are compared with the full length, including the guaranteed elements
+        assert_eq!(find(11, 100, 6), None);
+        let reversed = || [27, 20, 11].into_iter().filter_map(NonZero::new);
+        let matches = find_distance_matches(data, cursor, 3, 10, 6, reversed()).unwrap();
+        assert_eq!((matches.len.get(), matches.distance.get()), (10, 11));
 
-impl LzOutputBufferResult {
-    #[inline]
-    pub fn ok_or<E>(self, e: E) -> Result<(), E> {
-        match self {
-            LzOutputBufferResult::Success => Ok(()),
-            LzOutputBufferResult::Failure => Err(e),
-        }
+        let matches = find_distance_matches(data, cursor, 3, 8, 0, reversed()).unwrap();
+        assert_eq!((matches.len.get(), matches.distance.get()), (8, 20));
+        assert_eq!(
+            find_distance_matches(data, cursor, 3, 8, 0, core::iter::empty()),
+            None
+        );
+
+        let words = [1u32, 2, 3, 1, 2, 3, 1, 2];
+        let matches =
+            find_distance_matches(&words, 3, 2, 8, 0, NonZero::new(3).into_iter()).unwrap();
+        assert_eq!(matches.len.get(), 5);
     }
 }
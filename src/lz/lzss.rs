@@ -8,14 +8,57 @@ use crate::EncodeError;
 use crate::lz::{cache::*, *};
 use crate::*;
 
+/// Evaluates the closure with the offset cache of the key length of the configuration.
+///
+/// The cache reuses the tables, and returns them after the evaluation.
+macro_rules! with_offset_cache {
+    ($input:expr, $config:expr, $tables:expr, |$cache:ident| $body:expr) => {
+        match $config.key_len {
+            4 => with_offset_cache!(@new OffsetCache4, $input, $config, $tables, $cache, $body),
+            5 => with_offset_cache!(@new OffsetCache5, $input, $config, $tables, $cache, $body),
+            6 => with_offset_cache!(@new OffsetCache6, $input, $config, $tables, $cache, $body),
+            7 => with_offset_cache!(@new OffsetCache7, $input, $config, $tables, $cache, $body),
+            8 => with_offset_cache!(@new OffsetCache8, $input, $config, $tables, $cache, $body),
+            _ => with_offset_cache!(@new OffsetCache3, $input, $config, $tables, $cache, $body),
+        }
+    };
+    (@new $class:ident, $input:expr, $config:expr, $tables:expr, $cache:ident, $body:expr) => {{
+        let mut $cache =
+            $class::with_tables($input, $config.max_distance, core::mem::take($tables));
+        let result = $body;
+        *$tables = $cache.into_tables();
+        result
+    }};
+}
+
 #[derive(Debug)]
 pub struct Configuration {
     pub max_distance: usize,
     pub max_len: NonZero<usize>,
     pub skip_first_literal: usize,
+    pub dictionary_len: usize,
     pub number_of_attempts: usize,
     pub threshold_len: usize,
-    pub cache_purge_limit: usize,
+    /// Number of consecutive literals after which `encode_fast` starts skipping positions (0: disabled)
+    pub acceleration: usize,
+    /// Parsing strategy of `encode`
+    pub parser: Parser,
+    /// Length in bytes of the keys of the offset cache of `encode` and `encode_fast`
+    pub key_len: usize,
+    /// Length in bytes of the shortest match emitted, from [`LZSS::MIN_LEN`] to [`MAX_KEY_LEN`](Self::MAX_KEY_LEN)
+    pub min_len: usize,
+    /// Cost of a match of the length and distance, by which the greedy and lazy parsers of `encode` choose among the candidates
+    pub match_cost: Option<fn(usize, usize) -> u32>,
+    /// Distance covered by the offset cache of `encode`, beyond which the suffix array finds the matches (0: disabled)
+    pub long_range_distance: usize,
+    /// Number of bytes indexed at once by the suffix array of `encode_sa_lcp`, `encode_optimal` and the long-range matching
+    pub sa_window_size: usize,
+    /// Number of bytes before the current position kept in the next window of the suffix array, up to `max_distance`
+    pub sa_window_overlap: usize,
+    /// Number of neighbors in the suffix array examined in each direction
+    pub sa_search_depth: usize,
+    /// Whether the suffix array is searched past the neighbors for the longest match
+    pub sa_exact_search: bool,
 }
 
 impl Configuration {
@@ -31,8 +74,17 @@ impl Configuration {
 
     pub const LONG_THRESHOLD_LEN: usize = 64;
 
-    // 16M = 128MB
-    pub const CACHE_PURGE_LIMIT: usize = 16 * 1024 * 1024;
+    /// Default acceleration for the fastest level
+    pub const FAST_ACCELERATION: usize = 32;
+
+    /// Shortest key length of the offset cache
+    pub const MIN_KEY_LEN: usize = 3;
+
+    /// Longest key length of the offset cache
+    pub const MAX_KEY_LEN: usize = 8;
+
+    /// Default number of bytes indexed at once by the suffix array
+    pub const SA_WINDOW_SIZE: usize = 0x10_0000;
 
     #[inline]
     pub const fn new(max_distance: usize, max_len: usize) -> Self {
@@ -49,18 +101,48 @@ impl Configuration {
             })
             .unwrap(),
             skip_first_literal: 0,
+            dictionary_len: 0,
             number_of_attempts: Self::DEFAULT_ATTEMPTS,
             threshold_len: Self::THRESHOLD_LEN,
-            cache_purge_limit: Self::CACHE_PURGE_LIMIT,
+            acceleration: 0,
+            parser: Parser::Greedy,
+            key_len: Self::MIN_KEY_LEN,
+            min_len: LZSS::MIN_LEN,
+            match_cost: None,
+            long_range_distance: 0,
+            sa_window_size: Self::SA_WINDOW_SIZE,
+            sa_window_overlap: LZSS::MAX_DISTANCE,
+            sa_search_depth: MatchFinder::DEFAULT_SEARCH_DEPTH,
+            sa_exact_search: false,
         }
     }
 
+    /// Sets the maximum distance to the size of the sliding window.
+    #[inline]
+    pub const fn window_size(mut self, window_size: WindowSize) -> Self {
+        self.max_distance = if window_size.value() > LZSS::MAX_DISTANCE {
+            LZSS::MAX_DISTANCE
+        } else {
+            window_size.value()
+        };
+        self
+    }
+
     #[inline]
     pub const fn skip_first_literal(mut self, skip_first_literal: usize) -> Self {
         self.skip_first_literal = skip_first_literal;
         self
     }
 
+    /// Treats the first `dictionary_len` bytes of the input as a preset dictionary.
+    ///
+    /// The dictionary can be referenced by matches, but is not encoded itself.
+    #[inline]
+    pub const fn dictionary_len(mut self, dictionary_len: usize) -> Self {
+        self.dictionary_len = dictionary_len;
+        self
+    }
+
     #[inline]
     pub const fn number_of_attempts(mut self, number_of_attempts: usize) -> Self {
         self.number_of_attempts = number_of_attempts;
@@ -72,6 +154,137 @@ impl Configuration {
         self.threshold_len = threshold_len;
         self
     }
+
+    /// Sets the parsing strategy of `encode`.
+    #[inline]
+    pub const fn parser(mut self, parser: Parser) -> Self {
+        self.parser = parser;
+        self
+    }
+
+    /// Makes `encode_fast` skip positions in incompressible regions.
+    ///
+    /// After every `acceleration` consecutive literals, the stride between probed positions increases by one.
+    /// Skipped positions are neither searched nor registered in the cache.
+    #[inline]
+    pub const fn acceleration(mut self, acceleration: usize) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Sets the key length of the offset cache, clamped to [`MIN_KEY_LEN`](Self::MIN_KEY_LEN)..=[`MAX_KEY_LEN`](Self::MAX_KEY_LEN).
+    ///
+    /// Only the positions that share the first `key_len` bytes are probed,
+    /// so longer keys spend `number_of_attempts` on better candidates in binary data, but miss the shorter matches.
+    #[inline]
+    pub const fn key_len(mut self, key_len: usize) -> Self {
+        self.key_len = if key_len < Self::MIN_KEY_LEN {
+            Self::MIN_KEY_LEN
+        } else if key_len > Self::MAX_KEY_LEN {
+            Self::MAX_KEY_LEN
+        } else {
+            key_len
+        };
+        self
+    }
+
+    /// Sets the length of the shortest match emitted.
+    ///
+    /// Shorter matches are emitted as literals, which may compress better after entropy coding in binary data.
+    /// The encoders return `EncodeError::InvalidInput` unless `min_len` is in [`LZSS::MIN_LEN`]..=[`MAX_KEY_LEN`](Self::MAX_KEY_LEN),
+    /// and is neither shorter than `key_len` nor longer than `max_len`.
+    #[inline]
+    pub const fn min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len;
+        self
+    }
+
+    /// Makes the greedy and lazy parsers of `encode` choose the candidate with the lowest cost per byte
+    /// instead of the longest one.
+    ///
+    /// `match_cost(len, distance)` returns the estimated cost of a match, such as its size in bits,
+    /// so that a format can prefer the nearer matches whose distances are cheaper to encode.
+    /// The nearest candidate wins a tie.
+    #[inline]
+    pub const fn match_cost(mut self, match_cost: fn(usize, usize) -> u32) -> Self {
+        self.match_cost = Some(match_cost);
+        self
+    }
+
+    /// Makes `encode` combine the offset cache for the recent `distance` bytes with the suffix array for the whole window.
+    ///
+    /// The offset cache stays small and fast for the nearby matches, and where it finds no match of `threshold_len`,
+    /// the suffix array finds the longest match up to `max_distance`, which the cache alone misses in large windows.
+    /// The input is processed in segments of `sa_window_size` bytes, each of which builds its own suffix array.
+    /// This has no effect unless `distance` is less than `max_distance`.
+    #[inline]
+    pub const fn long_range_distance(mut self, distance: usize) -> Self {
+        self.long_range_distance = distance;
+        self
+    }
+
+    /// Sets the size of the windows of the suffix array, and the number of bytes shared by consecutive windows.
+    ///
+    /// Larger windows rebuild the suffix array less often but use more memory, about 12 bytes per byte of the window.
+    /// The overlap is the history that the matches at the start of a window can reach,
+    /// and is limited to `max_distance` and half the window.
+    /// The encoders that use the suffix array return `EncodeError::InvalidInput`
+    /// unless the window is longer than twice `max_len`.
+    #[inline]
+    pub const fn sa_window(mut self, window_size: usize, overlap: usize) -> Self {
+        self.sa_window_size = window_size;
+        self.sa_window_overlap = overlap;
+        self
+    }
+
+    /// Sets the number of neighbors in the suffix array examined in each direction at each position,
+    /// by `encode_sa_lcp`, `encode_optimal` and the long-range matching of `encode`.
+    #[inline]
+    pub const fn sa_search_depth(mut self, sa_search_depth: usize) -> Self {
+        self.sa_search_depth = sa_search_depth;
+        self
+    }
+
+    /// Makes `encode_sa_lcp` and the long-range matching of `encode` search the suffix array past the neighbors
+    /// when they do not settle the match, so that the longest match within `max_distance` is always found.
+    ///
+    /// See [`MatchFinder::set_exact_search`].
+    #[inline]
+    pub const fn sa_exact_search(mut self, sa_exact_search: bool) -> Self {
+        self.sa_exact_search = sa_exact_search;
+        self
+    }
+
+    /// Returns the number of bytes shared by consecutive windows of the suffix array.
+    #[inline]
+    const fn _sa_window_overlap(&self) -> usize {
+        let mut overlap = self.sa_window_overlap;
+        if overlap > self.max_distance {
+            overlap = self.max_distance;
+        }
+        if overlap > self.sa_window_size / 2 {
+            overlap = self.sa_window_size / 2;
+        }
+        overlap
+    }
+
+    #[inline]
+    const fn is_long_range(&self) -> bool {
+        self.long_range_distance > 0 && self.long_range_distance < self.max_distance
+    }
+
+    /// Returns `EncodeError::InvalidInput` if the minimum match length cannot be honored.
+    #[inline]
+    fn _validate(&self) -> Result<(), EncodeError> {
+        if (LZSS::MIN_LEN..=Self::MAX_KEY_LEN).contains(&self.min_len)
+            && self.key_len <= self.min_len
+            && self.min_len <= self.max_len.get()
+        {
+            Ok(())
+        } else {
+            Err(EncodeError::InvalidInput)
+        }
+    }
 }
 
 impl Default for Configuration {
@@ -81,6 +294,48 @@ impl Default for Configuration {
     }
 }
 
+/// Parsing strategy of [`LZSS::encode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parser {
+    /// Takes the longest match found at each position.
+    #[default]
+    Greedy,
+    /// Defers a match by one byte if the next position has a longer match.
+    ///
+    /// When a match shorter than `threshold_len` is found, the next position is also probed,
+    /// and if it has a longer match, a literal is emitted followed by that match.
+    Lazy,
+    /// Chooses the parse with the lowest cost under [`FlatCostModel`], as [`LZSS::encode_optimal`] does.
+    Optimal,
+}
+
+/// Estimated cost of LZSS elements in bits, used by [`LZSS::encode_optimal`]
+pub trait CostModel {
+    /// Returns the number of bits needed to encode a literal.
+    fn literal_cost(&self, literal: u8) -> u32;
+
+    /// Returns the number of bits needed to encode a match.
+    fn match_cost(&self, len: usize, distance: usize) -> u32;
+}
+
+/// Flat costs, similar to the static Huffman codes of deflate
+///
+/// Used by [`Parser::Optimal`] where the costs of the format are not known.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatCostModel;
+
+impl CostModel for FlatCostModel {
+    #[inline]
+    fn literal_cost(&self, _literal: u8) -> u32 {
+        9
+    }
+
+    #[inline]
+    fn match_cost(&self, len: usize, distance: usize) -> u32 {
+        8 + (len.ilog2() + distance.ilog2()) * 2
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum LZSS {
     Literal(u8),
@@ -95,220 +350,996 @@ impl LZSS {
 
     pub const MAX_DISTANCE: usize = 0x10_0000;
 
+    /// Number of positions resolved at once by `encode_optimal`
+    const OPTIMAL_SEGMENT_SIZE: usize = 0x4000;
+
+    /// Maximum number of bytes indexed at once by the offset cache of `encode` and `encode_fast`,
+    /// whose positions are stored in `u32`
+    const MAX_SEGMENT_SIZE: usize = 0x8000_0000;
+
     /// Encode in the fastest way possible
-    pub fn encode_fast<F>(input: &[u8], config: Configuration, mut f: F) -> Result<(), EncodeError>
+    pub fn encode_fast<F>(input: &[u8], config: Configuration, f: F) -> Result<(), EncodeError>
     where
         F: FnMut(LZSS) -> Result<(), EncodeError>,
     {
-        if input.is_empty() || input.len() > i32::MAX as usize {
-            return Err(EncodeError::InvalidInput);
-        }
+        Self::encode_fast_with_tables(input, config, &mut OffsetCacheTables::new(), f)
+    }
 
-        let mut offset3_cache =
-            OffsetCache3::new(input, config.max_distance, config.cache_purge_limit);
+    /// Encodes like [`Self::encode_fast`], reusing the tables of the offset cache of a previous call.
+    pub fn encode_fast_with_tables<F>(
+        input: &[u8],
+        config: Configuration,
+        tables: &mut OffsetCacheTables,
+        f: F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        Self::_encode_segmented(
+            input,
+            config,
+            Self::MAX_SEGMENT_SIZE,
+            tables,
+            f,
+            Self::_encode_fast,
+        )
+    }
 
-        let mut current = 1 + config.skip_first_literal;
-        for &literal in input.iter().take(current) {
-            f(LZSS::Literal(literal))?;
-        }
-        offset3_cache.advance(current);
+    fn _encode_fast<F>(
+        input: &[u8],
+        config: Configuration,
+        tables: &mut OffsetCacheTables,
+        f: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        with_offset_cache!(input, config, tables, |cache| Self::_encode_fast_with(
+            input, config, &mut cache, f
+        ))
+    }
 
-        let guaranteed_min_len = offset3_cache.guaranteed_min_len();
-        assert_eq!(guaranteed_min_len, 3);
+    fn _encode_fast_with<C, F>(
+        input: &[u8],
+        config: Configuration,
+        offset_cache: &mut C,
+        f: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        C: OffsetCache,
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        let mut current = Self::_encode_leading_literals(input, &config, f)?;
+        offset_cache.advance(current);
+
+        let guaranteed_min_len = offset_cache.guaranteed_min_len();
+        let min_len = config.min_len;
         let max_len = config.max_len;
+        let mut misses = 0;
 
         while let Some(&literal) = input.get(current) {
             let count = {
                 let mut matches = MaybeMatch::default();
 
-                if let Some(mut iter) = offset3_cache.matches() {
-                    if let Some(distance) = iter.next() {
-                        let len = lz::matching_len(input, current + guaranteed_min_len, distance);
-                        matches =
-                            Match::new(NonZero::new(len + guaranteed_min_len).unwrap(), distance)
-                                .into();
+                if let Some(mut iter) = offset_cache.matches()
+                    && let Some(distance) = iter.next()
+                {
+                    let len = lz::matching_len(input, current + guaranteed_min_len, distance)
+                        + guaranteed_min_len;
+                    if len >= min_len {
+                        matches = Match::new(NonZero::new(len).unwrap(), distance).into();
                     }
                 }
 
                 if let Some(matches) = matches.get() {
-                    let mut total_len = 0;
-                    let mut left = matches.len.get();
-                    loop {
-                        if left > max_len.get() {
-                            f(LZSS::Match(Match::new(max_len, matches.distance)))?;
-                            left -= max_len.get();
-                            total_len += max_len.get();
-                        } else if left >= LZSS::MIN_LEN {
-                            f(LZSS::Match(Match::new(
-                                NonZero::new(left).unwrap(),
-                                matches.distance,
-                            )))?;
-                            total_len += left;
-                            break;
-                        } else {
-                            break;
-                        }
-                    }
-                    total_len
+                    Self::_emit_match(matches, min_len, max_len, f)?
                 } else {
                     f(LZSS::Literal(literal))?;
                     1
                 }
             };
-            offset3_cache.advance(count);
-            current += count;
+            if count > 1 || config.acceleration == 0 {
+                misses = 0;
+                offset_cache.advance(count);
+                current += count;
+            } else {
+                misses += 1;
+                let stride = (1 + misses / config.acceleration).min(input.len() - current);
+                for &literal in &input[current + 1..current + stride] {
+                    f(LZSS::Literal(literal))?;
+                }
+                offset_cache.skip(stride);
+                current += stride;
+            }
         }
 
         Ok(())
     }
 
-    /// Encode LZSS using hash algorithm
-    pub fn encode<F>(input: &[u8], config: Configuration, mut f: F) -> Result<(), EncodeError>
+    /// Encode LZSS with the parser of the configuration
+    ///
+    /// The greedy and lazy parsers find the matches with the offset cache,
+    /// and the optimal parser finds them with the suffix array.
+    pub fn encode<F>(input: &[u8], config: Configuration, f: F) -> Result<(), EncodeError>
     where
         F: FnMut(LZSS) -> Result<(), EncodeError>,
     {
-        if input.is_empty() || input.len() > i32::MAX as usize {
-            return Err(EncodeError::InvalidInput);
-        }
+        Self::encode_with_tables(input, config, &mut OffsetCacheTables::new(), f)
+    }
 
-        let mut offset3_cache =
-            OffsetCache3::new(input, config.max_distance, config.cache_purge_limit);
+    /// Encodes like [`Self::encode`], reusing the tables of the offset cache of a previous call.
+    pub fn encode_with_tables<F>(
+        input: &[u8],
+        config: Configuration,
+        tables: &mut OffsetCacheTables,
+        f: F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        if config.parser == Parser::Optimal {
+            return Self::encode_optimal(input, config, &FlatCostModel, f);
+        }
+        let segment_size = if config.is_long_range() {
+            if config.sa_window_size <= config.max_len.get() * 2 {
+                return Err(EncodeError::InvalidInput);
+            }
+            config.sa_window_size.min(Self::MAX_SEGMENT_SIZE)
+        } else {
+            Self::MAX_SEGMENT_SIZE
+        };
+        Self::_encode_segmented(input, config, segment_size, tables, f, Self::_encode)
+    }
 
-        let mut current = 1 + config.skip_first_literal;
-        for &literal in input.iter().take(current) {
-            f(LZSS::Literal(literal))?;
+    fn _encode<F>(
+        input: &[u8],
+        config: Configuration,
+        tables: &mut OffsetCacheTables,
+        f: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        if config.is_long_range() {
+            let mut finder = MatchFinder::new(input);
+            finder.set_search_depth(config.sa_search_depth);
+            finder.set_exact_search(config.sa_exact_search);
+            let short_config = Configuration {
+                max_distance: config.long_range_distance,
+                ..config
+            };
+            with_offset_cache!(input, short_config, tables, |cache| Self::_encode_with(
+                input,
+                config,
+                &mut cache,
+                Some(&finder),
+                f
+            ))
+        } else {
+            with_offset_cache!(input, config, tables, |cache| Self::_encode_with(
+                input, config, &mut cache, None, f
+            ))
         }
-        offset3_cache.advance(current);
+    }
 
-        let guaranteed_min_len = offset3_cache.guaranteed_min_len();
-        assert_eq!(guaranteed_min_len, 3);
-        let max_len = config.max_len;
+    /// Encodes with the offset cache, and with the match finder for the matches it misses if any.
+    fn _encode_with<C, F>(
+        input: &[u8],
+        config: Configuration,
+        offset_cache: &mut C,
+        finder: Option<&MatchFinder>,
+        f: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        C: OffsetCache,
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        let mut current = Self::_encode_leading_literals(input, &config, f)?;
+        offset_cache.advance(current);
 
-        while let Some(&literal) = input.get(current) {
-            let count = {
-                let mut matches = MaybeMatch::default();
+        let guaranteed_min_len = offset_cache.guaranteed_min_len();
+        let min_len = config.min_len;
+        let max_len = config.max_len;
 
-                if let Some(iter) = offset3_cache.matches() {
-                    match lz::find_distance_matches(
+        let find_matches = |offset_cache: &C, current: usize| {
+            let matches = offset_cache.matches().and_then(|iter| {
+                let iter = iter.take(config.number_of_attempts);
+                match config.match_cost {
+                    Some(match_cost) => Self::_find_cheapest_match(
                         input,
                         current,
-                        Self::MIN_LEN,
+                        &config,
+                        guaranteed_min_len,
+                        iter,
+                        match_cost,
+                    ),
+                    None => lz::find_distance_matches(
+                        input,
+                        current,
+                        min_len,
                         config.threshold_len,
-                        offset3_cache.guaranteed_min_len(),
-                        iter.take(config.number_of_attempts),
-                    ) {
-                        Some(v) => {
-                            matches = v.into();
-                        }
-                        None => {}
-                    }
+                        guaranteed_min_len,
+                        iter,
+                    ),
+                }
+            });
+            match finder {
+                Some(finder) if matches.is_none_or(|v| v.len.get() < config.threshold_len) => {
+                    // the nearer match wins a tie
+                    let long = finder
+                        .matches(current, min_len, config.max_distance)
+                        .filter(|long| matches.is_none_or(|v| long.len > v.len));
+                    long.or(matches)
                 }
+                _ => matches,
+            }
+        };
 
-                if let Some(matches) = matches.get() {
-                    let mut total_len = 0;
-                    let mut left = matches.len.get();
-                    loop {
-                        if left > max_len.get() {
-                            f(LZSS::Match(Match::new(max_len, matches.distance)))?;
-                            left -= max_len.get();
-                            total_len += max_len.get();
-                        } else if left >= LZSS::MIN_LEN {
-                            f(LZSS::Match(Match::new(
-                                NonZero::new(left).unwrap(),
-                                matches.distance,
-                            )))?;
-                            total_len += left;
-                            break;
-                        } else {
-                            break;
-                        }
-                    }
-                    total_len
-                } else {
+        while let Some(&literal) = input.get(current) {
+            let Some(mut matches) = find_matches(offset_cache, current) else {
+                f(LZSS::Literal(literal))?;
+                offset_cache.advance(1);
+                current += 1;
+                continue;
+            };
+
+            // cache entries already registered from `current`
+            let mut advanced = 0;
+            if config.parser == Parser::Lazy && matches.len.get() < config.threshold_len {
+                offset_cache.advance(1);
+                advanced = 1;
+                if let Some(next) = find_matches(offset_cache, current + 1)
+                    && next.len > matches.len
+                {
                     f(LZSS::Literal(literal))?;
-                    1
+                    current += 1;
+                    advanced = 0;
+                    matches = next;
                 }
-            };
-            offset3_cache.advance(count);
+            }
+
+            let count = Self::_emit_match(matches, min_len, max_len, f)?;
+            offset_cache.advance(count - advanced);
             current += count;
         }
 
         Ok(())
     }
 
-    /// Encode LZSS with Suffix Array and Longest Common Prefix array compression (experimental)
-    pub fn encode_sa_lcp<F>(
+    /// Returns the candidate with the lowest cost per byte, or the nearest one of a tie.
+    ///
+    /// The lengths are clipped to `max_len`, and the search stops at the first candidate of `threshold_len`.
+    fn _find_cheapest_match(
+        input: &[u8],
+        current: usize,
+        config: &Configuration,
+        guaranteed_min_len: usize,
+        distances: impl Iterator<Item = NonZero<usize>>,
+        match_cost: fn(usize, usize) -> u32,
+    ) -> Option<Match> {
+        let mut cheapest: Option<(Match, u64)> = None;
+        for distance in distances {
+            let len = (lz::matching_len(input, current + guaranteed_min_len, distance)
+                + guaranteed_min_len)
+                .min(config.max_len.get());
+            if len < config.min_len {
+                continue;
+            }
+            let cost = match_cost(len, distance.get()) as u64;
+            // compares `cost / len` without division
+            if cheapest.is_none_or(|(v, v_cost)| cost * (v.len.get() as u64) < v_cost * len as u64)
+            {
+                cheapest = Some((Match::new(NonZero::new(len).unwrap(), distance), cost));
+            }
+            if len >= config.threshold_len {
+                break;
+            }
+        }
+        cheapest.map(|(v, _)| v)
+    }
+
+    /// Splits the input into segments of at most `segment_size` bytes and encodes them in order.
+    ///
+    /// Each segment after the first one starts with the last `max_distance` bytes of the previous segment as a dictionary,
+    /// so the positions of a segment fit in the offset cache while the matches can reach across the boundary.
+    fn _encode_segmented<F, E>(
         input: &[u8],
         config: Configuration,
+        segment_size: usize,
+        tables: &mut OffsetCacheTables,
         mut f: F,
+        mut encode: E,
     ) -> Result<(), EncodeError>
     where
         F: FnMut(LZSS) -> Result<(), EncodeError>,
+        E: FnMut(&[u8], Configuration, &mut OffsetCacheTables, &mut F) -> Result<(), EncodeError>,
     {
-        if input.is_empty() || input.len() > i32::MAX as usize {
+        if input.is_empty() {
             return Err(EncodeError::InvalidInput);
         }
-
-        let mut current = 1 + config.skip_first_literal;
-        for &literal in input.iter().take(current) {
-            f(LZSS::Literal(literal))?;
+        config._validate()?;
+        let overlap = config.max_distance.min(segment_size / 2);
+        let mut end = input.len().min(segment_size);
+        encode(&input[..end], Configuration { ..config }, tables, &mut f)?;
+        while end < input.len() {
+            let start = end - overlap;
+            let next = (start + segment_size).min(input.len());
+            let config = Configuration {
+                dictionary_len: overlap,
+                ..config
+            };
+            encode(&input[start..next], config, tables, &mut f)?;
+            end = next;
         }
+        Ok(())
+    }
 
-        let window_size = 0x100000;
-        let low_base = config.max_distance.min(window_size / 2);
-        let mut low = 0;
-        let mut high = input.len().min(window_size);
-        let mut threshold = if window_size >= input.len() {
-            input.len()
-        } else {
-            window_size - config.max_len.get()
-        };
+    /// Emits a match, splitting it into pieces of at most `max_len`, and returns the total length.
+    ///
+    /// The last piece is dropped if it is shorter than `min_len`.
+    #[inline]
+    fn _emit_match<F>(
+        matches: Match,
+        min_len: usize,
+        max_len: NonZero<usize>,
+        f: &mut F,
+    ) -> Result<usize, EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        let mut total_len = 0;
+        let mut left = matches.len.get();
         loop {
-            let input2 = &input[low..high];
-            let finder: MatchFinder<'_> = MatchFinder::new(input2);
-            while let Some(&literal) = input2.get(current) {
-                let count = {
-                    let matches = finder.matches(current, LZSS::MIN_LEN, config.max_distance);
-
-                    if let Some(matches) = matches {
-                        let mut total_len = 0;
-                        let mut left = matches.len.get();
-                        loop {
-                            if left > config.max_len.get() {
-                                f(LZSS::Match(Match::new(config.max_len, matches.distance)))?;
-                                left -= config.max_len.get();
-                                total_len += config.max_len.get();
-                                if current + total_len >= threshold {
-                                    break;
+            if left > max_len.get() {
+                f(LZSS::Match(Match::new(max_len, matches.distance)))?;
+                left -= max_len.get();
+                total_len += max_len.get();
+            } else if left >= min_len {
+                f(LZSS::Match(Match::new(
+                    NonZero::new(left).unwrap(),
+                    matches.distance,
+                )))?;
+                total_len += left;
+                break;
+            } else {
+                break;
+            }
+        }
+        Ok(total_len)
+    }
+
+    /// Encode LZSS greedily with the longest match found by the suffix array at each position
+    ///
+    /// The suffix array and the longest common prefix array are built for each window of `sa_window_size` bytes,
+    /// and `sa_search_depth` neighbors of the current suffix are examined in each direction
+    /// for the longest match within `max_distance`, the nearest one of a tie.
+    /// This finds more of the long and distant matches than the offset cache of [`encode`](Self::encode),
+    /// at the cost of building the arrays.
+    pub fn encode_sa_lcp<F>(
+        input: &[u8],
+        config: Configuration,
+        mut f: F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        Self::_encode_windowed(
+            input,
+            &config,
+            &mut f,
+            |window, finder, mut pos, limit, f| {
+                while let Some(&literal) = window.get(pos)
+                    && pos < limit
+                {
+                    pos += match finder.matches(pos, config.min_len, config.max_distance) {
+                        Some(matches) => {
+                            Self::_emit_match(matches, config.min_len, config.max_len, f)?
+                        }
+                        None => {
+                            f(LZSS::Literal(literal))?;
+                            1
+                        }
+                    };
+                }
+                Ok(pos)
+            },
+        )
+    }
+
+    /// Encode LZSS with the shortest path over the matches found by the suffix array
+    ///
+    /// Every prefix of the longest match at each position is a candidate,
+    /// and the parse with the lowest total cost under `cost_model` is emitted.
+    /// Matches of at least `threshold_len` are taken as they are, which bounds the cost on highly redundant input.
+    pub fn encode_optimal<F, C>(
+        input: &[u8],
+        config: Configuration,
+        cost_model: &C,
+        mut f: F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+        C: CostModel + ?Sized,
+    {
+        let min_len = config.min_len;
+        let max_len = config.max_len.get();
+        let threshold_len = config.threshold_len.clamp(min_len.min(max_len), max_len);
+
+        // cheapest cost to reach each position of the segment, and the last step of that path
+        let mut costs = Vec::with_capacity(Self::OPTIMAL_SEGMENT_SIZE + 1);
+        let mut steps = Vec::with_capacity(Self::OPTIMAL_SEGMENT_SIZE + 1);
+        let mut path = Vec::new();
+        let mut candidates = Vec::new();
+
+        Self::_encode_windowed(
+            input,
+            &config,
+            &mut f,
+            |window, finder, mut pos, limit, f| {
+                while pos < limit {
+                    let segment_len = (limit - pos).min(Self::OPTIMAL_SEGMENT_SIZE);
+                    costs.clear();
+                    costs.push(0);
+                    steps.clear();
+                    steps.push((0u32, 0u32));
+
+                    let mut end = segment_len;
+                    let mut long_match = None;
+                    for i in 0..segment_len {
+                        // extend the tables only as far as the current position can reach
+                        let reach = (i + max_len).min(segment_len);
+                        if costs.len() <= reach {
+                            costs.resize(reach + 1, u32::MAX);
+                            steps.resize(reach + 1, (0, 0));
+                        }
+                        let base = costs[i];
+                        let cost = base.saturating_add(cost_model.literal_cost(window[pos + i]));
+                        if cost < costs[i + 1] {
+                            costs[i + 1] = cost;
+                            steps[i + 1] = (1, 0);
+                        }
+
+                        finder.match_candidates(
+                            pos + i,
+                            min_len,
+                            max_len,
+                            config.max_distance,
+                            &mut candidates,
+                        );
+                        if let Some(&longest) = candidates.last()
+                            && longest.len.get() >= threshold_len
+                        {
+                            end = i;
+                            long_match = Some(longest);
+                            break;
+                        }
+                        // each length uses the nearest candidate that covers it
+                        let mut len = min_len;
+                        for candidate in candidates.iter() {
+                            let distance = candidate.distance.get();
+                            while len <= candidate.len.get().min(segment_len - i) {
+                                let cost =
+                                    base.saturating_add(cost_model.match_cost(len, distance));
+                                if cost < costs[i + len] {
+                                    costs[i + len] = cost;
+                                    steps[i + len] = (len as u32, distance as u32);
                                 }
-                            } else if left >= LZSS::MIN_LEN {
-                                f(LZSS::Match(Match::new(
-                                    NonZero::new(left).unwrap(),
-                                    matches.distance,
-                                )))?;
-                                total_len += left;
-                                break;
-                            } else {
-                                break;
+                                len += 1;
                             }
                         }
-                        total_len
-                    } else {
-                        f(LZSS::Literal(literal))?;
-                        1
                     }
+
+                    path.clear();
+                    let mut i = end;
+                    while i > 0 {
+                        path.push(i);
+                        i -= steps[i].0 as usize;
+                    }
+                    for &i in path.iter().rev() {
+                        let (len, distance) = steps[i];
+                        let len = len as usize;
+                        if len == 1 {
+                            f(LZSS::Literal(window[pos + i - 1]))?;
+                        } else {
+                            f(LZSS::Match(Match::new(
+                                NonZero::new(len).unwrap(),
+                                NonZero::new(distance as usize).unwrap(),
+                            )))?;
+                        }
+                    }
+                    pos += end;
+
+                    if let Some(long_match) = long_match {
+                        f(LZSS::Match(long_match))?;
+                        pos += long_match.len.get();
+                    }
+                }
+                Ok(pos)
+            },
+        )
+    }
+
+    /// Slides a window of `sa_window_size` bytes over the input, building the match finder for each window.
+    ///
+    /// `parse` encodes the window from the given position up to at least the limit, and returns the position where it stopped.
+    /// The limit leaves room for a match of `max_len` before the end of the window, except for the last window.
+    /// The next window starts up to `sa_window_overlap` bytes before that position, so the matches can reach across the windows.
+    fn _encode_windowed<F, P>(
+        input: &[u8],
+        config: &Configuration,
+        f: &mut F,
+        mut parse: P,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+        P: FnMut(&[u8], &MatchFinder, usize, usize, &mut F) -> Result<usize, EncodeError>,
+    {
+        if input.is_empty() {
+            return Err(EncodeError::InvalidInput);
+        }
+        config._validate()?;
+
+        let window_size = config.sa_window_size;
+        if window_size <= config.max_len.get() * 2 {
+            return Err(EncodeError::InvalidInput);
+        }
+
+        let mut current = Self::_encode_leading_literals(input, config, f)?;
+
+        let overlap = config._sa_window_overlap();
+        let mut finder = MatchFinder::new(&[]);
+        finder.set_search_depth(config.sa_search_depth);
+        finder.set_exact_search(config.sa_exact_search);
+        while current < input.len() {
+            let window = finder.advance_window(input, current, window_size, overlap);
+            let limit = if window.end == input.len() {
+                window.len()
+            } else {
+                window.len() - config.max_len.get()
+            };
+            current = window.start + parse(finder.s(), &finder, current - window.start, limit, f)?;
+        }
+
+        Ok(())
+    }
+
+    /// Emits the literals that cannot be matched at the beginning of the input,
+    /// and returns the position where matching starts.
+    fn _encode_leading_literals<F>(
+        input: &[u8],
+        config: &Configuration,
+        f: &mut F,
+    ) -> Result<usize, EncodeError>
+    where
+        F: FnMut(LZSS) -> Result<(), EncodeError>,
+    {
+        if config.dictionary_len > 0 {
+            if config.dictionary_len > input.len() {
+                return Err(EncodeError::InvalidInput);
+            }
+            return Ok(config.dictionary_len);
+        }
+        let current = 1 + config.skip_first_literal;
+        for &literal in input.iter().take(current) {
+            f(LZSS::Literal(literal))?;
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(tokens: &[LZSS]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for token in tokens {
+            match *token {
+                LZSS::Literal(literal) => output.push(literal),
+                LZSS::Match(matches) => {
+                    let start = output.len() - matches.distance.get();
+                    for i in 0..matches.len.get() {
+                        output.push(output[start + i]);
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn encode_fast_acceleration() {
+        let mut input = random_alphabet(0, 255, 0x4000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x4000));
+        input.extend_from_slice(&input.clone()[..0x1000]);
+        input.extend_from_slice(&random_alphabet(0, 255, 0x1001));
+
+        for acceleration in [0, 1, Configuration::FAST_ACCELERATION] {
+            let mut tokens = Vec::new();
+            LZSS::encode_fast(
+                &input,
+                Configuration::DEFAULT.acceleration(acceleration),
+                |lzss| {
+                    tokens.push(lzss);
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(decode(&tokens), input, "acceleration {}", acceleration);
+            assert!(tokens.len() < input.len() * 3 / 4);
+        }
+    }
+
+    #[test]
+    fn encode_segmented() {
+        // a block repeated at a distance that crosses the boundaries of the segments
+        let block = random_alphabet(0, 255, 0x300);
+        let mut input = Vec::new();
+        for _ in 0..20 {
+            input.extend_from_slice(&block);
+            input.extend_from_slice(&random_alphabet(b'a', b'd', 0x100));
+        }
+
+        for fast in [false, true] {
+            for segment_size in [0x1000, 0x1234, input.len()] {
+                let mut tokens = Vec::new();
+                let f = |lzss| {
+                    tokens.push(lzss);
+                    Ok(())
                 };
-                current += count;
+                let config = Configuration::new(0x800, 258);
+                let tables = &mut OffsetCacheTables::new();
+                if fast {
+                    LZSS::_encode_segmented(
+                        &input,
+                        config,
+                        segment_size,
+                        tables,
+                        f,
+                        LZSS::_encode_fast,
+                    )
+                } else {
+                    LZSS::_encode_segmented(&input, config, segment_size, tables, f, LZSS::_encode)
+                }
+                .unwrap();
+                assert_eq!(decode(&tokens), input, "segment {segment_size}");
+                // the repeated blocks are matched even across the segments
+                let literals = tokens
+                    .iter()
+                    .filter(|v| matches!(v, LZSS::Literal(_)))
+                    .count();
+                assert!(literals < input.len() / 4, "segment {segment_size}");
             }
-            if low + current == input.len() {
-                break;
+        }
+    }
+
+    #[test]
+    fn parsers() {
+        // "abcde" matches at the first position, but "bcdefg" is longer at the next one
+        let input = b"xbcdefg_abcdeZ-abcdefg";
+        let encode = |parser| {
+            let mut tokens = Vec::new();
+            LZSS::encode(input, Configuration::DEFAULT.parser(parser), |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&tokens), input);
+            tokens
+        };
+        let greedy = encode(Parser::Greedy);
+        let lazy = encode(Parser::Lazy);
+        assert!(lazy.len() < greedy.len());
+        assert!(encode(Parser::Optimal).len() <= lazy.len());
+
+        let mut input = random_alphabet(b'a', b'd', 0x1000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+        for parser in [Parser::Greedy, Parser::Lazy, Parser::Optimal] {
+            let mut tokens = Vec::new();
+            LZSS::encode(&input, Configuration::DEFAULT.parser(parser), |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&tokens), input, "{parser:?}");
+        }
+    }
+
+    #[test]
+    fn key_len() {
+        let mut input = random_alphabet(b'a', b'd', 0x2000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+        input.extend_from_slice(b"abcdefgh");
+
+        assert_eq!(Configuration::DEFAULT.key_len, 3);
+        assert_eq!(Configuration::DEFAULT.key_len(2).key_len, 3);
+        assert_eq!(Configuration::DEFAULT.key_len(9).key_len, 8);
+        for key_len in Configuration::MIN_KEY_LEN..=Configuration::MAX_KEY_LEN {
+            for fast in [false, true] {
+                let mut tokens = Vec::new();
+                let f = |lzss| {
+                    tokens.push(lzss);
+                    Ok(())
+                };
+                let config = Configuration::DEFAULT.key_len(key_len).min_len(key_len);
+                if fast {
+                    LZSS::encode_fast(&input, config, f)
+                } else {
+                    LZSS::encode(&input, config, f)
+                }
+                .unwrap();
+                assert_eq!(decode(&tokens), input, "key_len {key_len}");
+                // every match is at least as long as the key
+                assert!(tokens.iter().all(|v| match v {
+                    LZSS::Literal(_) => true,
+                    LZSS::Match(matches) => matches.len.get() >= key_len,
+                }));
+                assert!(tokens.len() < input.len() * 3 / 4, "key_len {key_len}");
             }
-            low += current - low_base;
-            high = (low + window_size).min(input.len());
-            current = low_base;
-            threshold = window_size;
         }
+    }
 
-        Ok(())
+    #[test]
+    fn min_len() {
+        let mut input = random_alphabet(b'a', b'd', 0x2000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+        input.extend_from_slice(&input.clone()[..0x800]);
+
+        let encode = |config: Configuration, variant: usize| {
+            let mut tokens = Vec::new();
+            let f = |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            };
+            match variant {
+                0 => LZSS::encode_fast(&input, config, f),
+                1 => LZSS::encode(&input, config, f),
+                2 => LZSS::encode(&input, config.parser(Parser::Lazy), f),
+                3 => LZSS::encode(&input, config.long_range_distance(0x100), f),
+                4 => LZSS::encode_sa_lcp(&input, config, f),
+                _ => LZSS::encode_optimal(&input, config, &FlatCostModel, f),
+            }
+            .map(|_| tokens)
+        };
+        for min_len in LZSS::MIN_LEN..=Configuration::MAX_KEY_LEN {
+            for variant in 0..6 {
+                let tokens =
+                    encode(Configuration::new(0x8000, 258).min_len(min_len), variant).unwrap();
+                assert_eq!(
+                    decode(&tokens),
+                    input,
+                    "min_len {min_len} variant {variant}"
+                );
+                assert!(tokens.iter().all(|v| match v {
+                    LZSS::Literal(_) => true,
+                    LZSS::Match(matches) => matches.len.get() >= min_len,
+                }));
+                // a single attempt of `encode_fast` mostly finds matches as short as the key
+                assert!(
+                    tokens.len() < input.len() * 7 / 8,
+                    "min_len {min_len} variant {variant}"
+                );
+            }
+        }
+
+        for config in [
+            Configuration::DEFAULT.min_len(2),
+            Configuration::DEFAULT.min_len(9),
+            Configuration::DEFAULT.key_len(5).min_len(4),
+            Configuration::new(0x8000, 4).min_len(5),
+        ] {
+            for variant in 0..6 {
+                assert_eq!(
+                    encode(Configuration { ..config }, variant).err(),
+                    Some(EncodeError::InvalidInput)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn match_cost() {
+        // "abcdefgh" is nearer, but the far "abcdefghi" is longer
+        let mut input = b"abcdefghi".to_vec();
+        input.extend_from_slice(&[b'-'; 0x1000]);
+        input.extend_from_slice(b"abcdefgh_abcdefghi");
+        // length of the match of the last "abcdefghi"
+        let last_len = |config: Configuration| {
+            let mut tokens = Vec::new();
+            LZSS::encode(&input, config, |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&tokens), input);
+            let mut pos = 0;
+            for token in tokens {
+                match token {
+                    LZSS::Literal(_) => pos += 1,
+                    LZSS::Match(matches) if pos == input.len() - 9 => return matches.len.get(),
+                    LZSS::Match(matches) => pos += matches.len.get(),
+                }
+            }
+            0
+        };
+        let config = Configuration::new(0x8000, 258).number_of_attempts(100);
+        assert_eq!(last_len(Configuration { ..config }), 9);
+        // the distance costs two bits per doubling
+        let config = config.match_cost(|len, distance| 8 + len.ilog2() + distance.ilog2() * 2);
+        assert_eq!(last_len(Configuration { ..config }), 8);
+        assert_eq!(last_len(config.parser(Parser::Lazy)), 8);
+    }
+
+    #[test]
+    fn long_range() {
+        // a block repeated beyond the distance of the offset cache
+        let block = random_alphabet(0, 255, 0x800);
+        let mut input = block.clone();
+        input.extend_from_slice(&random_alphabet(b'a', b'p', 0x20000));
+        input.extend_from_slice(&block);
+
+        let encode = |config: Configuration| {
+            let mut tokens = Vec::new();
+            LZSS::encode(&input, config, |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&tokens), input);
+            tokens
+        };
+        let short = encode(Configuration::new(0x8000, 258));
+        let long = encode(Configuration::new(0x40000, 258).long_range_distance(0x8000));
+        let far_match = |tokens: &[LZSS]| {
+            tokens.iter().any(|v| match v {
+                LZSS::Literal(_) => false,
+                LZSS::Match(matches) => matches.distance.get() > 0x8000,
+            })
+        };
+        assert!(!far_match(&short));
+        assert!(far_match(&long));
+        assert!(long.len() < short.len());
+
+        // the segments of the suffix array
+        let mut input = random_alphabet(0, 255, 0x1000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x30000));
+        let mut tokens = Vec::new();
+        let config = Configuration::new(0x40000, 258)
+            .long_range_distance(0x1000)
+            .sa_window(0x10000, 0x8000);
+        LZSS::encode(&input, config, |lzss| {
+            tokens.push(lzss);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(decode(&tokens), input);
+    }
+
+    #[test]
+    fn sa_window() {
+        // a block repeated across the windows
+        let block = random_alphabet(0, 255, 0x300);
+        let mut input = Vec::new();
+        for _ in 0..16 {
+            input.extend_from_slice(&block);
+            input.extend_from_slice(&random_alphabet(b'a', b'd', 0x200));
+        }
+
+        for (window_size, overlap) in [(0x1000, 0x800), (0x1000, 0x400), (0x777, usize::MAX)] {
+            let config = Configuration::new(0x800, 258).sa_window(window_size, overlap);
+            for optimal in [false, true] {
+                let mut tokens = Vec::new();
+                let f = |lzss| {
+                    tokens.push(lzss);
+                    Ok(())
+                };
+                if optimal {
+                    LZSS::encode_optimal(&input, Configuration { ..config }, &FlatCostModel, f)
+                } else {
+                    LZSS::encode_sa_lcp(&input, Configuration { ..config }, f)
+                }
+                .unwrap();
+                assert_eq!(decode(&tokens), input, "{window_size} {overlap}");
+                let literals = tokens
+                    .iter()
+                    .filter(|v| matches!(v, LZSS::Literal(_)))
+                    .count();
+                assert!(literals < input.len() / 3, "{window_size} {overlap}");
+            }
+        }
+
+        let config = Configuration::new(0x800, 258).sa_window(516, 0x100);
+        assert_eq!(
+            LZSS::encode_sa_lcp(&input, config, |_| Ok(())),
+            Err(EncodeError::InvalidInput)
+        );
+        let config = Configuration::new(0x800, 258)
+            .long_range_distance(0x100)
+            .sa_window(516, 0x100);
+        assert_eq!(
+            LZSS::encode(&input, config, |_| Ok(())),
+            Err(EncodeError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn sa_search_depth() {
+        // many repetitions of short strings hide the nearer of the long matches from a shallow search
+        let mut input = Vec::new();
+        for i in 0..0x400 {
+            input.extend_from_slice(&random_alphabet(b'a', b'b', 4 + i % 5));
+            if i % 64 == 0 {
+                input.extend_from_slice(b"the quick brown fox");
+            }
+        }
+        let encode = |depth, exact| {
+            let mut tokens = Vec::new();
+            let config = Configuration::new(0x8000, 258)
+                .sa_search_depth(depth)
+                .sa_exact_search(exact);
+            LZSS::encode_sa_lcp(&input, config, |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&tokens), input, "depth {depth} exact {exact}");
+            tokens.len()
+        };
+        let shallow = encode(1, false);
+        let deep = encode(MatchFinder::DEFAULT_SEARCH_DEPTH, false);
+        assert!(deep < shallow, "{deep} {shallow}");
+
+        // the exact search finds the longest matches at any depth
+        let exact = encode(1, true);
+        assert_eq!(exact, encode(MatchFinder::DEFAULT_SEARCH_DEPTH, true));
+        assert!(exact <= deep, "{exact} {deep}");
+    }
+
+    #[test]
+    fn encode_optimal() {
+        let cost_of = |tokens: &[LZSS]| -> u32 {
+            tokens
+                .iter()
+                .map(|token| match *token {
+                    LZSS::Literal(literal) => FlatCostModel.literal_cost(literal),
+                    LZSS::Match(matches) => {
+                        FlatCostModel.match_cost(matches.len.get(), matches.distance.get())
+                    }
+                })
+                .sum()
+        };
+
+        let mut input = random_alphabet(b'a', b'd', 0x2000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x2000));
+        input.extend_from_slice(&[0; 0x1000]);
+        for config in [
+            Configuration::new(0x8000, 258),
+            Configuration::new(0x8000, 258).threshold_len(32),
+            Configuration::new(0x100, 16).skip_first_literal(1),
+        ] {
+            let mut greedy = Vec::new();
+            LZSS::encode(&input, Configuration { ..config }, |lzss| {
+                greedy.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+
+            let mut optimal = Vec::new();
+            LZSS::encode_optimal(&input, Configuration { ..config }, &FlatCostModel, |lzss| {
+                optimal.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decode(&optimal), input);
+            assert!(cost_of(&optimal) <= cost_of(&greedy));
+
+            let mut parsed = Vec::new();
+            LZSS::encode(&input, config.parser(Parser::Optimal), |lzss| {
+                parsed.push(lzss);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(cost_of(&parsed), cost_of(&optimal));
+        }
     }
 }
@@ -1,12 +1,18 @@
 //! Match Finder using Suffix Array and LCP Array
-use crate::lz::{Match, MaybeMatch};
+use crate::lz::Match;
 use crate::*;
-use core::{num::NonZero, ops::Range};
-use lcp::LcpArray;
-use sais::SuffixArray;
+use core::{
+    cell::{Cell, OnceCell},
+    num::NonZero,
+    ops::Range,
+};
+use lz::suffix::LcpArray;
+pub use lz::suffix::{SuffixArray, Symbol};
+use rmq::RangeMin;
+use wavelet::WaveletMatrix;
 
-mod lcp;
-mod sais;
+mod rmq;
+mod wavelet;
 
 #[cfg(test)]
 mod tests;
@@ -19,43 +25,173 @@ pub struct MatchFinder<'a> {
     rev_sa: Vec<u32>,
     counts: Box<[u32; 256]>,
     buckets: Box<[u32; 257]>,
+    lcp_min: RangeMin,
+    /// Built on the first search that needs it, since most positions are settled without it
+    offsets: OnceCell<WaveletMatrix>,
+    /// The buffers of the previous `offsets`
+    spare_offsets: Cell<Option<WaveletMatrix>>,
+    search_depth: usize,
+    exact_search: bool,
 }
 
 impl<'a> MatchFinder<'a> {
+    /// Default number of neighbors in the suffix array examined in each direction
+    pub const DEFAULT_SEARCH_DEPTH: usize = 200;
+
+    /// Maximum length of the intervals of the suffix array whose offsets are scanned
+    /// instead of looked up in the wavelet matrix
+    const SMALL_INTERVAL: usize = 256;
+
     pub fn new(s: &'a [u8]) -> Self {
-        let mut counts = [0; 256];
-        for &byte in s {
-            counts[byte as usize] += 1;
+        let sa = SuffixArray::new(s);
+        let mut finder = Self {
+            s,
+            sa,
+            lcp: Vec::new(),
+            rev_sa: Vec::new(),
+            counts: Box::new([0; 256]),
+            buckets: Box::new([0; 257]),
+            lcp_min: RangeMin::default(),
+            offsets: OnceCell::new(),
+            spare_offsets: Cell::new(None),
+            search_depth: Self::DEFAULT_SEARCH_DEPTH,
+            exact_search: false,
+        };
+        finder._build_tables();
+        finder
+    }
+
+    /// Rebuilds the match finder for another string, reusing the allocated buffers.
+    ///
+    /// This avoids reallocations when many buffers of similar sizes are processed in turn,
+    /// such as the sliding windows of a long input.
+    pub fn rebuild(&mut self, s: &'a [u8]) {
+        self.s = s;
+        self.sa.rebuild(s);
+        self._build_tables();
+    }
+
+    /// Moves the window over `input` so that it starts `overlap` bytes before `pos`, and spans up to `window_size` bytes,
+    /// rebuilding the match finder with the allocated buffers.
+    ///
+    /// The bytes of the window before `pos` are the history that the matches from `pos` can refer to.
+    /// Returns the range of the window in `input`, whose start is the origin of the positions given to the other methods.
+    ///
+    /// ```
+    /// use compress::lz::match_finder::MatchFinder;
+    ///
+    /// let input = b"abcdefgh-abcdefgh-abcdefgh-abcdefgh";
+    /// let mut finder = MatchFinder::new(&[]);
+    /// let window = finder.advance_window(input, 18, 16, 9);
+    /// assert_eq!(window, 9..25);
+    /// let matches = finder.matches(18 - window.start, 3, 16);
+    /// assert_eq!(matches.map(|v| v.distance.get()), Some(9));
+    /// ```
+    pub fn advance_window(
+        &mut self,
+        input: &'a [u8],
+        pos: usize,
+        window_size: usize,
+        overlap: usize,
+    ) -> Range<usize> {
+        let start = pos.saturating_sub(overlap);
+        let end = start.saturating_add(window_size).min(input.len());
+        self.rebuild(&input[start..end]);
+        start..end
+    }
+
+    /// Rebuilds the match finder for a string of another lifetime, reusing the allocated buffers.
+    pub fn recycle<'b>(self, s: &'b [u8]) -> MatchFinder<'b> {
+        let mut finder = MatchFinder {
+            s,
+            sa: self.sa,
+            lcp: self.lcp,
+            rev_sa: self.rev_sa,
+            counts: self.counts,
+            buckets: self.buckets,
+            lcp_min: self.lcp_min,
+            offsets: OnceCell::new(),
+            spare_offsets: Cell::new(
+                self.offsets
+                    .into_inner()
+                    .or(self.spare_offsets.into_inner()),
+            ),
+            search_depth: self.search_depth,
+            exact_search: self.exact_search,
+        };
+        finder.sa.rebuild(s);
+        finder._build_tables();
+        finder
+    }
+
+    /// Sets the number of neighbors in the suffix array examined in each direction by [`matches`](Self::matches)
+    /// and [`match_candidates`](Self::match_candidates).
+    ///
+    /// The neighbors are ordered by the length of the common prefix, so deeper searches find nearer matches
+    /// of the same length, and longer matches within `max_distance` in large windows.
+    /// The result of `matches` does not depend on it with [`set_exact_search`](Self::set_exact_search),
+    /// since the search goes on past the neighbors when needed, but the neighbors are cheaper to examine.
+    #[inline]
+    pub fn set_search_depth(&mut self, search_depth: usize) {
+        self.search_depth = search_depth;
+    }
+
+    #[inline]
+    pub const fn search_depth(&self) -> usize {
+        self.search_depth
+    }
+
+    /// Makes [`matches`](Self::matches) go on past the neighbors with a binary search
+    /// when they do not settle the match, so that it always finds the longest match within `max_distance`.
+    ///
+    /// This finds more of the long and distant matches in large windows,
+    /// but is slower than the bounded search on typical data.
+    #[inline]
+    pub fn set_exact_search(&mut self, exact_search: bool) {
+        self.exact_search = exact_search;
+    }
+
+    #[inline]
+    pub const fn exact_search(&self) -> bool {
+        self.exact_search
+    }
+
+    /// Builds the tables other than the suffix array.
+    fn _build_tables(&mut self) {
+        let s = self.s;
+        for (p, q) in self.counts.iter_mut().zip(stats::histogram_of_bytes(s)) {
+            *p = q as u32;
         }
 
-        let mut buckets = [0; 257];
         let mut acc = 0;
-        for (&count, bucket) in counts.iter().zip(buckets.iter_mut()) {
+        for (&count, bucket) in self.counts.iter().zip(self.buckets.iter_mut()) {
             acc += count;
             *bucket = acc;
         }
-        buckets[256] = s.len() as u32;
+        self.buckets[256] = s.len() as u32;
 
-        let sa = SuffixArray::new(s);
-
-        let mut rev_sa = Vec::with_capacity(s.len());
-        rev_sa.resize(s.len(), 0);
-        for (i, &suffix) in sa.as_slice().iter().enumerate() {
-            rev_sa[suffix as usize] = i as u32;
+        self.rev_sa.clear();
+        self.rev_sa.resize(s.len(), 0);
+        for (i, &suffix) in self.sa.as_slice().iter().enumerate() {
+            self.rev_sa[suffix as usize] = i as u32;
         }
 
-        let lcp = LcpArray::new(s, sa.as_slice(), &rev_sa);
-
-        Self {
-            s,
-            sa,
-            lcp,
-            rev_sa,
-            counts: counts.into(),
-            buckets: buckets.into(),
+        LcpArray::build_into(s, self.sa.as_slice(), &self.rev_sa, &mut self.lcp);
+        self.lcp_min.rebuild(&self.lcp);
+        if let Some(offsets) = self.offsets.take() {
+            self.spare_offsets.set(Some(offsets));
         }
     }
 
+    /// Returns the wavelet matrix over the suffix array, building it on the first call.
+    fn _offsets(&self) -> &WaveletMatrix {
+        self.offsets.get_or_init(|| {
+            let mut offsets = self.spare_offsets.take().unwrap_or_default();
+            offsets.rebuild(self.sa());
+            offsets
+        })
+    }
+
     /// Returns the longest common prefix array.
     #[inline]
     pub fn lcp(&self) -> &[u32] {
@@ -95,123 +231,248 @@ impl<'a> MatchFinder<'a> {
         self.buckets[byte as usize] as usize..self.buckets[1 + byte as usize] as usize
     }
 
-    pub fn matches<'b>(&'b self, pos: usize, min_len: usize, max_distance: usize) -> Option<Match> {
+    /// Collects the matches at `pos` that are not dominated by a longer and nearer match.
+    ///
+    /// The candidates are ordered from the shortest to the longest, and their distances increase in the same order.
+    /// Lengths are truncated to `max_len`.
+    pub fn match_candidates(
+        &self,
+        pos: usize,
+        min_len: usize,
+        max_len: usize,
+        max_distance: usize,
+        candidates: &mut Vec<Match>,
+    ) {
+        candidates.clear();
         let min_offset = pos.saturating_sub(max_distance);
         let sa_base_index = self.rev_sa[pos] as usize;
-        let takes = 200;
-
-        let iter1 = (self.lcp().get(sa_base_index)).map(|_| {
-            self.lcp()
-                .iter()
-                .zip(self.sa().iter().skip(1))
-                .skip(sa_base_index)
-                .take(takes)
-        });
-        let iter2 = (sa_base_index > 0).then(|| {
-            let lcp2 = &self.lcp()[..sa_base_index];
-            let sa2 = &self.sa()[..sa_base_index];
-            lcp2.iter().zip(sa2.iter()).rev().take(takes)
-        });
+        let lcp = self.lcp();
+        let sa = self.sa();
 
-        let mut matches = MaybeMatch::default();
+        let mut push = |lcp_limit: usize, offset: u32| {
+            let offset = offset as usize;
+            if offset >= min_offset && offset < pos {
+                candidates.push(Match::new(
+                    NonZero::new(lcp_limit.min(max_len)).unwrap(),
+                    NonZero::new(pos - offset).unwrap(),
+                ));
+            }
+        };
 
-        let mut kernel = |lcp: &u32, offset: &u32, min_len: usize, lcp_limit: &mut usize| -> bool {
-            let lcp = *lcp as usize;
-            let offset = *offset as usize;
-            if lcp < min_len {
-                return false;
+        let mut lcp_limit = usize::MAX;
+        for (&lcp, &offset) in lcp[sa_base_index.min(lcp.len())..]
+            .iter()
+            .zip(sa[sa_base_index + 1..].iter())
+            .take(self.search_depth)
+        {
+            lcp_limit = lcp_limit.min(lcp as usize);
+            if lcp_limit < min_len {
+                break;
             }
-            if offset >= min_offset && offset < pos {
-                let len = (*lcp_limit).min(lcp);
-                let distance = pos - offset;
-
-                if let Some(ref mut matches2) = matches.get() {
-                    if matches.len() > len {
-                        return false;
-                    } else if matches.len() < len {
-                        matches = MaybeMatch::new(len, distance);
-                    } else if matches.len() == len && matches.distance() > distance {
-                        matches2.distance = NonZero::new(distance).unwrap();
-                    }
-                } else {
-                    matches = MaybeMatch::new(len, distance);
-                }
+            push(lcp_limit, offset);
+        }
 
-                // if matches.is_zero() {
-                //     matches = Match::new(len, distance).into();
-                // } else if matches.len() > len {
-                //     return false;
-                // } else if matches.len() < len {
-                //     matches = Match::new(len, distance).into();
-                // } else if matches.len() == len && matches.distance() > distance {
-                //     matches.distance = distance;
-                // }
+        let mut lcp_limit = usize::MAX;
+        for (&lcp, &offset) in lcp[..sa_base_index]
+            .iter()
+            .zip(sa[..sa_base_index].iter())
+            .rev()
+            .take(self.search_depth)
+        {
+            lcp_limit = lcp_limit.min(lcp as usize);
+            if lcp_limit < min_len {
+                break;
             }
-            *lcp_limit = (*lcp_limit).min(lcp);
-            true
-        };
+            push(lcp_limit, offset);
+        }
 
-        let mut lcp_limit1 = usize::MAX;
-        let mut lcp_limit2 = usize::MAX;
+        candidates.sort_unstable_by_key(|v| (v.distance, core::cmp::Reverse(v.len)));
+        let mut longest = 0;
+        candidates.retain(|v| {
+            if v.len.get() > longest {
+                longest = v.len.get();
+                true
+            } else {
+                false
+            }
+        });
+    }
 
-        match (iter1, iter2) {
-            (None, None) => {
-                // No iterators available
-                return None;
+    /// Scans the neighbors of a suffix in a direction for a longer or nearer match than `best`.
+    ///
+    /// Returns whether the scan is settled, when the common prefix gets shorter than the best match
+    /// within `search_depth` neighbors, since the farther neighbors share even less.
+    #[inline]
+    fn _scan_neighbors<'b>(
+        neighbors: impl Iterator<Item = (&'b u32, &'b u32)>,
+        search_depth: usize,
+        pos: usize,
+        min_offset: usize,
+        min_len: usize,
+        best: &mut Option<(usize, usize)>,
+    ) -> bool {
+        let mut lcp_limit = usize::MAX;
+        for (step, (&lcp, &offset)) in neighbors.enumerate() {
+            lcp_limit = lcp_limit.min(lcp as usize);
+            if lcp_limit < best.map_or(min_len, |(len, _)| len) {
+                return true;
             }
-            (Some(mut iter1), None) => {
-                while let Some((lcp, offset)) = iter1.next() {
-                    if !kernel(lcp, offset, min_len, &mut lcp_limit1) {
-                        break;
-                    }
-                }
-                return matches.get();
+            if step == search_depth {
+                return false;
             }
-            (None, Some(mut iter2)) => {
-                while let Some((lcp, offset)) = iter2.next() {
-                    if !kernel(lcp, offset, min_len, &mut lcp_limit2) {
-                        break;
+            let offset = offset as usize;
+            if offset >= min_offset
+                && offset < pos
+                && best.is_none_or(|(len, nearest)| lcp_limit > len || offset > nearest)
+            {
+                *best = Some((lcp_limit, offset));
+            }
+        }
+        true
+    }
+
+    /// Returns the longest match at `pos` of at least `min_len` within `max_distance` among the searched suffixes,
+    /// the nearest one of a tie.
+    ///
+    /// The nearest `search_depth` neighbors in the suffix array are scanned in each direction,
+    /// which settles most positions.
+    /// Otherwise, with [`set_exact_search`](Self::set_exact_search), the longest length whose nearest suffix
+    /// before `pos` is within `max_distance` is found by a binary search over the intervals of the suffix array
+    /// that share each length, and without it, the best match among the neighbors is returned.
+    pub fn matches(&self, pos: usize, min_len: usize, max_distance: usize) -> Option<Match> {
+        let min_offset = pos.saturating_sub(max_distance);
+        let min_len = min_len.max(1);
+        let rank = self.rev_sa[pos] as usize;
+        let lcp = self.lcp();
+        let sa = self.sa();
+
+        let mut best = None;
+        let settled_after = Self::_scan_neighbors(
+            lcp[rank..].iter().zip(sa[rank + 1..].iter()),
+            self.search_depth,
+            pos,
+            min_offset,
+            min_len,
+            &mut best,
+        );
+        let settled_before = Self::_scan_neighbors(
+            lcp[..rank].iter().zip(sa[..rank].iter()).rev(),
+            self.search_depth,
+            pos,
+            min_offset,
+            min_len,
+            &mut best,
+        );
+
+        if self.exact_search && !(settled_after && settled_before) {
+            // The nearest suffix found for a length is also the nearest one of its own matching length,
+            // so the search goes on from the length after it.
+            let mut low = best.map_or(min_len, |(len, _)| len);
+            let mut high = lcp[rank].max(rank.checked_sub(1).map_or(0, |v| lcp[v])) as usize;
+            best = None;
+            while low <= high {
+                let mid = low + (high - low) / 2;
+                match self._nearest(pos, mid, min_offset) {
+                    Some(offset) => {
+                        let len = self._common_len(pos, offset);
+                        best = Some((len, offset));
+                        low = len + 1;
                     }
+                    None => high = mid - 1,
                 }
-                return matches.get();
             }
-            (Some(mut iter1), Some(mut iter2)) => {
-                let (iter1, iter2) = loop {
-                    if let Some((lcp, offset)) = iter1.next() {
-                        if !kernel(lcp, offset, min_len, &mut lcp_limit1) {
-                            break (None, Some(iter2));
-                        }
-                    } else {
-                        break (None, Some(iter2));
-                    }
-                    if let Some((lcp, offset)) = iter2.next() {
-                        if !kernel(lcp, offset, min_len, &mut lcp_limit2) {
-                            break (Some(iter1), None);
-                        }
-                    } else {
-                        break (Some(iter1), None);
-                    }
-                };
-                match (iter1, iter2) {
-                    (Some(mut iter1), None) => {
-                        while let Some((lcp, offset)) = iter1.next() {
-                            if !kernel(lcp, offset, min_len, &mut lcp_limit1) {
-                                break;
-                            }
-                        }
-                        return matches.get();
-                    }
-                    (None, Some(mut iter2)) => {
-                        while let Some((lcp, offset)) = iter2.next() {
-                            if !kernel(lcp, offset, min_len, &mut lcp_limit2) {
-                                break;
-                            }
-                        }
-                        return matches.get();
-                    }
-                    _ => unreachable!(),
+        }
+
+        best.map(|(len, offset)| {
+            Match::new(
+                NonZero::new(len).unwrap(),
+                NonZero::new(pos - offset).unwrap(),
+            )
+        })
+    }
+
+    /// Collects the longest match at `pos` of at least `min_len` within each of `max_distance_tiers`,
+    /// the nearest one of a tie, or `None` if there is none within the tier.
+    ///
+    /// The matches are found in a single pass over the lengths, from the nearest match of `min_len`
+    /// to the next nearest one that is longer, and so on, so that a parser can weigh the length of a match
+    /// against the cost of its distance in a few tiers, such as up to 4 KiB, 32 KiB and 1 MiB.
+    ///
+    /// ```
+    /// use compress::lz::match_finder::MatchFinder;
+    ///
+    /// let s = b"abcd-abcdefgh-abcdx-abcdefgh";
+    /// let finder = MatchFinder::new(s);
+    /// let mut matches = Vec::new();
+    /// finder.best_match_in(20, 3, &[8, 16], &mut matches);
+    /// let matches = matches
+    ///     .iter()
+    ///     .map(|v| v.map(|v| (v.len.get(), v.distance.get())))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(matches, [Some((4, 6)), Some((8, 15))]);
+    /// ```
+    pub fn best_match_in(
+        &self,
+        pos: usize,
+        min_len: usize,
+        max_distance_tiers: &[usize],
+        matches: &mut Vec<Option<Match>>,
+    ) {
+        matches.clear();
+        matches.resize(max_distance_tiers.len(), None);
+        let Some(&max_distance) = max_distance_tiers.iter().max() else {
+            return;
+        };
+        let min_offset = pos.saturating_sub(max_distance);
+
+        // Each match found is the nearest one of its length, and the longer ones are farther.
+        let mut len = min_len.max(1);
+        while let Some(offset) = self._nearest(pos, len, min_offset) {
+            let matched = self._common_len(pos, offset);
+            let distance = pos - offset;
+            for (&tier, slot) in max_distance_tiers.iter().zip(matches.iter_mut()) {
+                if distance <= tier {
+                    *slot = Some(Match::new(
+                        NonZero::new(matched).unwrap(),
+                        NonZero::new(distance).unwrap(),
+                    ));
                 }
             }
+            len = matched + 1;
         }
     }
+
+    /// Returns the nearest offset before `pos` whose suffix shares at least `len` bytes with it,
+    /// if not before `min_offset`.
+    ///
+    /// The suffixes that share the prefix make up an interval of the suffix array,
+    /// whose bounds are found by the range minimum queries over the LCP array,
+    /// and the nearest of them by the wavelet matrix over the suffix array.
+    fn _nearest(&self, pos: usize, len: usize, min_offset: usize) -> Option<usize> {
+        let rank = self.rev_sa[pos] as usize;
+        let threshold = len.min(u32::MAX as usize) as u32;
+        let start = self.lcp_min.run_start(&self.lcp, rank, threshold);
+        let end = self.lcp_min.run_end(&self.lcp, rank, threshold) + 1;
+        let offset = if end - start <= Self::SMALL_INTERVAL {
+            self.sa()[start..end]
+                .iter()
+                .copied()
+                .filter(|&v| v < pos as u32)
+                .max()
+        } else {
+            self._offsets().prev_value(start..end, pos as u32)
+        };
+        offset
+            .map(|offset| offset as usize)
+            .filter(|&offset| offset >= min_offset)
+    }
+
+    /// Returns the length of the common prefix of the suffixes at `pos` and `offset`, which are different.
+    #[inline]
+    fn _common_len(&self, pos: usize, offset: usize) -> usize {
+        let rank = self.rev_sa[pos] as usize;
+        let other = self.rev_sa[offset] as usize;
+        self.lcp_min
+            .min(&self.lcp, rank.min(other)..rank.max(other)) as usize
+    }
 }
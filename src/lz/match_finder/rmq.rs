@@ -0,0 +1,139 @@
+//! Range minimum queries over the LCP array
+
+use crate::*;
+use core::ops::Range;
+
+/// Number of values whose minimum is kept in each entry of the first level of the table
+const BLOCK_SIZE: usize = 32;
+
+/// Sparse table over the minimums of the blocks of an array
+///
+/// Finds the run of values of at least a threshold around an index
+/// by scanning the partial blocks at its ends and doubling over the whole blocks between them.
+#[derive(Default)]
+pub(super) struct RangeMin {
+    /// `table[level * blocks + i]` is the minimum of the blocks `i..i + 2^level`
+    table: Vec<u32>,
+    blocks: usize,
+}
+
+impl RangeMin {
+    /// Rebuilds the table for `values`, reusing the allocated buffer.
+    pub fn rebuild(&mut self, values: &[u32]) {
+        let blocks = values.len().div_ceil(BLOCK_SIZE);
+        self.blocks = blocks;
+        self.table.clear();
+        self.table.extend(
+            values
+                .chunks(BLOCK_SIZE)
+                .map(|chunk| chunk.iter().copied().min().unwrap_or_default()),
+        );
+        let mut width = 1;
+        while width * 2 <= blocks {
+            let prev = self.table.len() - blocks;
+            for i in 0..blocks {
+                let value = if i + width < blocks {
+                    self.table[prev + i].min(self.table[prev + i + width])
+                } else {
+                    self.table[prev + i]
+                };
+                self.table.push(value);
+            }
+            width *= 2;
+        }
+    }
+
+    #[inline]
+    fn levels(&self) -> usize {
+        self.table.len().checked_div(self.blocks).unwrap_or(0)
+    }
+
+    /// Returns the minimum of `values[range]`, which is not empty.
+    pub fn min(&self, values: &[u32], range: Range<usize>) -> u32 {
+        let first = range.start.div_ceil(BLOCK_SIZE);
+        let last = range.end / BLOCK_SIZE;
+        if first >= last {
+            return values[range].iter().copied().min().unwrap();
+        }
+        let level = (last - first).ilog2() as usize;
+        let whole = self.table[level * self.blocks + first]
+            .min(self.table[level * self.blocks + last - (1 << level)]);
+        values[range.start..first * BLOCK_SIZE]
+            .iter()
+            .chain(values[last * BLOCK_SIZE..range.end].iter())
+            .copied()
+            .fold(whole, u32::min)
+    }
+
+    /// Returns the end of the run of values of at least `threshold` that starts at `start`.
+    pub fn run_end(&self, values: &[u32], start: usize, threshold: u32) -> usize {
+        let mut index = start;
+        while index < values.len() && !index.is_multiple_of(BLOCK_SIZE) {
+            if values[index] < threshold {
+                return index;
+            }
+            index += 1;
+        }
+        if index == values.len() {
+            return index;
+        }
+
+        // doubles the span of whole blocks up, then halves it down, so short runs take few steps
+        let mut block = index / BLOCK_SIZE;
+        let passes = |level: usize, block: usize| {
+            block + (1 << level) <= self.blocks
+                && self.table[level * self.blocks + block] >= threshold
+        };
+        let mut level = 0;
+        while level < self.levels() && passes(level, block) {
+            block += 1 << level;
+            level += 1;
+        }
+        while level > 0 {
+            level -= 1;
+            if passes(level, block) {
+                block += 1 << level;
+            }
+        }
+
+        index = (block * BLOCK_SIZE).min(values.len());
+        while index < values.len() && values[index] >= threshold {
+            index += 1;
+        }
+        index
+    }
+
+    /// Returns the start of the run of values of at least `threshold` that ends at `end`.
+    pub fn run_start(&self, values: &[u32], end: usize, threshold: u32) -> usize {
+        let mut index = end;
+        while index > 0 && !index.is_multiple_of(BLOCK_SIZE) {
+            if values[index - 1] < threshold {
+                return index;
+            }
+            index -= 1;
+        }
+
+        let mut block = index / BLOCK_SIZE;
+        let passes = |level: usize, block: usize| {
+            block >= 1 << level
+                && self.table[level * self.blocks + block - (1 << level)] >= threshold
+        };
+        let mut level = 0;
+        while level < self.levels() && passes(level, block) {
+            block -= 1 << level;
+            level += 1;
+        }
+        while level > 0 {
+            level -= 1;
+            if passes(level, block) {
+                block -= 1 << level;
+            }
+        }
+
+        index = block * BLOCK_SIZE;
+        while index > 0 && values[index - 1] >= threshold {
+            index -= 1;
+        }
+        index
+    }
+}
@@ -1,4 +1,5 @@
-use super::{lcp::LcpArrayNaive, *};
+use super::*;
+use crate::lz::suffix::LcpArrayNaive;
 
 #[test]
 fn abracadabra() {
@@ -47,6 +48,154 @@ fn lcp_random_alphabet() {
     assert_eq!(lcp.lcp(), naive.lcp());
 }
 
+#[test]
+fn match_candidates() {
+    let s = random_alphabet(b'a', b'c', 0x400);
+    let finder = MatchFinder::new(&s);
+    let mut candidates = Vec::new();
+    for pos in 0..s.len() {
+        finder.match_candidates(pos, 3, 16, 0x100, &mut candidates);
+
+        let mut expected = Vec::new();
+        for distance in 1..=pos.min(0x100) {
+            let len = s[pos..]
+                .iter()
+                .zip(s[pos - distance..].iter())
+                .take_while(|(a, b)| a == b)
+                .count()
+                .min(16);
+            if len >= 3 && expected.last().is_none_or(|v: &(usize, usize)| v.0 < len) {
+                expected.push((len, distance));
+            }
+        }
+        let actual = candidates
+            .iter()
+            .map(|v| (v.len.get(), v.distance.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected, "pos {}", pos);
+    }
+}
+
+#[test]
+fn matches() {
+    let inputs = [
+        random_alphabet(b'a', b'c', 0x600),
+        random_ab(b'a', b'b', 0x600),
+        fib_str(b'a', b'b', 0x600),
+        [b"the quick brown fox ".as_slice(); 0x30].concat(),
+    ];
+    for s in inputs.iter() {
+        let mut finder = MatchFinder::new(s);
+        for max_distance in [1, 7, 0x40, usize::MAX] {
+            let expected = (0..s.len())
+                .map(|pos| {
+                    let mut expected = None;
+                    for distance in 1..=pos.min(max_distance) {
+                        let len = lz::matching_len(s, pos, NonZero::new(distance).unwrap());
+                        if len >= 3 && expected.is_none_or(|(v, _)| v < len) {
+                            expected = Some((len, distance));
+                        }
+                    }
+                    expected
+                })
+                .collect::<Vec<_>>();
+            // the shallow searches go on with the binary search over the intervals
+            for search_depth in [0, 2, MatchFinder::DEFAULT_SEARCH_DEPTH] {
+                finder.set_search_depth(search_depth);
+                finder.set_exact_search(true);
+                for (pos, &expected) in expected.iter().enumerate() {
+                    let actual = finder
+                        .matches(pos, 3, max_distance)
+                        .map(|v| (v.len.get(), v.distance.get()));
+                    assert_eq!(
+                        actual, expected,
+                        "pos {pos} max_distance {max_distance} search_depth {search_depth}"
+                    );
+                }
+
+                // the bounded search finds a match among the neighbors, which may be shorter
+                finder.set_exact_search(false);
+                for (pos, &expected) in expected.iter().enumerate() {
+                    let actual = finder.matches(pos, 3, max_distance);
+                    if let Some(actual) = actual {
+                        let (len, distance) = (actual.len.get(), actual.distance.get());
+                        assert!(distance <= max_distance);
+                        assert_eq!(lz::matching_len(s, pos, actual.distance), len);
+                        assert!(expected.is_some_and(|(v, _)| len <= v));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn best_match_in() {
+    let s = random_alphabet(b'a', b'd', 0x800);
+    let finder = MatchFinder::new(&s);
+    let tiers = [0x400, 4, 0x40, 0x100];
+    let mut matches = Vec::new();
+    for pos in 0..s.len() {
+        finder.best_match_in(pos, 3, &tiers, &mut matches);
+        let expected = tiers
+            .iter()
+            .map(|&tier| {
+                finder
+                    .matches(pos, 3, tier)
+                    .map(|v| (v.len.get(), v.distance.get()))
+            })
+            .collect::<Vec<_>>();
+        let actual = matches
+            .iter()
+            .map(|v| v.map(|v| (v.len.get(), v.distance.get())))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected, "pos {pos}");
+    }
+
+    finder.best_match_in(0x10, 3, &[], &mut matches);
+    assert!(matches.is_empty());
+}
+
+#[test]
+fn range_queries() {
+    let values = random_alphabet(0, 9, 0x1234)
+        .iter()
+        .map(|&v| v as u32)
+        .collect::<Vec<_>>();
+    let mut lcp_min = RangeMin::default();
+    lcp_min.rebuild(&values);
+    for index in (0..=values.len()).step_by(7) {
+        for threshold in 0..=10 {
+            let end = index
+                + values[index..]
+                    .iter()
+                    .take_while(|&&v| v >= threshold)
+                    .count();
+            let start = index
+                - values[..index]
+                    .iter()
+                    .rev()
+                    .take_while(|&&v| v >= threshold)
+                    .count();
+            assert_eq!(lcp_min.run_end(&values, index, threshold), end);
+            assert_eq!(lcp_min.run_start(&values, index, threshold), start);
+        }
+    }
+
+    let s = random_alphabet(b'a', b'd', 0x1000);
+    let finder = MatchFinder::new(&s);
+    let sa = finder.sa();
+    for start in (0..sa.len()).step_by(97) {
+        for end in [start, start + 1, start + 50, start + 1000, sa.len()] {
+            let end = end.min(sa.len());
+            for upper in [0, 1, 100, 0x800, 0xfff, 0x1000, u32::MAX] {
+                let expected = sa[start..end].iter().copied().filter(|&v| v < upper).max();
+                assert_eq!(finder._offsets().prev_value(start..end, upper), expected);
+            }
+        }
+    }
+}
+
 #[allow(unused)]
 fn print_sa_lcp(s: &[u8], lcp: &MatchFinder) {
     println!("input: {:?}", unsafe { core::str::from_utf8_unchecked(s) });
@@ -75,3 +224,32 @@ fn print_sa_lcp(s: &[u8], lcp: &MatchFinder) {
     //     assert_eq!(suffix, index as u32);
     // }
 }
+
+#[test]
+fn match_finder_rebuild() {
+    let inputs = [
+        fib_str(b'a', b'b', 0x1000),
+        random_alphabet(b'A', b'Z', 0x800),
+        b"mississippi".to_vec(),
+        random_ab(0x55, 0xaa, 0x1800),
+        b"x".to_vec(),
+    ];
+    let mut finder = MatchFinder::new(&inputs[0]);
+    for s in inputs.iter() {
+        finder.rebuild(s);
+        let naive = LcpArrayNaive::new(s);
+        let expected = MatchFinder::new(s);
+        assert_eq!(finder.s(), s.as_slice());
+        assert_eq!(finder.sa(), naive.sa());
+        assert_eq!(finder.lcp(), naive.lcp());
+        assert_eq!(finder.rev_sa(), expected.rev_sa());
+        assert_eq!(finder.counts(), expected.counts());
+        assert_eq!(finder.buckets(), expected.buckets());
+    }
+
+    let s = random_alphabet(b'a', b'c', 0x400);
+    let finder = finder.recycle(&s);
+    let naive = LcpArrayNaive::new(&s);
+    assert_eq!(finder.sa(), naive.sa());
+    assert_eq!(finder.lcp(), naive.lcp());
+}
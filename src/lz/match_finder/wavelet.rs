@@ -0,0 +1,136 @@
+//! Wavelet matrix over the suffix array
+
+use crate::*;
+use core::ops::Range;
+
+/// Word of a bit vector with the number of ones before it, so that a rank takes a single cache line
+#[derive(Clone, Copy, Default)]
+struct RankedWord {
+    bits: u64,
+    rank: u32,
+}
+
+#[derive(Default)]
+struct Level {
+    /// The bits of the values at this level, followed by a word with the total rank
+    words: Vec<RankedWord>,
+    zeros: usize,
+}
+
+impl Level {
+    /// Returns the number of ones in `..index`.
+    #[inline]
+    fn rank1(&self, index: usize) -> usize {
+        let word = self.words[index / 64];
+        (word.rank + (word.bits & ((1 << (index % 64)) - 1)).count_ones()) as usize
+    }
+
+    /// Maps the range to the zeros and the ones of this level,
+    /// which are the first and the last part of the next level.
+    #[inline]
+    fn split(&self, range: &Range<usize>) -> (Range<usize>, Range<usize>) {
+        let start = self.rank1(range.start);
+        let end = self.rank1(range.end);
+        (
+            range.start - start..range.end - end,
+            self.zeros + start..self.zeros + end,
+        )
+    }
+}
+
+/// Wavelet matrix of an array of integers
+///
+/// Finds the largest value below a bound in a range of the array in `O(log σ)`,
+/// which is the nearest preceding occurrence when the array is a suffix array.
+#[derive(Default)]
+pub(super) struct WaveletMatrix {
+    /// The levels from the most significant bit
+    levels: Vec<Level>,
+    work: Vec<u32>,
+    next: Vec<u32>,
+    ones: Vec<u32>,
+}
+
+impl WaveletMatrix {
+    /// Rebuilds the matrix for `values`, which are less than their count, reusing the allocated buffers.
+    pub fn rebuild(&mut self, values: &[u32]) {
+        let len = values.len();
+        let depth = (usize::BITS - len.leading_zeros()) as usize;
+        self.levels.resize_with(depth, Level::default);
+        self.work.clear();
+        self.work.extend_from_slice(values);
+        self.next.resize(len, 0);
+        self.ones.resize(len, 0);
+
+        for (level, shift) in self.levels.iter_mut().zip((0..depth).rev()) {
+            // stable partition of the values by the bit, without branches on the bit
+            level.words.clear();
+            let mut zeros = 0;
+            let mut ones = 0;
+            for chunk in self.work.chunks(64) {
+                let mut bits = 0;
+                for (index, &value) in chunk.iter().enumerate() {
+                    let bit = (value >> shift) as usize & 1;
+                    bits |= (bit as u64) << index;
+                    self.next[zeros] = value;
+                    self.ones[ones] = value;
+                    zeros += 1 - bit;
+                    ones += bit;
+                }
+                level.words.push(RankedWord { bits, rank: 0 });
+            }
+            level.words.push(RankedWord::default());
+            level.zeros = zeros;
+            self.next[zeros..].copy_from_slice(&self.ones[..ones]);
+            core::mem::swap(&mut self.work, &mut self.next);
+
+            let mut acc = 0;
+            for word in level.words.iter_mut() {
+                word.rank = acc;
+                acc += word.bits.count_ones();
+            }
+        }
+    }
+
+    /// Returns the largest value less than `upper` in `range`.
+    pub fn prev_value(&self, range: Range<usize>, upper: u32) -> Option<u32> {
+        let depth = self.levels.len();
+        let target = (upper.checked_sub(1)? as u64).min((1 << depth) - 1) as u32;
+
+        // Follows the bits of `target`, remembering the deepest branch to a smaller value,
+        // whose largest value is the answer unless `target` itself is in the range.
+        let mut smaller = None;
+        let mut range = range;
+        let mut value = 0;
+        for (index, (level, shift)) in self.levels.iter().zip((0..depth).rev()).enumerate() {
+            if range.is_empty() {
+                break;
+            }
+            let (zeros, ones) = level.split(&range);
+            if (target >> shift) & 1 != 0 {
+                if !zeros.is_empty() {
+                    smaller = Some((index + 1, zeros, value));
+                }
+                range = ones;
+                value |= 1 << shift;
+            } else {
+                range = zeros;
+            }
+        }
+        if !range.is_empty() {
+            return Some(value);
+        }
+
+        let (index, mut range, mut value) = smaller?;
+        for (level, shift) in self.levels[index..].iter().zip((0..depth - index).rev()) {
+            let (zeros, ones) = level.split(&range);
+            if ones.is_empty() {
+                range = zeros;
+            } else {
+                range = ones;
+                value |= 1 << shift;
+            }
+        }
+        Some(value)
+    }
+}
@@ -0,0 +1,497 @@
+//! Outputs of LZ decoders
+
+use crate::*;
+use alloc::vec;
+
+/// Size of the window kept by [`LzOutputWindow`], which is that of deflate
+pub const LZ_OUTPUT_WINDOW_SIZE: usize = 0x8000;
+
+/// Output of an LZ decoder, to which the literals and the copies of the previous output are appended
+pub trait LzSink {
+    /// Returns `true` if no more output can be written.
+    fn is_eof(&self) -> bool;
+
+    /// Returns the number of bytes that can still be written.
+    fn remaining(&self) -> usize;
+
+    fn push_literal(&mut self, literal: u8) -> LzOutputBufferResult;
+
+    fn extend_from_slice(&mut self, data: &[u8]) -> LzOutputBufferResult;
+
+    /// Copies `copy_len` bytes from `distance` bytes before the current position.
+    ///
+    /// The source overlaps the destination if `distance` is less than `copy_len`,
+    /// and the copied bytes repeat with the period of `distance`.
+    fn copy_lz(&mut self, distance: usize, copy_len: usize) -> LzOutputBufferResult;
+}
+
+/// Output into a preallocated slice
+pub struct LzOutputBuffer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> LzOutputBuffer<'a> {
+    #[inline]
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Creates a buffer whose first `dictionary_len` bytes are already written,
+    /// so that the matches can refer to them.
+    #[inline]
+    pub fn with_dictionary(buffer: &'a mut [u8], dictionary_len: usize) -> Self {
+        let position = dictionary_len.min(buffer.len());
+        Self { buffer, position }
+    }
+
+    /// Returns the number of bytes written, including the dictionary.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl LzSink for LzOutputBuffer<'_> {
+    #[inline]
+    fn is_eof(&self) -> bool {
+        self.position >= self.buffer.len()
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.buffer.len().saturating_sub(self.position)
+    }
+
+    #[inline]
+    fn push_literal(&mut self, literal: u8) -> LzOutputBufferResult {
+        if self.position < self.buffer.len() {
+            self.buffer[self.position] = literal;
+            self.position += 1;
+            LzOutputBufferResult::Success
+        } else {
+            LzOutputBufferResult::Failure
+        }
+    }
+
+    fn extend_from_slice(&mut self, data: &[u8]) -> LzOutputBufferResult {
+        if self.position + data.len() <= self.buffer.len() {
+            self.buffer[self.position..self.position + data.len()].copy_from_slice(data);
+            self.position += data.len();
+            LzOutputBufferResult::Success
+        } else {
+            LzOutputBufferResult::Failure
+        }
+    }
+
+    /// The copy is truncated to the end of the buffer.
+    fn copy_lz(&mut self, distance: usize, copy_len: usize) -> LzOutputBufferResult {
+        if distance == 0 || distance > self.position {
+            return LzOutputBufferResult::Failure;
+        }
+        let copy_len = copy_len.min(self.buffer.len() - self.position);
+        _copy_lz(self.buffer, self.position, distance, copy_len);
+        self.position += copy_len;
+
+        LzOutputBufferResult::Success
+    }
+}
+
+/// Output appended to a vector, up to `max_len` bytes in total
+///
+/// The bytes already in the vector can be referred to by the matches.
+pub struct LzOutputVec<'a> {
+    buffer: &'a mut Vec<u8>,
+    max_len: usize,
+}
+
+impl<'a> LzOutputVec<'a> {
+    #[inline]
+    pub fn new(buffer: &'a mut Vec<u8>, max_len: usize) -> Self {
+        Self { buffer, max_len }
+    }
+
+    /// Returns the length of the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl LzSink for LzOutputVec<'_> {
+    #[inline]
+    fn is_eof(&self) -> bool {
+        self.buffer.len() >= self.max_len
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.max_len.saturating_sub(self.buffer.len())
+    }
+
+    #[inline]
+    fn push_literal(&mut self, literal: u8) -> LzOutputBufferResult {
+        if self.buffer.len() < self.max_len {
+            self.buffer.push(literal);
+            LzOutputBufferResult::Success
+        } else {
+            LzOutputBufferResult::Failure
+        }
+    }
+
+    fn extend_from_slice(&mut self, data: &[u8]) -> LzOutputBufferResult {
+        if self.buffer.len() + data.len() <= self.max_len {
+            self.buffer.extend_from_slice(data);
+            LzOutputBufferResult::Success
+        } else {
+            LzOutputBufferResult::Failure
+        }
+    }
+
+    /// The copy is truncated to `max_len`.
+    fn copy_lz(&mut self, distance: usize, copy_len: usize) -> LzOutputBufferResult {
+        let position = self.buffer.len();
+        if distance == 0 || distance > position {
+            return LzOutputBufferResult::Failure;
+        }
+        let copy_len = copy_len.min(self.max_len.saturating_sub(position));
+        if distance >= copy_len {
+            let start = position - distance;
+            self.buffer.extend_from_within(start..start + copy_len);
+        } else {
+            self.buffer.resize(position + copy_len, 0);
+            _copy_lz(self.buffer, position, distance, copy_len);
+        }
+
+        LzOutputBufferResult::Success
+    }
+}
+
+/// Output passed to a callback in chunks, keeping only the last [`LZ_OUTPUT_WINDOW_SIZE`] bytes for the matches
+///
+/// The buffer holds twice the window.
+/// When it is full, the output not passed yet is passed to the callback, and the last window is moved to the front,
+/// so the memory stays constant however long the output is.
+/// The output is unbounded, and the rest is passed by [`LzOutputWindow::finish`].
+///
+/// ```
+/// use compress::lz::{LzOutputWindow, LzSink};
+///
+/// let mut output = Vec::new();
+/// let mut window = LzOutputWindow::new(|chunk: &[u8]| output.extend_from_slice(chunk));
+/// let _ = window.extend_from_slice(b"abc");
+/// let _ = window.copy_lz(3, 0x10000);
+/// assert_eq!(window.total_len(), 0x10003);
+/// window.finish();
+/// assert_eq!(output.len(), 0x10003);
+/// assert_eq!(&output[0xfffe..0x10003], b"cabca");
+/// ```
+pub struct LzOutputWindow<F: FnMut(&[u8])> {
+    buffer: Vec<u8>,
+    position: usize,
+    /// Number of bytes in `buffer` that have already been passed to the callback
+    emitted: usize,
+    /// Number of bytes removed from the front of `buffer`
+    discarded: usize,
+    output: F,
+}
+
+impl<F: FnMut(&[u8])> LzOutputWindow<F> {
+    pub fn new(output: F) -> Self {
+        Self {
+            buffer: vec![0; LZ_OUTPUT_WINDOW_SIZE * 2],
+            position: 0,
+            emitted: 0,
+            discarded: 0,
+            output,
+        }
+    }
+
+    /// Returns the total number of bytes written.
+    #[inline]
+    pub const fn total_len(&self) -> usize {
+        self.discarded + self.position
+    }
+
+    /// Passes the output that has not been passed yet to the callback.
+    pub fn flush(&mut self) {
+        if self.position > self.emitted {
+            (self.output)(&self.buffer[self.emitted..self.position]);
+            self.emitted = self.position;
+        }
+    }
+
+    /// Passes the rest of the output to the callback.
+    #[inline]
+    pub fn finish(mut self) {
+        self.flush();
+    }
+
+    /// Returns the free space of the buffer, making room if it is full.
+    #[inline]
+    fn _reserve(&mut self) -> usize {
+        if self.position == self.buffer.len() {
+            self.flush();
+            let start = self.position - LZ_OUTPUT_WINDOW_SIZE;
+            self.buffer.copy_within(start.., 0);
+            self.discarded += start;
+            self.position = LZ_OUTPUT_WINDOW_SIZE;
+            self.emitted = LZ_OUTPUT_WINDOW_SIZE;
+        }
+        self.buffer.len() - self.position
+    }
+}
+
+impl<F: FnMut(&[u8])> LzSink for LzOutputWindow<F> {
+    #[inline]
+    fn is_eof(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        usize::MAX
+    }
+
+    #[inline]
+    fn push_literal(&mut self, literal: u8) -> LzOutputBufferResult {
+        self._reserve();
+        self.buffer[self.position] = literal;
+        self.position += 1;
+        LzOutputBufferResult::Success
+    }
+
+    fn extend_from_slice(&mut self, mut data: &[u8]) -> LzOutputBufferResult {
+        while !data.is_empty() {
+            let len = self._reserve().min(data.len());
+            self.buffer[self.position..self.position + len].copy_from_slice(&data[..len]);
+            self.position += len;
+            data = &data[len..];
+        }
+        LzOutputBufferResult::Success
+    }
+
+    /// Fails if `distance` exceeds [`LZ_OUTPUT_WINDOW_SIZE`].
+    fn copy_lz(&mut self, distance: usize, mut copy_len: usize) -> LzOutputBufferResult {
+        if distance == 0 || distance > self.position.min(LZ_OUTPUT_WINDOW_SIZE) {
+            return LzOutputBufferResult::Failure;
+        }
+        // A copy split at the end of the chunk continues after the window has moved,
+        // which still contains the source.
+        while copy_len > 0 {
+            let len = self._reserve().min(copy_len);
+            _copy_lz(&mut self.buffer, self.position, distance, len);
+            self.position += len;
+            copy_len -= len;
+        }
+        LzOutputBufferResult::Success
+    }
+}
+
+/// Copies `copy_len` bytes from `distance` bytes before `dest` in the buffer.
+///
+/// `distance` must be in `1..=dest`, and the copy must fit in the buffer.
+#[inline]
+fn _copy_lz(buffer: &mut [u8], dest: usize, distance: usize, copy_len: usize) {
+    let src = dest - distance;
+    if distance == 1 {
+        let byte = buffer[src];
+        buffer[dest..dest + copy_len].fill(byte);
+    } else if distance >= 8 && (8..=32).contains(&copy_len) {
+        // Each word is read after the bytes it overlaps are written,
+        // and the last one ends at the end of the copy, writing some bytes again with the same values.
+        for offset in (0..copy_len - 8).step_by(8).chain([copy_len - 8]) {
+            let word: [u8; 8] = buffer[src + offset..][..8].try_into().unwrap();
+            buffer[dest + offset..][..8].copy_from_slice(&word);
+        }
+    } else if distance >= copy_len {
+        buffer.copy_within(src..src + copy_len, dest);
+    } else if distance < 8 {
+        // The pattern doubles with each copy, whose offset stays a multiple of the period.
+        let mut copied = 0;
+        while copied < copy_len {
+            let len = (copied + distance).min(copy_len - copied);
+            buffer.copy_within(src..src + len, dest + copied);
+            copied += len;
+        }
+    } else {
+        // Each chunk of `distance` bytes is written after its source.
+        for offset in (0..copy_len).step_by(distance) {
+            let len = distance.min(copy_len - offset);
+            buffer.copy_within(src + offset..src + offset + len, dest + offset);
+        }
+    }
+}
+
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LzOutputBufferResult {
+    Success,
+    Failure,
+}
+
+impl LzOutputBufferResult {
+    #[inline]
+    pub fn ok_or<E>(self, e: E) -> Result<(), E> {
+        match self {
+            LzOutputBufferResult::Success => Ok(()),
+            LzOutputBufferResult::Failure => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lz::lzss::{Configuration, LZSS};
+
+    #[test]
+    fn copy_lz() {
+        let prefix = random_alphabet(0, 255, 20);
+        for distance in 1..=20 {
+            for copy_len in [0, 1, 2, 3, 7, 8, 9, 15, 16, 17, 40, 100, 258] {
+                let mut expected = prefix.clone();
+                for _ in 0..copy_len {
+                    expected.push(expected[expected.len() - distance]);
+                }
+
+                let mut buffer = prefix.clone();
+                buffer.resize(prefix.len() + copy_len, 0);
+                let mut output = LzOutputBuffer::with_dictionary(&mut buffer, prefix.len());
+                assert_eq!(
+                    output.copy_lz(distance, copy_len),
+                    LzOutputBufferResult::Success
+                );
+                assert!(output.is_eof());
+                assert_eq!(buffer, expected, "{distance} {copy_len}");
+
+                // truncated at the end of the buffer
+                let mut buffer = prefix.clone();
+                buffer.resize(prefix.len() + copy_len / 2, 0);
+                let mut output = LzOutputBuffer::with_dictionary(&mut buffer, prefix.len());
+                assert_eq!(
+                    output.copy_lz(distance, copy_len),
+                    LzOutputBufferResult::Success
+                );
+                assert_eq!(buffer, expected[..buffer.len()], "{distance} {copy_len}");
+
+                // the bytes after the copy are left as they are
+                let mut buffer = prefix.clone();
+                buffer.resize(prefix.len() + copy_len + 16, 0xaa);
+                let mut output = LzOutputBuffer::with_dictionary(&mut buffer, prefix.len());
+                assert_eq!(
+                    output.copy_lz(distance, copy_len),
+                    LzOutputBufferResult::Success
+                );
+                assert_eq!(buffer[..expected.len()], expected);
+                assert!(buffer[expected.len()..].iter().all(|&v| v == 0xaa));
+            }
+        }
+
+        let mut buffer = [0; 8];
+        let mut output = LzOutputBuffer::with_dictionary(&mut buffer, 2);
+        assert_eq!(output.copy_lz(3, 1), LzOutputBufferResult::Failure);
+        assert_eq!(output.copy_lz(0, 1), LzOutputBufferResult::Failure);
+    }
+
+    #[test]
+    fn sinks() {
+        let mut input = random_alphabet(0, 255, 0x9000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x12345));
+        input.extend_from_slice(&input.clone()[0x8000..0x10000]);
+        let mut tokens = Vec::new();
+        LZSS::encode(
+            &input,
+            Configuration::new(LZ_OUTPUT_WINDOW_SIZE, 258),
+            |lzss| {
+                tokens.push(lzss);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        fn _decode(sink: &mut impl LzSink, tokens: &[LZSS]) {
+            for (index, token) in tokens.iter().enumerate() {
+                match *token {
+                    // some literals are written as slices like stored blocks
+                    LZSS::Literal(literal) if index % 3 == 0 => {
+                        assert_eq!(
+                            sink.extend_from_slice(&[literal]),
+                            LzOutputBufferResult::Success
+                        );
+                    }
+                    LZSS::Literal(literal) => {
+                        assert_eq!(sink.push_literal(literal), LzOutputBufferResult::Success);
+                    }
+                    LZSS::Match(m) => {
+                        assert_eq!(
+                            sink.copy_lz(m.distance.get(), m.len.get()),
+                            LzOutputBufferResult::Success
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut buffer = vec![0; input.len()];
+        let mut output = LzOutputBuffer::new(&mut buffer);
+        _decode(&mut output, &tokens);
+        assert!(output.is_eof());
+        assert!(buffer == input);
+
+        let mut buffer = Vec::new();
+        let mut output = LzOutputVec::new(&mut buffer, usize::MAX);
+        _decode(&mut output, &tokens);
+        assert!(!output.is_eof());
+        assert!(buffer == input);
+
+        let mut chunks = Vec::new();
+        let mut output = LzOutputWindow::new(|chunk: &[u8]| chunks.push(chunk.to_vec()));
+        _decode(&mut output, &tokens);
+        assert_eq!(output.total_len(), input.len());
+        output.finish();
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.len() <= LZ_OUTPUT_WINDOW_SIZE * 2)
+        );
+        assert!(chunks.concat() == input);
+
+        // a stored block longer than the chunk
+        let mut output = Vec::new();
+        let mut window = LzOutputWindow::new(|chunk: &[u8]| output.extend_from_slice(chunk));
+        let _ = window.push_literal(input[0]);
+        let _ = window.extend_from_slice(&input[1..]);
+        window.finish();
+        assert!(output == input);
+
+        // the matches are limited to the window
+        let mut window = LzOutputWindow::new(|_: &[u8]| {});
+        let _ = window.extend_from_slice(&input);
+        assert_eq!(
+            window.copy_lz(LZ_OUTPUT_WINDOW_SIZE, 1),
+            LzOutputBufferResult::Success
+        );
+        assert_eq!(
+            window.copy_lz(LZ_OUTPUT_WINDOW_SIZE + 1, 1),
+            LzOutputBufferResult::Failure
+        );
+
+        // truncated at `max_len`
+        let mut buffer = b"abc".to_vec();
+        let mut output = LzOutputVec::new(&mut buffer, 8);
+        assert_eq!(output.copy_lz(4, 1), LzOutputBufferResult::Failure);
+        assert_eq!(output.copy_lz(3, 10), LzOutputBufferResult::Success);
+        assert!(output.is_eof());
+        assert_eq!(output.push_literal(b'x'), LzOutputBufferResult::Failure);
+        assert_eq!(buffer, b"abcabcab");
+    }
+}
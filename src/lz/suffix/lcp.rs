@@ -1,18 +1,22 @@
 //! Longest Common Prefix array
 
 #[allow(unused_imports)]
-use super::sais::SuffixArray;
+use super::sais::{SuffixArray, Symbol};
 use crate::*;
 
 /// Longest Common Prefix array
 pub struct LcpArray;
 
 impl LcpArray {
-    /// Creates a new LCP array using the Kasai's algorithm.
-    pub fn new(s: &[u8], sa: &[u32], rev_sa: &[u32]) -> Vec<u32> {
+    /// Creates a new LCP array in the buffer using the Kasai's algorithm, reusing its allocation.
+    ///
+    /// `rev_sa` is the inverse of the suffix array `sa`, the rank of each suffix.
+    /// Each element is the length of the common prefix of the suffix and the next suffix in `sa`,
+    /// and the last element is zero.
+    pub fn build_into<T: PartialEq>(s: &[T], sa: &[u32], rev_sa: &[u32], lcp: &mut Vec<u32>) {
         let n = s.len();
         let mut k = 0usize;
-        let mut lcp = Vec::new();
+        lcp.clear();
         lcp.resize(n, 0u32);
 
         for (i, &rank) in rev_sa.iter().enumerate() {
@@ -30,7 +34,6 @@ impl LcpArray {
                 k -= 1;
             }
         }
-        lcp
     }
 }
 
@@ -43,7 +46,7 @@ pub struct LcpArrayNaive {
 
 #[cfg(test)]
 impl LcpArrayNaive {
-    pub fn new(s: &[u8]) -> Self {
+    pub fn new<T: Symbol>(s: &[T]) -> Self {
         let sa = SuffixArray::naive(s);
 
         let mut lcp = Vec::with_capacity(s.len());
@@ -0,0 +1,303 @@
+//! Suffix Array
+
+use alloc::{vec, vec::Vec};
+
+/// Symbol of the input of [`SuffixArray`]
+///
+/// The buckets of the induced sorting are indexed by the symbols,
+/// so the memory used is proportional to the largest symbol in the input.
+pub trait Symbol: Copy + Ord {
+    /// Returns the index of the bucket of the symbol.
+    fn index(self) -> usize;
+}
+
+macro_rules! impl_symbol {
+    ($($ty:ty),*) => {
+        $(
+            impl Symbol for $ty {
+                #[inline]
+                fn index(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_symbol!(u8, u16, u32);
+
+/// Suffix Array
+pub struct SuffixArray {
+    inner: Vec<u32>,
+    offset: usize,
+}
+
+impl SuffixArray {
+    /// Creates a Suffix Array using the SA-IS algorithm.
+    ///
+    /// Besides the array of 4 bytes per symbol, the construction uses one bit per symbol
+    /// and the buckets of the symbols and of the names of the recursion.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input length is greater than `i32::MAX`.
+    pub fn new<T: Symbol>(source: &[T]) -> Self {
+        let mut sa = Self {
+            inner: Vec::new(),
+            offset: 1,
+        };
+        sa.rebuild(source);
+        sa
+    }
+
+    /// Recreates the Suffix Array for another input, reusing the allocated buffers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input length is greater than `i32::MAX`.
+    pub fn rebuild<T: Symbol>(&mut self, source: &[T]) {
+        assert!(source.len() < i32::MAX as usize);
+        let n = source.len() + 1;
+        let alphabet_max = source.iter().map(|v| v.index()).max().unwrap_or_default();
+
+        let sa = &mut self.inner;
+        sa.clear();
+        sa.resize(n, EMPTY);
+        let mut types = TypeFlags::default();
+        sa_is(source, sa, alphabet_max, &mut types);
+        self.offset = 1;
+    }
+
+    #[cfg(test)]
+    /// naive implementation for testing purposes
+    pub fn naive<T: Symbol>(input: &[T]) -> Self {
+        let mut sa = (0..input.len() as u32).collect::<Vec<_>>();
+        sa.sort_by(|&a, &b| input[(a as usize)..].cmp(&input[(b as usize)..]));
+        Self {
+            inner: sa,
+            offset: 0,
+        }
+    }
+
+    /// Returns the suffix array.
+    #[inline]
+    pub fn as_slice(&self) -> &[u32] {
+        &self.inner[self.offset..]
+    }
+}
+
+/// Empty slot of the suffix array during the induced sorting
+const EMPTY: u32 = u32::MAX;
+
+/// Suffix Array Induced Sorting (SA-IS) algorithm.
+///
+/// `sa` has one more slot than `s`, the first of which is the virtual sentinel at the end of `s`.
+/// Besides `sa`, only the type flags of one bit per symbol and the buckets are allocated.
+/// The reduced problem of the recursion is solved in the first half of `sa`, taking its input from the second half.
+#[inline(never)]
+fn sa_is<T: Symbol>(s: &[T], sa: &mut [u32], alphabet_max: usize, types: &mut TypeFlags) {
+    let n = s.len();
+    debug_assert_eq!(sa.len(), n + 1);
+    sa.fill(EMPTY);
+    sa[0] = n as u32;
+    if n == 0 {
+        return;
+    }
+
+    types.classify(s);
+
+    let mut counts = vec![0u32; alphabet_max + 1];
+    for &symbol in s {
+        counts[symbol.index()] += 1;
+    }
+    let mut buckets = Vec::with_capacity(counts.len());
+
+    // phase-1: sort the LMS substrings
+    _bucket_tails(&counts, &mut buckets);
+    for i in (1..n).filter(|&i| types.is_lms(i)) {
+        _push_tail(sa, &mut buckets, s[i].index(), i as u32);
+    }
+    _induce(s, sa, types, &counts, &mut buckets);
+
+    // phase-2: name the LMS substrings, and sort the LMS suffixes recursively if the names are not unique
+    let mut m = 0;
+    for i in 0..=n {
+        let suffix = sa[i];
+        if types.is_lms(suffix as usize) {
+            sa[m] = suffix;
+            m += 1;
+        }
+    }
+    sa[m..].fill(EMPTY);
+
+    // the LMS positions are at least two apart, so the names are stored at the halves of the positions in text order
+    let mut name = 0;
+    for k in 1..m {
+        let (prev, suffix) = (sa[k - 1] as usize, sa[k] as usize);
+        if !types.lms_substr_eq(s, prev, suffix) {
+            name += 1;
+        }
+        sa[m + suffix / 2] = name;
+    }
+    // the sentinel is the last LMS suffix in text order, and is not a part of the reduced string
+    let reduced_len = m - 1;
+    let mut j = n + 1;
+    for i in (m..=n).rev() {
+        if sa[i] != EMPTY {
+            j -= 1;
+            sa[j] = sa[i];
+        }
+    }
+    debug_assert_eq!(j, n + 1 - reduced_len);
+
+    {
+        let (sa1, s1) = sa.split_at_mut(n + 1 - reduced_len);
+        let sa1 = &mut sa1[..m];
+        if (name as usize) < reduced_len {
+            let mut types = TypeFlags::default();
+            sa_is(&s1[..], sa1, name as usize, &mut types);
+        } else {
+            sa1[0] = reduced_len as u32;
+            for (i, &name) in s1.iter().enumerate() {
+                sa1[name as usize] = i as u32;
+            }
+        }
+
+        // replace the reduced string with the LMS positions in text order
+        for (slot, i) in s1.iter_mut().zip((1..n).filter(|&i| types.is_lms(i))) {
+            *slot = i as u32;
+        }
+        sa1[0] = n as u32;
+        for suffix in sa1[1..].iter_mut() {
+            *suffix = s1[*suffix as usize];
+        }
+    }
+
+    // phase-3: induce the suffix array from the sorted LMS suffixes
+    sa[m..].fill(EMPTY);
+    _bucket_tails(&counts, &mut buckets);
+    for k in (1..m).rev() {
+        let suffix = sa[k];
+        sa[k] = EMPTY;
+        // the slot is never before `k`, since the LMS suffixes keep their order
+        _push_tail(sa, &mut buckets, s[suffix as usize].index(), suffix);
+    }
+    _induce(s, sa, types, &counts, &mut buckets);
+}
+
+/// Sorts the L-type suffixes from the LMS suffixes, and then the S-type suffixes from the L-type suffixes.
+fn _induce<T: Symbol>(
+    s: &[T],
+    sa: &mut [u32],
+    types: &TypeFlags,
+    counts: &[u32],
+    buckets: &mut Vec<u32>,
+) {
+    _bucket_heads(counts, buckets);
+    for i in 0..sa.len() {
+        let suffix = sa[i];
+        if suffix != EMPTY && suffix > 0 && !types.is_s(suffix as usize - 1) {
+            let index = suffix as usize - 1;
+            let bucket = &mut buckets[s[index].index()];
+            sa[*bucket as usize] = index as u32;
+            *bucket += 1;
+        }
+    }
+
+    _bucket_tails(counts, buckets);
+    for i in (0..sa.len()).rev() {
+        let suffix = sa[i];
+        if suffix != EMPTY && suffix > 0 && types.is_s(suffix as usize - 1) {
+            let index = suffix as usize - 1;
+            _push_tail(sa, buckets, s[index].index(), index as u32);
+        }
+    }
+}
+
+/// Sets the buckets to their first slots, after the slot of the sentinel.
+fn _bucket_heads(counts: &[u32], buckets: &mut Vec<u32>) {
+    buckets.clear();
+    let mut acc = 1;
+    for &count in counts {
+        buckets.push(acc);
+        acc += count;
+    }
+}
+
+/// Sets the buckets to the slots after their last ones.
+fn _bucket_tails(counts: &[u32], buckets: &mut Vec<u32>) {
+    buckets.clear();
+    let mut acc = 1;
+    for &count in counts {
+        acc += count;
+        buckets.push(acc);
+    }
+}
+
+#[inline]
+fn _push_tail(sa: &mut [u32], buckets: &mut [u32], symbol: usize, suffix: u32) {
+    let bucket = &mut buckets[symbol];
+    *bucket -= 1;
+    sa[*bucket as usize] = suffix;
+}
+
+/// Types of the suffixes packed in bits, set for S-type and clear for L-type
+#[derive(Default)]
+struct TypeFlags {
+    bits: Vec<u64>,
+}
+
+impl TypeFlags {
+    /// Classifies the suffixes of `s` and the sentinel, which is S-type and smaller than any symbol.
+    fn classify<T: Symbol>(&mut self, s: &[T]) {
+        let n = s.len();
+        self.bits.clear();
+        self.bits.resize(n / 64 + 1, 0);
+        self._set(n);
+        let mut is_s = false;
+        for i in (0..n.saturating_sub(1)).rev() {
+            is_s = s[i] < s[i + 1] || (s[i] == s[i + 1] && is_s);
+            if is_s {
+                self._set(i);
+            }
+        }
+    }
+
+    #[inline]
+    fn _set(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    #[inline]
+    fn is_s(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns whether the suffix is S-type and the previous one is L-type, including the sentinel.
+    #[inline]
+    fn is_lms(&self, index: usize) -> bool {
+        index > 0 && index != EMPTY as usize && self.is_s(index) && !self.is_s(index - 1)
+    }
+
+    /// Returns whether the LMS substrings, from the LMS positions up to the next ones inclusive, are equal.
+    fn lms_substr_eq<T: Symbol>(&self, s: &[T], lhs: usize, rhs: usize) -> bool {
+        let n = s.len();
+        if lhs == n || rhs == n {
+            return false;
+        }
+        for k in 0.. {
+            let (i, j) = (lhs + k, rhs + k);
+            if i == n || j == n || s[i] != s[j] || self.is_s(i) != self.is_s(j) {
+                return false;
+            }
+            if k > 0 {
+                match (self.is_lms(i), self.is_lms(j)) {
+                    (true, true) => return true,
+                    (false, false) => {}
+                    _ => return false,
+                }
+            }
+        }
+        unreachable!()
+    }
+}
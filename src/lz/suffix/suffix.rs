@@ -0,0 +1,113 @@
+//! Suffix Array and Longest Common Prefix array
+//!
+//! These are shared by the [`match_finder`](super::match_finder) of the LZ encoders
+//! and the Burrows–Wheeler transform of [`bwt`](crate::bwt) and [`bzip2`](crate::bzip2).
+//!
+//! ```
+//! use compress::lz::suffix::{LcpArray, SuffixArray};
+//!
+//! let s = b"banana";
+//! let sa = SuffixArray::new(s);
+//! assert_eq!(sa.as_slice(), &[5, 3, 1, 0, 4, 2]);
+//!
+//! let mut rev_sa = vec![0; s.len()];
+//! for (rank, &suffix) in sa.as_slice().iter().enumerate() {
+//!     rev_sa[suffix as usize] = rank as u32;
+//! }
+//! let mut lcp = Vec::new();
+//! LcpArray::build_into(s, sa.as_slice(), &rev_sa, &mut lcp);
+//! assert_eq!(lcp, [1, 3, 0, 0, 2, 0]);
+//! ```
+
+mod lcp;
+mod sais;
+
+pub use lcp::LcpArray;
+pub use sais::{SuffixArray, Symbol};
+
+#[cfg(test)]
+pub(crate) use lcp::LcpArrayNaive;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn suffix_array_symbols() {
+        let bytes = fib_str(b'a', b'b', 0x1000);
+        let words = bytes
+            .chunks(2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SuffixArray::new(&words).as_slice(),
+            SuffixArray::naive(&words).as_slice()
+        );
+
+        let dwords = random_alphabet(0, 255, 0x1000)
+            .iter()
+            .map(|&v| (v as u32 % 7) * 10_000 + 3)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SuffixArray::new(&dwords).as_slice(),
+            SuffixArray::naive(&dwords).as_slice()
+        );
+
+        // the same order as the bytes if the symbols are widened
+        let widened = bytes.iter().map(|&v| v as u32).collect::<Vec<_>>();
+        assert_eq!(
+            SuffixArray::new(&widened).as_slice(),
+            SuffixArray::new(&bytes).as_slice()
+        );
+        assert_eq!(SuffixArray::new::<u16>(&[]).as_slice(), &[]);
+        assert_eq!(SuffixArray::new(&[u32::MAX >> 12]).as_slice(), &[0]);
+    }
+
+    #[test]
+    fn suffix_array_small() {
+        // every string of up to 8 symbols over a binary and a ternary alphabet, with deep recursions
+        for len in 0..=8u32 {
+            for alphabet in [2u32, 3] {
+                for code in 0..alphabet.pow(len) {
+                    let s = (0..len)
+                        .map(|i| (code / alphabet.pow(i) % alphabet) as u8)
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        SuffixArray::new(&s).as_slice(),
+                        SuffixArray::naive(&s).as_slice(),
+                        "{s:?}"
+                    );
+                }
+            }
+        }
+
+        let mut sa = SuffixArray::new(b"x");
+        for s in [
+            vec![0; 1000],
+            b"ab".repeat(500),
+            b"aab".repeat(333),
+            fib_str(b'b', b'a', 0x1234),
+            random_alphabet(0, 255, 0x1001),
+        ] {
+            sa.rebuild(&s);
+            assert_eq!(sa.as_slice(), SuffixArray::naive(&s).as_slice());
+        }
+    }
+
+    #[test]
+    fn lcp_symbols() {
+        let words = fib_str(b'a', b'b', 0x800)
+            .iter()
+            .map(|&v| v as u16 * 0x101)
+            .collect::<Vec<_>>();
+        let sa = SuffixArray::new(&words);
+        let mut rev_sa = vec![0; words.len()];
+        for (rank, &suffix) in sa.as_slice().iter().enumerate() {
+            rev_sa[suffix as usize] = rank as u32;
+        }
+        let mut lcp = Vec::new();
+        LcpArray::build_into(&words, sa.as_slice(), &rev_sa, &mut lcp);
+        assert_eq!(lcp, LcpArrayNaive::new(&words).lcp());
+    }
+}
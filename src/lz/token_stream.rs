@@ -0,0 +1,267 @@
+//! Sequence of LZSS tokens
+
+use super::lzss::{self, LZSS};
+use crate::*;
+use core::slice;
+
+/// Sequence of LZSS tokens produced by the parsers of [`LZSS`]
+///
+/// The tokens are independent of any output format,
+/// so a custom entropy stage can be built on top of the LZ parsing of this crate.
+#[derive(Debug, Clone, Default)]
+pub struct TokenStream {
+    tokens: Vec<LZSS>,
+}
+
+/// Summary of a [`TokenStream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TokenStats {
+    /// Number of literals
+    pub literals: usize,
+    /// Number of matches
+    pub matches: usize,
+    /// Total number of bytes covered by the matches
+    pub matched_len: usize,
+    /// Length of the longest match
+    pub max_len: usize,
+    /// Longest distance of the matches
+    pub max_distance: usize,
+}
+
+impl TokenStats {
+    /// Returns the number of input bytes represented by the tokens.
+    #[inline]
+    pub const fn input_len(&self) -> usize {
+        self.literals + self.matched_len
+    }
+
+    /// Returns the average length of the matches, or `0.0` if there are no matches.
+    #[inline]
+    pub fn average_len(&self) -> f64 {
+        if self.matches > 0 {
+            self.matched_len as f64 / self.matches as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl TokenStream {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { tokens: Vec::new() }
+    }
+
+    /// Parses the input with [`LZSS::encode`].
+    #[inline]
+    pub fn collect(input: &[u8], config: lzss::Configuration) -> Result<Self, EncodeError> {
+        Self::collect_with(|f| LZSS::encode(input, config, f))
+    }
+
+    /// Collects the tokens emitted by any of the parsers.
+    ///
+    /// ```
+    /// use compress::lz::{TokenStream, lzss::{Configuration, LZSS}};
+    ///
+    /// let input = b"abcabcabcabc";
+    /// let tokens = TokenStream::collect_with(|f| LZSS::encode_fast(input, Configuration::DEFAULT, f)).unwrap();
+    /// assert_eq!(tokens.decode().unwrap(), input);
+    /// ```
+    pub fn collect_with<E>(encoder: E) -> Result<Self, EncodeError>
+    where
+        E: FnOnce(&mut dyn FnMut(LZSS) -> Result<(), EncodeError>) -> Result<(), EncodeError>,
+    {
+        let mut tokens = Vec::new();
+        encoder(&mut |token| {
+            tokens.push(token);
+            Ok(())
+        })?;
+        Ok(Self { tokens })
+    }
+
+    #[inline]
+    pub fn push(&mut self, token: LZSS) {
+        self.tokens.push(token);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[LZSS] {
+        &self.tokens
+    }
+
+    #[inline]
+    pub fn into_vec(self) -> Vec<LZSS> {
+        self.tokens
+    }
+
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, LZSS> {
+        self.tokens.iter()
+    }
+
+    /// Returns an iterator over the tokens with their positions in the input.
+    pub fn inspect(&self) -> impl Iterator<Item = (usize, LZSS)> + '_ {
+        self.tokens.iter().scan(0, |position, &token| {
+            let current = *position;
+            *position += _input_len_of(token);
+            Some((current, token))
+        })
+    }
+
+    /// Returns the summary of the tokens.
+    pub fn stats(&self) -> TokenStats {
+        let mut stats = TokenStats::default();
+        for token in self.tokens.iter() {
+            match token {
+                LZSS::Literal(_) => stats.literals += 1,
+                LZSS::Match(matches) => {
+                    stats.matches += 1;
+                    stats.matched_len += matches.len.get();
+                    stats.max_len = stats.max_len.max(matches.len.get());
+                    stats.max_distance = stats.max_distance.max(matches.distance.get());
+                }
+            }
+        }
+        stats
+    }
+
+    /// Reconstructs the input from the tokens.
+    ///
+    /// Returns `DecodeError::InvalidData` if a match refers to a position before the start,
+    /// such as the tokens of an input with a preset dictionary.
+    pub fn decode(&self) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::new();
+        for token in self.tokens.iter() {
+            match *token {
+                LZSS::Literal(literal) => output.push(literal),
+                LZSS::Match(matches) => {
+                    let distance = matches.distance.get();
+                    if distance > output.len() {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    for _ in 0..matches.len.get() {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[inline]
+fn _input_len_of(token: LZSS) -> usize {
+    match token {
+        LZSS::Literal(_) => 1,
+        LZSS::Match(matches) => matches.len.get(),
+    }
+}
+
+impl From<Vec<LZSS>> for TokenStream {
+    #[inline]
+    fn from(tokens: Vec<LZSS>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl FromIterator<LZSS> for TokenStream {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = LZSS>>(iter: T) -> Self {
+        Self {
+            tokens: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<LZSS> for TokenStream {
+    #[inline]
+    fn extend<T: IntoIterator<Item = LZSS>>(&mut self, iter: T) {
+        self.tokens.extend(iter);
+    }
+}
+
+impl IntoIterator for TokenStream {
+    type Item = LZSS;
+    type IntoIter = alloc::vec::IntoIter<LZSS>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TokenStream {
+    type Item = &'a LZSS;
+    type IntoIter = slice::Iter<'a, LZSS>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::num::NonZero;
+    use lzss::Configuration;
+
+    #[test]
+    fn token_stream() {
+        let mut input = random_alphabet(b'a', b'd', 0x1000);
+        input.extend_from_slice(&fib_str(b'a', b'b', 0x1000));
+
+        let tokens = TokenStream::collect(&input, Configuration::DEFAULT).unwrap();
+        assert_eq!(tokens.decode().unwrap(), input);
+        let fast =
+            TokenStream::collect_with(|f| LZSS::encode_fast(&input, Configuration::DEFAULT, f))
+                .unwrap();
+        assert_eq!(fast.decode().unwrap(), input);
+
+        let stats = tokens.stats();
+        assert_eq!(stats.input_len(), input.len());
+        assert_eq!(stats.literals + stats.matches, tokens.len());
+        assert!(stats.matches > 0 && stats.average_len() >= LZSS::MIN_LEN as f64);
+        assert!(stats.max_len <= LZSS::MAX_LEN && stats.max_distance < input.len());
+
+        let mut expected = 0;
+        for (position, token) in tokens.inspect() {
+            assert_eq!(position, expected);
+            if let LZSS::Match(matches) = token {
+                let (len, distance) = (matches.len.get(), matches.distance.get());
+                assert_eq!(
+                    input[position..position + len],
+                    input[position - distance..position - distance + len]
+                );
+            }
+            expected += _input_len_of(token);
+        }
+        assert_eq!(expected, input.len());
+
+        let copied = tokens.iter().copied().collect::<TokenStream>();
+        assert_eq!(copied.as_slice().len(), tokens.len());
+        assert_eq!(copied.stats(), stats);
+
+        let empty = TokenStream::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.stats(), TokenStats::default());
+        assert_eq!(empty.decode().unwrap(), b"");
+
+        // a match before the start
+        let dictionary = TokenStream::from(vec![LZSS::Match(lz::Match::new(
+            NonZero::new(3).unwrap(),
+            NonZero::new(1).unwrap(),
+        ))]);
+        assert_eq!(dictionary.decode(), Err(DecodeError::InvalidData));
+    }
+}
@@ -0,0 +1,213 @@
+//! Sliding window size for LZ-based encoders
+
+use super::cache::{OffsetCache, OffsetCache4};
+use crate::num::math;
+use core::fmt;
+
+/// Size of the sliding window (dictionary) used by LZ-based encoders
+///
+/// The size is always a power of two in the range [`WindowSize::MIN`] to [`WindowSize::MAX`].
+/// Each format clamps it to its own legal range (e.g. 32KB for deflate).
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WindowSize(u8);
+
+impl WindowSize {
+    /// Minimum window size in bits (256 bytes)
+    pub const MIN_BITS: u8 = 8;
+
+    /// Maximum window size in bits (1MB, same as `LZSS::MAX_DISTANCE`)
+    pub const MAX_BITS: u8 = 20;
+
+    /// 256 bytes
+    pub const MIN: Self = Self(Self::MIN_BITS);
+
+    /// 32KB, the maximum size of deflate
+    pub const SIZE_32K: Self = Self(15);
+
+    /// 128KB, the maximum size of stk1
+    pub const SIZE_128K: Self = Self(17);
+
+    /// 1MB
+    pub const MAX: Self = Self(Self::MAX_BITS);
+
+    /// Creates a window size of `1 << bits` bytes.
+    #[inline]
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        if bits >= Self::MIN_BITS && bits <= Self::MAX_BITS {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Creates a window size from a size in bytes.
+    ///
+    /// Returns `None` if `size` is not a power of two or is out of range.
+    #[inline]
+    pub const fn new(size: usize) -> Option<Self> {
+        if size.is_power_of_two() {
+            Self::from_bits(size.trailing_zeros() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the smallest window size that covers `size` bytes.
+    #[inline]
+    pub const fn preferred_for(size: usize) -> Self {
+        if size <= Self::MIN.value() {
+            Self::MIN
+        } else if size >= Self::MAX.value() {
+            Self::MAX
+        } else {
+            Self(size.next_power_of_two().trailing_zeros() as u8)
+        }
+    }
+
+    /// Returns the smallest window size that covers most of the matches observed in the input.
+    ///
+    /// The first `2 * max` bytes are sampled, and the distance of the nearest 4-byte match at each position is collected.
+    /// Data that only has local redundancy gets a small window, which reduces the memory required by the decoder.
+    /// The result never exceeds `max` or [`WindowSize::preferred_for`] the input length.
+    pub fn preferred_for_content(input: &[u8], max: Self) -> Self {
+        /// Ratio of observed matches that the window must cover
+        const COVERAGE: f64 = 0.99;
+
+        let upper = Self::preferred_for(input.len()).min(max);
+        let sample = &input[..input.len().min(max.value() * 2)];
+
+        let mut dist_hist = [0usize; Self::MAX_BITS as usize + 1];
+        let mut cache = OffsetCache4::new(sample, max.value());
+        for _ in 0..sample.len() {
+            if let Some(distance) = cache.nearest() {
+                dist_hist[Self::preferred_for(distance).bits() as usize] += 1;
+            }
+            cache.advance(1);
+        }
+
+        let total = dist_hist.iter().sum::<usize>();
+        if total == 0 {
+            return upper;
+        }
+        let required = math::ceil(total as f64 * COVERAGE) as usize;
+        let mut acc = 0;
+        for (bits, &count) in dist_hist.iter().enumerate() {
+            acc += count;
+            if acc >= required {
+                return Self::from_bits(bits as u8).unwrap_or(Self::MIN).min(upper);
+            }
+        }
+        upper
+    }
+
+    /// Returns the window size in bits.
+    #[inline]
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns the window size in bytes.
+    #[inline]
+    pub const fn value(&self) -> usize {
+        1 << self.0
+    }
+
+    /// Restricts the window size to the range `min..=max`.
+    #[inline]
+    pub const fn clamp(self, min: Self, max: Self) -> Self {
+        if self.0 < min.0 {
+            min
+        } else if self.0 > max.0 {
+            max
+        } else {
+            self
+        }
+    }
+
+    #[inline]
+    pub const fn min(self, other: Self) -> Self {
+        if self.0 < other.0 { self } else { other }
+    }
+}
+
+impl Default for WindowSize {
+    #[inline]
+    fn default() -> Self {
+        Self::SIZE_32K
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn window_size() {
+        assert_eq!(WindowSize::MIN.value(), 256);
+        assert_eq!(WindowSize::SIZE_32K.value(), 0x8000);
+        assert_eq!(WindowSize::MAX.value(), crate::lz::lzss::LZSS::MAX_DISTANCE);
+
+        assert_eq!(WindowSize::new(0x1000).unwrap().bits(), 12);
+        assert!(WindowSize::new(0x1001).is_none());
+        assert!(WindowSize::new(128).is_none());
+        assert!(WindowSize::new(0x20_0000).is_none());
+
+        for (size, expected) in [
+            (0usize, 256usize),
+            (1, 256),
+            (256, 256),
+            (257, 512),
+            (0x8000, 0x8000),
+            (0x8001, 0x10000),
+            (0x10_0000, 0x10_0000),
+            (usize::MAX, 0x10_0000),
+        ] {
+            assert_eq!(WindowSize::preferred_for(size).value(), expected);
+        }
+
+        assert_eq!(
+            WindowSize::MAX.clamp(WindowSize::MIN, WindowSize::SIZE_32K),
+            WindowSize::SIZE_32K
+        );
+    }
+
+    #[test]
+    fn preferred_for_content() {
+        // short-range repetition only
+        let mut input = Vec::new();
+        for i in 0..0x100 {
+            let chunk = random_alphabet(0, 255, 0x40 + i % 7);
+            input.extend_from_slice(&chunk);
+            input.extend_from_slice(&chunk);
+        }
+        let window_size = WindowSize::preferred_for_content(&input, WindowSize::SIZE_32K);
+        assert!(window_size.value() <= 0x100, "{}", window_size);
+
+        // long-range repetition
+        let chunk = random_alphabet(0, 255, 0x6000);
+        let mut input = chunk.clone();
+        input.extend_from_slice(&chunk);
+        assert_eq!(
+            WindowSize::preferred_for_content(&input, WindowSize::SIZE_32K),
+            WindowSize::SIZE_32K
+        );
+        assert_eq!(
+            WindowSize::preferred_for_content(&input, WindowSize::from_bits(12).unwrap()),
+            WindowSize::from_bits(12).unwrap()
+        );
+
+        // no matches
+        assert_eq!(
+            WindowSize::preferred_for_content(b"abcdefgh", WindowSize::SIZE_32K),
+            WindowSize::MIN
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! LZ4 block format
+
+use super::*;
+use crate::lz::Match;
+use crate::lz::lzss::{self, LZSS};
+
+/// Encodes an LZ4 block and appends it to `output`.
+///
+/// The last [`LAST_LITERALS`] bytes are always emitted as literals,
+/// and no match starts within the last [`MF_LIMIT`] bytes, as required by the format.
+pub fn encode_block(input: &[u8], output: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let mut literal_start = 0;
+    if input.len() > MF_LIMIT {
+        let match_limit = input.len() - MF_LIMIT;
+        let config = lzss::Configuration::new(MAX_DISTANCE, LZSS::MAX_LEN)
+            .parser(lzss::Parser::Lazy)
+            .min_len(MIN_MATCH);
+        let mut position = 0;
+        LZSS::encode(&input[..input.len() - LAST_LITERALS], config, |lzss| {
+            match lzss {
+                LZSS::Literal(_) => position += 1,
+                LZSS::Match(matches) => {
+                    if position <= match_limit {
+                        _write_sequence(output, &input[literal_start..position], Some(matches));
+                        literal_start = position + matches.len.get();
+                    }
+                    position += matches.len.get();
+                }
+            }
+            Ok(())
+        })?;
+    }
+    _write_sequence(output, &input[literal_start..], None);
+
+    Ok(())
+}
+
+/// Decodes an LZ4 block and appends the result to `output`.
+///
+/// Matches may refer to the last `history` bytes already in `output`, which allows linked blocks.
+/// Returns `DecodeError::LimitExceeded` if the block decodes to more than `max_size` bytes.
+pub fn decode_block(
+    input: &[u8],
+    output: &mut Vec<u8>,
+    history: usize,
+    max_size: usize,
+) -> Result<(), DecodeError> {
+    let base = output.len() - history.min(output.len());
+    let limit = output.len().saturating_add(max_size);
+    let mut cursor = 0;
+    loop {
+        let token = *input.get(cursor).ok_or(DecodeError::UnexpectedEof)?;
+        cursor += 1;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += _read_len_ext(input, &mut cursor)?;
+        }
+        let literals = input
+            .get(cursor..cursor + literal_len)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        if output.len() + literal_len > limit {
+            return Err(DecodeError::LimitExceeded);
+        }
+        output.extend_from_slice(literals);
+        cursor += literal_len;
+
+        if cursor == input.len() {
+            // the last sequence has no match
+            return Ok(());
+        }
+
+        let offset = input
+            .get(cursor..cursor + 2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]) as usize)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        cursor += 2;
+        if offset == 0 || offset > output.len() - base {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let mut match_len = (token & 15) as usize + MIN_MATCH;
+        if token & 15 == 15 {
+            match_len += _read_len_ext(input, &mut cursor)?;
+        }
+        if output.len() + match_len > limit {
+            return Err(DecodeError::LimitExceeded);
+        }
+
+        let start = output.len() - offset;
+        if offset >= match_len {
+            output.extend_from_within(start..start + match_len);
+        } else {
+            output.reserve(match_len);
+            for i in start..start + match_len {
+                output.push(output[i]);
+            }
+        }
+    }
+}
+
+fn _write_sequence(output: &mut Vec<u8>, literals: &[u8], matches: Option<Match>) {
+    let match_code = matches.map(|v| v.len.get() - MIN_MATCH).unwrap_or_default();
+    let token = ((literals.len().min(15) as u8) << 4) | match_code.min(15) as u8;
+    output.push(token);
+    if literals.len() >= 15 {
+        _write_len_ext(output, literals.len() - 15);
+    }
+    output.extend_from_slice(literals);
+
+    if let Some(matches) = matches {
+        output.extend_from_slice(&(matches.distance.get() as u16).to_le_bytes());
+        if match_code >= 15 {
+            _write_len_ext(output, match_code - 15);
+        }
+    }
+}
+
+#[inline]
+fn _write_len_ext(output: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        output.push(255);
+        len -= 255;
+    }
+    output.push(len as u8);
+}
+
+#[inline]
+fn _read_len_ext(input: &[u8], cursor: &mut usize) -> Result<usize, DecodeError> {
+    let mut len = 0usize;
+    loop {
+        let byte = *input.get(*cursor).ok_or(DecodeError::UnexpectedEof)?;
+        *cursor += 1;
+        len = len
+            .checked_add(byte as usize)
+            .ok_or(DecodeError::InvalidData)?;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! LZ4 frame format
+
+use super::*;
+use crate::num::bits::ByteAlignedWriter;
+
+/// Maximum size of the uncompressed data in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BlockMaxSize {
+    Size64K = 4,
+    Size256K = 5,
+    Size1M = 6,
+    #[default]
+    Size4M = 7,
+}
+
+impl BlockMaxSize {
+    #[inline]
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            4 => Some(Self::Size64K),
+            5 => Some(Self::Size256K),
+            6 => Some(Self::Size1M),
+            7 => Some(Self::Size4M),
+            _ => None,
+        }
+    }
+
+    /// Returns the block size in bytes.
+    #[inline]
+    pub const fn value(&self) -> usize {
+        1 << (8 + 2 * (*self as usize))
+    }
+}
+
+/// Options of the frame written by [`compress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameOptions {
+    block_max_size: BlockMaxSize,
+    block_checksum: bool,
+    content_checksum: bool,
+    content_size: bool,
+}
+
+impl FrameOptions {
+    /// 4MB independent blocks with a content checksum
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            block_max_size: BlockMaxSize::Size4M,
+            block_checksum: false,
+            content_checksum: true,
+            content_size: false,
+        }
+    }
+
+    #[inline]
+    pub const fn block_max_size(mut self, block_max_size: BlockMaxSize) -> Self {
+        self.block_max_size = block_max_size;
+        self
+    }
+
+    /// Appends the xxHash32 of the (compressed) data to each block.
+    #[inline]
+    pub const fn block_checksum(mut self, block_checksum: bool) -> Self {
+        self.block_checksum = block_checksum;
+        self
+    }
+
+    /// Appends the xxHash32 of the uncompressed data to the frame.
+    #[inline]
+    pub const fn content_checksum(mut self, content_checksum: bool) -> Self {
+        self.content_checksum = content_checksum;
+        self
+    }
+
+    /// Stores the uncompressed size in the frame descriptor.
+    #[inline]
+    pub const fn content_size(mut self, content_size: bool) -> Self {
+        self.content_size = content_size;
+        self
+    }
+}
+
+impl Default for FrameOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compresses the input into a single LZ4 frame.
+///
+/// Blocks are independent, and a block that does not shrink is stored uncompressed.
+pub fn compress(input: &[u8], options: FrameOptions) -> Result<Vec<u8>, EncodeError> {
+    let mut output = ByteAlignedWriter::new();
+    output.push_u32_le(MAGIC);
+
+    let mut flg = FLG_VERSION | FLG_BLOCK_INDEPENDENCE;
+    if options.block_checksum {
+        flg |= FLG_BLOCK_CHECKSUM;
+    }
+    if options.content_size {
+        flg |= FLG_CONTENT_SIZE;
+    }
+    if options.content_checksum {
+        flg |= FLG_CONTENT_CHECKSUM;
+    }
+    let descriptor_start = output.len();
+    output.push_byte(flg);
+    output.push_byte((options.block_max_size as u8) << 4);
+    if options.content_size {
+        output.push_u64_le(input.len() as u64);
+    }
+    let header_checksum =
+        (xxhash32::checksum(&output.as_bytes()[descriptor_start..], 0) >> 8) as u8;
+    output.push_byte(header_checksum);
+
+    let mut block = Vec::new();
+    for chunk in input.chunks(options.block_max_size.value()) {
+        block.clear();
+        encode_block(chunk, &mut block)?;
+        let (data, size) = if block.len() < chunk.len() {
+            (block.as_slice(), block.len() as u32)
+        } else {
+            (chunk, chunk.len() as u32 | BLOCK_UNCOMPRESSED)
+        };
+        output.push_u32_le(size);
+        output.extend_from_slice(data);
+        if options.block_checksum {
+            output.push_u32_le(xxhash32::checksum(data, 0));
+        }
+    }
+    output.push_u32_le(0); // end mark
+
+    if options.content_checksum {
+        output.push_u32_le(xxhash32::checksum(input, 0));
+    }
+
+    Ok(output.into_bytes())
+}
+
+/// Decompresses concatenated LZ4 frames, skipping skippable frames.
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses concatenated LZ4 frames, skipping skippable frames.
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut cursor = 0;
+    while cursor < input.len() {
+        let magic = _read_u32(input, &mut cursor)?;
+        if magic & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+            let size = _read_u32(input, &mut cursor)? as usize;
+            cursor = cursor
+                .checked_add(size)
+                .filter(|&v| v <= input.len())
+                .ok_or(DecodeError::UnexpectedEof)?;
+            continue;
+        }
+        if magic != MAGIC {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        _decompress_frame(input, &mut cursor, &mut output, max_size)?;
+    }
+    Ok(output)
+}
+
+fn _decompress_frame(
+    input: &[u8],
+    cursor: &mut usize,
+    output: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), DecodeError> {
+    let descriptor_start = *cursor;
+    let flg = _read_u8(input, cursor)?;
+    let bd = _read_u8(input, cursor)?;
+    if flg & FLG_VERSION_MASK != FLG_VERSION {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    if flg & FLG_RESERVED != 0 || bd & BD_RESERVED != 0 {
+        return Err(DecodeError::InvalidData);
+    }
+    let block_max_size = BlockMaxSize::from_code(bd >> 4)
+        .ok_or(DecodeError::InvalidData)?
+        .value();
+    let content_size = if flg & FLG_CONTENT_SIZE != 0 {
+        let low = _read_u32(input, cursor)? as u64;
+        let high = _read_u32(input, cursor)? as u64;
+        Some(low | (high << 32))
+    } else {
+        None
+    };
+    if flg & FLG_DICT_ID != 0 {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    let header_checksum = (xxhash32::checksum(&input[descriptor_start..*cursor], 0) >> 8) as u8;
+    if _read_u8(input, cursor)? != header_checksum {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    let is_independent = flg & FLG_BLOCK_INDEPENDENCE != 0;
+    let frame_start = output.len();
+    loop {
+        let size = _read_u32(input, cursor)?;
+        if size == 0 {
+            break;
+        }
+        let is_uncompressed = size & BLOCK_UNCOMPRESSED != 0;
+        let size = (size & !BLOCK_UNCOMPRESSED) as usize;
+        if size > block_max_size {
+            return Err(DecodeError::InvalidData);
+        }
+        let data = input
+            .get(*cursor..*cursor + size)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        *cursor += size;
+        if flg & FLG_BLOCK_CHECKSUM != 0 && _read_u32(input, cursor)? != xxhash32::checksum(data, 0)
+        {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+
+        let max_block_size = block_max_size.min(max_size - output.len());
+        if is_uncompressed {
+            if size > max_block_size {
+                return Err(DecodeError::LimitExceeded);
+            }
+            output.extend_from_slice(data);
+        } else {
+            let history = if is_independent {
+                0
+            } else {
+                (output.len() - frame_start).min(MAX_DISTANCE)
+            };
+            decode_block(data, output, history, max_block_size)?;
+        }
+    }
+
+    let content = &output[frame_start..];
+    if content_size.is_some_and(|v| v != content.len() as u64) {
+        return Err(DecodeError::InvalidData);
+    }
+    if flg & FLG_CONTENT_CHECKSUM != 0
+        && _read_u32(input, cursor)? != xxhash32::checksum(content, 0)
+    {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn _read_u8(input: &[u8], cursor: &mut usize) -> Result<u8, DecodeError> {
+    let value = *input.get(*cursor).ok_or(DecodeError::UnexpectedEof)?;
+    *cursor += 1;
+    Ok(value)
+}
+
+#[inline]
+fn _read_u32(input: &[u8], cursor: &mut usize) -> Result<u32, DecodeError> {
+    let bytes = input
+        .get(*cursor..*cursor + 4)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    *cursor += 4;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -0,0 +1,50 @@
+//! LZ4 compression format
+//!
+//! Supports the block format and the frame format, and is interoperable with the reference `lz4` command.
+//! Dictionaries (`DictID`) are not supported.
+//!
+//! See also:
+//!
+//! * <https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md>
+//! * <https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md>
+
+use crate::*;
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::hash::xxhash32;
+
+mod block;
+mod frame;
+pub use block::*;
+pub use frame::*;
+
+/// Minimum match length
+const MIN_MATCH: usize = 4;
+
+/// Maximum match distance
+const MAX_DISTANCE: usize = 0xffff;
+
+/// Number of bytes at the end of a block that must be literals
+pub const LAST_LITERALS: usize = 5;
+
+/// No match may start within this number of bytes from the end of a block
+pub const MF_LIMIT: usize = 12;
+
+const MAGIC: u32 = 0x184D_2204;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+const SKIPPABLE_MAGIC_MASK: u32 = 0xFFFF_FFF0;
+
+const FLG_VERSION_MASK: u8 = 0b1100_0000;
+const FLG_VERSION: u8 = 0b0100_0000;
+const FLG_BLOCK_INDEPENDENCE: u8 = 0b0010_0000;
+const FLG_BLOCK_CHECKSUM: u8 = 0b0001_0000;
+const FLG_CONTENT_SIZE: u8 = 0b0000_1000;
+const FLG_CONTENT_CHECKSUM: u8 = 0b0000_0100;
+const FLG_RESERVED: u8 = 0b0000_0010;
+const FLG_DICT_ID: u8 = 0b0000_0001;
+const BD_RESERVED: u8 = 0b1000_1111;
+
+/// The highest bit of the block size marks an uncompressed block
+const BLOCK_UNCOMPRESSED: u32 = 0x8000_0000;
@@ -0,0 +1,144 @@
+use super::*;
+
+/// `printf 'Hello, LZ4! Hello, LZ4! Hello, LZ4! Hello, LZ4!\n' | lz4`
+const HELLO_LZ4: &[u8] = &[
+    0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x16, 0x00, 0x00, 0x00, 0xcf, 0x48, 0x65, 0x6c, 0x6c,
+    0x6f, 0x2c, 0x20, 0x4c, 0x5a, 0x34, 0x21, 0x20, 0x0c, 0x00, 0x0c, 0x50, 0x4c, 0x5a, 0x34, 0x21,
+    0x0a, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x30, 0xca, 0x68,
+];
+
+/// Same as `HELLO_LZ4`, with `--content-size -BD -BX`
+const HELLO_LZ4_CHECKSUMS: &[u8] = &[
+    0x04, 0x22, 0x4d, 0x18, 0x7c, 0x40, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xcf, 0x16,
+    0x00, 0x00, 0x00, 0xcf, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x4c, 0x5a, 0x34, 0x21, 0x20,
+    0x0c, 0x00, 0x0c, 0x50, 0x4c, 0x5a, 0x34, 0x21, 0x0a, 0x5f, 0xb6, 0xed, 0xfd, 0x00, 0x00, 0x00,
+    0x00, 0xfe, 0x30, 0xca, 0x68,
+];
+
+const HELLO: &[u8] = b"Hello, LZ4! Hello, LZ4! Hello, LZ4! Hello, LZ4!\n";
+
+#[test]
+fn xxhash32() {
+    assert_eq!(xxhash32::checksum(b"", 0), 0x02CC_5D05);
+    assert_eq!(xxhash32::checksum(b"a", 0), 0x550D_7456);
+    assert_eq!(xxhash32::checksum(b"abc", 0), 0x32D1_53FF);
+    assert_eq!(
+        xxhash32::checksum(b"Nobody inspects the spammish repetition", 0),
+        0xE229_3B2F
+    );
+    assert_eq!(xxhash32::checksum(HELLO, 0), 0x68CA_30FE);
+}
+
+#[test]
+fn decompress_reference() {
+    assert_eq!(decompress(HELLO_LZ4).unwrap(), HELLO);
+    assert_eq!(decompress(HELLO_LZ4_CHECKSUMS).unwrap(), HELLO);
+
+    // concatenated with a skippable frame
+    let mut input = HELLO_LZ4.to_vec();
+    input.extend_from_slice(&0x184D_2A5Au32.to_le_bytes());
+    input.extend_from_slice(&3u32.to_le_bytes());
+    input.extend_from_slice(&[1, 2, 3]);
+    input.extend_from_slice(HELLO_LZ4_CHECKSUMS);
+    assert_eq!(decompress(&input).unwrap(), [HELLO, HELLO].concat());
+
+    // corrupted content checksum
+    let mut input = HELLO_LZ4.to_vec();
+    *input.last_mut().unwrap() ^= 1;
+    assert_eq!(decompress(&input), Err(DecodeError::ChecksumMismatch));
+
+    // corrupted header checksum
+    let mut input = HELLO_LZ4.to_vec();
+    input[6] ^= 1;
+    assert_eq!(decompress(&input), Err(DecodeError::ChecksumMismatch));
+
+    // truncated
+    assert_eq!(
+        decompress(&HELLO_LZ4[..HELLO_LZ4.len() - 6]),
+        Err(DecodeError::UnexpectedEof)
+    );
+
+    assert_eq!(
+        decompress_with_limit(HELLO_LZ4, HELLO.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn lz4_roundtrip() {
+    let mut input = Vec::new();
+    input.extend_from_slice(&random_alphabet(0, 255, 0x3000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x20000));
+    input.extend_from_slice(&[0; 0x1000]);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x20000));
+
+    for options in [
+        FrameOptions::new(),
+        FrameOptions::new()
+            .block_max_size(BlockMaxSize::Size64K)
+            .block_checksum(true)
+            .content_size(true),
+        FrameOptions::new().content_checksum(false),
+    ] {
+        let encoded = compress(&input, options).unwrap();
+        assert!(encoded.len() < input.len() / 2);
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+
+    for len in [0, 1, 12, 13, 17, 100] {
+        let input = &HELLO.repeat(3)[..len];
+        let encoded = compress(input, FrameOptions::new()).unwrap();
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+}
+
+#[test]
+fn lz4_block() {
+    let input = HELLO.repeat(4);
+    let mut encoded = Vec::new();
+    encode_block(&input, &mut encoded).unwrap();
+
+    let mut decoded = Vec::new();
+    decode_block(&encoded, &mut decoded, 0, input.len()).unwrap();
+    assert_eq!(decoded, input);
+
+    // the last literals and the match limit
+    let mut decoded = Vec::new();
+    let mut cursor = 0;
+    let mut last_match_end = 0;
+    while cursor < encoded.len() {
+        let token = encoded[cursor];
+        cursor += 1;
+        let literal_len = (token >> 4) as usize;
+        assert!(literal_len < 15);
+        decoded.extend_from_slice(&encoded[cursor..cursor + literal_len]);
+        cursor += literal_len;
+        if cursor == encoded.len() {
+            break;
+        }
+        cursor += 2;
+        let mut match_len = (token & 15) as usize + 4;
+        if token & 15 == 15 {
+            while encoded[cursor] == 255 {
+                match_len += 255;
+                cursor += 1;
+            }
+            match_len += encoded[cursor] as usize;
+            cursor += 1;
+        }
+        assert!(decoded.len() <= input.len() - MF_LIMIT);
+        decoded.resize(decoded.len() + match_len, 0);
+        last_match_end = decoded.len();
+    }
+    assert!(last_match_end <= input.len() - LAST_LITERALS);
+
+    // offset beyond the history
+    let mut decoded = b"Hello".to_vec();
+    assert_eq!(
+        decode_block(&[0x00, 0x05, 0x00, 0x00], &mut decoded, 4, 0x100),
+        Err(DecodeError::InvalidData)
+    );
+    let mut decoded = b"Hello".to_vec();
+    decode_block(&[0x01, 0x05, 0x00, 0x00], &mut decoded, 5, 0x100).unwrap();
+    assert_eq!(decoded, b"HelloHello");
+}
@@ -144,8 +144,178 @@ pub const fn nearest_power_of_two(value: usize) -> usize {
     if value >= threshold { next } else { next >> 1 }
 }
 
-pub struct BitStreamWriter {
-    buf: Vec<u8>,
+/// Destination of the bytes written by [`BitStreamWriter`]
+pub trait ByteSink {
+    fn push_byte(&mut self, byte: u8);
+
+    #[inline]
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter() {
+            self.push_byte(byte);
+        }
+    }
+
+    /// Returns the number of bytes written so far.
+    fn len(&self) -> usize;
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ByteSink for Vec<u8> {
+    #[inline]
+    fn push_byte(&mut self, byte: u8) {
+        self.push(byte);
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        Vec::extend_from_slice(self, bytes);
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+impl<S: ByteSink + ?Sized> ByteSink for &mut S {
+    #[inline]
+    fn push_byte(&mut self, byte: u8) {
+        (**self).push_byte(byte);
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        (**self).extend_from_slice(bytes);
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+/// Sink that writes into a fixed buffer
+///
+/// The bytes beyond the buffer are counted but discarded, which can be checked with `is_overflowed`.
+pub struct SliceSink<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    #[inline]
+    pub const fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Returns `true` if more bytes were written than the buffer can hold.
+    #[inline]
+    pub const fn is_overflowed(&self) -> bool {
+        self.len > self.buffer.len()
+    }
+
+    /// Returns the written part of the buffer, or `None` if it overflowed.
+    #[inline]
+    pub fn into_written(self) -> Option<&'a mut [u8]> {
+        let len = self.len;
+        self.buffer.get_mut(..len)
+    }
+}
+
+impl ByteSink for SliceSink<'_> {
+    #[inline]
+    fn push_byte(&mut self, byte: u8) {
+        if let Some(p) = self.buffer.get_mut(self.len) {
+            *p = byte;
+        }
+        self.len += 1;
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        if let Some(dest) = self.buffer.get_mut(self.len..self.len + bytes.len()) {
+            dest.copy_from_slice(bytes);
+        }
+        self.len += bytes.len();
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Sink that writes into `std::io::Write` through a small buffer
+///
+/// The first I/O error is kept and returned by `finish`, and the following bytes are discarded.
+#[cfg(feature = "std")]
+pub struct IoSink<W: std::io::Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    len: usize,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoSink<W> {
+    /// Size of the buffer in bytes
+    const BUFFER_SIZE: usize = 0x2000;
+
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::with_capacity(Self::BUFFER_SIZE),
+            len: 0,
+            error: None,
+        }
+    }
+
+    fn _flush(&mut self) {
+        if self.error.is_none()
+            && let Err(error) = self.writer.write_all(&self.buffer)
+        {
+            self.error = Some(error);
+        }
+        self.buffer.clear();
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self._flush();
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self.writer),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> ByteSink for IoSink<W> {
+    #[inline]
+    fn push_byte(&mut self, byte: u8) {
+        self.buffer.push(byte);
+        self.len += 1;
+        if self.buffer.len() >= Self::BUFFER_SIZE {
+            self._flush();
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// LSB-first bit writer
+///
+/// The bytes are written into a [`ByteSink`], which is a `Vec<u8>` by default.
+pub struct BitStreamWriter<S: ByteSink = Vec<u8>> {
+    buf: S,
     acc: u8,
     bit_position: u8,
 }
@@ -153,13 +323,33 @@ pub struct BitStreamWriter {
 impl BitStreamWriter {
     #[inline]
     pub const fn new() -> Self {
+        Self::with_sink(Vec::new())
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.into_sink()
+    }
+}
+
+impl<S: ByteSink> BitStreamWriter<S> {
+    /// Creates a writer that writes the bytes into the sink.
+    #[inline]
+    pub const fn with_sink(sink: S) -> Self {
         Self {
-            buf: Vec::new(),
+            buf: sink,
             acc: 0,
             bit_position: 0,
         }
     }
 
+    /// Writes the remaining bits padded to a byte, and returns the sink.
+    #[inline]
+    pub fn into_sink(mut self) -> S {
+        self.skip_to_next_byte_boundary();
+        self.buf
+    }
+
     #[inline]
     pub fn bit_count(&self) -> usize {
         self.buf.len() * 8 + self.bit_position as usize
@@ -180,6 +370,21 @@ impl BitStreamWriter {
         self.push(VarLenInteger::with_nibble(value))
     }
 
+    /// Writes the lower `bits` bits of the value, where `bits` is at most 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is greater than 64.
+    pub fn push_bits64(&mut self, value: u64, bits: u32) {
+        assert!(bits <= 64);
+        let mut shift = 0;
+        while shift < bits {
+            let size = BitSize::new((bits - shift).min(BitSize::MAX.as_u32()) as u8).unwrap();
+            self.push(VarLenInteger::new_truncated(size, (value >> shift) as u32));
+            shift += size.as_u32();
+        }
+    }
+
     #[inline]
     pub fn push_slice(&mut self, value: &[VarLenInteger]) {
         for &item in value.iter() {
@@ -193,7 +398,7 @@ impl BitStreamWriter {
         let mut acc = self.acc | ((value.value() as u8 & lowest_bit_mask) << self.bit_position);
         let mut remain_bits = value.size().as_u8();
         if self.bit_position + remain_bits >= 8 {
-            self.buf.push(acc);
+            self.buf.push_byte(acc);
             acc = 0;
             remain_bits -= lowest_bits;
             self.bit_position = 0;
@@ -202,7 +407,7 @@ impl BitStreamWriter {
                 let value_mask = (1u32 << value.size().as_usize()) - 1;
                 let mut acc32 = (value.value() & value_mask) >> lowest_bits;
                 while remain_bits >= 8 {
-                    self.buf.push(acc32 as u8);
+                    self.buf.push_byte(acc32 as u8);
                     acc32 >>= 8;
                     remain_bits -= 8;
                 }
@@ -223,7 +428,7 @@ impl BitStreamWriter {
     #[inline]
     pub fn skip_to_next_byte_boundary(&mut self) {
         if self.bit_position > 0 {
-            self.buf.push(self.acc);
+            self.buf.push_byte(self.acc);
             self.acc = 0;
             self.bit_position = 0;
         }
@@ -234,40 +439,34 @@ impl BitStreamWriter {
         self.skip_to_next_byte_boundary();
         self.buf.extend_from_slice(bytes);
     }
-
-    #[inline]
-    pub fn into_bytes(mut self) -> Vec<u8> {
-        self.skip_to_next_byte_boundary();
-        self.buf
-    }
 }
 
 pub trait Write<T> {
     fn write(&mut self, value: T);
 }
 
-impl Write<bool> for BitStreamWriter {
+impl<S: ByteSink> Write<bool> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: bool) {
         self.push_bool(value);
     }
 }
 
-impl Write<Nibble> for BitStreamWriter {
+impl<S: ByteSink> Write<Nibble> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: Nibble) {
         self.push_nibble(value);
     }
 }
 
-impl Write<u8> for BitStreamWriter {
+impl<S: ByteSink> Write<u8> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: u8) {
         self.push_byte(value);
     }
 }
 
-impl Write<&[u8]> for BitStreamWriter {
+impl<S: ByteSink> Write<&[u8]> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: &[u8]) {
         for &byte in value.iter() {
@@ -276,27 +475,130 @@ impl Write<&[u8]> for BitStreamWriter {
     }
 }
 
-impl Write<VarLenInteger> for BitStreamWriter {
+impl<S: ByteSink> Write<VarLenInteger> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: VarLenInteger) {
         self.push(value);
     }
 }
 
-impl Write<&[VarLenInteger]> for BitStreamWriter {
+impl<S: ByteSink> Write<&[VarLenInteger]> for BitStreamWriter<S> {
     #[inline]
     fn write(&mut self, value: &[VarLenInteger]) {
         self.push_slice(value);
     }
 }
 
+/// Byte-aligned writer for the fields of containers
+///
+/// The integers are written in little-endian order, and the varints in LEB128.
+/// The bytes are written into a [`ByteSink`], which is a `Vec<u8>` by default.
+pub struct ByteAlignedWriter<S: ByteSink = Vec<u8>> {
+    buf: S,
+}
+
+impl ByteAlignedWriter {
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_sink(Vec::new())
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Returns the bytes written so far.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Default for ByteAlignedWriter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: ByteSink> ByteAlignedWriter<S> {
+    /// Creates a writer that writes the bytes into the sink.
+    #[inline]
+    pub const fn with_sink(sink: S) -> Self {
+        Self { buf: sink }
+    }
+
+    #[inline]
+    pub fn into_sink(self) -> S {
+        self.buf
+    }
+
+    /// Returns the number of bytes written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    #[inline]
+    pub fn push_byte(&mut self, value: u8) {
+        self.buf.push_byte(value);
+    }
+
+    #[inline]
+    pub fn push_u16_le(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn push_u32_le(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn push_u64_le(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes the value in unsigned LEB128, 7 bits per byte with the continuation flag in the highest bit.
+    #[inline]
+    pub fn push_varint(&mut self, value: u64) {
+        super::varint::encode_u64(value, &mut self.buf);
+    }
+
+    /// Writes the zigzag encoded value in LEB128.
+    #[inline]
+    pub fn push_signed_varint(&mut self, value: i64) {
+        super::varint::encode_i64(value, &mut self.buf);
+    }
+
+    #[inline]
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Writes the length of the bytes as a varint, followed by the bytes.
+    #[inline]
+    pub fn push_length_prefixed(&mut self, bytes: &[u8]) {
+        self.push_varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+}
+
 type AccRepr = usize;
 
 #[repr(C)]
+#[derive(Clone)]
 pub struct BitStreamReader<'a> {
     acc: AccRepr,
     left: usize,
     slice: &'a [u8],
+    input: &'a [u8],
 }
 
 impl<'a> BitStreamReader<'a> {
@@ -306,6 +608,69 @@ impl<'a> BitStreamReader<'a> {
             slice,
             left: 0,
             acc: 0,
+            input: slice,
+        }
+    }
+
+    /// Returns the number of bits that have been read from the start of the input.
+    #[inline]
+    pub const fn bit_position(&self) -> usize {
+        self.input.len() * 8 - self.remaining_bits()
+    }
+
+    /// Returns the offset of the byte that contains the next bit to read.
+    ///
+    /// At a byte boundary, this is the number of bytes that have been read.
+    #[inline]
+    pub const fn byte_position(&self) -> usize {
+        self.bit_position() / 8
+    }
+
+    /// Moves the reader to the specified bit from the start of the input, in either direction.
+    ///
+    /// Returns `None` without moving if the position is beyond the end of the input.
+    pub fn seek_to_bit(&mut self, bit_position: usize) -> Option<()> {
+        if bit_position > self.input.len() * 8 {
+            return None;
+        }
+        self.slice = &self.input[bit_position / 8..];
+        self.acc = 0;
+        self.left = 0;
+        let bits = bit_position & 7;
+        if bits > 0 {
+            self.acc = self._iter_next()? as AccRepr >> bits;
+            self.left = 8 - bits;
+        }
+        Some(())
+    }
+
+    /// Returns the number of bits that have not been read yet.
+    #[inline]
+    pub const fn remaining_bits(&self) -> usize {
+        self.left + self.slice.len() * 8
+    }
+
+    /// Returns the number of bytes that have not been read yet, including a partially read byte.
+    #[inline]
+    pub const fn remaining_bytes(&self) -> usize {
+        self.remaining_bits().div_ceil(8)
+    }
+
+    /// Returns `true` if all bits have been read.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.remaining_bits() == 0
+    }
+
+    /// Verifies that nothing but the padding up to the next byte boundary remains.
+    ///
+    /// The value of the padding bits is not checked.
+    #[inline]
+    pub fn expect_end(&self) -> Result<(), DecodeError> {
+        if self.remaining_bits() > (self.left & 7) {
+            Err(DecodeError::InvalidData)
+        } else {
+            Ok(())
         }
     }
 
@@ -380,9 +745,9 @@ impl<'a> BitStreamReader<'a> {
             }
             Some(result)
         } else {
-            while bits.as_usize() > self.left {
-                self.acc |= (self._iter_next()? as AccRepr) << self.left;
-                self.left += 8;
+            self._refill();
+            if bits.as_usize() > self.left {
+                return None;
             }
             let result = self.acc as u32 & bits.mask();
             unsafe {
@@ -393,6 +758,23 @@ impl<'a> BitStreamReader<'a> {
         }
     }
 
+    /// Reads up to 64 bits, for fields wider than [`BitSize::MAX`].
+    ///
+    /// Returns `None` without consuming anything if `bits` is greater than 64 or exceeds the remaining bits.
+    pub fn read_bits64(&mut self, bits: u32) -> Option<u64> {
+        if bits > 64 || bits as usize > self.remaining_bits() {
+            return None;
+        }
+        let mut result = 0;
+        let mut shift = 0;
+        while shift < bits {
+            let size = BitSize::new((bits - shift).min(BitSize::MAX.as_u32()) as u8)?;
+            result |= (self.read_bits(size)? as u64) << shift;
+            shift += size.as_u32();
+        }
+        Some(result)
+    }
+
     #[inline]
     pub fn peek_bits(&mut self, bits: BitSize) -> Option<u32> {
         if bits.as_usize() <= self.left {
@@ -402,19 +784,50 @@ impl<'a> BitStreamReader<'a> {
         }
     }
 
+    /// Peeks the bits like [`Self::peek_bits`], but the bits beyond the end of the input are read as zero.
+    ///
+    /// This allows a table lookup near the end of the input, where fewer bits than the table size remain.
+    #[inline]
+    pub fn peek_bits_padded(&mut self, bits: BitSize) -> u32 {
+        if bits.as_usize() > self.left {
+            self._refill();
+        }
+        self.acc as u32 & bits.mask()
+    }
+
     /// # Safety
     ///
     /// `bits` must be less than or equal to 24
     fn _peek_bits2(&mut self, bits: BitSize) -> Option<u32> {
-        while self.left <= size_of::<AccRepr>() * 8 - 8 {
-            let Some((data, next)) = self.slice.split_first() else {
-                return (bits.as_usize() <= self.left).then(|| self.acc as u32 & bits.mask());
-            };
-            self.acc |= (*data as AccRepr) << self.left;
-            self.left += 8;
-            self.slice = next;
+        self._refill();
+        (bits.as_usize() <= self.left).then(|| self.acc as u32 & bits.mask())
+    }
+
+    /// Fills the accumulator with as many whole bytes as it can hold.
+    ///
+    /// If the slice has at least a word, the bytes are taken with a single unaligned load.
+    #[inline]
+    fn _refill(&mut self) {
+        const ACC_BITS: usize = AccRepr::BITS as usize;
+        if let Some(word) = self.slice.first_chunk::<{ size_of::<AccRepr>() }>() {
+            let n_bytes = (ACC_BITS - self.left) / 8;
+            if n_bytes > 0 {
+                // The bits beyond the bytes taken must be zero to keep the accumulator consistent.
+                let word =
+                    AccRepr::from_le_bytes(*word) & (AccRepr::MAX >> (ACC_BITS - n_bytes * 8));
+                self.acc |= word << self.left;
+                self.left += n_bytes * 8;
+                self.slice = &self.slice[n_bytes..];
+            }
+        } else {
+            while self.left <= ACC_BITS - 8 {
+                let Some(data) = self._iter_next() else {
+                    break;
+                };
+                self.acc |= (data as AccRepr) << self.left;
+                self.left += 8;
+            }
         }
-        Some(self.acc as u32 & bits.mask())
     }
 
     #[inline]
@@ -463,6 +876,7 @@ impl<'a> BitStreamReader<'a> {
         }
         if self.left > 0 {
             let rewind = self.left / 8;
+            self.acc = 0;
             self.left = 0;
             self.slice = unsafe {
                 // Safety: The value is checked, and the slice is guaranteed to be valid.
@@ -487,38 +901,447 @@ impl Iterator for BitStreamReader<'_> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// MSB-first bit reader, for formats such as bzip2 and JPEG
+///
+/// The methods are equivalent to those of [`BitStreamReader`], except that the first bit read is the most significant bit.
+pub struct MsbBitStreamReader<'a> {
+    /// The lower `left` bits are not read yet.
+    acc: AccRepr,
+    left: usize,
+    slice: &'a [u8],
+}
 
-    #[test]
-    fn bit_test() {
-        let tail = b"Lorem ipsum";
-        for padding_size in 1..=16 {
-            let padding_mask = (1u32 << padding_size) - 1;
-            for value_size in 1..=16 {
-                let mask = (1u32 << value_size) - 1;
-                for pattern in [
-                    0x0u32,
-                    u32::MAX,
-                    0x55555555,
-                    0xAAAAAAAA,
-                    0x5A5A5A5A,
-                    0xA5A5A5A5,
-                    0x0F0F0F0F,
-                    0xF0F0F0F0,
-                    0xE5E5E5E5,
-                    1234578,
-                    87654321,
-                    0xEDB88320,
-                    0x04C11DB7,
-                ] {
-                    let padding_size = BitSize::new(padding_size).unwrap();
-                    let value_size = BitSize::new(value_size).unwrap();
-                    // println!("PADDING {padding_size} VALUE {value_size} PATTERN {pattern:08x}");
-                    let pattern_n = !pattern & mask;
+impl<'a> MsbBitStreamReader<'a> {
+    #[inline]
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self {
+            slice,
+            left: 0,
+            acc: 0,
+        }
+    }
 
-                    let mut writer = BitStreamWriter::new();
+    /// Returns the number of bits that have not been read yet.
+    #[inline]
+    pub const fn remaining_bits(&self) -> usize {
+        self.left + self.slice.len() * 8
+    }
+
+    /// Returns the number of bytes that have not been read yet, including a partially read byte.
+    #[inline]
+    pub const fn remaining_bytes(&self) -> usize {
+        self.remaining_bits().div_ceil(8)
+    }
+
+    /// Returns `true` if all bits have been read.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.remaining_bits() == 0
+    }
+
+    /// Verifies that nothing but the padding up to the next byte boundary remains.
+    ///
+    /// The value of the padding bits is not checked.
+    #[inline]
+    pub fn expect_end(&self) -> Result<(), DecodeError> {
+        if self.remaining_bits() > (self.left & 7) {
+            Err(DecodeError::InvalidData)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn _iter_next(&mut self) -> Option<u8> {
+        let (left, right) = self.slice.split_first()?;
+        self.slice = right;
+        Some(*left)
+    }
+
+    #[inline]
+    fn _fill(&mut self, bits: usize) -> Option<()> {
+        while self.left < bits {
+            self.acc = (self.acc << 8) | self._iter_next()? as AccRepr;
+            self.left += 8;
+        }
+        Some(())
+    }
+
+    #[inline]
+    pub fn advance(&mut self, bits: BitSize) -> Option<()> {
+        if bits.as_usize() <= self.left {
+            self.left -= bits.as_usize();
+        } else {
+            let mut bits_left = bits.as_usize() - self.left;
+            while bits_left >= 8 {
+                self._iter_next()?;
+                bits_left -= 8;
+            }
+            if bits_left > 0 {
+                self.acc = self._iter_next()? as AccRepr;
+                self.left = 8 - bits_left;
+            } else {
+                self.left = 0;
+            }
+        }
+        Some(())
+    }
+
+    #[inline]
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read_bits(BitSize::Bit1).map(|v| v != 0)
+    }
+
+    #[inline]
+    pub fn read_nibble(&mut self) -> Option<Nibble> {
+        self.read_bits(BitSize::NIBBLE)
+            .and_then(|v| Nibble::new(v as u8))
+    }
+
+    #[inline]
+    pub fn read_byte(&mut self) -> Option<u8> {
+        self.read_bits(BitSize::BYTE).map(|v| v as u8)
+    }
+
+    #[inline]
+    pub fn read_bits(&mut self, bits: BitSize) -> Option<u32> {
+        let result = self.peek_bits(bits)?;
+        self.left -= bits.as_usize();
+        Some(result)
+    }
+
+    /// Reads up to 64 bits, for fields wider than [`BitSize::MAX`].
+    ///
+    /// Returns `None` without consuming anything if `bits` is greater than 64 or exceeds the remaining bits.
+    pub fn read_bits64(&mut self, bits: u32) -> Option<u64> {
+        if bits > 64 || bits as usize > self.remaining_bits() {
+            return None;
+        }
+        let mut result = 0;
+        let mut left = bits;
+        while left > 0 {
+            let size = BitSize::new(left.min(BitSize::MAX.as_u32()) as u8)?;
+            result = (result << size.as_u32()) | self.read_bits(size)? as u64;
+            left -= size.as_u32();
+        }
+        Some(result)
+    }
+
+    #[inline]
+    pub fn peek_bits(&mut self, bits: BitSize) -> Option<u32> {
+        self._fill(bits.as_usize())?;
+        Some((self.acc >> (self.left - bits.as_usize())) as u32 & bits.mask())
+    }
+
+    #[inline]
+    pub fn skip_to_next_byte_boundary(&mut self) {
+        self.left &= !7;
+    }
+
+    /// Skip to the next byte boundary and read the next byte
+    #[inline]
+    pub fn read_next_byte(&mut self) -> Option<u8> {
+        self.skip_to_next_byte_boundary();
+        self.read_byte()
+    }
+
+    /// Skip to the next byte boundary and read the specified number of bytes
+    #[inline]
+    pub fn read_next_bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.skip_to_next_byte_boundary();
+        let mut result = [0; N];
+        for p in result.iter_mut() {
+            *p = self.read_byte()?;
+        }
+        Some(result)
+    }
+
+    /// Skips to the next byte boundary and returns a slice with the specified number of bytes
+    #[inline]
+    pub fn read_next_bytes_slice(&mut self, size: usize) -> Option<&[u8]> {
+        self.skip_to_next_byte_boundary();
+        if size == 0 {
+            return Some(&[]);
+        }
+        if self.left > 0 {
+            let rewind = self.left / 8;
+            self.left = 0;
+            self.slice = unsafe {
+                // Safety: The bytes in the accumulator were taken from just before the slice.
+                core::slice::from_raw_parts(
+                    self.slice.as_ptr().sub(rewind),
+                    self.slice.len() + rewind,
+                )
+            }
+        }
+        let (left, right) = self.slice.split_at_checked(size)?;
+        self.slice = right;
+        Some(left)
+    }
+}
+
+impl Iterator for MsbBitStreamReader<'_> {
+    type Item = bool;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_bool()
+    }
+}
+
+/// MSB-first bit writer
+///
+/// The methods are equivalent to those of [`BitStreamWriter`], except that the most significant bit is written first.
+pub struct MsbBitStreamWriter<S: ByteSink = Vec<u8>> {
+    buf: S,
+    /// The lower `bit_position` bits are not written yet.
+    acc: u32,
+    bit_position: u8,
+}
+
+impl MsbBitStreamWriter {
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_sink(Vec::new())
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.into_sink()
+    }
+}
+
+impl Default for MsbBitStreamWriter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: ByteSink> MsbBitStreamWriter<S> {
+    /// Creates a writer that writes the bytes into the sink.
+    #[inline]
+    pub const fn with_sink(sink: S) -> Self {
+        Self {
+            buf: sink,
+            acc: 0,
+            bit_position: 0,
+        }
+    }
+
+    /// Writes the remaining bits padded to a byte, and returns the sink.
+    #[inline]
+    pub fn into_sink(mut self) -> S {
+        self.skip_to_next_byte_boundary();
+        self.buf
+    }
+
+    #[inline]
+    pub fn bit_count(&self) -> usize {
+        self.buf.len() * 8 + self.bit_position as usize
+    }
+
+    #[inline]
+    pub fn push_bool(&mut self, value: bool) {
+        self.push(VarLenInteger::with_bool(value));
+    }
+
+    #[inline]
+    pub fn push_byte(&mut self, value: u8) {
+        self.push(VarLenInteger::with_byte(value))
+    }
+
+    #[inline]
+    pub fn push_nibble(&mut self, value: Nibble) {
+        self.push(VarLenInteger::with_nibble(value))
+    }
+
+    /// Writes the lower `bits` bits of the value, where `bits` is at most 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is greater than 64.
+    pub fn push_bits64(&mut self, value: u64, bits: u32) {
+        assert!(bits <= 64);
+        let mut left = bits;
+        while left > 0 {
+            let size = BitSize::new(left.min(BitSize::MAX.as_u32()) as u8).unwrap();
+            left -= size.as_u32();
+            self.push(VarLenInteger::new_truncated(size, (value >> left) as u32));
+        }
+    }
+
+    #[inline]
+    pub fn push_slice(&mut self, value: &[VarLenInteger]) {
+        for &item in value.iter() {
+            self.push(item);
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: VarLenInteger) {
+        let size = value.size();
+        self.acc = (self.acc << size.as_usize()) | (value.value() & size.mask());
+        self.bit_position += size.as_u8();
+        while self.bit_position >= 8 {
+            self.bit_position -= 8;
+            self.buf.push_byte((self.acc >> self.bit_position) as u8);
+        }
+    }
+
+    #[inline]
+    pub fn skip_to_next_byte_boundary(&mut self) {
+        if self.bit_position > 0 {
+            self.buf
+                .push_byte((self.acc << (8 - self.bit_position)) as u8);
+            self.acc = 0;
+            self.bit_position = 0;
+        }
+    }
+
+    #[inline]
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.skip_to_next_byte_boundary();
+        self.buf.extend_from_slice(bytes);
+    }
+}
+
+impl<S: ByteSink> Write<bool> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: bool) {
+        self.push_bool(value);
+    }
+}
+
+impl<S: ByteSink> Write<Nibble> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: Nibble) {
+        self.push_nibble(value);
+    }
+}
+
+impl<S: ByteSink> Write<u8> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: u8) {
+        self.push_byte(value);
+    }
+}
+
+impl<S: ByteSink> Write<&[u8]> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: &[u8]) {
+        for &byte in value.iter() {
+            self.push_byte(byte);
+        }
+    }
+}
+
+impl<S: ByteSink> Write<VarLenInteger> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: VarLenInteger) {
+        self.push(value);
+    }
+}
+
+impl<S: ByteSink> Write<&[VarLenInteger]> for MsbBitStreamWriter<S> {
+    #[inline]
+    fn write(&mut self, value: &[VarLenInteger]) {
+        self.push_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _write_sample<S: ByteSink>(writer: &mut BitStreamWriter<S>) {
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit3, 0b101));
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit13, 0x1234));
+        writer.extend_from_slice(b"Lorem");
+        writer.push_bool(true);
+    }
+
+    #[test]
+    fn bit_stream_writer_sinks() {
+        let mut writer = BitStreamWriter::new();
+        _write_sample(&mut writer);
+        let expected = writer.into_bytes();
+        assert_eq!(expected.len(), 8);
+
+        // appends to an existing vector
+        let mut vec = b"head".to_vec();
+        let mut writer = BitStreamWriter::with_sink(&mut vec);
+        _write_sample(&mut writer);
+        assert_eq!(writer.bit_count(), (4 + 7) * 8 + 1);
+        writer.into_sink();
+        assert_eq!(vec[..4], *b"head");
+        assert_eq!(vec[4..], expected);
+
+        let mut array = [0u8; 16];
+        let mut writer = BitStreamWriter::with_sink(SliceSink::new(&mut array));
+        _write_sample(&mut writer);
+        let sink = writer.into_sink();
+        assert!(!sink.is_overflowed());
+        assert_eq!(sink.into_written().unwrap(), expected.as_slice());
+
+        let mut array = [0u8; 7];
+        let mut writer = BitStreamWriter::with_sink(SliceSink::new(&mut array));
+        _write_sample(&mut writer);
+        let sink = writer.into_sink();
+        assert!(sink.is_overflowed());
+        assert_eq!(sink.len(), expected.len());
+        assert!(sink.into_written().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn bit_stream_writer_io_sink() {
+        let mut writer = BitStreamWriter::with_sink(IoSink::new(Vec::new()));
+        for _ in 0..0x1000 {
+            _write_sample(&mut writer);
+        }
+        let output = writer.into_sink().finish().unwrap();
+
+        let mut expected = BitStreamWriter::new();
+        for _ in 0..0x1000 {
+            _write_sample(&mut expected);
+        }
+        assert_eq!(output, expected.into_bytes());
+
+        let mut array = [0u8; 4];
+        let mut writer = BitStreamWriter::with_sink(IoSink::new(&mut array[..]));
+        _write_sample(&mut writer);
+        assert!(writer.into_sink().finish().is_err());
+    }
+
+    #[test]
+    fn bit_test() {
+        let tail = b"Lorem ipsum";
+        for padding_size in 1..=16 {
+            let padding_mask = (1u32 << padding_size) - 1;
+            for value_size in 1..=16 {
+                let mask = (1u32 << value_size) - 1;
+                for pattern in [
+                    0x0u32,
+                    u32::MAX,
+                    0x55555555,
+                    0xAAAAAAAA,
+                    0x5A5A5A5A,
+                    0xA5A5A5A5,
+                    0x0F0F0F0F,
+                    0xF0F0F0F0,
+                    0xE5E5E5E5,
+                    1234578,
+                    87654321,
+                    0xEDB88320,
+                    0x04C11DB7,
+                ] {
+                    let padding_size = BitSize::new(padding_size).unwrap();
+                    let value_size = BitSize::new(value_size).unwrap();
+                    // println!("PADDING {padding_size} VALUE {value_size} PATTERN {pattern:08x}");
+                    let pattern_n = !pattern & mask;
+
+                    let mut writer = BitStreamWriter::new();
                     writer.push(VarLenInteger::new_checked(padding_size, 0).unwrap());
                     writer.push(VarLenInteger::new_truncated(value_size, pattern));
                     writer.push(VarLenInteger::new_truncated(padding_size, u32::MAX));
@@ -611,4 +1434,282 @@ mod tests {
             assert_eq!(shifted, BitSize::new(i).unwrap().power_of_two());
         }
     }
+
+    #[test]
+    fn remaining_bits() {
+        let data = [0x12, 0x34, 0x56];
+        let mut reader = BitStreamReader::new(&data);
+        assert_eq!(reader.remaining_bits(), 24);
+        assert_eq!(reader.remaining_bytes(), 3);
+        assert!(reader.expect_end().is_err());
+
+        reader.read_bits(BitSize::Bit5).unwrap();
+        assert_eq!(reader.remaining_bits(), 19);
+        assert_eq!(reader.remaining_bytes(), 3);
+
+        reader.peek_bits(BitSize::Bit16).unwrap();
+        assert_eq!(reader.remaining_bits(), 19);
+
+        reader.read_bits(BitSize::Bit11).unwrap();
+        assert_eq!(reader.remaining_bits(), 8);
+        assert!(reader.expect_end().is_err());
+
+        reader.read_bits(BitSize::Bit3).unwrap();
+        assert_eq!(reader.remaining_bits(), 5);
+        assert_eq!(reader.remaining_bytes(), 1);
+        assert!(!reader.is_empty());
+        assert!(reader.expect_end().is_ok());
+
+        reader.skip_to_next_byte_boundary();
+        assert!(reader.is_empty());
+        assert!(reader.expect_end().is_ok());
+    }
+
+    #[test]
+    fn byte_aligned_writer() {
+        let mut writer = ByteAlignedWriter::new();
+        assert!(writer.is_empty());
+        writer.push_byte(0xab);
+        writer.push_u16_le(0x1234);
+        writer.push_u32_le(0x89ab_cdef);
+        writer.push_u64_le(0x0102_0304_0506_0708);
+        assert_eq!(writer.len(), 15);
+        assert_eq!(
+            writer.into_bytes(),
+            [
+                0xab, 0x34, 0x12, 0xef, 0xcd, 0xab, 0x89, 8, 7, 6, 5, 4, 3, 2, 1
+            ]
+        );
+
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0x80, 0x01]),
+            (624485, &[0xe5, 0x8e, 0x26]),
+            (
+                u64::MAX,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ),
+        ] {
+            let mut writer = ByteAlignedWriter::new();
+            writer.push_varint(value);
+            assert_eq!(writer.as_bytes(), expected);
+        }
+
+        let mut writer = ByteAlignedWriter::new();
+        writer.push_length_prefixed(b"abc");
+        writer.push_length_prefixed(&[0x55; 200]);
+        let bytes = writer.into_bytes();
+        assert_eq!(&bytes[..4], b"\x03abc");
+        assert_eq!(&bytes[4..6], [0xc8, 0x01]);
+        assert_eq!(bytes[6..], [0x55; 200]);
+
+        // into a fixed buffer
+        let mut buffer = [0; 6];
+        let mut writer = ByteAlignedWriter::with_sink(SliceSink::new(&mut buffer));
+        writer.push_u32_le(0xdead_beef);
+        writer.push_varint(300);
+        assert_eq!(
+            writer.into_sink().into_written().unwrap(),
+            [0xef, 0xbe, 0xad, 0xde, 0xac, 0x02]
+        );
+    }
+
+    #[test]
+    fn reader_position() {
+        let data = (0..32u8).map(|v| v.wrapping_mul(0x9d)).collect::<Vec<_>>();
+        let mut reader = BitStreamReader::new(&data);
+        assert_eq!((reader.bit_position(), reader.byte_position()), (0, 0));
+
+        reader.read_bits(BitSize::Bit13).unwrap();
+        assert_eq!((reader.bit_position(), reader.byte_position()), (13, 1));
+        assert_eq!(reader.remaining_bytes(), 31);
+
+        // a lookahead fills the accumulator without moving the position
+        reader.peek_bits(BitSize::Bit24).unwrap();
+        assert_eq!(reader.bit_position(), 13);
+        reader.skip_to_next_byte_boundary();
+        assert_eq!((reader.bit_position(), reader.byte_position()), (16, 2));
+        assert_eq!(reader.read_next_bytes_slice(3).unwrap(), &data[2..5]);
+        assert_eq!(reader.byte_position(), 5);
+
+        // seeking back reads the same bits again
+        let mut expected = BitStreamReader::new(&data);
+        for position in [0, 1, 7, 8, 13, 100, 255] {
+            expected.seek_to_bit(0).unwrap();
+            for _ in 0..position {
+                expected.read_bool().unwrap();
+            }
+            reader.seek_to_bit(position).unwrap();
+            assert_eq!(reader.bit_position(), position);
+            assert_eq!(reader.remaining_bits(), data.len() * 8 - position);
+            assert_eq!(
+                reader.clone().collect::<Vec<_>>(),
+                expected.clone().collect::<Vec<_>>()
+            );
+        }
+
+        reader.seek_to_bit(data.len() * 8).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(reader.byte_position(), data.len());
+        reader.seek_to_bit(3).unwrap();
+        assert_eq!(reader.seek_to_bit(data.len() * 8 + 1), None);
+        assert_eq!(reader.bit_position(), 3);
+    }
+
+    #[test]
+    fn msb_bit_stream() {
+        let mut writer = MsbBitStreamWriter::new();
+        writer.push_bool(true);
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit7, 1));
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit12, 0xabc));
+        writer.push_nibble(Nibble::new(0xd).unwrap());
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit3, 0b101));
+        assert_eq!(writer.bit_count(), 27);
+        writer.extend_from_slice(b"Lorem");
+        writer.push(VarLenInteger::new_truncated(BitSize::Bit24, 0x123456));
+        let bytes = writer.into_bytes();
+        assert_eq!(&bytes[..4], &[0x81, 0xab, 0xcd, 0xa0]);
+        assert_eq!(&bytes[4..9], b"Lorem");
+        assert_eq!(&bytes[9..], &[0x12, 0x34, 0x56]);
+
+        let mut reader = MsbBitStreamReader::new(&bytes);
+        assert_eq!(reader.remaining_bits(), bytes.len() * 8);
+        assert_eq!(reader.read_bool(), Some(true));
+        assert_eq!(reader.peek_bits(BitSize::Bit7), Some(1));
+        reader.advance(BitSize::Bit7).unwrap();
+        assert_eq!(reader.read_bits(BitSize::Bit12), Some(0xabc));
+        assert_eq!(reader.read_nibble(), Nibble::new(0xd));
+        assert_eq!(reader.read_bits(BitSize::Bit3), Some(0b101));
+        assert_eq!(reader.read_next_bytes_slice(2), Some(&b"Lo"[..]));
+        assert_eq!(reader.read_next_bytes::<3>(), Some(*b"rem"));
+        assert_eq!(reader.read_bits(BitSize::Bit24), Some(0x123456));
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_bool(), None);
+
+        // advance across multiple bytes, then rewind the prefetched bytes
+        let mut reader = MsbBitStreamReader::new(&bytes);
+        reader.advance(BitSize::Bit19).unwrap();
+        assert_eq!(reader.peek_bits(BitSize::Bit24), Some(0x6d_0263));
+        assert_eq!(reader.read_next_byte(), Some(0xa0));
+        assert_eq!(reader.read_next_bytes_slice(5), Some(&b"Lorem"[..]));
+        assert!(reader.advance(BitSize::Bit24).is_some());
+        assert!(reader.expect_end().is_ok());
+        assert!(reader.advance(BitSize::Bit1).is_none());
+    }
+
+    #[test]
+    fn bits64() {
+        let fields = [
+            (1, 1u64),
+            (64, 0x0123_4567_89ab_cdef),
+            (0, 0),
+            (25, 0x1ab_cdef),
+            (57, 0x1fe_dcba_9876_5432),
+            (32, 0xdead_beef),
+            (7, 0x55),
+        ];
+        let total_bits = fields.iter().map(|v| v.0 as usize).sum::<usize>();
+
+        let mut writer = BitStreamWriter::new();
+        let mut msb_writer = MsbBitStreamWriter::new();
+        for (bits, value) in fields {
+            writer.push_bits64(value, bits);
+            msb_writer.push_bits64(value, bits);
+        }
+        assert_eq!(writer.bit_count(), total_bits);
+        assert_eq!(msb_writer.bit_count(), total_bits);
+        let bytes = writer.into_bytes();
+        let msb_bytes = msb_writer.into_bytes();
+
+        let mut reader = BitStreamReader::new(&bytes);
+        let mut msb_reader = MsbBitStreamReader::new(&msb_bytes);
+        for (bits, value) in fields {
+            assert_eq!(reader.read_bits64(bits), Some(value));
+            assert_eq!(msb_reader.read_bits64(bits), Some(value));
+        }
+        assert!(reader.expect_end().is_ok());
+        assert!(msb_reader.expect_end().is_ok());
+
+        // the first field of MSB-first order is in the highest bits
+        let mut msb_reader = MsbBitStreamReader::new(&msb_bytes);
+        assert_eq!(msb_reader.read_bits64(9), Some(0x101));
+
+        // nothing is consumed if the field is too long
+        let mut reader = BitStreamReader::new(&bytes[..8]);
+        assert_eq!(reader.read_bits64(65), None);
+        assert_eq!(reader.read_bits(BitSize::Bit1), Some(1));
+        assert_eq!(reader.read_bits64(64), None);
+        assert_eq!(reader.remaining_bits(), 63);
+        assert_eq!(
+            reader.read_bits64(63),
+            Some(0x0123_4567_89ab_cdef & (u64::MAX >> 1))
+        );
+    }
+
+    #[test]
+    fn reader_refill() {
+        let data = (0..100u32)
+            .map(|v| (v.wrapping_mul(0x9e37_79b1) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let bit_at = |position: usize| (data[position / 8] >> (position % 8)) & 1 != 0;
+
+        for first in 1..=24 {
+            let mut reader = BitStreamReader::new(&data);
+            let mut position = 0;
+            let mut size = first;
+            while let Some(bit_size) = BitSize::new(size) {
+                let Some(value) = reader.read_bits(bit_size) else {
+                    assert!(position + bit_size.as_usize() > data.len() * 8);
+                    break;
+                };
+                for i in 0..bit_size.as_usize() {
+                    assert_eq!(value & (1 << i) != 0, bit_at(position + i));
+                }
+                position += bit_size.as_usize();
+                assert_eq!(reader.remaining_bits(), data.len() * 8 - position);
+                size = size % 24 + 1;
+            }
+        }
+
+        // the prefetched bytes are rewound
+        let mut reader = BitStreamReader::new(&data);
+        assert_eq!(reader.peek_bits(BitSize::Bit3), Some(data[0] as u32 & 7));
+        assert_eq!(reader.read_next_bytes_slice(10), Some(&data[..10]));
+        assert_eq!(
+            reader.read_bits(BitSize::Bit16),
+            Some(u16::from_le_bytes([data[10], data[11]]) as u32)
+        );
+        assert_eq!(reader.read_next_bytes_slice(88), Some(&data[12..]));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn reader_bits_after_bytes_slice() {
+        // The bytes prefetched before a slice must not be left in the accumulator,
+        // where they were mixed into the bits read after it, such as the block after a stored block of deflate.
+        let data = (0..40u32)
+            .map(|v| (v.wrapping_mul(0x9e37_79b1) >> 24) as u8 | 0x80)
+            .collect::<Vec<_>>();
+        for len in [data.len(), 6] {
+            let data = &data[..len];
+            for bits in 1..=8 {
+                for size in 0..len - 2 {
+                    let mut reader = BitStreamReader::new(data);
+                    let bit_size = BitSize::new(bits).unwrap();
+                    assert_eq!(
+                        reader.read_bits(bit_size),
+                        Some(data[0] as u32 & bit_size.mask())
+                    );
+                    assert_eq!(reader.read_next_bytes_slice(size), Some(&data[1..1 + size]));
+                    assert_eq!(
+                        reader.read_byte(),
+                        Some(data[1 + size]),
+                        "{len} {bits} {size}"
+                    );
+                    assert_eq!(reader.remaining_bytes(), len - 2 - size);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,203 @@
+//! LEB128 variable-length integers
+//!
+//! Each byte holds 7 bits of the value from the least significant group,
+//! and the highest bit is set if more bytes follow.
+//! Signed values are mapped to unsigned values with the zigzag encoding first,
+//! so that small negative values are as short as small positive values.
+
+use super::bits::{BitStreamReader, ByteSink};
+use crate::*;
+
+/// Maximum number of bytes of a `u32`
+pub const MAX_LEN_U32: usize = 5;
+
+/// Maximum number of bytes of a `u64`
+pub const MAX_LEN_U64: usize = 10;
+
+/// Maps a signed value to an unsigned value, as in `0, -1, 1, -2, 2, ...`
+#[inline]
+pub const fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Reverses [`zigzag_encode`].
+#[inline]
+pub const fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Writes the value into the sink and returns the number of bytes written.
+#[inline]
+pub fn encode_u32<S: ByteSink + ?Sized>(value: u32, output: &mut S) -> usize {
+    encode_u64(value as u64, output)
+}
+
+/// Writes the value into the sink and returns the number of bytes written.
+pub fn encode_u64<S: ByteSink + ?Sized>(mut value: u64, output: &mut S) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        output.push_byte(value as u8 | 0x80);
+        value >>= 7;
+        len += 1;
+    }
+    output.push_byte(value as u8);
+    len
+}
+
+/// Writes the zigzag encoded value into the sink and returns the number of bytes written.
+#[inline]
+pub fn encode_i64<S: ByteSink + ?Sized>(value: i64, output: &mut S) -> usize {
+    encode_u64(zigzag_encode(value), output)
+}
+
+/// Returns the number of bytes of the encoded value.
+#[inline]
+pub const fn encoded_len(value: u64) -> usize {
+    let bits = 64 - (value | 1).leading_zeros() as usize;
+    bits.div_ceil(7)
+}
+
+/// Decodes a value at the start of the input, and returns it with the number of bytes read.
+///
+/// Returns `DecodeError::UnexpectedEof` if the input ends in the middle of the value,
+/// and `DecodeError::InvalidData` if the value does not fit in a `u32`.
+#[inline]
+pub fn decode_u32(input: &[u8]) -> Result<(u32, usize), DecodeError> {
+    let mut iter = input.iter().copied();
+    let value = _decode(|| iter.next(), u32::BITS)?;
+    Ok((value as u32, input.len() - iter.len()))
+}
+
+/// Decodes a value at the start of the input, and returns it with the number of bytes read.
+///
+/// See [`decode_u32`] for the errors.
+#[inline]
+pub fn decode_u64(input: &[u8]) -> Result<(u64, usize), DecodeError> {
+    let mut iter = input.iter().copied();
+    let value = _decode(|| iter.next(), u64::BITS)?;
+    Ok((value, input.len() - iter.len()))
+}
+
+/// Decodes a zigzag encoded value at the start of the input, and returns it with the number of bytes read.
+#[inline]
+pub fn decode_i64(input: &[u8]) -> Result<(i64, usize), DecodeError> {
+    decode_u64(input).map(|(value, len)| (zigzag_decode(value), len))
+}
+
+/// Reads a value from the next byte boundary of the reader.
+#[inline]
+pub fn read_u32(reader: &mut BitStreamReader) -> Result<u32, DecodeError> {
+    _decode(|| reader.read_next_byte(), u32::BITS).map(|v| v as u32)
+}
+
+/// Reads a value from the next byte boundary of the reader.
+#[inline]
+pub fn read_u64(reader: &mut BitStreamReader) -> Result<u64, DecodeError> {
+    _decode(|| reader.read_next_byte(), u64::BITS)
+}
+
+/// Reads a zigzag encoded value from the next byte boundary of the reader.
+#[inline]
+pub fn read_i64(reader: &mut BitStreamReader) -> Result<i64, DecodeError> {
+    read_u64(reader).map(zigzag_decode)
+}
+
+/// Decodes a value of up to `bits` bits from the bytes.
+fn _decode<F>(mut next: F, bits: u32) -> Result<u64, DecodeError>
+where
+    F: FnMut() -> Option<u8>,
+{
+    let mut value = 0u64;
+    for shift in (0..bits).step_by(7) {
+        let byte = next().ok_or(DecodeError::UnexpectedEof)?;
+        if bits - shift < 7 && byte >= 1 << (bits - shift) {
+            // the value overflows or more bytes follow
+            return Err(DecodeError::InvalidData);
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::bits::ByteAlignedWriter;
+
+    #[test]
+    fn varint_roundtrip() {
+        let mut values = vec![0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX];
+        values.extend((0..64).map(|v| 1u64 << v));
+        values.extend((1..64).map(|v| (1u64 << v) - 1));
+        for value in values {
+            let mut output = Vec::new();
+            let len = encode_u64(value, &mut output);
+            assert_eq!(len, output.len());
+            assert_eq!(len, encoded_len(value));
+            assert!(len <= MAX_LEN_U64);
+            assert_eq!(decode_u64(&output), Ok((value, len)));
+            if let Ok(value) = u32::try_from(value) {
+                assert!(len <= MAX_LEN_U32);
+                assert_eq!(decode_u32(&output), Ok((value, len)));
+            } else {
+                assert_eq!(decode_u32(&output), Err(DecodeError::InvalidData));
+            }
+            for end in 0..len {
+                assert_eq!(decode_u64(&output[..end]), Err(DecodeError::UnexpectedEof));
+            }
+        }
+
+        for value in [0, 1, -1, 63, -64, 64, -65, i64::MAX, i64::MIN] {
+            let mut output = Vec::new();
+            let len = encode_i64(value, &mut output);
+            assert_eq!(decode_i64(&output), Ok((value, len)));
+        }
+        assert_eq!([0, -1, 1, -2, 2].map(zigzag_encode), [0, 1, 2, 3, 4]);
+        assert_eq!(zigzag_encode(i64::MIN), u64::MAX);
+    }
+
+    #[test]
+    fn varint_invalid() {
+        // 11 bytes
+        let mut overlong = [0x80; 11];
+        overlong[10] = 0;
+        assert_eq!(decode_u64(&overlong), Err(DecodeError::InvalidData));
+        // bits beyond 64
+        let mut overflow = [0xff; 10];
+        overflow[9] = 0x02;
+        assert_eq!(decode_u64(&overflow), Err(DecodeError::InvalidData));
+        // bits beyond 32
+        assert_eq!(
+            decode_u32(&[0xff, 0xff, 0xff, 0xff, 0x10]),
+            Err(DecodeError::InvalidData)
+        );
+        assert_eq!(
+            decode_u32(&[0xff, 0xff, 0xff, 0xff, 0x0f]),
+            Ok((u32::MAX, 5))
+        );
+        // a redundant zero group is accepted
+        assert_eq!(decode_u32(&[0x81, 0x00, 0xaa]), Ok((1, 2)));
+    }
+
+    #[test]
+    fn varint_stream() {
+        let mut writer = ByteAlignedWriter::new();
+        writer.push_varint(300);
+        writer.push_signed_varint(-300);
+        writer.push_byte(0x55);
+        writer.push_varint(u64::MAX);
+        let bytes = writer.into_bytes();
+
+        let mut reader = BitStreamReader::new(&bytes);
+        assert_eq!(read_u32(&mut reader), Ok(300));
+        assert_eq!(read_i64(&mut reader), Ok(-300));
+        // unaligned bits before a value are skipped
+        reader.read_bool().unwrap();
+        assert_eq!(read_u64(&mut reader), Ok(u64::MAX));
+        assert!(reader.is_empty());
+        assert_eq!(read_u64(&mut reader), Err(DecodeError::UnexpectedEof));
+    }
+}
@@ -6,28 +6,53 @@ use core::fmt;
 use core::num::NonZero;
 
 /// A Variable-length integer
+///
+/// A value of 1 to 24 bits with its size, such as a prefix code or the extra bits of a length.
+/// The bits are written in the order of the bit stream, which is LSB first for [`BitStreamWriter`],
+/// so the prefix codes of MSB-first formats are [reversed](Self::reversed) before writing.
+///
+/// The values are ordered by the size and then by the value, which is the order of canonical prefix codes.
+///
+/// ```
+/// use compress::num::{VarLenInteger, bits::BitSize};
+///
+/// let code = VarLenInteger::new_checked(BitSize::Bit3, 0b101).unwrap();
+/// let extra = VarLenInteger::new_checked(BitSize::Bit2, 0b11).unwrap();
+/// let joined = code.concat(extra).unwrap();
+/// assert_eq!(joined, VarLenInteger::new_checked(BitSize::Bit5, 0b10111).unwrap());
+/// assert_eq!(joined.split_at(BitSize::Bit3), Some((code, extra)));
+/// assert_eq!(extra.prepend_bit(false).unwrap().to_string(), "011");
+/// assert!(extra < code);
+/// ```
 #[repr(transparent)]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VarLenInteger(NonZero<u32>);
 
 impl VarLenInteger {
     /// # Safety
     ///
     /// The `value` must fit within the `size`.
+    /// Debug builds assert this.
     #[inline]
     pub const unsafe fn from_raw_parts(size: BitSize, value: u32) -> Self {
+        debug_assert!(value <= size.mask(), "value exceeds the bit size");
         Self(unsafe { NonZero::new_unchecked(value | (size.as_u32() << 24)) })
     }
 
-    /// TODO: Remove this method in the future
-    // #[deprecated(
-    //     note = "Use `new_truncated` or `new_checked` instead. This method does not check the value range."
-    // )]
+    /// Creates a value without reporting an overflow.
+    ///
+    /// Debug builds panic if the value does not fit within the `size`,
+    /// and release builds truncate it like [`Self::new_truncated`].
+    #[deprecated(
+        note = "Use `new_truncated` or `new_checked` instead. This method does not check the value range."
+    )]
     #[inline]
     pub const fn new(size: BitSize, value: u32) -> Self {
-        unsafe { Self::from_raw_parts(size, value & 0x00ff_ffff) }
+        debug_assert!(value <= size.mask(), "value exceeds the bit size");
+        Self::new_truncated(size, value)
     }
 
+    /// Creates a value, or returns `None` if it does not fit within the `size`.
     #[inline]
     pub const fn new_checked(size: BitSize, value: u32) -> Option<Self> {
         if value <= size.mask() {
@@ -38,6 +63,7 @@ impl VarLenInteger {
         }
     }
 
+    /// Creates a value from the lower `size` bits of `value`.
     #[inline]
     pub const fn new_truncated(size: BitSize, value: u32) -> Self {
         // Safety: The value is truncated.
@@ -90,6 +116,46 @@ impl VarLenInteger {
         self.0 = self.reversed().0;
     }
 
+    /// Appends `lower` below the bits of this value,
+    /// or returns `None` if the total size exceeds [`BitSize::MAX`].
+    #[inline]
+    pub const fn concat(&self, lower: Self) -> Option<Self> {
+        let Some(size) = self.size().checked_add(lower.size()) else {
+            return None;
+        };
+        // Safety: Both values fit within their sizes.
+        Some(unsafe {
+            Self::from_raw_parts(
+                size,
+                (self.value() << lower.size().as_u32()) | lower.value(),
+            )
+        })
+    }
+
+    /// Splits the value into the upper `mid` bits and the remaining lower bits, which reverts [`Self::concat`].
+    ///
+    /// Returns `None` unless `mid` is less than the size.
+    #[inline]
+    pub const fn split_at(&self, mid: BitSize) -> Option<(Self, Self)> {
+        let Some(lower_size) = BitSize::new(self.size().as_u8().saturating_sub(mid.as_u8())) else {
+            return None;
+        };
+        // Safety: The values are masked or shifted to their sizes.
+        unsafe {
+            Some((
+                Self::from_raw_parts(mid, self.value() >> lower_size.as_u32()),
+                Self::from_raw_parts(lower_size, self.value() & lower_size.mask()),
+            ))
+        }
+    }
+
+    /// Adds a bit above the bits of this value, as when walking up a prefix code tree,
+    /// or returns `None` if the size is already [`BitSize::MAX`].
+    #[inline]
+    pub const fn prepend_bit(&self, bit: bool) -> Option<Self> {
+        Self::with_bool(bit).concat(*self)
+    }
+
     #[inline]
     pub fn to_vec<T>(iter: T) -> Vec<u8>
     where
@@ -130,6 +196,22 @@ impl VarLenInteger {
     }
 }
 
+impl PartialOrd for VarLenInteger {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for VarLenInteger {
+    /// Compares the sizes, and then the values.
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        // The size is stored above the value.
+        self.0.cmp(&other.0)
+    }
+}
+
 impl From<bool> for VarLenInteger {
     #[inline]
     fn from(value: bool) -> Self {
@@ -209,4 +291,57 @@ mod tests {
             assert_eq!(rhs.reversed().reversed(), rhs);
         }
     }
+
+    #[test]
+    fn concat_split() {
+        let value = |size: u8, value: u32| {
+            VarLenInteger::new_checked(BitSize::new(size).unwrap(), value).unwrap()
+        };
+        for (upper, lower) in [
+            (value(1, 1), value(1, 0)),
+            (value(3, 0b101), value(2, 0b11)),
+            (value(12, 0xabc), value(12, 0x123)),
+            (value(23, 0x7f_ffff), value(1, 1)),
+        ] {
+            let joined = upper.concat(lower).unwrap();
+            assert_eq!(
+                joined.size().as_usize(),
+                upper.size().as_usize() + lower.size().as_usize()
+            );
+            assert_eq!(joined.split_at(upper.size()), Some((upper, lower)));
+        }
+        assert_eq!(value(24, 1).concat(value(1, 1)), None);
+        assert_eq!(value(4, 0b1010).split_at(BitSize::Bit4), None);
+        assert_eq!(value(4, 0b1010).split_at(BitSize::Bit5), None);
+
+        assert_eq!(value(3, 0b010).prepend_bit(true), Some(value(4, 0b1010)));
+        assert_eq!(value(3, 0b010).prepend_bit(false), Some(value(4, 0b0010)));
+        assert_eq!(value(24, 0).prepend_bit(true), None);
+
+        let mut values = [value(3, 0), value(2, 3), value(2, 1), value(1, 1)];
+        values.sort();
+        assert_eq!(values, [value(1, 1), value(2, 1), value(2, 3), value(3, 0)]);
+    }
+
+    #[test]
+    fn overflow() {
+        let size = BitSize::Bit5;
+        assert_eq!(
+            VarLenInteger::new_checked(size, 0x1f).unwrap().value(),
+            0x1f
+        );
+        assert_eq!(VarLenInteger::new_checked(size, 0x20), None);
+        assert_eq!(VarLenInteger::new_truncated(size, 0x3f).value(), 0x1f);
+        #[allow(deprecated)]
+        let value = VarLenInteger::new(size, 0x1f);
+        assert_eq!(value.value(), 0x1f);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "value exceeds the bit size")]
+    fn overflow_debug_assert() {
+        #[allow(deprecated)]
+        let _ = VarLenInteger::new(BitSize::Bit5, 0x20);
+    }
 }
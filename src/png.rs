@@ -0,0 +1,343 @@
+//! PNG scanline filters
+//!
+//! [`filter`] prepends a filter type byte to each scanline as in the `IDAT` data of PNG, and [`unfilter`] reverts it.
+//! [`encode_idat`] and [`decode_idat`] combine them with the zlib stream of [`deflate`].
+//!
+//! Scanlines are given as `row_len` bytes each.
+//! `bytes_per_pixel` is the size of a complete pixel rounded up to a byte, so it is `1` for bit depths less than 8.
+//!
+//! See also: <https://www.w3.org/TR/png-3/#9Filters>
+
+use crate::deflate::{CompressionLevel, deflate_zlib, inflate_to_vec_with_limit};
+use crate::*;
+use alloc::vec;
+
+/// Filter type of a scanline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FilterType {
+    None = 0,
+    /// Difference from the left byte
+    Sub,
+    /// Difference from the byte above
+    Up,
+    /// Difference from the average of the left and above bytes
+    Average,
+    /// Difference from the left, above or upper left byte selected by the Paeth predictor
+    Paeth,
+}
+
+impl FilterType {
+    pub const ALL: [Self; 5] = [Self::None, Self::Sub, Self::Up, Self::Average, Self::Paeth];
+
+    #[inline]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::None),
+            1 => Some(Self::Sub),
+            2 => Some(Self::Up),
+            3 => Some(Self::Average),
+            4 => Some(Self::Paeth),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub const fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the predicted byte from the left (`a`), above (`b`) and upper left (`c`) bytes.
+    #[inline]
+    const fn predict(&self, a: u8, b: u8, c: u8) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Sub => a,
+            Self::Up => b,
+            Self::Average => ((a as u16 + b as u16) / 2) as u8,
+            Self::Paeth => paeth_predictor(a, b, c),
+        }
+    }
+}
+
+/// Selection of the filter type of each scanline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterStrategy {
+    /// Uses the same filter type for all scanlines
+    Fixed(FilterType),
+    /// Selects the filter type that minimizes the sum of the absolute values of the filtered bytes as signed bytes,
+    /// which is the heuristic recommended by the PNG specification
+    #[default]
+    MinSum,
+}
+
+/// Returns the byte closest to `a + b - c` among `a`, `b` and `c`, in that order of preference.
+#[inline]
+pub const fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Filters a scanline with the previous scanline, which is all zeros for the first scanline.
+fn _filter_row(
+    filter_type: FilterType,
+    row: &[u8],
+    prev: &[u8],
+    bytes_per_pixel: usize,
+    output: &mut Vec<u8>,
+) {
+    output.push(filter_type.as_u8());
+    for (i, (&x, &b)) in row.iter().zip(prev).enumerate() {
+        let (a, c) = if i >= bytes_per_pixel {
+            (row[i - bytes_per_pixel], prev[i - bytes_per_pixel])
+        } else {
+            (0, 0)
+        };
+        output.push(x.wrapping_sub(filter_type.predict(a, b, c)));
+    }
+}
+
+/// Filters the scanlines, each of which is prefixed with its filter type.
+///
+/// Returns `EncodeError::InvalidInput` if the input is not a whole number of scanlines.
+pub fn filter(
+    input: &[u8],
+    row_len: usize,
+    bytes_per_pixel: usize,
+    strategy: FilterStrategy,
+) -> Result<Vec<u8>, EncodeError> {
+    if row_len == 0 || bytes_per_pixel == 0 || !input.len().is_multiple_of(row_len) {
+        return Err(EncodeError::InvalidInput);
+    }
+    let n_rows = input.len() / row_len;
+    let mut output = Vec::with_capacity(input.len() + n_rows);
+    let zeros = vec![0; row_len];
+    let mut candidate = Vec::with_capacity(row_len + 1);
+    let mut best = Vec::with_capacity(row_len + 1);
+    let mut prev = zeros.as_slice();
+    for row in input.chunks_exact(row_len) {
+        match strategy {
+            FilterStrategy::Fixed(filter_type) => {
+                _filter_row(filter_type, row, prev, bytes_per_pixel, &mut output);
+            }
+            FilterStrategy::MinSum => {
+                let mut best_sum = usize::MAX;
+                for filter_type in FilterType::ALL {
+                    candidate.clear();
+                    _filter_row(filter_type, row, prev, bytes_per_pixel, &mut candidate);
+                    let sum = candidate[1..]
+                        .iter()
+                        .map(|&v| (v as i8).unsigned_abs() as usize)
+                        .sum::<usize>();
+                    if sum < best_sum {
+                        best_sum = sum;
+                        core::mem::swap(&mut best, &mut candidate);
+                    }
+                }
+                output.extend_from_slice(&best);
+            }
+        }
+        prev = row;
+    }
+    Ok(output)
+}
+
+/// Reverts the filters of the scanlines written by [`filter`].
+///
+/// Returns `DecodeError::InvalidData` if the input is not a whole number of filtered scanlines or has an unknown filter type.
+pub fn unfilter(
+    input: &[u8],
+    row_len: usize,
+    bytes_per_pixel: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    if row_len == 0 || bytes_per_pixel == 0 {
+        return Err(DecodeError::InvalidInput);
+    }
+    if !input.len().is_multiple_of(row_len + 1) {
+        return Err(DecodeError::InvalidData);
+    }
+    let n_rows = input.len() / (row_len + 1);
+    let mut output = vec![0; n_rows * row_len];
+    for (y, filtered) in input.chunks_exact(row_len + 1).enumerate() {
+        let filter_type = FilterType::from_u8(filtered[0]).ok_or(DecodeError::InvalidData)?;
+        let (prev, row) = output.split_at_mut(y * row_len);
+        let prev = &prev[prev.len().saturating_sub(row_len)..];
+        let row = &mut row[..row_len];
+        for (i, &x) in filtered[1..].iter().enumerate() {
+            let b = prev.get(i).copied().unwrap_or_default();
+            let (a, c) = if i >= bytes_per_pixel {
+                (
+                    row[i - bytes_per_pixel],
+                    prev.get(i - bytes_per_pixel).copied().unwrap_or_default(),
+                )
+            } else {
+                (0, 0)
+            };
+            row[i] = x.wrapping_add(filter_type.predict(a, b, c));
+        }
+    }
+    Ok(output)
+}
+
+/// Filters the scanlines and compresses them into a zlib stream, which is the content of the `IDAT` chunks.
+pub fn encode_idat(
+    input: &[u8],
+    row_len: usize,
+    bytes_per_pixel: usize,
+    strategy: FilterStrategy,
+    level: CompressionLevel,
+) -> Result<Vec<u8>, EncodeError> {
+    let filtered = filter(input, row_len, bytes_per_pixel, strategy)?;
+    deflate_zlib(&filtered, level, None)
+}
+
+/// Decompresses the content of the `IDAT` chunks, and reverts the filters of `height` scanlines.
+///
+/// Returns `DecodeError::InvalidData` if the decompressed size does not match the dimensions.
+pub fn decode_idat(
+    input: &[u8],
+    row_len: usize,
+    bytes_per_pixel: usize,
+    height: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let filtered_size = row_len
+        .checked_add(1)
+        .and_then(|v| v.checked_mul(height))
+        .ok_or(DecodeError::InvalidInput)?;
+    let filtered = inflate_to_vec_with_limit(input, filtered_size).map_err(|e| match e {
+        DecodeError::LimitExceeded => DecodeError::InvalidData,
+        e => e,
+    })?;
+    if filtered.len() != filtered_size {
+        return Err(DecodeError::InvalidData);
+    }
+    unfilter(&filtered, row_len, bytes_per_pixel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two RGB scanlines of three pixels
+    const ROWS: &[u8] = &[
+        10, 20, 30, 200, 100, 50, 255, 0, 128, //
+        12, 18, 33, 190, 110, 40, 250, 5, 120,
+    ];
+
+    /// `ROWS` filtered with each filter type
+    const FILTERED: [&[u8]; 5] = [
+        &[
+            0, 10, 20, 30, 200, 100, 50, 255, 0, 128, 0, 12, 18, 33, 190, 110, 40, 250, 5, 120,
+        ],
+        &[
+            1, 10, 20, 30, 190, 80, 20, 55, 156, 78, 1, 12, 18, 33, 178, 92, 7, 60, 151, 80,
+        ],
+        &[
+            2, 10, 20, 30, 200, 100, 50, 255, 0, 128, 2, 2, 254, 3, 246, 10, 246, 251, 5, 248,
+        ],
+        &[
+            3, 10, 20, 30, 195, 90, 35, 155, 206, 103, 3, 7, 8, 18, 84, 51, 255, 28, 206, 36,
+        ],
+        &[
+            4, 10, 20, 30, 190, 80, 20, 55, 156, 78, 4, 2, 254, 3, 246, 10, 246, 251, 5, 248,
+        ],
+    ];
+
+    /// `FILTERED[4]` compressed by `zlib.compress` of Python with level 9
+    const PAETH_ZLIB: &[u8] = &[
+        0x78, 0xda, 0x63, 0xe1, 0x12, 0x91, 0xdb, 0x17, 0x20, 0x62, 0x3e, 0xc7, 0x8f, 0x85, 0xe9,
+        0x1f, 0xf3, 0x37, 0xae, 0x6f, 0xbf, 0x59, 0x7f, 0x00, 0x00, 0x39, 0xa5, 0x07, 0x79,
+    ];
+
+    #[test]
+    fn png_filters() {
+        for (filter_type, expected) in FilterType::ALL.into_iter().zip(FILTERED) {
+            let filtered = filter(ROWS, 9, 3, FilterStrategy::Fixed(filter_type)).unwrap();
+            assert_eq!(filtered, expected, "{filter_type:?}");
+            assert_eq!(unfilter(&filtered, 9, 3).unwrap(), ROWS);
+        }
+
+        assert_eq!(decode_idat(PAETH_ZLIB, 9, 3, 2).unwrap(), ROWS);
+        assert_eq!(
+            decode_idat(PAETH_ZLIB, 9, 3, 3),
+            Err(DecodeError::InvalidData)
+        );
+        assert_eq!(
+            decode_idat(PAETH_ZLIB, 9, 3, 1),
+            Err(DecodeError::InvalidData)
+        );
+
+        assert_eq!(
+            filter(ROWS, 10, 3, FilterStrategy::MinSum),
+            Err(EncodeError::InvalidInput)
+        );
+        assert_eq!(
+            unfilter(&FILTERED[0][..19], 9, 3),
+            Err(DecodeError::InvalidData)
+        );
+        let mut invalid = FILTERED[0].to_vec();
+        invalid[10] = 5;
+        assert_eq!(unfilter(&invalid, 9, 3), Err(DecodeError::InvalidData));
+    }
+
+    #[test]
+    fn png_adaptive_filter() {
+        // alternating horizontal gradients are best filtered with `Sub`
+        let width = 64;
+        let horizontal = (0..width * 8)
+            .map(|v| {
+                let (x, y) = (v % width, v / width);
+                (if y % 2 == 0 { x } else { width - 1 - x }) as u8 * 3
+            })
+            .collect::<Vec<_>>();
+        let filtered = filter(&horizontal, width, 1, FilterStrategy::MinSum).unwrap();
+        for row in filtered.chunks_exact(width + 1) {
+            assert_eq!(row[0], FilterType::Sub.as_u8());
+        }
+        // a vertical gradient over a steep pattern is best filtered with `Up` after the first row
+        let vertical = (0..width * 8)
+            .map(|v| ((v % width) * 50 + (v / width) * 3) as u8)
+            .collect::<Vec<_>>();
+        let filtered = filter(&vertical, width, 1, FilterStrategy::MinSum).unwrap();
+        for row in filtered.chunks_exact(width + 1).skip(1) {
+            assert_eq!(row[0], FilterType::Up.as_u8());
+        }
+
+        // RGBA
+        let mut input = Vec::new();
+        for y in 0..37 {
+            for x in 0..41 {
+                input.extend_from_slice(&[x * 5, y * 3, x ^ y, 255]);
+            }
+        }
+        input.extend_from_slice(&random_alphabet(0, 255, 41 * 4 * 3));
+        let encoded = encode_idat(
+            &input,
+            41 * 4,
+            4,
+            FilterStrategy::MinSum,
+            CompressionLevel::Best,
+        )
+        .unwrap();
+        let unfiltered = encode_idat(
+            &input,
+            41 * 4,
+            4,
+            FilterStrategy::Fixed(FilterType::None),
+            CompressionLevel::Best,
+        )
+        .unwrap();
+        assert!(encoded.len() < unfiltered.len());
+        assert_eq!(decode_idat(&encoded, 41 * 4, 4, 40).unwrap(), input);
+        assert_eq!(decode_idat(&unfiltered, 41 * 4, 4, 40).unwrap(), input);
+    }
+}
@@ -0,0 +1,343 @@
+//! Experimental PPM compressor
+//!
+//! Prediction by partial matching predicts each byte from the preceding bytes of up to [`MAX_ORDER`] bytes,
+//! and codes it with the arithmetic coding of [`RangeEncoder`].
+//! If the byte has not been seen in the longest context, an escape is coded and the next shorter context is tried,
+//! excluding the bytes already predicted by the longer contexts.
+//! The escape frequency is the number of distinct bytes in the context (PPMC),
+//! and a byte never seen at all is coded with a uniform distribution.
+//!
+//! The compressed data is the uncompressed size in LEB128 followed by the range coded bytes.
+//! The model keeps every context of the input, so the memory grows with the variety of the input.
+//! The format may change in the future.
+//!
+//! See also: <https://en.wikipedia.org/wiki/Prediction_by_partial_matching>
+
+use crate::entropy::range::{MAX_TOTAL_FREQ, RangeDecoder, RangeEncoder};
+use crate::num::varint;
+use crate::*;
+
+/// Maximum number of preceding bytes of a context
+pub const MAX_ORDER: usize = 3;
+
+/// The counts of a context are halved when their sum exceeds this value.
+const MAX_CONTEXT_TOTAL: u32 = 0x8000;
+
+// The escape frequency is at most 256, and the preceding bytes of a context fit in the lower 24 bits of the key.
+const _: () = assert!(MAX_CONTEXT_TOTAL + 256 < MAX_TOTAL_FREQ && MAX_ORDER <= 3);
+
+/// Compresses the input.
+pub fn compress(input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+    let mut output = Vec::new();
+    varint::encode_u64(input.len() as u64, &mut output);
+    if input.is_empty() {
+        return Ok(output);
+    }
+
+    let mut encoder = RangeEncoder::new();
+    let mut model = Model::new();
+    for (position, &byte) in input.iter().enumerate() {
+        let history = &input[..position];
+        model.encode(&mut encoder, history, byte);
+        model.update(history, byte);
+    }
+    output.extend_from_slice(&encoder.finish());
+    Ok(output)
+}
+
+/// Decompresses the data compressed by [`compress`].
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses the data compressed by [`compress`].
+///
+/// Returns `DecodeError::LimitExceeded` if the uncompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let (size, header_size) = varint::decode_u64(input)?;
+    let size = usize::try_from(size).map_err(|_| DecodeError::LimitExceeded)?;
+    if size > max_size {
+        return Err(DecodeError::LimitExceeded);
+    }
+    let data = &input[header_size..];
+    let mut output = Vec::new();
+    if size == 0 {
+        return if data.is_empty() {
+            Ok(output)
+        } else {
+            Err(DecodeError::InvalidData)
+        };
+    }
+    output
+        .try_reserve_exact(size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+
+    let mut decoder = RangeDecoder::new(data)?;
+    let mut model = Model::new();
+    while output.len() < size {
+        let byte = model.decode(&mut decoder, &output)?;
+        model.update(&output, byte);
+        output.push(byte);
+    }
+    if !decoder.is_finished() || decoder.position() != data.len() {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok(output)
+}
+
+/// Counts of the bytes that followed a context
+#[derive(Debug, Default)]
+struct Context {
+    counts: Vec<(u8, u16)>,
+}
+
+impl Context {
+    /// Returns the sum of the counts and the escape frequency, excluding the bytes predicted by longer contexts.
+    #[inline]
+    fn totals(&self, excluded: &[bool; 256]) -> (u32, u32) {
+        self.counts
+            .iter()
+            .filter(|(byte, _)| !excluded[*byte as usize])
+            .fold((0, 0), |(sum, escape), &(_, count)| {
+                (sum + count as u32, escape + 1)
+            })
+    }
+
+    /// Returns the cumulative frequency and the count of the byte, or `None` if it has not been seen.
+    #[inline]
+    fn find(&self, byte: u8, excluded: &[bool; 256]) -> Option<(u32, u32)> {
+        let mut cum_freq = 0;
+        for &(current, count) in self.counts.iter() {
+            if current == byte {
+                return Some((cum_freq, count as u32));
+            }
+            if !excluded[current as usize] {
+                cum_freq += count as u32;
+            }
+        }
+        None
+    }
+
+    /// Returns the byte whose range contains the value, with its cumulative frequency and count.
+    #[inline]
+    fn byte_at(&self, value: u32, excluded: &[bool; 256]) -> Option<(u8, u32, u32)> {
+        let mut cum_freq = 0;
+        for &(byte, count) in self.counts.iter() {
+            if excluded[byte as usize] {
+                continue;
+            }
+            if value < cum_freq + count as u32 {
+                return Some((byte, cum_freq, count as u32));
+            }
+            cum_freq += count as u32;
+        }
+        None
+    }
+
+    #[inline]
+    fn exclude(&self, excluded: &mut [bool; 256]) {
+        for &(byte, _) in self.counts.iter() {
+            excluded[byte as usize] = true;
+        }
+    }
+
+    fn update(&mut self, byte: u8) {
+        match self.counts.iter_mut().find(|(current, _)| *current == byte) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((byte, 1)),
+        }
+        let total = self.counts.iter().map(|v| v.1 as u32).sum::<u32>();
+        if total > MAX_CONTEXT_TOTAL {
+            for (_, count) in self.counts.iter_mut() {
+                *count = count.div_ceil(2);
+            }
+        }
+    }
+}
+
+/// Contexts of all orders, keyed by the order in the highest byte and the preceding bytes
+struct Model {
+    contexts: BTreeMap<u32, Context>,
+}
+
+impl Model {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            contexts: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the keys of the contexts from the longest one available.
+    #[inline]
+    fn keys(history: &[u8]) -> impl Iterator<Item = u32> + '_ {
+        (0..=MAX_ORDER.min(history.len())).rev().map(|order| {
+            let bytes = history[history.len() - order..]
+                .iter()
+                .fold(0, |acc, &byte| (acc << 8) | byte as u32);
+            ((order as u32) << 24) | bytes
+        })
+    }
+
+    fn encode(&self, encoder: &mut RangeEncoder, history: &[u8], byte: u8) {
+        let mut excluded = [false; 256];
+        for key in Self::keys(history) {
+            let Some(context) = self.contexts.get(&key) else {
+                continue;
+            };
+            let (sum, escape) = context.totals(&excluded);
+            if escape == 0 {
+                continue;
+            }
+            if let Some((cum_freq, freq)) = context.find(byte, &excluded) {
+                encoder.encode_freq(cum_freq, freq, sum + escape);
+                return;
+            }
+            encoder.encode_freq(sum, escape, sum + escape);
+            context.exclude(&mut excluded);
+        }
+
+        // a byte that has not been seen in any context
+        let cum_freq = excluded[..byte as usize].iter().filter(|v| !**v).count() as u32;
+        let total = excluded.iter().filter(|v| !**v).count() as u32;
+        encoder.encode_freq(cum_freq, 1, total);
+    }
+
+    fn decode(&self, decoder: &mut RangeDecoder, history: &[u8]) -> Result<u8, DecodeError> {
+        let mut excluded = [false; 256];
+        for key in Self::keys(history) {
+            let Some(context) = self.contexts.get(&key) else {
+                continue;
+            };
+            let (sum, escape) = context.totals(&excluded);
+            if escape == 0 {
+                continue;
+            }
+            let value = decoder.decode_freq(sum + escape);
+            if let Some((byte, cum_freq, freq)) = context.byte_at(value, &excluded) {
+                decoder.consume_freq(cum_freq, freq)?;
+                return Ok(byte);
+            }
+            decoder.consume_freq(sum, escape)?;
+            context.exclude(&mut excluded);
+        }
+
+        let total = excluded.iter().filter(|v| !**v).count() as u32;
+        if total == 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        let value = decoder.decode_freq(total);
+        let byte = (0..=255u8)
+            .filter(|&v| !excluded[v as usize])
+            .nth(value as usize)
+            .ok_or(DecodeError::InvalidData)?;
+        decoder.consume_freq(value, 1)?;
+        Ok(byte)
+    }
+
+    fn update(&mut self, history: &[u8], byte: u8) {
+        for key in Self::keys(history) {
+            self.contexts.entry(key).or_default().update(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    /// Text of random words
+    fn _words(len: usize) -> Vec<u8> {
+        const WORDS: &[&[u8]] = &[
+            b"the",
+            b"of",
+            b"and",
+            b"compression",
+            b"a",
+            b"to",
+            b"in",
+            b"prediction",
+            b"is",
+            b"partial",
+            b"matching",
+            b"context",
+            b"model",
+            b"symbol",
+            b"escape",
+            b"order",
+        ];
+        let mut rng = StdRng::seed_from_u64(2569);
+        let mut output = Vec::new();
+        while output.len() < len {
+            output.extend_from_slice(WORDS[rng.random_range(0..WORDS.len())]);
+            output.push(if rng.random_range(0..10) == 0 {
+                b'\n'
+            } else {
+                b' '
+            });
+        }
+        output.truncate(len);
+        output
+    }
+
+    #[test]
+    fn ppm_roundtrip() {
+        let mut inputs = vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abracadabra".to_vec(),
+            vec![0; 1000],
+            (0..=255).collect(),
+            fib_str(b'a', b'b', 0x3000),
+            random_alphabet(0, 255, 0x3000),
+            _words(0x8000),
+        ];
+        // a long run pushes the counts beyond the rescaling
+        inputs.push([vec![b'x'; 0x12345], _words(100)].concat());
+        for input in inputs {
+            let compressed = compress(&input).unwrap();
+            assert_eq!(
+                decompress(&compressed).unwrap(),
+                input,
+                "len {}",
+                input.len()
+            );
+        }
+    }
+
+    #[test]
+    fn ppm_ratio() {
+        let input = _words(0x8000);
+        let compressed = compress(&input).unwrap();
+        let deflated = deflate::deflate(&input, deflate::CompressionLevel::Best, None).unwrap();
+        assert!(
+            compressed.len() < deflated.len() * 9 / 10,
+            "ppm {} deflate {}",
+            compressed.len(),
+            deflated.len()
+        );
+    }
+
+    #[test]
+    fn ppm_invalid() {
+        let input = _words(1000);
+        let compressed = compress(&input).unwrap();
+        assert_eq!(
+            decompress_with_limit(&compressed, input.len()).unwrap(),
+            input
+        );
+        assert_eq!(
+            decompress_with_limit(&compressed, input.len() - 1),
+            Err(DecodeError::LimitExceeded)
+        );
+        assert_eq!(decompress(&[]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(decompress(&[0, 0]), Err(DecodeError::InvalidData));
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        assert_eq!(decompress(&trailing), Err(DecodeError::InvalidData));
+    }
+}
@@ -0,0 +1,226 @@
+//! Byte-oriented run-length encoding
+//!
+//! Two variants are provided:
+//!
+//! * PackBits, as in TIFF and the Macintosh. Each control byte `n` is followed by `n + 1` literal bytes if `n < 128`,
+//!   or by a byte repeated `257 - n` times if `n > 128`. The control byte `128` is skipped.
+//! * Zero runs, for sparse data such as bitmaps. Each byte other than zero is copied as is,
+//!   and a zero is followed by the number of the following zeros in LEB128,
+//!   so a run of any length takes a few bytes.
+//!
+//! ```
+//! use compress::rle;
+//!
+//! let input = b"aaaaaaaaaabcd";
+//! let encoded = rle::encode_packbits(input);
+//! assert_eq!(encoded, [0xf7, b'a', 0x02, b'b', b'c', b'd']);
+//! assert_eq!(rle::decode_packbits(&encoded).unwrap(), input);
+//! ```
+
+use crate::num::varint;
+use crate::*;
+
+/// Maximum number of bytes of a run or a literal sequence of PackBits
+const PACKBITS_MAX_LEN: usize = 128;
+
+/// Minimum run length that PackBits encodes as a run in the middle of literals
+const PACKBITS_MIN_RUN: usize = 3;
+
+/// Encodes the input with PackBits.
+pub fn encode_packbits(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + input.len().div_ceil(PACKBITS_MAX_LEN));
+    let mut literal_start = 0;
+    let mut cursor = 0;
+    while cursor < input.len() {
+        let byte = input[cursor];
+        let run = input[cursor..]
+            .iter()
+            .take(PACKBITS_MAX_LEN)
+            .take_while(|&&v| v == byte)
+            .count();
+        // A run of 2 is shorter as a run only if it does not interrupt literals.
+        if run >= PACKBITS_MIN_RUN || (run == 2 && literal_start == cursor) {
+            _flush_packbits_literals(&mut output, &input[literal_start..cursor]);
+            output.push((257 - run) as u8);
+            output.push(byte);
+            cursor += run;
+            literal_start = cursor;
+        } else {
+            cursor += run;
+        }
+    }
+    _flush_packbits_literals(&mut output, &input[literal_start..]);
+    output
+}
+
+fn _flush_packbits_literals(output: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(PACKBITS_MAX_LEN) {
+        output.push((chunk.len() - 1) as u8);
+        output.extend_from_slice(chunk);
+    }
+}
+
+/// Decodes the data encoded with PackBits.
+#[inline]
+pub fn decode_packbits(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decode_packbits_with_limit(input, usize::MAX)
+}
+
+/// Decodes the data encoded with PackBits.
+///
+/// Returns `DecodeError::LimitExceeded` if the decoded size exceeds `max_size`.
+pub fn decode_packbits_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut iter = input.iter().copied();
+    while let Some(control) = iter.next() {
+        match control {
+            0..=127 => {
+                let len = control as usize + 1;
+                if len > max_size - output.len() {
+                    return Err(DecodeError::LimitExceeded);
+                }
+                let start = input.len() - iter.len();
+                let literals = input
+                    .get(start..start + len)
+                    .ok_or(DecodeError::UnexpectedEof)?;
+                output.extend_from_slice(literals);
+                iter.nth(len - 1);
+            }
+            128 => {}
+            129..=255 => {
+                let len = 257 - control as usize;
+                if len > max_size - output.len() {
+                    return Err(DecodeError::LimitExceeded);
+                }
+                let byte = iter.next().ok_or(DecodeError::UnexpectedEof)?;
+                output.resize(output.len() + len, byte);
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Encodes the runs of zeros of the input.
+pub fn encode_zero_runs(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut cursor = 0;
+    while let Some(&byte) = input.get(cursor) {
+        output.push(byte);
+        cursor += 1;
+        if byte == 0 {
+            let run = input[cursor..].iter().take_while(|&&v| v == 0).count();
+            varint::encode_u64(run as u64, &mut output);
+            cursor += run;
+        }
+    }
+    output
+}
+
+/// Decodes the data encoded with [`encode_zero_runs`].
+#[inline]
+pub fn decode_zero_runs(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decode_zero_runs_with_limit(input, usize::MAX)
+}
+
+/// Decodes the data encoded with [`encode_zero_runs`].
+///
+/// Returns `DecodeError::LimitExceeded` if the decoded size exceeds `max_size`.
+pub fn decode_zero_runs_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut cursor = 0;
+    while let Some(&byte) = input.get(cursor) {
+        cursor += 1;
+        let len = if byte == 0 {
+            let (run, len) = varint::decode_u64(&input[cursor..])?;
+            cursor += len;
+            usize::try_from(run)
+                .ok()
+                .and_then(|v| v.checked_add(1))
+                .ok_or(DecodeError::LimitExceeded)?
+        } else {
+            1
+        };
+        if len > max_size - output.len() {
+            return Err(DecodeError::LimitExceeded);
+        }
+        output
+            .try_reserve(len)
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        output.resize(output.len() + len, byte);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_roundtrip() {
+        let mut sparse = vec![0; 0x10000];
+        for index in (0..sparse.len()).step_by(997) {
+            sparse[index] = index as u8 | 1;
+        }
+        let inputs = [
+            Vec::new(),
+            vec![0],
+            vec![0x55],
+            vec![0x55; 2],
+            vec![0x55; 128],
+            vec![0x55; 129],
+            vec![0; 1000],
+            b"aabccddd".to_vec(),
+            (0..=255).cycle().take(1000).collect(),
+            fib_str(b'a', b'b', 0x1000),
+            random_alphabet(0, 3, 0x1000),
+            sparse.clone(),
+        ];
+        for input in inputs.iter() {
+            let encoded = encode_packbits(input);
+            assert_eq!(decode_packbits(&encoded).unwrap(), *input);
+            assert!(encoded.len() <= input.len() + input.len().div_ceil(128));
+
+            let encoded = encode_zero_runs(input);
+            assert_eq!(decode_zero_runs(&encoded).unwrap(), *input);
+        }
+
+        assert_eq!(encode_packbits(&[0x55; 129]), [0x81, 0x55, 0x00, 0x55]);
+        assert_eq!(encode_packbits(b"abbc"), [0x03, b'a', b'b', b'b', b'c']);
+        assert_eq!(encode_packbits(b"bba"), [0xff, b'b', 0x00, b'a']);
+        assert_eq!(encode_zero_runs(&[1, 0, 0, 0, 2, 0]), [1, 0, 2, 2, 0, 0]);
+        assert!(encode_zero_runs(&sparse).len() < 0x10000 / 100);
+    }
+
+    #[test]
+    fn rle_invalid() {
+        // a no-op control byte
+        assert_eq!(decode_packbits(&[0x80, 0xfe, 0x61]).unwrap(), b"aaa");
+        assert_eq!(
+            decode_packbits(&[0x02, 0x61]),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(decode_packbits(&[0xfe]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(
+            decode_packbits_with_limit(&[0xfe, 0x61], 2),
+            Err(DecodeError::LimitExceeded)
+        );
+        assert_eq!(
+            decode_packbits_with_limit(&[0xfe, 0x61], 3).unwrap(),
+            b"aaa"
+        );
+
+        assert_eq!(decode_zero_runs(&[1, 0]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(
+            decode_zero_runs(&[0, 0x80]),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode_zero_runs_with_limit(&[0, 0xff, 0xff, 0x03], 0xffff),
+            Err(DecodeError::LimitExceeded)
+        );
+        assert_eq!(
+            decode_zero_runs_with_limit(&[0, 0xff, 0xff, 0x03], 0x10000).map(|v| v.len()),
+            Ok(0x10000)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Snappy block format
+
+use super::*;
+use crate::lz::lzss::{self, LZSS};
+use crate::num::varint;
+
+/// Encodes a Snappy block and appends it to `output`.
+///
+/// Returns `EncodeError::InvalidInput` if the input is too large.
+pub fn encode_block(input: &[u8], output: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let len = u32::try_from(input.len()).map_err(|_| EncodeError::InvalidInput)?;
+    varint::encode_u32(len, output);
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    let mut literal_start = 0;
+    let mut position = 0;
+    let config = lzss::Configuration::new(MAX_DISTANCE, LZSS::MAX_LEN)
+        .parser(lzss::Parser::Lazy)
+        .min_len(MIN_MATCH);
+    LZSS::encode(input, config, |lzss| {
+        match lzss {
+            LZSS::Literal(_) => position += 1,
+            LZSS::Match(matches) => {
+                let len = matches.len.get();
+                _write_literal(output, &input[literal_start..position]);
+                _write_copy(output, matches.distance.get(), len);
+                literal_start = position + len;
+                position += len;
+            }
+        }
+        Ok(())
+    })?;
+    _write_literal(output, &input[literal_start..]);
+
+    Ok(())
+}
+
+/// Returns the uncompressed length stored at the beginning of a Snappy block.
+pub fn decoded_len(input: &[u8]) -> Result<usize, DecodeError> {
+    varint::decode_u32(input).map(|(v, _)| v as usize)
+}
+
+/// Decodes a Snappy block and appends the result to `output`.
+///
+/// Returns `DecodeError::LimitExceeded` if the block decodes to more than `max_size` bytes.
+pub fn decode_block(
+    input: &[u8],
+    output: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), DecodeError> {
+    let (len, mut cursor) = varint::decode_u32(input)?;
+    let len = len as usize;
+    if len > max_size {
+        return Err(DecodeError::LimitExceeded);
+    }
+    let base = output.len();
+    let limit = base.checked_add(len).ok_or(DecodeError::LimitExceeded)?;
+
+    while cursor < input.len() {
+        let tag = input[cursor];
+        cursor += 1;
+        let (offset, copy_len) = match tag & 3 {
+            TAG_LITERAL => {
+                let mut literal_len = (tag >> 2) as usize;
+                if literal_len >= MAX_INLINE_LITERAL {
+                    let n_bytes = literal_len - MAX_INLINE_LITERAL + 1;
+                    literal_len = _read_le(input, &mut cursor, n_bytes)?;
+                }
+                if literal_len >= limit - output.len() {
+                    return Err(DecodeError::InvalidData);
+                }
+                let literal_len = literal_len + 1;
+                let literals = input
+                    .get(cursor..)
+                    .and_then(|v| v.get(..literal_len))
+                    .ok_or(DecodeError::UnexpectedEof)?;
+                output.extend_from_slice(literals);
+                cursor += literal_len;
+                continue;
+            }
+            TAG_COPY1 => {
+                let low = _read_le(input, &mut cursor, 1)?;
+                (
+                    ((tag as usize >> 5) << 8) | low,
+                    ((tag >> 2) & 7) as usize + MIN_MATCH,
+                )
+            }
+            TAG_COPY2 => (_read_le(input, &mut cursor, 2)?, (tag >> 2) as usize + 1),
+            _ => (_read_le(input, &mut cursor, 4)?, (tag >> 2) as usize + 1),
+        };
+        if offset == 0 || offset > output.len() - base || copy_len > limit - output.len() {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let start = output.len() - offset;
+        if offset >= copy_len {
+            output.extend_from_within(start..start + copy_len);
+        } else {
+            output.reserve(copy_len);
+            for i in start..start + copy_len {
+                output.push(output[i]);
+            }
+        }
+    }
+
+    if output.len() != limit {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok(())
+}
+
+fn _write_literal(output: &mut Vec<u8>, literals: &[u8]) {
+    let Some(len_code) = literals.len().checked_sub(1) else {
+        return;
+    };
+    if len_code < MAX_INLINE_LITERAL {
+        output.push(((len_code as u8) << 2) | TAG_LITERAL);
+    } else {
+        // the input is limited to `u32::MAX` bytes, so the length fits in 4 bytes
+        let len_code = len_code as u32;
+        let n_bytes = (u32::BITS - len_code.leading_zeros()).div_ceil(8) as usize;
+        output.push((((MAX_INLINE_LITERAL + n_bytes - 1) as u8) << 2) | TAG_LITERAL);
+        output.extend_from_slice(&len_code.to_le_bytes()[..n_bytes]);
+    }
+    output.extend_from_slice(literals);
+}
+
+fn _write_copy(output: &mut Vec<u8>, offset: usize, mut len: usize) {
+    // Long matches are split so that the last copy is not shorter than `MIN_MATCH`.
+    while len >= MAX_COPY_LEN + MIN_MATCH {
+        _write_copy_short(output, offset, MAX_COPY_LEN);
+        len -= MAX_COPY_LEN;
+    }
+    if len > MAX_COPY_LEN {
+        _write_copy_short(output, offset, MAX_COPY_LEN - MIN_MATCH);
+        len -= MAX_COPY_LEN - MIN_MATCH;
+    }
+    _write_copy_short(output, offset, len);
+}
+
+#[inline]
+fn _write_copy_short(output: &mut Vec<u8>, offset: usize, len: usize) {
+    if (MIN_MATCH..=MAX_COPY1_LEN).contains(&len) && offset <= MAX_COPY1_OFFSET {
+        output.push(((offset >> 8) << 5) as u8 | (((len - MIN_MATCH) as u8) << 2) | TAG_COPY1);
+        output.push(offset as u8);
+    } else if offset <= 0xffff {
+        output.push((((len - 1) as u8) << 2) | TAG_COPY2);
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+    } else {
+        output.push((((len - 1) as u8) << 2) | TAG_COPY4);
+        output.extend_from_slice(&(offset as u32).to_le_bytes());
+    }
+}
+
+/// Reads a little endian integer of `n_bytes` bytes.
+#[inline]
+fn _read_le(input: &[u8], cursor: &mut usize, n_bytes: usize) -> Result<usize, DecodeError> {
+    let bytes = input
+        .get(*cursor..*cursor + n_bytes)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    *cursor += n_bytes;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, &byte| (acc << 8) | byte as usize))
+}
@@ -0,0 +1,28 @@
+//! CRC-32C (Castagnoli) used by the Snappy framing format
+
+use crate::hash::crc32::Reflected;
+
+type Crc = Reflected<0x82F6_3B78>;
+
+/// Constant added to the rotated CRC by [`mask`]
+const MASK_DELTA: u32 = 0xA282_EAD8;
+
+/// Updates the CRC with the data.
+///
+/// Starts with [`checksum`] of an empty slice, which is `0`.
+pub fn update(crc: u32, data: &[u8]) -> u32 {
+    Crc::update(crc, data)
+}
+
+/// CRC-32C implementation
+#[inline]
+pub fn checksum(data: &[u8]) -> u32 {
+    Crc::checksum(data)
+}
+
+/// Masks the CRC as stored in the framing format,
+/// so that the CRC of data containing CRCs is not degenerate.
+#[inline]
+pub const fn mask(crc: u32) -> u32 {
+    crc.rotate_right(15).wrapping_add(MASK_DELTA)
+}
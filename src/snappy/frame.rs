@@ -0,0 +1,108 @@
+//! Snappy framing format
+
+use super::*;
+
+/// Compresses the input into a Snappy framed stream.
+///
+/// The input is split into chunks of [`MAX_CHUNK_SIZE`] bytes,
+/// and a chunk that does not shrink is stored uncompressed.
+pub fn compress(input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+    let mut output = Vec::new();
+    _write_chunk_header(
+        &mut output,
+        CHUNK_STREAM_IDENTIFIER,
+        STREAM_IDENTIFIER.len(),
+    );
+    output.extend_from_slice(STREAM_IDENTIFIER);
+
+    let mut block = Vec::new();
+    for chunk in input.chunks(MAX_CHUNK_SIZE) {
+        block.clear();
+        encode_block(chunk, &mut block)?;
+        let (chunk_type, data) = if block.len() < chunk.len() {
+            (CHUNK_COMPRESSED, block.as_slice())
+        } else {
+            (CHUNK_UNCOMPRESSED, chunk)
+        };
+        _write_chunk_header(&mut output, chunk_type, 4 + data.len());
+        output.extend_from_slice(&crc32c::mask(crc32c::checksum(chunk)).to_le_bytes());
+        output.extend_from_slice(data);
+    }
+
+    Ok(output)
+}
+
+/// Decompresses a Snappy framed stream, which may consist of concatenated streams.
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses a Snappy framed stream, which may consist of concatenated streams.
+///
+/// Returns `DecodeError::LimitExceeded` if the decompressed size exceeds `max_size`.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut cursor = 0;
+    while cursor < input.len() {
+        // a stream starts with the stream identifier
+        let chunk_type = input[cursor];
+        if cursor == 0 && chunk_type != CHUNK_STREAM_IDENTIFIER {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        let header = input
+            .get(cursor + 1..cursor + 4)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        cursor += 4;
+        let data = input
+            .get(cursor..cursor + len)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        cursor += len;
+
+        match chunk_type {
+            CHUNK_STREAM_IDENTIFIER if data != STREAM_IDENTIFIER => {
+                return Err(DecodeError::UnsupportedFormat);
+            }
+            CHUNK_STREAM_IDENTIFIER | CHUNK_PADDING => {}
+            CHUNK_COMPRESSED | CHUNK_UNCOMPRESSED => {
+                if data.len() < 4 {
+                    return Err(DecodeError::InvalidData);
+                }
+                let crc = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                let data = &data[4..];
+                let chunk_start = output.len();
+                let max_chunk_size = max_size - chunk_start;
+                if chunk_type == CHUNK_COMPRESSED {
+                    if decoded_len(data)? > MAX_CHUNK_SIZE {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    decode_block(data, &mut output, max_chunk_size)?;
+                } else {
+                    if data.len() > MAX_CHUNK_SIZE {
+                        return Err(DecodeError::InvalidData);
+                    }
+                    if data.len() > max_chunk_size {
+                        return Err(DecodeError::LimitExceeded);
+                    }
+                    output.extend_from_slice(data);
+                }
+                if crc32c::mask(crc32c::checksum(&output[chunk_start..])) != crc {
+                    return Err(DecodeError::ChecksumMismatch);
+                }
+            }
+            _ if CHUNK_RESERVED_UNSKIPPABLE.contains(&chunk_type) => {
+                return Err(DecodeError::UnsupportedFormat);
+            }
+            // reserved skippable chunks
+            _ => {}
+        }
+    }
+    Ok(output)
+}
+
+#[inline]
+fn _write_chunk_header(output: &mut Vec<u8>, chunk_type: u8, len: usize) {
+    output.push(chunk_type);
+    output.extend_from_slice(&(len as u32).to_le_bytes()[..3]);
+}
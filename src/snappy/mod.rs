@@ -0,0 +1,57 @@
+//! Snappy compression format
+//!
+//! Supports the block format and the framing format with CRC-32C checksums.
+//! Blocks are encoded with offsets up to 64KB, like the reference implementation.
+//!
+//! See also:
+//!
+//! * <https://github.com/google/snappy/blob/main/format_description.txt>
+//! * <https://github.com/google/snappy/blob/main/framing_format.txt>
+
+use crate::*;
+
+#[cfg(test)]
+mod tests;
+
+pub mod crc32c;
+
+mod block;
+mod frame;
+pub use block::*;
+pub use frame::*;
+
+/// Minimum match length written by the encoder
+const MIN_MATCH: usize = 4;
+
+/// Maximum match distance written by the encoder
+const MAX_DISTANCE: usize = 0xffff;
+
+const TAG_LITERAL: u8 = 0b00;
+const TAG_COPY1: u8 = 0b01;
+const TAG_COPY2: u8 = 0b10;
+const TAG_COPY4: u8 = 0b11;
+
+/// Maximum length of a literal whose length is stored in the tag byte
+const MAX_INLINE_LITERAL: usize = 60;
+
+/// Maximum length of a copy with 2 or 4 byte offsets
+const MAX_COPY_LEN: usize = 64;
+
+/// Maximum length of a copy with a 1 byte offset
+const MAX_COPY1_LEN: usize = 11;
+
+/// Maximum offset of a copy with a 1 byte offset
+const MAX_COPY1_OFFSET: usize = 0x7ff;
+
+const CHUNK_COMPRESSED: u8 = 0x00;
+const CHUNK_UNCOMPRESSED: u8 = 0x01;
+const CHUNK_PADDING: u8 = 0xfe;
+const CHUNK_STREAM_IDENTIFIER: u8 = 0xff;
+
+/// Chunks of these types are reserved and must be rejected
+const CHUNK_RESERVED_UNSKIPPABLE: core::ops::RangeInclusive<u8> = 0x02..=0x7f;
+
+const STREAM_IDENTIFIER: &[u8] = b"sNaPpY";
+
+/// Maximum size of the uncompressed data in a chunk
+pub const MAX_CHUNK_SIZE: usize = 0x1_0000;
@@ -0,0 +1,147 @@
+use super::*;
+
+const HELLO: &[u8] = b"Hello, Snappy! Hello, Snappy!\n";
+
+/// `HELLO` in a framed stream, with a literal, a copy with a 2 byte offset and a literal
+const HELLO_SZ: &[u8] = &[
+    0xff, 0x06, 0x00, 0x00, 0x73, 0x4e, 0x61, 0x50, 0x70, 0x59, 0x00, 0x1a, 0x00, 0x00, 0x02, 0xbf,
+    0x7a, 0x45, 0x1e, 0x38, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x53, 0x6e, 0x61, 0x70, 0x70,
+    0x79, 0x21, 0x20, 0x36, 0x0f, 0x00, 0x00, 0x0a,
+];
+
+#[test]
+fn snappy_crc32c() {
+    assert_eq!(crc32c::checksum(b""), 0);
+    assert_eq!(crc32c::checksum(b"123456789"), 0xE306_9283);
+    assert_eq!(crc32c::checksum(&[0; 32]), 0x8A91_36AA);
+    assert_eq!(crc32c::checksum(&[0xff; 32]), 0x62A8_AB43);
+    assert_eq!(crc32c::checksum(&(0..32).collect::<Vec<u8>>()), 0x46DD_794E);
+    assert_eq!(
+        crc32c::update(crc32c::checksum(b"1234"), b"56789"),
+        0xE306_9283
+    );
+}
+
+#[test]
+fn snappy_block() {
+    // literal, copy with a 1 byte offset
+    let mut decoded = Vec::new();
+    decode_block(
+        &[0x0c, 0x0c, b'a', b'b', b'c', b'd', 0x11, 0x04],
+        &mut decoded,
+        12,
+    )
+    .unwrap();
+    assert_eq!(decoded, b"abcdabcdabcd");
+
+    // literal with a 1 byte length, overlapping copies with 2 and 4 byte offsets
+    let mut input = vec![0x80, 0x01, 0xf0, 63];
+    input.extend_from_slice(&[b'x'; 64]);
+    input.extend_from_slice(&[(31 << 2) | 0b10, 0x01, 0x00]);
+    input.extend_from_slice(&[(31 << 2) | 0b11, 0x40, 0x00, 0x00, 0x00]);
+    let mut decoded = Vec::new();
+    decode_block(&input, &mut decoded, 128).unwrap();
+    assert_eq!(decoded, [b'x'; 128]);
+
+    assert_eq!(decoded_len(&input), Ok(128));
+    assert_eq!(
+        decode_block(&input, &mut Vec::new(), 127),
+        Err(DecodeError::LimitExceeded)
+    );
+    // offset 0
+    assert_eq!(
+        decode_block(&[0x05, 0x00, b'a', 0x01, 0x00], &mut Vec::new(), 5),
+        Err(DecodeError::InvalidData)
+    );
+    // offset beyond the block
+    assert_eq!(
+        decode_block(&[0x05, 0x00, b'a', 0x01, 0x02], &mut b"xx".to_vec(), 5),
+        Err(DecodeError::InvalidData)
+    );
+    // shorter than the stored length
+    assert_eq!(
+        decode_block(&[0x02, 0x00, b'a'], &mut Vec::new(), 5),
+        Err(DecodeError::InvalidData)
+    );
+    assert_eq!(
+        decode_block(&[0x02, 0x04, b'a'], &mut Vec::new(), 5),
+        Err(DecodeError::UnexpectedEof)
+    );
+
+    let mut input = Vec::new();
+    input.extend_from_slice(&random_alphabet(0, 255, 0x3000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x20000));
+    input.extend_from_slice(&[0; 0x1000]);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x20000));
+    for len in [0, 1, 3, 4, 60, 61, 300, 0x10000, input.len()] {
+        let input = &input[..len];
+        let mut encoded = Vec::new();
+        encode_block(input, &mut encoded).unwrap();
+        assert_eq!(decoded_len(&encoded), Ok(len));
+        let mut decoded = b"prefix".to_vec();
+        decode_block(&encoded, &mut decoded, len).unwrap();
+        assert_eq!(&decoded[6..], input);
+    }
+}
+
+#[test]
+fn snappy_decompress_reference() {
+    assert_eq!(decompress(HELLO_SZ).unwrap(), HELLO);
+
+    // concatenated with padding and a skippable chunk
+    let mut input = HELLO_SZ.to_vec();
+    input.extend_from_slice(&[0xfe, 0x02, 0x00, 0x00, 0x00, 0x00]);
+    input.extend_from_slice(&[0x80, 0x01, 0x00, 0x00, 0x55]);
+    input.extend_from_slice(HELLO_SZ);
+    assert_eq!(decompress(&input).unwrap(), [HELLO, HELLO].concat());
+
+    // reserved unskippable chunk
+    let mut input = HELLO_SZ.to_vec();
+    input.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+    assert_eq!(decompress(&input), Err(DecodeError::UnsupportedFormat));
+
+    // no stream identifier
+    assert_eq!(
+        decompress(&HELLO_SZ[10..]),
+        Err(DecodeError::UnsupportedFormat)
+    );
+
+    // corrupted checksum
+    let mut input = HELLO_SZ.to_vec();
+    input[14] ^= 1;
+    assert_eq!(decompress(&input), Err(DecodeError::ChecksumMismatch));
+
+    assert_eq!(
+        decompress(&HELLO_SZ[..HELLO_SZ.len() - 1]),
+        Err(DecodeError::UnexpectedEof)
+    );
+    assert_eq!(
+        decompress_with_limit(HELLO_SZ, HELLO.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn snappy_roundtrip() {
+    let mut input = Vec::new();
+    input.extend_from_slice(&random_alphabet(0, 255, 0x3000));
+    input.extend_from_slice(&fib_str(b'a', b'b', 0x20000));
+    input.extend_from_slice(&[0; 0x1000]);
+    input.extend_from_slice(&random_alphabet(b'a', b'h', 0x20000));
+
+    let encoded = compress(&input).unwrap();
+    assert!(encoded.len() < input.len() / 2);
+    assert_eq!(decompress(&encoded).unwrap(), input);
+
+    // incompressible chunks are stored
+    let input = random_alphabet(0, 255, 0x1000);
+    let encoded = compress(&input).unwrap();
+    assert_eq!(encoded[10], CHUNK_UNCOMPRESSED);
+    assert_eq!(decompress(&encoded).unwrap(), input);
+
+    for len in [0, 1, 12, 13, 17, 100] {
+        let input = &HELLO.repeat(4)[..len];
+        let encoded = compress(input).unwrap();
+        assert_eq!(decompress(&encoded).unwrap(), input);
+    }
+}
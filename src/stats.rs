@@ -2,6 +2,7 @@
 
 use crate::*;
 use core::cmp;
+use core::ops::{Index, IndexMut};
 
 pub trait CountFreq<K: Ord> {
     fn count_freq(&mut self, key: K);
@@ -31,3 +32,684 @@ impl<K: Ord> IntoFreqTable<K> for BTreeMap<K, usize> {
         vec
     }
 }
+
+/// Frequency table of `N` symbols
+///
+/// ```
+/// use compress::stats::Histogram;
+///
+/// let mut histogram = Histogram::<4>::new();
+/// histogram.add(0);
+/// histogram.add(1);
+/// histogram.merge(&Histogram::from_counts([0, 0, 1, 1]));
+/// assert_eq!(histogram.total(), 4);
+/// assert_eq!(histogram.entropy(), 2.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram<const N: usize> {
+    counts: [usize; N],
+}
+
+impl<const N: usize> Histogram<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { counts: [0; N] }
+    }
+
+    #[inline]
+    pub const fn from_counts(counts: [usize; N]) -> Self {
+        Self { counts }
+    }
+
+    /// Counts a symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the symbol is `N` or greater.
+    #[inline]
+    pub fn add(&mut self, symbol: usize) {
+        self.counts[symbol] += 1;
+    }
+
+    /// Counts a symbol `count` times.
+    #[inline]
+    pub fn add_count(&mut self, symbol: usize, count: usize) {
+        self.counts[symbol] += count;
+    }
+
+    /// Adds the counts of another histogram.
+    #[inline]
+    pub fn merge(&mut self, other: &Self) {
+        for (p, q) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *p += *q;
+        }
+    }
+
+    /// Returns the number of symbols counted.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the entropy in bits per symbol.
+    #[inline]
+    pub fn entropy(&self) -> f64 {
+        entropy::entropy_of(&self.counts)
+    }
+
+    /// Normalizes the counts so that they sum to `1 << table_log`, for the FSE and rANS coders.
+    #[inline]
+    pub fn normalize(
+        &self,
+        table_log: u8,
+    ) -> Result<entropy::fse::NormalizedFrequencies, EncodeError> {
+        entropy::fse::NormalizedFrequencies::new(&self.counts, table_log)
+    }
+
+    #[inline]
+    pub const fn counts(&self) -> &[usize; N] {
+        &self.counts
+    }
+
+    #[inline]
+    pub const fn as_slice(&self) -> &[usize] {
+        &self.counts
+    }
+
+    #[inline]
+    pub const fn into_counts(self) -> [usize; N] {
+        self.counts
+    }
+}
+
+impl Histogram<256> {
+    /// Returns the histogram of byte values.
+    #[inline]
+    pub fn of_bytes(bytes: &[u8]) -> Self {
+        Self::from_counts(histogram_of_bytes(bytes))
+    }
+
+    /// Counts the byte values.
+    #[inline]
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        count_bytes(&mut self.counts, bytes);
+    }
+}
+
+impl<const N: usize> Default for Histogram<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> AsRef<[usize]> for Histogram<N> {
+    #[inline]
+    fn as_ref(&self) -> &[usize] {
+        &self.counts
+    }
+}
+
+impl<const N: usize> Index<usize> for Histogram<N> {
+    type Output = usize;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.counts[index]
+    }
+}
+
+impl<const N: usize> IndexMut<usize> for Histogram<N> {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.counts[index]
+    }
+}
+
+/// Number of partial tables used by [`count_bytes`]
+const HISTOGRAM_LANES: usize = 4;
+
+/// Returns the frequency table of byte values.
+#[inline]
+pub fn histogram_of_bytes(bytes: &[u8]) -> [usize; 256] {
+    let mut freq_table = [0; 256];
+    count_bytes(&mut freq_table, bytes);
+    freq_table
+}
+
+/// Adds the frequency of each byte value to the frequency table.
+///
+/// Consecutive identical bytes make a scalar loop stall on the same counter,
+/// so the counts are distributed over multiple partial tables and merged at the end.
+/// With the `simd` feature, blocks of 16 identical bytes are also counted at once.
+pub fn count_bytes(freq_table: &mut [usize; 256], bytes: &[u8]) {
+    // The partial tables are `u32` to keep them in L1 cache, so they are flushed before overflowing.
+    const MAX_CHUNK: usize = u32::MAX as usize & !15;
+
+    let mut lanes = [[0u32; 256]; HISTOGRAM_LANES];
+    for chunk in bytes.chunks(MAX_CHUNK) {
+        let mut iter = chunk.chunks_exact(16);
+        for block in iter.by_ref() {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            if sse2::is_uniform(block) {
+                lanes[0][block[0] as usize] += 16;
+                continue;
+            }
+            for bytes in block.chunks_exact(8) {
+                let value = u64::from_le_bytes(bytes.try_into().unwrap());
+                lanes[0][(value & 0xff) as usize] += 1;
+                lanes[1][((value >> 8) & 0xff) as usize] += 1;
+                lanes[2][((value >> 16) & 0xff) as usize] += 1;
+                lanes[3][((value >> 24) & 0xff) as usize] += 1;
+                lanes[0][((value >> 32) & 0xff) as usize] += 1;
+                lanes[1][((value >> 40) & 0xff) as usize] += 1;
+                lanes[2][((value >> 48) & 0xff) as usize] += 1;
+                lanes[3][(value >> 56) as usize] += 1;
+            }
+        }
+        for &byte in iter.remainder() {
+            lanes[0][byte as usize] += 1;
+        }
+
+        for lane in lanes.iter_mut() {
+            for (p, q) in freq_table.iter_mut().zip(lane.iter()) {
+                *p += *q as usize;
+            }
+            lane.fill(0);
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    /// Returns `true` if all 16 bytes of the block are the same.
+    #[inline]
+    pub fn is_uniform(block: &[u8]) -> bool {
+        assert_eq!(block.len(), 16);
+        unsafe {
+            // Safety: SSE2 is always available on x86_64, and the load is unaligned.
+            let bytes = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            let first = _mm_set1_epi8(block[0] as i8);
+            _mm_movemask_epi8(_mm_cmpeq_epi8(bytes, first)) == 0xffff
+        }
+    }
+}
+
+/// Class of content detected by [`classify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentClass {
+    /// Text with few control characters
+    Text,
+    /// Structured binary data
+    Binary,
+    /// Compressed or encrypted data, which is unlikely to compress further
+    AlreadyCompressed,
+    /// Data dominated by a single byte value, such as zero-filled regions
+    Sparse,
+}
+
+/// Magic numbers of compressed formats detected by [`classify`]
+const COMPRESSED_MAGICS: &[&[u8]] = &[
+    b"\x1f\x8b",           // gzip
+    b"PK\x03\x04",         // zip
+    b"BZh",                // bzip2
+    b"\xfd7zXZ\x00",       // xz
+    b"\x28\xb5\x2f\xfd",   // zstd
+    b"\x04\x22\x4d\x18",   // lz4 frame
+    b"7z\xbc\xaf\x27\x1c", // 7-Zip
+    b"\x89PNG\r\n\x1a\n",  // PNG
+    b"\xff\xd8\xff",       // JPEG
+    b"GIF8",               // GIF
+    b"stk1",               // stk1 container
+];
+
+/// Entropy in bits per byte above which the data is considered already compressed
+const COMPRESSED_MIN_ENTROPY: f64 = 7.5;
+
+/// Classifies the content of the input from its magic number, byte histogram and entropy.
+///
+/// This is a heuristic that helps to decide how to compress the input.
+/// An empty input is `Sparse`.
+pub fn classify(input: &[u8]) -> ContentClass {
+    if COMPRESSED_MAGICS
+        .iter()
+        .any(|magic| input.starts_with(magic))
+    {
+        return ContentClass::AlreadyCompressed;
+    }
+
+    let freq_table = histogram_of_bytes(input);
+    let max_freq = freq_table.iter().copied().max().unwrap_or_default();
+    if max_freq * 4 >= input.len() * 3 {
+        return ContentClass::Sparse;
+    }
+    if entropy::entropy_of(&freq_table) >= COMPRESSED_MIN_ENTROPY {
+        return ContentClass::AlreadyCompressed;
+    }
+
+    // Control characters other than tab, line feed, form feed and carriage return
+    let controls = freq_table[..0x20]
+        .iter()
+        .enumerate()
+        .filter(|&(byte, _)| !matches!(byte as u8, b'\t' | b'\n' | 0x0c | b'\r'))
+        .map(|(_, &count)| count)
+        .sum::<usize>()
+        + freq_table[0x7f];
+    if controls * 100 <= input.len() {
+        ContentClass::Text
+    } else {
+        ContentClass::Binary
+    }
+}
+
+/// Frequency tables of byte values conditioned on the preceding byte
+///
+/// This tells whether an order-1 context model would compress the data better than the order-0 entropy coders.
+/// The first byte is counted in the context of a zero byte.
+#[derive(Clone)]
+pub struct ContextModel {
+    tables: Box<[[usize; 256]; 256]>,
+    last: u8,
+    total: usize,
+}
+
+impl ContextModel {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            tables: Box::new([[0; 256]; 256]),
+            last: 0,
+            total: 0,
+        }
+    }
+
+    /// Counts the bytes of the input.
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut model = Self::new();
+        model.add(bytes);
+        model
+    }
+
+    /// Counts the bytes, following the bytes counted so far.
+    pub fn add(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.tables[self.last as usize][byte as usize] += 1;
+            self.last = byte;
+        }
+        self.total += bytes.len();
+    }
+
+    /// Returns the frequency table of the bytes following `context`.
+    #[inline]
+    pub fn freq_table(&self, context: u8) -> &[usize; 256] {
+        &self.tables[context as usize]
+    }
+
+    /// Returns the number of bytes counted.
+    #[inline]
+    pub const fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the order-0 frequency table of the bytes counted.
+    pub fn order0_freq_table(&self) -> [usize; 256] {
+        let mut freq_table = [0; 256];
+        for table in self.tables.iter() {
+            _add_freq(&mut freq_table, table);
+        }
+        freq_table
+    }
+
+    /// Returns the conditional entropy in bits per byte given the preceding byte.
+    pub fn conditional_entropy(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.tables
+            .iter()
+            .map(|table| {
+                let count = table.iter().sum::<usize>();
+                if count > 0 {
+                    count as f64 * entropy::entropy_of(table)
+                } else {
+                    0.0
+                }
+            })
+            .sum::<f64>()
+            / self.total as f64
+    }
+}
+
+impl Default for ContextModel {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the order-1 entropy of the input in bits per byte, that is, the entropy of each byte given the preceding byte.
+///
+/// It never exceeds the order-0 entropy of [`entropy::entropy_of_bytes`],
+/// and the difference estimates the gain of context modeling.
+/// Note that the estimate is optimistic for short inputs, since each context has few samples.
+#[inline]
+pub fn entropy_order1(bytes: &[u8]) -> f64 {
+    ContextModel::from_bytes(bytes).conditional_entropy()
+}
+
+/// Returns the entropy of each window sliding over the input.
+///
+/// The window of `window_size` bytes moves by `step` bytes,
+/// and the `n`th value is the entropy of `input[n * step..n * step + window_size]`.
+/// Returns an empty vector if the input is shorter than the window.
+pub fn entropy_series(input: &[u8], window_size: usize, step: usize) -> Vec<f64> {
+    let step = step.max(1);
+    let window_size = window_size.max(step);
+    let mut result = Vec::new();
+    if input.len() < window_size {
+        return result;
+    }
+
+    let mut freq_table = histogram_of_bytes(&input[..window_size]);
+    let mut start = 0;
+    loop {
+        result.push(entropy::entropy_of(&freq_table));
+        let next = start + step;
+        if next + window_size > input.len() {
+            break;
+        }
+        for &byte in &input[start..next] {
+            freq_table[byte as usize] -= 1;
+        }
+        count_bytes(
+            &mut freq_table,
+            &input[start + window_size..next + window_size],
+        );
+        start = next;
+    }
+    result
+}
+
+/// Suggests positions where the distribution of byte values changes.
+///
+/// For each candidate position (a multiple of `step`), the `window_size` bytes before and after
+/// it are compared, and the gain is the number of bits saved by coding them with separate
+/// frequency tables instead of a shared one.
+/// Positions whose gain exceeds `min_gain_bits` and is a local maximum are returned in ascending order,
+/// at least `window_size` bytes apart from each other.
+pub fn suggest_splits(
+    input: &[u8],
+    window_size: usize,
+    step: usize,
+    min_gain_bits: f64,
+) -> Vec<usize> {
+    let step = step.max(1);
+    let n_steps = (window_size / step).max(1);
+    let window_size = n_steps * step;
+    let mut result = Vec::new();
+    if input.len() < window_size * 2 {
+        return result;
+    }
+
+    let chunks = input
+        .chunks(step)
+        .map(histogram_of_bytes)
+        .collect::<Vec<_>>();
+
+    let mut left = [0; 256];
+    let mut right = [0; 256];
+    for chunk in &chunks[..n_steps] {
+        _add_freq(&mut left, chunk);
+    }
+    for chunk in &chunks[n_steps..n_steps * 2] {
+        _add_freq(&mut right, chunk);
+    }
+
+    // (position, gain) of the best candidate not yet adopted
+    let mut candidate: Option<(usize, f64)> = None;
+    let mut index = n_steps;
+    loop {
+        let position = index * step;
+        let gain = _split_gain(&left, &right);
+        if gain > min_gain_bits {
+            match candidate {
+                Some((_, best)) if best >= gain => {}
+                _ => candidate = Some((position, gain)),
+            }
+        }
+        if let Some((best_position, _)) = candidate
+            && position >= best_position + window_size
+        {
+            result.push(best_position);
+            candidate = None;
+        }
+
+        if index + n_steps >= chunks.len() {
+            break;
+        }
+        _sub_freq(&mut left, &chunks[index - n_steps]);
+        _add_freq(&mut left, &chunks[index]);
+        _sub_freq(&mut right, &chunks[index]);
+        _add_freq(&mut right, &chunks[index + n_steps]);
+        index += 1;
+    }
+    if let Some((best_position, _)) = candidate {
+        result.push(best_position);
+    }
+    result
+}
+
+/// Returns content-defined boundaries of the input in ascending order, as `gzip --rsyncable` does.
+///
+/// A position is a boundary if the rolling hash of the `window_size` bytes before it is a multiple of `window_size`,
+/// so the boundaries move with the content when bytes are inserted or removed elsewhere.
+/// Unlike the plain sum of bytes of gzip, the hash is evenly distributed also for data with a narrow range of values.
+/// Boundaries are at least `min_len` bytes apart from each other and from the start of the input,
+/// and neither end of the input is included.
+pub fn content_defined_boundaries(input: &[u8], window_size: usize, min_len: usize) -> Vec<usize> {
+    let window_size = window_size.max(1);
+    let mut result = Vec::new();
+    let mut hash = 0u64;
+    let mut last = 0;
+    for (index, &byte) in input.iter().enumerate() {
+        hash = hash.wrapping_add(_rolling_hash_of(byte));
+        if index >= window_size {
+            hash = hash.wrapping_sub(_rolling_hash_of(input[index - window_size]));
+        }
+        let position = index + 1;
+        if position - last >= min_len.max(1)
+            && position < input.len()
+            && hash.is_multiple_of(window_size as u64)
+        {
+            result.push(position);
+            last = position;
+        }
+    }
+    result
+}
+
+/// Scrambles a byte for the rolling hash of [`content_defined_boundaries`].
+#[inline]
+const fn _rolling_hash_of(byte: u8) -> u64 {
+    let x = (byte as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x ^ (x >> 29)
+}
+
+/// Number of bits saved by coding `left` and `right` with separate frequency tables.
+fn _split_gain(left: &[usize; 256], right: &[usize; 256]) -> f64 {
+    let mut merged = *left;
+    _add_freq(&mut merged, right);
+    _cost_of(&merged) - _cost_of(left) - _cost_of(right)
+}
+
+/// Total number of bits of the data estimated from its entropy.
+#[inline]
+fn _cost_of(freq_table: &[usize; 256]) -> f64 {
+    freq_table.iter().sum::<usize>() as f64 * entropy::entropy_of(freq_table)
+}
+
+#[inline]
+fn _add_freq(freq_table: &mut [usize; 256], other: &[usize; 256]) {
+    for (p, q) in freq_table.iter_mut().zip(other.iter()) {
+        *p += *q;
+    }
+}
+
+#[inline]
+fn _sub_freq(freq_table: &mut [usize; 256], other: &[usize; 256]) {
+    for (p, q) in freq_table.iter_mut().zip(other.iter()) {
+        *p -= *q;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut input = random_alphabet(0, 255, 0x1234);
+        input.extend_from_slice(&[0x55; 0x567]);
+        input.extend_from_slice(&fib_str(1, 2, 0x89));
+
+        for len in [0, 1, 7, 8, 9, 15, 16, 17, 0x1240, input.len()] {
+            let input = &input[..len];
+            let mut expected = [0usize; 256];
+            for &byte in input {
+                expected[byte as usize] += 1;
+            }
+            assert_eq!(histogram_of_bytes(input), expected);
+        }
+    }
+
+    #[test]
+    fn classify_content() {
+        let text = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit,\r\n\tsed do eiusmod tempor.\n"
+            .repeat(50);
+        assert_eq!(classify(&text), ContentClass::Text);
+        assert_eq!(
+            classify("\u{3042}\u{3044}\u{3046}\u{3048}\u{304a} 0123456789".as_bytes()),
+            ContentClass::Text
+        );
+
+        let binary = (0..0x4000u32)
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(classify(&binary), ContentClass::Binary);
+
+        let random = random_alphabet(0, 255, 0x4000);
+        assert_eq!(classify(&random), ContentClass::AlreadyCompressed);
+        let mut gzip = b"\x1f\x8b\x08\x00".to_vec();
+        gzip.extend_from_slice(&text);
+        assert_eq!(classify(&gzip), ContentClass::AlreadyCompressed);
+
+        let mut sparse = vec![0u8; 0x4000];
+        sparse[0x100..0x400].copy_from_slice(&random[..0x300]);
+        assert_eq!(classify(&sparse), ContentClass::Sparse);
+        assert_eq!(classify(b""), ContentClass::Sparse);
+    }
+
+    #[test]
+    fn order1_entropy() {
+        assert_eq!(entropy_order1(b""), 0.0);
+
+        // each byte is determined by the preceding one
+        let alternating = b"ab".repeat(1000);
+        assert!((entropy::entropy_of_bytes(&alternating) - 1.0).abs() < 1e-9);
+        assert!(entropy_order1(&alternating) < 0.01);
+
+        let text = fib_str(b'a', b'b', 0x4000);
+        assert!(entropy_order1(&text) < entropy::entropy_of_bytes(&text) * 0.8);
+
+        let random = random_alphabet(b'a', b'p', 0x10000);
+        let order0 = entropy::entropy_of_bytes(&random);
+        assert!(entropy_order1(&random) <= order0 && entropy_order1(&random) > order0 - 0.1);
+
+        let mut model = ContextModel::default();
+        model.add(&text[..1000]);
+        model.add(&text[1000..]);
+        assert_eq!(model.total(), text.len());
+        assert_eq!(model.order0_freq_table(), histogram_of_bytes(&text));
+        assert_eq!(model.conditional_entropy(), entropy_order1(&text));
+        // "bb" does not appear in the Fibonacci word
+        assert_eq!(model.freq_table(b'b')[b'b' as usize], 0);
+        assert_eq!(model.freq_table(0)[text[0] as usize], 1);
+    }
+
+    #[test]
+    fn histogram_type() {
+        let input = random_alphabet(b'a', b'h', 0x1000);
+        let mut histogram = Histogram::of_bytes(&input[..0x800]);
+        histogram.merge(&Histogram::of_bytes(&input[0x800..]));
+        assert_eq!(histogram.counts(), &histogram_of_bytes(&input));
+        assert_eq!(histogram.total(), input.len());
+        assert_eq!(histogram.entropy(), entropy::entropy_of_bytes(&input));
+
+        let mut bytes = Histogram::default();
+        bytes.add_bytes(&input);
+        assert_eq!(bytes, histogram);
+        bytes.add_count(0, 3);
+        bytes[0] -= 1;
+        assert_eq!(bytes[0], 2);
+
+        let normalized = histogram.normalize(11).unwrap();
+        assert_eq!(normalized.counts().iter().sum::<u32>(), 1 << 11);
+        assert_eq!(normalized.counts().iter().filter(|&&v| v > 0).count(), 8);
+        assert!(Histogram::<4>::new().normalize(11).is_err());
+    }
+
+    #[test]
+    fn entropy_splits() {
+        let mut input = random_alphabet(b'a', b'b', 0x3000);
+        input.extend_from_slice(&random_alphabet(0, 255, 0x5000));
+        input.extend_from_slice(&vec![0u8; 0x4000]);
+
+        let series = entropy_series(&input, 0x1000, 0x1000);
+        assert_eq!(series.len(), 0xc);
+        assert!(series[0] < 1.1);
+        assert!(series[5] > 7.0);
+        assert_eq!(series[0xb], 0.0);
+
+        assert_eq!(
+            suggest_splits(&input, 0x1000, 0x400, 256.0),
+            [0x3000, 0x8000]
+        );
+        assert!(suggest_splits(&vec![0u8; 0x10000], 0x1000, 0x400, 256.0).is_empty());
+    }
+
+    #[test]
+    fn content_defined() {
+        let input = random_alphabet(0, 255, 0x40000);
+        let boundaries = content_defined_boundaries(&input, 0x1000, 0x400);
+        assert!(boundaries.len() > 16);
+        assert!(boundaries[0] >= 0x400 && *boundaries.last().unwrap() < input.len());
+        for pair in boundaries.windows(2) {
+            assert!(pair[1] - pair[0] >= 0x400);
+        }
+        for &position in boundaries.iter() {
+            let hash = input[position.saturating_sub(0x1000)..position]
+                .iter()
+                .fold(0u64, |acc, &v| acc.wrapping_add(_rolling_hash_of(v)));
+            assert!(hash.is_multiple_of(0x1000));
+        }
+
+        // The boundaries after an insertion move with the content.
+        let mut edited = input.clone();
+        edited.splice(0x8000..0x8000, *b"inserted");
+        let edited_boundaries = content_defined_boundaries(&edited, 0x1000, 0x400);
+        let shifted = boundaries
+            .iter()
+            .filter(|&&v| v > 0x10000)
+            .map(|&v| v + 8)
+            .collect::<Vec<_>>();
+        assert!(edited_boundaries.ends_with(&shifted));
+
+        // also for data with a narrow range of values
+        let input = random_alphabet(b'a', b'd', 0x40000);
+        assert!(content_defined_boundaries(&input, 0x1000, 0x400).len() > 16);
+        assert!(content_defined_boundaries(&[], 0x1000, 0x400).is_empty());
+    }
+}
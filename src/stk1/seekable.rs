@@ -0,0 +1,186 @@
+//! Seekable container of independently compressed blocks
+
+use super::*;
+use core::ops::Range;
+
+/// Version of the seekable container
+pub const SEEKABLE_CONTAINER_VERSION: u8 = 2;
+
+/// Maximum size of the blocks of the seekable container
+pub const MAX_BLOCK_SIZE: usize = 0x1000_0000;
+
+/// Size of the seekable container header
+const SEEKABLE_HEADER_SIZE: usize = HEADER_SIZE + 4;
+
+/// Size of an entry of the block index
+const INDEX_ENTRY_SIZE: usize = 8;
+
+/// Compresses the input into the seekable container, splitting it into blocks of `block_size` bytes.
+///
+/// Returns `EncodeError::InvalidInput` if `block_size` is zero or exceeds [`MAX_BLOCK_SIZE`].
+pub fn compress_seekable(
+    input: &[u8],
+    config: Configuration,
+    block_size: usize,
+) -> Result<Vec<u8>, EncodeError> {
+    if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+        return Err(EncodeError::InvalidInput);
+    }
+    let mut output = ByteAlignedWriter::with_sink(Vec::with_capacity(SEEKABLE_HEADER_SIZE));
+    _write_header(&mut output, SEEKABLE_CONTAINER_VERSION, input);
+    output.push_u32_le(block_size as u32);
+
+    let mut index = ByteAlignedWriter::new();
+    for block in input.chunks(block_size) {
+        let compressed = Stk1::encode(block, config)?;
+        let compressed_size =
+            u32::try_from(compressed.len()).map_err(|_| EncodeError::InternalInconsistency)?;
+        output.extend_from_slice(&compressed);
+        index.push_u32_le(compressed_size);
+        index.push_u32_le(deflate::adler32::checksum(block));
+    }
+    output.extend_from_slice(index.as_bytes());
+
+    Ok(output.into_bytes())
+}
+
+/// Random access reader of the seekable container
+///
+/// Only the blocks that contain the requested range are decompressed.
+#[derive(Debug, Clone)]
+pub struct SeekableReader<'a> {
+    input: &'a [u8],
+    size: usize,
+    block_size: usize,
+    /// Offsets of the compressed blocks in the input, followed by the end of the last block
+    offsets: Vec<usize>,
+    checksums: Vec<u32>,
+}
+
+impl<'a> SeekableReader<'a> {
+    /// Reads the header and the block index of the seekable container.
+    ///
+    /// Returns `DecodeError::UnsupportedFormat` if the input is not a seekable container.
+    pub fn new(input: &'a [u8]) -> Result<Self, DecodeError> {
+        let (version, size, _) = _read_header(input)?;
+        if version != SEEKABLE_CONTAINER_VERSION {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        let header = input
+            .get(..SEEKABLE_HEADER_SIZE)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let block_size = u32::from_le_bytes(header[HEADER_SIZE..].try_into().unwrap()) as usize;
+        if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let n_blocks = size.div_ceil(block_size);
+        let index_size = n_blocks
+            .checked_mul(INDEX_ENTRY_SIZE)
+            .filter(|&v| v <= input.len() - SEEKABLE_HEADER_SIZE)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let index_offset = input.len() - index_size;
+
+        let mut offsets = Vec::with_capacity(n_blocks + 1);
+        let mut checksums = Vec::with_capacity(n_blocks);
+        let mut offset = SEEKABLE_HEADER_SIZE;
+        offsets.push(offset);
+        for entry in input[index_offset..].chunks_exact(INDEX_ENTRY_SIZE) {
+            offset += u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+            if offset > index_offset {
+                return Err(DecodeError::InvalidData);
+            }
+            offsets.push(offset);
+            checksums.push(u32::from_le_bytes(entry[4..].try_into().unwrap()));
+        }
+        if offset != index_offset {
+            return Err(DecodeError::InvalidData);
+        }
+
+        Ok(Self {
+            input,
+            size,
+            block_size,
+            offsets,
+            checksums,
+        })
+    }
+
+    /// Returns the size of the original data.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    #[inline]
+    pub const fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    #[inline]
+    pub fn num_blocks(&self) -> usize {
+        self.checksums.len()
+    }
+
+    /// Returns the range of the original data covered by a block.
+    #[inline]
+    pub fn block_range(&self, index: usize) -> Range<usize> {
+        let start = index.saturating_mul(self.block_size).min(self.size);
+        start..start.saturating_add(self.block_size).min(self.size)
+    }
+
+    /// Decompresses a block.
+    ///
+    /// Returns `DecodeError::InvalidInput` if the block does not exist,
+    /// and `DecodeError::ChecksumMismatch` if the checksum of the block does not match.
+    pub fn decompress_block(&self, index: usize) -> Result<Vec<u8>, DecodeError> {
+        if index >= self.num_blocks() {
+            return Err(DecodeError::InvalidInput);
+        }
+        let compressed = &self.input[self.offsets[index]..self.offsets[index + 1]];
+        let block = Stk1::decode_to_vec(compressed, self.block_range(index).len())?;
+        if deflate::adler32::checksum(&block) != self.checksums[index] {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        Ok(block)
+    }
+
+    /// Reads the original data from `offset` into the buffer, and returns the number of bytes read,
+    /// which is less than the size of the buffer only at the end of the data.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, DecodeError> {
+        let end = offset.saturating_add(buf.len()).min(self.size);
+        let mut cursor = offset;
+        while cursor < end {
+            let index = cursor / self.block_size;
+            let range = self.block_range(index);
+            let block = self.decompress_block(index)?;
+            let len = range.end.min(end) - cursor;
+            buf[cursor - offset..][..len].copy_from_slice(&block[cursor - range.start..][..len]);
+            cursor += len;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    /// Decompresses all the blocks.
+    ///
+    /// As with [`Stk1::decode_to_vec`], only a multiple of the input length is reserved up front,
+    /// since the size in the header may be corrupted, and the vector grows as the blocks decode.
+    pub fn decompress_all(&self) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::new();
+        output
+            .try_reserve_exact(
+                self.size
+                    .min(self.input.len().saturating_mul(DECODE_RESERVE_RATIO)),
+            )
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        for index in 0..self.num_blocks() {
+            output.extend_from_slice(&self.decompress_block(index)?);
+        }
+        Ok(output)
+    }
+}
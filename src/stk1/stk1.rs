@@ -3,7 +3,8 @@
 //! # CAUTION
 //!
 //! **THIS LIBRARY IS AN ALPHA VERSION**.
-//! Compression and decompression itself is possible, but you will need to provide your own processing for file headers and data size outside the library.
+//! [`Stk1`] compresses and decompresses the raw data only, so you will need to provide your own processing for file headers and data size outside the library.
+//! Alternatively, [`compress`] and [`decompress`] wrap the raw data in the container described below.
 //!
 //! # NOTE
 //!
@@ -19,20 +20,89 @@
 //! (C) Kawai Hidemi
 //!
 //! Related Documents: <http://osask.net/w/196.html> (But different from known final specifications)
+//!
+//! # Container
+//!
+//! The container of [`compress`] is specific to this library and is not compatible with the headers of the original tools.
+//! All integers are little endian.
+//!
+//! | Offset | Size | Description |
+//! | ------ | ---- | ----------- |
+//! | 0      | 4    | Magic number `b"stk1"` |
+//! | 4      | 1    | Version, currently [`CONTAINER_VERSION`] |
+//! | 5      | 8    | Size of the original data |
+//! | 13     | 4    | Adler-32 checksum of the original data |
+//! | 17     | -    | Raw stk1 data, which is empty if the original data is empty |
+//!
+//! The seekable container of [`compress_seekable`] has the version [`SEEKABLE_CONTAINER_VERSION`],
+//! and splits the original data into blocks that are compressed independently.
+//! It is followed by a block index, so [`SeekableReader`] can decompress only the blocks that contain the requested range.
+//!
+//! | Offset | Size | Description |
+//! | ------ | ---- | ----------- |
+//! | 0      | 17   | Same as above, but without the raw stk1 data |
+//! | 17     | 4    | Size of the blocks of the original data, except for the last block |
+//! | 21     | -    | Raw stk1 data of each block |
+//! | -      | -    | Block index, 8 bytes for each block |
+//!
+//! Each entry of the block index consists of the compressed size (4 bytes) and the Adler-32 checksum (4 bytes) of the block.
+//!
+//! The filtered container of [`compress_with_shuffle`] has the version [`FILTERED_CONTAINER_VERSION`],
+//! and the raw stk1 data is the compression of the original data after the filters of the flags.
+//! The checksum is of the original data before the filters.
+//!
+//! | Offset | Size | Description |
+//! | ------ | ---- | ----------- |
+//! | 0      | 17   | Same as above, but without the raw stk1 data |
+//! | 17     | 1    | Flags, [`FLAG_BYTE_SHUFFLE`] if [`filters::byte_shuffle`] is applied |
+//! | 18     | 1    | Element size of the byte shuffle, or zero if not applied |
+//! | 19     | -    | Raw stk1 data, which is empty if the original data is empty |
 
 use crate::lz;
 use crate::lz::Match;
 use crate::lz::MaybeMatch;
 use crate::lz::SliceWindow;
+use crate::lz::WindowSize;
 use crate::lz::cache::{OffsetCache, OffsetCache3};
+use crate::lz::{LzOutputBuffer, LzOutputVec, LzSink};
+use crate::num::bits::ByteAlignedWriter;
 use crate::*;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::num::NonZero;
+use core::ops::ControlFlow;
+
+#[cfg(test)]
+mod tests;
 
 mod s7s;
+mod seekable;
 pub use s7s::S7s;
+pub use seekable::*;
+
+/// Magic number of the container
+pub const MAGIC: &[u8; 4] = b"stk1";
+
+/// Version of the container
+pub const CONTAINER_VERSION: u8 = 1;
+
+/// Version of the filtered container
+pub const FILTERED_CONTAINER_VERSION: u8 = 3;
+
+/// Flag of the filtered container indicating that the bytes of the original data are shuffled
+pub const FLAG_BYTE_SHUFFLE: u8 = 0x01;
+
+/// Size of the container header
+const HEADER_SIZE: usize = 17;
+
+/// Size of the filtered container header
+const FILTERED_HEADER_SIZE: usize = HEADER_SIZE + 2;
+
+/// Ratio of the output to the input reserved up front by [`Stk1::decode_to_vec`]
+///
+/// The copy length is not bounded, so the output may still exceed it.
+const DECODE_RESERVE_RATIO: usize = 16;
 
 const LZ_MAX_LEN: usize = 0x80_00_00;
 const LZ_MAX_DISTANCE: usize = 0x02_00_00;
@@ -44,14 +114,21 @@ const LZ_SHORT_MAX_DIST: usize = 8;
 
 const LZ_MIN_MID_LEN: usize = 3;
 
+/// Window size in bytes of the rolling sum that finds the boundaries of the rsyncable mode
+const RSYNC_WINDOW_SIZE: usize = 4096;
+
+/// Minimum distance in bytes between the boundaries of the rsyncable mode
+const RSYNC_MIN_SEGMENT_SIZE: usize = 4096;
+
 /// Stk1 coder
 pub struct Stk1;
 
 /// Stk1 configuration
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Configuration {
     max_distance: usize,
     max_len: NonZero<usize>,
+    rsyncable: bool,
 }
 
 impl Configuration {
@@ -68,9 +145,28 @@ impl Configuration {
         Self {
             max_distance,
             max_len: NonZero::new(max_len).unwrap(),
+            rsyncable: false,
         }
     }
 
+    /// Sets the maximum distance to the size of the sliding window.
+    ///
+    /// Window sizes larger than 128KB are clamped because stk1 cannot represent longer distances.
+    #[inline]
+    pub const fn window_size(mut self, window_size: WindowSize) -> Self {
+        self.max_distance = window_size.min(WindowSize::SIZE_128K).value();
+        self
+    }
+
+    /// Encodes segments split at content-defined boundaries independently, as `gzip --rsyncable` does.
+    ///
+    /// A small edit of the input changes only the output around it, which helps delta synchronization such as rsync.
+    #[inline]
+    pub const fn rsyncable(mut self) -> Self {
+        self.rsyncable = true;
+        self
+    }
+
     #[inline]
     pub fn max_distance(&self) -> usize {
         self.max_distance
@@ -116,10 +212,66 @@ impl Stk1 {
         Ok(dst)
     }
 
+    #[inline]
     pub fn encode(input: &[u8], config: Configuration) -> Result<Vec<u8>, EncodeError> {
+        Self::_encode(input, config, ProgressReporter::new(None, input.len()))
+    }
+
+    /// Encodes the input like [`Stk1::encode`], reporting the progress to the hook.
+    ///
+    /// Returning `ControlFlow::Break` from the hook stops the encoder with `EncodeError::Cancelled`.
+    #[inline]
+    pub fn encode_with_progress(
+        input: &[u8],
+        config: Configuration,
+        progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<Vec<u8>, EncodeError> {
+        Self::_encode(
+            input,
+            config,
+            ProgressReporter::new(Some(progress), input.len()),
+        )
+    }
+
+    fn _encode(
+        input: &[u8],
+        config: Configuration,
+        mut progress: ProgressReporter,
+    ) -> Result<Vec<u8>, EncodeError> {
         let mut output = Vec::new();
+        if config.rsyncable {
+            // The decoder does not distinguish the segments, since none of them refers to the preceding ones.
+            let mut start = 0;
+            for end in
+                stats::content_defined_boundaries(input, RSYNC_WINDOW_SIZE, RSYNC_MIN_SEGMENT_SIZE)
+                    .into_iter()
+                    .chain(Some(input.len()))
+            {
+                Self::_encode_segment(
+                    &input[start..end],
+                    start,
+                    &config,
+                    &mut output,
+                    &mut progress,
+                )?;
+                start = end;
+            }
+        } else {
+            Self::_encode_segment(input, 0, &config, &mut output, &mut progress)?;
+        }
+        progress.report(input.len(), output.len())?;
+        Ok(output)
+    }
 
-        let mut offset_cache = OffsetCache3::new(input, config.max_distance(), 0);
+    /// Encodes a segment that starts at `offset` of the whole input.
+    fn _encode_segment(
+        input: &[u8],
+        offset: usize,
+        config: &Configuration,
+        output: &mut Vec<u8>,
+        progress: &mut ProgressReporter,
+    ) -> Result<(), EncodeError> {
+        let mut offset_cache = OffsetCache3::new(input, config.max_distance());
         let mut lit_buf = SliceWindow::new(input, 0);
         let mut lz_buf = Vec::new();
 
@@ -166,7 +318,7 @@ impl Stk1 {
                     matches.len
                 } else {
                     if lz_buf.len() > 0 {
-                        Self::_flush(&mut output, lit_buf, &mut lz_buf)?;
+                        Self::_flush(output, lit_buf, &mut lz_buf)?;
                         lit_buf = SliceWindow::new(input, cursor);
                     } else {
                         lit_buf.expand(1);
@@ -176,10 +328,11 @@ impl Stk1 {
             };
             offset_cache.advance(count.get());
             cursor += count.get();
+            progress.advance(offset + cursor, output.len())?;
         }
-        Self::_flush(&mut output, lit_buf, &mut lz_buf)?;
+        Self::_flush(output, lit_buf, &mut lz_buf)?;
 
-        Ok(output)
+        Ok(())
     }
 
     fn _flush(
@@ -240,10 +393,25 @@ impl Stk1 {
     }
 
     pub fn decode(input: &[u8], output: &mut [u8]) -> Result<(), DecodeError> {
+        Self::_decode(input, &mut LzOutputBuffer::new(output))
+    }
+
+    /// Decodes `size` bytes into a new vector.
+    ///
+    /// Since `size` comes from outside the raw data and may be corrupted,
+    /// only a multiple of the input length is reserved up front,
+    /// and the vector grows as the data decodes.
+    pub fn decode_to_vec(input: &[u8], size: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut vec = Vec::new();
+        vec.try_reserve_exact(size.min(input.len().saturating_mul(DECODE_RESERVE_RATIO)))
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        Self::_decode(input, &mut LzOutputVec::new(&mut vec, size)).map(|_| vec)
+    }
+
+    fn _decode(input: &[u8], output: &mut impl LzSink) -> Result<(), DecodeError> {
         let mut iter = input.iter();
         let iter = &mut iter;
-        let mut cursor = 0;
-        while cursor < output.len() {
+        while !output.is_eof() {
             let lead_lz = iter.next().ok_or(DecodeError::InvalidData)?;
             let by = lead_lz & 0x0F;
             let lz = lead_lz >> 4;
@@ -257,11 +425,11 @@ impl Stk1 {
             } else {
                 lz as usize
             };
-            for p in iter.take(by) {
-                output[cursor] = *p;
-                cursor += 1;
-            }
-            if cursor >= output.len() {
+            let rest = iter.as_slice();
+            let (literals, rest) = rest.split_at(by.min(output.remaining()).min(rest.len()));
+            let _ = output.extend_from_slice(literals);
+            *iter = rest.iter();
+            if output.is_eof() {
                 break;
             }
             for _ in 0..lz {
@@ -276,24 +444,114 @@ impl Stk1 {
                     cp as usize
                 };
                 let cp = cp + 1;
-                if ds > cursor {
-                    return Err(DecodeError::InvalidData);
-                }
-                let cp = cp.min(output.len() - cursor);
-                for _ in 0..cp {
-                    output[cursor] = output[cursor - ds];
-                    cursor += 1;
-                }
+                // the copy is truncated to the end of the output
+                output.copy_lz(ds, cp).ok_or(DecodeError::InvalidData)?;
             }
         }
         Ok(())
     }
+}
 
-    pub fn decode_to_vec(input: &[u8], size: usize) -> Result<Vec<u8>, DecodeError> {
-        let mut vec = Vec::new();
-        vec.try_reserve_exact(size)
-            .map_err(|_| DecodeError::OutOfMemory)?;
-        vec.resize(size, 0);
-        Self::decode(input, &mut vec).map(|_| vec)
+/// Compresses the input into the container.
+pub fn compress(input: &[u8], config: Configuration) -> Result<Vec<u8>, EncodeError> {
+    let mut output = ByteAlignedWriter::with_sink(Vec::with_capacity(HEADER_SIZE));
+    _write_header(&mut output, CONTAINER_VERSION, input);
+    if !input.is_empty() {
+        output.extend_from_slice(&Stk1::encode(input, config)?);
+    }
+    Ok(output.into_bytes())
+}
+
+/// Compresses the input into the filtered container,
+/// applying [`filters::byte_shuffle`] with `element_size` before the compression.
+///
+/// This improves the compression of arrays of integers or floating point values of `element_size` bytes.
+/// Returns `EncodeError::InvalidInput` if `element_size` is zero or greater than 255.
+pub fn compress_with_shuffle(
+    input: &[u8],
+    config: Configuration,
+    element_size: usize,
+) -> Result<Vec<u8>, EncodeError> {
+    if !(1..=255).contains(&element_size) {
+        return Err(EncodeError::InvalidInput);
+    }
+    let mut output = ByteAlignedWriter::with_sink(Vec::with_capacity(FILTERED_HEADER_SIZE));
+    _write_header(&mut output, FILTERED_CONTAINER_VERSION, input);
+    output.push_byte(FLAG_BYTE_SHUFFLE);
+    output.push_byte(element_size as u8);
+    if !input.is_empty() {
+        let shuffled = filters::byte_shuffle(input, element_size);
+        output.extend_from_slice(&Stk1::encode(&shuffled, config)?);
+    }
+    Ok(output.into_bytes())
+}
+
+/// Decompresses the container.
+#[inline]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_with_limit(input, usize::MAX)
+}
+
+/// Decompresses the container.
+///
+/// Returns `DecodeError::LimitExceeded` if the original size exceeds `max_size`,
+/// and `DecodeError::ChecksumMismatch` if the checksum does not match.
+pub fn decompress_with_limit(input: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+    let (version, size, checksum) = _read_header(input)?;
+    if size > max_size {
+        return Err(DecodeError::LimitExceeded);
+    }
+    let output = match version {
+        CONTAINER_VERSION => Stk1::decode_to_vec(&input[HEADER_SIZE..], size)?,
+        FILTERED_CONTAINER_VERSION => _decompress_filtered(input, size)?,
+        _ => SeekableReader::new(input)?.decompress_all()?,
+    };
+    if deflate::adler32::checksum(&output) != checksum {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+    Ok(output)
+}
+
+fn _decompress_filtered(input: &[u8], size: usize) -> Result<Vec<u8>, DecodeError> {
+    let header = input
+        .get(..FILTERED_HEADER_SIZE)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    let (flags, element_size) = (header[HEADER_SIZE], header[HEADER_SIZE + 1] as usize);
+    if (flags & !FLAG_BYTE_SHUFFLE) != 0 {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    if ((flags & FLAG_BYTE_SHUFFLE) != 0) != (element_size != 0) {
+        return Err(DecodeError::InvalidData);
+    }
+    let output = Stk1::decode_to_vec(&input[FILTERED_HEADER_SIZE..], size)?;
+    if element_size != 0 {
+        Ok(filters::byte_unshuffle(&output, element_size))
+    } else {
+        Ok(output)
+    }
+}
+
+fn _write_header(output: &mut ByteAlignedWriter, version: u8, input: &[u8]) {
+    output.extend_from_slice(MAGIC);
+    output.push_byte(version);
+    output.push_u64_le(input.len() as u64);
+    output.push_u32_le(deflate::adler32::checksum(input));
+}
+
+/// Returns the version, the original size and the checksum of the container.
+fn _read_header(input: &[u8]) -> Result<(u8, usize, u32), DecodeError> {
+    let header = input.get(..HEADER_SIZE).ok_or(DecodeError::UnexpectedEof)?;
+    let version = header[4];
+    if &header[..4] != MAGIC
+        || !matches!(
+            version,
+            CONTAINER_VERSION | SEEKABLE_CONTAINER_VERSION | FILTERED_CONTAINER_VERSION
+        )
+    {
+        return Err(DecodeError::UnsupportedFormat);
     }
+    let size = u64::from_le_bytes(header[5..13].try_into().unwrap());
+    let size = usize::try_from(size).map_err(|_| DecodeError::LimitExceeded)?;
+    let checksum = u32::from_le_bytes(header[13..17].try_into().unwrap());
+    Ok((version, size, checksum))
 }
@@ -0,0 +1,347 @@
+use super::*;
+use crate::lz::WindowSize;
+
+fn _configs() -> [Configuration; 4] {
+    [
+        Configuration::TINY,
+        Configuration::DEFAULT,
+        Configuration::MAX,
+        Configuration::DEFAULT.window_size(WindowSize::from_bits(12).unwrap()),
+    ]
+}
+
+#[test]
+fn stk1_container_layout() {
+    let compressed = compress(b"", Configuration::DEFAULT).unwrap();
+    assert_eq!(
+        compressed,
+        [
+            b's', b't', b'k', b'1', 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0
+        ]
+    );
+    assert_eq!(decompress(&compressed).unwrap(), b"");
+
+    let input = b"abcabcabcabcabc";
+    let compressed = compress(input, Configuration::DEFAULT).unwrap();
+    assert_eq!(&compressed[..4], MAGIC);
+    assert_eq!(compressed[4], CONTAINER_VERSION);
+    assert_eq!(&compressed[5..13], &15u64.to_le_bytes());
+    assert_eq!(&compressed[13..17], &0x2df5_05bfu32.to_le_bytes());
+    assert_eq!(
+        &compressed[17..],
+        Stk1::encode(input, Configuration::DEFAULT).unwrap()
+    );
+    assert_eq!(decompress(&compressed).unwrap(), input);
+}
+
+#[test]
+fn stk1_roundtrip() {
+    let mut inputs = Vec::new();
+    for len in 0..=64 {
+        inputs.push(vec![0u8; len]);
+        inputs.push((0..len as u8).collect());
+        inputs.push(fib_str(b'a', b'b', len));
+        inputs.push(random_alphabet(0, 255, len));
+        inputs.push(random_alphabet(b'a', b'c', len));
+    }
+    for len in [
+        0x7f, 0x80, 0x3fff, 0x4000, 0x4001, 0x12345, 0x20000, 0x20001, 0x54321,
+    ] {
+        inputs.push(vec![0x55; len]);
+        inputs.push(fib_str(b'x', b'y', len));
+        inputs.push(random_alphabet(0, 255, len));
+        inputs.push(random_alphabet(b'0', b'9', len));
+    }
+    // matches at distances around the boundaries of the encoding
+    let block = random_alphabet(0, 255, 0x20010);
+    for distance in [1, 2, 8, 9, 0x3ff, 0x400, 0x401, 0x1ffff, 0x20000] {
+        let mut input = block[..distance].to_vec();
+        input.extend_from_slice(&block[..16]);
+        inputs.push(input);
+    }
+
+    for config in _configs() {
+        for input in inputs.iter() {
+            let compressed = compress(input, Configuration { ..config }).unwrap();
+            assert_eq!(
+                decompress(&compressed).unwrap(),
+                *input,
+                "len {}",
+                input.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn stk1_container_invalid() {
+    let input = fib_str(b'a', b'b', 1000);
+    let compressed = compress(&input, Configuration::DEFAULT).unwrap();
+    assert_eq!(
+        decompress_with_limit(&compressed, input.len()).unwrap(),
+        input
+    );
+    assert_eq!(
+        decompress_with_limit(&compressed, input.len() - 1),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    for len in 0..HEADER_SIZE {
+        assert_eq!(
+            decompress(&compressed[..len]),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+    assert_eq!(
+        decompress(&compressed[..compressed.len() - 1]),
+        Err(DecodeError::InvalidData)
+    );
+
+    let mut invalid = compressed.clone();
+    invalid[0] = b'S';
+    assert_eq!(decompress(&invalid), Err(DecodeError::UnsupportedFormat));
+
+    let mut invalid = compressed.clone();
+    invalid[4] = 0xff;
+    assert_eq!(decompress(&invalid), Err(DecodeError::UnsupportedFormat));
+
+    let mut invalid = compressed.clone();
+    invalid[13] ^= 1;
+    assert_eq!(decompress(&invalid), Err(DecodeError::ChecksumMismatch));
+
+    // a shorter size decodes a prefix of the data, which fails the checksum
+    let mut invalid = compressed.clone();
+    invalid[5..13].copy_from_slice(&999u64.to_le_bytes());
+    assert_eq!(decompress(&invalid), Err(DecodeError::ChecksumMismatch));
+
+    // a corrupted size is not reserved up front, so the data runs out first
+    let mut invalid = compressed.clone();
+    invalid[5..13].copy_from_slice(&(u32::MAX as u64).to_le_bytes());
+    assert_eq!(decompress(&invalid), Err(DecodeError::InvalidData));
+
+    let mut invalid = compressed.clone();
+    invalid[5..13].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(
+        decompress_with_limit(&invalid, 1 << 20),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn stk1_seekable() {
+    let mut input = fib_str(b'a', b'b', 50000);
+    input.extend_from_slice(&random_alphabet(0, 255, 12345));
+    for block_size in [1, 1000, 4096, 0x10000, 0x20000] {
+        let compressed = compress_seekable(&input, Configuration::DEFAULT, block_size).unwrap();
+        assert_eq!(compressed[4], SEEKABLE_CONTAINER_VERSION);
+        assert_eq!(decompress(&compressed).unwrap(), input);
+
+        let reader = SeekableReader::new(&compressed).unwrap();
+        assert_eq!(reader.len(), input.len());
+        assert_eq!(reader.block_size(), block_size);
+        assert_eq!(reader.num_blocks(), input.len().div_ceil(block_size));
+        for index in [0, reader.num_blocks() / 2, reader.num_blocks() - 1] {
+            let range = reader.block_range(index);
+            assert_eq!(reader.decompress_block(index).unwrap(), input[range]);
+        }
+        assert_eq!(
+            reader.decompress_block(reader.num_blocks()),
+            Err(DecodeError::InvalidInput)
+        );
+
+        for (offset, len) in [
+            (0, 1),
+            (0, 5000),
+            (999, 2),
+            (4095, 4098),
+            (49999, 12345),
+            (input.len() - 10, 100),
+            (input.len(), 1),
+            (input.len() + 1, 1),
+        ] {
+            let mut buf = vec![0; len];
+            let read = reader.read_at(offset, &mut buf).unwrap();
+            let expected = input.get(offset..).unwrap_or_default();
+            let expected = &expected[..expected.len().min(len)];
+            assert_eq!(&buf[..read], expected);
+        }
+    }
+
+    let compressed = compress_seekable(b"", Configuration::DEFAULT, 100).unwrap();
+    assert_eq!(compressed.len(), 21);
+    assert_eq!(decompress(&compressed).unwrap(), b"");
+    let reader = SeekableReader::new(&compressed).unwrap();
+    assert!(reader.is_empty());
+    assert_eq!(reader.num_blocks(), 0);
+    assert_eq!(reader.read_at(0, &mut [0; 4]), Ok(0));
+
+    assert_eq!(
+        compress_seekable(&input, Configuration::DEFAULT, 0),
+        Err(EncodeError::InvalidInput)
+    );
+    assert_eq!(
+        compress_seekable(&input, Configuration::DEFAULT, MAX_BLOCK_SIZE + 1),
+        Err(EncodeError::InvalidInput)
+    );
+}
+
+#[test]
+fn stk1_seekable_invalid() {
+    let input = fib_str(b'a', b'b', 10000);
+    let compressed = compress_seekable(&input, Configuration::DEFAULT, 1024).unwrap();
+
+    assert_eq!(
+        SeekableReader::new(&compress(&input, Configuration::DEFAULT).unwrap()).unwrap_err(),
+        DecodeError::UnsupportedFormat
+    );
+    for len in [HEADER_SIZE, HEADER_SIZE + 3, compressed.len() - 1] {
+        assert!(SeekableReader::new(&compressed[..len]).is_err());
+        assert!(decompress(&compressed[..len]).is_err());
+    }
+
+    // block size of zero
+    let mut invalid = compressed.clone();
+    invalid[17..21].fill(0);
+    assert_eq!(
+        SeekableReader::new(&invalid).unwrap_err(),
+        DecodeError::InvalidData
+    );
+
+    // corrupted checksum of the last block
+    let mut invalid = compressed.clone();
+    let len = invalid.len();
+    invalid[len - 1] ^= 1;
+    let reader = SeekableReader::new(&invalid).unwrap();
+    assert_eq!(reader.decompress_block(0).unwrap(), input[..1024]);
+    assert_eq!(
+        reader.decompress_block(reader.num_blocks() - 1),
+        Err(DecodeError::ChecksumMismatch)
+    );
+    assert_eq!(decompress(&invalid), Err(DecodeError::ChecksumMismatch));
+
+    // compressed size of the first block
+    let mut invalid = compressed.clone();
+    let index_offset = len - reader.num_blocks() * 8;
+    invalid[index_offset] ^= 1;
+    assert_eq!(
+        SeekableReader::new(&invalid).unwrap_err(),
+        DecodeError::InvalidData
+    );
+
+    // a corrupted size of a single block is not reserved up front, so the data runs out first
+    let mut invalid = compress_seekable(b"abc", Configuration::DEFAULT, 1024).unwrap();
+    invalid[5..13].copy_from_slice(&(MAX_BLOCK_SIZE as u64).to_le_bytes());
+    invalid[17..21].copy_from_slice(&(MAX_BLOCK_SIZE as u32).to_le_bytes());
+    let reader = SeekableReader::new(&invalid).unwrap();
+    let (decoded, allocated) = allocated_bytes(|| reader.decompress_all());
+    assert_eq!(decoded, Err(DecodeError::InvalidData));
+    assert!(allocated < 0x1000, "{allocated}");
+}
+
+#[test]
+fn stk1_rsyncable() {
+    let common_suffix = |a: &[u8], b: &[u8]| {
+        a.iter()
+            .rev()
+            .zip(b.iter().rev())
+            .take_while(|(p, q)| p == q)
+            .count()
+    };
+    let input = random_alphabet_seeded(b'a', b'p', 0x40000, 2531);
+    let mut edited = input.clone();
+    edited.splice(0x10000..0x10000, *b"small edit");
+
+    let config = Configuration::DEFAULT.rsyncable();
+    let encoded1 = Stk1::encode(&input, config).unwrap();
+    let encoded2 = Stk1::encode(&edited, config).unwrap();
+    assert_eq!(Stk1::decode_to_vec(&encoded1, input.len()).unwrap(), input);
+    assert_eq!(
+        Stk1::decode_to_vec(&encoded2, edited.len()).unwrap(),
+        edited
+    );
+    assert!(common_suffix(&encoded1, &encoded2) > encoded1.len() / 2);
+
+    let plain1 = Stk1::encode(&input, Configuration::DEFAULT).unwrap();
+    let plain2 = Stk1::encode(&edited, Configuration::DEFAULT).unwrap();
+    assert!(common_suffix(&plain1, &plain2) < plain1.len() / 4);
+
+    for input in [&b""[..], b"a", &fib_str(b'a', b'b', 0x12345)] {
+        let compressed = compress(input, config).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), input);
+    }
+}
+
+#[test]
+fn stk1_progress() {
+    use core::ops::ControlFlow;
+
+    let input = fib_str(b'a', b'b', 0x54321);
+    for config in [Configuration::DEFAULT, Configuration::DEFAULT.rsyncable()] {
+        let mut reports = Vec::new();
+        let encoded = Stk1::encode_with_progress(&input, config, &mut |progress| {
+            reports.push(progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(encoded, Stk1::encode(&input, config).unwrap());
+        assert!(reports.len() > input.len() / PROGRESS_INTERVAL);
+        assert!(reports.is_sorted_by_key(|v| (v.consumed, v.emitted)));
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                consumed: input.len(),
+                emitted: encoded.len(),
+                total: input.len(),
+            })
+        );
+
+        let result = Stk1::encode_with_progress(&input, config, &mut |_| ControlFlow::Break(()));
+        assert_eq!(result, Err(EncodeError::Cancelled));
+    }
+}
+
+#[test]
+fn stk1_shuffle() {
+    let samples = (0..0x4000u32)
+        .map(|v| v.wrapping_mul(0x9e37_79b1) % 1000)
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+    let plain = compress(&samples, Configuration::DEFAULT).unwrap();
+    let compressed = compress_with_shuffle(&samples, Configuration::DEFAULT, 4).unwrap();
+    assert_eq!(compressed[4], FILTERED_CONTAINER_VERSION);
+    assert_eq!(compressed[17..19], [FLAG_BYTE_SHUFFLE, 4]);
+    assert!(compressed.len() < plain.len());
+    assert_eq!(decompress(&compressed).unwrap(), samples);
+
+    for input in [Vec::new(), b"abc".to_vec(), fib_str(b'a', b'b', 1001)] {
+        for element_size in [1, 2, 3, 8, 255] {
+            let compressed =
+                compress_with_shuffle(&input, Configuration::DEFAULT, element_size).unwrap();
+            assert_eq!(decompress(&compressed).unwrap(), input);
+        }
+    }
+
+    assert_eq!(
+        compress_with_shuffle(b"abc", Configuration::DEFAULT, 0),
+        Err(EncodeError::InvalidInput)
+    );
+    assert_eq!(
+        compress_with_shuffle(b"abc", Configuration::DEFAULT, 256),
+        Err(EncodeError::InvalidInput)
+    );
+
+    let mut invalid = compressed.clone();
+    invalid[17] |= 0x80;
+    assert_eq!(decompress(&invalid), Err(DecodeError::UnsupportedFormat));
+    let mut invalid = compressed.clone();
+    invalid[18] = 0;
+    assert_eq!(decompress(&invalid), Err(DecodeError::InvalidData));
+    // a different element size restores other data, which fails the checksum
+    let mut invalid = compressed.clone();
+    invalid[18] = 2;
+    assert_eq!(decompress(&invalid), Err(DecodeError::ChecksumMismatch));
+    assert_eq!(
+        decompress(&compressed[..18]),
+        Err(DecodeError::UnexpectedEof)
+    );
+}
@@ -0,0 +1,221 @@
+//! Round-trip oracles for fuzzing and property testing
+//!
+//! Each function compresses the input, decompresses the result, and compares it with the input,
+//! so a `cargo-fuzz` target or a `proptest` property can be a single call:
+//!
+//! ```
+//! use compress::{deflate::CompressionLevel, testing};
+//!
+//! let input = b"abracadabra abracadabra";
+//! testing::roundtrip_deflate(input, CompressionLevel::Default).unwrap();
+//! ```
+//!
+//! Errors of the encoder are reported as they are,
+//! such as `EncodeError::InvalidInput` for an empty input to the LZ parsers.
+
+use crate::lz::{TokenStream, lzss};
+use crate::*;
+use core::fmt;
+
+/// Failure of a round trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundtripFailure {
+    /// The encoder returned an error
+    Encode(EncodeError),
+    /// The decoder returned an error for the output of the encoder
+    Decode(DecodeError),
+    /// The decoded data differs from the input
+    Mismatch(Mismatch),
+}
+
+/// Location of the first difference between the input and the decoded data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Offset of the first differing byte
+    pub offset: usize,
+    /// Byte of the input at the offset, or `None` if the decoded data is longer
+    pub expected: Option<u8>,
+    /// Byte of the decoded data at the offset, or `None` if the decoded data is shorter
+    pub actual: Option<u8>,
+    /// Length of the input
+    pub expected_len: usize,
+    /// Length of the decoded data
+    pub actual_len: usize,
+    /// Index of the token that produced the differing byte,
+    /// if the oracle has access to the tokens (see [`roundtrip_lzss`])
+    pub token_index: Option<usize>,
+}
+
+impl fmt::Display for RoundtripFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encode(error) => write!(f, "encode failed: {error}"),
+            Self::Decode(error) => write!(f, "decode failed: {error}"),
+            Self::Mismatch(mismatch) => {
+                write!(
+                    f,
+                    "mismatch at offset {} (expected {:02x?}, actual {:02x?}, len {} vs {})",
+                    mismatch.offset,
+                    mismatch.expected,
+                    mismatch.actual,
+                    mismatch.expected_len,
+                    mismatch.actual_len,
+                )?;
+                if let Some(token_index) = mismatch.token_index {
+                    write!(f, " in token {token_index}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<EncodeError> for RoundtripFailure {
+    #[inline]
+    fn from(error: EncodeError) -> Self {
+        Self::Encode(error)
+    }
+}
+
+impl From<DecodeError> for RoundtripFailure {
+    #[inline]
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// Compresses the input with raw deflate and verifies that it decompresses to the input.
+pub fn roundtrip_deflate(
+    input: &[u8],
+    level: deflate::CompressionLevel,
+) -> Result<(), RoundtripFailure> {
+    let compressed = deflate::deflate(input, level, None)?;
+    let decoded = deflate::inflate(&compressed, input.len())?;
+    compare(input, &decoded, None)
+}
+
+/// Compresses the input into the stk1 container and verifies that it decompresses to the input.
+pub fn roundtrip_stk1(input: &[u8], config: stk1::Configuration) -> Result<(), RoundtripFailure> {
+    let compressed = stk1::compress(input, config)?;
+    let decoded = stk1::decompress(&compressed)?;
+    compare(input, &decoded, None)
+}
+
+/// Compresses the input into an LZ4 frame and verifies that it decompresses to the input.
+pub fn roundtrip_lz4(input: &[u8], options: lz4::FrameOptions) -> Result<(), RoundtripFailure> {
+    let compressed = lz4::compress(input, options)?;
+    let decoded = lz4::decompress(&compressed)?;
+    compare(input, &decoded, None)
+}
+
+/// Parses the input with [`lzss::LZSS::encode`] and verifies that the tokens reconstruct the input.
+///
+/// The mismatch reports the index of the token that produced the differing byte.
+pub fn roundtrip_lzss(input: &[u8], config: lzss::Configuration) -> Result<(), RoundtripFailure> {
+    let tokens = TokenStream::collect(input, config)?;
+    let decoded = tokens.decode()?;
+    compare(input, &decoded, Some(&tokens))
+}
+
+/// Compares the input with the decoded data.
+///
+/// If the tokens are given, the mismatch reports the token that produced the differing byte.
+pub fn compare(
+    input: &[u8],
+    decoded: &[u8],
+    tokens: Option<&TokenStream>,
+) -> Result<(), RoundtripFailure> {
+    let Some(offset) = input
+        .iter()
+        .zip(decoded.iter())
+        .position(|(p, q)| p != q)
+        .or_else(|| (input.len() != decoded.len()).then(|| input.len().min(decoded.len())))
+    else {
+        return Ok(());
+    };
+    let token_index = tokens.and_then(|tokens| {
+        tokens
+            .inspect()
+            .take_while(|&(position, _)| position <= offset)
+            .count()
+            .checked_sub(1)
+    });
+    Err(RoundtripFailure::Mismatch(Mismatch {
+        offset,
+        expected: input.get(offset).copied(),
+        actual: decoded.get(offset).copied(),
+        expected_len: input.len(),
+        actual_len: decoded.len(),
+        token_index,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lz::lzss::LZSS;
+    use core::num::NonZero;
+
+    #[test]
+    fn roundtrip_oracles() {
+        let mut input = fib_str(b'a', b'b', 0x2345);
+        input.extend_from_slice(&random_alphabet(0, 255, 0x1000));
+        for input in [&b"a"[..], b"abcabcabc", &input] {
+            for level in [
+                deflate::CompressionLevel::Level0,
+                deflate::CompressionLevel::Fastest,
+                deflate::CompressionLevel::Default,
+                deflate::CompressionLevel::Best,
+            ] {
+                assert_eq!(roundtrip_deflate(input, level), Ok(()));
+            }
+            assert_eq!(roundtrip_stk1(input, stk1::Configuration::DEFAULT), Ok(()));
+            assert_eq!(roundtrip_lz4(input, lz4::FrameOptions::new()), Ok(()));
+            assert_eq!(roundtrip_lzss(input, lzss::Configuration::DEFAULT), Ok(()));
+        }
+        assert_eq!(roundtrip_stk1(b"", stk1::Configuration::DEFAULT), Ok(()));
+        assert_eq!(roundtrip_lz4(b"", lz4::FrameOptions::new()), Ok(()));
+    }
+
+    #[test]
+    fn roundtrip_mismatch() {
+        let input = b"abcabcabcxyz";
+        assert_eq!(compare(input, input, None), Ok(()));
+
+        // a token that copies the wrong distance
+        let tokens = [
+            LZSS::Literal(b'a'),
+            LZSS::Literal(b'b'),
+            LZSS::Literal(b'c'),
+            LZSS::Match(lz::Match::new(
+                NonZero::new(6).unwrap(),
+                NonZero::new(2).unwrap(),
+            )),
+        ]
+        .into_iter()
+        .collect::<TokenStream>();
+        let decoded = tokens.decode().unwrap();
+        let Err(RoundtripFailure::Mismatch(mismatch)) = compare(input, &decoded, Some(&tokens))
+        else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!(mismatch.offset, 3);
+        assert_eq!(mismatch.expected, Some(b'a'));
+        assert_eq!(mismatch.actual, Some(b'b'));
+        assert_eq!(mismatch.token_index, Some(3));
+        assert_eq!(
+            RoundtripFailure::Mismatch(mismatch).to_string(),
+            "mismatch at offset 3 (expected Some(61), actual Some(62), len 12 vs 9) in token 3"
+        );
+
+        // truncated output
+        let Err(RoundtripFailure::Mismatch(mismatch)) = compare(input, &input[..10], None) else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!(
+            (mismatch.offset, mismatch.expected, mismatch.actual),
+            (10, Some(b'y'), None)
+        );
+        assert_eq!(mismatch.token_index, None);
+    }
+}
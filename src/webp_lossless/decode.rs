@@ -0,0 +1,396 @@
+//! VP8L decoder
+
+use super::transform::*;
+use super::*;
+use crate::entropy::prefix::{CanonicalPrefixDecoder, CodeLengthCodec};
+use crate::num::bits::{BitSize, BitStreamReader};
+use alloc::vec;
+
+/// Decodes a VP8L bitstream or a WebP file that contains one.
+#[inline]
+pub fn decode(input: &[u8]) -> Result<Image, DecodeError> {
+    decode_with_limit(input, usize::MAX)
+}
+
+/// Decodes a VP8L bitstream or a WebP file that contains one.
+///
+/// Returns `DecodeError::LimitExceeded` if the number of pixels exceeds `max_pixels`.
+pub fn decode_with_limit(input: &[u8], max_pixels: usize) -> Result<Image, DecodeError> {
+    let mut reader = BitStreamReader::new(_find_bitstream(input)?);
+    if _read_bits(&mut reader, 8)? != SIGNATURE as u32 {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    let width = _read_bits(&mut reader, 14)? as usize + 1;
+    let height = _read_bits(&mut reader, 14)? as usize + 1;
+    // whether the alpha channel is used, which is only a hint
+    let _ = _read_bool(&mut reader)?;
+    if _read_bits(&mut reader, 3)? != 0 {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    if width * height > max_pixels {
+        return Err(DecodeError::LimitExceeded);
+    }
+
+    // The transforms are reverted in the reverse order of the bitstream.
+    let mut transforms = Vec::new();
+    let mut xsize = width;
+    let mut seen = 0;
+    while _read_bool(&mut reader)? {
+        let kind = _read_bits(&mut reader, 2)?;
+        if seen & (1 << kind) != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        seen |= 1 << kind;
+        let transform = Transform::read(&mut reader, kind, xsize, height)?;
+        transforms.push((xsize, transform));
+        if let Some((_, Transform::ColorIndexing(palette))) = transforms.last() {
+            xsize = subsample_size(xsize, color_indexing_width_bits(palette.len()));
+        }
+    }
+
+    let mut pixels = _read_image_data(&mut reader, xsize, height, true)?;
+    for (xsize, transform) in transforms.iter().rev() {
+        let xsize = *xsize;
+        match transform {
+            Transform::Predictor(bits, modes) => {
+                inverse_predictor(&mut pixels, xsize, *bits, modes)
+            }
+            Transform::Color(bits, elements) => {
+                inverse_color_transform(&mut pixels, xsize, *bits, elements)
+            }
+            Transform::SubtractGreen => pixels.iter_mut().for_each(|v| *v = add_green(*v)),
+            Transform::ColorIndexing(palette) => {
+                pixels = inverse_color_indexing(&pixels, xsize, height, palette)
+            }
+        }
+    }
+
+    Image::new(width, height, pixels).ok_or(DecodeError::InvalidData)
+}
+
+/// Returns the VP8L bitstream in a RIFF container, or the input itself if it is not a RIFF container.
+fn _find_bitstream(input: &[u8]) -> Result<&[u8], DecodeError> {
+    if !input.starts_with(b"RIFF") {
+        return Ok(input);
+    }
+    if input.len() < 12 {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    if &input[8..12] != b"WEBP" {
+        return Err(DecodeError::UnsupportedFormat);
+    }
+    let riff_size = u32::from_le_bytes(input[4..8].try_into().unwrap()) as usize;
+    let input = input
+        .get(..riff_size.saturating_add(8))
+        .ok_or(DecodeError::UnexpectedEof)?;
+
+    let mut cursor = 12;
+    while let Some(header) = input.get(cursor..cursor + 8) {
+        let size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        let data = input
+            .get(cursor + 8..)
+            .and_then(|v| v.get(..size))
+            .ok_or(DecodeError::UnexpectedEof)?;
+        if &header[..4] == b"VP8L" {
+            return Ok(data);
+        }
+        cursor += 8 + size + (size & 1);
+    }
+    // lossy images are not supported
+    Err(DecodeError::UnsupportedFormat)
+}
+
+/// Reads `bits` bits, which may be zero.
+#[inline]
+fn _read_bits(reader: &mut BitStreamReader, bits: u32) -> Result<u32, DecodeError> {
+    if bits == 0 {
+        return Ok(0);
+    }
+    BitSize::new(bits as u8)
+        .and_then(|bits| reader.read_bits(bits))
+        .ok_or(DecodeError::UnexpectedEof)
+}
+
+#[inline]
+fn _read_bool(reader: &mut BitStreamReader) -> Result<bool, DecodeError> {
+    reader.read_bool().ok_or(DecodeError::UnexpectedEof)
+}
+
+enum Transform {
+    /// size bits and the predictor modes of the blocks
+    Predictor(u32, Vec<u32>),
+    /// size bits and the multipliers of the blocks
+    Color(u32, Vec<u32>),
+    SubtractGreen,
+    /// color table
+    ColorIndexing(Vec<u32>),
+}
+
+impl Transform {
+    fn read(
+        reader: &mut BitStreamReader,
+        kind: u32,
+        xsize: usize,
+        ysize: usize,
+    ) -> Result<Self, DecodeError> {
+        match kind {
+            PREDICTOR_TRANSFORM | COLOR_TRANSFORM => {
+                let bits = _read_bits(reader, 3)? + MIN_SIZE_BITS;
+                let data = _read_image_data(
+                    reader,
+                    subsample_size(xsize, bits),
+                    subsample_size(ysize, bits),
+                    false,
+                )?;
+                if kind == PREDICTOR_TRANSFORM {
+                    Ok(Self::Predictor(bits, data))
+                } else {
+                    Ok(Self::Color(bits, data))
+                }
+            }
+            SUBTRACT_GREEN_TRANSFORM => Ok(Self::SubtractGreen),
+            _ => {
+                debug_assert_eq!(kind, COLOR_INDEXING_TRANSFORM);
+                let size = _read_bits(reader, 8)? as usize + 1;
+                let mut palette = _read_image_data(reader, size, 1, false)?;
+                // The colors are coded as the differences from the previous colors.
+                for i in 1..size {
+                    palette[i] = add_pixels(palette[i], palette[i - 1]);
+                }
+                Ok(Self::ColorIndexing(palette))
+            }
+        }
+    }
+}
+
+/// Reads an entropy-coded image.
+///
+/// Only the main image (`is_main`) may have meta prefix codes.
+fn _read_image_data(
+    reader: &mut BitStreamReader,
+    xsize: usize,
+    ysize: usize,
+    is_main: bool,
+) -> Result<Vec<u32>, DecodeError> {
+    let cache_bits = if _read_bool(reader)? {
+        let bits = _read_bits(reader, 4)?;
+        if !(1..=MAX_CACHE_BITS).contains(&bits) {
+            return Err(DecodeError::InvalidData);
+        }
+        bits
+    } else {
+        0
+    };
+
+    let (prefix_bits, meta_codes) = if is_main && _read_bool(reader)? {
+        let bits = _read_bits(reader, 3)? + MIN_SIZE_BITS;
+        let meta_codes = _read_image_data(
+            reader,
+            subsample_size(xsize, bits),
+            subsample_size(ysize, bits),
+            false,
+        )?
+        .into_iter()
+        .map(|v| ((v >> 8) & 0xffff) as usize)
+        .collect::<Vec<_>>();
+        (bits, meta_codes)
+    } else {
+        (0, vec![0])
+    };
+    let n_groups = meta_codes.iter().copied().max().unwrap_or_default() + 1;
+    let groups = (0..n_groups)
+        .map(|_| PrefixGroup::read(reader, cache_bits))
+        .collect::<Result<Vec<_>, _>>()?;
+    let prefix_xsize = subsample_size(xsize, prefix_bits);
+
+    let total = xsize * ysize;
+    let mut pixels = Vec::with_capacity(total);
+    let mut cache = vec![0u32; if cache_bits > 0 { 1 << cache_bits } else { 0 }];
+    let mut n_cached = 0;
+    while pixels.len() < total {
+        let index = pixels.len();
+        let group = if meta_codes.len() == 1 {
+            &groups[meta_codes[0]]
+        } else {
+            let (x, y) = (index % xsize, index / xsize);
+            &groups[meta_codes[(y >> prefix_bits) * prefix_xsize + (x >> prefix_bits)]]
+        };
+
+        let symbol = group.green.decode(reader)? as usize;
+        if symbol < NUM_LITERAL_CODES {
+            let red = group.red.decode(reader)?;
+            let blue = group.blue.decode(reader)?;
+            let alpha = group.alpha.decode(reader)?;
+            pixels.push((alpha << 24) | (red << 16) | ((symbol as u32) << 8) | blue);
+        } else if symbol < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+            let len = _read_prefix_value(reader, symbol - NUM_LITERAL_CODES)?;
+            let distance_symbol = group.distance.decode(reader)? as usize;
+            let distance_code = _read_prefix_value(reader, distance_symbol)?;
+            let distance = _plane_distance(distance_code, xsize);
+            if distance > index || len > total - index {
+                return Err(DecodeError::InvalidData);
+            }
+            for i in index..index + len {
+                pixels.push(pixels[i - distance]);
+            }
+        } else {
+            // All the preceding pixels are inserted into the color cache.
+            while n_cached < index {
+                let color = pixels[n_cached];
+                cache[cache_key(color, cache_bits)] = color;
+                n_cached += 1;
+            }
+            let key = symbol - NUM_LITERAL_CODES - NUM_LENGTH_CODES;
+            pixels.push(*cache.get(key).ok_or(DecodeError::InvalidData)?);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Reads the extra bits of a length or distance symbol, and returns the value.
+#[inline]
+fn _read_prefix_value(reader: &mut BitStreamReader, symbol: usize) -> Result<usize, DecodeError> {
+    if symbol < 4 {
+        return Ok(symbol + 1);
+    }
+    let extra_bits = (symbol as u32 - 2) >> 1;
+    let offset = (2 + (symbol & 1)) << extra_bits;
+    Ok(offset + _read_bits(reader, extra_bits)? as usize + 1)
+}
+
+/// Converts a distance code to the distance in pixels.
+#[inline]
+fn _plane_distance(distance_code: usize, xsize: usize) -> usize {
+    if distance_code > NUM_PLANE_CODES {
+        return distance_code - NUM_PLANE_CODES;
+    }
+    let (dx, dy) = DISTANCE_MAP[distance_code - 1];
+    (dx as isize + dy as isize * xsize as isize).max(1) as usize
+}
+
+/// Prefix codes of the green (with the lengths and the color cache), red, blue, alpha and distance symbols
+struct PrefixGroup {
+    green: PrefixCode,
+    red: PrefixCode,
+    blue: PrefixCode,
+    alpha: PrefixCode,
+    distance: PrefixCode,
+}
+
+impl PrefixGroup {
+    fn read(reader: &mut BitStreamReader, cache_bits: u32) -> Result<Self, DecodeError> {
+        let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+        Ok(Self {
+            green: PrefixCode::read(reader, NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size)?,
+            red: PrefixCode::read(reader, NUM_LITERAL_CODES)?,
+            blue: PrefixCode::read(reader, NUM_LITERAL_CODES)?,
+            alpha: PrefixCode::read(reader, NUM_LITERAL_CODES)?,
+            distance: PrefixCode::read(reader, NUM_DISTANCE_CODES)?,
+        })
+    }
+}
+
+/// Prefix code, which may consist of a single symbol with a zero-length code
+enum PrefixCode {
+    Single(u16),
+    Canonical(CanonicalPrefixDecoder),
+}
+
+impl PrefixCode {
+    fn read(reader: &mut BitStreamReader, alphabet_size: usize) -> Result<Self, DecodeError> {
+        let mut lengths = vec![0u8; alphabet_size];
+        if _read_bool(reader)? {
+            // simple code of one or two symbols
+            let n_symbols = _read_bits(reader, 1)? + 1;
+            let first_bits = if _read_bool(reader)? { 8 } else { 1 };
+            let mut symbol = _read_bits(reader, first_bits)?;
+            for i in 0..n_symbols {
+                if i > 0 {
+                    symbol = _read_bits(reader, 8)?;
+                }
+                *lengths
+                    .get_mut(symbol as usize)
+                    .ok_or(DecodeError::InvalidData)? = 1;
+            }
+            return Self::_from_lengths(&lengths);
+        }
+
+        // code lengths of the code length code
+        let codec = CodeLengthCodec::WEBP;
+        let n_codes = _read_bits(reader, 4)? as usize + 4;
+        let mut code_length_lengths = [0u8; 19];
+        for &symbol in codec.permutation_order.iter().take(n_codes) {
+            code_length_lengths[symbol as usize] = _read_bits(reader, 3)? as u8;
+        }
+        let code_length_code = Self::_from_lengths(&code_length_lengths)?;
+
+        let mut max_symbol = if _read_bool(reader)? {
+            let bits = 2 + 2 * _read_bits(reader, 3)?;
+            let max_symbol = 2 + _read_bits(reader, bits)? as usize;
+            if max_symbol > alphabet_size {
+                return Err(DecodeError::InvalidData);
+            }
+            max_symbol
+        } else {
+            alphabet_size
+        };
+
+        // code lengths of the symbols
+        let mut symbol = 0;
+        let mut prev_len = codec.initial_prev;
+        while symbol < alphabet_size && max_symbol > 0 {
+            max_symbol -= 1;
+            let code = code_length_code.decode(reader)? as u8;
+            if code <= codec.max_length {
+                lengths[symbol] = code;
+                symbol += 1;
+                if code != 0 {
+                    prev_len = code;
+                }
+                continue;
+            }
+            let (repeat_code, len) = if code == codec.repeat_prev.symbol {
+                (codec.repeat_prev, prev_len)
+            } else if code == codec.repeat_zero_short.symbol {
+                (codec.repeat_zero_short, 0)
+            } else {
+                (codec.repeat_zero_long, 0)
+            };
+            let repeat = repeat_code.min_len()
+                + _read_bits(reader, repeat_code.extra_bits.as_u32())? as usize;
+            if repeat > alphabet_size - symbol {
+                return Err(DecodeError::InvalidData);
+            }
+            lengths[symbol..symbol + repeat].fill(len);
+            symbol += repeat;
+        }
+        Self::_from_lengths(&lengths)
+    }
+
+    /// Creates a prefix code, which must be complete unless it has a single symbol.
+    fn _from_lengths(lengths: &[u8]) -> Result<Self, DecodeError> {
+        let mut symbols = lengths.iter().enumerate().filter(|v| *v.1 != 0);
+        match (symbols.next(), symbols.next()) {
+            (None, _) => return Err(DecodeError::InvalidData),
+            (Some((symbol, _)), None) => return Ok(Self::Single(symbol as u16)),
+            _ => {}
+        }
+        let space = lengths
+            .iter()
+            .filter(|&&v| v != 0)
+            .map(|&v| 1usize << (MAX_CODE_LEN - v))
+            .sum::<usize>();
+        if space != 1 << MAX_CODE_LEN {
+            return Err(DecodeError::InvalidData);
+        }
+        CanonicalPrefixDecoder::with_lengths(lengths, false).map(Self::Canonical)
+    }
+
+    #[inline]
+    fn decode(&self, reader: &mut BitStreamReader) -> Result<u32, DecodeError> {
+        match self {
+            Self::Single(symbol) => Ok(*symbol as u32),
+            Self::Canonical(decoder) => decoder.decode(reader),
+        }
+    }
+}
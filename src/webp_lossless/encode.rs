@@ -0,0 +1,512 @@
+//! VP8L encoder
+
+use super::transform::*;
+use super::*;
+use crate::entropy::prefix::{CanonicalPrefixCoder, PermutationFlavor};
+use crate::lz::find_distance_matches;
+use crate::num::{
+    VarLenInteger,
+    bits::{BitSize, BitStreamWriter, Write},
+    math,
+};
+use alloc::vec;
+use core::iter;
+use core::num::NonZero;
+
+/// Size bits of the blocks of the predictor transform
+const PREDICTOR_BITS: u32 = 4;
+
+const MIN_MATCH: usize = 3;
+
+/// The largest value of the length codes
+const MAX_MATCH: usize = 4096;
+
+/// The largest distance that can be coded with the plane codes excluded
+const MAX_DISTANCE: usize = (1 << 20) - NUM_PLANE_CODES;
+
+const HASH_BITS: u32 = 16;
+
+/// Maximum number of candidates searched in a hash chain
+const MAX_CHAIN_LEN: usize = 32;
+
+/// Encodes an image to a VP8L bitstream.
+///
+/// Images with at most 256 colors are coded with a palette,
+/// and the others with the subtract green and predictor transforms.
+pub fn encode(image: &Image) -> Result<Vec<u8>, EncodeError> {
+    let mut writer = BitStreamWriter::new();
+    writer.write(SIGNATURE);
+    writer.write(VarLenInteger::new_checked(BitSize::Bit14, image.width() as u32 - 1).unwrap());
+    writer.write(VarLenInteger::new_checked(BitSize::Bit14, image.height() as u32 - 1).unwrap());
+    writer.write(image.has_alpha());
+    writer.write(VarLenInteger::new_truncated(BitSize::Bit3, 0)); // version
+
+    let mut xsize = image.width();
+    let pixels = if let Some(palette) = _palette(image.pixels()) {
+        writer.write(true);
+        writer.write(VarLenInteger::new_truncated(
+            BitSize::Bit2,
+            COLOR_INDEXING_TRANSFORM,
+        ));
+        writer.write((palette.len() - 1) as u8);
+        let deltas = iter::once(palette[0])
+            .chain(palette.windows(2).map(|v| sub_pixels(v[1], v[0])))
+            .collect::<Vec<_>>();
+        _write_image_data(&mut writer, &deltas, palette.len(), false)?;
+
+        let indices = image
+            .pixels()
+            .iter()
+            .map(|v| palette.binary_search(v).map(|v| v as u8))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| EncodeError::InternalInconsistency)?;
+        let width_bits = color_indexing_width_bits(palette.len());
+        xsize = subsample_size(xsize, width_bits);
+        bundle_indices(&indices, image.width(), width_bits)
+    } else {
+        writer.write(true);
+        writer.write(VarLenInteger::new_truncated(
+            BitSize::Bit2,
+            SUBTRACT_GREEN_TRANSFORM,
+        ));
+        let pixels = image
+            .pixels()
+            .iter()
+            .map(|&v| subtract_green(v))
+            .collect::<Vec<_>>();
+
+        writer.write(true);
+        writer.write(VarLenInteger::new_truncated(
+            BitSize::Bit2,
+            PREDICTOR_TRANSFORM,
+        ));
+        writer.write(VarLenInteger::new_truncated(
+            BitSize::Bit3,
+            PREDICTOR_BITS - MIN_SIZE_BITS,
+        ));
+        let modes = _select_predictor_modes(&pixels, xsize);
+        _write_image_data(
+            &mut writer,
+            &modes,
+            subsample_size(xsize, PREDICTOR_BITS),
+            false,
+        )?;
+        (0..pixels.len())
+            .map(|index| {
+                let mode = predictor_mode(&modes, PREDICTOR_BITS, xsize, index);
+                sub_pixels(pixels[index], predict(&pixels, index, xsize, mode))
+            })
+            .collect()
+    };
+    writer.write(false); // end of the transforms
+
+    _write_image_data(&mut writer, &pixels, xsize, true)?;
+    Ok(writer.into_bytes())
+}
+
+/// Encodes an image to a WebP file, which is a RIFF container with a `VP8L` chunk.
+pub fn encode_file(image: &Image) -> Result<Vec<u8>, EncodeError> {
+    let bitstream = encode(image)?;
+    let chunk_size = u32::try_from(bitstream.len()).map_err(|_| EncodeError::InvalidInput)?;
+    let padding = bitstream.len() & 1;
+    let riff_size = 12 + chunk_size + padding as u32;
+
+    let mut output = Vec::with_capacity(20 + bitstream.len() + padding);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&riff_size.to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(b"VP8L");
+    output.extend_from_slice(&chunk_size.to_le_bytes());
+    output.extend_from_slice(&bitstream);
+    output.resize(output.len() + padding, 0);
+    Ok(output)
+}
+
+/// Returns the sorted colors of the image, or `None` if it has more than 256 colors.
+fn _palette(pixels: &[u32]) -> Option<Vec<u32>> {
+    let mut palette = Vec::new();
+    let mut prev = None;
+    for &pixel in pixels {
+        if prev == Some(pixel) {
+            continue;
+        }
+        prev = Some(pixel);
+        if let Err(index) = palette.binary_search(&pixel) {
+            if palette.len() == 256 {
+                return None;
+            }
+            palette.insert(index, pixel);
+        }
+    }
+    Some(palette)
+}
+
+/// Selects the predictor mode of each block that minimizes the magnitude of the residuals.
+fn _select_predictor_modes(pixels: &[u32], xsize: usize) -> Vec<u32> {
+    let ysize = pixels.len() / xsize;
+    let block_size = 1 << PREDICTOR_BITS;
+    let mut modes = Vec::new();
+    for block_y in (0..ysize).step_by(block_size) {
+        for block_x in (0..xsize).step_by(block_size) {
+            let (mode, _) = (0..NUM_PREDICTOR_MODES)
+                .map(|mode| {
+                    let mut cost = 0;
+                    for y in block_y..(block_y + block_size).min(ysize) {
+                        for x in block_x..(block_x + block_size).min(xsize) {
+                            let index = y * xsize + x;
+                            let residual =
+                                sub_pixels(pixels[index], predict(pixels, index, xsize, mode));
+                            cost += residual
+                                .to_le_bytes()
+                                .iter()
+                                .map(|&v| (v as i8).unsigned_abs() as usize)
+                                .sum::<usize>();
+                        }
+                    }
+                    (mode, cost)
+                })
+                .min_by_key(|v| v.1)
+                .unwrap();
+            modes.push(0xff00_0000 | (mode << 8));
+        }
+    }
+    modes
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Symbol {
+    Literal(u32),
+    CacheIndex(usize),
+    Copy { len: usize, distance_code: usize },
+}
+
+/// Writes an entropy-coded image with a single group of prefix codes.
+fn _write_image_data(
+    writer: &mut BitStreamWriter,
+    pixels: &[u32],
+    xsize: usize,
+    is_main: bool,
+) -> Result<(), EncodeError> {
+    let symbols = _find_backward_references(pixels, xsize);
+    let (cache_bits, symbols) = (0..MAX_CACHE_BITS)
+        .map(|cache_bits| {
+            let symbols = _apply_color_cache(pixels, &symbols, cache_bits);
+            let cost = _histograms(&symbols, cache_bits)
+                .iter()
+                .map(|v| _estimate_bits(v))
+                .sum::<f64>();
+            (cache_bits, symbols, cost)
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|v| (v.0, v.1))
+        .unwrap();
+
+    if cache_bits > 0 {
+        writer.write(true);
+        writer.write(VarLenInteger::new_checked(BitSize::Bit4, cache_bits).unwrap());
+    } else {
+        writer.write(false);
+    }
+    if is_main {
+        writer.write(false); // meta prefix codes
+    }
+
+    let histograms = _histograms(&symbols, cache_bits);
+    let mut codes = Vec::with_capacity(histograms.len());
+    for histogram in histograms.iter() {
+        codes.push(_write_prefix_code(writer, histogram)?);
+    }
+    let [green, red, blue, alpha, distance] = &codes[..] else {
+        unreachable!()
+    };
+    for &symbol in symbols.iter() {
+        match symbol {
+            Symbol::Literal(pixel) => {
+                _write_symbol(writer, green, ((pixel >> 8) & 0xff) as usize);
+                _write_symbol(writer, red, ((pixel >> 16) & 0xff) as usize);
+                _write_symbol(writer, blue, (pixel & 0xff) as usize);
+                _write_symbol(writer, alpha, (pixel >> 24) as usize);
+            }
+            Symbol::CacheIndex(key) => {
+                _write_symbol(writer, green, NUM_LITERAL_CODES + NUM_LENGTH_CODES + key);
+            }
+            Symbol::Copy { len, distance_code } => {
+                let (symbol, extra) = _prefix_encode(len);
+                _write_symbol(writer, green, NUM_LITERAL_CODES + symbol);
+                if let Some(extra) = extra {
+                    writer.write(extra);
+                }
+                let (symbol, extra) = _prefix_encode(distance_code);
+                _write_symbol(writer, distance, symbol);
+                if let Some(extra) = extra {
+                    writer.write(extra);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn _write_symbol(writer: &mut BitStreamWriter, code: &[Option<VarLenInteger>], symbol: usize) {
+    // A code of a single symbol has no bits.
+    if let Some(code) = code[symbol] {
+        writer.write(code);
+    }
+}
+
+/// Returns the symbol and the extra bits of a length or a distance code.
+#[inline]
+fn _prefix_encode(value: usize) -> (usize, Option<VarLenInteger>) {
+    let value = value as u32 - 1;
+    if value < 4 {
+        return (value as usize, None);
+    }
+    let highest_bit = 31 - value.leading_zeros();
+    let second_bit = (value >> (highest_bit - 1)) & 1;
+    let extra_bits = BitSize::new(highest_bit as u8 - 1).unwrap();
+    (
+        (2 * highest_bit + second_bit) as usize,
+        Some(VarLenInteger::new_truncated(extra_bits, value)),
+    )
+}
+
+/// Returns the distance code of a distance, using the plane codes if possible.
+fn _distance_code(distance: usize, xsize: usize) -> usize {
+    let dy = distance / xsize;
+    for dy in [dy, dy + 1] {
+        let dx = distance as isize - (dy * xsize) as isize;
+        if let Some(index) = DISTANCE_MAP
+            .iter()
+            .position(|&v| (v.0 as isize, v.1 as usize) == (dx, dy))
+        {
+            return index + 1;
+        }
+    }
+    distance + NUM_PLANE_CODES
+}
+
+/// Hash chains of the positions of the pixels
+struct HashChain {
+    head: Vec<u32>,
+    chain: Vec<u32>,
+}
+
+impl HashChain {
+    const NIL: u32 = u32::MAX;
+
+    fn new(len: usize) -> Self {
+        Self {
+            head: vec![Self::NIL; 1 << HASH_BITS],
+            chain: vec![Self::NIL; len],
+        }
+    }
+
+    #[inline]
+    fn hash(pixels: &[u32], index: usize) -> usize {
+        let value =
+            pixels[index] ^ pixels[index + 1].rotate_left(11) ^ pixels[index + 2].rotate_left(22);
+        (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    #[inline]
+    fn insert(&mut self, pixels: &[u32], index: usize) {
+        if index + MIN_MATCH <= pixels.len() {
+            let key = Self::hash(pixels, index);
+            self.chain[index] = self.head[key];
+            self.head[key] = index as u32;
+        }
+    }
+
+    /// Returns the previous positions with the same hash, the nearest first.
+    #[inline]
+    fn candidates(&self, pixels: &[u32], index: usize) -> impl Iterator<Item = usize> {
+        let head = self.head[Self::hash(pixels, index)];
+        iter::successors((head != Self::NIL).then_some(head), |&v| {
+            let next = self.chain[v as usize];
+            (next != Self::NIL).then_some(next)
+        })
+        .take(MAX_CHAIN_LEN)
+        .map(|v| v as usize)
+    }
+}
+
+/// Finds backward references with hash chains, and returns the pixels as literals and copies.
+fn _find_backward_references(pixels: &[u32], xsize: usize) -> Vec<Symbol> {
+    let mut hash_chain = HashChain::new(pixels.len());
+    let mut symbols = Vec::new();
+    let mut cursor = 0;
+    while cursor < pixels.len() {
+        let matches = if cursor + MIN_MATCH <= pixels.len() {
+            // the previous pixel and the pixel above are tried first
+            let distances = [1, xsize]
+                .into_iter()
+                .filter(|&v| v <= cursor)
+                .chain(hash_chain.candidates(pixels, cursor).map(|v| cursor - v))
+                .filter(|&v| v <= MAX_DISTANCE)
+                .filter_map(NonZero::new);
+            let limit = (cursor + MAX_MATCH).min(pixels.len());
+            find_distance_matches(&pixels[..limit], cursor, MIN_MATCH, MAX_MATCH, 0, distances)
+        } else {
+            None
+        };
+
+        match matches {
+            Some(matches) => {
+                let len = matches.len.get();
+                symbols.push(Symbol::Copy {
+                    len,
+                    distance_code: _distance_code(matches.distance.get(), xsize),
+                });
+                for index in cursor..cursor + len {
+                    hash_chain.insert(pixels, index);
+                }
+                cursor += len;
+            }
+            None => {
+                symbols.push(Symbol::Literal(pixels[cursor]));
+                hash_chain.insert(pixels, cursor);
+                cursor += 1;
+            }
+        }
+    }
+    symbols
+}
+
+/// Replaces the literals found in the color cache with the cache indices.
+fn _apply_color_cache(pixels: &[u32], symbols: &[Symbol], cache_bits: u32) -> Vec<Symbol> {
+    if cache_bits == 0 {
+        return symbols.to_vec();
+    }
+    let mut cache = vec![0u32; 1 << cache_bits];
+    let mut cursor = 0;
+    let mut output = Vec::with_capacity(symbols.len());
+    for &symbol in symbols {
+        match symbol {
+            Symbol::Literal(pixel) => {
+                let key = cache_key(pixel, cache_bits);
+                if cache[key] == pixel {
+                    output.push(Symbol::CacheIndex(key));
+                } else {
+                    cache[key] = pixel;
+                    output.push(symbol);
+                }
+                cursor += 1;
+            }
+            Symbol::CacheIndex(_) => unreachable!(),
+            Symbol::Copy { len, .. } => {
+                for &pixel in &pixels[cursor..cursor + len] {
+                    cache[cache_key(pixel, cache_bits)] = pixel;
+                }
+                output.push(symbol);
+                cursor += len;
+            }
+        }
+    }
+    output
+}
+
+/// Returns the histograms of the green, red, blue, alpha and distance symbols.
+fn _histograms(symbols: &[Symbol], cache_bits: u32) -> [Vec<usize>; 5] {
+    let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+    let mut green = vec![0; NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size];
+    let mut red = vec![0; NUM_LITERAL_CODES];
+    let mut blue = vec![0; NUM_LITERAL_CODES];
+    let mut alpha = vec![0; NUM_LITERAL_CODES];
+    let mut distance = vec![0; NUM_DISTANCE_CODES];
+    for &symbol in symbols {
+        match symbol {
+            Symbol::Literal(pixel) => {
+                green[((pixel >> 8) & 0xff) as usize] += 1;
+                red[((pixel >> 16) & 0xff) as usize] += 1;
+                blue[(pixel & 0xff) as usize] += 1;
+                alpha[(pixel >> 24) as usize] += 1;
+            }
+            Symbol::CacheIndex(key) => green[NUM_LITERAL_CODES + NUM_LENGTH_CODES + key] += 1,
+            Symbol::Copy { len, distance_code } => {
+                green[NUM_LITERAL_CODES + _prefix_encode(len).0] += 1;
+                distance[_prefix_encode(distance_code).0] += 1;
+            }
+        }
+    }
+    [green, red, blue, alpha, distance]
+}
+
+/// Estimates the size of the symbols in bits from their entropy.
+fn _estimate_bits(histogram: &[usize]) -> f64 {
+    let total = histogram.iter().sum::<usize>() as f64;
+    histogram
+        .iter()
+        .filter(|&&v| v > 0)
+        .map(|&v| v as f64 * math::log2(total / v as f64))
+        .sum()
+}
+
+/// Writes a prefix code for the histogram, and returns the codes of the symbols.
+///
+/// The codes are `None` if the prefix code has a single symbol, which is coded with no bits.
+fn _write_prefix_code(
+    writer: &mut BitStreamWriter,
+    histogram: &[usize],
+) -> Result<Vec<Option<VarLenInteger>>, EncodeError> {
+    let used = histogram
+        .iter()
+        .enumerate()
+        .filter_map(|(symbol, &v)| (v > 0).then_some(symbol))
+        .collect::<Vec<_>>();
+
+    if used.len() <= 2 && used.iter().all(|&v| v < NUM_LITERAL_CODES) {
+        // simple code
+        let first = used.first().copied().unwrap_or_default();
+        writer.write(true);
+        writer.write(used.len() == 2);
+        if first < 2 {
+            writer.write(false);
+            writer.write(first == 1);
+        } else {
+            writer.write(true);
+            writer.write(first as u8);
+        }
+        let mut codes = vec![None; histogram.len()];
+        if let Some(&second) = used.get(1) {
+            writer.write(second as u8);
+            codes[first] = Some(VarLenInteger::with_bool(false));
+            codes[second] = Some(VarLenInteger::with_bool(true));
+        }
+        return Ok(codes);
+    }
+
+    let codes = CanonicalPrefixCoder::make_prefix_table(histogram, BitSize::Bit15, histogram.len());
+    let lengths = codes
+        .iter()
+        .map(|v| v.map(|v| v.size().as_u8()).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let prefix_tables =
+        CanonicalPrefixCoder::encode_prefix_tables(&[&lengths], PermutationFlavor::WebP)
+            .map_err(|_| EncodeError::EntropyError)?;
+    writer.write(false);
+    writer.write(prefix_tables.hclen);
+    writer.write(prefix_tables.prefix_table.as_slice());
+    writer.write(false); // max_symbol
+    if prefix_tables
+        .prefix_table
+        .iter()
+        .filter(|v| v.value() != 0)
+        .count()
+        == 1
+    {
+        // The code length code has a single symbol, only the extra bits of the repeat codes are written.
+        for &item in prefix_tables.intermediate_tables.iter().flatten() {
+            if item.size() != BitSize::OCTET {
+                writer.write(item);
+            }
+        }
+    } else {
+        writer.write(prefix_tables.content.as_slice());
+    }
+
+    if used.len() == 1 {
+        return Ok(vec![None; histogram.len()]);
+    }
+    Ok(codes.iter().map(|v| v.map(|v| v.reversed())).collect())
+}
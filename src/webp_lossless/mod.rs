@@ -0,0 +1,265 @@
+//! WebP lossless bitstream (VP8L)
+//!
+//! [`decode`] accepts a bare VP8L bitstream or a RIFF container with a `VP8L` chunk.
+//! [`encode`] writes a bare bitstream, and [`encode_file`] wraps it in a RIFF container.
+//!
+//! See also: <https://www.rfc-editor.org/rfc/rfc9649>
+
+use crate::*;
+
+#[cfg(test)]
+mod tests;
+
+mod decode;
+mod encode;
+mod transform;
+pub use decode::*;
+pub use encode::*;
+
+/// The first byte of a VP8L bitstream
+const SIGNATURE: u8 = 0x2f;
+
+/// Maximum width and height of an image
+pub const MAX_DIMENSION: usize = 0x4000;
+
+const PREDICTOR_TRANSFORM: u32 = 0;
+const COLOR_TRANSFORM: u32 = 1;
+const SUBTRACT_GREEN_TRANSFORM: u32 = 2;
+const COLOR_INDEXING_TRANSFORM: u32 = 3;
+
+/// Valid range of the size bits of the predictor and color transforms and of the meta prefix codes
+const MIN_SIZE_BITS: u32 = 2;
+
+/// Maximum number of bits of the color cache index
+const MAX_CACHE_BITS: u32 = 11;
+
+/// Number of symbols of the green alphabet before the color cache symbols
+const NUM_LITERAL_CODES: usize = 256;
+const NUM_LENGTH_CODES: usize = 24;
+const NUM_DISTANCE_CODES: usize = 40;
+
+/// Maximum length of the prefix codes
+const MAX_CODE_LEN: u8 = 15;
+
+/// Number of distance codes that refer to nearby pixels in two dimensions
+const NUM_PLANE_CODES: usize = 120;
+
+/// Offsets `(dx, dy)` of the distance codes `1..=120`
+const DISTANCE_MAP: [(i8, i8); NUM_PLANE_CODES] = [
+    (0, 1),
+    (1, 0),
+    (1, 1),
+    (-1, 1),
+    (0, 2),
+    (2, 0),
+    (1, 2),
+    (-1, 2),
+    (2, 1),
+    (-2, 1),
+    (2, 2),
+    (-2, 2),
+    (0, 3),
+    (3, 0),
+    (1, 3),
+    (-1, 3),
+    (3, 1),
+    (-3, 1),
+    (2, 3),
+    (-2, 3),
+    (3, 2),
+    (-3, 2),
+    (0, 4),
+    (4, 0),
+    (1, 4),
+    (-1, 4),
+    (4, 1),
+    (-4, 1),
+    (3, 3),
+    (-3, 3),
+    (2, 4),
+    (-2, 4),
+    (4, 2),
+    (-4, 2),
+    (0, 5),
+    (3, 4),
+    (-3, 4),
+    (4, 3),
+    (-4, 3),
+    (5, 0),
+    (1, 5),
+    (-1, 5),
+    (5, 1),
+    (-5, 1),
+    (2, 5),
+    (-2, 5),
+    (5, 2),
+    (-5, 2),
+    (4, 4),
+    (-4, 4),
+    (3, 5),
+    (-3, 5),
+    (5, 3),
+    (-5, 3),
+    (0, 6),
+    (6, 0),
+    (1, 6),
+    (-1, 6),
+    (6, 1),
+    (-6, 1),
+    (2, 6),
+    (-2, 6),
+    (6, 2),
+    (-6, 2),
+    (4, 5),
+    (-4, 5),
+    (5, 4),
+    (-5, 4),
+    (3, 6),
+    (-3, 6),
+    (6, 3),
+    (-6, 3),
+    (0, 7),
+    (7, 0),
+    (1, 7),
+    (-1, 7),
+    (5, 5),
+    (-5, 5),
+    (7, 1),
+    (-7, 1),
+    (4, 6),
+    (-4, 6),
+    (6, 4),
+    (-6, 4),
+    (2, 7),
+    (-2, 7),
+    (7, 2),
+    (-7, 2),
+    (3, 7),
+    (-3, 7),
+    (7, 3),
+    (-7, 3),
+    (5, 6),
+    (-5, 6),
+    (6, 5),
+    (-6, 5),
+    (8, 0),
+    (4, 7),
+    (-4, 7),
+    (7, 4),
+    (-7, 4),
+    (8, 1),
+    (8, 2),
+    (6, 6),
+    (-6, 6),
+    (8, 3),
+    (5, 7),
+    (-5, 7),
+    (7, 5),
+    (-7, 5),
+    (8, 4),
+    (6, 7),
+    (-6, 7),
+    (7, 6),
+    (-7, 6),
+    (8, 5),
+    (7, 7),
+    (-7, 7),
+    (8, 6),
+    (8, 7),
+];
+
+/// An image with 32-bit ARGB pixels in row-major order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Image {
+    /// Creates an image from ARGB pixels.
+    ///
+    /// Returns `None` if the dimensions are out of `1..=MAX_DIMENSION` or do not match the number of pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<u32>) -> Option<Self> {
+        ((1..=MAX_DIMENSION).contains(&width)
+            && (1..=MAX_DIMENSION).contains(&height)
+            && pixels.len() == width * height)
+            .then_some(Self {
+                width,
+                height,
+                pixels,
+            })
+    }
+
+    /// Creates an image from RGBA bytes.
+    pub fn from_rgba(width: usize, height: usize, rgba: &[u8]) -> Option<Self> {
+        if !rgba.len().is_multiple_of(4) {
+            return None;
+        }
+        let pixels = rgba
+            .chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[3], v[0], v[1], v[2]]))
+            .collect();
+        Self::new(width, height, pixels)
+    }
+
+    #[inline]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the ARGB pixels.
+    #[inline]
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn into_pixels(self) -> Vec<u32> {
+        self.pixels
+    }
+
+    /// Returns the pixels as RGBA bytes.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&v| {
+                let [a, r, g, b] = v.to_be_bytes();
+                [r, g, b, a]
+            })
+            .collect()
+    }
+
+    /// Returns `true` if any pixel is not opaque.
+    pub fn has_alpha(&self) -> bool {
+        self.pixels.iter().any(|&v| v >> 24 != 0xff)
+    }
+}
+
+/// Number of blocks of `1 << bits` pixels needed to cover `size` pixels
+#[inline]
+const fn subsample_size(size: usize, bits: u32) -> usize {
+    size.div_ceil(1 << bits)
+}
+
+/// Index of the color cache for a color
+#[inline]
+const fn cache_key(color: u32, bits: u32) -> usize {
+    (0x1e35_a7bd_u32.wrapping_mul(color) >> (32 - bits)) as usize
+}
+
+/// Returns the size bits of the pixel bundling of a color indexing transform.
+#[inline]
+const fn color_indexing_width_bits(color_table_size: usize) -> u32 {
+    match color_table_size {
+        0..=2 => 3,
+        3..=4 => 2,
+        5..=16 => 1,
+        _ => 0,
+    }
+}
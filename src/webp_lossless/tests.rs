@@ -0,0 +1,171 @@
+use super::*;
+use base64::prelude::*;
+
+/// A gradient of 37x23 pixels encoded by libwebp,
+/// which has the subtract green, predictor and color transforms and a color cache
+const GRADIENT_WEBP_BASE64: &[u8] = b"UklGRmoAAABXRUJQVlA4TF4AAAAvJIAFAE0ARNL+4CtE9D9tIRBI8scdYoZRGPD+RwMAx6C2bSPH/d3+E3/O352EBCmkKZm0DfWvd3sTCtu2QTrm3RFKSipAR0oNPxhj4JxJLQRzAKATbhmWUGOD32gJ";
+
+/// A pattern of 4 colors of 33x33 pixels encoded by libwebp, which has a color indexing transform
+const PALETTE_WEBP_BASE64: &[u8] = b"UklGRmYAAABXRUJQVlA4TFkAAAAvIAAIEB8gEEjyJxphRIEAYcL/oAcCgSSI/QWXEJAQnstyAdhbtVATyVbz/6dDCiqQEF1GCahACiXdqwh9RP+FBImN6ozZ5aSGTiA6k3EbxGeyK4PUmf58xQA=";
+
+/// The image of `mixed_image` encoded by libwebp, which has two groups of meta prefix codes
+const MIXED_WEBP_BASE64: &[u8] = b"UklGRrIEAABXRUJQVlA4TKUEAAAvX8APAAlFbRsxUr8tf8TjBoKI/k+ALNV2WOOjBUAQ/tsFRPQ/9QwFAQtB1r827kkQEROgIUBg5p38C1iRbLtW+gYxxH/koBET+HrhnHXWfQIgRrJt2lqv5tm2bdv6tp3Mz+ZH9VOBGAYAwSadbdu2bdu2bfP1YOC2kSJ395j5viBBkmzTtqKuz/V9ts1r433bNuY/invWWudErAnAjmzbtC2p99oxXNvWs9+nbUcwcNtIUWfphpbhvkAYABMWbDhw4cFHgBARYiRIkSFHgRIVajRo0aHHgBETZixYsWHHgRPOuOCKG+544IkX3vjgix82CCGqqsMR2KQdUg+CXx5CXx7C696EEPImVYhs2Qy2O5c1EN2c/pBrILbjlAffcg3El062qoDg14GNxDUZwB53MySHyKyANOBARFv48AFSp8ujA0kB7Ig5kn1IDxuBGPMBMZBo6w9GZiRNapDYj+gpbvl8IDtRsAaRlAXUuakgN1CxDVEowyVPM+TPxfFZF7EFn9KDwk0WUxXVywqHBMXTsXsiVgBuRLkccwBKZ0uzLRJwSu5FrkIUocGHjfLJWtcSDXMnbIFFJWjzYqMyX2bZAbH3OA+RhxY/M1SP790dFmpOWPeN6bKgD7Vp94Iz1lI7BigUYaAP9cO24ZGozc/E2nYB9Hmw0RjOYUhz9liruMWHjebRUgx54ICxXI0O1lzwUkFr0p1g1Fp1wTDpgxfQvslKaq5ot2e0jgMbnYM34gGARA1AwSld1nFZAyQ2dDkk6F7oLtHSTX36FHwMDkBv8KS+oSo6CfYkdk0ABPfAqg4fNvoHr5vYFeCOXQHx+6/+3sfgJqXkKOWqfgPDqYo6JZXlEjVZ26b6MDp9YtTJfXZMeZuQo9Wk3jPPoQ/jmyxlmayopk2q1wEO715MPn2Zn5FX7yWBvBuyIw2yLCJp8aXHxwzT449SaqoCZtLojDSV0lVjIPPjAcxmd3SLXZEcw3Uz4vxzAOYnn7t19qknlc7IeEe2y4GNxaRd1CZOb3UsSakol3R4fGB5k7WQV8/vgMwFFo2JvdgHD2D18RL6FrGvbgmHDWIKhEFBhxivHhEH4AKsb1JIWp1Q7RM7sT8YmyMkSqZBn7hnHrOWXIeo7oF+A9vBE2/M6kuXOOueA5BUJ+JD6MPuSEab9XwbsO6hKeuYegAOwH7+l0GwU3PHaMe+twCIdvQH4zD8O+1CBK14DEDBiI8Zjlce2o8BqLgDIDAUisWs3fr766nDaYLMBPHp0i+kDucPd9aP3ARpXABQcgdAzUNh/hSAdS+Fhm+F4o/mOPaVAxuXmywloQtAwQyAJU8B2PaOBxvXiaS+UOGxiKMjFC96Ch7A7WzagYjejMyx5jVIypoAsKEP9+lyAnegqA/Aery91XfRU/3BeByc4yGtMAL1FhjT1YT2B74DMqYcPvC8yWKy7mj75wC4sPE6uNsomFj0iv0P+BIkHfvDA3iffFA8BYvWfQQEHtK6fwHL3gLS+oPxGfzzPQckTKmbiGMAm74AKl4AkmZ8PvCdL7Pipblc59Bu/+vDb4BgCJv2/AZs+Q5Y8xmw5H3w///730YAAA==";
+
+const PALETTE: [[u8; 4]; 4] = [
+    [255, 0, 0, 255],
+    [0, 128, 255, 255],
+    [20, 200, 20, 128],
+    [0, 0, 0, 0],
+];
+
+/// Creates an image from a function that returns the RGBA color of each pixel.
+fn make_image(width: usize, height: usize, f: impl Fn(usize, usize) -> [u8; 4]) -> Image {
+    let mut rgba = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            rgba.extend_from_slice(&f(x, y));
+        }
+    }
+    Image::from_rgba(width, height, &rgba).unwrap()
+}
+
+fn gradient_image() -> Image {
+    make_image(37, 23, |x, y| {
+        [
+            (x * 255 / 36) as u8,
+            (y * 255 / 22) as u8,
+            (x + y) as u8,
+            255,
+        ]
+    })
+}
+
+fn palette_image() -> Image {
+    make_image(33, 33, |x, y| PALETTE[(x / 3 + y / 5) % 4])
+}
+
+/// The left half is noisy and the right half is flat.
+fn mixed_image() -> Image {
+    make_image(96, 64, |x, y| {
+        if x < 48 {
+            [(x * x + y * 3) as u8, (x * y) as u8, (y * y) as u8, 255]
+        } else {
+            [(x / 4 * 40) as u8, (y / 4 * 40) as u8, 0, 255]
+        }
+    })
+}
+
+#[test]
+fn webp_lossless_decode_reference() {
+    for (encoded, expected) in [
+        (GRADIENT_WEBP_BASE64, gradient_image()),
+        (PALETTE_WEBP_BASE64, palette_image()),
+        (MIXED_WEBP_BASE64, mixed_image()),
+    ] {
+        let encoded = BASE64_STANDARD.decode(encoded).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), expected);
+
+        // the bitstream without the RIFF container
+        assert_eq!(&encoded[12..16], b"VP8L");
+        assert_eq!(decode(&encoded[20..]).unwrap(), expected);
+
+        assert_eq!(
+            decode(&encoded[..encoded.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode(&encoded[20..encoded.len() / 2]),
+            Err(DecodeError::UnexpectedEof)
+        );
+        let n_pixels = expected.width() * expected.height();
+        assert_eq!(decode_with_limit(&encoded, n_pixels), Ok(expected));
+        assert_eq!(
+            decode_with_limit(&encoded, n_pixels - 1),
+            Err(DecodeError::LimitExceeded)
+        );
+    }
+}
+
+#[test]
+fn webp_lossless_roundtrip() {
+    use rand::RngCore;
+    let mut noise = vec![0; 64 * 48 * 4];
+    rand::rng().fill_bytes(&mut noise);
+
+    for image in [
+        make_image(1, 1, |_, _| [1, 2, 3, 4]),
+        make_image(1, 100, |_, y| [y as u8, 0, 0, 255]),
+        make_image(100, 1, |x, _| [0, x as u8, 0, 255]),
+        gradient_image(),
+        palette_image(),
+        mixed_image(),
+        Image::from_rgba(64, 48, &noise).unwrap(),
+        // opaque noise
+        make_image(64, 48, |x, y| {
+            let v = &noise[(y * 64 + x) * 4..];
+            [v[0], v[1], v[2], 255]
+        }),
+        // 2, 3, 16, 256 and 257 colors
+        make_image(50, 40, |x, y| [0, 0, 0, ((x + y) % 2) as u8 * 255]),
+        make_image(31, 17, |x, y| [((x * y) % 3) as u8, 0, 0, 255]),
+        make_image(70, 30, |x, y| [0, ((x / 2 + y) % 16) as u8 * 16, 0, 255]),
+        make_image(64, 64, |x, y| [x as u8, (y % 4) as u8, 0, 255]),
+        make_image(257, 3, |x, y| [x as u8, (x >> 8) as u8, y as u8, 255]),
+        // alpha
+        make_image(120, 77, |x, y| {
+            let alpha = if (x / 8 + y / 8) % 3 != 0 { x * 7 } else { 0 };
+            [x as u8, y as u8, (x * y) as u8, alpha as u8]
+        }),
+        make_image(300, 200, |_, _| [12, 34, 56, 78]),
+    ] {
+        let encoded = encode(&image).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), image);
+
+        let file = encode_file(&image).unwrap();
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[8..16], b"WEBPVP8L");
+        assert_eq!(file.len() % 2, 0);
+        assert_eq!(
+            u32::from_le_bytes(file[4..8].try_into().unwrap()) as usize,
+            file.len() - 8
+        );
+        assert_eq!(decode(&file).unwrap(), image);
+    }
+
+    // a flat image consists of a few backward references
+    let image = make_image(300, 200, |_, _| [12, 34, 56, 78]);
+    assert!(encode(&image).unwrap().len() < 64);
+}
+
+#[test]
+fn webp_lossless_invalid() {
+    assert_eq!(decode(&[]), Err(DecodeError::UnexpectedEof));
+    assert_eq!(
+        decode(&[0x2e, 0, 0, 0, 0]),
+        Err(DecodeError::UnsupportedFormat)
+    );
+
+    // version 1
+    let mut encoded = encode(&gradient_image()).unwrap();
+    encoded[4] |= 0x20;
+    assert_eq!(decode(&encoded), Err(DecodeError::UnsupportedFormat));
+
+    // lossy
+    let mut file = b"RIFF\x0c\0\0\0WEBPVP8 \0\0\0\0".to_vec();
+    assert_eq!(decode(&file), Err(DecodeError::UnsupportedFormat));
+    file[4] = 0x20;
+    assert_eq!(decode(&file), Err(DecodeError::UnexpectedEof));
+
+    assert!(Image::new(0, 1, Vec::new()).is_none());
+    assert!(Image::new(2, 2, vec![0; 3]).is_none());
+    assert!(Image::new(MAX_DIMENSION + 1, 1, vec![0; MAX_DIMENSION + 1]).is_none());
+    assert!(Image::from_rgba(1, 1, &[0; 5]).is_none());
+
+    // corrupted data must not cause a panic
+    let encoded = BASE64_STANDARD.decode(MIXED_WEBP_BASE64).unwrap();
+    for i in 20..encoded.len() {
+        let mut corrupted = encoded.clone();
+        corrupted[i] ^= 1 << (i % 8);
+        let _ = decode_with_limit(&corrupted, 0x10000);
+    }
+}
@@ -0,0 +1,199 @@
+//! Transforms of the ARGB pixels
+
+use super::*;
+
+/// Number of the predictor modes, the modes 14 and 15 behave as the mode 0
+pub(super) const NUM_PREDICTOR_MODES: u32 = 14;
+
+/// Prediction of the top-left pixel, and of the mode 0
+const BLACK: u32 = 0xff00_0000;
+
+/// Adds each channel modulo 256.
+#[inline]
+pub(super) const fn add_pixels(a: u32, b: u32) -> u32 {
+    let ag = (a & 0xff00_ff00).wrapping_add(b & 0xff00_ff00);
+    let rb = (a & 0x00ff_00ff).wrapping_add(b & 0x00ff_00ff);
+    (ag & 0xff00_ff00) | (rb & 0x00ff_00ff)
+}
+
+/// Subtracts each channel modulo 256.
+#[inline]
+pub(super) const fn sub_pixels(a: u32, b: u32) -> u32 {
+    let ag = 0x00ff_00ffu32
+        .wrapping_add(a & 0xff00_ff00)
+        .wrapping_sub(b & 0xff00_ff00);
+    let rb = 0xff00_ff00u32
+        .wrapping_add(a & 0x00ff_00ff)
+        .wrapping_sub(b & 0x00ff_00ff);
+    (ag & 0xff00_ff00) | (rb & 0x00ff_00ff)
+}
+
+#[inline]
+const fn _average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefe_fefe) >> 1) + (a & b)
+}
+
+#[inline]
+fn _channels(pixel: u32) -> [i32; 4] {
+    pixel.to_be_bytes().map(|v| v as i32)
+}
+
+#[inline]
+fn _from_channels(channels: [i32; 4]) -> u32 {
+    u32::from_be_bytes(channels.map(|v| v.clamp(0, 255) as u8))
+}
+
+fn _select(left: u32, top: u32, top_left: u32) -> u32 {
+    let (l, t, tl) = (_channels(left), _channels(top), _channels(top_left));
+    let mut dist_l = 0;
+    let mut dist_t = 0;
+    for i in 0..4 {
+        let estimate = l[i] + t[i] - tl[i];
+        dist_l += (estimate - l[i]).abs();
+        dist_t += (estimate - t[i]).abs();
+    }
+    if dist_l < dist_t { left } else { top }
+}
+
+fn _clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    let (a, b, c) = (_channels(a), _channels(b), _channels(c));
+    _from_channels([0, 1, 2, 3].map(|i| a[i] + b[i] - c[i]))
+}
+
+fn _clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    let (a, b) = (_channels(a), _channels(b));
+    _from_channels([0, 1, 2, 3].map(|i| a[i] + (a[i] - b[i]) / 2))
+}
+
+/// Predicts the pixel at `index` from the pixels already decoded.
+#[inline]
+pub(super) fn predict(pixels: &[u32], index: usize, xsize: usize, mode: u32) -> u32 {
+    let x = index % xsize;
+    if index < xsize {
+        return if x == 0 { BLACK } else { pixels[index - 1] };
+    }
+    if x == 0 {
+        return pixels[index - xsize];
+    }
+    let left = || pixels[index - 1];
+    let top = || pixels[index - xsize];
+    // The rightmost pixel refers to the leftmost pixel of the current row.
+    let top_right = || pixels[index - xsize + 1];
+    let top_left = || pixels[index - xsize - 1];
+    match mode {
+        1 => left(),
+        2 => top(),
+        3 => top_right(),
+        4 => top_left(),
+        5 => _average2(_average2(left(), top_right()), top()),
+        6 => _average2(left(), top_left()),
+        7 => _average2(left(), top()),
+        8 => _average2(top_left(), top()),
+        9 => _average2(top(), top_right()),
+        10 => _average2(_average2(left(), top_left()), _average2(top(), top_right())),
+        11 => _select(left(), top(), top_left()),
+        12 => _clamp_add_subtract_full(left(), top(), top_left()),
+        13 => _clamp_add_subtract_half(_average2(left(), top()), top_left()),
+        _ => BLACK,
+    }
+}
+
+/// Returns the value of the sub-image of a transform for a pixel.
+#[inline]
+fn _block_value(data: &[u32], bits: u32, xsize: usize, index: usize) -> u32 {
+    let (x, y) = (index % xsize, index / xsize);
+    data[(y >> bits) * subsample_size(xsize, bits) + (x >> bits)]
+}
+
+/// Returns the predictor mode of a pixel.
+#[inline]
+pub(super) fn predictor_mode(modes: &[u32], bits: u32, xsize: usize, index: usize) -> u32 {
+    (_block_value(modes, bits, xsize, index) >> 8) & 0xf
+}
+
+/// Reverts the predictor transform in place.
+pub(super) fn inverse_predictor(pixels: &mut [u32], xsize: usize, bits: u32, modes: &[u32]) {
+    for index in 0..pixels.len() {
+        let mode = predictor_mode(modes, bits, xsize, index);
+        pixels[index] = add_pixels(pixels[index], predict(pixels, index, xsize, mode));
+    }
+}
+
+#[inline]
+const fn _color_transform_delta(t: u32, c: u32) -> u32 {
+    ((t as u8 as i8 as i32 * c as u8 as i8 as i32) >> 5) as u32
+}
+
+/// Reverts the color transform in place.
+pub(super) fn inverse_color_transform(
+    pixels: &mut [u32],
+    xsize: usize,
+    bits: u32,
+    elements: &[u32],
+) {
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let element = _block_value(elements, bits, xsize, index);
+        let (green_to_red, green_to_blue, red_to_blue) = (element, element >> 8, element >> 16);
+        let green = *pixel >> 8;
+        let red = (*pixel >> 16).wrapping_add(_color_transform_delta(green_to_red, green)) & 0xff;
+        let blue = pixel
+            .wrapping_add(_color_transform_delta(green_to_blue, green))
+            .wrapping_add(_color_transform_delta(red_to_blue, red))
+            & 0xff;
+        *pixel = (*pixel & 0xff00_ff00) | (red << 16) | blue;
+    }
+}
+
+/// Adds the green channel to the red and blue channels.
+#[inline]
+pub(super) const fn add_green(pixel: u32) -> u32 {
+    let green = (pixel >> 8) & 0xff;
+    add_pixels(pixel, (green << 16) | green)
+}
+
+/// Subtracts the green channel from the red and blue channels.
+#[inline]
+pub(super) const fn subtract_green(pixel: u32) -> u32 {
+    let green = (pixel >> 8) & 0xff;
+    sub_pixels(pixel, (green << 16) | green)
+}
+
+/// Expands the bundled indices of the color indexing transform to the colors of the palette.
+///
+/// Indices out of the palette become transparent black.
+pub(super) fn inverse_color_indexing(
+    packed: &[u32],
+    xsize: usize,
+    ysize: usize,
+    palette: &[u32],
+) -> Vec<u32> {
+    let width_bits = color_indexing_width_bits(palette.len());
+    let packed_xsize = subsample_size(xsize, width_bits);
+    let bits_per_pixel = 8 >> width_bits;
+    let mask = (1 << bits_per_pixel) - 1;
+    let mut output = Vec::with_capacity(xsize * ysize);
+    for row in packed.chunks_exact(packed_xsize).take(ysize) {
+        for x in 0..xsize {
+            let shift = (x & ((1 << width_bits) - 1)) * bits_per_pixel;
+            let index = (row[x >> width_bits] >> (8 + shift)) & mask;
+            output.push(palette.get(index as usize).copied().unwrap_or_default());
+        }
+    }
+    output
+}
+
+/// Bundles the palette indices of a row-major image into the green channel of pixels.
+pub(super) fn bundle_indices(indices: &[u8], xsize: usize, width_bits: u32) -> Vec<u32> {
+    let bits_per_pixel = 8 >> width_bits;
+    let mut output = Vec::new();
+    for row in indices.chunks_exact(xsize) {
+        for chunk in row.chunks(1 << width_bits) {
+            let mut bundle = 0;
+            for (i, &index) in chunk.iter().enumerate() {
+                bundle |= (index as u32) << (i * bits_per_pixel);
+            }
+            output.push(BLACK | (bundle << 8));
+        }
+    }
+    output
+}
@@ -0,0 +1,20 @@
+//! CRC-32 used by zip and gzip
+//!
+//! The bits are reflected, unlike the CRC-32 of bzip2.
+
+use crate::hash::crc32::Reflected;
+
+type Crc = Reflected<0xEDB8_8320>;
+
+/// Updates the CRC with the data.
+///
+/// Starts with [`checksum`] of an empty slice, which is `0`.
+pub fn update(crc: u32, data: &[u8]) -> u32 {
+    Crc::update(crc, data)
+}
+
+/// CRC-32 implementation
+#[inline]
+pub fn checksum(data: &[u8]) -> u32 {
+    Crc::checksum(data)
+}
@@ -0,0 +1,76 @@
+//! ZIP archive format
+//!
+//! Reads the entries stored or compressed with deflate through the central directory,
+//! and writes archives of such entries.
+//! Multi-disk archives, ZIP64 and encryption are not supported.
+//!
+//! See also: <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+//!
+//! ```
+//! use compress::deflate::CompressionLevel;
+//! use compress::zip::{ZipArchive, ZipWriter};
+//!
+//! let mut writer = ZipWriter::new();
+//! writer.add_file("hello.txt", b"Hello, world!", CompressionLevel::Default).unwrap();
+//! writer.add_file("empty.txt", b"", CompressionLevel::Default).unwrap();
+//! let archive = writer.finish().unwrap();
+//!
+//! let archive = ZipArchive::new(&archive).unwrap();
+//! assert_eq!(archive.entries().len(), 2);
+//! let entry = archive.find("hello.txt").unwrap();
+//! assert_eq!(archive.extract(entry).unwrap(), b"Hello, world!");
+//! ```
+
+use crate::*;
+
+#[cfg(test)]
+mod tests;
+
+pub mod crc32;
+
+mod read;
+mod write;
+pub use read::*;
+pub use write::*;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+
+/// The entry is encrypted
+const FLAG_ENCRYPTED: u16 = 0x0001;
+/// The name is encoded in UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+
+/// Compression method of an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipMethod {
+    Stored,
+    Deflate,
+    /// Other methods, which cannot be extracted
+    Other(u16),
+}
+
+impl ZipMethod {
+    #[inline]
+    pub const fn from_u16(value: u16) -> Self {
+        match value {
+            0 => Self::Stored,
+            8 => Self::Deflate,
+            _ => Self::Other(value),
+        }
+    }
+
+    #[inline]
+    pub const fn as_u16(&self) -> u16 {
+        match *self {
+            Self::Stored => 0,
+            Self::Deflate => 8,
+            Self::Other(value) => value,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! ZIP archive reader
+
+use super::*;
+use crate::deflate::{
+    DEFAULT_MAX_OUTPUT_SIZE, InflateLimits, InflateOptions, inflate_to_vec_with_options,
+};
+use core::ops::Range;
+
+/// Entry of the central directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry<'a> {
+    /// Name of the entry, which ends with `/` for a directory
+    pub name: &'a [u8],
+    pub method: ZipMethod,
+    /// General purpose bit flags
+    pub flags: u16,
+    /// CRC-32 of the uncompressed data
+    pub crc32: u32,
+    pub compressed_size: usize,
+    pub uncompressed_size: usize,
+    /// Offset of the local header in the archive
+    pub local_header_offset: usize,
+}
+
+impl ZipEntry<'_> {
+    /// Returns the name if it is valid UTF-8.
+    #[inline]
+    pub fn name_str(&self) -> Option<&str> {
+        core::str::from_utf8(self.name).ok()
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+
+    #[inline]
+    pub const fn is_encrypted(&self) -> bool {
+        (self.flags & FLAG_ENCRYPTED) != 0
+    }
+}
+
+/// ZIP archive in a slice
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry<'a>>,
+}
+
+impl<'a> ZipArchive<'a> {
+    /// Reads the central directory of the archive.
+    ///
+    /// Returns `DecodeError::UnsupportedFormat` for multi-disk and ZIP64 archives.
+    pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let eocd = _find_end_of_central_directory(data)?;
+        let disk = _read_u16(data, eocd + 4)?;
+        let cd_disk = _read_u16(data, eocd + 6)?;
+        let disk_entries = _read_u16(data, eocd + 8)?;
+        let n_entries = _read_u16(data, eocd + 10)?;
+        let cd_size = _read_u32(data, eocd + 12)?;
+        let cd_offset = _read_u32(data, eocd + 16)?;
+        if disk != 0 || cd_disk != 0 || disk_entries != n_entries {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        if n_entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+            // ZIP64
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        let cd_offset = cd_offset as usize;
+        let cd_end = cd_offset + cd_size as usize;
+        if cd_end > eocd {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let mut entries = Vec::new();
+        entries
+            .try_reserve_exact(n_entries as usize)
+            .map_err(|_| DecodeError::OutOfMemory)?;
+        let mut cursor = cd_offset;
+        for _ in 0..n_entries {
+            if cursor + CENTRAL_HEADER_SIZE > cd_end {
+                return Err(DecodeError::InvalidData);
+            }
+            if _read_u32(data, cursor)? != CENTRAL_HEADER_SIGNATURE {
+                return Err(DecodeError::InvalidData);
+            }
+            let name_len = _read_u16(data, cursor + 28)? as usize;
+            let extra_len = _read_u16(data, cursor + 30)? as usize;
+            let comment_len = _read_u16(data, cursor + 32)? as usize;
+            let name_start = cursor + CENTRAL_HEADER_SIZE;
+            let next = name_start + name_len + extra_len + comment_len;
+            if next > cd_end {
+                return Err(DecodeError::InvalidData);
+            }
+            let compressed_size = _read_u32(data, cursor + 20)?;
+            let uncompressed_size = _read_u32(data, cursor + 24)?;
+            let local_header_offset = _read_u32(data, cursor + 42)?;
+            if compressed_size == u32::MAX
+                || uncompressed_size == u32::MAX
+                || local_header_offset == u32::MAX
+            {
+                // ZIP64
+                return Err(DecodeError::UnsupportedFormat);
+            }
+            entries.push(ZipEntry {
+                name: &data[name_start..name_start + name_len],
+                method: ZipMethod::from_u16(_read_u16(data, cursor + 10)?),
+                flags: _read_u16(data, cursor + 8)?,
+                crc32: _read_u32(data, cursor + 16)?,
+                compressed_size: compressed_size as usize,
+                uncompressed_size: uncompressed_size as usize,
+                local_header_offset: local_header_offset as usize,
+            });
+            cursor = next;
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    /// Returns the entries in the order of the central directory.
+    #[inline]
+    pub fn entries(&self) -> &[ZipEntry<'a>] {
+        &self.entries
+    }
+
+    /// Returns the first entry with the name.
+    pub fn find(&self, name: &str) -> Option<&ZipEntry<'a>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name.as_bytes())
+    }
+
+    /// Returns the compressed data of the entry, located by its local header.
+    pub fn raw_data(&self, entry: &ZipEntry) -> Result<&'a [u8], DecodeError> {
+        let range = self._data_range(entry)?;
+        Ok(&self.data[range])
+    }
+
+    /// Extracts the entry, up to [`DEFAULT_MAX_OUTPUT_SIZE`] bytes.
+    #[inline]
+    pub fn extract(&self, entry: &ZipEntry) -> Result<Vec<u8>, DecodeError> {
+        self.extract_with_limit(entry, DEFAULT_MAX_OUTPUT_SIZE)
+    }
+
+    /// Extracts the entry and validates its CRC-32.
+    ///
+    /// Returns `DecodeError::LimitExceeded` if the uncompressed size exceeds `max_size`,
+    /// `DecodeError::UnsupportedFormat` for the other methods and encrypted entries,
+    /// and `DecodeError::ChecksumMismatch` if the CRC-32 does not match.
+    pub fn extract_with_limit(
+        &self,
+        entry: &ZipEntry,
+        max_size: usize,
+    ) -> Result<Vec<u8>, DecodeError> {
+        if entry.is_encrypted() {
+            return Err(DecodeError::UnsupportedFormat);
+        }
+        if entry.uncompressed_size > max_size {
+            return Err(DecodeError::LimitExceeded);
+        }
+        let data = self.raw_data(entry)?;
+        let output = match entry.method {
+            ZipMethod::Stored => {
+                if entry.compressed_size != entry.uncompressed_size {
+                    return Err(DecodeError::InvalidData);
+                }
+                data.to_vec()
+            }
+            ZipMethod::Deflate => {
+                // the sizes in the central directory bound the output,
+                // and the entries are raw deflate streams even if they look like zlib
+                let output = inflate_to_vec_with_options(
+                    data,
+                    InflateLimits::DEFAULT.max_output(entry.uncompressed_size),
+                    InflateOptions::new().raw(),
+                )
+                .map_err(|error| match error {
+                    DecodeError::LimitExceeded => DecodeError::InvalidData,
+                    error => error,
+                })?;
+                if output.len() != entry.uncompressed_size {
+                    return Err(DecodeError::InvalidData);
+                }
+                output
+            }
+            ZipMethod::Other(_) => return Err(DecodeError::UnsupportedFormat),
+        };
+        if crc32::checksum(&output) != entry.crc32 {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        Ok(output)
+    }
+
+    fn _data_range(&self, entry: &ZipEntry) -> Result<Range<usize>, DecodeError> {
+        let offset = entry.local_header_offset;
+        if _read_u32(self.data, offset)? != LOCAL_HEADER_SIGNATURE {
+            return Err(DecodeError::InvalidData);
+        }
+        // The sizes in the local header may be zero if they follow the data,
+        // so only those of the central directory are used.
+        let name_len = _read_u16(self.data, offset + 26)? as usize;
+        let extra_len = _read_u16(self.data, offset + 28)? as usize;
+        let start = offset + LOCAL_HEADER_SIZE + name_len + extra_len;
+        let end = start + entry.compressed_size;
+        if end > self.data.len() {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        Ok(start..end)
+    }
+}
+
+/// Returns the offset of the end of central directory record, which is followed by a comment of up to 64KB.
+fn _find_end_of_central_directory(data: &[u8]) -> Result<usize, DecodeError> {
+    let last = data
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&offset| {
+            _read_u32(data, offset) == Ok(END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+                && _read_u16(data, offset + 20)
+                    .is_ok_and(|comment_len| offset + comment_len as usize <= last)
+        })
+        .ok_or(DecodeError::InvalidData)
+}
+
+#[inline]
+fn _read_u16(data: &[u8], offset: usize) -> Result<u16, DecodeError> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+#[inline]
+fn _read_u32(data: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
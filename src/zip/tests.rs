@@ -0,0 +1,123 @@
+use super::*;
+use crate::deflate::CompressionLevel;
+use base64::prelude::*;
+
+/// Archive written by Python's zipfile to a pipe, whose sizes follow the data in data descriptors:
+/// `lorem.txt` compressed with deflate, the directory `dir/` and `dir/stored.bin` stored, with a comment
+const PYTHON_ZIP_BASE64: &[u8] = b"UEsDBBQACAAIAIMYIlgAAAAAAAAAAAAAAAAJAAAAbG9yZW0udHh0NZDBcUMxCETvv4otwOMqkluuKYAg7DAjCVkCj8sPyk9uQsCy+z5sSoOOFQ3Fqk0sdVATv4CtL2EXjwkqOnSx9jukajaXlFyAaKxmBS5t5LJ21qIluiMclb5SHuKntKDRvROo6iPoenw6pGtLbTTdj2eW1C54hC50Wz6jQF4yWZ1crSNqpcZ2Ku8hXbov/UrqyGEIpfGWnuwMkKf8erxtSQoX6Ix0cmbVjiljyrf0IjOD58fTaow8J2knk0LWErDW+k8oAwVucVdy9G0Ig2YWMa/H+4tluMTGmAyMmYRzjmNoId8bmWJM0yJ9U9yk8ihHHbRzw243ZSUUWTJ3t1ndNmgD0sSx/rhGux4/UEsHCKAKG6gLAQAAvgEAAFBLAwQUAAgAAACDGCJYAAAAAAAAAAAAAAAABAAAAGRpci9QSwcIAAAAAAAAAAAAAAAAUEsDBBQACAAAAIMYIlgAAAAAAAAAAAAAAAAOAAAAZGlyL3N0b3JlZC5iaW4AAQIDBAUGBwgJCgsMDQ4PUEsHCIjizs4QAAAAEAAAAFBLAQIUAxQACAAIAIMYIligChuoCwEAAL4BAAAJAAAAAAAAAAAAAACAAQAAAABsb3JlbS50eHRQSwECFAMUAAgAAACDGCJYAAAAAAAAAAAAAAAABAAAAAAAAAAAAAAAgAFCAQAAZGlyL1BLAQIUAxQACAAAAIMYIliI4s7OEAAAABAAAAAOAAAAAAAAAAAAAACAAXQBAABkaXIvc3RvcmVkLmJpblBLBQYAAAAAAwADAKUAAADAAQAADAB0ZXN0IGFyY2hpdmU=";
+
+/// Archive of `hello.txt`, whose raw deflate stream starts with a non-final stored block with a padding bit set,
+/// so that its first byte `08` looks like a zlib header
+const RAW_08_ZIP_BASE64: &[u8] = b"UEsDBBQAAAAIAAAAIQCGphA2DAAAAAUAAAAJAAAAaGVsbG8udHh0CAUA+v9oZWxsbwMAUEsBAhQAFAAAAAgAAAAhAIamEDYMAAAABQAAAAkAAAAAAAAAAAAAAAAAAAAAAGhlbGxvLnR4dFBLBQYAAAAAAQABADcAAAAzAAAAAAA=";
+
+#[test]
+fn zip_crc32() {
+    assert_eq!(crc32::checksum(b""), 0);
+    assert_eq!(crc32::checksum(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32::checksum(&[0; 32]), 0x190A_55AD);
+    assert_eq!(crc32::checksum(&[0xff; 32]), 0xFF6C_AB0B);
+    assert_eq!(
+        crc32::update(crc32::checksum(b"1234"), b"56789"),
+        0xCBF4_3926
+    );
+}
+
+#[test]
+fn zip_read() {
+    let data = BASE64_STANDARD.decode(PYTHON_ZIP_BASE64).unwrap();
+    let archive = ZipArchive::new(&data).unwrap();
+    let names = archive
+        .entries()
+        .iter()
+        .map(|entry| entry.name_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["lorem.txt", "dir/", "dir/stored.bin"]);
+
+    let lorem = archive.find("lorem.txt").unwrap();
+    assert_eq!(lorem.method, ZipMethod::Deflate);
+    assert!(!lorem.is_dir());
+    let text = archive.extract(lorem).unwrap();
+    assert_eq!(text.len(), 446);
+    assert!(text.starts_with(b"Lorem ipsum dolor sit amet"));
+
+    let dir = archive.find("dir/").unwrap();
+    assert!(dir.is_dir());
+    assert_eq!(archive.extract(dir).unwrap(), b"");
+
+    let stored = archive.find("dir/stored.bin").unwrap();
+    assert_eq!(stored.method, ZipMethod::Stored);
+    assert_eq!(
+        archive.extract(stored).unwrap(),
+        (0..16).collect::<Vec<u8>>()
+    );
+    assert_eq!(
+        archive.extract_with_limit(stored, 15),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert!(archive.find("missing").is_none());
+
+    // corrupted data
+    let offset = archive.raw_data(stored).unwrap().as_ptr() as usize - data.as_ptr() as usize;
+    let mut corrupted = data.clone();
+    corrupted[offset + 3] ^= 1;
+    let archive = ZipArchive::new(&corrupted).unwrap();
+    assert_eq!(
+        archive.extract(&archive.entries()[2]),
+        Err(DecodeError::ChecksumMismatch)
+    );
+
+    // the deflate entries are always raw
+    let data = BASE64_STANDARD.decode(RAW_08_ZIP_BASE64).unwrap();
+    let archive = ZipArchive::new(&data).unwrap();
+    let entry = archive.find("hello.txt").unwrap();
+    assert_eq!(archive.raw_data(entry).unwrap()[0], 0x08);
+    assert_eq!(archive.extract(entry).unwrap(), b"hello");
+    let data = BASE64_STANDARD.decode(PYTHON_ZIP_BASE64).unwrap();
+
+    // truncated archives
+    assert!(ZipArchive::new(&data[..data.len() - 13]).is_err());
+    assert!(ZipArchive::new(&data[data.len() - 40..]).is_err());
+    assert_eq!(ZipArchive::new(&[]).err(), Some(DecodeError::UnexpectedEof));
+}
+
+#[test]
+fn zip_roundtrip() {
+    let files: [(&str, Vec<u8>); 5] = [
+        ("empty", Vec::new()),
+        ("random.bin", random_alphabet(0, 255, 0x1234)),
+        ("fib.txt", fib_str(b'a', b'b', 0x5000)),
+        ("dir/ab.txt", random_ab(b'a', b'b', 0x2000)),
+        ("日本語.txt", "日本語".repeat(100).into_bytes()),
+    ];
+    for level in [
+        CompressionLevel::Level0,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        let mut writer = ZipWriter::new();
+        for (name, data) in &files {
+            writer.add_file(name, data, level).unwrap();
+        }
+        let data = writer.finish().unwrap();
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.entries().len(), files.len());
+        for ((name, data), entry) in files.iter().zip(archive.entries()) {
+            assert_eq!(entry.name_str(), Some(*name));
+            assert_eq!(entry.flags & FLAG_UTF8 != 0, !name.is_ascii());
+            let expected_method =
+                if level.is_store_only() || name == &"random.bin" || data.is_empty() {
+                    ZipMethod::Stored
+                } else {
+                    ZipMethod::Deflate
+                };
+            assert_eq!(entry.method, expected_method, "{name}");
+            assert_eq!(archive.extract(entry).unwrap(), *data);
+        }
+    }
+
+    let archive = ZipWriter::new().finish().unwrap();
+    assert_eq!(archive.len(), END_OF_CENTRAL_DIRECTORY_SIZE);
+    assert!(ZipArchive::new(&archive).unwrap().entries().is_empty());
+}
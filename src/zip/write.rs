@@ -0,0 +1,139 @@
+//! ZIP archive writer
+
+use super::*;
+use crate::deflate::{CompressionLevel, deflate};
+
+/// Version needed to extract a stored entry (1.0)
+const VERSION_STORED: u16 = 10;
+/// Version needed to extract an entry compressed with deflate (2.0), which is also the version made by
+const VERSION_DEFLATE: u16 = 20;
+
+/// MS-DOS date of the entries, which is 1980-01-01
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Writer of a ZIP archive into a vector
+///
+/// The entries are written with a fixed date, so the same entries make the same archive.
+#[derive(Default)]
+pub struct ZipWriter {
+    output: Vec<u8>,
+    central_directory: Vec<u8>,
+    n_entries: usize,
+}
+
+impl ZipWriter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file compressed with deflate at the level,
+    /// or stored if the level is `Level0` or the data does not shrink.
+    ///
+    /// Returns `EncodeError::InvalidInput` if the archive would need ZIP64,
+    /// that is, 65535 or more entries or sizes of 4GB or more.
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        level: CompressionLevel,
+    ) -> Result<(), EncodeError> {
+        let compressed = if level.is_store_only() || data.is_empty() {
+            None
+        } else {
+            Some(deflate(data, level, None)?).filter(|v| v.len() < data.len())
+        };
+        let (method, body) = match &compressed {
+            Some(compressed) => (ZipMethod::Deflate, compressed.as_slice()),
+            None => (ZipMethod::Stored, data),
+        };
+
+        let offset = self.output.len();
+        if name.len() > u16::MAX as usize
+            || self.n_entries + 1 >= u16::MAX as usize
+            || data.len() >= u32::MAX as usize
+            || offset >= u32::MAX as usize
+        {
+            return Err(EncodeError::InvalidInput);
+        }
+        let header = HeaderFields {
+            version: match method {
+                ZipMethod::Deflate => VERSION_DEFLATE,
+                _ => VERSION_STORED,
+            },
+            flags: if name.is_ascii() { 0 } else { FLAG_UTF8 },
+            method,
+            crc32: crc32::checksum(data),
+            compressed_size: body.len() as u32,
+            uncompressed_size: data.len() as u32,
+            name_len: name.len() as u16,
+        };
+
+        self.output
+            .extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        header.write(&mut self.output);
+        self.output.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        self.output.extend_from_slice(name.as_bytes());
+        self.output.extend_from_slice(body);
+
+        let cd = &mut self.central_directory;
+        cd.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        cd.extend_from_slice(&VERSION_DEFLATE.to_le_bytes()); // version made by
+        header.write(cd);
+        cd.extend_from_slice(&[0; 2]); // extra field length
+        cd.extend_from_slice(&[0; 2]); // comment length
+        cd.extend_from_slice(&[0; 2]); // disk number
+        cd.extend_from_slice(&[0; 2]); // internal attributes
+        cd.extend_from_slice(&[0; 4]); // external attributes
+        cd.extend_from_slice(&(offset as u32).to_le_bytes());
+        cd.extend_from_slice(name.as_bytes());
+        self.n_entries += 1;
+
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the archive.
+    pub fn finish(mut self) -> Result<Vec<u8>, EncodeError> {
+        let cd_offset = self.output.len();
+        let cd_size = self.central_directory.len();
+        if cd_offset + cd_size >= u32::MAX as usize {
+            return Err(EncodeError::InvalidInput);
+        }
+        let n_entries = (self.n_entries as u16).to_le_bytes();
+        let output = &mut self.output;
+        output.extend_from_slice(&self.central_directory);
+        output.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        output.extend_from_slice(&[0; 4]); // disk numbers
+        output.extend_from_slice(&n_entries);
+        output.extend_from_slice(&n_entries);
+        output.extend_from_slice(&(cd_size as u32).to_le_bytes());
+        output.extend_from_slice(&(cd_offset as u32).to_le_bytes());
+        output.extend_from_slice(&[0; 2]); // comment length
+        Ok(self.output)
+    }
+}
+
+/// Fields shared by the local header and the central directory, from the version needed to the name length
+struct HeaderFields {
+    version: u16,
+    flags: u16,
+    method: ZipMethod,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    name_len: u16,
+}
+
+impl HeaderFields {
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.version.to_le_bytes());
+        output.extend_from_slice(&self.flags.to_le_bytes());
+        output.extend_from_slice(&self.method.as_u16().to_le_bytes());
+        output.extend_from_slice(&0u16.to_le_bytes()); // time
+        output.extend_from_slice(&DOS_DATE.to_le_bytes());
+        output.extend_from_slice(&self.crc32.to_le_bytes());
+        output.extend_from_slice(&self.compressed_size.to_le_bytes());
+        output.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        output.extend_from_slice(&self.name_len.to_le_bytes());
+    }
+}